@@ -17,23 +17,42 @@ pub struct BspLimit {
     pub min_size: BoundingBox,
 }
 
-pub fn build_bsp_tree(meshes: Vec<Mesh>, limit: BspLimit) -> BspNode {
-    build::split(BspNode::leaf(meshes), 0, &limit)
+/// Builds a BSP tree. Vertices within `plane_epsilon` of a dividing plane are considered
+/// to be on it; [`DEFAULT_PLANE_EPSILON`] suits meshes modeled in meters.
+/// Fails if the vertex list of a mesh that has to be split is malformed.
+pub fn build_bsp_tree(
+    meshes: Vec<Mesh>,
+    limit: BspLimit,
+    plane_epsilon: f32,
+) -> Result<BspNode, VertexListError> {
+    build::split(
+        BspNode::leaf(meshes),
+        0,
+        &limit,
+        plane_epsilon,
+        &mut SplitScratch::new(),
+    )
 }
 
 mod build {
     use super::*;
     use lvl_math::{Plane, Vec3};
 
-    pub fn split(bsp_node: BspNode, depth: usize, limit: &BspLimit) -> BspNode {
+    pub fn split(
+        bsp_node: BspNode,
+        depth: usize,
+        limit: &BspLimit,
+        plane_epsilon: f32,
+        scratch: &mut SplitScratch,
+    ) -> Result<BspNode, VertexListError> {
         let leaf = match bsp_node {
             BspNode::Leaf(leaf) => leaf,
-            BspNode::Internal(internal) => return BspNode::Internal(internal),
+            BspNode::Internal(internal) => return Ok(BspNode::Internal(internal)),
         };
 
         if let Some(max_depth) = limit.max_depth {
             if max_depth <= depth {
-                return BspNode::Leaf(leaf);
+                return Ok(BspNode::Leaf(leaf));
             }
         }
 
@@ -45,12 +64,12 @@ mod build {
                 .sum::<usize>()
                 < min_triangle_count
             {
-                return BspNode::Leaf(leaf);
+                return Ok(BspNode::Leaf(leaf));
             }
         }
 
         if limit.min_size.contains_bounding_box(&leaf.bounding_box) {
-            return BspNode::Leaf(leaf);
+            return Ok(BspNode::Leaf(leaf));
         }
 
         let dividing_plane = make_dividing_plane(&leaf);
@@ -58,7 +77,8 @@ mod build {
         let mut back_meshes = Vec::new();
 
         for mesh in leaf.meshes {
-            let splitted = mesh.split_by_plane(dividing_plane);
+            let splitted =
+                mesh.split_by_plane_with_scratch(dividing_plane, plane_epsilon, scratch)?;
 
             if !splitted.front.is_empty() {
                 front_meshes.push(splitted.front);
@@ -72,14 +92,14 @@ mod build {
         let front = BspNode::leaf(front_meshes);
         let back = BspNode::leaf(back_meshes);
 
-        let front = split(front, depth + 1, limit);
-        let back = split(back, depth + 1, limit);
+        let front = split(front, depth + 1, limit, plane_epsilon, scratch)?;
+        let back = split(back, depth + 1, limit, plane_epsilon, scratch)?;
 
-        BspNode::Internal(BspNodeInternal {
+        Ok(BspNode::Internal(BspNodeInternal {
             plane: dividing_plane,
             front: Some(Box::new(front)),
             back: Some(Box::new(back)),
-        })
+        }))
     }
 
     #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -1,26 +1,47 @@
 use super::{
     BoundingBox, BoundingBoxPlaneSide, SurfaceShading, Triangle, TrianglePlaneSide, VertexList,
+    VertexListError,
 };
 use lvl_math::{Plane, Vec3};
 use std::{collections::HashMap, num::NonZeroU32};
 
+macro_rules! transfer_vertex {
+    ($index:expr, $vertex_map:expr, $from:expr, $to:expr) => {{
+        *$vertex_map
+            .entry($index)
+            .or_insert_with(|| super::VertexList::transfer_vertex($index, &$from, &mut $to))
+    }};
+}
+
 macro_rules! transfer_triangle {
     ($triangle:expr, $vertex_map:expr, $from:expr, $to:expr) => {{
         let indices = [
-            *$vertex_map.entry($triangle.indices[0]).or_insert_with(|| {
-                super::VertexList::transfer_vertex($triangle.indices[0], &$from, &mut $to)
-            }),
-            *$vertex_map.entry($triangle.indices[1]).or_insert_with(|| {
-                super::VertexList::transfer_vertex($triangle.indices[1], &$from, &mut $to)
-            }),
-            *$vertex_map.entry($triangle.indices[2]).or_insert_with(|| {
-                super::VertexList::transfer_vertex($triangle.indices[2], &$from, &mut $to)
-            }),
+            transfer_vertex!($triangle.indices[0], $vertex_map, $from, $to),
+            transfer_vertex!($triangle.indices[1], $vertex_map, $from, $to),
+            transfer_vertex!($triangle.indices[2], $vertex_map, $from, $to),
         ];
         super::Triangle { indices }
     }};
 }
 
+/// Reusable buffers for [`Mesh::split_by_plane_with_scratch`].
+/// The buffers keep their capacity between splits, so recursive splitting does not allocate
+/// intermediate data once they are large enough.
+#[derive(Debug, Default)]
+pub struct SplitScratch {
+    sides: Vec<TrianglePlaneSide>,
+    front_vertex_map: HashMap<usize, usize>,
+    back_vertex_map: HashMap<usize, usize>,
+    front_new_texcoords: [Vec<[f32; 2]>; 2],
+    back_new_texcoords: [Vec<[f32; 2]>; 2],
+}
+
+impl SplitScratch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct SplittedMesh {
     pub front: Mesh,
@@ -57,7 +78,29 @@ impl Mesh {
         self.triangles.is_empty()
     }
 
-    pub fn split_by_plane(self, plane: Plane) -> SplittedMesh {
+    /// Splits the mesh into the parts in front of and behind the given plane.
+    /// Every produced triangle keeps the winding order of the triangle it was cut from.
+    /// Vertices within `epsilon` of the plane are considered to be on it, and triangles
+    /// lying on the plane go to the front part; see [`Triangle::plane_side`].
+    /// Fails if the vertex list is malformed; see [`VertexList::validate`].
+    pub fn split_by_plane(
+        self,
+        plane: Plane,
+        epsilon: f32,
+    ) -> Result<SplittedMesh, VertexListError> {
+        self.split_by_plane_with_scratch(plane, epsilon, &mut SplitScratch::new())
+    }
+
+    /// Same as [`Mesh::split_by_plane`], but reuses the buffers of the given scratch.
+    /// Use this when splitting many meshes in a row, e.g. while building a BSP tree.
+    pub fn split_by_plane_with_scratch(
+        self,
+        plane: Plane,
+        epsilon: f32,
+        scratch: &mut SplitScratch,
+    ) -> Result<SplittedMesh, VertexListError> {
+        self.vertex_list.validate()?;
+
         match self.bounding_box.plane_side(plane) {
             BoundingBoxPlaneSide::Front => {
                 let back = Self::new(
@@ -67,7 +110,7 @@ impl Mesh {
                     Vec::new(),
                 );
 
-                return SplittedMesh { front: self, back };
+                return Ok(SplittedMesh { front: self, back });
             }
             BoundingBoxPlaneSide::Back => {
                 let front = Self::new(
@@ -77,23 +120,67 @@ impl Mesh {
                     Vec::new(),
                 );
 
-                return SplittedMesh { front, back: self };
+                return Ok(SplittedMesh { front, back: self });
             }
             BoundingBoxPlaneSide::Spanning => {}
         }
 
-        let mut front_vertex_list = VertexList::empty(self.vertex_list.surface_shading);
-        let mut back_vertex_list = VertexList::empty(self.vertex_list.surface_shading);
+        let SplitScratch {
+            sides,
+            front_vertex_map,
+            back_vertex_map,
+            front_new_texcoords,
+            back_new_texcoords,
+        } = scratch;
 
-        let mut front_vertex_map = HashMap::new();
-        let mut back_vertex_map = HashMap::new();
+        sides.clear();
+        front_vertex_map.clear();
+        back_vertex_map.clear();
 
-        let mut front_triangles = Vec::new();
-        let mut back_triangles = Vec::new();
+        // count the output first, so that the output buffers are allocated only once
+        let mut front_triangle_count = 0;
+        let mut back_triangle_count = 0;
+        let mut spanning_triangle_count = 0;
 
-        for triangle in self.triangles {
-            match triangle.plane_side(&self.vertex_list, plane) {
-                TrianglePlaneSide::Front => {
+        for triangle in &self.triangles {
+            let side = triangle.plane_side(&self.vertex_list, plane, epsilon);
+
+            match &side {
+                TrianglePlaneSide::Front | TrianglePlaneSide::OnPlane => {
+                    front_triangle_count += 1;
+                }
+                TrianglePlaneSide::Back => {
+                    back_triangle_count += 1;
+                }
+                TrianglePlaneSide::Front2Back1 { .. } => {
+                    front_triangle_count += 2;
+                    back_triangle_count += 1;
+                    spanning_triangle_count += 1;
+                }
+                TrianglePlaneSide::Back2Front1 { .. } => {
+                    front_triangle_count += 1;
+                    back_triangle_count += 2;
+                    spanning_triangle_count += 1;
+                }
+            }
+
+            sides.push(side);
+        }
+
+        let vertex_count = self.vertex_list.positions.len();
+        let mut front_vertex_list = self
+            .vertex_list
+            .empty_like(vertex_count.min(front_triangle_count * 3) + spanning_triangle_count * 2);
+        let mut back_vertex_list = self
+            .vertex_list
+            .empty_like(vertex_count.min(back_triangle_count * 3) + spanning_triangle_count * 2);
+
+        let mut front_triangles = Vec::with_capacity(front_triangle_count);
+        let mut back_triangles = Vec::with_capacity(back_triangle_count);
+
+        for (triangle, side) in self.triangles.iter().zip(sides.drain(..)) {
+            match side {
+                TrianglePlaneSide::Front | TrianglePlaneSide::OnPlane => {
                     let triangle = transfer_triangle!(
                         triangle,
                         front_vertex_map,
@@ -113,14 +200,14 @@ impl Mesh {
                 }
                 TrianglePlaneSide::Front2Back1 { front, back } => {
                     let front_positions = [
-                        self.vertex_list.positions[triangle.indices[front[0]]],
-                        self.vertex_list.positions[triangle.indices[front[1]]],
+                        self.vertex_list.positions[front[0]],
+                        self.vertex_list.positions[front[1]],
                     ];
-                    let back_positions = [self.vertex_list.positions[triangle.indices[back[0]]]];
+                    let back_positions = [self.vertex_list.positions[back[0]]];
 
                     let contact_points = [
-                        plane.point_on(front_positions[0], back_positions[0] - front_positions[0]),
-                        plane.point_on(front_positions[1], back_positions[0] - front_positions[1]),
+                        edge_contact_point(plane, front_positions[0], back_positions[0]),
+                        edge_contact_point(plane, front_positions[1], back_positions[0]),
                     ];
                     let total_lengths = [
                         (back_positions[0] - front_positions[0]).len(),
@@ -149,33 +236,33 @@ impl Mesh {
                         Some(normals) => match self.vertex_list.surface_shading {
                             SurfaceShading::Flat => Some([
                                 [
-                                    normals[triangle.indices[front[0]] * 3 + 0],
-                                    normals[triangle.indices[front[0]] * 3 + 1],
-                                    normals[triangle.indices[front[0]] * 3 + 2],
+                                    normals[front[0] * 3 + 0],
+                                    normals[front[0] * 3 + 1],
+                                    normals[front[0] * 3 + 2],
                                 ],
                                 [
-                                    normals[triangle.indices[front[0]] * 3 + 0],
-                                    normals[triangle.indices[front[0]] * 3 + 1],
-                                    normals[triangle.indices[front[0]] * 3 + 2],
+                                    normals[front[0] * 3 + 0],
+                                    normals[front[0] * 3 + 1],
+                                    normals[front[0] * 3 + 2],
                                 ],
                             ]),
                             SurfaceShading::Smooth => {
                                 let front_normals = [
                                     Vec3::new(
-                                        normals[triangle.indices[front[0]] * 3 + 0],
-                                        normals[triangle.indices[front[0]] * 3 + 1],
-                                        normals[triangle.indices[front[0]] * 3 + 2],
+                                        normals[front[0] * 3 + 0],
+                                        normals[front[0] * 3 + 1],
+                                        normals[front[0] * 3 + 2],
                                     ),
                                     Vec3::new(
-                                        normals[triangle.indices[front[1]] * 3 + 0],
-                                        normals[triangle.indices[front[1]] * 3 + 1],
-                                        normals[triangle.indices[front[1]] * 3 + 2],
+                                        normals[front[1] * 3 + 0],
+                                        normals[front[1] * 3 + 1],
+                                        normals[front[1] * 3 + 2],
                                     ),
                                 ];
                                 let back_normal = Vec3::new(
-                                    normals[triangle.indices[back[0]] * 3 + 0],
-                                    normals[triangle.indices[back[0]] * 3 + 1],
-                                    normals[triangle.indices[back[0]] * 3 + 2],
+                                    normals[back[0] * 3 + 0],
+                                    normals[back[0] * 3 + 1],
+                                    normals[back[0] * 3 + 2],
                                 );
 
                                 let front_new_normals = [
@@ -203,14 +290,14 @@ impl Mesh {
                         Some(normals) => match self.vertex_list.surface_shading {
                             SurfaceShading::Flat => Some([
                                 [
-                                    normals[triangle.indices[back[0]] * 3 + 0],
-                                    normals[triangle.indices[back[0]] * 3 + 1],
-                                    normals[triangle.indices[back[0]] * 3 + 2],
+                                    normals[back[0] * 3 + 0],
+                                    normals[back[0] * 3 + 1],
+                                    normals[back[0] * 3 + 2],
                                 ],
                                 [
-                                    normals[triangle.indices[back[0]] * 3 + 0],
-                                    normals[triangle.indices[back[0]] * 3 + 1],
-                                    normals[triangle.indices[back[0]] * 3 + 2],
+                                    normals[back[0] * 3 + 0],
+                                    normals[back[0] * 3 + 1],
+                                    normals[back[0] * 3 + 2],
                                 ],
                             ]),
                             SurfaceShading::Smooth => front_new_normals.clone(),
@@ -222,33 +309,33 @@ impl Mesh {
                         Some(tangents) => match self.vertex_list.surface_shading {
                             SurfaceShading::Flat => Some([
                                 [
-                                    tangents[triangle.indices[front[0]] * 3 + 0],
-                                    tangents[triangle.indices[front[0]] * 3 + 1],
-                                    tangents[triangle.indices[front[0]] * 3 + 2],
+                                    tangents[front[0] * 3 + 0],
+                                    tangents[front[0] * 3 + 1],
+                                    tangents[front[0] * 3 + 2],
                                 ],
                                 [
-                                    tangents[triangle.indices[front[0]] * 3 + 0],
-                                    tangents[triangle.indices[front[0]] * 3 + 1],
-                                    tangents[triangle.indices[front[0]] * 3 + 2],
+                                    tangents[front[0] * 3 + 0],
+                                    tangents[front[0] * 3 + 1],
+                                    tangents[front[0] * 3 + 2],
                                 ],
                             ]),
                             SurfaceShading::Smooth => {
                                 let front_tangents = [
                                     Vec3::new(
-                                        tangents[triangle.indices[front[0]] * 3 + 0],
-                                        tangents[triangle.indices[front[0]] * 3 + 1],
-                                        tangents[triangle.indices[front[0]] * 3 + 2],
+                                        tangents[front[0] * 3 + 0],
+                                        tangents[front[0] * 3 + 1],
+                                        tangents[front[0] * 3 + 2],
                                     ),
                                     Vec3::new(
-                                        tangents[triangle.indices[front[1]] * 3 + 0],
-                                        tangents[triangle.indices[front[1]] * 3 + 1],
-                                        tangents[triangle.indices[front[1]] * 3 + 2],
+                                        tangents[front[1] * 3 + 0],
+                                        tangents[front[1] * 3 + 1],
+                                        tangents[front[1] * 3 + 2],
                                     ),
                                 ];
                                 let back_tangent = Vec3::new(
-                                    tangents[triangle.indices[back[0]] * 3 + 0],
-                                    tangents[triangle.indices[back[0]] * 3 + 1],
-                                    tangents[triangle.indices[back[0]] * 3 + 2],
+                                    tangents[back[0] * 3 + 0],
+                                    tangents[back[0] * 3 + 1],
+                                    tangents[back[0] * 3 + 2],
                                 );
 
                                 let front_new_tangents = [
@@ -278,14 +365,14 @@ impl Mesh {
                         Some(tangents) => match self.vertex_list.surface_shading {
                             SurfaceShading::Flat => Some([
                                 [
-                                    tangents[triangle.indices[back[0]] * 3 + 0],
-                                    tangents[triangle.indices[back[0]] * 3 + 1],
-                                    tangents[triangle.indices[back[0]] * 3 + 2],
+                                    tangents[back[0] * 3 + 0],
+                                    tangents[back[0] * 3 + 1],
+                                    tangents[back[0] * 3 + 2],
                                 ],
                                 [
-                                    tangents[triangle.indices[back[0]] * 3 + 0],
-                                    tangents[triangle.indices[back[0]] * 3 + 1],
-                                    tangents[triangle.indices[back[0]] * 3 + 2],
+                                    tangents[back[0] * 3 + 0],
+                                    tangents[back[0] * 3 + 1],
+                                    tangents[back[0] * 3 + 2],
                                 ],
                             ]),
                             SurfaceShading::Smooth => front_new_tangents.clone(),
@@ -293,30 +380,20 @@ impl Mesh {
                         None => None,
                     };
 
-                    let mut front_new_texcoords = [
-                        Vec::with_capacity(self.vertex_list.texcoords.len()),
-                        Vec::with_capacity(self.vertex_list.texcoords.len()),
-                    ];
-                    let mut back_new_texcoords = [
-                        Vec::with_capacity(self.vertex_list.texcoords.len()),
-                        Vec::with_capacity(self.vertex_list.texcoords.len()),
-                    ];
+                    for texcoords in front_new_texcoords
+                        .iter_mut()
+                        .chain(back_new_texcoords.iter_mut())
+                    {
+                        texcoords.clear();
+                    }
 
                     for texcoords in &self.vertex_list.texcoords {
                         let front_texcoords = [
-                            [
-                                texcoords[triangle.indices[front[0]] * 2 + 0],
-                                texcoords[triangle.indices[front[0]] * 2 + 1],
-                            ],
-                            [
-                                texcoords[triangle.indices[front[1]] * 2 + 0],
-                                texcoords[triangle.indices[front[1]] * 2 + 1],
-                            ],
-                        ];
-                        let back_texcoords = [
-                            texcoords[triangle.indices[back[0]] * 2 + 0],
-                            texcoords[triangle.indices[back[0]] * 2 + 1],
+                            [texcoords[front[0] * 2 + 0], texcoords[front[0] * 2 + 1]],
+                            [texcoords[front[1] * 2 + 0], texcoords[front[1] * 2 + 1]],
                         ];
+                        let back_texcoords =
+                            [texcoords[back[0] * 2 + 0], texcoords[back[0] * 2 + 1]];
 
                         let new_texcoords = [
                             [
@@ -349,13 +426,13 @@ impl Mesh {
                             front_new_positions[0],
                             front_new_normals.map(|n| n[0]),
                             front_new_tangents.map(|t| t[0]),
-                            std::mem::take(&mut front_new_texcoords[0]),
+                            &front_new_texcoords[0],
                         ),
                         front_vertex_list.add_vertex(
                             front_new_positions[1],
                             front_new_normals.map(|n| n[1]),
                             front_new_tangents.map(|t| t[1]),
-                            std::mem::take(&mut front_new_texcoords[1]),
+                            &front_new_texcoords[1],
                         ),
                     ];
                     let back_new_vertex_indices = [
@@ -363,45 +440,72 @@ impl Mesh {
                             back_new_positions[0],
                             back_new_normals.map(|n| n[0]),
                             back_new_tangents.map(|t| t[0]),
-                            std::mem::take(&mut back_new_texcoords[0]),
+                            &back_new_texcoords[0],
                         ),
                         back_vertex_list.add_vertex(
                             back_new_positions[1],
                             back_new_normals.map(|n| n[1]),
                             back_new_tangents.map(|t| t[1]),
-                            std::mem::take(&mut back_new_texcoords[1]),
+                            &back_new_texcoords[1],
+                        ),
+                    ];
+
+                    let front_vertex_indices = [
+                        transfer_vertex!(
+                            front[0],
+                            front_vertex_map,
+                            self.vertex_list,
+                            front_vertex_list
+                        ),
+                        transfer_vertex!(
+                            front[1],
+                            front_vertex_map,
+                            self.vertex_list,
+                            front_vertex_list
                         ),
                     ];
+                    let back_vertex_indices = [transfer_vertex!(
+                        back[0],
+                        back_vertex_map,
+                        self.vertex_list,
+                        back_vertex_list
+                    )];
 
+                    // the source triangle is (front[0], front[1], back[0]) in its winding order;
+                    // the front quad (front[0], front[1], contact[1], contact[0]) is fanned from front[0]
                     front_triangles.push(Triangle {
-                        indices: [front[0], front[1], front_new_vertex_indices[0]],
+                        indices: [
+                            front_vertex_indices[0],
+                            front_vertex_indices[1],
+                            front_new_vertex_indices[1],
+                        ],
                     });
                     front_triangles.push(Triangle {
                         indices: [
-                            front[0],
-                            front_new_vertex_indices[0],
+                            front_vertex_indices[0],
                             front_new_vertex_indices[1],
+                            front_new_vertex_indices[0],
                         ],
                     });
 
                     back_triangles.push(Triangle {
                         indices: [
                             back_new_vertex_indices[0],
-                            back[0],
                             back_new_vertex_indices[1],
+                            back_vertex_indices[0],
                         ],
                     })
                 }
                 TrianglePlaneSide::Back2Front1 { front, back } => {
                     let back_positions = [
-                        self.vertex_list.positions[triangle.indices[back[0]]],
-                        self.vertex_list.positions[triangle.indices[back[1]]],
+                        self.vertex_list.positions[back[0]],
+                        self.vertex_list.positions[back[1]],
                     ];
-                    let front_positions = [self.vertex_list.positions[triangle.indices[front[0]]]];
+                    let front_positions = [self.vertex_list.positions[front[0]]];
 
                     let contact_points = [
-                        plane.point_on(back_positions[0], front_positions[0] - back_positions[0]),
-                        plane.point_on(back_positions[1], front_positions[0] - back_positions[1]),
+                        edge_contact_point(plane, back_positions[0], front_positions[0]),
+                        edge_contact_point(plane, back_positions[1], front_positions[0]),
                     ];
                     let total_lengths = [
                         (front_positions[0] - back_positions[0]).len(),
@@ -430,33 +534,33 @@ impl Mesh {
                         Some(normals) => match self.vertex_list.surface_shading {
                             SurfaceShading::Flat => Some([
                                 [
-                                    normals[triangle.indices[back[0]] * 3 + 0],
-                                    normals[triangle.indices[back[0]] * 3 + 1],
-                                    normals[triangle.indices[back[0]] * 3 + 2],
+                                    normals[back[0] * 3 + 0],
+                                    normals[back[0] * 3 + 1],
+                                    normals[back[0] * 3 + 2],
                                 ],
                                 [
-                                    normals[triangle.indices[back[0]] * 3 + 0],
-                                    normals[triangle.indices[back[0]] * 3 + 1],
-                                    normals[triangle.indices[back[0]] * 3 + 2],
+                                    normals[back[0] * 3 + 0],
+                                    normals[back[0] * 3 + 1],
+                                    normals[back[0] * 3 + 2],
                                 ],
                             ]),
                             SurfaceShading::Smooth => {
                                 let back_normals = [
                                     Vec3::new(
-                                        normals[triangle.indices[back[0]] * 3 + 0],
-                                        normals[triangle.indices[back[0]] * 3 + 1],
-                                        normals[triangle.indices[back[0]] * 3 + 2],
+                                        normals[back[0] * 3 + 0],
+                                        normals[back[0] * 3 + 1],
+                                        normals[back[0] * 3 + 2],
                                     ),
                                     Vec3::new(
-                                        normals[triangle.indices[back[1]] * 3 + 0],
-                                        normals[triangle.indices[back[1]] * 3 + 1],
-                                        normals[triangle.indices[back[1]] * 3 + 2],
+                                        normals[back[1] * 3 + 0],
+                                        normals[back[1] * 3 + 1],
+                                        normals[back[1] * 3 + 2],
                                     ),
                                 ];
                                 let front_normal = Vec3::new(
-                                    normals[triangle.indices[front[0]] * 3 + 0],
-                                    normals[triangle.indices[front[0]] * 3 + 1],
-                                    normals[triangle.indices[front[0]] * 3 + 2],
+                                    normals[front[0] * 3 + 0],
+                                    normals[front[0] * 3 + 1],
+                                    normals[front[0] * 3 + 2],
                                 );
 
                                 let back_new_normals = [
@@ -484,14 +588,14 @@ impl Mesh {
                         Some(normals) => match self.vertex_list.surface_shading {
                             SurfaceShading::Flat => Some([
                                 [
-                                    normals[triangle.indices[front[0]] * 3 + 0],
-                                    normals[triangle.indices[front[0]] * 3 + 1],
-                                    normals[triangle.indices[front[0]] * 3 + 2],
+                                    normals[front[0] * 3 + 0],
+                                    normals[front[0] * 3 + 1],
+                                    normals[front[0] * 3 + 2],
                                 ],
                                 [
-                                    normals[triangle.indices[front[0]] * 3 + 0],
-                                    normals[triangle.indices[front[0]] * 3 + 1],
-                                    normals[triangle.indices[front[0]] * 3 + 2],
+                                    normals[front[0] * 3 + 0],
+                                    normals[front[0] * 3 + 1],
+                                    normals[front[0] * 3 + 2],
                                 ],
                             ]),
                             SurfaceShading::Smooth => back_new_normals.clone(),
@@ -503,33 +607,33 @@ impl Mesh {
                         Some(tangents) => match self.vertex_list.surface_shading {
                             SurfaceShading::Flat => Some([
                                 [
-                                    tangents[triangle.indices[back[0]] * 3 + 0],
-                                    tangents[triangle.indices[back[0]] * 3 + 1],
-                                    tangents[triangle.indices[back[0]] * 3 + 2],
+                                    tangents[back[0] * 3 + 0],
+                                    tangents[back[0] * 3 + 1],
+                                    tangents[back[0] * 3 + 2],
                                 ],
                                 [
-                                    tangents[triangle.indices[back[0]] * 3 + 0],
-                                    tangents[triangle.indices[back[0]] * 3 + 1],
-                                    tangents[triangle.indices[back[0]] * 3 + 2],
+                                    tangents[back[0] * 3 + 0],
+                                    tangents[back[0] * 3 + 1],
+                                    tangents[back[0] * 3 + 2],
                                 ],
                             ]),
                             SurfaceShading::Smooth => {
                                 let back_tangents = [
                                     Vec3::new(
-                                        tangents[triangle.indices[back[0]] * 3 + 0],
-                                        tangents[triangle.indices[back[0]] * 3 + 1],
-                                        tangents[triangle.indices[back[0]] * 3 + 2],
+                                        tangents[back[0] * 3 + 0],
+                                        tangents[back[0] * 3 + 1],
+                                        tangents[back[0] * 3 + 2],
                                     ),
                                     Vec3::new(
-                                        tangents[triangle.indices[back[1]] * 3 + 0],
-                                        tangents[triangle.indices[back[1]] * 3 + 1],
-                                        tangents[triangle.indices[back[1]] * 3 + 2],
+                                        tangents[back[1] * 3 + 0],
+                                        tangents[back[1] * 3 + 1],
+                                        tangents[back[1] * 3 + 2],
                                     ),
                                 ];
                                 let front_tangent = Vec3::new(
-                                    tangents[triangle.indices[front[0]] * 3 + 0],
-                                    tangents[triangle.indices[front[0]] * 3 + 1],
-                                    tangents[triangle.indices[front[0]] * 3 + 2],
+                                    tangents[front[0] * 3 + 0],
+                                    tangents[front[0] * 3 + 1],
+                                    tangents[front[0] * 3 + 2],
                                 );
 
                                 let back_new_tangents = [
@@ -559,14 +663,14 @@ impl Mesh {
                         Some(tangents) => match self.vertex_list.surface_shading {
                             SurfaceShading::Flat => Some([
                                 [
-                                    tangents[triangle.indices[front[0]] * 3 + 0],
-                                    tangents[triangle.indices[front[0]] * 3 + 1],
-                                    tangents[triangle.indices[front[0]] * 3 + 2],
+                                    tangents[front[0] * 3 + 0],
+                                    tangents[front[0] * 3 + 1],
+                                    tangents[front[0] * 3 + 2],
                                 ],
                                 [
-                                    tangents[triangle.indices[front[0]] * 3 + 0],
-                                    tangents[triangle.indices[front[0]] * 3 + 1],
-                                    tangents[triangle.indices[front[0]] * 3 + 2],
+                                    tangents[front[0] * 3 + 0],
+                                    tangents[front[0] * 3 + 1],
+                                    tangents[front[0] * 3 + 2],
                                 ],
                             ]),
                             SurfaceShading::Smooth => back_new_tangents.clone(),
@@ -574,30 +678,20 @@ impl Mesh {
                         None => None,
                     };
 
-                    let mut back_new_texcoords = [
-                        Vec::with_capacity(self.vertex_list.texcoords.len()),
-                        Vec::with_capacity(self.vertex_list.texcoords.len()),
-                    ];
-                    let mut front_new_texcoords = [
-                        Vec::with_capacity(self.vertex_list.texcoords.len()),
-                        Vec::with_capacity(self.vertex_list.texcoords.len()),
-                    ];
+                    for texcoords in back_new_texcoords
+                        .iter_mut()
+                        .chain(front_new_texcoords.iter_mut())
+                    {
+                        texcoords.clear();
+                    }
 
                     for texcoords in &self.vertex_list.texcoords {
                         let back_texcoords = [
-                            [
-                                texcoords[triangle.indices[back[0]] * 2 + 0],
-                                texcoords[triangle.indices[back[0]] * 2 + 1],
-                            ],
-                            [
-                                texcoords[triangle.indices[back[1]] * 2 + 0],
-                                texcoords[triangle.indices[back[1]] * 2 + 1],
-                            ],
-                        ];
-                        let front_texcoords = [
-                            texcoords[triangle.indices[front[0]] * 2 + 0],
-                            texcoords[triangle.indices[front[0]] * 2 + 1],
+                            [texcoords[back[0] * 2 + 0], texcoords[back[0] * 2 + 1]],
+                            [texcoords[back[1] * 2 + 0], texcoords[back[1] * 2 + 1]],
                         ];
+                        let front_texcoords =
+                            [texcoords[front[0] * 2 + 0], texcoords[front[0] * 2 + 1]];
 
                         let new_texcoords = [
                             [
@@ -630,13 +724,13 @@ impl Mesh {
                             back_new_positions[0],
                             back_new_normals.map(|n| n[0]),
                             back_new_tangents.map(|t| t[0]),
-                            std::mem::take(&mut back_new_texcoords[0]),
+                            &back_new_texcoords[0],
                         ),
                         back_vertex_list.add_vertex(
                             back_new_positions[1],
                             back_new_normals.map(|n| n[1]),
                             back_new_tangents.map(|t| t[1]),
-                            std::mem::take(&mut back_new_texcoords[1]),
+                            &back_new_texcoords[1],
                         ),
                     ];
                     let front_new_vertex_indices = [
@@ -644,32 +738,59 @@ impl Mesh {
                             front_new_positions[0],
                             front_new_normals.map(|n| n[0]),
                             front_new_tangents.map(|t| t[0]),
-                            std::mem::take(&mut front_new_texcoords[0]),
+                            &front_new_texcoords[0],
                         ),
                         front_vertex_list.add_vertex(
                             front_new_positions[1],
                             front_new_normals.map(|n| n[1]),
                             front_new_tangents.map(|t| t[1]),
-                            std::mem::take(&mut front_new_texcoords[1]),
+                            &front_new_texcoords[1],
+                        ),
+                    ];
+
+                    let back_vertex_indices = [
+                        transfer_vertex!(
+                            back[0],
+                            back_vertex_map,
+                            self.vertex_list,
+                            back_vertex_list
+                        ),
+                        transfer_vertex!(
+                            back[1],
+                            back_vertex_map,
+                            self.vertex_list,
+                            back_vertex_list
                         ),
                     ];
+                    let front_vertex_indices = [transfer_vertex!(
+                        front[0],
+                        front_vertex_map,
+                        self.vertex_list,
+                        front_vertex_list
+                    )];
 
+                    // the source triangle is (back[0], back[1], front[0]) in its winding order;
+                    // the back quad (back[0], back[1], contact[1], contact[0]) is fanned from back[0]
                     back_triangles.push(Triangle {
-                        indices: [back[0], back[1], back_new_vertex_indices[0]],
+                        indices: [
+                            back_vertex_indices[0],
+                            back_vertex_indices[1],
+                            back_new_vertex_indices[1],
+                        ],
                     });
                     back_triangles.push(Triangle {
                         indices: [
-                            back[0],
-                            back_new_vertex_indices[0],
+                            back_vertex_indices[0],
                             back_new_vertex_indices[1],
+                            back_new_vertex_indices[0],
                         ],
                     });
 
                     front_triangles.push(Triangle {
                         indices: [
                             front_new_vertex_indices[0],
-                            front[0],
                             front_new_vertex_indices[1],
+                            front_vertex_indices[0],
                         ],
                     })
                 }
@@ -689,6 +810,242 @@ impl Mesh {
             back_triangles,
         );
 
-        SplittedMesh { front, back }
+        Ok(SplittedMesh { front, back })
+    }
+}
+
+/// Intersects the edge between the two points with the plane. The intersection is clamped to the
+/// edge: a vertex on the plane within the epsilon may still lie on the same side as the other
+/// one, and intersecting the infinite line would then put the contact point past the edge.
+fn edge_contact_point(plane: Plane, from: Vec3, to: Vec3) -> Vec3 {
+    let from_distance = plane.distance_to_point(from);
+    let to_distance = plane.distance_to_point(to);
+    let distance_delta = from_distance - to_distance;
+    let t = if distance_delta.abs() <= f32::EPSILON {
+        0.0
+    } else {
+        (from_distance / distance_delta).clamp(0.0, 1.0)
+    };
+
+    from + (to - from) * t
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{VertexPlaneSide, Winding, DEFAULT_PLANE_EPSILON};
+
+    fn equals_float(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-4
+    }
+
+    fn make_mesh(indices: [usize; 3]) -> Mesh {
+        let vertex_list = VertexList {
+            surface_shading: SurfaceShading::Flat,
+            positions: vec![
+                Vec3::new(0.0, 0.0, 0.0),
+                Vec3::new(1.0, 0.0, 0.0),
+                Vec3::new(0.0, 1.0, 0.0),
+            ],
+            normals: None,
+            tangents: None,
+            texcoords: vec![],
+        };
+
+        Mesh::new(
+            NonZeroU32::new(1).unwrap(),
+            NonZeroU32::new(1).unwrap(),
+            vertex_list,
+            vec![Triangle { indices }],
+        )
+    }
+
+    #[test]
+    fn check_split_malformed_vertex_list() {
+        let mut mesh = make_mesh([0, 1, 2]);
+        mesh.vertex_list.normals = Some(vec![0.0; 3]);
+        let plane = Plane::new(Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.25, 0.0, 0.0));
+
+        assert_eq!(
+            mesh.split_by_plane(plane, DEFAULT_PLANE_EPSILON),
+            Err(VertexListError::NormalCountMismatch {
+                expected: 9,
+                actual: 3
+            })
+        );
+    }
+
+    #[test]
+    fn check_split_by_plane_preserves_winding() {
+        let reference_plane = Plane::new(Vec3::new(0.0, 0.0, 1.0), Vec3::ZERO);
+        let splitting_planes = [
+            Plane::new(Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.25, 0.0, 0.0)),
+            Plane::new(Vec3::new(-1.0, 0.0, 0.0), Vec3::new(0.25, 0.0, 0.0)),
+            Plane::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 0.5, 0.0)),
+        ];
+        let triangles = [
+            [0, 1, 2],
+            [1, 2, 0],
+            [2, 0, 1],
+            [0, 2, 1],
+            [2, 1, 0],
+            [1, 0, 2],
+        ];
+
+        for plane in splitting_planes {
+            for indices in triangles {
+                let mesh = make_mesh(indices);
+                let source_winding = mesh.triangles[0].winding(&mesh.vertex_list, reference_plane);
+                let source_area = mesh.triangles[0].signed_area(&mesh.vertex_list, reference_plane);
+
+                let splitted = mesh.split_by_plane(plane, DEFAULT_PLANE_EPSILON).unwrap();
+                assert!(!splitted.front.is_empty());
+                assert!(!splitted.back.is_empty());
+
+                let mut area = 0.0;
+
+                for mesh in [&splitted.front, &splitted.back] {
+                    for triangle in &mesh.triangles {
+                        assert_eq!(
+                            triangle.winding(&mesh.vertex_list, reference_plane),
+                            source_winding
+                        );
+                        area += triangle.signed_area(&mesh.vertex_list, reference_plane);
+                    }
+                }
+
+                assert!(equals_float(area, source_area));
+            }
+        }
+    }
+
+    #[test]
+    fn check_split_by_plane_keeps_sides() {
+        let plane = Plane::new(Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.25, 0.0, 0.0));
+        let splitted = make_mesh([0, 1, 2])
+            .split_by_plane(plane, DEFAULT_PLANE_EPSILON)
+            .unwrap();
+
+        for position in &splitted.front.vertex_list.positions {
+            assert!(-1e-4 <= plane.distance_to_point(*position));
+        }
+
+        for position in &splitted.back.vertex_list.positions {
+            assert!(plane.distance_to_point(*position) <= 1e-4);
+        }
+    }
+
+    #[test]
+    fn check_plane_epsilon() {
+        let mesh = make_mesh([0, 1, 2]);
+        let triangle = &mesh.triangles[0];
+        // the vertex at (1, 0, 0) is 0.01 behind the plane, the others are in front of it
+        let plane = Plane::new(Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.99, 0.0, 0.0));
+
+        assert_eq!(
+            VertexPlaneSide::classify(plane, Vec3::new(1.0, 0.0, 0.0), 0.1),
+            VertexPlaneSide::OnPlane
+        );
+        assert_eq!(
+            triangle.plane_side(&mesh.vertex_list, plane, 0.1),
+            TrianglePlaneSide::Back
+        );
+        assert!(matches!(
+            triangle.plane_side(&mesh.vertex_list, plane, 0.001),
+            TrianglePlaneSide::Back2Front1 { .. }
+        ));
+
+        let splitted = mesh.clone().split_by_plane(plane, 0.1).unwrap();
+        assert!(splitted.front.is_empty());
+        assert_eq!(splitted.back.triangles.len(), 1);
+
+        let splitted = mesh.clone().split_by_plane(plane, 0.001).unwrap();
+        assert_eq!(splitted.front.triangles.len(), 1);
+        assert_eq!(splitted.back.triangles.len(), 2);
+
+        let coplanar_plane = Plane::new(Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 0.0, 0.05));
+        assert_eq!(
+            triangle.plane_side(&mesh.vertex_list, coplanar_plane, 0.1),
+            TrianglePlaneSide::OnPlane
+        );
+    }
+
+    #[test]
+    fn check_split_with_on_plane_vertex_behind_plane() {
+        // the vertex at (-0.05, 1, 0) is on the plane within the epsilon, but behind it
+        let vertex_list = VertexList {
+            surface_shading: SurfaceShading::Flat,
+            positions: vec![
+                Vec3::new(1.0, 0.0, 0.0),
+                Vec3::new(-0.05, 1.0, 0.0),
+                Vec3::new(-1.0, 0.0, 0.0),
+            ],
+            normals: None,
+            tangents: None,
+            texcoords: vec![],
+        };
+        let mesh = Mesh::new(
+            NonZeroU32::new(1).unwrap(),
+            NonZeroU32::new(1).unwrap(),
+            vertex_list,
+            vec![Triangle { indices: [0, 1, 2] }],
+        );
+        let plane = Plane::new(Vec3::new(1.0, 0.0, 0.0), Vec3::ZERO);
+
+        assert!(matches!(
+            mesh.triangles[0].plane_side(&mesh.vertex_list, plane, 0.1),
+            TrianglePlaneSide::Front2Back1 { .. }
+        ));
+
+        let splitted = mesh.split_by_plane(plane, 0.1).unwrap();
+
+        for mesh in [&splitted.front, &splitted.back] {
+            for position in &mesh.vertex_list.positions {
+                assert!(
+                    -1.0 <= position.x && position.x <= 1.0,
+                    "{} is out of the triangle",
+                    position
+                );
+                assert!(
+                    0.0 <= position.y && position.y <= 1.0,
+                    "{} is out of the triangle",
+                    position
+                );
+            }
+        }
+
+        // the contact on the edge to the vertex behind the plane is the on-plane vertex itself
+        assert!(splitted
+            .back
+            .vertex_list
+            .positions
+            .iter()
+            .any(|position| Vec3::distance(*position, Vec3::new(-0.05, 1.0, 0.0)) < 1e-4));
+    }
+
+    #[test]
+    fn check_triangle_winding() {
+        let reference_plane = Plane::new(Vec3::new(0.0, 0.0, 1.0), Vec3::ZERO);
+        let mesh = make_mesh([0, 1, 2]);
+        let triangle = &mesh.triangles[0];
+        let reversed = Triangle { indices: [0, 2, 1] };
+        let degenerate = Triangle { indices: [0, 1, 1] };
+
+        assert_eq!(
+            triangle.winding(&mesh.vertex_list, reference_plane),
+            Winding::CounterClockwise
+        );
+        assert_eq!(
+            reversed.winding(&mesh.vertex_list, reference_plane),
+            Winding::Clockwise
+        );
+        assert_eq!(
+            degenerate.winding(&mesh.vertex_list, reference_plane),
+            Winding::Degenerate
+        );
+        assert!(equals_float(
+            triangle.signed_area(&mesh.vertex_list, reference_plane),
+            0.5
+        ));
     }
 }
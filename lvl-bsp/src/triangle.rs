@@ -1,12 +1,60 @@
 use super::VertexList;
-use lvl_math::{Plane, PlaneSide};
+use lvl_math::{Plane, Vec3};
 
+/// Default distance within which a vertex is considered to be on a splitting plane.
+/// It suits meshes modeled in meters; scale it with the units of the mesh.
+pub const DEFAULT_PLANE_EPSILON: f32 = 1e-5;
+
+/// Side of a vertex against a plane.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VertexPlaneSide {
+    Front,
+    Back,
+    /// The vertex is within the epsilon of the plane.
+    OnPlane,
+}
+
+impl VertexPlaneSide {
+    pub fn classify(plane: Plane, point: Vec3, epsilon: f32) -> Self {
+        let distance = plane.distance_to_point(point);
+
+        if epsilon < distance {
+            Self::Front
+        } else if distance < -epsilon {
+            Self::Back
+        } else {
+            Self::OnPlane
+        }
+    }
+}
+
+/// Side of a triangle against a plane.
+/// The indices of the spanning variants are vertex indices, ordered so that
+/// `front ++ back` (for `Front2Back1`) and `back ++ front` (for `Back2Front1`)
+/// follow the winding order of the triangle.
 #[derive(Debug, Clone, PartialEq)]
 pub enum TrianglePlaneSide {
     Front,
     Back,
-    Front2Back1 { front: [usize; 2], back: [usize; 1] },
-    Back2Front1 { front: [usize; 1], back: [usize; 2] },
+    /// All vertices are on the plane.
+    OnPlane,
+    Front2Back1 {
+        front: [usize; 2],
+        back: [usize; 1],
+    },
+    Back2Front1 {
+        front: [usize; 1],
+        back: [usize; 2],
+    },
+}
+
+/// Winding order of a triangle, as seen from the front side of a reference plane.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Winding {
+    Clockwise,
+    CounterClockwise,
+    /// The triangle has no area when projected onto the reference plane.
+    Degenerate,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -16,57 +64,87 @@ pub struct Triangle {
 }
 
 impl Triangle {
-    pub fn plane_side(&self, vertex_list: &VertexList, plane: Plane) -> TrianglePlaneSide {
+    /// Computes the area of the triangle projected onto the given plane.
+    /// It is positive if the triangle is counter-clockwise as seen from the front side of the plane.
+    pub fn signed_area(&self, vertex_list: &VertexList, plane: Plane) -> f32 {
         let positions = [
             vertex_list.positions[self.indices[0]],
             vertex_list.positions[self.indices[1]],
             vertex_list.positions[self.indices[2]],
         ];
-        let sides = [
-            plane.point_side(positions[0]),
-            plane.point_side(positions[1]),
-            plane.point_side(positions[2]),
-        ];
+        let cross = Vec3::cross(positions[1] - positions[0], positions[2] - positions[0]);
+        Vec3::dot(cross, plane.normal) * 0.5
+    }
 
-        match (sides[0], sides[1], sides[2]) {
-            (PlaneSide::Front, PlaneSide::Front, PlaneSide::Front) => TrianglePlaneSide::Front,
-            (PlaneSide::Front, PlaneSide::Front, PlaneSide::Back) => {
-                TrianglePlaneSide::Front2Back1 {
-                    front: [self.indices[0], self.indices[1]],
-                    back: [self.indices[2]],
-                }
-            }
-            (PlaneSide::Front, PlaneSide::Back, PlaneSide::Front) => {
-                TrianglePlaneSide::Front2Back1 {
-                    front: [self.indices[2], self.indices[0]],
-                    back: [self.indices[1]],
-                }
-            }
-            (PlaneSide::Front, PlaneSide::Back, PlaneSide::Back) => {
-                TrianglePlaneSide::Back2Front1 {
-                    front: [self.indices[0]],
-                    back: [self.indices[1], self.indices[2]],
-                }
-            }
-            (PlaneSide::Back, PlaneSide::Front, PlaneSide::Front) => {
-                TrianglePlaneSide::Front2Back1 {
-                    front: [self.indices[1], self.indices[2]],
-                    back: [self.indices[0]],
-                }
+    pub fn winding(&self, vertex_list: &VertexList, plane: Plane) -> Winding {
+        const EPSILON: f32 = 1e-6;
+
+        let signed_area = self.signed_area(vertex_list, plane);
+
+        if EPSILON < signed_area {
+            Winding::CounterClockwise
+        } else if signed_area < -EPSILON {
+            Winding::Clockwise
+        } else {
+            Winding::Degenerate
+        }
+    }
+
+    /// Classifies the triangle against the plane. Vertices within `epsilon` of the plane
+    /// are on the plane; they do not make the triangle span the plane, and count as
+    /// the front side of a triangle that spans it anyway.
+    pub fn plane_side(
+        &self,
+        vertex_list: &VertexList,
+        plane: Plane,
+        epsilon: f32,
+    ) -> TrianglePlaneSide {
+        let sides = self
+            .indices
+            .map(|index| VertexPlaneSide::classify(plane, vertex_list.positions[index], epsilon));
+        let is_front = sides.map(|side| side == VertexPlaneSide::Front);
+        let is_back = sides.map(|side| side == VertexPlaneSide::Back);
+
+        match (is_front.contains(&true), is_back.contains(&true)) {
+            (false, false) => {
+                return TrianglePlaneSide::OnPlane;
             }
-            (PlaneSide::Back, PlaneSide::Front, PlaneSide::Back) => {
-                TrianglePlaneSide::Back2Front1 {
-                    front: [self.indices[1]],
-                    back: [self.indices[2], self.indices[0]],
-                }
+            (_, false) => {
+                return TrianglePlaneSide::Front;
             }
-            (PlaneSide::Back, PlaneSide::Back, PlaneSide::Front) => {
-                TrianglePlaneSide::Back2Front1 {
-                    front: [self.indices[2]],
-                    back: [self.indices[0], self.indices[1]],
-                }
+            (false, true) => {
+                return TrianglePlaneSide::Back;
             }
-            (PlaneSide::Back, PlaneSide::Back, PlaneSide::Back) => TrianglePlaneSide::Back,
+            (true, true) => {}
+        }
+
+        match (!is_back[0], !is_back[1], !is_back[2]) {
+            (true, true, false) => TrianglePlaneSide::Front2Back1 {
+                front: [self.indices[0], self.indices[1]],
+                back: [self.indices[2]],
+            },
+            (true, false, true) => TrianglePlaneSide::Front2Back1 {
+                front: [self.indices[2], self.indices[0]],
+                back: [self.indices[1]],
+            },
+            (true, false, false) => TrianglePlaneSide::Back2Front1 {
+                front: [self.indices[0]],
+                back: [self.indices[1], self.indices[2]],
+            },
+            (false, true, true) => TrianglePlaneSide::Front2Back1 {
+                front: [self.indices[1], self.indices[2]],
+                back: [self.indices[0]],
+            },
+            (false, true, false) => TrianglePlaneSide::Back2Front1 {
+                front: [self.indices[1]],
+                back: [self.indices[2], self.indices[0]],
+            },
+            (false, false, true) => TrianglePlaneSide::Back2Front1 {
+                front: [self.indices[2]],
+                back: [self.indices[0], self.indices[1]],
+            },
+            // a spanning triangle has at least one vertex on each side
+            (true, true, true) | (false, false, false) => unreachable!(),
         }
     }
 }
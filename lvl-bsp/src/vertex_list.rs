@@ -1,4 +1,21 @@
 use lvl_math::Vec3;
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum VertexListError {
+    #[error("expected {expected} normal elements for the vertices, but found {actual}")]
+    NormalCountMismatch { expected: usize, actual: usize },
+    #[error("expected {expected} tangent elements for the vertices, but found {actual}")]
+    TangentCountMismatch { expected: usize, actual: usize },
+    #[error(
+        "expected {expected} texture coordinate elements in channel {channel}, but found {actual}"
+    )]
+    TexcoordCountMismatch {
+        channel: usize,
+        expected: usize,
+        actual: usize,
+    },
+}
 
 /// Indicates how normals and tangents are calculated for the mesh, when splitting it.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -25,6 +42,25 @@ pub struct VertexList {
 }
 
 impl VertexList {
+    /// Creates a vertex list, checking that all attributes have data for every vertex.
+    pub fn new(
+        surface_shading: SurfaceShading,
+        positions: Vec<Vec3>,
+        normals: Option<Vec<f32>>,
+        tangents: Option<Vec<f32>>,
+        texcoords: Vec<Vec<f32>>,
+    ) -> Result<Self, VertexListError> {
+        let vertex_list = Self {
+            surface_shading,
+            positions,
+            normals,
+            tangents,
+            texcoords,
+        };
+        vertex_list.validate()?;
+        Ok(vertex_list)
+    }
+
     pub fn empty(surface_shading: SurfaceShading) -> Self {
         Self {
             surface_shading,
@@ -35,12 +71,69 @@ impl VertexList {
         }
     }
 
+    /// Creates an empty vertex list that has the same attributes as this one,
+    /// with room for `capacity` vertices.
+    pub fn empty_like(&self, capacity: usize) -> Self {
+        Self {
+            surface_shading: self.surface_shading,
+            positions: Vec::with_capacity(capacity),
+            normals: self
+                .normals
+                .as_ref()
+                .map(|_| Vec::with_capacity(capacity * 3)),
+            tangents: self
+                .tangents
+                .as_ref()
+                .map(|_| Vec::with_capacity(capacity * 3)),
+            texcoords: self
+                .texcoords
+                .iter()
+                .map(|_| Vec::with_capacity(capacity * 2))
+                .collect(),
+        }
+    }
+
+    /// Checks that the normals, tangents and texture coordinates are in lock-step with the positions.
+    pub fn validate(&self) -> Result<(), VertexListError> {
+        let vertex_count = self.positions.len();
+
+        if let Some(normals) = &self.normals {
+            if normals.len() != vertex_count * 3 {
+                return Err(VertexListError::NormalCountMismatch {
+                    expected: vertex_count * 3,
+                    actual: normals.len(),
+                });
+            }
+        }
+
+        if let Some(tangents) = &self.tangents {
+            if tangents.len() != vertex_count * 3 {
+                return Err(VertexListError::TangentCountMismatch {
+                    expected: vertex_count * 3,
+                    actual: tangents.len(),
+                });
+            }
+        }
+
+        for (channel, texcoords) in self.texcoords.iter().enumerate() {
+            if texcoords.len() != vertex_count * 2 {
+                return Err(VertexListError::TexcoordCountMismatch {
+                    channel,
+                    expected: vertex_count * 2,
+                    actual: texcoords.len(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn add_vertex(
         &mut self,
         position: Vec3,
         normal: Option<[f32; 3]>,
         tangent: Option<[f32; 3]>,
-        texcoords: Vec<[f32; 2]>,
+        texcoords: &[[f32; 2]],
     ) -> usize {
         self.positions.push(position);
         self.normals.as_mut().map(|n| {
@@ -63,33 +156,119 @@ impl VertexList {
             self.texcoords[index].push(texcoords[index][1]);
         }
 
+        debug_assert_eq!(self.validate(), Ok(()));
+
         self.positions.len() - 1
     }
 
     pub fn transfer_vertex(index: usize, from: &Self, to: &mut Self) -> usize {
         let position = from.positions[index];
-        let mut texcoords = from.texcoords.iter().map(|t| t[index]);
-        let normal = from.normals.as_ref().map(|n| n[index]);
-        let tangent = from.tangents.as_ref().map(|t| t[index]);
+        let mut texcoords = from
+            .texcoords
+            .iter()
+            .map(|t| [t[index * 2 + 0], t[index * 2 + 1]]);
+        let normal = from
+            .normals
+            .as_ref()
+            .map(|n| [n[index * 3 + 0], n[index * 3 + 1], n[index * 3 + 2]]);
+        let tangent = from
+            .tangents
+            .as_ref()
+            .map(|t| [t[index * 3 + 0], t[index * 3 + 1], t[index * 3 + 2]]);
 
         to.positions.push(position);
         to.normals.as_mut().map(|n| {
             if let Some(normal) = normal {
-                n.push(normal);
+                n.extend_from_slice(&normal);
             }
         });
         to.tangents.as_mut().map(|t| {
             if let Some(tangent) = tangent {
-                t.push(tangent);
+                t.extend_from_slice(&tangent);
             }
         });
 
         for t in &mut to.texcoords {
             if let Some(texcoord) = texcoords.next() {
-                t.push(texcoord);
+                t.extend_from_slice(&texcoord);
             }
         }
 
         to.positions.len() - 1
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_validate() {
+        let vertex_list = VertexList::new(
+            SurfaceShading::Flat,
+            vec![Vec3::ZERO, Vec3::ONE],
+            Some(vec![0.0, 0.0, 1.0, 0.0, 0.0, 1.0]),
+            None,
+            vec![vec![0.0, 0.0, 1.0, 1.0]],
+        );
+        assert!(vertex_list.is_ok());
+    }
+
+    #[test]
+    fn check_validate_mismatched_lengths() {
+        let vertex_list = VertexList::new(
+            SurfaceShading::Flat,
+            vec![Vec3::ZERO, Vec3::ONE],
+            Some(vec![0.0, 0.0, 1.0]),
+            None,
+            vec![],
+        );
+        assert_eq!(
+            vertex_list,
+            Err(VertexListError::NormalCountMismatch {
+                expected: 6,
+                actual: 3
+            })
+        );
+
+        let vertex_list = VertexList::new(
+            SurfaceShading::Flat,
+            vec![Vec3::ZERO, Vec3::ONE],
+            None,
+            Some(vec![1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0]),
+            vec![],
+        );
+        assert_eq!(
+            vertex_list,
+            Err(VertexListError::TangentCountMismatch {
+                expected: 6,
+                actual: 9
+            })
+        );
+
+        let vertex_list = VertexList::new(
+            SurfaceShading::Flat,
+            vec![Vec3::ZERO, Vec3::ONE],
+            None,
+            None,
+            vec![vec![0.0, 0.0, 1.0, 1.0], vec![0.0, 0.0]],
+        );
+        assert_eq!(
+            vertex_list,
+            Err(VertexListError::TexcoordCountMismatch {
+                channel: 1,
+                expected: 4,
+                actual: 2
+            })
+        );
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic]
+    fn check_add_vertex_missing_normal() {
+        let mut vertex_list = VertexList::empty(SurfaceShading::Flat);
+        vertex_list.normals = Some(vec![]);
+        vertex_list.add_vertex(Vec3::ZERO, None, None, &[]);
+    }
+}
@@ -0,0 +1,120 @@
+//! Counts the heap allocations made while splitting a large mesh.
+//! This lives in its own test binary, since it installs a counting global allocator.
+
+use lvl_bsp::{Mesh, SplitScratch, SurfaceShading, Triangle, VertexList, DEFAULT_PLANE_EPSILON};
+use lvl_math::{Plane, Vec3};
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    num::NonZeroU32,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+struct CountingAllocator;
+
+static ALLOCATION_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATION_COUNT.fetch_add(1, Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATION_COUNT.fetch_add(1, Ordering::SeqCst);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// Makes a grid of `width` x `height` quads on the xy plane, two triangles each.
+fn make_grid_mesh(width: usize, height: usize) -> Mesh {
+    let mut vertex_list = VertexList {
+        surface_shading: SurfaceShading::Smooth,
+        positions: vec![],
+        normals: Some(vec![]),
+        tangents: None,
+        texcoords: vec![vec![]],
+    };
+
+    for y in 0..=height {
+        for x in 0..=width {
+            vertex_list.add_vertex(
+                Vec3::new(x as f32, y as f32, 0.0),
+                Some([0.0, 0.0, 1.0]),
+                None,
+                &[[x as f32 / width as f32, y as f32 / height as f32]],
+            );
+        }
+    }
+
+    let mut triangles = Vec::with_capacity(width * height * 2);
+
+    for y in 0..height {
+        for x in 0..width {
+            let index = y * (width + 1) + x;
+            triangles.push(Triangle {
+                indices: [index, index + 1, index + width + 2],
+            });
+            triangles.push(Triangle {
+                indices: [index, index + width + 2, index + width + 1],
+            });
+        }
+    }
+
+    Mesh::new(
+        NonZeroU32::new(1).unwrap(),
+        NonZeroU32::new(1).unwrap(),
+        vertex_list,
+        triangles,
+    )
+}
+
+#[test]
+fn check_split_by_plane_with_scratch_allocations() {
+    let mesh = make_grid_mesh(100, 50);
+    assert_eq!(mesh.triangles.len(), 10_000);
+
+    // cuts through a column of quads, so both sides get spanning triangles
+    let plane = Plane::new(Vec3::new(1.0, 0.0, 0.0), Vec3::new(50.5, 0.0, 0.0));
+    let mut scratch = SplitScratch::new();
+
+    // warm up the scratch buffers
+    mesh.clone()
+        .split_by_plane_with_scratch(plane, DEFAULT_PLANE_EPSILON, &mut scratch)
+        .unwrap();
+
+    let splitting_mesh = mesh.clone();
+    let allocation_count_before = ALLOCATION_COUNT.load(Ordering::SeqCst);
+    let splitted = splitting_mesh
+        .split_by_plane_with_scratch(plane, DEFAULT_PLANE_EPSILON, &mut scratch)
+        .unwrap();
+    let allocation_count = ALLOCATION_COUNT.load(Ordering::SeqCst) - allocation_count_before;
+
+    assert!(!splitted.front.is_empty());
+    assert!(!splitted.back.is_empty());
+
+    // only the output buffers should be allocated, each of them exactly once:
+    // positions, normals, one texcoord channel and the vertex texcoord list, per side,
+    // plus the triangle lists
+    assert!(
+        allocation_count <= 10,
+        "too many allocations: {}",
+        allocation_count
+    );
+
+    let splitting_mesh = mesh.clone();
+    let allocation_count_before = ALLOCATION_COUNT.load(Ordering::SeqCst);
+    splitting_mesh
+        .split_by_plane(plane, DEFAULT_PLANE_EPSILON)
+        .unwrap();
+    let unscratched_allocation_count =
+        ALLOCATION_COUNT.load(Ordering::SeqCst) - allocation_count_before;
+
+    assert!(allocation_count < unscratched_allocation_count);
+}
@@ -1,36 +1,57 @@
+pub mod clipboard;
 pub mod driver;
 pub mod input;
 pub mod phases;
 pub mod screen_size;
 pub mod time;
 
-use self::{input::Input, screen_size::ScreenSize, time::Time};
-use crate::gfx::GfxContext;
+use self::{clipboard::Clipboard, input::Input, screen_size::ScreenSize, time::Time};
+use crate::{
+    gfx::{
+        elements::Material, FrameCapture, FrameCaptureError, GfxContext, GfxContextCreationError,
+    },
+    resource::ResourceRegistry,
+};
+use lvl_resource::{ResourceFile, ResourceLoadOrderError};
+use pollster::FutureExt;
 use std::{
-    cell::{Ref, RefCell, RefMut},
+    cell::{Cell, Ref, RefCell, RefMut},
     sync::Arc,
 };
+use wgpu::TextureView;
 use winit::dpi::PhysicalSize;
 
 pub struct Context<'window> {
-    gfx_ctx: Arc<GfxContext<'window>>,
+    gfx_ctx: RefCell<GfxContext<'window>>,
     screen_size: RefCell<ScreenSize>,
     input: RefCell<Input>,
     time: RefCell<Time>,
+    clipboard: RefCell<Clipboard>,
+    resource_registry: RefCell<ResourceRegistry>,
+    is_frame_capture_requested: Cell<bool>,
+    frame_capture: RefCell<Option<Result<FrameCapture, FrameCaptureError>>>,
+    reloaded_resources: RefCell<Vec<String>>,
 }
 
 impl<'window> Context<'window> {
     pub(crate) fn new(gfx_ctx: GfxContext<'window>, screen_size: PhysicalSize<u32>) -> Self {
         Self {
-            gfx_ctx: Arc::new(gfx_ctx),
+            gfx_ctx: RefCell::new(gfx_ctx),
             screen_size: RefCell::new(ScreenSize::new(screen_size)),
             input: RefCell::new(Input::new()),
             time: RefCell::new(Time::new()),
+            clipboard: RefCell::new(Clipboard::new()),
+            resource_registry: RefCell::new(ResourceRegistry::new()),
+            is_frame_capture_requested: Cell::new(false),
+            frame_capture: RefCell::new(None),
+            reloaded_resources: RefCell::new(Vec::new()),
         }
     }
 
-    pub fn gfx_ctx(&self) -> &GfxContext<'window> {
-        &self.gfx_ctx
+    /// Returns the gfx context. It is replaced when the device is lost, so do not keep it
+    /// across frames.
+    pub fn gfx_ctx(&self) -> Ref<'_, GfxContext<'window>> {
+        self.gfx_ctx.borrow()
     }
 
     pub fn screen_size(&self) -> Ref<ScreenSize> {
@@ -57,7 +78,260 @@ impl<'window> Context<'window> {
         self.time.borrow_mut()
     }
 
+    pub fn clipboard(&self) -> RefMut<Clipboard> {
+        self.clipboard.borrow_mut()
+    }
+
+    pub fn resource_registry(&self) -> Ref<ResourceRegistry> {
+        self.resource_registry.borrow()
+    }
+
+    pub fn resource_registry_mut(&self) -> RefMut<ResourceRegistry> {
+        self.resource_registry.borrow_mut()
+    }
+
+    /// Registers the textures, shaders and materials of the file in the resource registry; see
+    /// [`ResourceRegistry::load`].
+    pub fn load_resources(&self, file: &ResourceFile) -> Result<(), ResourceLoadOrderError> {
+        self.resource_registry
+            .borrow_mut()
+            .load(file, &self.gfx_ctx.borrow())
+    }
+
+    /// Replaces the changed textures, shaders and materials of the resource registry with the ones
+    /// of the file; see [`ResourceRegistry::reload`]. The driver is notified of every replaced
+    /// resource by [`driver::Driver::on_resource_reloaded`] before the next update.
+    pub fn reload_resources(&self, file: &ResourceFile) -> Result<(), ResourceLoadOrderError> {
+        let reloaded = self
+            .resource_registry
+            .borrow_mut()
+            .reload(file, &self.gfx_ctx.borrow())?;
+        self.reloaded_resources.borrow_mut().extend(reloaded);
+        Ok(())
+    }
+
+    /// Returns the texture from the resource registry, uploading it if this is the first access.
+    pub fn get_texture(&self, name: &str) -> Option<Arc<TextureView>> {
+        self.resource_registry
+            .borrow_mut()
+            .get_texture(name, &self.gfx_ctx.borrow())
+    }
+
+    /// Returns the material from the resource registry, making it if this is the first access.
+    pub fn get_material(&self, name: &str) -> Option<Arc<Material>> {
+        self.resource_registry
+            .borrow_mut()
+            .get_material(name, &self.gfx_ctx.borrow())
+    }
+
+    /// Uploads the given textures of the resource registry now instead of on their first access.
+    pub fn preload_textures<'a>(&self, names: impl IntoIterator<Item = &'a str>) {
+        self.resource_registry
+            .borrow_mut()
+            .preload_textures(names, &self.gfx_ctx.borrow());
+    }
+
+    /// Makes the gfx context again after its device is lost, and uploads the resources of the
+    /// registry to the new device; see [`ResourceRegistry::reupload`]. The driver is notified of
+    /// every re-uploaded resource by [`driver::Driver::on_resource_reloaded`] before the next
+    /// update, as by [`Self::reload_resources`].
+    pub(crate) fn recreate_gfx_ctx(&self) -> Result<(), GfxContextCreationError> {
+        let mut gfx_ctx = self.gfx_ctx.borrow_mut();
+        gfx_ctx.recreate().block_on()?;
+
+        let reuploaded = self.resource_registry.borrow_mut().reupload(&gfx_ctx);
+        self.reloaded_resources.borrow_mut().extend(reuploaded);
+        Ok(())
+    }
+
+    /// Requests the next rendered frame to be read back.
+    /// The result can be taken by [`Self::take_frame_capture`] once the frame is rendered,
+    /// e.g. in [`driver::Driver::on_after_render`].
+    pub fn request_frame_capture(&self) {
+        self.is_frame_capture_requested.set(true);
+    }
+
+    /// Takes the result of the last requested frame capture, if it is done.
+    pub fn take_frame_capture(&self) -> Option<Result<FrameCapture, FrameCaptureError>> {
+        self.frame_capture.borrow_mut().take()
+    }
+
+    pub(crate) fn take_frame_capture_request(&self) -> bool {
+        self.is_frame_capture_requested.replace(false)
+    }
+
+    pub(crate) fn set_frame_capture(&self, frame_capture: Result<FrameCapture, FrameCaptureError>) {
+        *self.frame_capture.borrow_mut() = Some(frame_capture);
+    }
+
+    pub(crate) fn take_reloaded_resources(&self) -> Vec<String> {
+        std::mem::take(&mut *self.reloaded_resources.borrow_mut())
+    }
+
     pub(crate) fn update_screen_size(&self, screen_size: PhysicalSize<u32>) {
         self.screen_size.borrow_mut().set_size(screen_size);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gfx::{
+        elements::{make_material_render_state, make_shader_source_descriptor},
+        test_gfx_ctx,
+    };
+    use lvl_resource::{
+        MaterialProperty, MaterialPropertyValue, MaterialSource, Resource, ResourceFileVersion,
+        ResourceKind, ShaderBinding, ShaderBindingKind, ShaderSource, ShaderSourceDescriptor,
+        TextureElement, TextureElementSamplingMode, TextureElementSize,
+        TextureElementTextureFormat, TextureElementWrappingMode, TextureKind, TextureSource,
+    };
+    use wgpu::{TextureSampleType, TextureViewDimension};
+
+    const SHADER: &str = r#"
+        @group(1) @binding(0) var diffuse: texture_2d<f32>;
+
+        @vertex
+        fn vs_main() -> @builtin(position) vec4<f32> {
+            return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+        }
+
+        @fragment
+        fn fs_main() -> @location(0) vec4<f32> {
+            return textureLoad(diffuse, vec2<i32>(0, 0), 0);
+        }
+    "#;
+
+    fn make_resource_file() -> ResourceFile {
+        let resource = |name: &str, kind| Resource {
+            name: name.to_owned(),
+            kind,
+            metadata: Default::default(),
+        };
+
+        ResourceFile::new(
+            ResourceFileVersion::CURRENT,
+            vec![
+                resource(
+                    "texture",
+                    ResourceKind::Texture(TextureSource::new(TextureKind::Single(
+                        TextureElement {
+                            data: vec![255; 2 * 2 * 4],
+                            size: TextureElementSize {
+                                width: 2,
+                                height: 2,
+                            },
+                            texture_format: TextureElementTextureFormat::RGBA8Unorm,
+                            sampling_mode: TextureElementSamplingMode::Point,
+                            wrapping_mode_u: TextureElementWrappingMode::Clamp,
+                            wrapping_mode_v: TextureElementWrappingMode::Clamp,
+                            mip_data: vec![],
+                        },
+                    ))),
+                ),
+                resource(
+                    "shader",
+                    ResourceKind::Shader(ShaderSource::new(ShaderSourceDescriptor {
+                        bindings: vec![ShaderBinding {
+                            name: "diffuse".to_owned(),
+                            group: 1,
+                            binding: 0,
+                            kind: ShaderBindingKind::Texture {
+                                sample_type: TextureSampleType::Float { filterable: true },
+                                view_dimension: TextureViewDimension::D2,
+                                multisampled: false,
+                            },
+                        }],
+                        ..make_shader_source_descriptor(SHADER)
+                    })),
+                ),
+                resource(
+                    "material",
+                    ResourceKind::Material(MaterialSource::new(
+                        "shader".to_owned(),
+                        make_material_render_state(),
+                        vec![MaterialProperty {
+                            name: "diffuse".to_owned(),
+                            value: MaterialPropertyValue::Texture {
+                                texture_name: "texture".to_owned(),
+                            },
+                        }],
+                    )),
+                ),
+            ],
+        )
+    }
+
+    #[test]
+    fn check_material_textures_uploaded_on_first_use() {
+        let gfx_ctx = match test_gfx_ctx() {
+            Some(gfx_ctx) => gfx_ctx,
+            None => {
+                return;
+            }
+        };
+        let context = Context::new(gfx_ctx, PhysicalSize::new(4, 4));
+        context.load_resources(&make_resource_file()).unwrap();
+
+        let registry = context.resource_registry();
+        assert!(registry.get_shader("shader").is_some());
+        assert!(!registry.materials().is_loaded("material"));
+        assert!(!registry.textures().is_loaded("texture"));
+        drop(registry);
+
+        let material = context.get_material("material").unwrap();
+        assert!(context.resource_registry().textures().is_loaded("texture"));
+        assert!(matches!(
+            material
+                .get_property("diffuse")
+                .and_then(|property| property.value()),
+            Some(crate::gfx::elements::MaterialPropertyValue::Texture(_))
+        ));
+        assert!(Arc::ptr_eq(
+            &context.get_material("material").unwrap(),
+            &material
+        ));
+    }
+
+    #[test]
+    fn check_recreate_reuploads_resources() {
+        let gfx_ctx = match test_gfx_ctx() {
+            Some(gfx_ctx) => gfx_ctx,
+            None => {
+                return;
+            }
+        };
+        let context = Context::new(gfx_ctx, PhysicalSize::new(4, 4));
+        context.load_resources(&make_resource_file()).unwrap();
+        assert!(context.get_texture("texture").is_some());
+
+        let shader = context.resource_registry().get_shader("shader").unwrap();
+        let material = context.get_material("material").unwrap();
+
+        context.gfx_ctx().lose_device();
+        assert!(context.gfx_ctx().is_device_lost());
+        context.recreate_gfx_ctx().unwrap();
+        assert!(!context.gfx_ctx().is_device_lost());
+
+        assert_eq!(
+            context.take_reloaded_resources(),
+            ["material", "shader", "texture"]
+        );
+
+        let registry = context.resource_registry();
+        assert!(!Arc::ptr_eq(
+            &registry.get_shader("shader").unwrap(),
+            &shader
+        ));
+        // textures and materials are uploaded again on their next access
+        assert!(!registry.textures().is_loaded("texture"));
+        assert!(!registry.materials().is_loaded("material"));
+        drop(registry);
+
+        assert!(!Arc::ptr_eq(
+            &context.get_material("material").unwrap(),
+            &material
+        ));
+        assert!(context.resource_registry().textures().is_loaded("texture"));
+    }
+}
@@ -0,0 +1,84 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ClipboardError {
+    #[error("the system clipboard is not available: {0}")]
+    Unavailable(String),
+    #[error("the clipboard does not contain text")]
+    NoText,
+    #[error("failed to access the clipboard: {0}")]
+    Access(String),
+}
+
+impl From<arboard::Error> for ClipboardError {
+    fn from(error: arboard::Error) -> Self {
+        match error {
+            arboard::Error::ContentNotAvailable => Self::NoText,
+            arboard::Error::ClipboardNotSupported | arboard::Error::ClipboardOccupied => {
+                Self::Unavailable(error.to_string())
+            }
+            error => Self::Access(error.to_string()),
+        }
+    }
+}
+
+/// Where the [`Clipboard`] reads and writes the text.
+pub trait ClipboardBackend {
+    fn get_text(&mut self) -> Result<String, ClipboardError>;
+    fn set_text(&mut self, text: &str) -> Result<(), ClipboardError>;
+}
+
+impl ClipboardBackend for arboard::Clipboard {
+    fn get_text(&mut self) -> Result<String, ClipboardError> {
+        Ok(arboard::Clipboard::get_text(self)?)
+    }
+
+    fn set_text(&mut self, text: &str) -> Result<(), ClipboardError> {
+        Ok(arboard::Clipboard::set_text(self, text)?)
+    }
+}
+
+/// Text access to the system clipboard. The system clipboard is opened on the first access,
+/// so that windows without a clipboard do not fail until they use it.
+pub struct Clipboard {
+    backend: Option<Box<dyn ClipboardBackend>>,
+}
+
+impl Clipboard {
+    pub fn new() -> Self {
+        Self { backend: None }
+    }
+
+    /// Makes a clipboard that uses the given backend instead of the system clipboard.
+    pub fn with_backend(backend: impl ClipboardBackend + 'static) -> Self {
+        Self {
+            backend: Some(Box::new(backend)),
+        }
+    }
+
+    pub fn set_backend(&mut self, backend: impl ClipboardBackend + 'static) {
+        self.backend = Some(Box::new(backend));
+    }
+
+    pub fn get_text(&mut self) -> Result<String, ClipboardError> {
+        self.backend()?.get_text()
+    }
+
+    pub fn set_text(&mut self, text: &str) -> Result<(), ClipboardError> {
+        self.backend()?.set_text(text)
+    }
+
+    fn backend(&mut self) -> Result<&mut dyn ClipboardBackend, ClipboardError> {
+        if self.backend.is_none() {
+            self.backend = Some(Box::new(arboard::Clipboard::new()?));
+        }
+
+        Ok(self.backend.as_deref_mut().unwrap())
+    }
+}
+
+impl Default for Clipboard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,12 +1,15 @@
 use super::Context;
-use crate::scene::Scene;
-use winit::window::Window;
+use crate::{gfx::RenderGraph, scene::Scene};
+use winit::{event::WindowEvent, window::Window};
 
 pub trait Driver
 where
     Self: 'static,
 {
     fn on_init(&mut self, _context: &Context, _window: &Window, _scene: &mut Scene) {}
+    /// Called after `on_init` to insert custom passes into the render graph, and again with a new
+    /// render graph after the device is recreated.
+    fn on_configure_render(&mut self, _context: &Context, _render_graph: &mut RenderGraph) {}
     fn on_finish(&mut self, _context: &Context, _window: &Window, _scene: &mut Scene) {}
     fn on_before_update(&mut self, _context: &Context, _window: &Window, _scene: &mut Scene) {}
     fn on_after_update(&mut self, _context: &Context, _window: &Window, _scene: &mut Scene) {}
@@ -14,4 +17,35 @@ where
     fn on_after_late_update(&mut self, _context: &Context, _window: &Window, _scene: &mut Scene) {}
     fn on_before_render(&mut self, _context: &Context, _window: &Window, _scene: &mut Scene) {}
     fn on_after_render(&mut self, _context: &Context, _window: &Window, _scene: &mut Scene) {}
+
+    /// Called before the update for every resource replaced by [`Context::reload_resources`],
+    /// so that the objects made from it can be rebuilt, e.g. by [`Material::reload_from_source`].
+    ///
+    /// [`Material::reload_from_source`]: crate::gfx::elements::Material::reload_from_source
+    fn on_resource_reloaded(
+        &mut self,
+        _context: &Context,
+        _window: &Window,
+        _scene: &mut Scene,
+        _name: &str,
+    ) {
+    }
+
+    /// Called after the device was lost and the gfx context was made again, before the next
+    /// update. The resources of the registry have been uploaded again and are reported by
+    /// [`Self::on_resource_reloaded`]; objects made from sources directly, such as the models of
+    /// the scene, still refer to the lost device and must be made again here.
+    fn on_device_recreated(&mut self, _context: &Context, _window: &Window, _scene: &mut Scene) {}
+
+    /// Called for every window event before the looper handles it. Returning `true` consumes
+    /// the event, so that input events (keyboard, mouse, IME) do not reach the `Input` manager.
+    /// Events the looper depends on, such as resizing and redraw requests, are always handled.
+    fn on_window_event(
+        &mut self,
+        _context: &Context,
+        _window: &Window,
+        _event: &WindowEvent,
+    ) -> bool {
+        false
+    }
 }
@@ -1,18 +1,22 @@
 use std::collections::HashMap;
 use winit::{
-    event::{ElementState, KeyEvent},
+    event::{ElementState, Ime, KeyEvent},
     keyboard::PhysicalKey,
 };
 
 #[derive(Debug)]
 pub struct Input {
     keys: HashMap<String, InputKey>,
+    text_input: String,
+    ime_preedit: String,
 }
 
 impl Input {
     pub fn new() -> Self {
         Self {
             keys: HashMap::new(),
+            text_input: String::new(),
+            ime_preedit: String::new(),
         }
     }
 
@@ -24,11 +28,24 @@ impl Input {
         self.keys.insert(name.into(), InputKey::new(key));
     }
 
+    /// Returns the text that has been typed or committed through the IME during this frame.
+    /// Note that IME events are only delivered after `Window::set_ime_allowed(true)` is called.
+    pub fn text_input_this_frame(&self) -> &str {
+        &self.text_input
+    }
+
+    /// Returns the text that is being composed through the IME, but has not been committed yet.
+    pub fn ime_preedit(&self) -> &str {
+        &self.ime_preedit
+    }
+
     /// It must be called at the end of each frame.
     pub(crate) fn reset_current_frame_state(&mut self) {
         for input_key in self.keys.values_mut() {
             input_key.is_pressed_frame = false;
         }
+
+        self.text_input.clear();
     }
 
     pub(crate) fn handle_key_event(&mut self, event: &KeyEvent) {
@@ -40,6 +57,35 @@ impl Input {
             input_key.is_pressed = event.state == ElementState::Pressed;
             input_key.is_pressed_frame = event.state == ElementState::Pressed;
         }
+
+        if event.state == ElementState::Pressed {
+            if let Some(text) = &event.text {
+                self.handle_received_character(text);
+            }
+        }
+    }
+
+    /// Control characters (backspace, enter, escape, ...) are not part of the text input;
+    /// they are available as key events instead.
+    pub(crate) fn handle_received_character(&mut self, text: &str) {
+        self.text_input
+            .extend(text.chars().filter(|char| !char.is_control()));
+    }
+
+    pub(crate) fn handle_ime(&mut self, ime: &Ime) {
+        match ime {
+            Ime::Enabled | Ime::Disabled => {
+                self.ime_preedit.clear();
+            }
+            Ime::Preedit(text, _) => {
+                self.ime_preedit.clear();
+                self.ime_preedit.push_str(text);
+            }
+            Ime::Commit(text) => {
+                self.ime_preedit.clear();
+                self.handle_received_character(text);
+            }
+        }
     }
 }
 
@@ -59,3 +105,44 @@ impl InputKey {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_text_input_accumulates_and_resets() {
+        let mut input = Input::new();
+
+        input.handle_received_character("a");
+        input.handle_received_character("b");
+        input.handle_received_character("\u{8}");
+        input.handle_received_character("c");
+
+        assert_eq!(input.text_input_this_frame(), "abc");
+
+        input.reset_current_frame_state();
+
+        assert_eq!(input.text_input_this_frame(), "");
+
+        input.handle_received_character("d");
+
+        assert_eq!(input.text_input_this_frame(), "d");
+    }
+
+    #[test]
+    fn check_ime_commit_appends_text_input() {
+        let mut input = Input::new();
+
+        input.handle_received_character("a");
+        input.handle_ime(&Ime::Preedit("啊".to_owned(), Some((3, 3))));
+
+        assert_eq!(input.text_input_this_frame(), "a");
+        assert_eq!(input.ime_preedit(), "啊");
+
+        input.handle_ime(&Ime::Commit("啊不".to_owned()));
+
+        assert_eq!(input.text_input_this_frame(), "a啊不");
+        assert_eq!(input.ime_preedit(), "");
+    }
+}
@@ -1,6 +1,7 @@
+use super::common::get_all_cameras;
 use crate::{
     context::{driver::Driver, Context},
-    scene::Scene,
+    scene::{components::update_billboards, Scene},
 };
 use winit::window::Window;
 
@@ -16,6 +17,13 @@ pub fn late_update(
 
     scene.trigger_late_update();
 
+    // billboards face the first camera, after the controllers have moved it
+    scene.with_proxy(|proxy| {
+        if let Some(&camera_id) = get_all_cameras(proxy).first() {
+            update_billboards(proxy, camera_id);
+        }
+    });
+
     if let Some(driver) = driver {
         driver.on_after_late_update(&ctx, window, scene);
     }
@@ -1,30 +1,51 @@
 mod collects;
 mod render_command;
+mod render_mesh_renderer;
 mod render_pmx_model_renderer;
 
 use self::{
-    collects::collect_components,
+    collects::collect_components, render_mesh_renderer::build_render_command_mesh_renderer,
     render_pmx_model_renderer::build_render_command_pmx_model_renderer,
 };
 use super::common::get_all_cameras;
 use crate::{
     context::{driver::Driver, Context},
-    gfx::{ClearMode, Frame, InstanceDataProvider, RenderPassTarget},
+    gfx::{
+        ClearMode, Frame, InstanceDataProvider, PendingFrameCapture, RenderGraph, RenderGraphPlan,
+        RenderPassTarget, SurfaceRecovery,
+    },
     scene::{
-        components::{Camera, CameraClearMode, Light, PmxModelRenderer},
+        components::{Camera, CameraClearMode, Light, MeshRenderer, PmxModelRenderer},
         ObjectId, Scene, SceneProxy,
     },
 };
 use lvl_math::{Vec3, Vec4};
-use wgpu::{Color, TextureView};
+use wgpu::{Color, SurfaceError, TextureView};
 use winit::window::Window;
 
 pub fn render(
     window: &Window,
     ctx: &Context,
     scene: &mut Scene,
+    render_graph: &mut RenderGraph,
+    render_graph_plan: &RenderGraphPlan,
     driver: &mut Option<Box<dyn Driver>>,
-) {
+) -> Result<(), SurfaceError> {
+    let gfx_ctx = ctx.gfx_ctx();
+
+    // a lost or outdated surface skips the frame, so that the driver sees no half-rendered one
+    let surface_texture = match gfx_ctx.obtain_surface_view() {
+        Ok(surface_texture) => surface_texture,
+        Err(error) => match SurfaceRecovery::from_error(&error) {
+            SurfaceRecovery::Reconfigure => {
+                gfx_ctx.reconfigure_surface();
+                return Ok(());
+            }
+            SurfaceRecovery::SkipFrame => return Ok(()),
+            SurfaceRecovery::Fatal => return Err(error),
+        },
+    };
+
     if let Some(driver) = driver {
         driver.on_before_render(&ctx, window, scene);
     }
@@ -34,12 +55,60 @@ pub fn render(
     // update_camera_transform_buffer_system.run_now(&self.ctx.world());
     // render_system.run_now(&self.ctx.world());
 
-    let surface_texture = ctx.gfx_ctx().obtain_surface_view().unwrap();
     let surface_texture_view = surface_texture.texture.create_view(&Default::default());
 
-    let mut frame = ctx.gfx_ctx().begin_frame();
+    let mut frame = gfx_ctx.begin_frame();
+
+    render_graph.execute(&gfx_ctx, &mut frame, &surface_texture_view, |frame| {
+        render_main_pass(ctx, scene, render_graph_plan, &surface_texture_view, frame)
+    });
+
+    let pending_frame_capture = if ctx.take_frame_capture_request() {
+        Some(PendingFrameCapture::record(
+            &surface_texture.texture,
+            &mut frame,
+            &gfx_ctx.device,
+        ))
+    } else {
+        None
+    };
+
+    gfx_ctx.end_frame(frame);
+
+    if let Some(pending_frame_capture) = pending_frame_capture {
+        ctx.set_frame_capture(
+            pending_frame_capture.and_then(|pending| pending.read(&gfx_ctx.device)),
+        );
+    }
+
+    window.pre_present_notify();
+    surface_texture.present();
+
+    if let Some(driver) = driver {
+        driver.on_after_render(&ctx, window, scene);
+    }
+
+    Ok(())
+}
 
+fn render_main_pass(
+    ctx: &Context,
+    scene: &mut Scene,
+    render_graph_plan: &RenderGraphPlan,
+    surface_texture_view: &TextureView,
+    frame: &mut Frame,
+) -> f32 {
+    let gfx_ctx = ctx.gfx_ctx();
+    let global_texture_set = gfx_ctx.global_texture_set.borrow();
+    let target_texture_view = match &global_texture_set.main_color {
+        Some(main_color) if render_graph_plan.is_main_color_offscreen() => &main_color.texture_view,
+        _ => surface_texture_view,
+    };
+
+    // the exposure of the last rendered camera is used for tonemapping
     scene.with_proxy(|proxy| {
+        let mut exposure = 1.0;
+
         for camera_id in get_all_cameras(proxy) {
             let screen_size = ctx.screen_size().size();
 
@@ -54,38 +123,31 @@ pub fn render(
                 screen_size.width as f32 / screen_size.height as f32,
                 &camera_transform_matrix.inversed(),
             );
+            exposure = camera.exposure;
 
-            ctx.gfx_ctx()
-                .uniform_bind_group_provider
-                .update_camera_matrix(
-                    &camera_projection_matrix,
-                    camera_world_pos,
-                    camera_transform_matrix,
-                    &ctx.gfx_ctx().queue,
-                );
+            gfx_ctx.uniform_bind_group_provider.update_camera_matrix(
+                &camera_projection_matrix,
+                camera_world_pos,
+                camera_transform_matrix,
+                &gfx_ctx.queue,
+            );
 
-            render_pass_stage_opaque(ctx, camera_id, &surface_texture_view, &mut frame, proxy);
+            render_pass_stage_opaque(ctx, camera_id, target_texture_view, frame, proxy);
             // render_pass_stage_ui(ctx, camera_id, &surface_texture_view, &mut frame, proxy);
         }
-    });
-
-    ctx.gfx_ctx().end_frame(frame);
-
-    window.pre_present_notify();
-    surface_texture.present();
 
-    if let Some(driver) = driver {
-        driver.on_after_render(&ctx, window, scene);
-    }
+        exposure
+    })
 }
 
 fn render_pass_stage_opaque(
     ctx: &Context,
     camera_id: ObjectId,
-    surface_texture_view: &TextureView,
+    target_texture_view: &TextureView,
     frame: &mut Frame,
     scene: &mut SceneProxy,
 ) {
+    let gfx_ctx = ctx.gfx_ctx();
     let camera = scene
         .find_object_by_id(camera_id)
         .unwrap()
@@ -95,7 +157,7 @@ fn render_pass_stage_opaque(
         scene.transform_matrix(camera_id).unwrap() * Vec4::new(0.0, 0.0, 0.0, 1.0);
 
     let mut commands = Vec::new();
-    let global_texture_set = ctx.gfx_ctx().global_texture_set.borrow();
+    let global_texture_set = gfx_ctx.global_texture_set.borrow();
 
     if let Some(ids) = scene.find_object_ids_by_component_type::<PmxModelRenderer>() {
         let mut renderers_and_distances = Vec::with_capacity(ids.len());
@@ -117,17 +179,36 @@ fn render_pass_stage_opaque(
             .sort_unstable_by(|(a, _, _), (b, _, _)| f32::partial_cmp(a, b).unwrap());
 
         for (_, id, renderer) in renderers_and_distances {
+            // the world matrix kept up to date by the hierarchy is the instance transform,
+            // so moving an object or any of its parents moves the rendered model
             let transform_matrix = scene.transform_matrix(id).unwrap();
             commands.extend(build_render_command_pmx_model_renderer(
-                global_texture_set.msaa_sample_count,
+                &global_texture_set,
                 transform_matrix,
                 renderer,
                 &InstanceDataProvider,
-                ctx.gfx_ctx(),
+                &gfx_ctx,
             ));
         }
     }
 
+    if let Some(ids) = scene.find_object_ids_by_component_type::<MeshRenderer>() {
+        for id in ids {
+            let object = scene.find_object_by_id(*id).unwrap();
+            let transform_matrix = scene.transform_matrix(*id).unwrap();
+
+            for renderer in object.find_components_by_type::<MeshRenderer>() {
+                commands.extend(build_render_command_mesh_renderer(
+                    &global_texture_set,
+                    transform_matrix,
+                    renderer,
+                    &InstanceDataProvider,
+                    &gfx_ctx,
+                ));
+            }
+        }
+    }
+
     let color_texture_view = global_texture_set
         .color
         .as_ref()
@@ -153,9 +234,9 @@ fn render_pass_stage_opaque(
             CameraClearMode::Keep => ClearMode::Keep,
         },
         &[Some(RenderPassTarget {
-            view: color_texture_view.unwrap_or(surface_texture_view),
+            view: color_texture_view.unwrap_or(target_texture_view),
             resolve_target: if color_texture_view.is_some() {
-                Some(surface_texture_view)
+                Some(target_texture_view)
             } else {
                 None
             },
@@ -168,7 +249,7 @@ fn render_pass_stage_opaque(
         }),
     );
 
-    let bind_group = ctx.gfx_ctx().uniform_bind_group_provider.bind_group();
+    let bind_group = gfx_ctx.uniform_bind_group_provider.bind_group();
 
     for command in &commands {
         command.render(&mut render_pass, bind_group);
@@ -204,15 +285,16 @@ fn _test_render(
     scene: &mut Scene,
     driver: &mut Option<Box<dyn Driver>>,
 ) {
-    let bind_group = ctx.gfx_ctx().uniform_bind_group_provider.bind_group();
+    let gfx_ctx = ctx.gfx_ctx();
+    let bind_group = gfx_ctx.uniform_bind_group_provider.bind_group();
 
-    let surface_texture = ctx.gfx_ctx().obtain_surface_view().unwrap();
+    let surface_texture = gfx_ctx.obtain_surface_view().unwrap();
     let surface_texture_view = surface_texture.texture.create_view(&Default::default());
 
-    let global_texture_set = ctx.gfx_ctx().global_texture_set.borrow();
+    let global_texture_set = gfx_ctx.global_texture_set.borrow();
     let depth_texture_view = &global_texture_set.depth_stencil.texture_view;
 
-    let mut frame = ctx.gfx_ctx().begin_frame();
+    let mut frame = gfx_ctx.begin_frame();
 
     scene.with_proxy(|scene| {
         let cameras = collect_components::<Camera>(scene);
@@ -260,10 +342,10 @@ fn _test_render(
 
             for renderer in &pmx_model_renderers {
                 let pipelines = renderer.component.construct_render_pipelines(
-                    global_texture_set.msaa_sample_count,
+                    &global_texture_set,
                     InstanceDataProvider.instance_data_size(),
                     InstanceDataProvider.instance_data_attributes(),
-                    ctx.gfx_ctx(),
+                    &gfx_ctx,
                 );
             }
 
@@ -0,0 +1,316 @@
+use super::render_command::RenderCommand;
+use crate::{
+    gfx::{GfxContext, GlobalTextureSet, InstanceDataProvider},
+    scene::components::MeshRenderer,
+};
+use lvl_math::Mat4;
+
+pub fn build_render_command_mesh_renderer<'r>(
+    global_texture_set: &GlobalTextureSet,
+    transform_matrix: &Mat4,
+    renderer: &'r MeshRenderer,
+    instance_data_provider: &InstanceDataProvider,
+    gfx_ctx: &GfxContext,
+) -> Option<RenderCommand<'r>> {
+    let mesh = renderer.mesh();
+
+    // empty buffers cannot be bound, and there is nothing to draw anyway
+    if mesh.index_count() == 0 {
+        return None;
+    }
+
+    let material = renderer.material();
+    let bind_groups = material.construct_bind_groups(gfx_ctx)?;
+    let instance_buffer = instance_data_provider.create_instance_buffer(
+        transform_matrix,
+        &gfx_ctx.per_frame_buffer_pool,
+        &gfx_ctx.device,
+        &gfx_ctx.queue,
+    );
+    let render_pipeline = renderer.construct_render_pipeline(
+        global_texture_set,
+        instance_data_provider.instance_data_size(),
+        instance_data_provider.instance_data_attributes(),
+        gfx_ctx,
+    );
+
+    Some(RenderCommand::new(
+        material.shader().reflection().builtin_uniform_bind_group,
+        render_pipeline,
+        bind_groups,
+        instance_buffer,
+        mesh.vertex_buffer().slice(..),
+        mesh.index_buffer().slice(..),
+        mesh.index_format(),
+        0..mesh.index_count(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        gfx::{
+            elements::{
+                make_material_render_state, make_shader_source_descriptor, Material, Mesh, Shader,
+            },
+            test_gfx_ctx, ClearMode, FrameCapture, PendingFrameCapture, RenderPassTarget,
+        },
+        scene::{HierarchyStorage, ObjectId, Transform},
+    };
+    use lvl_math::Vec3;
+    use lvl_resource::{
+        MaterialSource, MeshElement, MeshElementKind, MeshIndexKind, MeshSource, MeshTopology,
+        ShaderSource, ShaderSourceDescriptor,
+    };
+    use std::{num::NonZeroU32, sync::Arc};
+    use wgpu::{
+        Color, Extent3d, PrimitiveTopology, TextureDescriptor, TextureDimension, TextureUsages,
+        TextureViewDescriptor,
+    };
+    use zerocopy::AsBytes;
+
+    const WHITE_SHADER: &str = r#"
+        @vertex
+        fn vs_main(@location(8) position: vec3<f32>) -> @builtin(position) vec4<f32> {
+            return vec4<f32>(position, 1.0);
+        }
+
+        @fragment
+        fn fs_main() -> @location(0) vec4<f32> {
+            return vec4<f32>(1.0, 1.0, 1.0, 1.0);
+        }
+    "#;
+
+    /// Places the vertices with the model matrix of the instance, as the builtin
+    /// `builtin_transform_vertex_to_world_space` does, but without any camera.
+    const INSTANCE_SHADER: &str = r#"
+        struct InstanceInput {
+            @location(0) model_matrix_col_0: vec4<f32>,
+            @location(1) model_matrix_col_1: vec4<f32>,
+            @location(2) model_matrix_col_2: vec4<f32>,
+            @location(3) model_matrix_col_3: vec4<f32>,
+        }
+
+        @vertex
+        fn vs_main(
+            instance: InstanceInput,
+            @location(8) position: vec3<f32>,
+        ) -> @builtin(position) vec4<f32> {
+            let model_matrix = mat4x4<f32>(
+                instance.model_matrix_col_0,
+                instance.model_matrix_col_1,
+                instance.model_matrix_col_2,
+                instance.model_matrix_col_3,
+            );
+            return model_matrix * vec4<f32>(position, 1.0);
+        }
+
+        @fragment
+        fn fs_main() -> @location(0) vec4<f32> {
+            return vec4<f32>(1.0, 1.0, 1.0, 1.0);
+        }
+    "#;
+
+    fn make_renderer(
+        gfx_ctx: &GfxContext,
+        shader: &str,
+        topology: MeshTopology,
+        positions: &[[f32; 3]],
+    ) -> MeshRenderer {
+        let shader_source = ShaderSource::new(ShaderSourceDescriptor {
+            builtin_uniform_bind_group: Some(0),
+            vertex_inputs: [("position".to_owned(), 8)].into_iter().collect(),
+            ..make_shader_source_descriptor(shader)
+        });
+        let shader = Arc::new(Shader::load_from_source(&shader_source, gfx_ctx));
+        let material = Material::load_from_source(
+            |_: &str| Some((shader.clone(), &shader_source)),
+            |_| None,
+            &MaterialSource::new("white".to_owned(), make_material_render_state(), vec![]),
+            gfx_ctx,
+        );
+
+        let indices = (0..positions.len() as u16).collect::<Vec<_>>();
+        let mesh = Mesh::load_from_source(
+            &MeshSource::new(
+                positions.len() as u32,
+                positions.as_bytes().to_vec(),
+                indices.as_bytes().to_vec(),
+                MeshIndexKind::U16,
+                topology,
+                vec![MeshElement {
+                    name: "position".to_owned(),
+                    kind: MeshElementKind::Position,
+                    offset: 0,
+                }],
+            ),
+            gfx_ctx,
+        )
+        .unwrap();
+
+        MeshRenderer::new(Arc::new(mesh), Arc::from(material))
+    }
+
+    /// Renders the renderer with the given world matrix into a black 4x4 target.
+    fn render(
+        gfx_ctx: &GfxContext,
+        renderer: &MeshRenderer,
+        transform_matrix: &Mat4,
+    ) -> FrameCapture {
+        let global_texture_set = gfx_ctx.global_texture_set.borrow();
+        let target = gfx_ctx.device.create_texture(&TextureDescriptor {
+            label: None,
+            size: Extent3d {
+                width: 4,
+                height: 4,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: global_texture_set.main_color_format,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let target_view = target.create_view(&TextureViewDescriptor::default());
+        let instance_data_provider = InstanceDataProvider;
+        let command = build_render_command_mesh_renderer(
+            &global_texture_set,
+            transform_matrix,
+            renderer,
+            &instance_data_provider,
+            gfx_ctx,
+        )
+        .unwrap();
+
+        let mut frame = gfx_ctx.begin_frame();
+        {
+            let mut render_pass = frame.begin_render_pass(
+                ClearMode::All {
+                    color: Color::BLACK,
+                    depth: 1.0,
+                    stencil: 0,
+                },
+                &[Some(RenderPassTarget {
+                    view: global_texture_set
+                        .color
+                        .as_ref()
+                        .map_or(&target_view, |color| &color.texture_view),
+                    resolve_target: global_texture_set.color.as_ref().map(|_| &target_view),
+                    writable: true,
+                })],
+                Some(RenderPassTarget {
+                    view: &global_texture_set.depth_stencil.texture_view,
+                    resolve_target: None,
+                    writable: true,
+                }),
+            );
+            command.render(
+                &mut render_pass,
+                gfx_ctx.uniform_bind_group_provider.bind_group(),
+            );
+        }
+        let capture = PendingFrameCapture::record(&target, &mut frame, &gfx_ctx.device).unwrap();
+        gfx_ctx.end_frame(frame);
+        capture.read(&gfx_ctx.device).unwrap()
+    }
+
+    /// Returns the positions of the pixels that are not black.
+    fn lit_pixels(capture: &FrameCapture) -> Vec<(u32, u32)> {
+        capture
+            .pixels
+            .chunks_exact(4)
+            .enumerate()
+            .filter(|(_, pixel)| pixel[..3] != [0; 3])
+            .map(|(index, _)| (index as u32 % capture.width, index as u32 / capture.width))
+            .collect()
+    }
+
+    #[test]
+    fn check_line_list_mesh_renders_segments() {
+        let gfx_ctx = match test_gfx_ctx() {
+            Some(gfx_ctx) => gfx_ctx,
+            None => {
+                return;
+            }
+        };
+        // a segment through the centers of the second row of a 4x4 target,
+        // and another through the centers of its last column
+        let renderer = make_renderer(
+            &gfx_ctx,
+            WHITE_SHADER,
+            MeshTopology::LineList,
+            &[
+                [-1.0, 0.25, 0.5],
+                [1.0, 0.25, 0.5],
+                [0.75, -1.0, 0.5],
+                [0.75, 1.0, 0.5],
+            ],
+        );
+        assert_eq!(
+            renderer
+                .primitive_state(gfx_ctx.global_texture_set.borrow().face_culling)
+                .topology,
+            PrimitiveTopology::LineList
+        );
+
+        let capture = render(&gfx_ctx, &renderer, &Mat4::identity());
+
+        for (index, pixel) in capture.pixels.chunks_exact(4).enumerate() {
+            let (x, y) = (index % 4, index / 4);
+            let expected = if x == 3 || y == 1 { 255 } else { 0 };
+            assert_eq!(pixel[..3], [expected; 3], "pixel ({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn check_moving_parent_moves_rendered_pixels() {
+        let gfx_ctx = match test_gfx_ctx() {
+            Some(gfx_ctx) => gfx_ctx,
+            None => {
+                return;
+            }
+        };
+        // a point at the center of the top left pixel of a 4x4 target
+        let renderer = make_renderer(
+            &gfx_ctx,
+            INSTANCE_SHADER,
+            MeshTopology::PointList,
+            &[[-0.75, 0.75, 0.5]],
+        );
+
+        let parent = ObjectId::new(NonZeroU32::new(1).unwrap());
+        let child = ObjectId::new(NonZeroU32::new(2).unwrap());
+        let mut hierarchy = HierarchyStorage::new();
+        hierarchy.add(parent);
+        hierarchy.add(child);
+        hierarchy.set_parent(child, Some(parent));
+
+        let mut parent_transform = Transform::identity();
+        let child_transform = Transform {
+            position: Vec3::new(0.0, -0.5, 0.0),
+            ..Transform::identity()
+        };
+        let mut lit_pixels_per_position = Vec::new();
+
+        // a pixel is half a unit wide in the normalized device coordinates of a 4x4 target
+        for position in [Vec3::ZERO, Vec3::new(1.0, 0.0, 0.0)] {
+            parent_transform.position = position;
+            hierarchy.set_dirty(parent);
+            hierarchy.update_object_matrices(|object_id| {
+                Some(if object_id == parent {
+                    parent_transform.matrix()
+                } else {
+                    child_transform.matrix()
+                })
+            });
+
+            let capture = render(&gfx_ctx, &renderer, hierarchy.matrix(child));
+            lit_pixels_per_position.push(lit_pixels(&capture));
+        }
+
+        // the child is a pixel below the top row, and follows its parent two pixels to the right
+        assert_eq!(lit_pixels_per_position, [vec![(0, 1)], vec![(2, 1)]]);
+    }
+}
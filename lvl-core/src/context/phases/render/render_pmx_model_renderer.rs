@@ -1,6 +1,6 @@
 use super::render_command::RenderCommand;
 use crate::{
-    gfx::{elements::MaterialPropertyValue, GfxContext, InstanceDataProvider},
+    gfx::{elements::MaterialPropertyValue, GfxContext, GlobalTextureSet, InstanceDataProvider},
     scene::components::PmxModelRenderer,
 };
 use lvl_math::Mat4;
@@ -8,9 +8,9 @@ use lvl_resource::PmxModelIndexKind;
 use wgpu::IndexFormat;
 
 pub fn build_render_command_pmx_model_renderer<'r>(
-    msaa_sample_count: u32,
+    global_texture_set: &GlobalTextureSet,
     transform_matrix: &Mat4,
-renderer: &'r PmxModelRenderer,
+    renderer: &'r PmxModelRenderer,
     instance_data_provider: &InstanceDataProvider,
     gfx_ctx: &GfxContext,
 ) -> Vec<RenderCommand<'r>> {
@@ -23,9 +23,10 @@ renderer: &'r PmxModelRenderer,
 
     let model = renderer.model();
     model.morph().update_coefficients(&gfx_ctx.queue);
+    model.skeleton().update_matrices(&gfx_ctx.queue);
 
     let render_pipelines = renderer.construct_render_pipelines(
-        msaa_sample_count,
+        global_texture_set,
         instance_data_provider.instance_data_size(),
         instance_data_provider.instance_data_attributes(),
         gfx_ctx,
@@ -38,6 +39,11 @@ renderer: &'r PmxModelRenderer,
     let mut commands = Vec::with_capacity(model.elements().len());
 
     for (index, element) in model.elements().iter().enumerate() {
+        // empty buffers cannot be bound, and there is nothing to draw anyway
+        if element.index_range.is_empty() {
+            continue;
+        }
+
         let material = &element.material;
         let diffuse_color = material
             .get_property("diffuse_color")
@@ -1,6 +1,9 @@
 use crate::{
     context::{driver::Driver, Context},
-    scene::Scene,
+    scene::{
+        components::{update_model_instances, update_pmx_model_animators},
+        Scene,
+    },
 };
 use winit::window::Window;
 
@@ -11,11 +14,22 @@ pub fn update(
     driver: &mut Option<Box<dyn Driver>>,
 ) {
     if let Some(driver) = driver {
+        for name in ctx.take_reloaded_resources() {
+            driver.on_resource_reloaded(ctx, window, scene, &name);
+        }
+
         driver.on_before_update(&ctx, window, scene);
     }
 
     scene.trigger_update();
 
+    // the models are posed after the controllers have played or posed them
+    let delta_time = ctx.time().delta_time().as_secs_f32();
+    scene.with_proxy(|proxy| {
+        update_pmx_model_animators(proxy, ctx);
+        update_model_instances(proxy, delta_time);
+    });
+
     if let Some(driver) = driver {
         driver.on_after_update(&ctx, window, scene);
     }
@@ -14,7 +14,10 @@ pub struct Time {
 
 impl Time {
     pub fn new() -> Self {
-        let now = Instant::now();
+        Self::new_at(Instant::now())
+    }
+
+    pub(crate) fn new_at(now: Instant) -> Self {
         Self {
             time_scale: 1.0,
             time: Duration::from_secs(0),
@@ -55,7 +58,10 @@ impl Time {
     }
 
     pub(crate) fn update(&mut self) {
-        let now = Instant::now();
+        self.update_at(Instant::now());
+    }
+
+    pub(crate) fn update_at(&mut self, now: Instant) {
         self.time = now
             .duration_since(self.last_scale_updated_time)
             .mul_f64(self.time_scale);
@@ -1,15 +1,27 @@
+mod bloom_pass;
 pub mod elements;
 mod frame;
+mod frame_capture;
 mod gfx_context;
 mod global_texture_set;
 pub mod glyph;
+mod gpu_timer;
 mod instance_data_provider;
 mod per_frame_buffer_pool;
+mod render_config;
+mod render_graph;
+mod tonemap_pass;
 mod uniform_bind_group_provider;
 
+pub use bloom_pass::*;
 pub use frame::*;
+pub use frame_capture::*;
 pub use gfx_context::*;
 pub use global_texture_set::*;
+pub(crate) use gpu_timer::*;
 pub use instance_data_provider::*;
 pub use per_frame_buffer_pool::*;
+pub use render_config::*;
+pub use render_graph::*;
+pub use tonemap_pass::*;
 pub use uniform_bind_group_provider::*;
@@ -0,0 +1,654 @@
+use super::{
+    ClearMode, RenderGraphPass, RenderGraphPassContext, RenderGraphTexture, RenderPassTarget,
+};
+use std::mem::size_of;
+use wgpu::{
+    AddressMode, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, BlendComponent, BlendFactor,
+    BlendOperation, BlendState, Buffer, BufferBindingType, BufferDescriptor, BufferUsages,
+    ColorTargetState, ColorWrites, Device, Extent3d, FilterMode, FragmentState, MultisampleState,
+    PipelineLayoutDescriptor, PrimitiveState, RenderPipeline, RenderPipelineDescriptor, Sampler,
+    SamplerBindingType, SamplerDescriptor, ShaderModule, ShaderModuleDescriptor, ShaderStages,
+    TextureDescriptor, TextureDimension, TextureFormat, TextureSampleType, TextureUsages,
+    TextureView, TextureViewDimension, VertexState,
+};
+use zerocopy::AsBytes;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BloomSettings {
+    /// Brightness above which the colors start to bloom.
+    pub threshold: f32,
+    /// Scale of the blurred colors added back to the main color.
+    pub intensity: f32,
+    /// Number of the downsampled textures; more levels spread the glow wider.
+    pub level_count: u32,
+}
+
+impl Default for BloomSettings {
+    fn default() -> Self {
+        Self {
+            threshold: 1.0,
+            intensity: 0.5,
+            level_count: 5,
+        }
+    }
+}
+
+const BLOOM_SHADER: &str = r"
+struct BloomParams {
+    threshold: f32,
+    intensity: f32,
+    _padding0: f32,
+    _padding1: f32,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@group(0) @binding(0) var source: texture_2d<f32>;
+@group(0) @binding(1) var source_sampler: sampler;
+@group(0) @binding(2) var<uniform> params: BloomParams;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    // a triangle covering the whole screen
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    out.uv = vec2<f32>(uv.x, 1.0 - uv.y);
+    return out;
+}
+
+fn sample_source(uv: vec2<f32>) -> vec3<f32> {
+    return textureSampleLevel(source, source_sampler, uv, 0.0).rgb;
+}
+
+@fragment
+fn fs_prefilter(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = sample_source(in.uv);
+    let brightness = max(color.r, max(color.g, color.b));
+    let contribution = max(brightness - params.threshold, 0.0) / max(brightness, 0.00001);
+    return vec4<f32>(color * contribution, 1.0);
+}
+
+@fragment
+fn fs_downsample(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(sample_source(in.uv), 1.0);
+}
+
+@fragment
+fn fs_upsample(in: VertexOutput) -> @location(0) vec4<f32> {
+    // 3x3 tent filter over the texels of the smaller level
+    let texel = 1.0 / vec2<f32>(textureDimensions(source));
+    var color = sample_source(in.uv) * 4.0;
+    color += sample_source(in.uv + vec2<f32>(-texel.x, 0.0)) * 2.0;
+    color += sample_source(in.uv + vec2<f32>(texel.x, 0.0)) * 2.0;
+    color += sample_source(in.uv + vec2<f32>(0.0, -texel.y)) * 2.0;
+    color += sample_source(in.uv + vec2<f32>(0.0, texel.y)) * 2.0;
+    color += sample_source(in.uv + vec2<f32>(-texel.x, -texel.y));
+    color += sample_source(in.uv + vec2<f32>(texel.x, -texel.y));
+    color += sample_source(in.uv + vec2<f32>(-texel.x, texel.y));
+    color += sample_source(in.uv + vec2<f32>(texel.x, texel.y));
+    return vec4<f32>(color / 16.0, 1.0);
+}
+
+@fragment
+fn fs_composite(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(sample_source(in.uv) * params.intensity, 0.0);
+}
+";
+
+const LEVEL_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+
+const ADDITIVE_BLENDING: BlendState = BlendState {
+    color: BlendComponent {
+        src_factor: BlendFactor::One,
+        dst_factor: BlendFactor::One,
+        operation: BlendOperation::Add,
+    },
+    alpha: BlendComponent {
+        src_factor: BlendFactor::Zero,
+        dst_factor: BlendFactor::One,
+        operation: BlendOperation::Add,
+    },
+};
+
+#[repr(C)]
+#[derive(AsBytes)]
+struct BloomParams {
+    threshold: f32,
+    intensity: f32,
+    _padding0: f32,
+    _padding1: f32,
+}
+
+#[derive(Debug)]
+struct BloomPipelines {
+    bind_group_layout: BindGroupLayout,
+    sampler: Sampler,
+    params_buffer: Buffer,
+    prefilter: RenderPipeline,
+    downsample: RenderPipeline,
+    upsample: RenderPipeline,
+    composite: RenderPipeline,
+    main_color_format: TextureFormat,
+}
+
+impl BloomPipelines {
+    fn new(main_color_format: TextureFormat, device: &Device) -> Self {
+        let module = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("bloom-shader"),
+            source: wgpu::ShaderSource::Wgsl(BLOOM_SHADER.into()),
+        });
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("bloom-bind-group-layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("bloom-sampler"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+        let params_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("bloom-params"),
+            size: size_of::<BloomParams>() as u64,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let make_pipeline =
+            |entry_point: &str, format: TextureFormat, blend: Option<BlendState>| {
+                create_pipeline(
+                    &module,
+                    &bind_group_layout,
+                    entry_point,
+                    format,
+                    blend,
+                    device,
+                )
+            };
+        let prefilter = make_pipeline("fs_prefilter", LEVEL_FORMAT, None);
+        let downsample = make_pipeline("fs_downsample", LEVEL_FORMAT, None);
+        let upsample = make_pipeline("fs_upsample", LEVEL_FORMAT, Some(ADDITIVE_BLENDING));
+        let composite = make_pipeline("fs_composite", main_color_format, Some(ADDITIVE_BLENDING));
+
+        Self {
+            bind_group_layout,
+            sampler,
+            params_buffer,
+            prefilter,
+            downsample,
+            upsample,
+            composite,
+            main_color_format,
+        }
+    }
+}
+
+fn create_pipeline(
+    module: &ShaderModule,
+    bind_group_layout: &BindGroupLayout,
+    entry_point: &str,
+    format: TextureFormat,
+    blend: Option<BlendState>,
+    device: &Device,
+) -> RenderPipeline {
+    let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+        label: Some("bloom-pipeline-layout"),
+        bind_group_layouts: &[bind_group_layout],
+        push_constant_ranges: &[],
+    });
+
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some("bloom-render-pipeline"),
+        layout: Some(&pipeline_layout),
+        vertex: VertexState {
+            module,
+            entry_point: "vs_main",
+            buffers: &[],
+        },
+        primitive: PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: MultisampleState::default(),
+        fragment: Some(FragmentState {
+            module,
+            entry_point,
+            targets: &[Some(ColorTargetState {
+                format,
+                blend,
+                write_mask: ColorWrites::all(),
+            })],
+        }),
+        multiview: None,
+    })
+}
+
+/// Returns the sizes of the downsampled textures, halving the size at each level.
+fn level_sizes(width: u32, height: u32, level_count: u32) -> Vec<(u32, u32)> {
+    let mut sizes = Vec::with_capacity(level_count as usize);
+    let (mut width, mut height) = (width, height);
+
+    for _ in 0..level_count {
+        width = (width / 2).max(1);
+        height = (height / 2).max(1);
+        sizes.push((width, height));
+    }
+
+    sizes
+}
+
+#[derive(Debug)]
+struct BloomLevels {
+    size: (u32, u32),
+    views: Vec<TextureView>,
+}
+
+impl BloomLevels {
+    fn new(size: (u32, u32), level_count: u32, device: &Device) -> Self {
+        let views = level_sizes(size.0, size.1, level_count)
+            .into_iter()
+            .map(|(width, height)| {
+                device
+                    .create_texture(&TextureDescriptor {
+                        label: Some("bloom-level"),
+                        size: Extent3d {
+                            width,
+                            height,
+                            depth_or_array_layers: 1,
+                        },
+                        mip_level_count: 1,
+                        sample_count: 1,
+                        dimension: TextureDimension::D2,
+                        format: LEVEL_FORMAT,
+                        usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+                        view_formats: &[],
+                    })
+                    .create_view(&Default::default())
+            })
+            .collect();
+
+        Self { size, views }
+    }
+}
+
+/// A pass that adds a glow around the bright areas of the main color.
+/// It extracts the colors above the threshold, blurs them through a chain of downsampled textures,
+/// and adds the result back to the main color. Meant to run before the [`super::TonemapPass`].
+#[derive(Debug, Default)]
+pub struct BloomPass {
+    settings: BloomSettings,
+    pipelines: Option<BloomPipelines>,
+    levels: Option<BloomLevels>,
+}
+
+impl BloomPass {
+    pub fn new(settings: BloomSettings) -> Self {
+        Self {
+            settings,
+            pipelines: None,
+            levels: None,
+        }
+    }
+
+    pub fn settings(&self) -> &BloomSettings {
+        &self.settings
+    }
+}
+
+impl RenderGraphPass for BloomPass {
+    fn name(&self) -> &str {
+        "bloom"
+    }
+
+    fn inputs(&self) -> &[RenderGraphTexture] {
+        &[RenderGraphTexture::MainColor]
+    }
+
+    fn outputs(&self) -> &[RenderGraphTexture] {
+        &[RenderGraphTexture::MainColor]
+    }
+
+    fn execute(&mut self, context: &mut RenderGraphPassContext) {
+        if self.settings.level_count == 0 {
+            return;
+        }
+
+        let main_color_texture_view = match context.texture_view(RenderGraphTexture::MainColor) {
+            Some(view) => view,
+            None => {
+                return;
+            }
+        };
+        let gfx_ctx = context.gfx_ctx();
+        let main_color_format = gfx_ctx.global_texture_set.borrow().main_color_format;
+        let size = {
+            let surface_config = gfx_ctx.surface_config.borrow();
+            (surface_config.width, surface_config.height)
+        };
+
+        let pipelines = match &mut self.pipelines {
+            Some(pipelines) if pipelines.main_color_format == main_color_format => pipelines,
+            pipelines => pipelines.insert(BloomPipelines::new(main_color_format, &gfx_ctx.device)),
+        };
+        let levels = match &mut self.levels {
+            Some(levels)
+                if levels.size == size
+                    && levels.views.len() == self.settings.level_count as usize =>
+            {
+                levels
+            }
+            levels => levels.insert(BloomLevels::new(
+                size,
+                self.settings.level_count,
+                &gfx_ctx.device,
+            )),
+        };
+
+        let params = BloomParams {
+            threshold: self.settings.threshold,
+            intensity: self.settings.intensity,
+            _padding0: 0.0,
+            _padding1: 0.0,
+        };
+        gfx_ctx
+            .queue
+            .write_buffer(&pipelines.params_buffer, 0, params.as_bytes());
+
+        let mut steps = Vec::with_capacity(levels.views.len() * 2 + 1);
+        steps.push((
+            &pipelines.prefilter,
+            main_color_texture_view,
+            &levels.views[0],
+        ));
+
+        for index in 1..levels.views.len() {
+            steps.push((
+                &pipelines.downsample,
+                &levels.views[index - 1],
+                &levels.views[index],
+            ));
+        }
+
+        for index in (1..levels.views.len()).rev() {
+            steps.push((
+                &pipelines.upsample,
+                &levels.views[index],
+                &levels.views[index - 1],
+            ));
+        }
+
+        steps.push((
+            &pipelines.composite,
+            &levels.views[0],
+            main_color_texture_view,
+        ));
+
+        for (pipeline, source, target) in steps {
+            let bind_group = gfx_ctx.device.create_bind_group(&BindGroupDescriptor {
+                label: Some("bloom-bind-group"),
+                layout: &pipelines.bind_group_layout,
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: BindingResource::TextureView(source),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: BindingResource::Sampler(&pipelines.sampler),
+                    },
+                    BindGroupEntry {
+                        binding: 2,
+                        resource: pipelines.params_buffer.as_entire_binding(),
+                    },
+                ],
+            });
+
+            let mut render_pass = context.frame().begin_render_pass(
+                ClearMode::Keep,
+                &[Some(RenderPassTarget {
+                    view: target,
+                    resolve_target: None,
+                    writable: true,
+                })],
+                None,
+            );
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, &bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gfx::{test_gfx_ctx, GfxContext};
+    use std::sync::mpsc;
+    use wgpu::{
+        ImageCopyBuffer, ImageCopyTexture, ImageDataLayout, MaintainBase, MapMode, Origin3d,
+        TextureAspect, TextureViewDescriptor, COPY_BYTES_PER_ROW_ALIGNMENT,
+    };
+
+    const SIZE: u32 = 16;
+
+    /// Converts a color component into a half float; enough for the normal values of the tests.
+    fn f32_to_f16(value: f32) -> u16 {
+        if value == 0.0 {
+            return 0;
+        }
+
+        let bits = value.to_bits();
+        let sign = (bits >> 16) & 0x8000;
+        let exponent = ((bits >> 23) & 0xff) as i32 - 127 + 15;
+        let mantissa = (bits >> 13) & 0x3ff;
+        (sign | (exponent as u32) << 10 | mantissa) as u16
+    }
+
+    fn f16_to_f32(bits: u16) -> f32 {
+        let exponent = ((bits >> 10) & 0x1f) as i32;
+        let mantissa = (bits & 0x3ff) as f32 / 1024.0;
+        let magnitude = if exponent == 0 {
+            mantissa * 2f32.powi(-14)
+        } else {
+            (1.0 + mantissa) * 2f32.powi(exponent - 15)
+        };
+
+        if bits & 0x8000 != 0 {
+            -magnitude
+        } else {
+            magnitude
+        }
+    }
+
+    /// Runs the pass over a `SIZE`x`SIZE` main color whose texels are black except the given
+    /// ones, and reads back the red components of the main color.
+    fn bloom_on_gpu(
+        gfx_ctx: &GfxContext,
+        settings: BloomSettings,
+        texels: &[((u32, u32), f32)],
+    ) -> Vec<Vec<f32>> {
+        let format = TextureFormat::Rgba16Float;
+        let size = Extent3d {
+            width: SIZE,
+            height: SIZE,
+            depth_or_array_layers: 1,
+        };
+        let main_color = gfx_ctx.device.create_texture(&TextureDescriptor {
+            label: None,
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::RENDER_ATTACHMENT
+                | TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_DST
+                | TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let mut data = vec![0u16; (SIZE * SIZE * 4) as usize];
+
+        for (index, texel) in data.chunks_exact_mut(4).enumerate() {
+            let position = (index as u32 % SIZE, index as u32 / SIZE);
+            let value = texels
+                .iter()
+                .find(|(p, _)| *p == position)
+                .map_or(0.0, |(_, value)| *value);
+            texel.copy_from_slice(&[value, value, value, 1.0].map(f32_to_f16));
+        }
+
+        gfx_ctx.queue.write_texture(
+            main_color.as_image_copy(),
+            data.as_bytes(),
+            ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(SIZE * 8),
+                rows_per_image: None,
+            },
+            size,
+        );
+        gfx_ctx.global_texture_set.borrow_mut().main_color_format = format;
+        {
+            let mut surface_config = gfx_ctx.surface_config.borrow_mut();
+            surface_config.width = SIZE;
+            surface_config.height = SIZE;
+        }
+
+        let readback = gfx_ctx.device.create_buffer(&BufferDescriptor {
+            label: None,
+            size: (COPY_BYTES_PER_ROW_ALIGNMENT * SIZE) as u64,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let main_color_view = main_color.create_view(&TextureViewDescriptor::default());
+        // the pass does not touch the surface
+        let surface_view = main_color.create_view(&TextureViewDescriptor::default());
+        let global_texture_set = gfx_ctx.global_texture_set.borrow();
+
+        let mut frame = gfx_ctx.begin_frame();
+        BloomPass::new(settings).execute(&mut RenderGraphPassContext::new(
+            gfx_ctx,
+            &mut frame,
+            &surface_view,
+            Some(&main_color_view),
+            &global_texture_set.depth_stencil.texture_view,
+            1.0,
+        ));
+        frame.cmd_encoder_mut().copy_texture_to_buffer(
+            ImageCopyTexture {
+                texture: &main_color,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            ImageCopyBuffer {
+                buffer: &readback,
+                layout: ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(COPY_BYTES_PER_ROW_ALIGNMENT),
+                    rows_per_image: None,
+                },
+            },
+            size,
+        );
+        gfx_ctx.end_frame(frame);
+
+        let (sender, receiver) = mpsc::channel();
+        let slice = readback.slice(..);
+        slice.map_async(MapMode::Read, move |result| {
+            sender.send(result).ok();
+        });
+        gfx_ctx.device.poll(MaintainBase::Wait);
+        receiver.recv().unwrap().unwrap();
+
+        let data = slice.get_mapped_range();
+        data.chunks_exact(COPY_BYTES_PER_ROW_ALIGNMENT as usize)
+            .map(|row| {
+                row[..(SIZE * 8) as usize]
+                    .chunks_exact(8)
+                    .map(|texel| f16_to_f32(u16::from_le_bytes([texel[0], texel[1]])))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn check_level_sizes() {
+        assert_eq!(level_sizes(64, 32, 3), [(32, 16), (16, 8), (8, 4)]);
+        assert_eq!(level_sizes(4, 2, 3), [(2, 1), (1, 1), (1, 1)]);
+    }
+
+    #[test]
+    fn check_bright_region_produces_halo() {
+        let gfx_ctx = match test_gfx_ctx() {
+            Some(gfx_ctx) => gfx_ctx,
+            None => {
+                return;
+            }
+        };
+        let settings = BloomSettings {
+            threshold: 1.0,
+            intensity: 1.0,
+            level_count: 3,
+        };
+        let bright_texels = [
+            ((7, 7), 20.0),
+            ((8, 7), 20.0),
+            ((7, 8), 20.0),
+            ((8, 8), 20.0),
+        ];
+        let bloomed = bloom_on_gpu(&gfx_ctx, settings, &bright_texels);
+
+        // the surrounding texels glow, fading with the distance
+        let near = bloomed[8][5];
+        let far = bloomed[8][1];
+        assert!(0.0 < near, "{:?}", bloomed);
+        assert!(0.0 < far && far < near, "{:?}", bloomed);
+
+        // the glow is added on top of the original colors
+        assert!(20.0 < bloomed[8][8], "{:?}", bloomed);
+
+        // the colors below the threshold do not bloom
+        let dimmed = bloom_on_gpu(&gfx_ctx, settings, &[((8, 8), 0.5)]);
+
+        for (y, row) in dimmed.iter().enumerate() {
+            for (x, &value) in row.iter().enumerate() {
+                let expected = if (x, y) == (8, 8) { 0.5 } else { 0.0 };
+                assert_eq!(value, expected, "{:?}", dimmed);
+            }
+        }
+    }
+}
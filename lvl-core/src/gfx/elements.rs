@@ -1,5 +1,6 @@
 mod font;
 mod material;
+mod mesh;
 mod pmx_model;
 mod pmx_model_animation;
 mod shader;
@@ -9,6 +10,7 @@ mod texture;
 
 pub use font::*;
 pub use material::*;
+pub use mesh::*;
 pub use pmx_model::*;
 pub use pmx_model_animation::*;
 pub use shader::*;
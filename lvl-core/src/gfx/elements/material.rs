@@ -7,16 +7,26 @@ use lvl_resource::{
 };
 use std::{
     cell::{RefCell, RefMut},
-    collections::BTreeMap,
+    collections::{BTreeMap, BTreeSet},
     num::NonZeroU64,
     sync::Arc,
 };
+use thiserror::Error;
 use wgpu::{
     BindGroup, BindGroupDescriptor, BindGroupEntry, BindingResource, Buffer, BufferBinding,
-    BufferDescriptor, BufferSize, BufferUsages, Queue, Sampler, SamplerDescriptor, TextureView,
+    BufferDescriptor, BufferSize, BufferUsages, Device, Queue, Sampler, SamplerDescriptor,
+    TextureSampleType, TextureView, TextureViewDimension,
 };
 use zerocopy::AsBytes;
 
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum SetPropertyError {
+    #[error("the material has no property `{0}`")]
+    PropertyNotFound(String),
+    #[error("the property `{0}` does not accept a value of this type")]
+    IncompatibleValue(String),
+}
+
 #[derive(Debug)]
 pub struct Material {
     shader: Arc<Shader>,
@@ -26,6 +36,8 @@ pub struct Material {
     bind_groups: RefCell<Vec<Option<BindGroup>>>,
     properties: Vec<MaterialProperty>,
     property_name_index_map: BTreeMap<String, usize>,
+    /// Names of the properties set after loading, which survive [`Material::reload_from_source`].
+    overridden_properties: BTreeSet<String>,
 }
 
 impl Material {
@@ -166,6 +178,11 @@ impl Material {
                 }
             }
 
+            // an unresolved texture would leave the bind group incomplete, hiding the whole mesh
+            if value.is_none() && accepts_missing_texture(&binding.kind) {
+                value = Some(MaterialPropertyValue::Texture(gfx_ctx.missing_texture()));
+            }
+
             properties.push(MaterialProperty {
                 group: binding.group,
                 binding: binding.binding,
@@ -230,6 +247,64 @@ impl Material {
             bind_groups: RefCell::new(bind_groups),
             properties,
             property_name_index_map,
+            overridden_properties: BTreeSet::new(),
+        }
+    }
+
+    /// Rebuilds the material from a changed source, e.g. after its shader has been edited.
+    /// The properties set since loading keep their values if the new shader still declares them
+    /// with a compatible binding; the others take the values of the new source. All bind groups
+    /// are constructed again on the next draw.
+    pub fn reload_from_source<'a>(
+        &mut self,
+        shader_loader: impl FnMut(&str) -> Option<(Arc<Shader>, &'a ShaderSource)>,
+        texture_loader: impl FnMut(&str) -> Option<Arc<TextureView>>,
+        source: &MaterialSource,
+        gfx_ctx: &GfxContext,
+    ) {
+        let mut reloaded = Self::load_from_source(shader_loader, texture_loader, source, gfx_ctx);
+        reloaded.overridden_properties = carry_over_properties(
+            &self.property_name_index_map,
+            &self.properties,
+            &self.overridden_properties,
+            &reloaded.property_name_index_map,
+            &mut reloaded.properties,
+        );
+
+        *self = reloaded;
+    }
+
+    /// Creates a copy of this material that shares the shader and the bound resources,
+    /// but owns its uniform buffers and property values, so it can be modified independently.
+    pub fn duplicate(&self, gfx_ctx: &GfxContext) -> Self {
+        self.duplicate_on(&gfx_ctx.device)
+    }
+
+    /// Same as [`Material::duplicate`], but only needs the device.
+    pub(crate) fn duplicate_on(&self, device: &Device) -> Self {
+        let uniform_buffers = self
+            .uniform_buffers
+            .iter()
+            .map(|buffer| {
+                device.create_buffer(&BufferDescriptor {
+                    label: None,
+                    size: buffer.size(),
+                    usage: buffer.usage(),
+                    mapped_at_creation: false,
+                })
+            })
+            .collect();
+        let bind_groups = self.bind_groups.borrow().iter().map(|_| None).collect();
+
+        Self {
+            shader: self.shader.clone(),
+            render_state: self.render_state.clone(),
+            uniform_buffers,
+            uniform_structs: self.uniform_structs.clone(),
+            bind_groups: RefCell::new(bind_groups),
+            properties: self.properties.clone(),
+            property_name_index_map: self.property_name_index_map.clone(),
+            overridden_properties: self.overridden_properties.clone(),
         }
     }
 
@@ -247,21 +322,51 @@ impl Material {
             .map(|index| &self.properties[*index])
     }
 
+    /// Sets the property; see [`Material::set_property_checked`]. Returns `false` if the
+    /// property is not set.
     pub fn set_property(&mut self, name: &str, value: MaterialPropertyValue) -> bool {
-        let index = if let Some(index) = self.property_name_index_map.get(name) {
-            *index
-        } else {
-            return false;
-        };
+        self.set_property_checked(name, value).is_ok()
+    }
 
-        if !self.properties[index].kind.is_compatible(&value) {
-            return false;
-        }
+    /// Sets the property, which is bound the next time the bind groups are constructed. Fails if
+    /// the shader does not declare the property or the value does not fit its binding.
+    pub fn set_property_checked(
+        &mut self,
+        name: &str,
+        value: MaterialPropertyValue,
+    ) -> Result<(), SetPropertyError> {
+        let index = find_settable_property(
+            &self.property_name_index_map,
+            &self.properties,
+            name,
+            &value,
+        )?;
 
         self.properties[index].value = Some(value);
-        self.bind_groups.borrow_mut()[self.properties[index].group as usize] = None;
 
-        true
+        // user-defined bind groups come after the built-in bind group
+        let bind_group_index = (self.properties[index].group as usize).checked_sub(1);
+
+        if let Some(bind_group) =
+            bind_group_index.and_then(|index| self.bind_groups.get_mut().get_mut(index))
+        {
+            *bind_group = None;
+        }
+
+        self.overridden_properties.insert(name.to_owned());
+
+        Ok(())
+    }
+
+    /// Returns `true` if the property has been set since loading.
+    pub fn is_property_overridden(&self, name: &str) -> bool {
+        self.overridden_properties.contains(name)
+    }
+
+    /// Makes the property take the value of the source again on the next reload. The current
+    /// value is kept until then.
+    pub fn reset_property_override(&mut self, name: &str) {
+        self.overridden_properties.remove(name);
     }
 
     pub fn construct_bind_groups(
@@ -454,14 +559,14 @@ impl Material {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct UniformStruct {
     group: u32,
     binding: u32,
     buffer_index: u32,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct MaterialProperty {
     group: u32,
     binding: u32,
@@ -475,7 +580,7 @@ impl MaterialProperty {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum MaterialPropertyKind {
     UniformBuffer {
         group: u32,
@@ -537,7 +642,71 @@ impl MaterialPropertyKind {
     }
 }
 
-#[derive(Debug)]
+/// Returns the index of the property that can take the value.
+fn find_settable_property(
+    property_name_index_map: &BTreeMap<String, usize>,
+    properties: &[MaterialProperty],
+    name: &str,
+    value: &MaterialPropertyValue,
+) -> Result<usize, SetPropertyError> {
+    let index = *property_name_index_map
+        .get(name)
+        .ok_or_else(|| SetPropertyError::PropertyNotFound(name.to_owned()))?;
+
+    if !properties[index].kind.is_compatible(value) {
+        return Err(SetPropertyError::IncompatibleValue(name.to_owned()));
+    }
+
+    Ok(index)
+}
+
+/// Copies the values of the overridden properties into the properties of the reloaded material.
+/// Returns the names of the overrides that were kept; properties that are gone or no longer
+/// compatible fall back to the values of the new source.
+fn carry_over_properties(
+    previous_name_index_map: &BTreeMap<String, usize>,
+    previous_properties: &[MaterialProperty],
+    overridden_properties: &BTreeSet<String>,
+    property_name_index_map: &BTreeMap<String, usize>,
+    properties: &mut [MaterialProperty],
+) -> BTreeSet<String> {
+    let mut kept = BTreeSet::new();
+
+    for name in overridden_properties {
+        let value = match previous_name_index_map
+            .get(name)
+            .and_then(|index| previous_properties[*index].value.as_ref())
+        {
+            Some(value) => value,
+            None => {
+                continue;
+            }
+        };
+
+        if let Ok(index) = find_settable_property(property_name_index_map, properties, name, value)
+        {
+            properties[index].value = Some(value.clone());
+            kept.insert(name.clone());
+        }
+    }
+
+    kept
+}
+
+/// Returns `true` if the binding can take the placeholder of
+/// [`GfxContext::missing_texture`], which is a filterable 2D float texture.
+fn accepts_missing_texture(kind: &ShaderBindingKind) -> bool {
+    matches!(
+        kind,
+        ShaderBindingKind::Texture {
+            sample_type: TextureSampleType::Float { .. },
+            view_dimension: TextureViewDimension::D2,
+            multisampled: false,
+        }
+    )
+}
+
+#[derive(Debug, Clone)]
 pub enum MaterialPropertyValue {
     // buffer values
     Float(f32),
@@ -574,3 +743,265 @@ impl MaterialPropertyValue {
         }
     }
 }
+
+/// Makes the render state of an opaque material with every option turned off, for tests.
+#[cfg(test)]
+pub(crate) fn make_material_render_state() -> MaterialRenderState {
+    use lvl_resource::MaterialRenderType;
+
+    MaterialRenderState {
+        render_type: MaterialRenderType::Opaque,
+        no_cull_back_face: false,
+        cast_shadow_on_ground: false,
+        cast_shadow_on_object: false,
+        receive_shadow: false,
+        has_edge: false,
+        vertex_color: false,
+        point_drawing: false,
+        line_drawing: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gfx::{
+        elements::{make_shader_source_descriptor, missing_texture_element},
+        test_gfx_ctx,
+    };
+    use lvl_resource::{ShaderBinding, ShaderSourceDescriptor};
+
+    const TINT_SHADER: &str = r#"
+        @group(1) @binding(0) var<uniform> tint: vec4<f32>;
+
+        @vertex
+        fn vs_main() -> @builtin(position) vec4<f32> {
+            return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+        }
+
+        @fragment
+        fn fs_main() -> @location(0) vec4<f32> {
+            return tint;
+        }
+    "#;
+
+    fn make_tint_shader_source() -> ShaderSource {
+        ShaderSource::new(ShaderSourceDescriptor {
+            bindings: vec![ShaderBinding {
+                name: "tint".to_owned(),
+                group: 1,
+                binding: 0,
+                kind: ShaderBindingKind::UniformBuffer {
+                    index: 0,
+                    size: NonZeroU64::new(16).unwrap(),
+                    is_struct: false,
+                },
+            }],
+            ..make_shader_source_descriptor(TINT_SHADER)
+        })
+    }
+
+    fn make_tint_material_source(tint: Vec4) -> MaterialSource {
+        MaterialSource::new(
+            "tint".to_owned(),
+            make_material_render_state(),
+            vec![lvl_resource::MaterialProperty {
+                name: "tint".to_owned(),
+                value: lvl_resource::MaterialPropertyValue::Uniform(
+                    MaterialPropertyUniformValue::Vec4(tint),
+                ),
+            }],
+        )
+    }
+
+    #[test]
+    fn check_reload_invalidates_bind_groups() {
+        let gfx_ctx = match test_gfx_ctx() {
+            Some(gfx_ctx) => gfx_ctx,
+            None => {
+                return;
+            }
+        };
+        let shader_source = make_tint_shader_source();
+        let shader = Arc::new(Shader::load_from_source(&shader_source, &gfx_ctx));
+        let shader_loader = |_: &str| Some((shader.clone(), &shader_source));
+
+        let mut material = Material::load_from_source(
+            shader_loader,
+            |_| None,
+            &make_tint_material_source(Vec4::ONE),
+            &gfx_ctx,
+        );
+        assert!(material.construct_bind_groups(&gfx_ctx).is_some());
+        assert!(material.bind_groups.borrow().iter().all(Option::is_some));
+
+        let tint = Vec4::new(1.0, 0.0, 0.0, 1.0);
+        material.reload_from_source(
+            shader_loader,
+            |_| None,
+            &make_tint_material_source(tint),
+            &gfx_ctx,
+        );
+        assert!(!material.bind_groups.borrow().is_empty());
+        assert!(material.bind_groups.borrow().iter().all(Option::is_none));
+
+        match material
+            .get_property("tint")
+            .and_then(|property| property.value())
+        {
+            Some(MaterialPropertyValue::Vec4(value)) => assert_eq!(*value, tint),
+            _ => panic!("the tint is not a vec4"),
+        }
+
+        assert!(material.construct_bind_groups(&gfx_ctx).is_some());
+        assert!(material.bind_groups.borrow().iter().all(Option::is_some));
+        // the group of a property is rebuilt when it is set
+        assert!(material.set_property("tint", MaterialPropertyValue::Vec4(Vec4::ONE)));
+        assert!(material.bind_groups.borrow()[0].is_none());
+    }
+
+    #[test]
+    fn check_missing_texture_placeholder() {
+        let diffuse = ShaderBindingKind::Texture {
+            sample_type: TextureSampleType::Float { filterable: true },
+            view_dimension: TextureViewDimension::D2,
+            multisampled: false,
+        };
+        let morph_index = ShaderBindingKind::Texture {
+            sample_type: TextureSampleType::Uint,
+            view_dimension: TextureViewDimension::D2,
+            multisampled: false,
+        };
+
+        assert!(accepts_missing_texture(&diffuse));
+        assert!(!accepts_missing_texture(&morph_index));
+
+        let placeholder = missing_texture_element();
+        assert_eq!((placeholder.size.width, placeholder.size.height), (2, 2));
+        assert_eq!(&placeholder.data[..4], &[255, 0, 255, 255]);
+        assert_eq!(placeholder.data.len(), 2 * 2 * 4);
+    }
+
+    #[test]
+    fn check_settable_property() {
+        let properties = vec![
+            MaterialProperty {
+                group: 0,
+                binding: 0,
+                kind: MaterialPropertyKind::UniformMember {
+                    offset: 0,
+                    size: NonZeroU64::new(16).unwrap(),
+                    buffer_index: 0,
+                },
+                value: None,
+            },
+            MaterialProperty {
+                group: 1,
+                binding: 0,
+                kind: MaterialPropertyKind::Texture,
+                value: None,
+            },
+        ];
+        let property_name_index_map =
+            BTreeMap::from([("diffuse_color".to_owned(), 0), ("texture".to_owned(), 1)]);
+        let color = MaterialPropertyValue::Vec4(Vec4::ONE);
+
+        assert_eq!(
+            find_settable_property(
+                &property_name_index_map,
+                &properties,
+                "diffuse_color",
+                &color
+            ),
+            Ok(0)
+        );
+        assert_eq!(
+            find_settable_property(
+                &property_name_index_map,
+                &properties,
+                "diffuse_color ",
+                &color
+            ),
+            Err(SetPropertyError::PropertyNotFound(
+                "diffuse_color ".to_owned()
+            ))
+        );
+        assert_eq!(
+            find_settable_property(&property_name_index_map, &properties, "texture", &color),
+            Err(SetPropertyError::IncompatibleValue("texture".to_owned()))
+        );
+    }
+
+    fn uniform_property(offset: u64, value: f32) -> MaterialProperty {
+        MaterialProperty {
+            group: 0,
+            binding: 0,
+            kind: MaterialPropertyKind::UniformMember {
+                offset,
+                size: NonZeroU64::new(4).unwrap(),
+                buffer_index: 0,
+            },
+            value: Some(MaterialPropertyValue::Float(value)),
+        }
+    }
+
+    fn float_value(property: &MaterialProperty) -> Option<f32> {
+        match property.value {
+            Some(MaterialPropertyValue::Float(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn check_reload_carries_over_properties() {
+        // `edge_size` was set at runtime; `toon_strength` still has the value of the source
+        let previous_properties = vec![
+            uniform_property(0, 2.0),
+            uniform_property(4, 0.5),
+            uniform_property(8, 1.0),
+            uniform_property(12, 1.0),
+        ];
+        let previous_name_index_map = BTreeMap::from([
+            ("edge_size".to_owned(), 0),
+            ("toon_strength".to_owned(), 1),
+            ("outline".to_owned(), 2),
+            ("sphere_strength".to_owned(), 3),
+        ]);
+        let overridden_properties = BTreeSet::from([
+            "edge_size".to_owned(),
+            "outline".to_owned(),
+            "sphere_strength".to_owned(),
+        ]);
+
+        // the new source changes both defaults, turns `outline` into a texture and drops
+        // `sphere_strength`
+        let mut properties = vec![
+            uniform_property(0, 1.0),
+            uniform_property(4, 0.75),
+            MaterialProperty {
+                group: 1,
+                binding: 0,
+                kind: MaterialPropertyKind::Texture,
+                value: None,
+            },
+        ];
+        let property_name_index_map = BTreeMap::from([
+            ("edge_size".to_owned(), 0),
+            ("toon_strength".to_owned(), 1),
+            ("outline".to_owned(), 2),
+        ]);
+
+        let kept = carry_over_properties(
+            &previous_name_index_map,
+            &previous_properties,
+            &overridden_properties,
+            &property_name_index_map,
+            &mut properties,
+        );
+
+        assert_eq!(kept, BTreeSet::from(["edge_size".to_owned()]));
+        assert_eq!(float_value(&properties[0]), Some(2.0));
+        assert_eq!(float_value(&properties[1]), Some(0.75));
+        assert!(properties[2].value.is_none());
+    }
+}
@@ -0,0 +1,143 @@
+use crate::gfx::GfxContext;
+use lvl_resource::{MeshElement, MeshIndexError, MeshIndexKind, MeshSource, MeshTopology};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    Buffer, BufferUsages, IndexFormat,
+};
+
+/// A static mesh on the GPU, drawn by [`crate::scene::components::MeshRenderer`].
+#[derive(Debug)]
+pub struct Mesh {
+    vertex_buffer: Buffer,
+    index_buffer: Buffer,
+    index_count: u32,
+    index_kind: MeshIndexKind,
+    topology: MeshTopology,
+    stride: u64,
+    elements: Vec<MeshElement>,
+}
+
+impl Mesh {
+    /// Uploads the mesh. Fails without touching the GPU if its indices are invalid.
+    pub fn load_from_source(
+        source: &MeshSource,
+        gfx_ctx: &GfxContext,
+    ) -> Result<Self, MeshIndexError> {
+        source.validate_indices()?;
+
+        let vertex_buffer = gfx_ctx.device.create_buffer_init(&BufferInitDescriptor {
+            label: None,
+            contents: source.vertex_data(),
+            usage: BufferUsages::VERTEX,
+        });
+        let index_buffer = gfx_ctx.device.create_buffer_init(&BufferInitDescriptor {
+            label: None,
+            contents: source.index_data(),
+            usage: BufferUsages::INDEX,
+        });
+        let stride = match source.vertex_count() {
+            0 => 0,
+            vertex_count => (source.vertex_data().len() / vertex_count as usize) as u64,
+        };
+
+        Ok(Self {
+            vertex_buffer,
+            index_buffer,
+            index_count: (source.index_data().len() / source.index_kind().size()) as u32,
+            index_kind: source.index_kind(),
+            topology: source.topology(),
+            stride,
+            elements: Vec::from(source.elements()),
+        })
+    }
+
+    pub fn vertex_buffer(&self) -> &Buffer {
+        &self.vertex_buffer
+    }
+
+    pub fn index_buffer(&self) -> &Buffer {
+        &self.index_buffer
+    }
+
+    pub fn index_count(&self) -> u32 {
+        self.index_count
+    }
+
+    pub fn index_format(&self) -> IndexFormat {
+        match self.index_kind {
+            MeshIndexKind::U16 => IndexFormat::Uint16,
+            MeshIndexKind::U32 => IndexFormat::Uint32,
+        }
+    }
+
+    /// The topology the render pipelines of the mesh are built with.
+    pub fn topology(&self) -> MeshTopology {
+        self.topology
+    }
+
+    /// The size of a vertex in bytes.
+    pub fn stride(&self) -> u64 {
+        self.stride
+    }
+
+    /// The attributes of a vertex, bound to the shader inputs of the same names.
+    pub fn elements(&self) -> &[MeshElement] {
+        &self.elements
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gfx::test_gfx_ctx;
+    use lvl_resource::{MeshElementKind, MeshIndexKind};
+
+    fn make_mesh(indices: &[u32]) -> MeshSource {
+        MeshSource::new(
+            3,
+            vec![0; 3 * 20],
+            indices
+                .iter()
+                .flat_map(|index| index.to_le_bytes())
+                .collect(),
+            MeshIndexKind::U32,
+            MeshTopology::TriangleList,
+            vec![
+                MeshElement {
+                    name: "position".to_owned(),
+                    kind: MeshElementKind::Position,
+                    offset: 0,
+                },
+                MeshElement {
+                    name: "uv".to_owned(),
+                    kind: MeshElementKind::TexCoord(0),
+                    offset: 12,
+                },
+            ],
+        )
+    }
+
+    #[test]
+    fn check_load_from_source() {
+        let gfx_ctx = match test_gfx_ctx() {
+            Some(gfx_ctx) => gfx_ctx,
+            None => {
+                return;
+            }
+        };
+
+        let mesh = Mesh::load_from_source(&make_mesh(&[0, 1, 2]), &gfx_ctx).unwrap();
+        assert_eq!(mesh.stride(), 20);
+        assert_eq!(mesh.index_count(), 3);
+        assert_eq!(mesh.index_format(), IndexFormat::Uint32);
+        assert_eq!(mesh.elements().len(), 2);
+
+        assert_eq!(
+            Mesh::load_from_source(&make_mesh(&[0, 1, 3]), &gfx_ctx).unwrap_err(),
+            MeshIndexError::IndexOutOfRange {
+                index: 3,
+                vertex_count: 3,
+            }
+        );
+    }
+}
@@ -1,8 +1,21 @@
+mod ik_solver;
+mod material_overrides;
 mod morph;
+mod skeleton;
 
-use self::morph::Morph;
-use super::{Material, Shader, Texture};
-use crate::gfx::GfxContext;
+pub use self::ik_solver::IkSolver;
+#[cfg(test)]
+pub(crate) use self::morph::make_material_shader_source;
+pub use self::morph::{MorphClampPolicy, MAX_MORPH_COUNT};
+pub use self::skeleton::{skin_vertex, Skeleton, VertexDeform, MAX_BONE_COUNT};
+
+use self::{
+    material_overrides::MaterialOverrides,
+    morph::{reset_material_value_overrides, Morph},
+};
+use super::{Material, MaterialPropertyValue, Shader, Texture};
+use crate::{gfx::GfxContext, resource::ResourceRegistry};
+use lvl_math::{Aabb, Vec3, Vec4};
 use lvl_resource::{
     MaterialSource, PmxModelIndexKind, PmxModelSource, PmxModelVertexLayoutElement,
     PmxModelVertexLayoutElementKind, ResourceFile, ShaderSource, TextureKind, TextureSource,
@@ -14,40 +27,80 @@ use std::{
     ops::Range,
     sync::Arc,
 };
+use thiserror::Error;
 use wgpu::{
     util::{BufferInitDescriptor, DeviceExt},
-    Buffer, BufferUsages, TextureView,
+    Buffer, BufferUsages, Device, Queue, TextureView,
 };
+use zerocopy::AsBytes;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum PmxModelError {
+    #[error("the model has {count} morphs, but at most {max} are supported")]
+    TooManyMorphs { count: usize, max: usize },
+    #[error("the model has {count} bones, but at most {max} are supported")]
+    TooManyBones { count: usize, max: usize },
+    #[error("the vertex data of {size} bytes is not a whole number of {stride}-byte vertices")]
+    InvalidVertexStride { size: u64, stride: u64 },
+    #[error("the index range {start}..{end} of the element {element} is out of the {index_count} indices")]
+    ElementIndexRangeOutOfBounds {
+        element: usize,
+        start: u32,
+        end: u32,
+        index_count: u32,
+    },
+    #[error("the index range of the element {element} starts at {start}, but the previous element ends at {expected}")]
+    ElementIndexRangeNotContiguous {
+        element: usize,
+        start: u32,
+        expected: u32,
+    },
+    #[error("the material `{0}` is not found")]
+    MaterialNotFound(String),
+    #[error("the shader `{shader}` of the material `{material}` is not found")]
+    ShaderNotFound { material: String, shader: String },
+    #[error("the source has {found} elements, but the model has {expected}")]
+    ElementCountMismatch { expected: usize, found: usize },
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum PmxModelMorphReloadError {
+    #[error("the vertex data size differs from the loaded one; expected {expected} bytes, got {actual} bytes")]
+    VertexDataSizeMismatch { expected: u64, actual: u64 },
+    #[error("the vertex layout differs from the loaded one")]
+    VertexLayoutMismatch,
+    #[error("the morph texture `{0}` is not found or is not a single texture")]
+    MorphTextureNotFound(String),
+    #[error("the model has {count} morphs, but at most {max} are supported")]
+    TooManyMorphs { count: usize, max: usize },
+}
 
 #[derive(Debug)]
 pub struct PmxModel {
-    vertex_buffer: Buffer,
-    index_buffer: Buffer,
+    vertex_buffer: Arc<Buffer>,
+    index_buffer: Arc<Buffer>,
     elements: Vec<PmxModelElement>,
     vertex_layout: PmxModelVertexLayout,
     index_kind: PmxModelIndexKind,
+    bounds: Option<Aabb>,
     morph: RefCell<Morph>,
+    skeleton: Skeleton,
+    material_overrides: MaterialOverrides,
+    vertex_displacement_texture: Option<Arc<Texture>>,
+    uv_displacement_texture: Option<Arc<Texture>>,
+    /// The device the model is uploaded to, which makes the copies of [`Self::duplicate`].
+    device: Arc<Device>,
 }
 
 impl PmxModel {
+    /// Uploads the model. Fails without touching the GPU if the source is malformed.
     pub fn load_from_source<'a>(
         resource: &'a ResourceFile,
         source: &PmxModelSource,
         gfx_ctx: &GfxContext,
-    ) -> Self {
-        let vertex_buffer = gfx_ctx.device.create_buffer_init(&BufferInitDescriptor {
-            label: None,
-            contents: source.vertex_data(),
-            usage: BufferUsages::VERTEX,
-        });
-        let index_buffer = gfx_ctx.device.create_buffer_init(&BufferInitDescriptor {
-            label: None,
-            contents: source.index_data(),
-            usage: BufferUsages::INDEX,
-        });
-
+    ) -> Result<Self, PmxModelError> {
         let mut shader_cache = HashMap::<String, (Arc<Shader>, &'a ShaderSource)>::new();
-        let mut shader_loader = |name: &str| -> Option<(Arc<Shader>, &'a ShaderSource)> {
+        let shader_loader = |name: &str| -> Option<(Arc<Shader>, &'a ShaderSource)> {
             match shader_cache.entry(name.to_owned()) {
                 Entry::Occupied(entry) => Some(entry.get().clone()),
                 Entry::Vacant(entry) => {
@@ -66,7 +119,7 @@ impl PmxModel {
         };
 
         let mut texture_cache = HashMap::<String, Arc<TextureView>>::new();
-        let mut texture_loader = |name: &str| -> Option<Arc<TextureView>> {
+        let texture_loader = |name: &str| -> Option<Arc<TextureView>> {
             match texture_cache.entry(name.to_owned()) {
                 Entry::Occupied(entry) => Some(entry.get().clone()),
                 Entry::Vacant(entry) => {
@@ -91,12 +144,101 @@ impl PmxModel {
             }
         };
 
+        Self::load(resource, source, shader_loader, texture_loader, gfx_ctx)
+    }
+
+    /// Uploads the model as [`Self::load_from_source`], but shares the shaders and textures of
+    /// the registry instead of uploading them again. The registry must have loaded the file,
+    /// e.g. by [`crate::context::Context::load_resources`].
+    pub fn load_with_registry<'a>(
+        resource: &'a ResourceFile,
+        source: &PmxModelSource,
+        registry: &mut ResourceRegistry,
+        gfx_ctx: &GfxContext,
+    ) -> Result<Self, PmxModelError> {
+        // taken beforehand, as the textures below borrow the registry mutably
+        let shaders = source
+            .elements()
+            .iter()
+            .filter_map(|element| resource.find::<MaterialSource>(&element.material_name))
+            .filter_map(|material_source| {
+                let name = material_source.shader_name();
+                Some((name.to_owned(), registry.get_shader(name)?))
+            })
+            .collect::<HashMap<_, _>>();
+        let shader_loader = |name: &str| -> Option<(Arc<Shader>, &'a ShaderSource)> {
+            Some((
+                shaders.get(name)?.clone(),
+                resource.find::<ShaderSource>(name)?,
+            ))
+        };
+        let texture_loader = |name: &str| registry.get_texture(name, gfx_ctx);
+
+        Self::load(resource, source, shader_loader, texture_loader, gfx_ctx)
+    }
+
+    fn load<'a>(
+        resource: &'a ResourceFile,
+        source: &PmxModelSource,
+        mut shader_loader: impl FnMut(&str) -> Option<(Arc<Shader>, &'a ShaderSource)>,
+        mut texture_loader: impl FnMut(&str) -> Option<Arc<TextureView>>,
+        gfx_ctx: &GfxContext,
+    ) -> Result<Self, PmxModelError> {
+        validate_source(source)?;
+
+        let vertex_buffer = gfx_ctx.device.create_buffer_init(&BufferInitDescriptor {
+            label: None,
+            contents: source.vertex_data(),
+            // the morph attributes of the vertices are rewritten when the morphs are reloaded
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+        });
+        let index_buffer = gfx_ctx.device.create_buffer_init(&BufferInitDescriptor {
+            label: None,
+            contents: source.index_data(),
+            usage: BufferUsages::INDEX,
+        });
+
+        let mut displacement_texture_views = HashMap::<String, Arc<TextureView>>::new();
+
+        // the displacement textures are kept to allow editing the morphs in place
+        let mut displacement_texture_loader = |name: &str| -> Option<Arc<Texture>> {
+            let texture = match resource.find::<TextureSource>(name)?.kind() {
+                TextureKind::Single(element) => Texture::load_from_source(element, gfx_ctx),
+                TextureKind::Cubemap { .. } => {
+                    return None;
+                }
+            };
+            let texture_view = Arc::new(texture.handle().create_view(&Default::default()));
+            displacement_texture_views.insert(name.to_owned(), texture_view);
+            Some(Arc::new(texture))
+        };
+        let vertex_displacement_texture =
+            displacement_texture_loader(source.vertex_displacement_texture_name());
+        let uv_displacement_texture =
+            displacement_texture_loader(source.uv_displacement_texture_name());
+
+        let mut texture_loader = |name: &str| -> Option<Arc<TextureView>> {
+            match displacement_texture_views.get(name) {
+                Some(texture_view) => Some(texture_view.clone()),
+                None => texture_loader(name),
+            }
+        };
+
         let mut elements = Vec::with_capacity(source.elements().len());
 
         for pmx_element in source.elements() {
             let material_source = resource
                 .find::<MaterialSource>(&pmx_element.material_name)
-                .unwrap();
+                .ok_or_else(|| {
+                    PmxModelError::MaterialNotFound(pmx_element.material_name.clone())
+                })?;
+
+            if shader_loader(material_source.shader_name()).is_none() {
+                return Err(PmxModelError::ShaderNotFound {
+                    material: pmx_element.material_name.clone(),
+                    shader: material_source.shader_name().to_owned(),
+                });
+            }
 
             let material = Material::load_from_source(
                 &mut shader_loader,
@@ -111,22 +253,315 @@ impl PmxModel {
             });
         }
 
-        let morph: Morph = Morph::new(source.morphs(), &mut elements, &gfx_ctx.device);
+        let morph = Morph::new(source.morphs(), &mut elements, &gfx_ctx.device)?;
+        let skeleton = Skeleton::new(
+            source.bones(),
+            source.inverse_bind_matrices(),
+            &mut elements,
+            &gfx_ctx.device,
+        )?;
+        let vertex_layout = PmxModelVertexLayout::new(Vec::from(source.vertex_layout()));
+        let bounds = vertex_bounds(source.vertex_data(), &vertex_layout);
 
-        Self {
-            vertex_buffer,
-            index_buffer,
+        Ok(Self {
+            vertex_buffer: Arc::new(vertex_buffer),
+            index_buffer: Arc::new(index_buffer),
             elements,
-            vertex_layout: PmxModelVertexLayout::new(Vec::from(source.vertex_layout())),
+            vertex_layout,
             index_kind: source.index_kind(),
+            bounds,
+            morph: RefCell::new(morph),
+            skeleton,
+            material_overrides: MaterialOverrides::new(),
+            vertex_displacement_texture,
+            uv_displacement_texture,
+            device: gfx_ctx.device.clone(),
+        })
+    }
+
+    /// Creates another instance of this model that shares the vertex/index buffers and
+    /// the morph displacement textures with this one.
+    /// The new instance owns its materials, morph coefficients and skeleton, which start at the base
+    /// values and the bind pose, so its morphs, pose and material properties can be changed without
+    /// affecting this instance.
+    pub fn share_geometry(&self, gfx_ctx: &GfxContext) -> Self {
+        self.share_geometry_on(&gfx_ctx.device)
+    }
+
+    /// Creates another instance of this model as [`Self::share_geometry`] does, which starts with
+    /// the morph coefficients and clamp policies, the pose and the material overrides of this one.
+    /// Unlike [`Self::share_geometry`], it needs no [`GfxContext`], so that components can copy
+    /// their models.
+    pub fn duplicate(&self) -> Self {
+        let mut copy = self.share_geometry_on(&self.device);
+        let morph = self.morph.borrow();
+        let coefficients = morph
+            .names()
+            .filter_map(|name| Some((name, morph.coefficient(name)?, morph.clamp_policy(name)?)))
+            .collect::<Vec<_>>();
+
+        for (name, _, clamp_policy) in &coefficients {
+            copy.set_morph_clamp_policy(name, *clamp_policy);
+        }
+
+        copy.skeleton.copy_pose(&self.skeleton);
+        copy.material_overrides = self.material_overrides.clone();
+        // also writes the material overrides
+        copy.set_morphs(
+            coefficients
+                .iter()
+                .map(|(name, coefficient, _)| (*name, *coefficient)),
+        );
+        copy
+    }
+
+    fn share_geometry_on(&self, device: &Arc<Device>) -> Self {
+        let mut elements = self
+            .elements
+            .iter()
+            .map(|element| PmxModelElement {
+                material: element.material.duplicate_on(device),
+                index_range: element.index_range.clone(),
+            })
+            .collect::<Vec<_>>();
+        let morph = self.morph.borrow().share(&mut elements, device);
+        let skeleton = self.skeleton.share(&mut elements, device);
+
+        Self {
+            vertex_buffer: self.vertex_buffer.clone(),
+            index_buffer: self.index_buffer.clone(),
+            elements,
+            vertex_layout: self.vertex_layout.clone(),
+            index_kind: self.index_kind,
+            bounds: self.bounds,
             morph: RefCell::new(morph),
+            skeleton,
+            material_overrides: MaterialOverrides::new(),
+            vertex_displacement_texture: self.vertex_displacement_texture.clone(),
+            uv_displacement_texture: self.uv_displacement_texture.clone(),
+            device: device.clone(),
+        }
+    }
+
+    /// Returns `true` if both instances draw from the same vertex and index buffers.
+    pub fn shares_geometry_with(&self, other: &PmxModel) -> bool {
+        Arc::ptr_eq(&self.vertex_buffer, &other.vertex_buffer)
+            && Arc::ptr_eq(&self.index_buffer, &other.index_buffer)
+    }
+
+    /// Replaces the morphs with the ones of the given source, which must be a recompilation of
+    /// the loaded model with the same vertex layout and count. The vertex and index buffers are
+    /// reused, the whole vertex data of the source is uploaded into the vertex buffer, and the
+    /// morph textures are loaded again. All morph coefficients and clamp policies are reset.
+    ///
+    /// The vertex buffer is shared with the instances made by [`PmxModel::share_geometry`], so
+    /// they draw the uploaded vertices, including any attribute other than the morph ones that
+    /// has changed, and their morphs have to be reloaded as well.
+    pub fn reload_morphs(
+        &mut self,
+        resource: &ResourceFile,
+        source: &PmxModelSource,
+        gfx_ctx: &GfxContext,
+    ) -> Result<(), PmxModelMorphReloadError> {
+        check_vertex_compatibility(
+            &self.vertex_layout,
+            self.vertex_buffer.size(),
+            source.vertex_layout(),
+            source.vertex_data().len() as u64,
+        )?;
+
+        // checked before anything is modified, so that the morph creation below cannot fail
+        if MAX_MORPH_COUNT < source.morphs().len() {
+            return Err(PmxModelMorphReloadError::TooManyMorphs {
+                count: source.morphs().len(),
+                max: MAX_MORPH_COUNT,
+            });
         }
+
+        let load_texture = |name: &str| -> Result<Texture, PmxModelMorphReloadError> {
+            match resource
+                .find::<TextureSource>(name)
+                .map(|source| source.kind())
+            {
+                Some(TextureKind::Single(element)) => {
+                    Ok(Texture::load_from_source(element, gfx_ctx))
+                }
+                _ => Err(PmxModelMorphReloadError::MorphTextureNotFound(
+                    name.to_owned(),
+                )),
+            }
+        };
+        let morph_textures = [
+            (
+                "vertex_morph_index_texture",
+                load_texture(source.vertex_morph_index_texture_name())?,
+            ),
+            (
+                "uv_morph_index_texture",
+                load_texture(source.uv_morph_index_texture_name())?,
+            ),
+            (
+                "vertex_displacement_texture",
+                load_texture(source.vertex_displacement_texture_name())?,
+            ),
+            (
+                "uv_displacement_texture",
+                load_texture(source.uv_displacement_texture_name())?,
+            ),
+        ];
+
+        // the new morph system takes the current material values as the base values
+        self.morph
+            .borrow()
+            .restore_material_values(&mut self.elements);
+
+        for (property_name, texture) in &morph_textures {
+            let texture_view = Arc::new(texture.handle().create_view(&Default::default()));
+
+            for element in &mut self.elements {
+                element.material.set_property(
+                    property_name,
+                    MaterialPropertyValue::Texture(texture_view.clone()),
+                );
+            }
+        }
+
+        gfx_ctx
+            .queue
+            .write_buffer(&self.vertex_buffer, 0, source.vertex_data());
+
+        let [_, _, (_, vertex_displacement_texture), (_, uv_displacement_texture)] = morph_textures;
+        self.vertex_displacement_texture = Some(Arc::new(vertex_displacement_texture));
+        self.uv_displacement_texture = Some(Arc::new(uv_displacement_texture));
+        self.morph =
+            RefCell::new(Morph::new(source.morphs(), &mut self.elements, &gfx_ctx.device).unwrap());
+        self.material_overrides.apply(&mut self.elements);
+
+        Ok(())
+    }
+
+    /// Loads the materials of the elements again, e.g. after their shaders or textures have been
+    /// edited. The properties set at runtime, such as the morph and skinning bindings and the
+    /// overrides of [`Self::override_material_property`], are kept; the values driven by the
+    /// material morphs start from the new materials.
+    ///
+    /// Fails without touching the materials if the source has another number of elements, or a
+    /// material or its shader is not found.
+    pub fn reload_materials<'a>(
+        &mut self,
+        resource: &'a ResourceFile,
+        source: &PmxModelSource,
+        gfx_ctx: &GfxContext,
+    ) -> Result<(), PmxModelError> {
+        if source.elements().len() != self.elements.len() {
+            return Err(PmxModelError::ElementCountMismatch {
+                expected: self.elements.len(),
+                found: source.elements().len(),
+            });
+        }
+
+        let mut material_sources = Vec::with_capacity(source.elements().len());
+
+        for pmx_element in source.elements() {
+            let material_source = resource
+                .find::<MaterialSource>(&pmx_element.material_name)
+                .ok_or_else(|| {
+                    PmxModelError::MaterialNotFound(pmx_element.material_name.clone())
+                })?;
+
+            if resource
+                .find::<ShaderSource>(material_source.shader_name())
+                .is_none()
+            {
+                return Err(PmxModelError::ShaderNotFound {
+                    material: pmx_element.material_name.clone(),
+                    shader: material_source.shader_name().to_owned(),
+                });
+            }
+
+            material_sources.push(material_source);
+        }
+
+        let mut shader_cache = HashMap::<String, (Arc<Shader>, &'a ShaderSource)>::new();
+        let mut shader_loader = |name: &str| -> Option<(Arc<Shader>, &'a ShaderSource)> {
+            match shader_cache.entry(name.to_owned()) {
+                Entry::Occupied(entry) => Some(entry.get().clone()),
+                Entry::Vacant(entry) => {
+                    let shader_source = resource.find::<ShaderSource>(name)?;
+                    let shader = Arc::new(Shader::load_from_source(shader_source, gfx_ctx));
+                    entry.insert((shader.clone(), shader_source));
+                    Some((shader, shader_source))
+                }
+            }
+        };
+
+        // the displacement textures stay shared, so that editing the morphs still reaches them
+        let mut texture_cache = HashMap::<String, Arc<TextureView>>::new();
+
+        for (name, texture) in [
+            (
+                source.vertex_displacement_texture_name(),
+                &self.vertex_displacement_texture,
+            ),
+            (
+                source.uv_displacement_texture_name(),
+                &self.uv_displacement_texture,
+            ),
+        ] {
+            if let Some(texture) = texture {
+                let texture_view = texture.handle().create_view(&Default::default());
+                texture_cache.insert(name.to_owned(), Arc::new(texture_view));
+            }
+        }
+
+        let mut texture_loader = |name: &str| -> Option<Arc<TextureView>> {
+            match texture_cache.entry(name.to_owned()) {
+                Entry::Occupied(entry) => Some(entry.get().clone()),
+                Entry::Vacant(entry) => match resource.find::<TextureSource>(name)?.kind() {
+                    TextureKind::Single(element) => {
+                        let texture = Texture::load_from_source(element, gfx_ctx);
+                        let texture_view =
+                            Arc::new(texture.handle().create_view(&Default::default()));
+                        entry.insert(texture_view.clone());
+                        Some(texture_view)
+                    }
+                    TextureKind::Cubemap { .. } => None,
+                },
+            }
+        };
+
+        let mut morph = self.morph.borrow_mut();
+        morph.restore_material_values(&mut self.elements);
+
+        for (element, material_source) in self.elements.iter_mut().zip(material_sources) {
+            reset_material_value_overrides(&mut element.material);
+            element.material.reload_from_source(
+                &mut shader_loader,
+                &mut texture_loader,
+                material_source,
+                gfx_ctx,
+            );
+        }
+
+        morph.refresh_material_values(&self.elements);
+        morph.update_material_values(&mut self.elements);
+        self.material_overrides.apply(&mut self.elements);
+
+        Ok(())
     }
 
     pub fn morph(&self) -> Ref<Morph> {
         self.morph.borrow()
     }
 
+    pub fn skeleton(&self) -> &Skeleton {
+        &self.skeleton
+    }
+
+    pub fn skeleton_mut(&mut self) -> &mut Skeleton {
+        &mut self.skeleton
+    }
+
     pub fn vertex_buffer(&self) -> &Buffer {
         &self.vertex_buffer
     }
@@ -147,6 +582,12 @@ impl PmxModel {
         &self.vertex_layout
     }
 
+    /// Returns the local-space bounds of the vertices in the rest pose, without morphs and bones.
+    /// Returns `None` if the model has no vertices.
+    pub fn bounds(&self) -> Option<Aabb> {
+        self.bounds
+    }
+
     pub fn index_kind(&self) -> PmxModelIndexKind {
         self.index_kind
     }
@@ -155,7 +596,222 @@ impl PmxModel {
         let mut morph = self.morph.borrow_mut();
         morph.set_morph(name, coefficient);
         morph.update_material_values(&mut self.elements);
+        self.material_overrides.apply(&mut self.elements);
+    }
+
+    /// Sets many morphs at once; the materials are updated only once at the end.
+    pub fn set_morphs<'a>(&mut self, coefficients: impl IntoIterator<Item = (&'a str, f32)>) {
+        let mut morph = self.morph.borrow_mut();
+
+        for (name, coefficient) in coefficients {
+            morph.set_morph(name, coefficient);
+        }
+
+        morph.update_material_values(&mut self.elements);
+        self.material_overrides.apply(&mut self.elements);
+    }
+
+    /// Overrides a material property of the given element for this instance only; other instances
+    /// made by [`Self::share_geometry`] own their materials and are not affected. The override
+    /// takes precedence over the material morphs. Returns `false` if the element or the property
+    /// does not exist, or the value does not match the property.
+    pub fn override_material_property(
+        &mut self,
+        element_index: usize,
+        name: &str,
+        value: MaterialPropertyValue,
+    ) -> bool {
+        let element = match self.elements.get_mut(element_index) {
+            Some(element) => element,
+            None => {
+                return false;
+            }
+        };
+
+        if !element.material.set_property(name, value.clone()) {
+            return false;
+        }
+
+        self.material_overrides.insert(element_index, name, value);
+        true
+    }
+
+    pub fn material_property_override(
+        &self,
+        element_index: usize,
+        name: &str,
+    ) -> Option<&MaterialPropertyValue> {
+        self.material_overrides.get(element_index, name)
+    }
+
+    /// Overrides how the coefficients of the given morph are clamped.
+    pub fn set_morph_clamp_policy(&mut self, name: &str, policy: MorphClampPolicy) {
+        self.morph.borrow_mut().set_clamp_policy(name, policy);
     }
+
+    /// Replaces the displacements of the given vertex morph in place.
+    /// Only the texels owned by the morph are re-uploaded; the index layout is not changed,
+    /// so `displacements` must contain exactly as many entries as the morph already has.
+    /// Returns `false` if the morph does not exist, is not a vertex morph or the count differs.
+    pub fn set_vertex_morph_displacements(
+        &self,
+        name: &str,
+        displacements: &[Vec3],
+        queue: &Queue,
+    ) -> bool {
+        let (range, texture) = match (
+            self.morph.borrow().vertex_displacement_range(name),
+            &self.vertex_displacement_texture,
+        ) {
+            (Some(range), Some(texture)) => (range, texture),
+            _ => {
+                return false;
+            }
+        };
+
+        if range.len() != displacements.len() {
+            return false;
+        }
+
+        let texels = displacements
+            .iter()
+            .map(|displacement| [displacement.x, displacement.y, displacement.z, 0f32])
+            .collect::<Vec<_>>();
+        texture.write_texels(range.start, texels.as_bytes(), queue);
+
+        true
+    }
+
+    /// Replaces the displacements of the given uv morph in place.
+    /// See [`PmxModel::set_vertex_morph_displacements`] for the constraints.
+    pub fn set_uv_morph_displacements(
+        &self,
+        name: &str,
+        displacements: &[Vec4],
+        queue: &Queue,
+    ) -> bool {
+        let (range, texture) = match (
+            self.morph.borrow().uv_displacement_range(name),
+            &self.uv_displacement_texture,
+        ) {
+            (Some(range), Some(texture)) => (range, texture),
+            _ => {
+                return false;
+            }
+        };
+
+        if range.len() != displacements.len() {
+            return false;
+        }
+
+        let texels = displacements
+            .iter()
+            .map(|displacement| {
+                [
+                    displacement.x,
+                    displacement.y,
+                    displacement.z,
+                    displacement.w,
+                ]
+            })
+            .collect::<Vec<_>>();
+        texture.write_texels(range.start, texels.as_bytes(), queue);
+
+        true
+    }
+}
+
+/// Checks what the loading relies on, so that a corrupt source fails instead of panicking.
+fn validate_source(source: &PmxModelSource) -> Result<(), PmxModelError> {
+    if MAX_MORPH_COUNT < source.morphs().len() {
+        return Err(PmxModelError::TooManyMorphs {
+            count: source.morphs().len(),
+            max: MAX_MORPH_COUNT,
+        });
+    }
+
+    let size = source.vertex_data().len() as u64;
+    let stride = source.vertex_stride();
+
+    if (stride == 0 && size != 0) || (stride != 0 && !size.is_multiple_of(stride)) {
+        return Err(PmxModelError::InvalidVertexStride { size, stride });
+    }
+
+    let index_count = source.index_count();
+    let mut expected_start = 0;
+
+    // the ranges partition the index buffer in order; the indices after the last one are unused,
+    // as the compiler clamps the materials claiming more surfaces than the file has
+    for (element, pmx_element) in source.elements().iter().enumerate() {
+        let (start, end) = pmx_element.index_range;
+
+        if end < start || index_count < end {
+            return Err(PmxModelError::ElementIndexRangeOutOfBounds {
+                element,
+                start,
+                end,
+                index_count,
+            });
+        }
+
+        if start != expected_start {
+            return Err(PmxModelError::ElementIndexRangeNotContiguous {
+                element,
+                start,
+                expected: expected_start,
+            });
+        }
+
+        expected_start = end;
+    }
+
+    Ok(())
+}
+
+/// Checks that a recompiled model can be drawn from the vertex buffer of the loaded one.
+fn check_vertex_compatibility(
+    layout: &PmxModelVertexLayout,
+    size: u64,
+    new_layout: &[PmxModelVertexLayoutElement],
+    new_size: u64,
+) -> Result<(), PmxModelMorphReloadError> {
+    if layout.elements != new_layout {
+        return Err(PmxModelMorphReloadError::VertexLayoutMismatch);
+    }
+
+    if size != new_size {
+        return Err(PmxModelMorphReloadError::VertexDataSizeMismatch {
+            expected: size,
+            actual: new_size,
+        });
+    }
+
+    Ok(())
+}
+
+/// Returns the bounds of the vertex positions in the given vertex data.
+fn vertex_bounds(vertex_data: &[u8], vertex_layout: &PmxModelVertexLayout) -> Option<Aabb> {
+    let offset = vertex_layout
+        .elements
+        .iter()
+        .find(|element| element.kind == PmxModelVertexLayoutElementKind::Position)?
+        .offset as usize;
+
+    if vertex_layout.stride == 0 {
+        return None;
+    }
+
+    Aabb::from_points(
+        vertex_data
+            .chunks_exact(vertex_layout.stride as usize)
+            .map(|vertex| {
+                let component = |index: usize| {
+                    let begin = offset + index * size_of::<f32>();
+                    f32::from_le_bytes(vertex[begin..begin + size_of::<f32>()].try_into().unwrap())
+                };
+                Vec3::new(component(0), component(1), component(2))
+            }),
+    )
 }
 
 #[derive(Debug)]
@@ -174,30 +830,141 @@ impl PmxModelVertexLayout {
     pub fn new(elements: Vec<PmxModelVertexLayoutElement>) -> Self {
         let stride = elements
             .iter()
-            .map(|element| {
-                let size = match element.kind {
-                    PmxModelVertexLayoutElementKind::Position => size_of::<[f32; 3]>(),
-                    PmxModelVertexLayoutElementKind::Normal => size_of::<[f32; 3]>(),
-                    PmxModelVertexLayoutElementKind::TexCoord => size_of::<[f32; 2]>(),
-                    PmxModelVertexLayoutElementKind::Tangent => size_of::<[f32; 3]>(),
-                    PmxModelVertexLayoutElementKind::AdditionalVec4(_) => size_of::<[f32; 4]>(),
-                    PmxModelVertexLayoutElementKind::DeformKind => size_of::<u32>(),
-                    PmxModelVertexLayoutElementKind::BoneIndex => size_of::<[i32; 4]>(),
-                    PmxModelVertexLayoutElementKind::BoneWeight => size_of::<[f32; 4]>(),
-                    PmxModelVertexLayoutElementKind::SdefC => size_of::<[f32; 3]>(),
-                    PmxModelVertexLayoutElementKind::SdefR0 => size_of::<[f32; 3]>(),
-                    PmxModelVertexLayoutElementKind::SdefR1 => size_of::<[f32; 3]>(),
-                    PmxModelVertexLayoutElementKind::EdgeSize => size_of::<f32>(),
-                    PmxModelVertexLayoutElementKind::VertexMorphIndexStart => size_of::<u32>(),
-                    PmxModelVertexLayoutElementKind::UvMorphIndexStart => size_of::<u32>(),
-                    PmxModelVertexLayoutElementKind::VertexMorphCount => size_of::<u32>(),
-                    PmxModelVertexLayoutElementKind::UvMorphCount => size_of::<u32>(),
-                };
-                element.offset + size as u64
-            })
+            .map(PmxModelVertexLayoutElement::end)
             .max()
             .unwrap_or_default();
 
         Self { elements, stride }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout_element(
+        kind: PmxModelVertexLayoutElementKind,
+        offset: u64,
+    ) -> PmxModelVertexLayoutElement {
+        PmxModelVertexLayoutElement { kind, offset }
+    }
+
+    fn make_source(
+        vertex_size: usize,
+        index_count: usize,
+        index_ranges: &[(u32, u32)],
+    ) -> PmxModelSource {
+        PmxModelSource::new(
+            vec![0; vertex_size],
+            vec![layout_element(PmxModelVertexLayoutElementKind::Position, 0)],
+            vec![0; index_count * 2],
+            PmxModelIndexKind::U16,
+            index_ranges
+                .iter()
+                .map(|&index_range| lvl_resource::PmxModelElement {
+                    material_name: "material".to_owned(),
+                    index_range,
+                })
+                .collect(),
+            vec![],
+            vec![],
+            vec![],
+            String::new(),
+            String::new(),
+            String::new(),
+            String::new(),
+        )
+    }
+
+    #[test]
+    fn check_element_index_ranges_partition() {
+        assert_eq!(
+            validate_source(&make_source(36, 9, &[(0, 3), (3, 6)])),
+            Ok(())
+        );
+        assert_eq!(
+            validate_source(&make_source(36, 9, &[(0, 6), (3, 9)])),
+            Err(PmxModelError::ElementIndexRangeNotContiguous {
+                element: 1,
+                start: 3,
+                expected: 6,
+            })
+        );
+        assert_eq!(
+            validate_source(&make_source(36, 9, &[(0, 3), (6, 9)])),
+            Err(PmxModelError::ElementIndexRangeNotContiguous {
+                element: 1,
+                start: 6,
+                expected: 3,
+            })
+        );
+    }
+
+    #[test]
+    fn check_invalid_element_index_range() {
+        assert_eq!(validate_source(&make_source(36, 3, &[(0, 3)])), Ok(()));
+        assert_eq!(
+            validate_source(&make_source(36, 3, &[(0, 6)])),
+            Err(PmxModelError::ElementIndexRangeOutOfBounds {
+                element: 0,
+                start: 0,
+                end: 6,
+                index_count: 3,
+            })
+        );
+        assert_eq!(
+            validate_source(&make_source(40, 3, &[(0, 3)])),
+            Err(PmxModelError::InvalidVertexStride {
+                size: 40,
+                stride: 12
+            })
+        );
+    }
+
+    #[test]
+    fn check_vertex_compatibility_for_morph_reload() {
+        let elements = vec![
+            layout_element(PmxModelVertexLayoutElementKind::Position, 0),
+            layout_element(PmxModelVertexLayoutElementKind::VertexMorphIndexStart, 12),
+            layout_element(PmxModelVertexLayoutElementKind::VertexMorphCount, 16),
+        ];
+        let layout = PmxModelVertexLayout::new(elements.clone());
+
+        assert_eq!(layout.stride, 20);
+        assert_eq!(
+            check_vertex_compatibility(&layout, 200, &elements, 200),
+            Ok(())
+        );
+        assert_eq!(
+            check_vertex_compatibility(&layout, 200, &elements, 220),
+            Err(PmxModelMorphReloadError::VertexDataSizeMismatch {
+                expected: 200,
+                actual: 220
+            })
+        );
+        assert_eq!(
+            check_vertex_compatibility(&layout, 200, &elements[..2], 200),
+            Err(PmxModelMorphReloadError::VertexLayoutMismatch)
+        );
+    }
+
+    #[test]
+    fn check_vertex_bounds() {
+        let layout = PmxModelVertexLayout::new(vec![
+            layout_element(PmxModelVertexLayoutElementKind::VertexMorphCount, 0),
+            layout_element(PmxModelVertexLayoutElementKind::Position, 4),
+        ]);
+        let vertex_data = [[0.0f32, 1.0, -2.0, 3.0], [0.0, -4.0, 5.0, 0.5]]
+            .as_bytes()
+            .to_vec();
+
+        assert_eq!(
+            vertex_bounds(&vertex_data, &layout),
+            Some(Aabb::new(
+                Vec3::new(-4.0, -2.0, 0.5),
+                Vec3::new(1.0, 5.0, 3.0)
+            ))
+        );
+        assert_eq!(vertex_bounds(&[], &layout), None);
+    }
+}
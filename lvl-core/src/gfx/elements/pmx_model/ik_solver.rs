@@ -0,0 +1,365 @@
+use lvl_math::{Quat, Vec3};
+use lvl_resource::{PmxModelBone, PmxModelBoneIKAngleLimit};
+use std::collections::HashMap;
+
+/// Below this distance in model space, the target of an IK chain has reached its goal.
+const IK_TOLERANCE: f32 = 1e-4;
+
+/// Solves the IK chains of a model with cyclic coordinate descent (CCD), as MMD does.
+///
+/// Each IK bone pulls its target bone towards its own position by turning the links of its
+/// chain, one link at a time, for up to the loop count of the chain. A turn of a link is capped
+/// by the limit angle of the chain, and links with an angle limit are clamped to it in the ZXY
+/// Euler angles of their local rotations.
+///
+/// The pose is given as local rotations and translations of the bones relative to their bind
+/// poses, as in [`Skeleton::set_local_poses`](super::Skeleton::set_local_poses); the solver only
+/// writes the rotations of the links.
+#[derive(Debug, Clone)]
+pub struct IkSolver {
+    name_index_map: HashMap<String, u32>,
+    bones: Vec<IkSolverBone>,
+    /// The IK bones in the order they are solved: by layer, then by index.
+    ik_bone_indices: Vec<usize>,
+}
+
+#[derive(Debug, Clone)]
+struct IkSolverBone {
+    name: String,
+    parent_index: Option<usize>,
+    /// Model-space position at the bind pose.
+    position: Vec3,
+    ik: Option<IkChain>,
+}
+
+#[derive(Debug, Clone)]
+struct IkChain {
+    target_index: usize,
+    loop_count: u32,
+    /// Largest turn of a link per step in radians; not capped if zero or less.
+    limit_angle: f32,
+    links: Vec<IkLink>,
+}
+
+#[derive(Debug, Clone)]
+struct IkLink {
+    bone_index: usize,
+    angle_limit: Option<PmxModelBoneIKAngleLimit>,
+}
+
+impl IkSolver {
+    /// Collects the IK chains of the bones. Chains whose target or links are out of bounds are
+    /// ignored, as are the links that point at the IK bone itself.
+    pub fn new(bones: &[PmxModelBone]) -> Self {
+        let resolve_index = |index: u32| Some(index as usize).filter(|&index| index < bones.len());
+
+        let solver_bones = Vec::from_iter(bones.iter().enumerate().map(|(index, bone)| {
+            let ik = bone.ik.as_ref().and_then(|ik| {
+                let links = ik
+                    .links
+                    .iter()
+                    .map(|link| {
+                        Some(IkLink {
+                            bone_index: resolve_index(link.index).filter(|&link| link != index)?,
+                            angle_limit: link.angle_limit.clone(),
+                        })
+                    })
+                    .collect::<Option<Vec<_>>>()?;
+
+                Some(IkChain {
+                    target_index: resolve_index(ik.index)?,
+                    loop_count: ik.loop_count.max(0) as u32,
+                    limit_angle: ik.limit_angle,
+                    links,
+                })
+            });
+
+            IkSolverBone {
+                name: bone.name.clone(),
+                parent_index: bone
+                    .parent_index
+                    .and_then(resolve_index)
+                    .filter(|&parent| parent != index),
+                position: bone.position,
+                ik,
+            }
+        }));
+
+        let mut ik_bone_indices = Vec::from_iter(
+            solver_bones
+                .iter()
+                .enumerate()
+                .filter(|(_, bone)| bone.ik.is_some())
+                .map(|(index, _)| index),
+        );
+        ik_bone_indices.sort_by_key(|&index| (bones[index].layer, index));
+
+        Self {
+            name_index_map: bones
+                .iter()
+                .enumerate()
+                .map(|(index, bone)| (bone.name.clone(), index as u32))
+                .collect(),
+            bones: solver_bones,
+            ik_bone_indices,
+        }
+    }
+
+    /// Returns the number of IK bones.
+    pub fn ik_bone_count(&self) -> usize {
+        self.ik_bone_indices.len()
+    }
+
+    /// Solves the IK chains in turn, writing the local rotations of their links. Bones without
+    /// an entry are at their bind poses.
+    pub fn solve(&self, rotations: &mut HashMap<u32, Quat>, translations: &HashMap<u32, Vec3>) {
+        for &ik_bone_index in &self.ik_bone_indices {
+            let chain = self.bones[ik_bone_index].ik.as_ref().unwrap();
+            self.solve_chain(ik_bone_index, chain, rotations, translations);
+        }
+    }
+
+    /// Same as [`IkSolver::solve`], but for sampled key frames, such as the ones of
+    /// [`PmxModelAnimation::sample_bones`](crate::gfx::elements::PmxModelAnimation::sample_bones).
+    /// The links that are turned get an entry if they have none.
+    pub fn solve_sampled(&self, samples: &mut HashMap<String, (Vec3, Quat)>) {
+        let mut rotations = HashMap::new();
+        let mut translations = HashMap::new();
+
+        for (name, (translation, rotation)) in samples.iter() {
+            if let Some(&index) = self.name_index_map.get(name) {
+                rotations.insert(index, *rotation);
+                translations.insert(index, *translation);
+            }
+        }
+
+        self.solve(&mut rotations, &translations);
+
+        for (index, rotation) in rotations {
+            let name = &self.bones[index as usize].name;
+            samples
+                .entry(name.clone())
+                .or_insert((Vec3::ZERO, Quat::IDENTITY))
+                .1 = rotation;
+        }
+    }
+
+    fn solve_chain(
+        &self,
+        ik_bone_index: usize,
+        chain: &IkChain,
+        rotations: &mut HashMap<u32, Quat>,
+        translations: &HashMap<u32, Vec3>,
+    ) {
+        let (goal, _) = self.world_transform(ik_bone_index, rotations, translations);
+
+        for _ in 0..chain.loop_count {
+            for link in &chain.links {
+                let (target, _) = self.world_transform(chain.target_index, rotations, translations);
+
+                if Vec3::distance(target, goal) < IK_TOLERANCE {
+                    return;
+                }
+
+                let (link_position, link_rotation) =
+                    self.world_transform(link.bone_index, rotations, translations);
+                let to_target = target - link_position;
+                let to_goal = goal - link_position;
+                let axis = Vec3::cross(to_target, to_goal);
+                let mut angle = Vec3::angle(to_target, to_goal);
+
+                if axis.len() < f32::EPSILON || angle < f32::EPSILON {
+                    continue;
+                }
+
+                if 0f32 < chain.limit_angle {
+                    angle = angle.min(chain.limit_angle);
+                }
+
+                // the turn is made in the local space of the link, after its current rotation
+                let local_axis = link_rotation.inverted() * axis.normalized();
+                let key = link.bone_index as u32;
+                let rotation = rotations.get(&key).copied().unwrap_or(Quat::IDENTITY)
+                    * Quat::from_axis_angle(local_axis, angle);
+                let rotation = match &link.angle_limit {
+                    Some(angle_limit) => clamp_rotation(rotation, angle_limit),
+                    None => rotation.normalized(),
+                };
+                rotations.insert(key, rotation);
+            }
+        }
+    }
+
+    /// Computes the model-space position and rotation of the bone in the pose, walking up its
+    /// ancestors. A cycle in the parent indices ends the walk once every bone has been visited.
+    fn world_transform(
+        &self,
+        bone_index: usize,
+        rotations: &HashMap<u32, Quat>,
+        translations: &HashMap<u32, Vec3>,
+    ) -> (Vec3, Quat) {
+        let chain = Vec::from_iter(
+            std::iter::successors(Some(bone_index), |&index| self.bones[index].parent_index)
+                .take(self.bones.len()),
+        );
+        let mut position = Vec3::ZERO;
+        let mut rotation = Quat::IDENTITY;
+        let mut parent_position = Vec3::ZERO;
+
+        for &index in chain.iter().rev() {
+            let bone = &self.bones[index];
+            let key = index as u32;
+            let offset = bone.position - parent_position
+                + translations.get(&key).copied().unwrap_or(Vec3::ZERO);
+
+            position += rotation * offset;
+            rotation *= rotations.get(&key).copied().unwrap_or(Quat::IDENTITY);
+            parent_position = bone.position;
+        }
+
+        (position, rotation)
+    }
+}
+
+/// Clamps the ZXY Euler angles of the local rotation to the angle limit of the link. Unlike
+/// [`f32::clamp`], this does not panic on the malformed limits a model may have: if the minimum
+/// is above the maximum, the maximum wins, and a NaN bound is ignored.
+fn clamp_rotation(rotation: Quat, angle_limit: &PmxModelBoneIKAngleLimit) -> Quat {
+    let angles = rotation.normalized().to_euler();
+    let clamp = |angle: f32, min: f32, max: f32| angle.max(min).min(max);
+    Quat::from_euler(
+        clamp(angles.x, angle_limit.min.x, angle_limit.max.x),
+        clamp(angles.y, angle_limit.min.y, angle_limit.max.y),
+        clamp(angles.z, angle_limit.min.z, angle_limit.max.z),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lvl_resource::{PmxModelBoneFlags, PmxModelBoneIK, PmxModelBoneIKLink};
+
+    fn make_bone(name: &str, position: Vec3, parent_index: Option<u32>) -> PmxModelBone {
+        PmxModelBone {
+            name: name.to_owned(),
+            position,
+            parent_index,
+            layer: 0,
+            flags: PmxModelBoneFlags {
+                supports_ik: false,
+                inherit_rotation: false,
+                inherit_translation: false,
+                local_coordinate: false,
+                physics_after_deform: false,
+            },
+            inheritance: None,
+            ik: None,
+        }
+    }
+
+    /// A 2-bone arm in the xy plane, from the origin up to `(0, 2, 0)`, and an IK bone at
+    /// `(1, 1, 0)` that pulls the tip with the elbow and the shoulder.
+    fn make_arm(elbow_limit: Option<PmxModelBoneIKAngleLimit>) -> Vec<PmxModelBone> {
+        let mut ik_bone = make_bone("ik", Vec3::new(1.0, 1.0, 0.0), None);
+        ik_bone.flags.supports_ik = true;
+        ik_bone.ik = Some(PmxModelBoneIK {
+            index: 2,
+            loop_count: 40,
+            limit_angle: 0.5,
+            links: vec![
+                PmxModelBoneIKLink {
+                    index: 1,
+                    angle_limit: elbow_limit,
+                },
+                PmxModelBoneIKLink {
+                    index: 0,
+                    angle_limit: None,
+                },
+            ],
+        });
+
+        vec![
+            make_bone("shoulder", Vec3::ZERO, None),
+            make_bone("elbow", Vec3::new(0.0, 1.0, 0.0), Some(0)),
+            make_bone("tip", Vec3::new(0.0, 2.0, 0.0), Some(1)),
+            ik_bone,
+        ]
+    }
+
+    #[test]
+    fn check_planar_chain_reaches_goal() {
+        let solver = IkSolver::new(&make_arm(None));
+        assert_eq!(solver.ik_bone_count(), 1);
+
+        let mut rotations = HashMap::new();
+        solver.solve(&mut rotations, &HashMap::new());
+
+        let (tip, _) = solver.world_transform(2, &rotations, &HashMap::new());
+        assert!(
+            Vec3::distance(tip, Vec3::new(1.0, 1.0, 0.0)) < 1e-3,
+            "{}",
+            tip
+        );
+
+        // the chain is turned in its plane
+        for rotation in rotations.values() {
+            assert!(rotation.x.abs() < 1e-4 && rotation.y.abs() < 1e-4);
+        }
+
+        // moving the IK bone moves the goal
+        let translations = HashMap::from([(3, Vec3::new(-2.0, 0.0, 0.0))]);
+        let mut rotations = HashMap::new();
+        solver.solve(&mut rotations, &translations);
+
+        let (tip, _) = solver.world_transform(2, &rotations, &translations);
+        assert!(
+            Vec3::distance(tip, Vec3::new(-1.0, 1.0, 0.0)) < 1e-3,
+            "{}",
+            tip
+        );
+    }
+
+    #[test]
+    fn check_angle_limit_holds_link() {
+        // the elbow cannot bend around the z axis, so only the shoulder turns
+        let solver = IkSolver::new(&make_arm(Some(PmxModelBoneIKAngleLimit {
+            min: Vec3::ZERO,
+            max: Vec3::ZERO,
+        })));
+        let mut samples = HashMap::new();
+        solver.solve_sampled(&mut samples);
+
+        let (_, elbow_rotation) = samples["elbow"];
+        assert!(Quat::dot(elbow_rotation, Quat::IDENTITY).abs() > 1.0 - 1e-4);
+
+        // the straight arm points at the goal
+        let (_, shoulder_rotation) = samples["shoulder"];
+        let direction = shoulder_rotation * Vec3::new(0.0, 1.0, 0.0);
+        let expected = Vec3::new(1.0, 1.0, 0.0).normalized();
+        assert!(Vec3::distance(direction, expected) < 1e-3, "{}", direction);
+    }
+
+    #[test]
+    fn check_malformed_angle_limit() {
+        let rotation = Quat::from_euler(0.0, 0.0, 0.5);
+
+        // the bounds are swapped, so the maximum wins
+        let swapped = PmxModelBoneIKAngleLimit {
+            min: Vec3::new(0.0, 0.0, 1.0),
+            max: Vec3::new(0.0, 0.0, -1.0),
+        };
+        let clamped = clamp_rotation(rotation, &swapped).to_euler();
+        assert!((clamped.z + 1.0).abs() < 1e-4, "{}", clamped);
+
+        let nan = PmxModelBoneIKAngleLimit {
+            min: Vec3::new(f32::NAN, f32::NAN, f32::NAN),
+            max: Vec3::new(f32::NAN, f32::NAN, f32::NAN),
+        };
+        let clamped = clamp_rotation(rotation, &nan).to_euler();
+        assert!((clamped.z - 0.5).abs() < 1e-4, "{}", clamped);
+
+        let mut samples = HashMap::new();
+        IkSolver::new(&make_arm(Some(swapped))).solve_sampled(&mut samples);
+        assert!(samples.contains_key("elbow"));
+    }
+}
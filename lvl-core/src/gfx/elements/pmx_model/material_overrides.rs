@@ -0,0 +1,64 @@
+use super::PmxModelElement;
+use crate::gfx::elements::MaterialPropertyValue;
+use std::collections::BTreeMap;
+
+/// Material property values of a single model instance that take precedence over the values
+/// of the source and the material morphs.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct MaterialOverrides {
+    overrides: BTreeMap<(usize, String), MaterialPropertyValue>,
+}
+
+impl MaterialOverrides {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, element_index: usize, name: &str) -> Option<&MaterialPropertyValue> {
+        self.overrides.get(&(element_index, name.to_owned()))
+    }
+
+    pub fn insert(&mut self, element_index: usize, name: &str, value: MaterialPropertyValue) {
+        self.overrides
+            .insert((element_index, name.to_owned()), value);
+    }
+
+    /// Writes the overrides into the materials again, e.g. after the morphs rewrote them.
+    pub fn apply(&self, elements: &mut [PmxModelElement]) {
+        for ((element_index, name), value) in &self.overrides {
+            if let Some(element) = elements.get_mut(*element_index) {
+                element.material.set_property(name, value.clone());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lvl_math::Vec4;
+
+    fn diffuse_color(overrides: &MaterialOverrides, element_index: usize) -> Option<Vec4> {
+        match overrides.get(element_index, "diffuse_color")? {
+            MaterialPropertyValue::Vec4(color) => Some(*color),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn check_overrides_per_instance() {
+        let mut tinted = MaterialOverrides::new();
+        let other = MaterialOverrides::new();
+        let red = Vec4::new(1.0, 0.0, 0.0, 1.0);
+
+        tinted.insert(1, "diffuse_color", MaterialPropertyValue::Vec4(red));
+
+        assert_eq!(diffuse_color(&tinted, 1), Some(red));
+        assert_eq!(diffuse_color(&tinted, 0), None);
+        assert_eq!(diffuse_color(&other, 1), None);
+
+        let blue = Vec4::new(0.0, 0.0, 1.0, 1.0);
+        tinted.insert(1, "diffuse_color", MaterialPropertyValue::Vec4(blue));
+        assert_eq!(diffuse_color(&tinted, 1), Some(blue));
+    }
+}
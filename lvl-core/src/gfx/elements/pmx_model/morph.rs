@@ -1,5 +1,5 @@
-use super::PmxModelElement;
-use crate::gfx::elements::{Material, MaterialPropertyValue};
+use super::{PmxModelElement, PmxModelError};
+use crate::gfx::elements::{Material, MaterialPropertyValue, SetPropertyError};
 use lvl_math::{Vec3, Vec4};
 use lvl_resource::{
     PmxModelMorph, PmxModelMorphKind, PmxModelMorphMaterialElement, PmxModelMorphMaterialOffsetMode,
@@ -9,6 +9,7 @@ use std::{
     collections::{btree_map::Entry, BTreeMap, HashMap},
     mem::size_of,
     num::NonZeroU64,
+    ops::Range,
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc,
@@ -21,13 +22,49 @@ use wgpu::{
 use zerocopy::AsBytes;
 
 // TODO: make engine decide the maximum morph count, not hardcoded
-const MAX_MORPH_COUNT: usize = 128;
+/// Number of morphs a model can have; the shaders read the coefficients from a fixed-size array.
+pub const MAX_MORPH_COUNT: usize = 128;
+
+/// How the coefficients given to [`Morph::set_morph`] are limited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MorphClampPolicy {
+    /// Clamps the coefficient into `0..=1`.
+    Clamp,
+    /// Allows the coefficient to overshoot, e.g. for group morphs that scale their children.
+    Overshoot,
+}
+
+impl MorphClampPolicy {
+    /// Returns the default policy of the given morph kind. Group morphs and material morphs
+    /// with additive offsets may overshoot; all other morphs are clamped.
+    pub fn from_kind(kind: &PmxModelMorphKind) -> Self {
+        match kind {
+            PmxModelMorphKind::Group(_) => Self::Overshoot,
+            PmxModelMorphKind::Material(elements)
+                if elements.iter().any(|element| {
+                    element.offset_mode == PmxModelMorphMaterialOffsetMode::Additive
+                }) =>
+            {
+                Self::Overshoot
+            }
+            _ => Self::Clamp,
+        }
+    }
+
+    pub fn apply(self, coefficient: f32) -> f32 {
+        match self {
+            Self::Clamp => coefficient.clamp(0.0, 1.0),
+            Self::Overshoot => coefficient,
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct Morph {
     is_dirty: AtomicBool,
     is_material_dirty: AtomicBool,
     kinds: Vec<PmxModelMorphKind>,
+    clamp_policies: Vec<MorphClampPolicy>,
     name_index_map: HashMap<String, u32>,
     material_values: Vec<MaterialValue>,
     material_active_offsets: RefCell<Vec<MaterialActiveOffset>>,
@@ -41,9 +78,12 @@ impl Morph {
         morphs: &[PmxModelMorph],
         elements: &mut [PmxModelElement],
         device: &Device,
-    ) -> Self {
+    ) -> Result<Self, PmxModelError> {
         if MAX_MORPH_COUNT < morphs.len() {
-            panic!("morph count is greater than the maximum morph count");
+            return Err(PmxModelError::TooManyMorphs {
+                count: morphs.len(),
+                max: MAX_MORPH_COUNT,
+            });
         }
 
         let mut group_coefficients = Vec::with_capacity(morphs.len());
@@ -53,18 +93,15 @@ impl Morph {
         }
 
         let individual_coefficients = vec![0f32; MAX_MORPH_COUNT];
-        let coefficients_buffer = device.create_buffer_init(&BufferInitDescriptor {
-            label: None,
-            contents: individual_coefficients.as_bytes(),
-            usage: BufferUsages::COPY_DST | BufferUsages::STORAGE,
-        });
-        let coefficients_buffer = Arc::new(coefficients_buffer);
+        let coefficients_buffer = create_coefficients_buffer(&individual_coefficients, device);
 
         let mut kinds = Vec::with_capacity(morphs.len());
+        let mut clamp_policies = Vec::with_capacity(morphs.len());
         let mut name_index_map = HashMap::with_capacity(morphs.len());
 
         for (index, morph) in morphs.iter().enumerate() {
             kinds.push(morph.kind.clone());
+            clamp_policies.push(MorphClampPolicy::from_kind(&morph.kind));
             name_index_map.insert(morph.name.clone(), index as u32);
         }
 
@@ -74,27 +111,106 @@ impl Morph {
         for element in elements {
             material_values.push(MaterialValue::from_material(&element.material));
             material_active_offsets.push(MaterialActiveOffset::new());
-
-            element.material.set_property(
-                "morph_coefficients",
-                MaterialPropertyValue::StorageBuffer {
-                    buffer: coefficients_buffer.clone(),
-                    offset: 0,
-                    size: NonZeroU64::new((size_of::<[f32; 128]>()) as u64).unwrap(),
-                },
-            );
+            bind_coefficients_buffer(&mut element.material, &coefficients_buffer);
         }
 
-        Self {
+        Ok(Self {
             is_dirty: AtomicBool::new(false),
             is_material_dirty: AtomicBool::new(false),
             kinds,
+            clamp_policies,
             name_index_map,
             material_values,
             material_active_offsets: RefCell::new(material_active_offsets),
             group_coefficients,
             individual_coefficients,
             individual_coefficients_buffer: coefficients_buffer,
+        })
+    }
+
+    /// Creates a fresh morph state with all coefficients reset, sharing the morph definitions.
+    /// The given elements are expected to be duplicates of the ones this morph was created for;
+    /// their materials are reset to the base values and bound to the new coefficient buffer.
+    pub(crate) fn share(&self, elements: &mut [PmxModelElement], device: &Device) -> Self {
+        let mut group_coefficients = Vec::with_capacity(self.kinds.len());
+
+        for _ in 0..self.kinds.len() {
+            group_coefficients.push(HashMap::new());
+        }
+
+        let individual_coefficients = vec![0f32; MAX_MORPH_COUNT];
+        let coefficients_buffer = create_coefficients_buffer(&individual_coefficients, device);
+
+        let mut material_active_offsets = Vec::with_capacity(elements.len());
+
+        for (element_index, element) in elements.iter_mut().enumerate() {
+            material_active_offsets.push(MaterialActiveOffset::new());
+            self.material_values[element_index].apply(&mut element.material);
+            bind_coefficients_buffer(&mut element.material, &coefficients_buffer);
+        }
+
+        Self {
+            is_dirty: AtomicBool::new(false),
+            is_material_dirty: AtomicBool::new(false),
+            kinds: self.kinds.clone(),
+            clamp_policies: self.clamp_policies.clone(),
+            name_index_map: self.name_index_map.clone(),
+            material_values: self.material_values.clone(),
+            material_active_offsets: RefCell::new(material_active_offsets),
+            group_coefficients,
+            individual_coefficients,
+            individual_coefficients_buffer: coefficients_buffer,
+        }
+    }
+
+    /// Returns the names of all morphs, in no particular order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.name_index_map.keys().map(|name| name.as_str())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.name_index_map.contains_key(name)
+    }
+
+    /// Resets the materials of the given elements to the values they had before any morph applied.
+    pub(crate) fn restore_material_values(&self, elements: &mut [PmxModelElement]) {
+        for (element, value) in elements.iter_mut().zip(&self.material_values) {
+            value.apply(&mut element.material);
+        }
+    }
+
+    /// Takes the current values of the materials as the values before any morph applies, e.g.
+    /// after the materials have been reloaded. The active material morphs are applied again by
+    /// the next [`Self::update_material_values`].
+    pub(crate) fn refresh_material_values(&mut self, elements: &[PmxModelElement]) {
+        self.material_values = Vec::from_iter(
+            elements
+                .iter()
+                .map(|element| MaterialValue::from_material(&element.material)),
+        );
+
+        for offsets in self.material_active_offsets.get_mut() {
+            offsets.is_dirty = true;
+        }
+
+        *self.is_material_dirty.get_mut() = true;
+    }
+
+    /// Returns the coefficient the morph was set to, without the ones of the group morphs.
+    pub fn coefficient(&self, name: &str) -> Option<f32> {
+        let morph_index = *self.name_index_map.get(name)?;
+        Some(self.individual_coefficients[morph_index as usize])
+    }
+
+    pub fn clamp_policy(&self, name: &str) -> Option<MorphClampPolicy> {
+        let morph_index = *self.name_index_map.get(name)?;
+        Some(self.clamp_policies[morph_index as usize])
+    }
+
+    /// Overrides the clamp policy of the given morph. It applies from the next coefficient set.
+    pub fn set_clamp_policy(&mut self, name: &str, policy: MorphClampPolicy) {
+        if let Some(morph_index) = self.name_index_map.get(name) {
+            self.clamp_policies[*morph_index as usize] = policy;
         }
     }
 
@@ -105,6 +221,7 @@ impl Morph {
                 return;
             }
         };
+        let coefficient = self.clamp_policies[morph_index as usize].apply(coefficient);
 
         if (self.individual_coefficients[morph_index as usize] - coefficient).abs() <= 0.001 {
             return;
@@ -159,6 +276,30 @@ impl Morph {
         }
     }
 
+    /// Returns the range of the vertex displacement texture entries owned by the given morph.
+    pub fn vertex_displacement_range(&self, name: &str) -> Option<Range<u32>> {
+        let morph_index = *self.name_index_map.get(name)?;
+
+        match &self.kinds[morph_index as usize] {
+            PmxModelMorphKind::Vertex { displacement_range } => {
+                Some(displacement_range.0..displacement_range.1)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the range of the uv displacement texture entries owned by the given morph.
+    pub fn uv_displacement_range(&self, name: &str) -> Option<Range<u32>> {
+        let morph_index = *self.name_index_map.get(name)?;
+
+        match &self.kinds[morph_index as usize] {
+            PmxModelMorphKind::Uv { displacement_range } => {
+                Some(displacement_range.0..displacement_range.1)
+            }
+            _ => None,
+        }
+    }
+
     fn update_material_offsets(&self, morph_index: u32, element: &PmxModelMorphMaterialElement) {
         let coefficient = self.compute_final_coefficient(morph_index);
         let is_removed = coefficient.abs() <= 0.001;
@@ -242,6 +383,34 @@ impl Morph {
     }
 }
 
+fn create_coefficients_buffer(coefficients: &[f32], device: &Device) -> Arc<Buffer> {
+    let buffer = device.create_buffer_init(&BufferInitDescriptor {
+        label: None,
+        contents: coefficients.as_bytes(),
+        usage: BufferUsages::COPY_DST | BufferUsages::STORAGE,
+    });
+    Arc::new(buffer)
+}
+
+/// Makes the properties driven by the material morphs take the values of the source when the
+/// material is reloaded, as the values written by the morphs are not overrides.
+pub(crate) fn reset_material_value_overrides(material: &mut Material) {
+    for (name, _) in MaterialValue::from_material(material).properties() {
+        material.reset_property_override(name);
+    }
+}
+
+fn bind_coefficients_buffer(material: &mut Material, buffer: &Arc<Buffer>) {
+    material.set_property(
+        "morph_coefficients",
+        MaterialPropertyValue::StorageBuffer {
+            buffer: buffer.clone(),
+            offset: 0,
+            size: NonZeroU64::new((size_of::<[f32; 128]>()) as u64).unwrap(),
+        },
+    );
+}
+
 #[derive(Debug, Clone)]
 pub struct MaterialValue {
     pub diffuse_color: Vec4,
@@ -318,52 +487,71 @@ impl MaterialValue {
         }
     }
 
+    /// Returns the material properties driven by material morphs, by name.
+    pub fn properties(&self) -> [(&'static str, MaterialPropertyValue); 12] {
+        [
+            (
+                "diffuse_color",
+                MaterialPropertyValue::Vec4(self.diffuse_color),
+            ),
+            (
+                "specular_color",
+                MaterialPropertyValue::Vec3(self.specular_color),
+            ),
+            (
+                "specular_strength",
+                MaterialPropertyValue::Float(self.specular_strength),
+            ),
+            (
+                "ambient_color",
+                MaterialPropertyValue::Vec3(self.ambient_color),
+            ),
+            ("edge_color", MaterialPropertyValue::Vec4(self.edge_color)),
+            ("edge_size", MaterialPropertyValue::Float(self.edge_size)),
+            (
+                "texture_tint_color_mul",
+                MaterialPropertyValue::Vec4(self.texture_tint_color_mul),
+            ),
+            (
+                "texture_tint_color_add",
+                MaterialPropertyValue::Vec4(self.texture_tint_color_add),
+            ),
+            (
+                "env_tint_color_mul",
+                MaterialPropertyValue::Vec4(self.environment_tint_color_mul),
+            ),
+            (
+                "env_tint_color_add",
+                MaterialPropertyValue::Vec4(self.environment_tint_color_add),
+            ),
+            (
+                "toon_tint_color_mul",
+                MaterialPropertyValue::Vec4(self.toon_tint_color_mul),
+            ),
+            (
+                "toon_tint_color_add",
+                MaterialPropertyValue::Vec4(self.toon_tint_color_add),
+            ),
+        ]
+    }
+
+    /// Sets the properties on the material. The tints of the toon and environment textures and
+    /// the outline properties are skipped if the shader does not declare them, as the shader
+    /// variants without those textures omit them and no shader draws the outline.
     pub fn apply(&self, material: &mut Material) {
-        material.set_property(
-            "diffuse_color",
-            MaterialPropertyValue::Vec4(self.diffuse_color),
-        );
-        material.set_property(
-            "specular_color",
-            MaterialPropertyValue::Vec3(self.specular_color),
-        );
-        material.set_property(
-            "specular_strength",
-            MaterialPropertyValue::Float(self.specular_strength),
-        );
-        material.set_property(
-            "ambient_color",
-            MaterialPropertyValue::Vec3(self.ambient_color),
-        );
-        material.set_property("edge_color", MaterialPropertyValue::Vec4(self.edge_color));
-        material.set_property("edge_size", MaterialPropertyValue::Float(self.edge_size));
-        material.set_property(
-            "texture_tint_color_mul ",
-            MaterialPropertyValue::Vec4(self.texture_tint_color_mul),
-        );
-        material.set_property(
-            "texture_tint_color_add",
-            MaterialPropertyValue::Vec4(self.texture_tint_color_add),
-        );
-        material.set_property(
-            "env_tint_color_mul",
-            MaterialPropertyValue::Vec4(self.environment_tint_color_mul),
-        );
-        material.set_property(
-            "env_tint_color_add",
-            MaterialPropertyValue::Vec4(self.environment_tint_color_add),
-        );
-        material.set_property(
-            "toon_tint_color_mul",
-            MaterialPropertyValue::Vec4(self.toon_tint_color_mul),
-        );
-        material.set_property(
-            "toon_tint_color_add",
-            MaterialPropertyValue::Vec4(self.toon_tint_color_add),
-        );
+        for (name, value) in self.properties() {
+            let result = material.set_property_checked(name, value);
+            let is_omitted = matches!(result, Err(SetPropertyError::PropertyNotFound(_)))
+                && is_optional_property(name);
+            debug_assert!(result.is_ok() || is_omitted, "{:?}", result);
+        }
     }
 }
 
+fn is_optional_property(name: &str) -> bool {
+    name.starts_with("env_") || name.starts_with("toon_") || name.starts_with("edge_")
+}
+
 #[derive(Debug)]
 pub struct MaterialActiveOffset {
     pub is_dirty: bool,
@@ -490,3 +678,196 @@ impl MaterialOffset {
 fn lerp_unclamped_f32(a: f32, b: f32, t: f32) -> f32 {
     a + (b - a) * t
 }
+
+/// A shader with the uniforms of the material morphs that every standard shader declares.
+#[cfg(test)]
+const MATERIAL_SHADER: &str = r#"
+    struct MaterialUniform {
+        diffuse_color: vec4<f32>,
+        specular_color: vec3<f32>,
+        specular_strength: f32,
+        ambient_color: vec3<f32>,
+        texture_tint_color_mul: vec4<f32>,
+        texture_tint_color_add: vec4<f32>,
+    }
+
+    @group(1) @binding(0) var<uniform> material: MaterialUniform;
+
+    @vertex
+    fn vs_main() -> @builtin(position) vec4<f32> {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+
+    @fragment
+    fn fs_main() -> @location(0) vec4<f32> {
+        let specular = material.specular_color * material.specular_strength;
+        let color = material.diffuse_color.rgb + specular + material.ambient_color;
+        return vec4<f32>(color, 1.0) * material.texture_tint_color_mul
+            + material.texture_tint_color_add;
+    }
+"#;
+
+/// Makes a shader source with the uniforms of the material morphs, for the tests of the models.
+#[cfg(test)]
+pub(crate) fn make_material_shader_source() -> lvl_resource::ShaderSource {
+    use crate::gfx::elements::make_shader_source_descriptor;
+    use lvl_resource::{
+        ShaderBinding, ShaderBindingKind, ShaderSource, ShaderSourceDescriptor, ShaderUniformMember,
+    };
+
+    let member = |name: &str, offset, size| ShaderUniformMember {
+        name: name.to_owned(),
+        offset,
+        size: NonZeroU64::new(size).unwrap(),
+        buffer_index: 0,
+    };
+
+    ShaderSource::new(ShaderSourceDescriptor {
+        bindings: vec![ShaderBinding {
+            name: "material".to_owned(),
+            group: 1,
+            binding: 0,
+            kind: ShaderBindingKind::UniformBuffer {
+                index: 0,
+                size: NonZeroU64::new(80).unwrap(),
+                is_struct: true,
+            },
+        }],
+        uniform_members: vec![
+            member("diffuse_color", 0, 16),
+            member("specular_color", 16, 12),
+            member("specular_strength", 28, 4),
+            member("ambient_color", 32, 12),
+            member("texture_tint_color_mul", 48, 16),
+            member("texture_tint_color_add", 64, 16),
+        ],
+        ..make_shader_source_descriptor(MATERIAL_SHADER)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gfx::{
+        elements::{make_material_render_state, Shader},
+        test_gfx_ctx,
+    };
+    use lvl_resource::{
+        MaterialProperty, MaterialPropertyUniformValue, MaterialSource, PmxModelMorphGroupElement,
+    };
+
+    fn material_element(
+        offset_mode: PmxModelMorphMaterialOffsetMode,
+    ) -> PmxModelMorphMaterialElement {
+        PmxModelMorphMaterialElement {
+            material_index: None,
+            offset_mode,
+            diffuse_color: Vec4::ZERO,
+            specular_color: Vec3::ZERO,
+            specular_strength: 0.0,
+            ambient_color: Vec3::ZERO,
+            edge_color: Vec4::ZERO,
+            edge_size: 0.0,
+            texture_tint_color: Vec4::ZERO,
+            environment_tint_color: Vec4::ZERO,
+            toon_tint_color: Vec4::ZERO,
+        }
+    }
+
+    #[test]
+    fn check_material_morph_sets_properties() {
+        let gfx_ctx = match test_gfx_ctx() {
+            Some(gfx_ctx) => gfx_ctx,
+            None => {
+                return;
+            }
+        };
+        let shader_source = make_material_shader_source();
+        let shader = Arc::new(Shader::load_from_source(&shader_source, &gfx_ctx));
+        let material_source = MaterialSource::new(
+            "material".to_owned(),
+            make_material_render_state(),
+            vec![MaterialProperty {
+                name: "diffuse_color".to_owned(),
+                value: lvl_resource::MaterialPropertyValue::Uniform(
+                    MaterialPropertyUniformValue::Vec4(Vec4::new(1.0, 1.0, 1.0, 0.5)),
+                ),
+            }],
+        );
+        let mut material = Material::load_from_source(
+            |_| Some((shader.clone(), &shader_source)),
+            |_| None,
+            &material_source,
+            &gfx_ctx,
+        );
+
+        let mut value = MaterialValue::from_material(&material);
+        let offset = MaterialOffset {
+            diffuse_color: Vec4::new(0.5, 0.5, 0.5, 1.0),
+            texture_tint_color: Vec4::new(0.0, 0.5, 1.0, 1.0),
+            ..MaterialOffset::from_element(&material_element(
+                PmxModelMorphMaterialOffsetMode::Multiply,
+            ))
+        };
+        offset.apply(&mut value, 1.0);
+        value.apply(&mut material);
+
+        let property = |name| match material
+            .get_property(name)
+            .and_then(|property| property.value())
+        {
+            Some(MaterialPropertyValue::Vec4(value)) => *value,
+            value => panic!("`{}` is not a vec4: {:?}", name, value),
+        };
+        assert_eq!(property("diffuse_color"), Vec4::new(0.5, 0.5, 0.5, 0.5));
+        assert_eq!(
+            property("texture_tint_color_mul"),
+            Vec4::new(0.0, 0.5, 1.0, 1.0)
+        );
+        assert_eq!(property("texture_tint_color_add"), Vec4::ZERO);
+    }
+
+    #[test]
+    fn check_vertex_morph_clamped() {
+        let policy = MorphClampPolicy::from_kind(&PmxModelMorphKind::Vertex {
+            displacement_range: (0, 4),
+        });
+
+        assert_eq!(policy, MorphClampPolicy::Clamp);
+        assert_eq!(policy.apply(2.0), 1.0);
+        assert_eq!(policy.apply(-0.5), 0.0);
+        assert_eq!(policy.apply(0.25), 0.25);
+    }
+
+    #[test]
+    fn check_group_morph_overshoots() {
+        let policy = MorphClampPolicy::from_kind(&PmxModelMorphKind::Group(vec![
+            PmxModelMorphGroupElement {
+                morph_index: 0,
+                coefficient: 1.0,
+            },
+        ]));
+
+        assert_eq!(policy, MorphClampPolicy::Overshoot);
+        assert_eq!(policy.apply(2.0), 2.0);
+    }
+
+    #[test]
+    fn check_material_morph_policy() {
+        let multiply = PmxModelMorphKind::Material(vec![material_element(
+            PmxModelMorphMaterialOffsetMode::Multiply,
+        )]);
+        let additive = PmxModelMorphKind::Material(vec![material_element(
+            PmxModelMorphMaterialOffsetMode::Additive,
+        )]);
+
+        assert_eq!(
+            MorphClampPolicy::from_kind(&multiply),
+            MorphClampPolicy::Clamp
+        );
+        assert_eq!(
+            MorphClampPolicy::from_kind(&additive),
+            MorphClampPolicy::Overshoot
+        );
+    }
+}
@@ -0,0 +1,789 @@
+use super::{PmxModelElement, PmxModelError};
+use crate::gfx::elements::{Material, MaterialPropertyValue};
+use lvl_math::{Mat4, Quat, Vec3, Vec4};
+use lvl_resource::PmxModelBone;
+use std::{
+    collections::HashMap,
+    mem::size_of,
+    num::NonZeroU64,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    Buffer, BufferUsages, Device, Queue,
+};
+use zerocopy::AsBytes;
+
+/// Number of bones a model can have; the shaders read the matrices from a fixed-size array.
+pub const MAX_BONE_COUNT: usize = 1024;
+
+/// The pose of the bones of a model, which deforms the vertices in the vertex shader.
+/// Each bone has a skinning matrix, which moves a vertex from the bind pose to the current pose;
+/// all of them are identity at the bind pose.
+#[derive(Debug)]
+pub struct Skeleton {
+    is_dirty: AtomicBool,
+    name_index_map: HashMap<String, u32>,
+    bones: Vec<SkeletonBone>,
+    inverse_bind_matrices: Vec<Mat4>,
+    skinning_matrices: Vec<Mat4>,
+    skinning_matrices_buffer: Arc<Buffer>,
+}
+
+impl Skeleton {
+    pub fn new(
+        bones: &[PmxModelBone],
+        inverse_bind_matrices: &[Mat4],
+        elements: &mut [PmxModelElement],
+        device: &Device,
+    ) -> Result<Self, PmxModelError> {
+        if MAX_BONE_COUNT < bones.len() {
+            return Err(PmxModelError::TooManyBones {
+                count: bones.len(),
+                max: MAX_BONE_COUNT,
+            });
+        }
+
+        let name_index_map = bones
+            .iter()
+            .enumerate()
+            .map(|(index, bone)| (bone.name.clone(), index as u32))
+            .collect();
+        let skeleton_bones = bones
+            .iter()
+            .map(|bone| SkeletonBone {
+                parent_index: bone
+                    .parent_index
+                    .filter(|&parent_index| (parent_index as usize) < bones.len()),
+                position: bone.position,
+            })
+            .collect();
+        // the bones without an inverse bind matrix are bound at the origin
+        let inverse_bind_matrices = (0..bones.len())
+            .map(|index| {
+                inverse_bind_matrices
+                    .get(index)
+                    .cloned()
+                    .unwrap_or_else(Mat4::identity)
+            })
+            .collect();
+
+        Ok(Self::with_bones(
+            name_index_map,
+            skeleton_bones,
+            inverse_bind_matrices,
+            elements,
+            device,
+        ))
+    }
+
+    /// Creates a skeleton at the bind pose with the same bones, for the given elements, which are
+    /// expected to be duplicates of the ones this skeleton was created for.
+    pub(crate) fn share(&self, elements: &mut [PmxModelElement], device: &Device) -> Self {
+        Self::with_bones(
+            self.name_index_map.clone(),
+            self.bones.clone(),
+            self.inverse_bind_matrices.clone(),
+            elements,
+            device,
+        )
+    }
+
+    fn with_bones(
+        name_index_map: HashMap<String, u32>,
+        bones: Vec<SkeletonBone>,
+        inverse_bind_matrices: Vec<Mat4>,
+        elements: &mut [PmxModelElement],
+        device: &Device,
+    ) -> Self {
+        let skinning_matrices = vec![Mat4::identity(); inverse_bind_matrices.len()];
+        let skinning_matrices_buffer = create_skinning_matrices_buffer(device);
+
+        for element in elements {
+            bind_skinning_matrices_buffer(&mut element.material, &skinning_matrices_buffer);
+        }
+
+        Self {
+            is_dirty: AtomicBool::new(false),
+            name_index_map,
+            bones,
+            inverse_bind_matrices,
+            skinning_matrices,
+            skinning_matrices_buffer,
+        }
+    }
+
+    pub fn bone_count(&self) -> usize {
+        self.inverse_bind_matrices.len()
+    }
+
+    pub fn bone_index(&self, name: &str) -> Option<u32> {
+        self.name_index_map.get(name).copied()
+    }
+
+    pub fn skinning_matrices(&self) -> &[Mat4] {
+        &self.skinning_matrices
+    }
+
+    /// Places the bone by its model-space matrix in the current pose. Does nothing if the bone
+    /// does not exist.
+    pub fn set_bone_matrix(&mut self, bone_index: u32, matrix: &Mat4) {
+        let index = bone_index as usize;
+
+        if let Some(inverse_bind_matrix) = self.inverse_bind_matrices.get(index) {
+            self.skinning_matrices[index] = inverse_bind_matrix * matrix;
+            self.is_dirty.store(true, Ordering::SeqCst);
+        }
+    }
+
+    /// Same as [`Skeleton::set_bone_matrix`], but finds the bone by its name.
+    pub fn set_bone_matrix_by_name(&mut self, name: &str, matrix: &Mat4) {
+        if let Some(bone_index) = self.bone_index(name) {
+            self.set_bone_matrix(bone_index, matrix);
+        }
+    }
+
+    /// Poses the whole skeleton from the local poses of the bones, keyed by bone name. Each one
+    /// transforms the bone relative to its bind pose, around its own position, and moves its
+    /// descendants along. The bones without a pose stay in their bind pose relative to their
+    /// parents.
+    pub fn set_local_poses(&mut self, local_poses: &HashMap<String, Mat4>) {
+        let mut local_matrices = vec![None; self.bones.len()];
+
+        for (name, matrix) in local_poses {
+            if let Some(bone_index) = self.bone_index(name) {
+                local_matrices[bone_index as usize] = Some(matrix.clone());
+            }
+        }
+
+        let pose_matrices = pose_matrices(&self.bones, &local_matrices);
+
+        for (index, pose_matrix) in pose_matrices.iter().enumerate() {
+            self.skinning_matrices[index] = &self.inverse_bind_matrices[index] * pose_matrix;
+        }
+
+        self.is_dirty.store(true, Ordering::SeqCst);
+    }
+
+    /// Takes the pose of the other skeleton, which must have the same bones, e.g. the one this
+    /// skeleton was shared from.
+    pub(crate) fn copy_pose(&mut self, other: &Skeleton) {
+        self.skinning_matrices.clone_from(&other.skinning_matrices);
+        self.is_dirty.store(true, Ordering::SeqCst);
+    }
+
+    /// Puts all the bones back to the bind pose.
+    pub fn reset_pose(&mut self) {
+        self.skinning_matrices.fill(Mat4::identity());
+        self.is_dirty.store(true, Ordering::SeqCst);
+    }
+
+    pub(crate) fn update_matrices(&self, queue: &Queue) {
+        if !self.is_dirty.load(Ordering::SeqCst) {
+            return;
+        }
+
+        let bytes = self
+            .skinning_matrices
+            .iter()
+            .flat_map(|matrix| matrix.as_bytes())
+            .copied()
+            .collect::<Vec<_>>();
+        queue.write_buffer(&self.skinning_matrices_buffer, 0, &bytes);
+        self.is_dirty.store(false, Ordering::SeqCst);
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SkeletonBone {
+    parent_index: Option<u32>,
+    /// Model-space position at the bind pose.
+    position: Vec3,
+}
+
+/// Computes the model-space matrix of each bone from the local poses, walking from the roots.
+/// Bones in a parent cycle are treated as roots, as in the inverse bind matrices.
+fn pose_matrices(bones: &[SkeletonBone], local_matrices: &[Option<Mat4>]) -> Vec<Mat4> {
+    let mut matrices: Vec<Option<Mat4>> = vec![None; bones.len()];
+    let mut chain = Vec::new();
+
+    for index in 0..bones.len() {
+        // collect the ancestors that are not computed yet, from the bone up to the root
+        let mut current = Some(index);
+
+        while let Some(bone_index) = current {
+            if matrices[bone_index].is_some() || chain.contains(&bone_index) {
+                break;
+            }
+
+            chain.push(bone_index);
+            current = bones[bone_index].parent_index.map(|index| index as usize);
+        }
+
+        while let Some(bone_index) = chain.pop() {
+            let bone = &bones[bone_index];
+            let local_matrix = local_matrices[bone_index]
+                .clone()
+                .unwrap_or_else(Mat4::identity);
+            let parent = bone
+                .parent_index
+                .map(|index| index as usize)
+                .and_then(|index| Some((index, matrices[index].clone()?)));
+
+            let matrix = match parent {
+                Some((parent_index, parent_matrix)) => {
+                    let offset = bone.position - bones[parent_index].position;
+                    local_matrix * Mat4::translation(offset) * parent_matrix
+                }
+                None => local_matrix * Mat4::translation(bone.position),
+            };
+            matrices[bone_index] = Some(matrix);
+        }
+    }
+
+    matrices.into_iter().flatten().collect()
+}
+
+fn create_skinning_matrices_buffer(device: &Device) -> Arc<Buffer> {
+    let matrices = vec![Mat4::identity(); MAX_BONE_COUNT];
+    let bytes = matrices
+        .iter()
+        .flat_map(|matrix| matrix.as_bytes())
+        .copied()
+        .collect::<Vec<_>>();
+    let buffer = device.create_buffer_init(&BufferInitDescriptor {
+        label: None,
+        contents: &bytes,
+        usage: BufferUsages::COPY_DST | BufferUsages::STORAGE,
+    });
+    Arc::new(buffer)
+}
+
+fn bind_skinning_matrices_buffer(material: &mut Material, buffer: &Arc<Buffer>) {
+    material.set_property(
+        "bone_matrices",
+        MaterialPropertyValue::StorageBuffer {
+            buffer: buffer.clone(),
+            offset: 0,
+            size: NonZeroU64::new((size_of::<Mat4>() * MAX_BONE_COUNT) as u64).unwrap(),
+        },
+    );
+}
+
+/// How a vertex follows the bones, as written in the vertex data by the resource compiler.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VertexDeform {
+    /// Bdef1 = 0, Bdef2 = 1, Bdef4 = 2, Sdef = 3; Qdef is written as Bdef4.
+    pub kind: u32,
+    /// `-1` for the unused slots.
+    pub bone_indices: [i32; 4],
+    pub bone_weights: [f32; 4],
+    pub sdef_c: Vec3,
+    pub sdef_r0: Vec3,
+    pub sdef_r1: Vec3,
+}
+
+/// Deforms a vertex on the CPU, e.g. for picking; the same as the skinning of the standard
+/// shaders. Returns the position and the normal in the current pose.
+pub fn skin_vertex(
+    skinning_matrices: &[Mat4],
+    deform: &VertexDeform,
+    position: Vec3,
+    normal: Vec3,
+) -> (Vec3, Vec3) {
+    let bone_matrix = |slot: usize| {
+        usize::try_from(deform.bone_indices[slot])
+            .ok()
+            .and_then(|index| skinning_matrices.get(index))
+            .cloned()
+            .unwrap_or_else(Mat4::identity)
+    };
+    let transform_point = |matrix: &Mat4, point: Vec3| {
+        let point = Vec4::from_vec3(point, 1.0) * matrix;
+        Vec3::new(point.x, point.y, point.z)
+    };
+    let transform_normal = |matrix: &Mat4, normal: Vec3| {
+        let normal = Vec4::from_vec3(normal, 0.0) * matrix;
+        Vec3::new(normal.x, normal.y, normal.z)
+    };
+
+    match deform.kind {
+        // bdef1 has no weights
+        0 => {
+            let matrix = bone_matrix(0);
+            (
+                transform_point(&matrix, position),
+                transform_normal(&matrix, normal).normalized(),
+            )
+        }
+        3 => {
+            let (matrix_0, matrix_1) = (bone_matrix(0), bone_matrix(1));
+            let (weight_0, weight_1) = (deform.bone_weights[0], deform.bone_weights[1]);
+
+            // the centers of rotation of the bones, moved so that their blend is the center
+            let r = deform.sdef_r0 * weight_0 + deform.sdef_r1 * weight_1;
+            let cr_0 = (deform.sdef_c * 2.0 + deform.sdef_r0 - r) * 0.5;
+            let cr_1 = (deform.sdef_c * 2.0 + deform.sdef_r1 - r) * 0.5;
+
+            let rotation_0 = Quat::from_mat4(&matrix_0);
+            let mut rotation_1 = Quat::from_mat4(&matrix_1);
+
+            if Quat::dot(rotation_0, rotation_1) < 0.0 {
+                rotation_1 = -rotation_1;
+            }
+
+            let rotation = Quat::new(
+                rotation_0.x * weight_0 + rotation_1.x * weight_1,
+                rotation_0.y * weight_0 + rotation_1.y * weight_1,
+                rotation_0.z * weight_0 + rotation_1.z * weight_1,
+                rotation_0.w * weight_0 + rotation_1.w * weight_1,
+            )
+            .normalized();
+
+            (
+                rotation * (position - deform.sdef_c)
+                    + transform_point(&matrix_0, cr_0) * weight_0
+                    + transform_point(&matrix_1, cr_1) * weight_1,
+                (rotation * normal).normalized(),
+            )
+        }
+        _ => {
+            let mut skinned_position = Vec3::ZERO;
+            let mut skinned_normal = Vec3::ZERO;
+
+            for slot in 0..4 {
+                let weight = deform.bone_weights[slot];
+                let matrix = bone_matrix(slot);
+                skinned_position += transform_point(&matrix, position) * weight;
+                skinned_normal += transform_normal(&matrix, normal) * weight;
+            }
+
+            (skinned_position, skinned_normal.normalized())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        gfx::{test_gfx_ctx, GfxContext},
+        scene::Transform,
+    };
+    use std::sync::mpsc;
+    use wgpu::{
+        BindGroupDescriptor, BindGroupEntry, BufferDescriptor, CommandEncoderDescriptor,
+        ComputePassDescriptor, ComputePipelineDescriptor, MaintainBase, MapMode,
+        ShaderModuleDescriptor, ShaderSource,
+    };
+
+    fn equals_vec3(a: Vec3, b: Vec3) -> bool {
+        (a - b).len() < 1e-4
+    }
+
+    fn deform(kind: u32, bone_indices: [i32; 4], bone_weights: [f32; 4]) -> VertexDeform {
+        VertexDeform {
+            kind,
+            bone_indices,
+            bone_weights,
+            sdef_c: Vec3::ZERO,
+            sdef_r0: Vec3::ZERO,
+            sdef_r1: Vec3::ZERO,
+        }
+    }
+
+    /// Two bones at the bind pose: the root at the origin and an arm at `(0, 1, 0)`; the arm is
+    /// rotated by 90 degrees around the z axis in the current pose.
+    fn make_skinning_matrices() -> Vec<Mat4> {
+        let arm_position = Vec3::new(0.0, 1.0, 0.0);
+        let inverse_bind_matrices = [Mat4::identity(), Mat4::translation(-arm_position)];
+        let pose_matrices = [
+            Mat4::identity(),
+            Mat4::rotation(Quat::from_axis_angle(
+                Vec3::new(0.0, 0.0, 1.0),
+                std::f32::consts::FRAC_PI_2,
+            )) * Mat4::translation(arm_position),
+        ];
+
+        inverse_bind_matrices
+            .iter()
+            .zip(&pose_matrices)
+            .map(|(inverse_bind_matrix, pose_matrix)| inverse_bind_matrix * pose_matrix)
+            .collect()
+    }
+
+    #[test]
+    fn check_rotated_bone_moves_bound_vertices() {
+        let matrices = make_skinning_matrices();
+        let position = Vec3::new(0.0, 2.0, 0.0);
+        let normal = Vec3::new(0.0, 1.0, 0.0);
+        let rotated = Quat::from_axis_angle(Vec3::new(0.0, 0.0, 1.0), std::f32::consts::FRAC_PI_2);
+        let expected_position = rotated * Vec3::new(0.0, 1.0, 0.0) + Vec3::new(0.0, 1.0, 0.0);
+        let expected_normal = rotated * normal;
+
+        // bound to the arm
+        let (arm_position, arm_normal) = skin_vertex(
+            &matrices,
+            &deform(0, [1, -1, -1, -1], [0.0; 4]),
+            position,
+            normal,
+        );
+        assert!(equals_vec3(arm_position, expected_position));
+        assert!(equals_vec3(arm_normal, expected_normal));
+
+        // bound to the root, which is not moved
+        let (root_position, root_normal) = skin_vertex(
+            &matrices,
+            &deform(0, [0, -1, -1, -1], [0.0; 4]),
+            position,
+            normal,
+        );
+        assert!(equals_vec3(root_position, position));
+        assert!(equals_vec3(root_normal, normal));
+
+        // half and half
+        let (blended_position, _) = skin_vertex(
+            &matrices,
+            &deform(1, [0, 1, -1, -1], [0.5, 0.5, 0.0, 0.0]),
+            position,
+            normal,
+        );
+        assert!(equals_vec3(
+            blended_position,
+            (position + expected_position) * 0.5
+        ));
+    }
+
+    #[test]
+    fn check_pose_moves_descendants() {
+        let bones = [
+            SkeletonBone {
+                parent_index: None,
+                position: Vec3::new(0.0, 1.0, 0.0),
+            },
+            SkeletonBone {
+                parent_index: Some(0),
+                position: Vec3::new(0.0, 2.0, 0.0),
+            },
+        ];
+        let rotation = Quat::from_axis_angle(Vec3::new(0.0, 0.0, 1.0), std::f32::consts::FRAC_PI_2);
+
+        // at the bind pose, the bones are where they are bound
+        let bind_matrices = pose_matrices(&bones, &[None, None]);
+        assert!(equals_vec3(
+            bind_matrices[1].split_translation(),
+            Vec3::new(0.0, 2.0, 0.0)
+        ));
+
+        // turning the parent carries the child around the parent
+        let matrices = pose_matrices(&bones, &[Some(Mat4::rotation(rotation)), None]);
+        assert!(equals_vec3(
+            matrices[0].split_translation(),
+            Vec3::new(0.0, 1.0, 0.0)
+        ));
+        assert!(equals_vec3(
+            matrices[1].split_translation(),
+            Vec3::new(-1.0, 1.0, 0.0)
+        ));
+
+        // so does a vertex bound to the child
+        let skinning_matrices = bind_matrices
+            .iter()
+            .zip(&matrices)
+            .map(|(bind_matrix, matrix)| bind_matrix.inversed() * matrix)
+            .collect::<Vec<_>>();
+        let (position, _) = skin_vertex(
+            &skinning_matrices,
+            &deform(0, [1, -1, -1, -1], [0.0; 4]),
+            Vec3::new(0.0, 3.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        );
+        assert!(equals_vec3(position, Vec3::new(-2.0, 1.0, 0.0)));
+    }
+
+    #[test]
+    fn check_sampled_pose_skins_single_bone() {
+        let bones = [SkeletonBone {
+            parent_index: None,
+            position: Vec3::new(0.0, 1.0, 0.0),
+        }];
+        let inverse_bind_matrices = [Mat4::translation(Vec3::new(0.0, -1.0, 0.0))];
+        let position = Vec3::new(0.0, 2.0, 0.0);
+        let normal = Vec3::new(0.0, 1.0, 0.0);
+
+        for step in 0..8 {
+            let angle = step as f32 * std::f32::consts::FRAC_PI_4;
+            let rotation = Quat::from_axis_angle(Vec3::new(0.0, 0.0, 1.0), angle);
+            let translation = Vec3::new(0.0, 0.0, 0.5);
+
+            // what `ModelPose::apply` passes to `Skeleton::set_local_poses` for a sampled key frame
+            let local_pose = Transform {
+                position: translation,
+                rotation,
+                ..Transform::identity()
+            };
+            let matrices = pose_matrices(&bones, &[Some(local_pose.matrix())]);
+            let skinning_matrices = [&inverse_bind_matrices[0] * &matrices[0]];
+            let (skinned_position, skinned_normal) = skin_vertex(
+                &skinning_matrices,
+                &deform(0, [0, -1, -1, -1], [0.0; 4]),
+                position,
+                normal,
+            );
+
+            // turned around the bone, then moved by the translation
+            let expected_position =
+                rotation * (position - bones[0].position) + bones[0].position + translation;
+            assert!(equals_vec3(skinned_position, expected_position), "{}", step);
+            assert!(equals_vec3(skinned_normal, rotation * normal), "{}", step);
+        }
+    }
+
+    #[test]
+    fn check_sdef_follows_single_bone() {
+        let matrices = make_skinning_matrices();
+        let position = Vec3::new(0.5, 2.0, 0.0);
+        let normal = Vec3::new(1.0, 0.0, 0.0);
+        let sdef = VertexDeform {
+            kind: 3,
+            bone_indices: [1, 0, -1, -1],
+            bone_weights: [1.0, 0.0, 0.0, 0.0],
+            sdef_c: Vec3::new(0.0, 1.5, 0.0),
+            sdef_r0: Vec3::new(0.0, 1.5, 0.0),
+            sdef_r1: Vec3::new(0.0, 0.5, 0.0),
+        };
+
+        // with all the weight on one bone, sdef is the same as bdef1
+        assert!({
+            let (sdef_position, sdef_normal) = skin_vertex(&matrices, &sdef, position, normal);
+            let (bdef_position, bdef_normal) = skin_vertex(
+                &matrices,
+                &deform(0, [1, -1, -1, -1], [0.0; 4]),
+                position,
+                normal,
+            );
+            equals_vec3(sdef_position, bdef_position) && equals_vec3(sdef_normal, bdef_normal)
+        });
+    }
+
+    /// Runs the skinning of the shader in a compute pass, for each deform and the position and
+    /// normal of the vertex. The skinning is taken from the shader as it is.
+    fn skin_vertices_on_gpu(
+        gfx_ctx: &GfxContext,
+        shader: &str,
+        skinning_matrices: &[Mat4],
+        vertices: &[(VertexDeform, Vec3, Vec3)],
+    ) -> Vec<(Vec3, Vec3)> {
+        let vertex_input = &shader[shader.find("struct VertexInput").unwrap()
+            ..shader.find("struct VertexOutput").unwrap()];
+        let code = format!(
+            "{}{}{}{}",
+            r#"
+            struct SkinningInput {
+              position: vec4<f32>,
+              normal: vec4<f32>,
+              bone_index: vec4<u32>,
+              bone_weight: vec4<f32>,
+              sdef_c: vec4<f32>,
+              sdef_r0: vec4<f32>,
+              sdef_r1: vec4<f32>,
+              deform_kind: vec4<u32>,
+            };
+
+            @group(0) @binding(0) var<storage, read> inputs: array<SkinningInput>;
+            @group(0) @binding(1) var<storage, read_write> outputs: array<vec4<f32>>;
+            @group(0) @binding(2) var<storage, read> bone_matrices: array<mat4x4<f32>, 1024>;
+            "#,
+            vertex_input,
+            include_str!("../../../../../lvl-resource-compiler/assets/standard-skinning.wgsl"),
+            r#"
+            @compute @workgroup_size(1)
+            fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+              let input = inputs[id.x];
+              var vertex: VertexInput;
+              vertex.normal = input.normal.xyz;
+              vertex.deform_kind = input.deform_kind.x;
+              vertex.bone_index = input.bone_index;
+              vertex.bone_weight = input.bone_weight;
+              vertex.sdef_c = input.sdef_c.xyz;
+              vertex.sdef_r0 = input.sdef_r0.xyz;
+              vertex.sdef_r1 = input.sdef_r1.xyz;
+
+              let skinned = skin_vertex(vertex, input.position.xyz);
+              outputs[id.x * 2u] = vec4<f32>(skinned.position, 0.0);
+              outputs[id.x * 2u + 1u] = vec4<f32>(skinned.normal, 0.0);
+            }
+            "#
+        );
+
+        let vec4 = |v: Vec3| [v.x.to_bits(), v.y.to_bits(), v.z.to_bits(), 0];
+        let inputs = vertices
+            .iter()
+            .flat_map(|(deform, position, normal)| {
+                [
+                    vec4(*position),
+                    vec4(*normal),
+                    deform.bone_indices.map(|index| index as u32),
+                    deform.bone_weights.map(f32::to_bits),
+                    vec4(deform.sdef_c),
+                    vec4(deform.sdef_r0),
+                    vec4(deform.sdef_r1),
+                    [deform.kind, 0, 0, 0],
+                ]
+            })
+            .flatten()
+            .collect::<Vec<u32>>();
+        let mut matrices = vec![Mat4::identity(); MAX_BONE_COUNT];
+        matrices[..skinning_matrices.len()].clone_from_slice(skinning_matrices);
+
+        let device = &gfx_ctx.device;
+        let input_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: None,
+            contents: inputs.as_bytes(),
+            usage: BufferUsages::STORAGE,
+        });
+        let output_size = (vertices.len() * 2 * size_of::<Vec4>()) as u64;
+        let output_buffer = device.create_buffer(&BufferDescriptor {
+            label: None,
+            size: output_size,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback_buffer = device.create_buffer(&BufferDescriptor {
+            label: None,
+            size: output_size,
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let matrix_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: None,
+            contents: matrices.as_bytes(),
+            usage: BufferUsages::STORAGE,
+        });
+
+        let module = device.create_shader_module(ShaderModuleDescriptor {
+            label: None,
+            source: ShaderSource::Wgsl(code.into()),
+        });
+        let pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: None,
+            layout: None,
+            module: &module,
+            entry_point: "cs_main",
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: input_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: output_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: matrix_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor { label: None });
+        {
+            let mut pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+                label: None,
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(vertices.len() as u32, 1, 1);
+        }
+        encoder.copy_buffer_to_buffer(&output_buffer, 0, &readback_buffer, 0, output_size);
+        gfx_ctx.queue.submit([encoder.finish()]);
+
+        let (sender, receiver) = mpsc::channel();
+        let slice = readback_buffer.slice(..);
+        slice.map_async(MapMode::Read, move |result| {
+            sender.send(result).ok();
+        });
+        device.poll(MaintainBase::Wait);
+        receiver.recv().unwrap().unwrap();
+
+        let data = slice.get_mapped_range();
+        let outputs = data
+            .chunks_exact(size_of::<Vec4>())
+            .map(|chunk| {
+                let component =
+                    |index: usize| f32::from_le_bytes(chunk[index * 4..][..4].try_into().unwrap());
+                Vec3::new(component(0), component(1), component(2))
+            })
+            .collect::<Vec<_>>();
+
+        outputs
+            .chunks_exact(2)
+            .map(|output| (output[0], output[1]))
+            .collect()
+    }
+
+    #[test]
+    fn check_standard_shaders_skin_as_cpu() {
+        let gfx_ctx = match test_gfx_ctx() {
+            Some(gfx_ctx) => gfx_ctx,
+            None => {
+                return;
+            }
+        };
+        let matrices = make_skinning_matrices();
+        let position = Vec3::new(0.5, 2.0, 0.0);
+        let normal = Vec3::new(1.0, 0.0, 0.0);
+        let vertices = [
+            (deform(0, [1, -1, -1, -1], [0.0; 4]), position, normal),
+            (
+                deform(1, [0, 1, -1, -1], [0.3, 0.7, 0.0, 0.0]),
+                position,
+                normal,
+            ),
+            (
+                deform(2, [1, 0, 1, -1], [0.2, 0.5, 0.3, 0.0]),
+                position,
+                normal,
+            ),
+            (
+                VertexDeform {
+                    kind: 3,
+                    bone_indices: [1, 0, -1, -1],
+                    bone_weights: [0.6, 0.4, 0.0, 0.0],
+                    sdef_c: Vec3::new(0.0, 1.5, 0.0),
+                    sdef_r0: Vec3::new(0.0, 1.5, 0.0),
+                    sdef_r1: Vec3::new(0.0, 0.5, 0.0),
+                },
+                position,
+                normal,
+            ),
+        ];
+
+        for shader in [
+            include_str!("../../../../../lvl-resource-compiler/assets/standard-full.wgsl"),
+            include_str!("../../../../../lvl-resource-compiler/assets/standard-no-env.wgsl"),
+            include_str!("../../../../../lvl-resource-compiler/assets/standard-no-toon.wgsl"),
+            include_str!(
+                "../../../../../lvl-resource-compiler/assets/standard-no-toon-no-env.wgsl"
+            ),
+        ] {
+            let skinned = skin_vertices_on_gpu(&gfx_ctx, shader, &matrices, &vertices);
+
+            for (index, ((deform, position, normal), (gpu_position, gpu_normal))) in
+                vertices.iter().zip(skinned).enumerate()
+            {
+                let (cpu_position, cpu_normal) = skin_vertex(&matrices, deform, *position, *normal);
+                assert!(equals_vec3(gpu_position, cpu_position), "{}", index);
+                assert!(equals_vec3(gpu_normal, cpu_normal), "{}", index);
+            }
+        }
+    }
+}
@@ -1,35 +1,54 @@
-use lvl_resource::{
-    PmxModelAnimationBoneKeyFrame, PmxModelAnimationMorphKeyFrame, PmxModelAnimationSource,
-};
+use lvl_math::{BoneKeyFrame, KeyFrameSampler, MorphKeyFrame, Quat, Vec3};
+use lvl_resource::PmxModelAnimationSource;
+use std::collections::HashMap;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct PmxModelAnimation {
-    bone_key_frames: Vec<PmxModelAnimationBoneKeyFrame>,
-    morph_key_frames: Vec<PmxModelAnimationMorphKeyFrame>,
+    sampler: KeyFrameSampler,
     total_time: f32,
     fps: f32,
 }
 
 impl PmxModelAnimation {
     pub fn load_from_source(source: &PmxModelAnimationSource, fps: f32) -> Self {
-        let max_bone_key_frame = source
-            .bone_key_frames()
-            .last()
-            .map_or(0, |kf| kf.frame_index);
-        let max_morph_key_frame = source
-            .morph_key_frames()
-            .last()
-            .map_or(0, |kf| kf.frame_index);
-        let total_time = (max_bone_key_frame + max_morph_key_frame) as f32 / fps;
+        let bone_key_frames = source.bone_key_frames().iter().flat_map(|key_frame| {
+            key_frame.elements.iter().map(|element| {
+                let translation = element.translation;
+                let rotation = element.rotation;
+
+                // the motions are authored in the MMD space, whose z axis is flipped in the models
+                (
+                    element.bone_name.clone(),
+                    BoneKeyFrame {
+                        frame_index: key_frame.frame_index,
+                        translation: Vec3::new(translation.x, translation.y, -translation.z),
+                        rotation: Quat::new(-rotation.x, -rotation.y, rotation.z, rotation.w),
+                        curves: element.bezier.curves(),
+                    },
+                )
+            })
+        });
+        let morph_key_frames = source.morph_key_frames().iter().flat_map(|key_frame| {
+            key_frame.elements.iter().map(|element| {
+                (
+                    element.morph_name.clone(),
+                    MorphKeyFrame {
+                        frame_index: key_frame.frame_index,
+                        weight: element.weight,
+                    },
+                )
+            })
+        });
+        let sampler = KeyFrameSampler::new(bone_key_frames, morph_key_frames);
 
         Self {
+            total_time: sampler.last_frame_index() as f32 / fps,
+            sampler,
             fps,
-            total_time,
-            bone_key_frames: source.bone_key_frames().to_vec(),
-            morph_key_frames: source.morph_key_frames().to_vec(),
         }
     }
 
+    /// The time in seconds of the last bone or morph key frame.
     pub fn total_time(&self) -> f32 {
         self.total_time
     }
@@ -38,103 +57,71 @@ impl PmxModelAnimation {
         self.fps
     }
 
-    pub fn get_current_bone_key_frame(&self, play_time: f32) -> CurrentBoneKeyFrame {
-        let frame_index = (play_time * self.fps) as u32;
-
-        match self
-            .bone_key_frames
-            .binary_search_by_key(&frame_index, |kf| kf.frame_index)
-        {
-            Ok(index) => CurrentBoneKeyFrame {
-                weight: 0f32,
-                current: self.bone_key_frames.get(index),
-                next: self.bone_key_frames.get(index + 1),
-            },
-            Err(index) => match index {
-                0 => CurrentBoneKeyFrame {
-                    weight: 0f32,
-                    current: self.bone_key_frames.get(index),
-                    next: self.bone_key_frames.get(index + 1),
-                },
-                index if index == self.bone_key_frames.len() => CurrentBoneKeyFrame {
-                    weight: 0f32,
-                    current: self.bone_key_frames.last(),
-                    next: None,
-                },
-                index => {
-                    let current = &self.bone_key_frames[index - 1];
-                    let next = &self.bone_key_frames[index];
-
-                    CurrentBoneKeyFrame {
-                        weight: (frame_index - current.frame_index) as f32
-                            / (next.frame_index - current.frame_index) as f32,
-                        current: Some(current),
-                        next: Some(next),
-                    }
-                }
-            },
-        }
+    /// Returns the translation and rotation of the bone at the time in seconds, relative to its
+    /// bind pose and in the model space, or `None` if the animation does not animate the bone.
+    /// Before the first and after the last key frame, the value of that key frame is held.
+    pub fn sample_bone(&self, bone_name: &str, time: f32) -> Option<(Vec3, Quat)> {
+        self.sampler.sample_bone(bone_name, time * self.fps)
     }
 
-    pub fn get_current_morph_key_frame(&self, play_time: f32) -> CurrentMorphKeyFrame {
-        let frame_index = (play_time * self.fps) as u32;
+    /// Returns the weight of the morph at the time in seconds, or `None` if the animation does
+    /// not animate the morph. The weights are interpolated linearly.
+    pub fn sample_morph(&self, morph_name: &str, time: f32) -> Option<f32> {
+        self.sampler.sample_morph(morph_name, time * self.fps)
+    }
 
-        match self
-            .morph_key_frames
-            .binary_search_by_key(&frame_index, |kf| kf.frame_index)
-        {
-            Ok(index) => CurrentMorphKeyFrame {
-                weight: 0f32,
-                current: self.morph_key_frames.get(index),
-                next: self.morph_key_frames.get(index + 1),
-            },
-            Err(index) => match index {
-                0 => CurrentMorphKeyFrame {
-                    weight: 0f32,
-                    current: self.morph_key_frames.get(index),
-                    next: self.morph_key_frames.get(index + 1),
-                },
-                index if index == self.morph_key_frames.len() => CurrentMorphKeyFrame {
-                    weight: 0f32,
-                    current: self.morph_key_frames.last(),
-                    next: None,
-                },
-                index => {
-                    let current = &self.morph_key_frames[index - 1];
-                    let next = &self.morph_key_frames[index];
+    pub fn sample_bones(&self, time: f32) -> HashMap<String, (Vec3, Quat)> {
+        self.sampler.sample_bones(time * self.fps)
+    }
 
-                    CurrentMorphKeyFrame {
-                        weight: (frame_index - current.frame_index) as f32
-                            / (next.frame_index - current.frame_index) as f32,
-                        current: Some(current),
-                        next: Some(next),
-                    }
-                }
-            },
-        }
+    pub fn sample_morphs(&self, time: f32) -> HashMap<String, f32> {
+        self.sampler.sample_morphs(time * self.fps)
     }
 }
 
-pub struct CurrentBoneKeyFrame<'a> {
-    /// Represents how much of the next frame is shown.
-    ///
-    /// - `0` if the given frame is at exactly the current frame.
-    /// - `1` if the given frame is at exactly the next frame.
-    ///
-    /// It is intended to be used to interpolate between the current and next frame.
-    pub weight: f32,
-    pub current: Option<&'a PmxModelAnimationBoneKeyFrame>,
-    pub next: Option<&'a PmxModelAnimationBoneKeyFrame>,
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lvl_resource::{
+        PmxModelAnimationBoneBezier, PmxModelAnimationBoneKeyFrame,
+        PmxModelAnimationBoneKeyFrameElement, PmxModelAnimationMorphKeyFrame,
+        PmxModelAnimationMorphKeyFrameElement,
+    };
 
-pub struct CurrentMorphKeyFrame<'a> {
-    /// Represents how much of the next frame is shown.
-    ///
-    /// - `0` if the given frame is at exactly the current frame.
-    /// - `1` if the given frame is at exactly the next frame.
-    ///
-    /// It is intended to be used to interpolate between the current and next frame.
-    pub weight: f32,
-    pub current: Option<&'a PmxModelAnimationMorphKeyFrame>,
-    pub next: Option<&'a PmxModelAnimationMorphKeyFrame>,
+    #[test]
+    fn check_total_time_and_space() {
+        let linear = [20, 107, 20, 107];
+        let source = PmxModelAnimationSource::new(
+            vec![PmxModelAnimationBoneKeyFrame {
+                frame_index: 30,
+                elements: vec![PmxModelAnimationBoneKeyFrameElement {
+                    bone_name: "arm".to_owned(),
+                    translation: Vec3::new(1.0, 2.0, 3.0),
+                    rotation: Quat::IDENTITY,
+                    bezier: PmxModelAnimationBoneBezier {
+                        x_axis: linear,
+                        y_axis: linear,
+                        z_axis: linear,
+                        rotation: linear,
+                    },
+                }],
+            }],
+            vec![PmxModelAnimationMorphKeyFrame {
+                frame_index: 60,
+                elements: vec![PmxModelAnimationMorphKeyFrameElement {
+                    morph_name: "smile".to_owned(),
+                    weight: 1.0,
+                }],
+            }],
+        );
+        let animation = PmxModelAnimation::load_from_source(&source, 30.0);
+
+        // the bones and the morphs play together, so the longer of them decides
+        assert_eq!(animation.total_time(), 2.0);
+        assert_eq!(
+            animation.sample_bone("arm", 0.0),
+            Some((Vec3::new(1.0, 2.0, -3.0), Quat::IDENTITY))
+        );
+        assert_eq!(animation.sample_morph("smile", 2.0), Some(1.0));
+    }
 }
@@ -1,6 +1,7 @@
 use super::ShaderReflection;
 use crate::gfx::GfxContext;
 use lvl_resource::{ShaderBindingKind, ShaderSource};
+use std::borrow::Cow;
 use wgpu::{
     BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType,
     BufferBindingType, PipelineLayout, PipelineLayoutDescriptor, ShaderModule,
@@ -104,7 +105,12 @@ impl Shader {
 
         let module = gfx_ctx.device.create_shader_module(ShaderModuleDescriptor {
             label: None,
-            source: wgpu::ShaderSource::Wgsl(source.source().into()),
+            // the precompiled module is preferred, as it does not need to be parsed again
+            source: match (source.module(), source.source()) {
+                (Some(module), _) => wgpu::ShaderSource::Naga(Cow::Owned(module.clone())),
+                (None, Some(source)) => wgpu::ShaderSource::Wgsl(source.into()),
+                (None, None) => unreachable!("the shader has neither a module nor a source"),
+            },
         });
 
         Self {
@@ -131,3 +137,22 @@ impl Shader {
         &self.reflection
     }
 }
+
+/// Makes a descriptor of the given WGSL code with the `vs_main` and `fs_main` entry points and
+/// nothing else, for tests.
+#[cfg(test)]
+pub(crate) fn make_shader_source_descriptor(code: &str) -> lvl_resource::ShaderSourceDescriptor {
+    use lvl_resource::{ShaderCode, ShaderSourceDescriptor};
+
+    ShaderSourceDescriptor {
+        code: ShaderCode::from_wgsl(code.to_owned()),
+        vs_main: "vs_main".to_owned(),
+        fs_main: "fs_main".to_owned(),
+        vertex_entry_points: vec![],
+        fragment_entry_points: vec![],
+        builtin_uniform_bind_group: None,
+        bindings: vec![],
+        uniform_members: vec![],
+        vertex_inputs: Default::default(),
+    }
+}
@@ -5,6 +5,8 @@ use std::collections::BTreeMap;
 pub struct ShaderReflection {
     pub vertex_entry_point: String,
     pub fragment_entry_point: String,
+    pub vertex_entry_points: Vec<String>,
+    pub fragment_entry_points: Vec<String>,
     pub locations: BTreeMap<String, u32>,
     pub builtin_uniform_bind_group: Option<u32>,
 }
@@ -14,8 +16,38 @@ impl ShaderReflection {
         Self {
             vertex_entry_point: source.vs_main().to_owned(),
             fragment_entry_point: source.fs_main().to_owned(),
-            locations: source.locations().clone(),
+            vertex_entry_points: source.vertex_entry_points().to_vec(),
+            fragment_entry_points: source.fragment_entry_points().to_vec(),
+            locations: source.vertex_inputs().clone(),
             builtin_uniform_bind_group: source.builtin_uniform_bind_group(),
         }
     }
+
+    /// Returns the vertex entry point with the given name, or the default one if `name` is `None`.
+    pub fn select_vertex_entry_point(&self, name: Option<&str>) -> Option<&str> {
+        select_entry_point(&self.vertex_entry_point, &self.vertex_entry_points, name)
+    }
+
+    /// Returns the fragment entry point with the given name, or the default one if `name` is `None`.
+    pub fn select_fragment_entry_point(&self, name: Option<&str>) -> Option<&str> {
+        select_entry_point(
+            &self.fragment_entry_point,
+            &self.fragment_entry_points,
+            name,
+        )
+    }
+}
+
+fn select_entry_point<'a>(
+    default: &'a str,
+    entry_points: &'a [String],
+    name: Option<&str>,
+) -> Option<&'a str> {
+    match name {
+        Some(name) => entry_points
+            .iter()
+            .find(|entry_point| entry_point.as_str() == name)
+            .map(|entry_point| entry_point.as_str()),
+        None => Some(default),
+    }
 }
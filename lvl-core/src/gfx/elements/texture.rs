@@ -1,10 +1,34 @@
 use crate::gfx::GfxContext;
-use lvl_resource::{TextureElement, TextureElementTextureFormat};
+use log::warn;
+use lvl_resource::{
+    TextureElement, TextureElementSamplingMode, TextureElementSize, TextureElementTextureFormat,
+    TextureElementWrappingMode,
+};
 use wgpu::{
-    Extent3d, ImageCopyTexture, ImageDataLayout, Origin3d, TextureAspect, TextureDescriptor,
-    TextureDimension, TextureFormat, TextureUsages,
+    Extent3d, Features, ImageCopyTexture, ImageDataLayout, Origin3d, Queue, TextureAspect,
+    TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
 };
 
+/// Makes the placeholder that stands in for textures that cannot be resolved: a 2x2 checker of
+/// magenta and black, which is hard to mistake for a real texture.
+pub fn missing_texture_element() -> TextureElement {
+    const MAGENTA: [u8; 4] = [255, 0, 255, 255];
+    const BLACK: [u8; 4] = [0, 0, 0, 255];
+
+    TextureElement {
+        data: [MAGENTA, BLACK, BLACK, MAGENTA].concat(),
+        size: TextureElementSize {
+            width: 2,
+            height: 2,
+        },
+        texture_format: TextureElementTextureFormat::RGBA8UnormSrgb,
+        sampling_mode: TextureElementSamplingMode::Point,
+        wrapping_mode_u: TextureElementWrappingMode::Repeat,
+        wrapping_mode_v: TextureElementWrappingMode::Repeat,
+        mip_data: vec![],
+    }
+}
+
 #[derive(Debug)]
 pub struct Texture {
     width: u16,
@@ -21,7 +45,23 @@ impl Texture {
         }
     }
 
+    /// Uploads the texture and its mip chain. Block-compressed textures need the BC texture
+    /// compression feature; where the device lacks it, the missing texture placeholder is
+    /// uploaded instead.
     pub fn load_from_source(source: &TextureElement, gfx_ctx: &GfxContext) -> Self {
+        if source.texture_format.is_block_compressed()
+            && !gfx_ctx
+                .device
+                .features()
+                .contains(Features::TEXTURE_COMPRESSION_BC)
+        {
+            warn!(
+                "the texture format `{:?}` is not supported on this device; the placeholder will be used instead.",
+                source.texture_format
+            );
+            return Self::load_from_source(&missing_texture_element(), gfx_ctx);
+        }
+
         let handle = gfx_ctx.device.create_texture(&TextureDescriptor {
             label: None,
             size: Extent3d {
@@ -29,7 +69,7 @@ impl Texture {
                 height: source.size.height as u32,
                 depth_or_array_layers: 1,
             },
-            mip_level_count: 1,
+            mip_level_count: source.mip_level_count(),
             sample_count: 1,
             dimension: TextureDimension::D2,
             format: match source.texture_format {
@@ -38,36 +78,42 @@ impl Texture {
                 TextureElementTextureFormat::RGBA32Float => TextureFormat::Rgba32Float,
                 TextureElementTextureFormat::RGBA8Unorm => TextureFormat::Rgba8Unorm,
                 TextureElementTextureFormat::RGBA8UnormSrgb => TextureFormat::Rgba8UnormSrgb,
+                TextureElementTextureFormat::Bc3RgbaUnorm => TextureFormat::Bc3RgbaUnorm,
+                TextureElementTextureFormat::Bc3RgbaUnormSrgb => TextureFormat::Bc3RgbaUnormSrgb,
+                TextureElementTextureFormat::Bc7RgbaUnorm => TextureFormat::Bc7RgbaUnorm,
+                TextureElementTextureFormat::Bc7RgbaUnormSrgb => TextureFormat::Bc7RgbaUnormSrgb,
             },
             usage: TextureUsages::COPY_DST | TextureUsages::TEXTURE_BINDING,
             view_formats: &[],
         });
 
-        gfx_ctx.queue.write_texture(
-            ImageCopyTexture {
-                texture: &handle,
-                mip_level: 0,
-                origin: Origin3d::ZERO,
-                aspect: TextureAspect::All,
-            },
-            &source.data,
-            ImageDataLayout {
-                offset: 0,
-                bytes_per_row: Some(match source.texture_format {
-                    TextureElementTextureFormat::RG32Uint => 8 * source.size.width as u32,
-                    TextureElementTextureFormat::RGBA32Uint => 16 * source.size.width as u32,
-                    TextureElementTextureFormat::RGBA32Float => 16 * source.size.width as u32,
-                    TextureElementTextureFormat::RGBA8Unorm => 4 * source.size.width as u32,
-                    TextureElementTextureFormat::RGBA8UnormSrgb => 4 * source.size.width as u32,
-                }),
-                rows_per_image: None,
-            },
-            Extent3d {
-                width: source.size.width as u32,
-                height: source.size.height as u32,
-                depth_or_array_layers: 1,
-            },
-        );
+        let block_dimension = source.texture_format.block_dimension();
+
+        for level in 0..source.mip_level_count() {
+            let size = source.mip_level_size(level);
+            let (columns, rows) = source.texture_format.block_count(size);
+
+            gfx_ctx.queue.write_texture(
+                ImageCopyTexture {
+                    texture: &handle,
+                    mip_level: level,
+                    origin: Origin3d::ZERO,
+                    aspect: TextureAspect::All,
+                },
+                source.mip_level_data(level).unwrap(),
+                ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(source.texture_format.block_size() as u32 * columns),
+                    rows_per_image: Some(rows),
+                },
+                // compressed levels are copied in whole blocks, even below 4x4
+                Extent3d {
+                    width: columns * block_dimension,
+                    height: rows * block_dimension,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
 
         Self {
             width: source.size.width,
@@ -87,4 +133,147 @@ impl Texture {
     pub fn handle(&self) -> &wgpu::Texture {
         &self.handle
     }
+
+    /// Writes `data` into a linear run of texels starting at `texel_offset`, in row-major order.
+    /// Only the rows covered by the run are uploaded; all other texels are left unchanged.
+    pub fn write_texels(&self, texel_offset: u32, data: &[u8], queue: &Queue) {
+        let texel_size = self.handle.format().block_copy_size(None).unwrap_or(1);
+        let texel_count = data.len() as u32 / texel_size;
+
+        for span in texel_row_spans(self.width as u32, texel_offset, texel_count) {
+            let start = (span.data_offset * texel_size) as usize;
+            let end = start + (span.count * texel_size) as usize;
+
+            queue.write_texture(
+                ImageCopyTexture {
+                    texture: &self.handle,
+                    mip_level: 0,
+                    origin: Origin3d {
+                        x: span.x,
+                        y: span.y,
+                        z: 0,
+                    },
+                    aspect: TextureAspect::All,
+                },
+                &data[start..end],
+                ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(span.count * texel_size),
+                    rows_per_image: None,
+                },
+                Extent3d {
+                    width: span.count,
+                    height: 1,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+    }
+}
+
+/// A run of texels that lies within a single row of a texture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct TexelRowSpan {
+    pub x: u32,
+    pub y: u32,
+    pub count: u32,
+    /// Index of the first texel of this span, relative to the start of the whole run.
+    pub data_offset: u32,
+}
+
+/// Splits a linear run of texels into per-row spans for a texture of the given width.
+fn texel_row_spans(width: u32, texel_offset: u32, texel_count: u32) -> Vec<TexelRowSpan> {
+    let mut spans = Vec::new();
+
+    if width == 0 {
+        return spans;
+    }
+
+    let mut data_offset = 0;
+
+    while data_offset < texel_count {
+        let texel_index = texel_offset + data_offset;
+        let x = texel_index % width;
+        let count = (width - x).min(texel_count - data_offset);
+
+        spans.push(TexelRowSpan {
+            x,
+            y: texel_index / width,
+            count,
+            data_offset,
+        });
+
+        data_offset += count;
+    }
+
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Applies the spans to a CPU-side mirror of a texture, the same way `write_texels` does.
+    fn write_texels_to(texels: &mut [u32], width: u32, texel_offset: u32, data: &[u32]) {
+        for span in texel_row_spans(width, texel_offset, data.len() as u32) {
+            let dst = (span.y * width + span.x) as usize;
+            let src = span.data_offset as usize;
+            texels[dst..dst + span.count as usize]
+                .copy_from_slice(&data[src..src + span.count as usize]);
+        }
+    }
+
+    #[test]
+    fn check_texel_row_spans_within_row() {
+        assert_eq!(
+            texel_row_spans(4, 1, 2),
+            vec![TexelRowSpan {
+                x: 1,
+                y: 0,
+                count: 2,
+                data_offset: 0,
+            }]
+        );
+    }
+
+    #[test]
+    fn check_texel_row_spans_across_rows() {
+        assert_eq!(
+            texel_row_spans(4, 3, 6),
+            vec![
+                TexelRowSpan {
+                    x: 3,
+                    y: 0,
+                    count: 1,
+                    data_offset: 0,
+                },
+                TexelRowSpan {
+                    x: 0,
+                    y: 1,
+                    count: 4,
+                    data_offset: 1,
+                },
+                TexelRowSpan {
+                    x: 0,
+                    y: 2,
+                    count: 1,
+                    data_offset: 5,
+                },
+            ]
+        );
+        assert!(texel_row_spans(4, 3, 0).is_empty());
+    }
+
+    #[test]
+    fn check_write_texels_leaves_other_texels_unchanged() {
+        // three morphs owning the displacement ranges 0..3, 3..9 and 9..12 of a 4x4 texture
+        let mut texels = (0..16).collect::<Vec<u32>>();
+
+        write_texels_to(&mut texels, 4, 3, &[100, 101, 102, 103, 104, 105]);
+
+        assert_eq!(
+            texels,
+            vec![0, 1, 2, 100, 101, 102, 103, 104, 105, 9, 10, 11, 12, 13, 14, 15]
+        );
+    }
 }
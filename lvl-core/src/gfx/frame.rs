@@ -38,6 +38,10 @@ impl Frame {
         self.cmd_encoder.finish()
     }
 
+    pub(crate) fn cmd_encoder_mut(&mut self) -> &mut CommandEncoder {
+        &mut self.cmd_encoder
+    }
+
     pub fn begin_render_pass<'pass, 'tex: 'pass, 'a: 'pass>(
         &'a mut self,
         clear_mode: ClearMode,
@@ -0,0 +1,257 @@
+use super::Frame;
+use std::sync::mpsc;
+use thiserror::Error;
+use wgpu::{
+    Buffer, BufferDescriptor, BufferUsages, Device, Extent3d, ImageCopyBuffer, ImageCopyTexture,
+    ImageDataLayout, MaintainBase, MapMode, Origin3d, Texture, TextureAspect, TextureFormat,
+    TextureUsages, COPY_BYTES_PER_ROW_ALIGNMENT,
+};
+
+#[derive(Error, Debug)]
+pub enum FrameCaptureError {
+    #[error("the surface does not support being copied")]
+    SurfaceNotCopyable,
+    #[error("capturing the format {0:?} is not supported")]
+    UnsupportedFormat(TextureFormat),
+    #[error("failed to map the readback buffer: {0}")]
+    MapFailed(#[from] wgpu::BufferAsyncError),
+}
+
+/// A captured frame, in tightly packed RGBA8 rows from top to bottom.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameCapture {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+/// A readback that has been recorded into a frame, but not read yet.
+#[derive(Debug)]
+pub(crate) struct PendingFrameCapture {
+    buffer: Buffer,
+    width: u32,
+    height: u32,
+    padded_bytes_per_row: u32,
+    format: TextureFormat,
+}
+
+impl PendingFrameCapture {
+    /// Records a copy of the given texture into a readback buffer.
+    pub(crate) fn record(
+        texture: &Texture,
+        frame: &mut Frame,
+        device: &Device,
+    ) -> Result<Self, FrameCaptureError> {
+        if !texture.usage().contains(TextureUsages::COPY_SRC) {
+            return Err(FrameCaptureError::SurfaceNotCopyable);
+        }
+
+        let format = texture.format();
+
+        if channel_order(format).is_none() {
+            return Err(FrameCaptureError::UnsupportedFormat(format));
+        }
+
+        let width = texture.width();
+        let height = texture.height();
+        let padded_bytes_per_row = padded_bytes_per_row(width);
+        let buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("frame-capture-buffer"),
+            size: padded_bytes_per_row as u64 * height as u64,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        frame.cmd_encoder_mut().copy_texture_to_buffer(
+            ImageCopyTexture {
+                texture,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            ImageCopyBuffer {
+                buffer: &buffer,
+                layout: ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        Ok(Self {
+            buffer,
+            width,
+            height,
+            padded_bytes_per_row,
+            format,
+        })
+    }
+
+    /// Reads the buffer back. The frame that recorded the copy must have been submitted.
+    pub(crate) fn read(self, device: &Device) -> Result<FrameCapture, FrameCaptureError> {
+        let (sender, receiver) = mpsc::channel();
+        let slice = self.buffer.slice(..);
+        slice.map_async(MapMode::Read, move |result| {
+            sender.send(result).ok();
+        });
+        device.poll(MaintainBase::Wait);
+
+        // the callback has been called by the poll above
+        receiver.recv().unwrap()?;
+
+        let pixels = to_rgba8(
+            &slice.get_mapped_range(),
+            self.width,
+            self.height,
+            self.padded_bytes_per_row,
+            self.format,
+        )
+        .ok_or(FrameCaptureError::UnsupportedFormat(self.format))?;
+        self.buffer.unmap();
+
+        Ok(FrameCapture {
+            width: self.width,
+            height: self.height,
+            pixels,
+        })
+    }
+}
+
+/// Returns the row size of a RGBA8-sized readback, aligned as the buffer copies require.
+fn padded_bytes_per_row(width: u32) -> u32 {
+    (width * 4).div_ceil(COPY_BYTES_PER_ROW_ALIGNMENT) * COPY_BYTES_PER_ROW_ALIGNMENT
+}
+
+/// Returns `true` if the channels of the given format are stored in BGRA order,
+/// or `None` if the format cannot be captured.
+fn channel_order(format: TextureFormat) -> Option<bool> {
+    match format {
+        TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => Some(false),
+        TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb => Some(true),
+        _ => None,
+    }
+}
+
+/// Removes the row padding and swizzles the pixels into RGBA order.
+fn to_rgba8(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    padded_bytes_per_row: u32,
+    format: TextureFormat,
+) -> Option<Vec<u8>> {
+    let is_bgra = channel_order(format)?;
+    let bytes_per_row = width as usize * 4;
+    let mut pixels = Vec::with_capacity(bytes_per_row * height as usize);
+
+    for row in data
+        .chunks(padded_bytes_per_row as usize)
+        .take(height as usize)
+    {
+        for pixel in row[..bytes_per_row].chunks_exact(4) {
+            if is_bgra {
+                pixels.extend_from_slice(&[pixel[2], pixel[1], pixel[0], pixel[3]]);
+            } else {
+                pixels.extend_from_slice(pixel);
+            }
+        }
+    }
+
+    Some(pixels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gfx::test_gfx_ctx;
+    use wgpu::{
+        Color, LoadOp, Operations, RenderPassColorAttachment, RenderPassDescriptor, StoreOp,
+        TextureDescriptor, TextureDimension, TextureViewDescriptor,
+    };
+
+    #[test]
+    fn check_padded_bytes_per_row() {
+        assert_eq!(padded_bytes_per_row(1), 256);
+        assert_eq!(padded_bytes_per_row(64), 256);
+        assert_eq!(padded_bytes_per_row(65), 512);
+        assert_eq!(padded_bytes_per_row(800), 3328);
+    }
+
+    #[test]
+    fn check_clear_color_readback() {
+        let gfx_ctx = match test_gfx_ctx() {
+            Some(gfx_ctx) => gfx_ctx,
+            None => {
+                return;
+            }
+        };
+
+        // 3 pixels wide, so that the rows of the readback are padded
+        for format in [TextureFormat::Bgra8Unorm, TextureFormat::Rgba8Unorm] {
+            let texture = gfx_ctx.device.create_texture(&TextureDescriptor {
+                label: None,
+                size: Extent3d {
+                    width: 3,
+                    height: 2,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format,
+                usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+                view_formats: &[],
+            });
+            let view = texture.create_view(&TextureViewDescriptor::default());
+
+            let mut frame = gfx_ctx.begin_frame();
+            frame
+                .cmd_encoder_mut()
+                .begin_render_pass(&RenderPassDescriptor {
+                    label: None,
+                    color_attachments: &[Some(RenderPassColorAttachment {
+                        view: &view,
+                        resolve_target: None,
+                        ops: Operations {
+                            load: LoadOp::Clear(Color {
+                                r: 1.0,
+                                g: 128.0 / 255.0,
+                                b: 0.0,
+                                a: 1.0,
+                            }),
+                            store: StoreOp::Store,
+                        },
+                    })],
+                    depth_stencil_attachment: None,
+                    timestamp_writes: None,
+                    occlusion_query_set: None,
+                });
+            let capture =
+                PendingFrameCapture::record(&texture, &mut frame, &gfx_ctx.device).unwrap();
+            gfx_ctx.end_frame(frame);
+            let capture = capture.read(&gfx_ctx.device).unwrap();
+
+            assert_eq!((capture.width, capture.height), (3, 2));
+            assert_eq!(capture.pixels.len(), 3 * 2 * 4, "{:?}", format);
+            assert!(
+                capture
+                    .pixels
+                    .chunks_exact(4)
+                    .all(|pixel| pixel == [255, 128, 0, 255]),
+                "{:?}",
+                format
+            );
+        }
+    }
+
+    #[test]
+    fn check_unsupported_format() {
+        assert_eq!(to_rgba8(&[], 0, 0, 256, TextureFormat::Rgba16Float), None);
+    }
+}
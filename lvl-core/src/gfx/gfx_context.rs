@@ -1,10 +1,23 @@
-use super::{Frame, GlobalTextureSet, PerFrameBufferPool, UniformBindGroupProvider};
-use std::cell::RefCell;
+use super::{
+    elements::{missing_texture_element, Texture},
+    DepthStencilFormat, Frame, GlobalTextureSet, GpuTimer, PerFrameBufferPool, RenderConfig,
+    UniformBindGroupProvider, MSAA_SAMPLE_COUNTS,
+};
+use log::warn;
+use std::{
+    cell::{Cell, OnceCell, RefCell},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 use thiserror::Error;
 use wgpu::{
-    Adapter, Backend, Backends, CommandEncoderDescriptor, Device, DeviceDescriptor, DeviceType,
-    Features, Instance, InstanceDescriptor, MaintainBase, PresentMode, Queue, Surface,
-    SurfaceConfiguration, SurfaceError, SurfaceTexture, TextureUsages,
+    Adapter, Backend, Backends, CommandEncoderDescriptor, CompositeAlphaMode, Device,
+    DeviceDescriptor, DeviceLostReason, DeviceType, Features, Instance, InstanceDescriptor,
+    MaintainBase, PresentMode, Queue, Surface, SurfaceConfiguration, SurfaceError, SurfaceTexture,
+    TextureFormat, TextureUsages, TextureView,
 };
 use winit::{dpi::PhysicalSize, window::Window};
 
@@ -14,98 +27,222 @@ pub enum GfxContextCreationError {
     AdapterNotFound,
     #[error("surface not supported")]
     SurfaceNotSupported,
+    #[error("MSAA sample count {0} is invalid; it must be one of 1, 2, 4 or 8")]
+    InvalidMsaaSampleCount(u32),
+    #[error("depth stencil format {0:?} with {1} samples not supported")]
+    DepthStencilFormatNotSupported(TextureFormat, u32),
     #[error("failed to obtain device: {0}")]
     RequestDeviceError(#[from] wgpu::RequestDeviceError),
     #[error("failed to create surface: {0}")]
     CreateSurfaceError(#[from] wgpu::CreateSurfaceError),
 }
 
+/// What to do when the surface texture of a frame cannot be obtained.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SurfaceRecovery {
+    /// The surface is configured again and the frame is skipped.
+    Reconfigure,
+    /// The frame is skipped; the next one may succeed.
+    SkipFrame,
+    /// The surface cannot be recovered.
+    Fatal,
+}
+
+impl SurfaceRecovery {
+    pub fn from_error(error: &SurfaceError) -> Self {
+        match error {
+            SurfaceError::Lost | SurfaceError::Outdated => Self::Reconfigure,
+            SurfaceError::Timeout => Self::SkipFrame,
+            SurfaceError::OutOfMemory => Self::Fatal,
+        }
+    }
+}
+
 pub struct GfxContext<'window> {
     pub instance: Instance,
-    pub device: Device,
+    /// Shared with the GPU objects that make copies of themselves, e.g. the PMX models.
+    pub device: Arc<Device>,
     pub queue: Queue,
-    pub surface: Surface<'window>,
+    /// The surface of the window, or `None` if the context is headless.
+    surface: Option<Surface<'window>>,
+    window: Option<&'window Window>,
+    present_mode: PresentMode,
+    render_config: RenderConfig,
     pub surface_config: RefCell<SurfaceConfiguration>,
     pub global_texture_set: RefCell<GlobalTextureSet>,
     pub per_frame_buffer_pool: PerFrameBufferPool,
     pub uniform_bind_group_provider: UniformBindGroupProvider,
+    is_device_lost: Arc<AtomicBool>,
+    gpu_timer: Option<GpuTimer>,
+    gpu_frame_time: Cell<Option<Duration>>,
+    missing_texture: OnceCell<Arc<TextureView>>,
 }
 
 impl<'window> GfxContext<'window> {
     pub(crate) async fn new(
         window: &'window Window,
-        vsync: bool,
-        msaa_sample_count: u32,
+        present_mode: PresentMode,
+        render_config: RenderConfig,
+    ) -> Result<Self, GfxContextCreationError> {
+        Self::create(
+            Some(window),
+            window.inner_size(),
+            present_mode,
+            render_config,
+        )
+        .await
+    }
+
+    /// Makes a context without a window, which renders offscreen only, e.g. for tests.
+    /// It has no surface, so frames cannot be presented.
+    pub async fn new_headless(
+        size: PhysicalSize<u32>,
+        render_config: RenderConfig,
+    ) -> Result<GfxContext<'static>, GfxContextCreationError> {
+        GfxContext::create(None, size, PresentMode::AutoVsync, render_config).await
+    }
+
+    /// The device is requested with the `max_vertex_attributes` of the adapter rather than the
+    /// default 16, as the standard PMX shaders take up to 22 vertex attributes with the bone
+    /// inputs of the skinning. The WebGL2 limits requested on the web allow 16 only.
+    async fn create(
+        window: Option<&'window Window>,
+        size: PhysicalSize<u32>,
+        present_mode: PresentMode,
+        render_config: RenderConfig,
     ) -> Result<Self, GfxContextCreationError> {
+        validate_msaa_sample_count(render_config.msaa_sample_count)?;
+
         let instance = Instance::new(InstanceDescriptor::default());
-        let surface = instance.create_surface(window)?;
+        let surface = window
+            .map(|window| instance.create_surface(window))
+            .transpose()?;
         let adapters = instance.enumerate_adapters(Backends::all());
-        let adapter = match select_adapter(&surface, &adapters) {
+        let adapter = match select_adapter(surface.as_ref(), &adapters) {
             Some(adapter_index) => &adapters[adapter_index],
             None => return Err(GfxContextCreationError::AdapterNotFound),
         };
 
+        validate_depth_stencil_format(
+            adapter,
+            render_config.depth_stencil_format,
+            render_config.msaa_sample_count,
+        )?;
+
         let (device, queue) = adapter
             .request_device(
                 &DeviceDescriptor {
                     label: None,
                     required_features: Features::CLEAR_TEXTURE
-                        | Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES,
+                        | Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES
+                        | render_config.depth_stencil_format.required_features()
+                        // the frame time is measured on the GPU only where it is supported
+                        | (adapter.features() & Features::TIMESTAMP_QUERY)
+                        // block-compressed textures fall back to a placeholder without it
+                        | (adapter.features() & Features::TEXTURE_COMPRESSION_BC),
                     required_limits: if cfg!(target_arch = "wasm32") {
                         wgpu::Limits::downlevel_webgl2_defaults()
                     } else {
-                        wgpu::Limits::default()
+                        // the skinned pmx shaders take more vertex attributes than the default 16
+                        wgpu::Limits {
+                            max_vertex_attributes: adapter.limits().max_vertex_attributes,
+                            ..wgpu::Limits::default()
+                        }
                     },
                 },
                 None,
             )
             .await?;
 
-        let adapter_surface_caps = surface.get_capabilities(adapter);
-        let preferred_format = match adapter_surface_caps.formats.first() {
-            Some(format) => *format,
-            None => return Err(GfxContextCreationError::SurfaceNotSupported),
-        };
-        let preferred_alpha_mode = match adapter_surface_caps.alpha_modes.first() {
-            Some(mode) => *mode,
-            None => return Err(GfxContextCreationError::SurfaceNotSupported),
-        };
+        let surface_config = RefCell::new(match &surface {
+            Some(surface) => {
+                let adapter_surface_caps = surface.get_capabilities(adapter);
+                let preferred_format = match adapter_surface_caps.formats.first() {
+                    Some(format) => *format,
+                    None => return Err(GfxContextCreationError::SurfaceNotSupported),
+                };
+                let preferred_alpha_mode = match adapter_surface_caps.alpha_modes.first() {
+                    Some(mode) => *mode,
+                    None => return Err(GfxContextCreationError::SurfaceNotSupported),
+                };
 
-        let window_inner_size = window.inner_size();
-        let surface_config = RefCell::new(SurfaceConfiguration {
-            usage: TextureUsages::RENDER_ATTACHMENT,
-            format: preferred_format,
-            width: window_inner_size.width,
-            height: window_inner_size.height,
-            present_mode: if vsync {
-                PresentMode::AutoVsync
-            } else {
-                PresentMode::AutoNoVsync
+                SurfaceConfiguration {
+                    // the surface is copied out when the frame is captured
+                    usage: TextureUsages::RENDER_ATTACHMENT
+                        | (adapter_surface_caps.usages & TextureUsages::COPY_SRC),
+                    format: preferred_format,
+                    width: size.width,
+                    height: size.height,
+                    present_mode: select_present_mode(
+                        present_mode,
+                        &adapter_surface_caps.present_modes,
+                    ),
+                    desired_maximum_frame_latency: 2,
+                    alpha_mode: preferred_alpha_mode,
+                    view_formats: vec![],
+                }
+            }
+            // only the format and the size are used without a surface
+            None => SurfaceConfiguration {
+                usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+                format: TextureFormat::Rgba8UnormSrgb,
+                width: size.width,
+                height: size.height,
+                present_mode,
+                desired_maximum_frame_latency: 2,
+                alpha_mode: CompositeAlphaMode::Opaque,
+                view_formats: vec![],
             },
-            desired_maximum_frame_latency: 2,
-            alpha_mode: preferred_alpha_mode,
-            view_formats: vec![],
         });
-        surface.configure(&device, &surface_config.borrow());
+        let preferred_format = surface_config.borrow().format;
+
+        if let Some(surface) = &surface {
+            surface.configure(&device, &surface_config.borrow());
+        }
+
+        // dropping or destroying the device on purpose also invokes the callback
+        let is_device_lost = Arc::new(AtomicBool::new(false));
+        device.set_device_lost_callback({
+            let is_device_lost = is_device_lost.clone();
+            move |reason, _| {
+                if matches!(reason, DeviceLostReason::Unknown) {
+                    is_device_lost.store(true, Ordering::Relaxed);
+                }
+            }
+        });
 
         let global_texture_set = RefCell::new(GlobalTextureSet::new(
             &device,
-            window_inner_size,
-            preferred_format,
-            msaa_sample_count,
+            size,
+            if render_config.hdr {
+                TextureFormat::Rgba16Float
+            } else {
+                preferred_format
+            },
+            render_config.msaa_sample_count,
+            render_config.depth_stencil_format,
+            render_config.face_culling,
         ));
         let per_frame_buffer_pool = PerFrameBufferPool::new();
         let uniform_bind_group_provider = UniformBindGroupProvider::new(&device);
+        let gpu_timer = GpuTimer::new(&device, &queue);
 
         Ok(GfxContext {
             instance,
-            device,
+            device: Arc::new(device),
             queue,
             surface,
+            window,
+            present_mode,
+            render_config,
             surface_config,
             global_texture_set,
             per_frame_buffer_pool,
             uniform_bind_group_provider,
+            is_device_lost,
+            gpu_timer,
+            gpu_frame_time: Cell::new(None),
+            missing_texture: OnceCell::new(),
         })
     }
 
@@ -114,14 +251,86 @@ impl<'window> GfxContext<'window> {
         surface_config.width = size.width;
         surface_config.height = size.height;
 
-        self.surface.configure(&self.device, &surface_config);
+        if let Some(surface) = &self.surface {
+            surface.configure(&self.device, &surface_config);
+        }
+
         self.global_texture_set
             .borrow_mut()
             .resize(&self.device, size);
     }
 
+    /// Configures the surface again with the current configuration, e.g. after it was lost.
+    pub fn reconfigure_surface(&self) {
+        if let Some(surface) = &self.surface {
+            surface.configure(&self.device, &self.surface_config.borrow());
+        }
+    }
+
+    /// Returns `true` if the driver lost the device, e.g. after a sleep or a GPU reset.
+    /// Every GPU resource is invalid from then on; see [`Self::recreate`].
+    pub fn is_device_lost(&self) -> bool {
+        self.is_device_lost.load(Ordering::Relaxed)
+    }
+
+    /// Marks the device as lost, as the driver would.
+    #[cfg(test)]
+    pub(crate) fn lose_device(&self) {
+        self.is_device_lost.store(true, Ordering::Relaxed);
+    }
+
+    /// Makes the device, the surface and the global textures again for the same window, present
+    /// mode and render config, e.g. after the device is lost. Every GPU object made from the
+    /// previous device must be made again; see [`crate::context::Context`] for the resources of
+    /// the registry. If this fails, the context is left without a surface.
+    pub async fn recreate(&mut self) -> Result<(), GfxContextCreationError> {
+        let size = match self.window {
+            Some(window) => window.inner_size(),
+            None => {
+                let surface_config = self.surface_config.borrow();
+                PhysicalSize::new(surface_config.width, surface_config.height)
+            }
+        };
+        let is_main_color_enabled = self.global_texture_set.borrow().main_color.is_some();
+
+        // a window can only have one surface at a time
+        self.surface = None;
+        *self = Self::create(self.window, size, self.present_mode, self.render_config).await?;
+
+        if is_main_color_enabled {
+            self.enable_main_color_texture();
+        }
+
+        Ok(())
+    }
+
+    /// Makes the main pass render into a separate texture, so that other passes can sample it.
+    pub(crate) fn enable_main_color_texture(&self) {
+        let surface_config = self.surface_config.borrow();
+        self.global_texture_set.borrow_mut().enable_main_color(
+            &self.device,
+            PhysicalSize::new(surface_config.width, surface_config.height),
+        );
+    }
+
+    /// Returns the placeholder bound in place of textures that cannot be resolved.
+    /// See [`missing_texture_element`]. It is uploaded on the first call.
+    pub fn missing_texture(&self) -> Arc<TextureView> {
+        self.missing_texture
+            .get_or_init(|| {
+                let texture = Texture::load_from_source(&missing_texture_element(), self);
+                Arc::new(texture.handle().create_view(&Default::default()))
+            })
+            .clone()
+    }
+
+    /// Returns the texture to render the frame into. A headless context has none, and fails
+    /// with [`SurfaceError::Lost`].
     pub fn obtain_surface_view(&self) -> Result<SurfaceTexture, SurfaceError> {
-        self.surface.get_current_texture()
+        match &self.surface {
+            Some(surface) => surface.get_current_texture(),
+            None => Err(SurfaceError::Lost),
+        }
     }
 
     pub fn begin_frame(&self) -> Frame {
@@ -132,20 +341,111 @@ impl<'window> GfxContext<'window> {
             .create_command_encoder(&CommandEncoderDescriptor {
                 label: Some("[GfxContext] begin_frame"),
             });
-        Frame::new(cmd_encoder)
+        let mut frame = Frame::new(cmd_encoder);
+
+        if let Some(gpu_timer) = &self.gpu_timer {
+            gpu_timer.begin(&mut frame);
+        }
+
+        frame
     }
 
-    pub fn end_frame(&self, frame: Frame) {
+    pub fn end_frame(&self, mut frame: Frame) {
+        if let Some(gpu_timer) = &self.gpu_timer {
+            gpu_timer.end(&mut frame);
+        }
+
         self.queue.submit(std::iter::once(frame.finish()));
         self.device.poll(MaintainBase::Wait);
+
+        if let Some(gpu_timer) = &self.gpu_timer {
+            self.gpu_frame_time.set(gpu_timer.read(&self.device));
+        }
+    }
+
+    /// Returns how long the GPU took to execute the last frame, or `None` if the device does not
+    /// support timestamp queries.
+    pub fn gpu_frame_time(&self) -> Option<Duration> {
+        self.gpu_frame_time.get()
+    }
+}
+
+/// Makes a small headless context for tests, or returns `None` if there is no adapter to test
+/// with.
+#[cfg(test)]
+pub(crate) fn test_gfx_ctx() -> Option<GfxContext<'static>> {
+    use pollster::FutureExt;
+
+    match GfxContext::new_headless(PhysicalSize::new(4, 4), RenderConfig::default()).block_on() {
+        Ok(gfx_ctx) => Some(gfx_ctx),
+        Err(GfxContextCreationError::AdapterNotFound) => None,
+        Err(err) => panic!("failed to create a headless gfx context: {}", err),
+    }
+}
+
+/// Returns the requested present mode if the surface supports it, or `Fifo` otherwise, which
+/// every surface supports. The `Auto*` modes are always accepted, as they fall back by themselves.
+fn select_present_mode(requested: PresentMode, supported: &[PresentMode]) -> PresentMode {
+    match requested {
+        PresentMode::AutoVsync | PresentMode::AutoNoVsync => requested,
+        _ if supported.contains(&requested) => requested,
+        _ => {
+            warn!(
+                "the present mode {:?} is not supported by the surface; falling back to {:?}",
+                requested,
+                PresentMode::Fifo
+            );
+            PresentMode::Fifo
+        }
+    }
+}
+
+fn validate_msaa_sample_count(msaa_sample_count: u32) -> Result<(), GfxContextCreationError> {
+    if !MSAA_SAMPLE_COUNTS.contains(&msaa_sample_count) {
+        return Err(GfxContextCreationError::InvalidMsaaSampleCount(
+            msaa_sample_count,
+        ));
     }
+
+    Ok(())
 }
 
-fn select_adapter(surface: &Surface, adapters: impl AsRef<[Adapter]>) -> Option<usize> {
+fn validate_depth_stencil_format(
+    adapter: &Adapter,
+    format: DepthStencilFormat,
+    msaa_sample_count: u32,
+) -> Result<(), GfxContextCreationError> {
+    let texture_format = format.texture_format();
+    let format_features = adapter.get_texture_format_features(texture_format);
+
+    if !adapter.features().contains(format.required_features())
+        || !format_features
+            .allowed_usages
+            .contains(TextureUsages::RENDER_ATTACHMENT)
+        || !format_features
+            .flags
+            .sample_count_supported(msaa_sample_count)
+    {
+        return Err(GfxContextCreationError::DepthStencilFormatNotSupported(
+            texture_format,
+            msaa_sample_count,
+        ));
+    }
+
+    Ok(())
+}
+
+/// Returns the index of the best adapter that supports the surface, or of the best adapter if
+/// there is no surface.
+fn select_adapter(surface: Option<&Surface>, adapters: impl AsRef<[Adapter]>) -> Option<usize> {
+    let is_supported = |adapter: &Adapter| match surface {
+        Some(surface) => !surface.get_capabilities(adapter).formats.is_empty(),
+        None => true,
+    };
     let adapters = adapters
         .as_ref()
         .iter()
-        .filter(|adapter| !surface.get_capabilities(adapter).formats.is_empty())
+        .filter(|adapter| is_supported(adapter))
         .collect::<Vec<_>>();
 
     if adapters.is_empty() {
@@ -155,7 +455,7 @@ fn select_adapter(surface: &Surface, adapters: impl AsRef<[Adapter]>) -> Option<
     let mut scores = adapters.iter().map(|_| 0).collect::<Vec<_>>();
 
     for (index, adapter) in adapters.iter().enumerate() {
-        if surface.get_capabilities(adapter).formats.is_empty() {
+        if !is_supported(adapter) {
             continue;
         }
 
@@ -184,3 +484,62 @@ fn select_adapter(surface: &Surface, adapters: impl AsRef<[Adapter]>) -> Option<
         .max_by_key(|(_, score)| *score)
         .map(|(index, _)| index)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gfx::main_pass_multisample_state;
+
+    #[test]
+    fn check_present_mode_fallback() {
+        let supported = [PresentMode::Fifo, PresentMode::Immediate];
+
+        assert_eq!(
+            select_present_mode(PresentMode::Mailbox, &supported),
+            PresentMode::Fifo
+        );
+        assert_eq!(
+            select_present_mode(PresentMode::Immediate, &supported),
+            PresentMode::Immediate
+        );
+        assert_eq!(
+            select_present_mode(PresentMode::AutoNoVsync, &supported),
+            PresentMode::AutoNoVsync
+        );
+    }
+
+    #[test]
+    fn check_msaa_sample_count() {
+        for count in MSAA_SAMPLE_COUNTS {
+            assert!(validate_msaa_sample_count(count).is_ok());
+            assert_eq!(main_pass_multisample_state(count).count, count);
+        }
+
+        for count in [0, 3, 16] {
+            assert!(matches!(
+                validate_msaa_sample_count(count),
+                Err(GfxContextCreationError::InvalidMsaaSampleCount(invalid)) if invalid == count
+            ));
+        }
+    }
+
+    #[test]
+    fn check_surface_recovery() {
+        assert_eq!(
+            SurfaceRecovery::from_error(&SurfaceError::Lost),
+            SurfaceRecovery::Reconfigure
+        );
+        assert_eq!(
+            SurfaceRecovery::from_error(&SurfaceError::Outdated),
+            SurfaceRecovery::Reconfigure
+        );
+        assert_eq!(
+            SurfaceRecovery::from_error(&SurfaceError::Timeout),
+            SurfaceRecovery::SkipFrame
+        );
+        assert_eq!(
+            SurfaceRecovery::from_error(&SurfaceError::OutOfMemory),
+            SurfaceRecovery::Fatal
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use super::{DepthStencilFormat, FaceCulling, RenderTargetFormats};
 use wgpu::{
     Device, Extent3d, Texture, TextureAspect, TextureDescriptor, TextureDimension, TextureFormat,
     TextureUsages, TextureView, TextureViewDescriptor,
@@ -6,7 +7,13 @@ use winit::dpi::PhysicalSize;
 
 pub struct GlobalTextureSet {
     pub msaa_sample_count: u32,
+    pub depth_stencil_format: DepthStencilFormat,
+    pub face_culling: FaceCulling,
+    /// Format of the color targets of the main pass.
+    pub main_color_format: TextureFormat,
     pub color: Option<TextureSet>,
+    /// The color output of the main pass, if the render graph samples it.
+    pub main_color: Option<TextureSet>,
     pub depth_stencil: TextureSet,
 }
 
@@ -14,11 +21,16 @@ impl GlobalTextureSet {
     pub(crate) fn new(
         device: &Device,
         size: PhysicalSize<u32>,
-        color_texture_format: TextureFormat,
+        main_color_format: TextureFormat,
         msaa_sample_count: u32,
+        depth_stencil_format: DepthStencilFormat,
+        face_culling: FaceCulling,
     ) -> Self {
         Self {
             msaa_sample_count,
+            depth_stencil_format,
+            face_culling,
+            main_color_format,
             color: if msaa_sample_count == 1 {
                 None
             } else {
@@ -26,27 +38,55 @@ impl GlobalTextureSet {
                     device,
                     "color",
                     size,
-                    color_texture_format,
+                    main_color_format,
                     TextureUsages::RENDER_ATTACHMENT,
                     msaa_sample_count,
                 ))
             },
+            main_color: None,
             depth_stencil: TextureSet::new(
                 device,
                 "depth stencil",
                 size,
-                TextureFormat::Depth32Float,
+                depth_stencil_format.texture_format(),
                 TextureUsages::RENDER_ATTACHMENT,
                 msaa_sample_count,
             ),
         }
     }
 
+    pub fn render_target_formats(&self) -> RenderTargetFormats {
+        RenderTargetFormats {
+            color: self.main_color_format,
+            depth_stencil: self.depth_stencil_format,
+            msaa_sample_count: self.msaa_sample_count,
+        }
+    }
+
+    pub(crate) fn enable_main_color(&mut self, device: &Device, size: PhysicalSize<u32>) {
+        if self.main_color.is_some() {
+            return;
+        }
+
+        self.main_color = Some(TextureSet::new(
+            device,
+            "main color",
+            size,
+            self.main_color_format,
+            TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            1,
+        ));
+    }
+
     pub(crate) fn resize(&mut self, device: &Device, size: PhysicalSize<u32>) {
         if let Some(color) = &mut self.color {
             color.resize(device, size);
         }
 
+        if let Some(main_color) = &mut self.main_color {
+            main_color.resize(device, size);
+        }
+
         self.depth_stencil.resize(device, size);
     }
 }
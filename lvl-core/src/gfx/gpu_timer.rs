@@ -0,0 +1,149 @@
+use super::Frame;
+use std::{sync::mpsc, time::Duration};
+use wgpu::{
+    Buffer, BufferDescriptor, BufferUsages, Device, Features, MaintainBase, MapMode, QuerySet,
+    QuerySetDescriptor, QueryType, Queue, QUERY_SIZE,
+};
+
+/// Measures how long the GPU takes to execute a frame, with a timestamp written at the beginning
+/// and the end of its commands. Requires the [`Features::TIMESTAMP_QUERY`] feature.
+#[derive(Debug)]
+pub(crate) struct GpuTimer {
+    query_set: QuerySet,
+    resolve_buffer: Buffer,
+    readback_buffer: Buffer,
+    timestamp_period: f32,
+}
+
+impl GpuTimer {
+    const QUERY_COUNT: u32 = 2;
+
+    /// Returns `None` if the device does not support timestamp queries.
+    pub fn new(device: &Device, queue: &Queue) -> Option<Self> {
+        if !device.features().contains(Features::TIMESTAMP_QUERY) {
+            return None;
+        }
+
+        let size = (Self::QUERY_COUNT * QUERY_SIZE) as u64;
+        let query_set = device.create_query_set(&QuerySetDescriptor {
+            label: Some("gpu-timer-query-set"),
+            ty: QueryType::Timestamp,
+            count: Self::QUERY_COUNT,
+        });
+        let resolve_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("gpu-timer-resolve-buffer"),
+            size,
+            usage: BufferUsages::QUERY_RESOLVE | BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("gpu-timer-readback-buffer"),
+            size,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        Some(Self {
+            query_set,
+            resolve_buffer,
+            readback_buffer,
+            timestamp_period: queue.get_timestamp_period(),
+        })
+    }
+
+    pub fn begin(&self, frame: &mut Frame) {
+        frame.cmd_encoder_mut().write_timestamp(&self.query_set, 0);
+    }
+
+    pub fn end(&self, frame: &mut Frame) {
+        let cmd_encoder = frame.cmd_encoder_mut();
+        cmd_encoder.write_timestamp(&self.query_set, 1);
+        cmd_encoder.resolve_query_set(
+            &self.query_set,
+            0..Self::QUERY_COUNT,
+            &self.resolve_buffer,
+            0,
+        );
+        cmd_encoder.copy_buffer_to_buffer(
+            &self.resolve_buffer,
+            0,
+            &self.readback_buffer,
+            0,
+            self.resolve_buffer.size(),
+        );
+    }
+
+    /// Reads the time back. The frame that ended the timer must have been submitted.
+    pub fn read(&self, device: &Device) -> Option<Duration> {
+        let (sender, receiver) = mpsc::channel();
+        let slice = self.readback_buffer.slice(..);
+        slice.map_async(MapMode::Read, move |result| {
+            sender.send(result).ok();
+        });
+        device.poll(MaintainBase::Wait);
+        receiver.recv().ok()?.ok()?;
+
+        let timestamps = {
+            let data = slice.get_mapped_range();
+            let start = u64::from_le_bytes(data[0..8].try_into().unwrap());
+            let end = u64::from_le_bytes(data[8..16].try_into().unwrap());
+            (start, end)
+        };
+        self.readback_buffer.unmap();
+
+        Some(timestamps_to_duration(
+            timestamps.0,
+            timestamps.1,
+            self.timestamp_period,
+        ))
+    }
+}
+
+/// Converts the ticks between two timestamps into a duration. Some drivers reset the counter
+/// between the two, which is reported as zero rather than a huge time.
+fn timestamps_to_duration(start: u64, end: u64, timestamp_period: f32) -> Duration {
+    let ticks = end.saturating_sub(start);
+    Duration::from_nanos((ticks as f64 * timestamp_period as f64) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gfx::test_gfx_ctx;
+
+    #[test]
+    fn check_timestamps_to_duration() {
+        assert_eq!(
+            timestamps_to_duration(1_000, 3_000, 1.0),
+            Duration::from_micros(2)
+        );
+        assert_eq!(
+            timestamps_to_duration(1_000, 3_000, 83.333),
+            Duration::from_nanos(166_666)
+        );
+        assert_eq!(timestamps_to_duration(3_000, 1_000, 1.0), Duration::ZERO);
+    }
+
+    #[test]
+    fn check_frame_reports_gpu_frame_time() {
+        let gfx_ctx = match test_gfx_ctx() {
+            Some(gfx_ctx) => gfx_ctx,
+            None => {
+                return;
+            }
+        };
+        let frame = gfx_ctx.begin_frame();
+        gfx_ctx.end_frame(frame);
+
+        // the time is measured only if the adapter offers timestamp queries
+        if gfx_ctx
+            .device
+            .features()
+            .contains(Features::TIMESTAMP_QUERY)
+        {
+            assert!(gfx_ctx.gpu_frame_time().is_some());
+        } else {
+            assert_eq!(gfx_ctx.gpu_frame_time(), None);
+        }
+    }
+}
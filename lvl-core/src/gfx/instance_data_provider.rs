@@ -67,10 +67,78 @@ impl InstanceDataProvider {
         let slicer = buffer_pool.allocate(size, device);
 
         if let Some(mut view) = queue.write_buffer_with(slicer.buffer(), slicer.offset(), size) {
-            view[..size_of::<[f32; 4]>() * 4].copy_from_slice(matrix.as_bytes());
-            view[size_of::<[f32; 4]>() * 4..].copy_from_slice(matrix.inversed().as_bytes());
+            self.write_instance_data(matrix, &mut view);
         }
 
         slicer
     }
+
+    /// Writes the per-instance data for the given world matrix into `data`, which must be
+    /// [`Self::instance_data_size`] bytes long: the matrix followed by its inverse.
+    pub fn write_instance_data(&self, matrix: &Mat4, data: &mut [u8]) {
+        let (matrix_data, inversed_data) = data.split_at_mut(size_of::<[f32; 4]>() * 4);
+        matrix_data.copy_from_slice(matrix.as_bytes());
+        inversed_data.copy_from_slice(matrix.inversed().as_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::{HierarchyStorage, ObjectId, Transform};
+    use lvl_math::{Vec3, Vec4};
+    use std::num::NonZeroU32;
+
+    fn read_matrix(data: &[u8]) -> Mat4 {
+        let mut elements = [0f32; 16];
+
+        for (element, bytes) in elements.iter_mut().zip(data.chunks_exact(4)) {
+            *element = f32::from_le_bytes(bytes.try_into().unwrap());
+        }
+
+        Mat4::new(elements)
+    }
+
+    #[test]
+    fn check_instance_data_follows_hierarchy() {
+        let parent = ObjectId::new(NonZeroU32::new(1).unwrap());
+        let child = ObjectId::new(NonZeroU32::new(2).unwrap());
+        let mut hierarchy = HierarchyStorage::new();
+        hierarchy.add(parent);
+        hierarchy.add(child);
+        hierarchy.set_parent(child, Some(parent));
+
+        let mut parent_transform = Transform::identity();
+        let child_transform = Transform {
+            position: Vec3::new(0.0, 1.0, 0.0),
+            ..Transform::identity()
+        };
+        let vertex = Vec4::new(0.5, 0.0, 0.0, 1.0);
+        let mut data = vec![0; InstanceDataProvider.instance_data_size() as usize];
+        let mut rendered_positions = Vec::new();
+
+        for position in [Vec3::ZERO, Vec3::new(3.0, 0.0, -2.0)] {
+            parent_transform.position = position;
+            hierarchy.set_dirty(parent);
+            hierarchy.update_object_matrices(|object_id| {
+                Some(if object_id == parent {
+                    parent_transform.matrix()
+                } else {
+                    child_transform.matrix()
+                })
+            });
+
+            InstanceDataProvider.write_instance_data(hierarchy.matrix(child), &mut data);
+
+            let world_position = vertex * read_matrix(&data[..64]);
+            let local_position = world_position * read_matrix(&data[64..]);
+            assert!((local_position - vertex).len() <= 1e-5);
+
+            rendered_positions.push(world_position);
+        }
+
+        // moving the parent moves the vertices of the child by the same offset
+        assert_eq!(rendered_positions[0], Vec4::new(0.5, 1.0, 0.0, 1.0));
+        assert_eq!(rendered_positions[1], Vec4::new(3.5, 1.0, -2.0, 1.0));
+    }
 }
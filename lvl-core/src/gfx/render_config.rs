@@ -0,0 +1,301 @@
+use wgpu::{
+    BlendState, ColorTargetState, ColorWrites, CompareFunction, DepthStencilState, Face, Features,
+    FrontFace, MultisampleState, StencilState, TextureFormat,
+};
+
+/// Sample counts that [`RenderConfig::msaa_sample_count`] accepts.
+pub const MSAA_SAMPLE_COUNTS: [u32; 4] = [1, 2, 4, 8];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RenderConfig {
+    /// Number of samples per pixel of the main pass; one of [`MSAA_SAMPLE_COUNTS`].
+    pub msaa_sample_count: u32,
+    pub depth_stencil_format: DepthStencilFormat,
+    /// Renders the main pass into an `Rgba16Float` texture, which is tonemapped into the surface.
+    pub hdr: bool,
+    pub face_culling: FaceCulling,
+}
+
+impl Default for RenderConfig {
+    fn default() -> Self {
+        Self {
+            msaa_sample_count: 1,
+            depth_stencil_format: DepthStencilFormat::default(),
+            hdr: false,
+            face_culling: FaceCulling::default(),
+        }
+    }
+}
+
+/// Returns the multisample state of the render pipelines drawing into the main pass, whose
+/// color and depth-stencil targets have the given number of samples.
+pub fn main_pass_multisample_state(msaa_sample_count: u32) -> MultisampleState {
+    MultisampleState {
+        count: msaa_sample_count,
+        mask: !0,
+        alpha_to_coverage_enabled: false,
+    }
+}
+
+/// Formats and sample count of the targets of the main pass, which the render pipelines drawing
+/// into it have to match. The color format follows the surface, or `Rgba16Float` with HDR.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RenderTargetFormats {
+    pub color: TextureFormat,
+    pub depth_stencil: DepthStencilFormat,
+    pub msaa_sample_count: u32,
+}
+
+impl RenderTargetFormats {
+    /// Makes the state of the single color target of the main pass.
+    pub fn color_target_state(self, blend: Option<BlendState>) -> ColorTargetState {
+        ColorTargetState {
+            format: self.color,
+            blend,
+            write_mask: ColorWrites::all(),
+        }
+    }
+
+    pub fn multisample_state(self) -> MultisampleState {
+        main_pass_multisample_state(self.msaa_sample_count)
+    }
+}
+
+/// Format of the depth-stencil attachment that is shared by all render pipelines.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DepthStencilFormat {
+    /// A depth format of at least 24 bits, which is cheaper than `Depth32Float` on some GPUs.
+    Depth24Plus,
+    Depth24PlusStencil8,
+    #[default]
+    Depth32Float,
+    /// Requires the [`Features::DEPTH32FLOAT_STENCIL8`] feature.
+    Depth32FloatStencil8,
+}
+
+impl DepthStencilFormat {
+    pub fn texture_format(self) -> TextureFormat {
+        match self {
+            Self::Depth24Plus => TextureFormat::Depth24Plus,
+            Self::Depth24PlusStencil8 => TextureFormat::Depth24PlusStencil8,
+            Self::Depth32Float => TextureFormat::Depth32Float,
+            Self::Depth32FloatStencil8 => TextureFormat::Depth32FloatStencil8,
+        }
+    }
+
+    pub fn has_stencil(self) -> bool {
+        match self {
+            Self::Depth24Plus => false,
+            Self::Depth24PlusStencil8 => true,
+            Self::Depth32Float => false,
+            Self::Depth32FloatStencil8 => true,
+        }
+    }
+
+    pub fn required_features(self) -> Features {
+        match self {
+            Self::Depth24Plus => Features::empty(),
+            Self::Depth24PlusStencil8 => Features::empty(),
+            Self::Depth32Float => Features::empty(),
+            Self::Depth32FloatStencil8 => Features::DEPTH32FLOAT_STENCIL8,
+        }
+    }
+
+    /// Makes a depth-stencil state of this format.
+    /// The stencil state is dropped if this format has no stencil aspect.
+    pub fn depth_stencil_state(
+        self,
+        depth_write_enabled: bool,
+        depth_compare: CompareFunction,
+        stencil: StencilState,
+    ) -> DepthStencilState {
+        DepthStencilState {
+            format: self.texture_format(),
+            depth_write_enabled,
+            depth_compare,
+            stencil: if self.has_stencil() {
+                stencil
+            } else {
+                StencilState::default()
+            },
+            bias: Default::default(),
+        }
+    }
+}
+
+/// Winding and culling shared by all render pipelines of the main pass.
+/// Models whose winding is imported reversed can be fixed by flipping the front face here,
+/// instead of flipping their geometry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FaceCulling {
+    pub front_face: FrontFace,
+    pub cull_mode: Option<Face>,
+}
+
+impl FaceCulling {
+    /// Returns the cull mode of a material; materials without back-face culling
+    /// (e.g. the `no_cull_back_face` flag of PMX) are drawn double-sided.
+    pub fn cull_mode(self, no_cull_back_face: bool) -> Option<Face> {
+        if no_cull_back_face {
+            None
+        } else {
+            self.cull_mode
+        }
+    }
+
+    /// Returns `true` if a triangle with the given winding on the screen is culled.
+    pub fn is_culled(self, winding: FrontFace, no_cull_back_face: bool) -> bool {
+        let face = if winding == self.front_face {
+            Face::Front
+        } else {
+            Face::Back
+        };
+
+        self.cull_mode(no_cull_back_face) == Some(face)
+    }
+}
+
+impl Default for FaceCulling {
+    fn default() -> Self {
+        Self {
+            front_face: FrontFace::Cw,
+            cull_mode: Some(Face::Back),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lvl_math::Vec2;
+    use wgpu::{StencilFaceState, StencilOperation};
+
+    /// Returns the winding of a triangle in normalized device coordinates.
+    fn triangle_winding(a: Vec2, b: Vec2, c: Vec2) -> FrontFace {
+        let area = (b.x - a.x) * (c.y - a.y) - (c.x - a.x) * (b.y - a.y);
+
+        if 0.0 < area {
+            FrontFace::Ccw
+        } else {
+            FrontFace::Cw
+        }
+    }
+
+    fn outline_stencil_state() -> StencilState {
+        let face = StencilFaceState {
+            compare: CompareFunction::Always,
+            fail_op: StencilOperation::Keep,
+            depth_fail_op: StencilOperation::Keep,
+            pass_op: StencilOperation::Replace,
+        };
+
+        StencilState {
+            front: face,
+            back: face,
+            read_mask: 0xff,
+            write_mask: 0xff,
+        }
+    }
+
+    #[test]
+    fn check_stencil_state_honored() {
+        for format in [
+            DepthStencilFormat::Depth24PlusStencil8,
+            DepthStencilFormat::Depth32FloatStencil8,
+        ] {
+            let state =
+                format.depth_stencil_state(true, CompareFunction::Less, outline_stencil_state());
+
+            assert_eq!(state.format, format.texture_format());
+            assert!(state.format.has_stencil_aspect());
+            assert_eq!(state.stencil, outline_stencil_state());
+            assert!(state.stencil.is_enabled());
+        }
+    }
+
+    #[test]
+    fn check_stencil_state_dropped_without_stencil() {
+        for format in [
+            DepthStencilFormat::Depth24Plus,
+            DepthStencilFormat::Depth32Float,
+        ] {
+            let state =
+                format.depth_stencil_state(true, CompareFunction::Less, outline_stencil_state());
+
+            assert_eq!(state.format, format.texture_format());
+            assert!(!state.format.has_stencil_aspect());
+            assert!(!state.stencil.is_enabled());
+        }
+    }
+
+    #[test]
+    fn check_render_target_formats() {
+        let formats = RenderTargetFormats {
+            color: TextureFormat::Rgba8UnormSrgb,
+            depth_stencil: DepthStencilFormat::Depth24Plus,
+            msaa_sample_count: 4,
+        };
+        let color_target = formats.color_target_state(Some(BlendState::ALPHA_BLENDING));
+
+        assert_eq!(
+            color_target,
+            ColorTargetState {
+                format: TextureFormat::Rgba8UnormSrgb,
+                blend: Some(BlendState::ALPHA_BLENDING),
+                write_mask: ColorWrites::all(),
+            }
+        );
+        assert_eq!(formats.multisample_state().count, 4);
+        assert_eq!(
+            formats.depth_stencil.texture_format(),
+            TextureFormat::Depth24Plus
+        );
+    }
+
+    #[test]
+    fn check_front_face_flips_culled_faces() {
+        let ccw = triangle_winding(
+            Vec2::new(0.0, 0.0),
+            Vec2::new(1.0, 0.0),
+            Vec2::new(0.0, 1.0),
+        );
+        let cw = triangle_winding(
+            Vec2::new(0.0, 0.0),
+            Vec2::new(0.0, 1.0),
+            Vec2::new(1.0, 0.0),
+        );
+        assert_eq!(ccw, FrontFace::Ccw);
+        assert_eq!(cw, FrontFace::Cw);
+
+        let cw_front = FaceCulling {
+            front_face: FrontFace::Cw,
+            cull_mode: Some(Face::Back),
+        };
+        assert!(cw_front.is_culled(ccw, false));
+        assert!(!cw_front.is_culled(cw, false));
+
+        let ccw_front = FaceCulling {
+            front_face: FrontFace::Ccw,
+            cull_mode: Some(Face::Back),
+        };
+        assert!(!ccw_front.is_culled(ccw, false));
+        assert!(ccw_front.is_culled(cw, false));
+    }
+
+    #[test]
+    fn check_material_disables_culling() {
+        for front_face in [FrontFace::Cw, FrontFace::Ccw] {
+            for cull_mode in [Some(Face::Back), Some(Face::Front), None] {
+                let face_culling = FaceCulling {
+                    front_face,
+                    cull_mode,
+                };
+
+                assert_eq!(face_culling.cull_mode(false), cull_mode);
+                assert_eq!(face_culling.cull_mode(true), None);
+                assert!(!face_culling.is_culled(FrontFace::Cw, true));
+                assert!(!face_culling.is_culled(FrontFace::Ccw, true));
+            }
+        }
+    }
+}
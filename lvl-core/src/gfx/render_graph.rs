@@ -0,0 +1,519 @@
+use super::{Frame, GfxContext};
+use thiserror::Error;
+use wgpu::TextureView;
+
+/// Textures that the passes of a [`RenderGraph`] can read and write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RenderGraphTexture {
+    /// The texture of the window surface. It can be written, but not read.
+    Surface,
+    /// The color output of the main pass.
+    MainColor,
+    /// The depth-stencil texture of the main pass.
+    DepthStencil,
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum RenderGraphError {
+    #[error("pass `{pass}` reads {texture:?}, which is not written by any preceding pass")]
+    InputNotProduced {
+        pass: String,
+        texture: RenderGraphTexture,
+    },
+    #[error("pass `{pass}` reads the surface, which is write-only")]
+    SurfaceNotReadable { pass: String },
+    #[error("the main color is read by a pass, but no pass writes the surface after that")]
+    SurfaceNotWritten,
+}
+
+/// A custom pass that can be inserted into the [`RenderGraph`].
+pub trait RenderGraphPass
+where
+    Self: 'static,
+{
+    fn name(&self) -> &str;
+
+    /// Textures this pass reads. Each of them must be written by a preceding pass.
+    fn inputs(&self) -> &[RenderGraphTexture] {
+        &[]
+    }
+
+    /// Textures this pass writes.
+    fn outputs(&self) -> &[RenderGraphTexture];
+
+    fn execute(&mut self, context: &mut RenderGraphPassContext);
+}
+
+pub(crate) enum RenderGraphNode {
+    /// The built-in pass that renders all the cameras of the scene.
+    Main,
+    Custom(Box<dyn RenderGraphPass>),
+}
+
+impl RenderGraphNode {
+    fn name(&self) -> &str {
+        match self {
+            Self::Main => RenderGraph::MAIN_PASS_NAME,
+            Self::Custom(pass) => pass.name(),
+        }
+    }
+
+    fn inputs(&self) -> &[RenderGraphTexture] {
+        match self {
+            Self::Main => &[],
+            Self::Custom(pass) => pass.inputs(),
+        }
+    }
+}
+
+/// A linear list of passes executed in order every frame. The main pass is always the first one;
+/// drivers append their passes via [`crate::context::driver::Driver::on_configure_render`].
+pub struct RenderGraph {
+    nodes: Vec<RenderGraphNode>,
+}
+
+impl RenderGraph {
+    pub const MAIN_PASS_NAME: &'static str = "main";
+
+    pub(crate) fn new() -> Self {
+        Self {
+            nodes: vec![RenderGraphNode::Main],
+        }
+    }
+
+    /// Appends a pass that runs after all the passes added so far.
+    pub fn add_pass(&mut self, pass: impl RenderGraphPass) {
+        self.nodes.push(RenderGraphNode::Custom(Box::new(pass)));
+    }
+
+    pub fn pass_names(&self) -> impl Iterator<Item = &str> {
+        self.nodes.iter().map(|node| node.name())
+    }
+
+    /// Returns `true` if any pass added by the drivers writes the surface.
+    pub fn is_surface_written_by_pass(&self) -> bool {
+        self.nodes.iter().any(|node| match node {
+            RenderGraphNode::Main => false,
+            RenderGraphNode::Custom(pass) => pass.outputs().contains(&RenderGraphTexture::Surface),
+        })
+    }
+
+    /// Executes the passes in order into the given frame. The main pass is run by
+    /// `render_main_pass`, which returns the exposure of the last camera it rendered.
+    pub(crate) fn execute(
+        &mut self,
+        gfx_ctx: &GfxContext,
+        frame: &mut Frame,
+        surface_texture_view: &TextureView,
+        mut render_main_pass: impl FnMut(&mut Frame) -> f32,
+    ) {
+        let mut exposure = 1.0;
+
+        for node in &mut self.nodes {
+            match node {
+                RenderGraphNode::Main => {
+                    exposure = render_main_pass(frame);
+                }
+                RenderGraphNode::Custom(pass) => {
+                    let global_texture_set = gfx_ctx.global_texture_set.borrow();
+                    let mut pass_ctx = RenderGraphPassContext::new(
+                        gfx_ctx,
+                        frame,
+                        surface_texture_view,
+                        global_texture_set
+                            .main_color
+                            .as_ref()
+                            .map(|main_color| &main_color.texture_view),
+                        &global_texture_set.depth_stencil.texture_view,
+                        exposure,
+                    );
+                    pass.execute(&mut pass_ctx);
+                }
+            }
+        }
+    }
+
+    /// Checks the inputs of all passes and decides where the main pass renders into.
+    pub fn plan(&self) -> Result<RenderGraphPlan, RenderGraphError> {
+        // the main color has to be kept in a separate texture if any pass samples it
+        let is_main_color_offscreen = self
+            .nodes
+            .iter()
+            .any(|node| node.inputs().contains(&RenderGraphTexture::MainColor));
+        let mut producers = Vec::with_capacity(self.nodes.len());
+        let mut last_writers = Vec::<(RenderGraphTexture, usize)>::with_capacity(3);
+        let mut is_surface_written_after_main_color_read = !is_main_color_offscreen;
+
+        for (index, node) in self.nodes.iter().enumerate() {
+            let mut node_producers = Vec::with_capacity(node.inputs().len());
+
+            for &texture in node.inputs() {
+                if texture == RenderGraphTexture::Surface {
+                    return Err(RenderGraphError::SurfaceNotReadable {
+                        pass: node.name().to_owned(),
+                    });
+                }
+
+                let producer = last_writers
+                    .iter()
+                    .find(|(written, _)| *written == texture)
+                    .map(|(_, producer)| *producer);
+
+                match producer {
+                    Some(producer) => node_producers.push((texture, producer)),
+                    None => {
+                        return Err(RenderGraphError::InputNotProduced {
+                            pass: node.name().to_owned(),
+                            texture,
+                        });
+                    }
+                }
+
+                if texture == RenderGraphTexture::MainColor {
+                    is_surface_written_after_main_color_read = false;
+                }
+            }
+
+            let outputs: &[RenderGraphTexture] = match node {
+                RenderGraphNode::Main if is_main_color_offscreen => &[
+                    RenderGraphTexture::MainColor,
+                    RenderGraphTexture::DepthStencil,
+                ],
+                RenderGraphNode::Main => &[
+                    RenderGraphTexture::MainColor,
+                    RenderGraphTexture::DepthStencil,
+                    RenderGraphTexture::Surface,
+                ],
+                RenderGraphNode::Custom(pass) => pass.outputs(),
+            };
+
+            for &texture in outputs {
+                last_writers.retain(|(written, _)| *written != texture);
+                last_writers.push((texture, index));
+
+                if texture == RenderGraphTexture::Surface {
+                    is_surface_written_after_main_color_read = true;
+                }
+            }
+
+            producers.push(node_producers);
+        }
+
+        if !is_surface_written_after_main_color_read {
+            return Err(RenderGraphError::SurfaceNotWritten);
+        }
+
+        Ok(RenderGraphPlan {
+            is_main_color_offscreen,
+            producers,
+        })
+    }
+}
+
+/// The result of [`RenderGraph::plan`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderGraphPlan {
+    is_main_color_offscreen: bool,
+    producers: Vec<Vec<(RenderGraphTexture, usize)>>,
+}
+
+impl RenderGraphPlan {
+    /// Returns `true` if the main pass renders into a separate texture instead of the surface.
+    pub fn is_main_color_offscreen(&self) -> bool {
+        self.is_main_color_offscreen
+    }
+
+    /// Returns the index of the pass that writes the given input of the given pass.
+    pub fn producer(&self, pass_index: usize, texture: RenderGraphTexture) -> Option<usize> {
+        self.producers
+            .get(pass_index)?
+            .iter()
+            .find(|(input, _)| *input == texture)
+            .map(|(_, producer)| *producer)
+    }
+}
+
+/// Resources handed to a [`RenderGraphPass`] while it is executed.
+pub struct RenderGraphPassContext<'a, 'window> {
+    gfx_ctx: &'a GfxContext<'window>,
+    frame: &'a mut Frame,
+    surface_texture_view: &'a TextureView,
+    main_color_texture_view: Option<&'a TextureView>,
+    depth_stencil_texture_view: &'a TextureView,
+    exposure: f32,
+}
+
+impl<'a, 'window> RenderGraphPassContext<'a, 'window> {
+    pub(crate) fn new(
+        gfx_ctx: &'a GfxContext<'window>,
+        frame: &'a mut Frame,
+        surface_texture_view: &'a TextureView,
+        main_color_texture_view: Option<&'a TextureView>,
+        depth_stencil_texture_view: &'a TextureView,
+        exposure: f32,
+    ) -> Self {
+        Self {
+            gfx_ctx,
+            frame,
+            surface_texture_view,
+            main_color_texture_view,
+            depth_stencil_texture_view,
+            exposure,
+        }
+    }
+
+    pub fn gfx_ctx(&self) -> &'a GfxContext<'window> {
+        self.gfx_ctx
+    }
+
+    pub fn frame(&mut self) -> &mut Frame {
+        self.frame
+    }
+
+    /// Exposure of the last camera rendered by the main pass; `1.0` if there is no camera.
+    pub fn exposure(&self) -> f32 {
+        self.exposure
+    }
+
+    /// Returns the view of the given texture, or `None` if it is not available;
+    /// the main color is available only if a pass declares it as an input.
+    pub fn texture_view(&self, texture: RenderGraphTexture) -> Option<&'a TextureView> {
+        match texture {
+            RenderGraphTexture::Surface => Some(self.surface_texture_view),
+            RenderGraphTexture::MainColor => self.main_color_texture_view,
+            RenderGraphTexture::DepthStencil => Some(self.depth_stencil_texture_view),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gfx::{test_gfx_ctx, ClearMode, PendingFrameCapture, RenderPassTarget};
+    use wgpu::{
+        BindGroupDescriptor, BindGroupEntry, BindingResource, Color, ColorTargetState, ColorWrites,
+        Extent3d, FragmentState, MultisampleState, PrimitiveState, RenderPipelineDescriptor,
+        ShaderModuleDescriptor, TextureDescriptor, TextureDimension, TextureUsages,
+        TextureViewDescriptor, VertexState,
+    };
+
+    struct TestPass {
+        name: &'static str,
+        inputs: Vec<RenderGraphTexture>,
+        outputs: Vec<RenderGraphTexture>,
+    }
+
+    impl RenderGraphPass for TestPass {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn inputs(&self) -> &[RenderGraphTexture] {
+            &self.inputs
+        }
+
+        fn outputs(&self) -> &[RenderGraphTexture] {
+            &self.outputs
+        }
+
+        fn execute(&mut self, _context: &mut RenderGraphPassContext) {}
+    }
+
+    const COPY_SHADER: &str = r"
+        @group(0) @binding(0) var main_color: texture_2d<f32>;
+
+        @vertex
+        fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+            let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+            return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+        }
+
+        @fragment
+        fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+            return textureLoad(main_color, vec2<i32>(position.xy), 0);
+        }
+    ";
+
+    /// Copies the main color into the surface by sampling it.
+    struct CopyPass;
+
+    impl RenderGraphPass for CopyPass {
+        fn name(&self) -> &str {
+            "copy"
+        }
+
+        fn inputs(&self) -> &[RenderGraphTexture] {
+            &[RenderGraphTexture::MainColor]
+        }
+
+        fn outputs(&self) -> &[RenderGraphTexture] {
+            &[RenderGraphTexture::Surface]
+        }
+
+        fn execute(&mut self, context: &mut RenderGraphPassContext) {
+            let main_color_texture_view =
+                context.texture_view(RenderGraphTexture::MainColor).unwrap();
+            let surface_texture_view = context.texture_view(RenderGraphTexture::Surface).unwrap();
+            let gfx_ctx = context.gfx_ctx();
+            let module = gfx_ctx.device.create_shader_module(ShaderModuleDescriptor {
+                label: None,
+                source: wgpu::ShaderSource::Wgsl(COPY_SHADER.into()),
+            });
+            let pipeline = gfx_ctx
+                .device
+                .create_render_pipeline(&RenderPipelineDescriptor {
+                    label: None,
+                    layout: None,
+                    vertex: VertexState {
+                        module: &module,
+                        entry_point: "vs_main",
+                        buffers: &[],
+                    },
+                    primitive: PrimitiveState::default(),
+                    depth_stencil: None,
+                    multisample: MultisampleState::default(),
+                    fragment: Some(FragmentState {
+                        module: &module,
+                        entry_point: "fs_main",
+                        targets: &[Some(ColorTargetState {
+                            format: gfx_ctx.surface_config.borrow().format,
+                            blend: None,
+                            write_mask: ColorWrites::all(),
+                        })],
+                    }),
+                    multiview: None,
+                });
+            let bind_group = gfx_ctx.device.create_bind_group(&BindGroupDescriptor {
+                label: None,
+                layout: &pipeline.get_bind_group_layout(0),
+                entries: &[BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(main_color_texture_view),
+                }],
+            });
+
+            let mut render_pass = context.frame().begin_render_pass(
+                ClearMode::Keep,
+                &[Some(RenderPassTarget {
+                    view: surface_texture_view,
+                    resolve_target: None,
+                    writable: true,
+                })],
+                None,
+            );
+            render_pass.set_pipeline(&pipeline);
+            render_pass.set_bind_group(0, &bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+    }
+
+    #[test]
+    fn check_main_pass_only() {
+        let graph = RenderGraph::new();
+        let plan = graph.plan().unwrap();
+
+        assert_eq!(graph.pass_names().collect::<Vec<_>>(), ["main"]);
+        assert!(!plan.is_main_color_offscreen());
+    }
+
+    #[test]
+    fn check_custom_pass_reads_main_color() {
+        let mut graph = RenderGraph::new();
+        graph.add_pass(TestPass {
+            name: "tonemap",
+            inputs: vec![RenderGraphTexture::MainColor],
+            outputs: vec![RenderGraphTexture::Surface],
+        });
+
+        let plan = graph.plan().unwrap();
+
+        assert_eq!(graph.pass_names().collect::<Vec<_>>(), ["main", "tonemap"]);
+        assert!(plan.is_main_color_offscreen());
+        assert_eq!(plan.producer(1, RenderGraphTexture::MainColor), Some(0));
+    }
+
+    #[test]
+    fn check_invalid_graphs() {
+        let mut graph = RenderGraph::new();
+        graph.add_pass(TestPass {
+            name: "bloom",
+            inputs: vec![RenderGraphTexture::MainColor],
+            outputs: vec![RenderGraphTexture::MainColor],
+        });
+        assert_eq!(graph.plan(), Err(RenderGraphError::SurfaceNotWritten));
+
+        let mut graph = RenderGraph::new();
+        graph.add_pass(TestPass {
+            name: "readback",
+            inputs: vec![RenderGraphTexture::Surface],
+            outputs: vec![],
+        });
+        assert_eq!(
+            graph.plan(),
+            Err(RenderGraphError::SurfaceNotReadable {
+                pass: "readback".to_owned()
+            })
+        );
+    }
+
+    #[test]
+    fn check_custom_pass_samples_main_color() {
+        let gfx_ctx = match test_gfx_ctx() {
+            Some(gfx_ctx) => gfx_ctx,
+            None => {
+                return;
+            }
+        };
+        let mut graph = RenderGraph::new();
+        graph.add_pass(CopyPass);
+
+        assert!(graph.plan().unwrap().is_main_color_offscreen());
+        gfx_ctx.enable_main_color_texture();
+
+        let surface_config = gfx_ctx.surface_config.borrow().clone();
+        let surface = gfx_ctx.device.create_texture(&TextureDescriptor {
+            label: None,
+            size: Extent3d {
+                width: surface_config.width,
+                height: surface_config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: surface_config.format,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let surface_texture_view = surface.create_view(&TextureViewDescriptor::default());
+
+        let mut frame = gfx_ctx.begin_frame();
+        graph.execute(&gfx_ctx, &mut frame, &surface_texture_view, |frame| {
+            // stands in for the cameras, which clear the main color
+            let global_texture_set = gfx_ctx.global_texture_set.borrow();
+            frame.begin_render_pass(
+                ClearMode::All {
+                    color: Color::RED,
+                    depth: 1.0,
+                    stencil: 0,
+                },
+                &[Some(RenderPassTarget {
+                    view: &global_texture_set.main_color.as_ref().unwrap().texture_view,
+                    resolve_target: None,
+                    writable: true,
+                })],
+                None,
+            );
+            1.0
+        });
+        let capture = PendingFrameCapture::record(&surface, &mut frame, &gfx_ctx.device).unwrap();
+        gfx_ctx.end_frame(frame);
+        let capture = capture.read(&gfx_ctx.device).unwrap();
+
+        // the surface is left black unless the pass runs after the main pass
+        assert!(capture
+            .pixels
+            .chunks_exact(4)
+            .all(|pixel| pixel == [255, 0, 0, 255]));
+    }
+}
@@ -0,0 +1,475 @@
+use super::{
+    ClearMode, RenderGraphPass, RenderGraphPassContext, RenderGraphTexture, RenderPassTarget,
+};
+#[cfg(test)]
+use lvl_math::Vec3;
+use std::mem::size_of;
+use wgpu::{
+    BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, Buffer, BufferBindingType,
+    BufferDescriptor, BufferUsages, ColorTargetState, ColorWrites, Device, FragmentState,
+    MultisampleState, PipelineLayoutDescriptor, PrimitiveState, RenderPipeline,
+    RenderPipelineDescriptor, ShaderModuleDescriptor, ShaderStages, TextureFormat,
+    TextureSampleType, TextureViewDimension, VertexState,
+};
+use zerocopy::AsBytes;
+
+/// Curve that maps the HDR colors into the displayable range.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TonemapOperator {
+    Reinhard,
+    /// The curve fitted to the ACES filmic reference by Krzysztof Narkowicz.
+    #[default]
+    Aces,
+}
+
+impl TonemapOperator {
+    /// Maps a linear HDR color into the `0..1` range, after scaling it by the exposure.
+    /// This is the reference of the tonemap shader.
+    #[cfg(test)]
+    fn apply(self, color: Vec3, exposure: f32) -> Vec3 {
+        let color = color * exposure;
+        let map = |c: f32| match self {
+            Self::Reinhard => c / (1.0 + c),
+            Self::Aces => {
+                let c = c.max(0.0);
+                ((c * (2.51 * c + 0.03)) / (c * (2.43 * c + 0.59) + 0.14)).clamp(0.0, 1.0)
+            }
+        };
+
+        Vec3::new(map(color.x), map(color.y), map(color.z))
+    }
+
+    fn shader_index(self) -> u32 {
+        match self {
+            Self::Reinhard => 0,
+            Self::Aces => 1,
+        }
+    }
+}
+
+/// Encodes a linear color component with the sRGB transfer function.
+/// Used by the tonemap shader when the surface format is not an sRGB one.
+#[cfg(test)]
+fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+const TONEMAP_SHADER: &str = r"
+struct TonemapParams {
+    exposure: f32,
+    operator_index: u32,
+    encode_srgb: u32,
+    _padding: u32,
+}
+
+@group(0) @binding(0) var hdr_color: texture_2d<f32>;
+@group(0) @binding(1) var<uniform> params: TonemapParams;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    // a triangle covering the whole screen
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+fn reinhard(c: vec3<f32>) -> vec3<f32> {
+    return c / (vec3<f32>(1.0) + c);
+}
+
+fn aces(c: vec3<f32>) -> vec3<f32> {
+    let x = max(c, vec3<f32>(0.0));
+    return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+fn linear_to_srgb(c: vec3<f32>) -> vec3<f32> {
+    let low = c * 12.92;
+    let high = 1.055 * pow(c, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, c <= vec3<f32>(0.0031308));
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let hdr = textureLoad(hdr_color, vec2<i32>(position.xy), 0);
+    let exposed = hdr.rgb * params.exposure;
+    var mapped: vec3<f32>;
+
+    if params.operator_index == 0u {
+        mapped = reinhard(exposed);
+    } else {
+        mapped = aces(exposed);
+    }
+
+    if params.encode_srgb != 0u {
+        mapped = linear_to_srgb(mapped);
+    }
+
+    return vec4<f32>(mapped, 1.0);
+}
+";
+
+#[repr(C)]
+#[derive(AsBytes)]
+struct TonemapParams {
+    exposure: f32,
+    operator_index: u32,
+    encode_srgb: u32,
+    _padding: u32,
+}
+
+#[derive(Debug)]
+struct TonemapPipeline {
+    bind_group_layout: BindGroupLayout,
+    pipeline: RenderPipeline,
+    params_buffer: Buffer,
+    surface_format: TextureFormat,
+}
+
+impl TonemapPipeline {
+    fn new(surface_format: TextureFormat, device: &Device) -> Self {
+        let module = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("tonemap-shader"),
+            source: wgpu::ShaderSource::Wgsl(TONEMAP_SHADER.into()),
+        });
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("tonemap-bind-group-layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: false },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("tonemap-pipeline-layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("tonemap-render-pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                module: &module,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            fragment: Some(FragmentState {
+                module: &module,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format: surface_format,
+                    blend: None,
+                    write_mask: ColorWrites::all(),
+                })],
+            }),
+            multiview: None,
+        });
+        let params_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("tonemap-params"),
+            size: size_of::<TonemapParams>() as u64,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            bind_group_layout,
+            pipeline,
+            params_buffer,
+            surface_format,
+        }
+    }
+}
+
+/// A fullscreen pass that tonemaps the HDR main color into the surface.
+/// The exposure is taken from the last rendered camera.
+#[derive(Debug, Default)]
+pub struct TonemapPass {
+    operator: TonemapOperator,
+    pipeline: Option<TonemapPipeline>,
+}
+
+impl TonemapPass {
+    pub fn new(operator: TonemapOperator) -> Self {
+        Self {
+            operator,
+            pipeline: None,
+        }
+    }
+
+    pub fn operator(&self) -> TonemapOperator {
+        self.operator
+    }
+}
+
+impl RenderGraphPass for TonemapPass {
+    fn name(&self) -> &str {
+        "tonemap"
+    }
+
+    fn inputs(&self) -> &[RenderGraphTexture] {
+        &[RenderGraphTexture::MainColor]
+    }
+
+    fn outputs(&self) -> &[RenderGraphTexture] {
+        &[RenderGraphTexture::Surface]
+    }
+
+    fn execute(&mut self, context: &mut RenderGraphPassContext) {
+        let main_color_texture_view = match context.texture_view(RenderGraphTexture::MainColor) {
+            Some(view) => view,
+            None => {
+                return;
+            }
+        };
+        let surface_texture_view = context.texture_view(RenderGraphTexture::Surface).unwrap();
+        let gfx_ctx = context.gfx_ctx();
+        let surface_format = gfx_ctx.surface_config.borrow().format;
+
+        let pipeline = match &mut self.pipeline {
+            Some(pipeline) if pipeline.surface_format == surface_format => pipeline,
+            pipeline => pipeline.insert(TonemapPipeline::new(surface_format, &gfx_ctx.device)),
+        };
+
+        let params = TonemapParams {
+            exposure: context.exposure(),
+            operator_index: self.operator.shader_index(),
+            encode_srgb: if surface_format.is_srgb() { 0 } else { 1 },
+            _padding: 0,
+        };
+        gfx_ctx
+            .queue
+            .write_buffer(&pipeline.params_buffer, 0, params.as_bytes());
+
+        let bind_group = gfx_ctx.device.create_bind_group(&BindGroupDescriptor {
+            label: Some("tonemap-bind-group"),
+            layout: &pipeline.bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(main_color_texture_view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: pipeline.params_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let mut render_pass = context.frame().begin_render_pass(
+            ClearMode::Keep,
+            &[Some(RenderPassTarget {
+                view: surface_texture_view,
+                resolve_target: None,
+                writable: true,
+            })],
+            None,
+        );
+        render_pass.set_pipeline(&pipeline.pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gfx::{test_gfx_ctx, GfxContext};
+    use std::sync::mpsc;
+    use wgpu::{
+        Extent3d, ImageCopyBuffer, ImageCopyTexture, ImageDataLayout, MaintainBase, MapMode,
+        Origin3d, TextureAspect, TextureDescriptor, TextureDimension, TextureUsages,
+        TextureViewDescriptor, COPY_BYTES_PER_ROW_ALIGNMENT,
+    };
+
+    /// Tonemaps a row of HDR texels through the pass and reads the result back. The surface is
+    /// a float one, so that nothing clamps the output of the pass.
+    fn tonemap_on_gpu(
+        gfx_ctx: &GfxContext,
+        operator: TonemapOperator,
+        exposure: f32,
+        texels: &[[f32; 4]],
+    ) -> Vec<[f32; 4]> {
+        let format = TextureFormat::Rgba32Float;
+        let width = texels.len() as u32;
+        let size = Extent3d {
+            width,
+            height: 1,
+            depth_or_array_layers: 1,
+        };
+        let create_texture = |usage| {
+            gfx_ctx.device.create_texture(&TextureDescriptor {
+                label: None,
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format,
+                usage,
+                view_formats: &[],
+            })
+        };
+        let hdr_color = create_texture(TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST);
+        let surface = create_texture(TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC);
+        gfx_ctx.queue.write_texture(
+            hdr_color.as_image_copy(),
+            texels.as_bytes(),
+            ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(width * 16),
+                rows_per_image: None,
+            },
+            size,
+        );
+        gfx_ctx.surface_config.borrow_mut().format = format;
+
+        let readback = gfx_ctx.device.create_buffer(&BufferDescriptor {
+            label: None,
+            size: COPY_BYTES_PER_ROW_ALIGNMENT as u64,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let hdr_color_view = hdr_color.create_view(&TextureViewDescriptor::default());
+        let surface_view = surface.create_view(&TextureViewDescriptor::default());
+        let global_texture_set = gfx_ctx.global_texture_set.borrow();
+
+        let mut frame = gfx_ctx.begin_frame();
+        TonemapPass::new(operator).execute(&mut RenderGraphPassContext::new(
+            gfx_ctx,
+            &mut frame,
+            &surface_view,
+            Some(&hdr_color_view),
+            &global_texture_set.depth_stencil.texture_view,
+            exposure,
+        ));
+        frame.cmd_encoder_mut().copy_texture_to_buffer(
+            ImageCopyTexture {
+                texture: &surface,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            ImageCopyBuffer {
+                buffer: &readback,
+                layout: ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(COPY_BYTES_PER_ROW_ALIGNMENT),
+                    rows_per_image: None,
+                },
+            },
+            size,
+        );
+        gfx_ctx.end_frame(frame);
+
+        let (sender, receiver) = mpsc::channel();
+        let slice = readback.slice(..);
+        slice.map_async(MapMode::Read, move |result| {
+            sender.send(result).ok();
+        });
+        gfx_ctx.device.poll(MaintainBase::Wait);
+        receiver.recv().unwrap().unwrap();
+
+        let data = slice.get_mapped_range();
+        data[..(width * 16) as usize]
+            .chunks_exact(16)
+            .map(|texel| {
+                let component = |index: usize| {
+                    f32::from_le_bytes(texel[index * 4..index * 4 + 4].try_into().unwrap())
+                };
+                [component(0), component(1), component(2), component(3)]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn check_bright_input_rendered_into_unit_range() {
+        let gfx_ctx = match test_gfx_ctx() {
+            Some(gfx_ctx) => gfx_ctx,
+            None => {
+                return;
+            }
+        };
+        let texels = [
+            [4.0, 16.0, 1000.0, 1.0],
+            [1.5, 2.0, 3.0, 1.0],
+            [0.25, 0.5, 1.0, 1.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ];
+
+        for operator in [TonemapOperator::Reinhard, TonemapOperator::Aces] {
+            for exposure in [0.5, 1.0, 4.0] {
+                let mapped = tonemap_on_gpu(&gfx_ctx, operator, exposure, &texels);
+
+                for (texel, mapped) in texels.iter().zip(mapped) {
+                    // the surface is not an sRGB one, so the pass encodes the output itself
+                    let expected =
+                        operator.apply(Vec3::new(texel[0], texel[1], texel[2]), exposure);
+                    let expected = [expected.x, expected.y, expected.z].map(linear_to_srgb);
+
+                    for (c, expected) in mapped[..3].iter().zip(expected) {
+                        assert!((0.0..=1.0).contains(c), "{:?} {:?}", operator, mapped);
+                        assert!((c - expected).abs() <= 1e-3, "{:?} {:?}", operator, mapped);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn check_bright_input_mapped_into_unit_range() {
+        let hdr = Vec3::new(4.0, 16.0, 1000.0);
+
+        for operator in [TonemapOperator::Reinhard, TonemapOperator::Aces] {
+            for exposure in [0.5, 1.0, 4.0] {
+                let mapped = operator.apply(hdr, exposure);
+
+                for c in [mapped.x, mapped.y, mapped.z] {
+                    assert!((0.0..=1.0).contains(&c), "{:?} {}", operator, c);
+                    assert!(0.5 < c);
+                }
+            }
+
+            // the mapping keeps the order of the intensities
+            let mapped = operator.apply(hdr, 1.0);
+            assert!(mapped.x <= mapped.y && mapped.y <= mapped.z);
+        }
+    }
+
+    #[test]
+    fn check_exposure_scales_input() {
+        let dark = TonemapOperator::Reinhard.apply(Vec3::ONE, 0.25);
+        let bright = TonemapOperator::Reinhard.apply(Vec3::ONE, 4.0);
+
+        assert!((dark.x - 0.2).abs() <= 1e-6);
+        assert!((bright.x - 0.8).abs() <= 1e-6);
+    }
+
+    #[test]
+    fn check_linear_to_srgb() {
+        assert_eq!(linear_to_srgb(0.0), 0.0);
+        assert!((linear_to_srgb(1.0) - 1.0).abs() <= 1e-6);
+        assert!((linear_to_srgb(0.5) - 0.7353569).abs() <= 1e-5);
+    }
+}
@@ -6,27 +6,48 @@ pub mod resource;
 pub mod scene;
 
 use context::driver::Driver;
+use gfx::RenderConfig;
+use log::error;
 use looper::{
     loop_window::{LoopWindow, LoopWindowConfig},
     Looper, LooperMode, TargetFps,
 };
 use pollster::FutureExt;
+use wgpu::PresentMode;
 
 pub fn launch_core(
     window_config: LoopWindowConfig,
-    vsync: bool,
-    msaa_sample_count: u32,
+    present_mode: PresentMode,
+    render_config: RenderConfig,
     looper_mode: LooperMode,
     target_fps: TargetFps,
+    render_when_occluded: bool,
     driver: Option<Box<dyn Driver>>,
 ) {
-    let window = LoopWindow::new(window_config).unwrap();
+    let window = match LoopWindow::new(window_config) {
+        Ok(window) => window,
+        Err(err) => {
+            error!("failed to create the window: {}", err);
+            return;
+        }
+    };
     let (event_loop, window) = window.into();
 
-    let looper = Looper::new(&window, vsync, msaa_sample_count, driver)
-        .block_on()
-        .unwrap();
-    looper
-        .run(event_loop, &window, looper_mode, target_fps)
-        .unwrap();
+    let looper = match Looper::new(&window, present_mode, render_config, driver).block_on() {
+        Ok(looper) => looper,
+        Err(err) => {
+            error!("failed to create the looper: {}", err);
+            return;
+        }
+    };
+
+    if let Err(err) = looper.run(
+        event_loop,
+        &window,
+        looper_mode,
+        target_fps,
+        render_when_occluded,
+    ) {
+        error!("the looper stopped: {}", err);
+    }
 }
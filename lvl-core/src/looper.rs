@@ -1,19 +1,24 @@
 pub mod loop_window;
+mod redraw_policy;
 pub mod vsync;
 
 use crate::{
-    context::{driver::Driver, phases, Context},
-    gfx::GfxContext,
-    looper::vsync::TargetFrameInterval,
+    context::{driver::Driver, input::Input, phases, Context},
+    gfx::{
+        GfxContext, GfxContextCreationError, RenderConfig, RenderGraph, RenderGraphError,
+        RenderGraphPlan, TonemapPass,
+    },
+    looper::{redraw_policy::RedrawPolicy, vsync::TargetFrameInterval},
     perf::PerfRecorder,
     scene::Scene,
 };
+use log::warn;
 use std::{
     num::NonZeroU32,
     time::{Duration, Instant},
 };
 use thiserror::Error;
-use wgpu::MaintainBase;
+use wgpu::{MaintainBase, PresentMode};
 use winit::{
     event::{Event, StartCause, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
@@ -36,6 +41,10 @@ pub enum LooperError {
     EventLoopError(#[from] winit::error::EventLoopError),
     #[error("gfx surface error: {0}")]
     SurfaceError(#[from] wgpu::SurfaceError),
+    #[error("render graph error: {0}")]
+    RenderGraphError(#[from] crate::gfx::RenderGraphError),
+    #[error("failed to recreate the lost gfx device: {0}")]
+    DeviceRecreationError(#[from] GfxContextCreationError),
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -65,6 +74,7 @@ impl Default for TargetFps {
 
 pub struct Looper<'window> {
     ctx: Context<'window>,
+    render_config: RenderConfig,
     driver: Option<Box<dyn Driver>>,
 }
 
@@ -79,22 +89,29 @@ pub struct LooperConfig {
 impl<'window> Looper<'window> {
     pub async fn new(
         window: &'window Window,
-        vsync: bool,
-        msaa_sample_count: u32,
+        present_mode: PresentMode,
+        render_config: RenderConfig,
         driver: Option<Box<dyn Driver>>,
     ) -> Result<Self, LooperCreationError> {
         let physical_size = window.inner_size();
-        let gfx_ctx = GfxContext::new(window, vsync, msaa_sample_count).await?;
+        let gfx_ctx = GfxContext::new(window, present_mode, render_config).await?;
         let ctx = Context::new(gfx_ctx, physical_size);
-        Ok(Self { ctx, driver })
+        Ok(Self {
+            ctx,
+            render_config,
+            driver,
+        })
     }
 
+    /// Runs the loop until the window is closed. Redraws stop while the window is occluded,
+    /// unless `render_when_occluded` is set.
     pub fn run(
         mut self,
         event_loop: EventLoop<()>,
         window: &'window Window,
         looper_mode: LooperMode,
         target_fps: TargetFps,
+        render_when_occluded: bool,
     ) -> Result<(), LooperError> {
         event_loop.set_control_flow(match looper_mode {
             LooperMode::Wait => ControlFlow::Wait,
@@ -103,8 +120,7 @@ impl<'window> Looper<'window> {
         window.set_visible(true);
 
         let window_id = window.id();
-        let mut window_occluded = false;
-        let mut window_too_small = false;
+        let mut redraw_policy = RedrawPolicy::new(render_when_occluded);
         let mut target_frame_interval = TargetFrameInterval::new(
             match target_fps {
                 TargetFps::VSync => None,
@@ -122,172 +138,314 @@ impl<'window> Looper<'window> {
             .as_mut()
             .map(|driver| driver.on_init(&self.ctx, window, &mut scene));
 
-        event_loop.run(|event, target| match event {
-            Event::NewEvents(cause) if cause == StartCause::Poll => {
-                if !window_occluded && !window_too_small {
-                    window.request_redraw();
+        let render_config = self.render_config;
+        let (mut render_graph, mut render_graph_plan) =
+            configure_render_graph(&self.ctx, &mut self.driver, render_config)?;
+
+        // the event loop cannot return errors, so they are kept until it exits
+        let mut fatal_error = None;
+
+        event_loop.run(|event, target| {
+            if let Event::WindowEvent {
+                event: window_event,
+                window_id: id,
+            } = &event
+            {
+                if *id == window_id {
+                    let is_consumed = match self.driver.as_mut() {
+                        Some(driver) => driver.on_window_event(&self.ctx, window, window_event),
+                        None => false,
+                    };
+
+                    if dispatch_input_event(&mut self.ctx.input_mut(), window_event, is_consumed) {
+                        return;
+                    }
                 }
             }
-            Event::WindowEvent {
-                event: WindowEvent::RedrawRequested,
-                window_id: id,
-            } if id == window_id => {
-                let now = Instant::now();
 
-                #[cfg(target_os = "macos")]
-                if looper_mode == LooperMode::Poll
-                    && now - last_frame_time < target_frame_interval.interval()
-                {
+            match event {
+                Event::NewEvents(cause) if cause == StartCause::Poll => {
+                    if redraw_policy.should_redraw() {
+                        window.request_redraw();
+                    }
+                }
+                Event::WindowEvent {
+                    event: WindowEvent::RedrawRequested,
+                    window_id: id,
+                } if id == window_id => {
+                    let now = Instant::now();
+
+                    #[cfg(target_os = "macos")]
+                    if looper_mode == LooperMode::Poll
+                        && now - last_frame_time < target_frame_interval.interval()
+                    {
+                        return;
+                    }
+
+                    last_frame_time = now;
+
+                    if self.ctx.gfx_ctx().is_device_lost() {
+                        warn!("the gfx device is lost; recreating it");
+
+                        match recover_lost_device(
+                            window,
+                            &self.ctx,
+                            &mut scene,
+                            &mut self.driver,
+                            render_config,
+                        ) {
+                            Ok((recreated_graph, recreated_plan)) => {
+                                render_graph = recreated_graph;
+                                render_graph_plan = recreated_plan;
+                            }
+                            Err(err) => {
+                                fatal_error = Some(err);
+                                target.exit();
+                            }
+                        }
+
+                        return;
+                    }
+
+                    self.ctx.time_mut().update();
+                    scene.begin_frame();
+
+                    perf_recorder.frame_begin();
+
+                    // {
+                    //     let mut input_mgr = self.ctx.input_mgr_mut();
+                    //     input_mgr.poll();
+                    // }
+
+                    phases::update::update(&window, &self.ctx, &mut scene, &mut self.driver);
+                    perf_recorder.frame_update_end();
+
+                    phases::late_update::late_update(
+                        &window,
+                        &self.ctx,
+                        &mut scene,
+                        &mut self.driver,
+                    );
+                    perf_recorder.frame_late_update_end();
+
+                    scene.prepare_render(&mut self.ctx.screen_size_mut());
+                    perf_recorder.frame_prepare_render_end();
+
+                    if let Err(err) = phases::render::render(
+                        &window,
+                        &self.ctx,
+                        &mut scene,
+                        &mut render_graph,
+                        &render_graph_plan,
+                        &mut self.driver,
+                    ) {
+                        fatal_error = Some(LooperError::SurfaceError(err));
+                        target.exit();
+                        return;
+                    }
+                    perf_recorder.frame_render_end();
+
+                    if let Some(gpu_frame_time) = self.ctx.gfx_ctx().gpu_frame_time() {
+                        perf_recorder.frame_gpu_render(gpu_frame_time);
+                    }
+
+                    if Duration::from_secs(1) <= now - last_perf_report_time {
+                        println!("{}", perf_recorder.report());
+                        last_perf_report_time = now;
+                    }
+
+                    self.ctx.input_mut().reset_current_frame_state();
+
                     return;
                 }
+                Event::WindowEvent {
+                    event: WindowEvent::Occluded(occluded),
+                    window_id: id,
+                } if id == window_id => {
+                    redraw_policy.set_occluded(occluded);
 
-                last_frame_time = now;
-                self.ctx.time_mut().update();
+                    if looper_mode == LooperMode::Wait && redraw_policy.should_redraw() {
+                        window.request_redraw();
+                    }
 
-                perf_recorder.frame_begin();
+                    return;
+                }
+                Event::WindowEvent {
+                    event: WindowEvent::Resized(inner_size),
+                    window_id: id,
+                } if id == window_id => {
+                    if inner_size.width == 0 || inner_size.height == 0 {
+                        redraw_policy.set_too_small(true);
+                        return;
+                    } else {
+                        redraw_policy.set_too_small(false);
+                    }
+
+                    self.ctx.update_screen_size(inner_size);
+                    self.ctx.gfx_ctx().device.poll(MaintainBase::Wait);
+                    self.ctx.gfx_ctx().resize(inner_size);
+
+                    if looper_mode == LooperMode::Wait && redraw_policy.should_redraw() {
+                        window.request_redraw();
+                    }
 
-                // {
-                //     let mut input_mgr = self.ctx.input_mgr_mut();
-                //     input_mgr.poll();
-                // }
+                    return;
+                }
+                Event::WindowEvent {
+                    event: WindowEvent::ScaleFactorChanged { .. },
+                    window_id: id,
+                } if id == window_id => {
+                    target_frame_interval.update_window(window);
 
-                phases::update::update(&window, &self.ctx, &mut scene, &mut self.driver);
-                perf_recorder.frame_update_end();
+                    let inner_size = window.inner_size();
 
-                phases::late_update::late_update(&window, &self.ctx, &mut scene, &mut self.driver);
-                perf_recorder.frame_late_update_end();
+                    if inner_size.width == 0 || inner_size.height == 0 {
+                        redraw_policy.set_too_small(true);
+                        return;
+                    } else {
+                        redraw_policy.set_too_small(false);
+                    }
 
-                scene.prepare_render(&mut self.ctx.screen_size_mut());
-                perf_recorder.frame_prepare_render_end();
+                    self.ctx.update_screen_size(inner_size);
+                    self.ctx.gfx_ctx().resize(inner_size);
 
-                phases::render::render(&window, &self.ctx, &mut scene, &mut self.driver);
-                perf_recorder.frame_render_end();
+                    if looper_mode == LooperMode::Wait && redraw_policy.should_redraw() {
+                        window.request_redraw();
+                    }
 
-                if Duration::from_secs(1) <= now - last_perf_report_time {
-                    println!("{}", perf_recorder.report());
-                    last_perf_report_time = now;
+                    return;
                 }
+                Event::WindowEvent {
+                    event: WindowEvent::CloseRequested,
+                    window_id: id,
+                } if id == window_id => {
+                    target.exit();
 
-                self.ctx.input_mut().reset_current_frame_state();
+                    if let Some(driver) = self.driver.as_mut() {
+                        driver.on_finish(&self.ctx, window, &mut scene);
+                    }
 
-                return;
+                    return;
+                }
+                _ => return,
             }
-            Event::WindowEvent {
-                event: WindowEvent::Occluded(occluded),
-                window_id: id,
-            } if id == window_id => {
-                window_occluded = occluded;
+        })?;
 
-                if looper_mode == LooperMode::Wait && !occluded && !window_too_small {
-                    window.request_redraw();
-                }
+        match fatal_error {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+}
 
-                return;
-            }
-            Event::WindowEvent {
-                event: WindowEvent::KeyboardInput { event, .. },
-                window_id: id,
-            } if id == window_id => {
-                self.ctx.input_mut().handle_key_event(&event);
+/// Builds the render graph with the passes of the driver, enabling the offscreen main color if
+/// a pass reads it.
+fn configure_render_graph(
+    ctx: &Context,
+    driver: &mut Option<Box<dyn Driver>>,
+    render_config: RenderConfig,
+) -> Result<(RenderGraph, RenderGraphPlan), RenderGraphError> {
+    let mut render_graph = RenderGraph::new();
+
+    if let Some(driver) = driver.as_mut() {
+        driver.on_configure_render(ctx, &mut render_graph);
+    }
 
-                return;
-            }
-            Event::WindowEvent {
-                event: WindowEvent::CursorEntered { .. },
-                window_id: id,
-            } if id == window_id => {
-                // TODO: Handle cursor entered event here.
+    // the HDR main color cannot be presented as is
+    if render_config.hdr && !render_graph.is_surface_written_by_pass() {
+        render_graph.add_pass(TonemapPass::default());
+    }
 
-                return;
-            }
-            Event::WindowEvent {
-                event: WindowEvent::CursorLeft { .. },
-                window_id: id,
-            } if id == window_id => {
-                // TODO: Handle cursor left event here.
-                return;
-            }
-            Event::WindowEvent {
-                event: event @ WindowEvent::CursorMoved { .. },
-                window_id: id,
-            } if id == window_id => {
-                // TODO: Handle cursor moved event here.
-                return;
-            }
-            Event::WindowEvent {
-                event: event @ WindowEvent::MouseInput { .. },
-                window_id: id,
-            } if id == window_id => {
-                // TODO: Handle mouse input event here.
-                return;
-            }
-            Event::WindowEvent {
-                event: event @ WindowEvent::MouseWheel { .. },
-                window_id: id,
-            } if id == window_id => {
-                // TODO: Handle mouse wheel event here.
-                return;
-            }
-            Event::WindowEvent {
-                event: WindowEvent::Resized(inner_size),
-                window_id: id,
-            } if id == window_id => {
-                if inner_size.width == 0 || inner_size.height == 0 {
-                    window_too_small = true;
-                    return;
-                } else {
-                    window_too_small = false;
-                }
+    let render_graph_plan = render_graph.plan()?;
 
-                self.ctx.update_screen_size(inner_size);
-                self.ctx.gfx_ctx().device.poll(MaintainBase::Wait);
-                self.ctx.gfx_ctx().resize(inner_size);
+    if render_graph_plan.is_main_color_offscreen() {
+        ctx.gfx_ctx().enable_main_color_texture();
+    }
 
-                if looper_mode == LooperMode::Wait && !window_occluded {
-                    window.request_redraw();
-                }
+    Ok((render_graph, render_graph_plan))
+}
 
-                return;
-            }
-            Event::WindowEvent {
-                event: WindowEvent::ScaleFactorChanged { .. },
-                window_id: id,
-            } if id == window_id => {
-                target_frame_interval.update_window(window);
+/// Makes the gfx context and the render graph again after the device is lost, and lets the
+/// driver make its objects again. The frame is skipped.
+fn recover_lost_device(
+    window: &Window,
+    ctx: &Context,
+    scene: &mut Scene,
+    driver: &mut Option<Box<dyn Driver>>,
+    render_config: RenderConfig,
+) -> Result<(RenderGraph, RenderGraphPlan), LooperError> {
+    ctx.recreate_gfx_ctx()?;
+
+    // the passes keep pipelines made from the lost device
+    let render_graph = configure_render_graph(ctx, driver, render_config)?;
+
+    if let Some(driver) = driver.as_mut() {
+        driver.on_device_recreated(ctx, window, scene);
+    }
 
-                let inner_size = window.inner_size();
+    Ok(render_graph)
+}
 
-                if inner_size.width == 0 || inner_size.height == 0 {
-                    window_too_small = true;
-                    return;
-                } else {
-                    window_too_small = false;
-                }
+/// Hands the input event to the input unless the driver consumed it. Returns `false` for the
+/// other events, which are left to the event loop.
+fn dispatch_input_event(input: &mut Input, event: &WindowEvent, is_consumed: bool) -> bool {
+    if !is_input_event(event) {
+        return false;
+    }
 
-                self.ctx.update_screen_size(inner_size);
-                self.ctx.gfx_ctx().resize(inner_size);
+    if is_consumed {
+        return true;
+    }
 
-                if looper_mode == LooperMode::Wait && !window_occluded {
-                    window.request_redraw();
-                }
+    match event {
+        WindowEvent::KeyboardInput { event, .. } => {
+            input.handle_key_event(event);
+        }
+        WindowEvent::Ime(ime) => {
+            input.handle_ime(ime);
+        }
+        // TODO: Handle the modifiers, cursor and mouse events here.
+        _ => {}
+    }
 
-                return;
-            }
-            Event::WindowEvent {
-                event: WindowEvent::CloseRequested,
-                window_id: id,
-            } if id == window_id => {
-                target.exit();
+    true
+}
 
-                if let Some(driver) = self.driver.as_mut() {
-                    driver.on_finish(&self.ctx, window, &mut scene);
-                }
+fn is_input_event(event: &WindowEvent) -> bool {
+    matches!(
+        event,
+        WindowEvent::KeyboardInput { .. }
+            | WindowEvent::ModifiersChanged(_)
+            | WindowEvent::Ime(_)
+            | WindowEvent::CursorEntered { .. }
+            | WindowEvent::CursorLeft { .. }
+            | WindowEvent::CursorMoved { .. }
+            | WindowEvent::MouseInput { .. }
+            | WindowEvent::MouseWheel { .. }
+    )
+}
 
-                return;
-            }
-            _ => return,
-        })?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use winit::event::Ime;
+
+    #[test]
+    fn check_consumed_input_event_is_not_dispatched() {
+        let event = WindowEvent::Ime(Ime::Commit("a".to_owned()));
+        let mut input = Input::new();
+
+        assert!(dispatch_input_event(&mut input, &event, true));
+        assert_eq!(input.text_input_this_frame(), "");
+
+        assert!(dispatch_input_event(&mut input, &event, false));
+        assert_eq!(input.text_input_this_frame(), "a");
 
-        Ok(())
+        // the other events are left to the event loop, consumed or not
+        assert!(!dispatch_input_event(
+            &mut input,
+            &WindowEvent::Focused(true),
+            true
+        ));
     }
 }
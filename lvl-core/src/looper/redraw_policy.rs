@@ -0,0 +1,55 @@
+/// Decides whether the looper requests a redraw, from what it knows of the window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct RedrawPolicy {
+    render_when_occluded: bool,
+    is_occluded: bool,
+    is_too_small: bool,
+}
+
+impl RedrawPolicy {
+    /// Occluded windows are not redrawn to save power, unless `render_when_occluded` is set,
+    /// e.g. for recording or streaming tools that capture the window while it is hidden.
+    pub fn new(render_when_occluded: bool) -> Self {
+        Self {
+            render_when_occluded,
+            is_occluded: false,
+            is_too_small: false,
+        }
+    }
+
+    pub fn set_occluded(&mut self, is_occluded: bool) {
+        self.is_occluded = is_occluded;
+    }
+
+    pub fn set_too_small(&mut self, is_too_small: bool) {
+        self.is_too_small = is_too_small;
+    }
+
+    /// Returns `true` if a redraw should be requested. A zero-sized window is never redrawn, as
+    /// there is no surface to present to.
+    pub fn should_redraw(&self) -> bool {
+        !self.is_too_small && (!self.is_occluded || self.render_when_occluded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_redraw_while_occluded() {
+        let mut power_saving = RedrawPolicy::new(false);
+        let mut recording = RedrawPolicy::new(true);
+
+        power_saving.set_occluded(true);
+        recording.set_occluded(true);
+        assert!(!power_saving.should_redraw());
+        assert!(recording.should_redraw());
+
+        recording.set_too_small(true);
+        assert!(!recording.should_redraw());
+
+        power_saving.set_occluded(false);
+        assert!(power_saving.should_redraw());
+    }
+}
@@ -1,4 +1,8 @@
-use std::{collections::VecDeque, fmt::Display, time::Instant};
+use std::{
+    collections::VecDeque,
+    fmt::Display,
+    time::{Duration, Instant},
+};
 
 #[derive(Debug, Clone)]
 pub struct PerfRecorder {
@@ -8,6 +12,7 @@ pub struct PerfRecorder {
     late_update_times: VecDeque<f32>,
     prepare_render_times: VecDeque<f32>,
     render_times: VecDeque<f32>,
+    gpu_render_times: VecDeque<f32>,
 }
 
 impl PerfRecorder {
@@ -21,6 +26,7 @@ impl PerfRecorder {
             late_update_times: VecDeque::with_capacity(Self::MAX_FRAMES),
             prepare_render_times: VecDeque::with_capacity(Self::MAX_FRAMES),
             render_times: VecDeque::with_capacity(Self::MAX_FRAMES),
+            gpu_render_times: VecDeque::with_capacity(Self::MAX_FRAMES),
         }
     }
 
@@ -80,6 +86,18 @@ impl PerfRecorder {
         self.current = now;
     }
 
+    /// Records the time the GPU took to execute the frame; see [`GfxContext::gpu_frame_time`].
+    ///
+    /// [`GfxContext::gpu_frame_time`]: crate::gfx::GfxContext::gpu_frame_time
+    pub fn frame_gpu_render(&mut self, gpu_render_time: Duration) {
+        if Self::MAX_FRAMES <= self.gpu_render_times.len() {
+            self.gpu_render_times.pop_front();
+        }
+
+        self.gpu_render_times
+            .push_back(gpu_render_time.as_secs_f32());
+    }
+
     pub fn report(&self) -> PerfReport {
         let update_avg = self.update_times.iter().sum::<f32>() / self.update_times.len() as f32;
         let late_update_avg =
@@ -87,6 +105,11 @@ impl PerfRecorder {
         let prepare_render_avg =
             self.prepare_render_times.iter().sum::<f32>() / self.prepare_render_times.len() as f32;
         let render_avg = self.render_times.iter().sum::<f32>() / self.render_times.len() as f32;
+        let gpu_render_avg = if self.gpu_render_times.is_empty() {
+            None
+        } else {
+            Some(self.gpu_render_times.iter().sum::<f32>() / self.gpu_render_times.len() as f32)
+        };
 
         PerfReport {
             name: &self.name,
@@ -94,6 +117,7 @@ impl PerfRecorder {
             late_update_avg,
             prepare_render_avg,
             render_avg,
+            gpu_render_avg,
         }
     }
 }
@@ -105,6 +129,8 @@ pub struct PerfReport<'a> {
     pub late_update_avg: f32,
     pub prepare_render_avg: f32,
     pub render_avg: f32,
+    /// `None` if the GPU time is not measured.
+    pub gpu_render_avg: Option<f32>,
 }
 
 impl<'a> Display for PerfReport<'a> {
@@ -118,6 +144,39 @@ impl<'a> Display for PerfReport<'a> {
             self.prepare_render_avg * 1000.0,
             self.render_avg * 1000.0,
             (self.update_avg + self.late_update_avg + self.prepare_render_avg + self.render_avg) * 1000.0
-        )
+        )?;
+
+        if let Some(gpu_render_avg) = self.gpu_render_avg {
+            write!(f, ", gpu_render_avg: {:.2}ms", gpu_render_avg * 1000.0)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_gpu_render_report() {
+        let mut recorder = PerfRecorder::new("test");
+        recorder.frame_begin();
+        recorder.frame_update_end();
+        recorder.frame_late_update_end();
+        recorder.frame_prepare_render_end();
+        recorder.frame_render_end();
+        assert_eq!(recorder.report().gpu_render_avg, None);
+        assert!(!recorder.report().to_string().contains("gpu_render_avg"));
+
+        recorder.frame_gpu_render(Duration::from_millis(2));
+        recorder.frame_gpu_render(Duration::from_millis(4));
+
+        let gpu_render_avg = recorder.report().gpu_render_avg.unwrap();
+        assert!((gpu_render_avg - 0.003).abs() <= 1e-6);
+        assert!(recorder
+            .report()
+            .to_string()
+            .contains("gpu_render_avg: 3.00ms"));
     }
 }
@@ -1,22 +1,18 @@
-use lvl_resource::{ResourceFile, ResourceFileVersion};
+mod resource_registry;
+
+pub use resource_registry::*;
+
+use lvl_resource::{ResourceFile, ResourceFileCodecError};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum ResourceFileLoadError {
     #[error("failed to decode: {0}")]
-    BincodeError(#[from] bincode::Error),
-    #[error("unsupported version: {0}")]
-    UnsupportedVersion(ResourceFileVersion),
+    DecodeError(#[from] ResourceFileCodecError),
 }
 
+/// Loads a resource file of any supported version; compressed files are decompressed. Files
+/// of older versions fail with [`ResourceFileCodecError::UnsupportedVersion`].
 pub fn load_resource_file(bytes: &[u8]) -> Result<ResourceFile, ResourceFileLoadError> {
-    let resource_file = bincode::deserialize::<ResourceFile>(bytes)?;
-
-    if resource_file.version() != ResourceFileVersion::V1 {
-        return Err(ResourceFileLoadError::UnsupportedVersion(
-            resource_file.version(),
-        ));
-    }
-
-    Ok(resource_file)
+    Ok(ResourceFile::read(bytes)?)
 }
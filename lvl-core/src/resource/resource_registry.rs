@@ -0,0 +1,510 @@
+use crate::gfx::{
+    elements::{Material, Shader, Texture},
+    GfxContext,
+};
+use lvl_resource::{
+    is_source_changed, MaterialPropertyValue, MaterialSource, ResourceFile, ResourceKind,
+    ResourceLoadOrderError, ShaderSource, TextureKind, TextureSource,
+};
+use std::{collections::HashMap, sync::Arc};
+use wgpu::TextureView;
+
+/// Loaded objects keyed by their resource name.
+#[derive(Debug)]
+pub struct ResourceCache<T> {
+    entries: HashMap<String, Arc<T>>,
+}
+
+impl<T> ResourceCache<T> {
+    pub fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.entries.contains_key(name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<T>> {
+        self.entries.get(name).cloned()
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(|name| name.as_str())
+    }
+
+    /// Returns the cached object, or caches the one made by `load` if there is none yet.
+    /// Nothing is cached if `load` returns `None`.
+    pub fn get_or_load(&mut self, name: &str, load: impl FnOnce() -> Option<T>) -> Option<Arc<T>> {
+        if let Some(entry) = self.entries.get(name) {
+            return Some(entry.clone());
+        }
+
+        let entry = Arc::new(load()?);
+        self.entries.insert(name.to_owned(), entry.clone());
+        Some(entry)
+    }
+
+    /// Caches the object in place of the one under the same name, which is returned. Holders of
+    /// the previous object keep it until they look the name up again.
+    pub fn replace(&mut self, name: &str, entry: T) -> Option<Arc<T>> {
+        self.entries.insert(name.to_owned(), Arc::new(entry))
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Arc<T>> {
+        self.entries.remove(name)
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+impl<T> Default for ResourceCache<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A [`ResourceCache`] whose objects are made from their sources on first access.
+#[derive(Debug)]
+pub struct LazyResourceCache<S, T> {
+    sources: HashMap<String, S>,
+    loaded: ResourceCache<T>,
+}
+
+impl<S, T> LazyResourceCache<S, T> {
+    pub fn new() -> Self {
+        Self {
+            sources: HashMap::new(),
+            loaded: ResourceCache::new(),
+        }
+    }
+
+    /// Registers the source of an object without loading it. A source already registered
+    /// under the same name is kept.
+    pub fn insert_source(&mut self, name: &str, source: S) {
+        self.sources.entry(name.to_owned()).or_insert(source);
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.sources.contains_key(name)
+    }
+
+    pub fn is_loaded(&self, name: &str) -> bool {
+        self.loaded.contains(name)
+    }
+
+    pub fn loaded(&self) -> &ResourceCache<T> {
+        &self.loaded
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.sources.keys().map(|name| name.as_str())
+    }
+
+    /// Returns the loaded object, loading it from its source if this is the first access.
+    pub fn get_or_load(
+        &mut self,
+        name: &str,
+        load: impl FnOnce(&S) -> Option<T>,
+    ) -> Option<Arc<T>> {
+        let source = self.sources.get(name)?;
+        self.loaded.get_or_load(name, || load(source))
+    }
+
+    /// Drops the loaded object, keeping its source so that it can be loaded again.
+    pub fn unload(&mut self, name: &str) -> Option<Arc<T>> {
+        self.loaded.remove(name)
+    }
+
+    /// Drops all the loaded objects, keeping their sources. Returns the names of the objects
+    /// that were loaded.
+    pub fn unload_all(&mut self) -> Vec<String> {
+        let names = Vec::from_iter(self.loaded.names().map(|name| name.to_owned()));
+        self.loaded.clear();
+        names
+    }
+}
+
+impl<S, T> LazyResourceCache<S, T>
+where
+    S: PartialEq,
+{
+    /// Registers the source in place of the one under the same name and drops the object loaded
+    /// from the previous source, so that the next access loads the new one. Returns `false`
+    /// without changing anything if the source is the same.
+    pub fn replace_source(&mut self, name: &str, source: S) -> bool {
+        if self.sources.get(name) == Some(&source) {
+            return false;
+        }
+
+        self.sources.insert(name.to_owned(), source);
+        self.loaded.remove(name);
+        true
+    }
+}
+
+impl<S, T> Default for LazyResourceCache<S, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Owns the GPU objects loaded from resource files, so that they can be shared by name.
+/// Materials resolve their shaders and textures from the registry, so a texture used by
+/// many materials is uploaded once. Materials are made, and textures uploaded, on their first
+/// access.
+#[derive(Debug, Default)]
+pub struct ResourceRegistry {
+    textures: LazyResourceCache<TextureSource, TextureView>,
+    shaders: ResourceCache<Shader>,
+    shader_sources: HashMap<String, ShaderSource>,
+    materials: LazyResourceCache<MaterialSource, Material>,
+}
+
+impl ResourceRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the texture, uploading it to the GPU if this is the first access.
+    pub fn get_texture(&mut self, name: &str, gfx_ctx: &GfxContext) -> Option<Arc<TextureView>> {
+        self.textures
+            .get_or_load(name, |source| load_texture(source, gfx_ctx))
+    }
+
+    /// Uploads the given textures now instead of on their first access.
+    pub fn preload_textures<'a>(
+        &mut self,
+        names: impl IntoIterator<Item = &'a str>,
+        gfx_ctx: &GfxContext,
+    ) {
+        for name in names {
+            self.get_texture(name, gfx_ctx);
+        }
+    }
+
+    pub fn get_shader(&self, name: &str) -> Option<Arc<Shader>> {
+        self.shaders.get(name)
+    }
+
+    /// Returns the shared material, making it and uploading its textures if this is the first
+    /// access. Returns `None` if its shader is not registered. Use [`Material::duplicate`] to
+    /// change properties per instance.
+    pub fn get_material(&mut self, name: &str, gfx_ctx: &GfxContext) -> Option<Arc<Material>> {
+        let Self {
+            textures,
+            shaders,
+            shader_sources,
+            materials,
+        } = self;
+
+        materials.get_or_load(name, |source| {
+            load_material(shaders, shader_sources, textures, source, gfx_ctx)
+        })
+    }
+
+    pub fn textures(&self) -> &LazyResourceCache<TextureSource, TextureView> {
+        &self.textures
+    }
+
+    pub fn shaders(&self) -> &ResourceCache<Shader> {
+        &self.shaders
+    }
+
+    pub fn materials(&self) -> &LazyResourceCache<MaterialSource, Material> {
+        &self.materials
+    }
+
+    /// Loads the shaders of the file and registers its textures and materials, which are made
+    /// when [`Self::get_material`] or [`Self::get_texture`] accesses them first, so that no
+    /// texture is uploaded before it is used. Resources already registered under the same name
+    /// are kept. Materials can refer to the shaders and textures of the files loaded before.
+    /// Nothing is loaded if the resources depend on each other in a cycle.
+    pub fn load(
+        &mut self,
+        file: &ResourceFile,
+        gfx_ctx: &GfxContext,
+    ) -> Result<(), ResourceLoadOrderError> {
+        for name in file.load_order()? {
+            let resource = match file.find_by_name(name) {
+                Some(resource) => resource,
+                None => {
+                    continue;
+                }
+            };
+
+            match &resource.kind {
+                ResourceKind::Texture(source) => {
+                    self.textures.insert_source(name, source.clone());
+                }
+                ResourceKind::Shader(source) => {
+                    self.shaders
+                        .get_or_load(name, || Some(Shader::load_from_source(source, gfx_ctx)));
+                    self.shader_sources
+                        .entry(name.to_owned())
+                        .or_insert_with(|| source.clone());
+                }
+                ResourceKind::Material(source) => {
+                    self.materials.insert_source(name, source.clone());
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Replaces the textures, shaders and materials that differ from the ones registered under
+    /// the same names with the ones of the file, e.g. after the file has been compiled again.
+    /// Materials using a replaced shader or texture are made again as well, and resources new
+    /// to the registry are loaded as by [`Self::load`]. Replaced textures and materials are made
+    /// again on their next access. Returns the names of the replaced and newly loaded resources,
+    /// in load order.
+    ///
+    /// Objects taken from the registry before are not updated; look them up again by name.
+    pub fn reload(
+        &mut self,
+        file: &ResourceFile,
+        gfx_ctx: &GfxContext,
+    ) -> Result<Vec<String>, ResourceLoadOrderError> {
+        let mut reloaded = Vec::new();
+
+        for name in file.load_order()? {
+            let resource = match file.find_by_name(name) {
+                Some(resource) => resource,
+                None => {
+                    continue;
+                }
+            };
+
+            match &resource.kind {
+                ResourceKind::Texture(source) => {
+                    if !self.textures.replace_source(name, source.clone()) {
+                        continue;
+                    }
+
+                    reloaded.push(name.to_owned());
+                }
+                ResourceKind::Shader(source) => {
+                    let is_changed = match self.shader_sources.get(name) {
+                        Some(previous) => is_source_changed(previous, source),
+                        None => true,
+                    };
+
+                    if !is_changed {
+                        continue;
+                    }
+
+                    self.shaders
+                        .replace(name, Shader::load_from_source(source, gfx_ctx));
+                    self.shader_sources.insert(name.to_owned(), source.clone());
+                    reloaded.push(name.to_owned());
+                }
+                ResourceKind::Material(source) => {
+                    let is_dependency_reloaded = material_dependencies(source)
+                        .any(|dependency| reloaded.iter().any(|name| name == dependency));
+
+                    if !self.materials.replace_source(name, source.clone())
+                        && !is_dependency_reloaded
+                    {
+                        continue;
+                    }
+
+                    self.materials.unload(name);
+                    reloaded.push(name.to_owned());
+                }
+                _ => {}
+            }
+        }
+
+        Ok(reloaded)
+    }
+
+    /// Uploads every loaded resource again from its source, e.g. to a new device after the
+    /// previous one was lost. Shaders are made again at once, and textures and materials on
+    /// their next access. Returns the names of the resources whose objects have been replaced,
+    /// in name order.
+    ///
+    /// Objects taken from the registry before are not updated; look them up again by name.
+    pub fn reupload(&mut self, gfx_ctx: &GfxContext) -> Vec<String> {
+        let mut reuploaded = self.textures.unload_all();
+        reuploaded.extend(self.materials.unload_all());
+
+        for (name, source) in &self.shader_sources {
+            self.shaders
+                .replace(name, Shader::load_from_source(source, gfx_ctx));
+            reuploaded.push(name.clone());
+        }
+
+        reuploaded.sort_unstable();
+        reuploaded
+    }
+}
+
+/// Makes the material with the shader and the textures of the registry. Returns `None` if its
+/// shader is not registered.
+fn load_material(
+    shaders: &ResourceCache<Shader>,
+    shader_sources: &HashMap<String, ShaderSource>,
+    textures: &mut LazyResourceCache<TextureSource, TextureView>,
+    source: &MaterialSource,
+    gfx_ctx: &GfxContext,
+) -> Option<Material> {
+    let shader_loader = |name: &str| -> Option<(Arc<Shader>, &ShaderSource)> {
+        Some((shaders.get(name)?, shader_sources.get(name)?))
+    };
+
+    shader_loader(source.shader_name())?;
+
+    Some(Material::load_from_source(
+        shader_loader,
+        |name| textures.get_or_load(name, |source| load_texture(source, gfx_ctx)),
+        source,
+        gfx_ctx,
+    ))
+}
+
+/// Returns the names of the shader and the textures the material refers to.
+fn material_dependencies(source: &MaterialSource) -> impl Iterator<Item = &str> {
+    let texture_names = source
+        .properties()
+        .values()
+        .filter_map(|property| match &property.value {
+            MaterialPropertyValue::Texture { texture_name } => Some(texture_name.as_str()),
+            _ => None,
+        });
+
+    std::iter::once(source.shader_name()).chain(texture_names)
+}
+
+fn load_texture(source: &TextureSource, gfx_ctx: &GfxContext) -> Option<TextureView> {
+    match source.kind() {
+        TextureKind::Single(element) => {
+            let texture = Texture::load_from_source(element, gfx_ctx);
+            Some(texture.handle().create_view(&Default::default()))
+        }
+        TextureKind::Cubemap { .. } => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn check_loaded_once_and_shared() {
+        let mut textures = ResourceCache::<String>::new();
+        let load_count = Cell::new(0);
+        let load = || {
+            load_count.set(load_count.get() + 1);
+            Some("texels".to_owned())
+        };
+
+        // two materials referring to the same texture
+        let first = textures.get_or_load("toon01.bmp", load).unwrap();
+        let second = textures.get_or_load("toon01.bmp", load).unwrap();
+
+        assert_eq!(load_count.get(), 1);
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(Arc::ptr_eq(&textures.get("toon01.bmp").unwrap(), &first));
+        assert_eq!(textures.names().collect::<Vec<_>>(), ["toon01.bmp"]);
+    }
+
+    #[test]
+    fn check_failed_load_not_cached() {
+        let mut shaders = ResourceCache::<String>::new();
+
+        assert_eq!(shaders.get_or_load("standard", || None), None);
+        assert!(!shaders.contains("standard"));
+        assert!(shaders.is_empty());
+
+        assert!(shaders
+            .get_or_load("standard", || Some("module".to_owned()))
+            .is_some());
+        assert_eq!(shaders.len(), 1);
+        assert!(shaders.remove("standard").is_some());
+        assert_eq!(shaders.get("standard"), None);
+    }
+
+    #[test]
+    fn check_lazy_load_on_first_access() {
+        let mut textures = LazyResourceCache::<&str, String>::new();
+        let upload_count = Cell::new(0);
+        let upload = |source: &&str| {
+            upload_count.set(upload_count.get() + 1);
+            Some(source.to_string())
+        };
+
+        textures.insert_source("toon01.bmp", "texels");
+        assert!(textures.contains("toon01.bmp"));
+        assert!(!textures.is_loaded("toon01.bmp"));
+        assert_eq!(upload_count.get(), 0);
+
+        let first = textures.get_or_load("toon01.bmp", upload).unwrap();
+        assert!(textures.is_loaded("toon01.bmp"));
+        assert_eq!(upload_count.get(), 1);
+
+        let second = textures.get_or_load("toon01.bmp", upload).unwrap();
+        assert_eq!(upload_count.get(), 1);
+        assert!(Arc::ptr_eq(&first, &second));
+
+        assert_eq!(textures.get_or_load("missing.bmp", upload), None);
+        assert_eq!(upload_count.get(), 1);
+
+        textures.unload("toon01.bmp");
+        assert!(!textures.is_loaded("toon01.bmp"));
+        assert!(textures.get_or_load("toon01.bmp", upload).is_some());
+        assert_eq!(upload_count.get(), 2);
+    }
+
+    #[test]
+    fn check_unload_all() {
+        let mut textures = LazyResourceCache::<&str, String>::new();
+        let upload = |source: &&str| Some(source.to_string());
+
+        textures.insert_source("toon01.bmp", "texels");
+        textures.insert_source("toon02.bmp", "texels");
+        textures.get_or_load("toon01.bmp", upload).unwrap();
+
+        assert_eq!(textures.unload_all(), ["toon01.bmp"]);
+        assert!(!textures.is_loaded("toon01.bmp"));
+        assert!(textures.contains("toon01.bmp"));
+        assert!(textures.get_or_load("toon02.bmp", upload).is_some());
+    }
+
+    #[test]
+    fn check_replace_source() {
+        let mut textures = LazyResourceCache::<&str, String>::new();
+        let upload = |source: &&str| Some(source.to_string());
+
+        textures.insert_source("toon01.bmp", "texels");
+        let previous = textures.get_or_load("toon01.bmp", upload).unwrap();
+
+        // the same source keeps the loaded texture
+        assert!(!textures.replace_source("toon01.bmp", "texels"));
+        assert!(textures.is_loaded("toon01.bmp"));
+
+        assert!(textures.replace_source("toon01.bmp", "edited"));
+        assert!(!textures.is_loaded("toon01.bmp"));
+        assert_eq!(
+            *textures.get_or_load("toon01.bmp", upload).unwrap(),
+            "edited"
+        );
+        assert_eq!(*previous, "texels");
+
+        assert!(textures.replace_source("toon02.bmp", "texels"));
+        assert!(textures.contains("toon02.bmp"));
+    }
+}
@@ -1,5 +1,8 @@
+mod billboard;
 mod camera;
 mod light;
+mod mesh_renderer;
+mod model_instance;
 mod pmx_model_animator;
 mod pmx_model_renderer;
 mod ui_element;
@@ -7,8 +10,11 @@ mod ui_glyph_renderer;
 mod ui_scaler;
 mod ui_sprite_renderer;
 
+pub use billboard::*;
 pub use camera::*;
 pub use light::*;
+pub use mesh_renderer::*;
+pub use model_instance::*;
 pub use pmx_model_animator::*;
 pub use pmx_model_renderer::*;
 pub use ui_element::*;
@@ -0,0 +1,176 @@
+use crate::scene::{Component, ObjectId, SceneProxy};
+use lvl_math::{Quat, Vec3};
+use std::any::Any;
+
+/// Turns the forward axis of the object toward the camera every frame.
+/// The rotation of the object's [`crate::scene::Transform`] is overwritten.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Billboard {
+    pub mode: BillboardMode,
+}
+
+impl Billboard {
+    pub fn new(mode: BillboardMode) -> Self {
+        Self { mode }
+    }
+
+    /// Returns the world rotation that faces the camera from the given position,
+    /// or `None` if the direction toward the camera is undefined.
+    pub fn world_rotation(&self, position: Vec3, camera_position: Vec3) -> Option<Quat> {
+        let mut direction = camera_position - position;
+
+        if self.mode == BillboardMode::YAxisLocked {
+            direction.y = 0.0;
+        }
+
+        if direction.len() <= f32::EPSILON {
+            return None;
+        }
+
+        let direction = direction.normalized();
+        // looking straight up or down, the up vector is replaced to keep the basis valid
+        let up = if 1.0 - f32::EPSILON <= Vec3::dot(direction, Vec3::UP).abs() {
+            Vec3::BACKWARD
+        } else {
+            Vec3::UP
+        };
+
+        Some(Quat::look_rotation(direction, up))
+    }
+}
+
+impl Component for Billboard {
+    fn clone_boxed(&self) -> Option<Box<dyn Component>> {
+        Some(Box::new(*self))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BillboardMode {
+    /// Faces the camera on all axes.
+    Full,
+    /// Rotates only around the world up axis, keeping the object upright.
+    YAxisLocked,
+}
+
+/// Rotates all objects with a [`Billboard`] toward the given camera.
+pub fn update_billboards(scene: &mut SceneProxy, camera_id: ObjectId) {
+    let camera_position = match scene.local_to_world_matrix(camera_id) {
+        Some(matrix) => matrix.split_translation(),
+        None => {
+            return;
+        }
+    };
+    let object_ids = match scene.find_object_ids_by_component_type::<Billboard>() {
+        Some(object_ids) => object_ids.iter().copied().collect::<Vec<_>>(),
+        None => {
+            return;
+        }
+    };
+
+    for object_id in object_ids {
+        if object_id == camera_id || !scene.is_active(object_id) {
+            continue;
+        }
+
+        let object = scene.find_object_by_id(object_id).unwrap();
+        let billboard = *object.find_component_by_type::<Billboard>().unwrap();
+        let mut transform = object.transform();
+
+        let position = scene
+            .local_to_world_matrix(object_id)
+            .unwrap()
+            .split_translation();
+        let world_rotation = match billboard.world_rotation(position, camera_position) {
+            Some(rotation) => rotation,
+            None => {
+                continue;
+            }
+        };
+
+        transform.rotation = match scene
+            .parent(object_id)
+            .and_then(|parent_id| scene.local_to_world_matrix(parent_id))
+        {
+            Some(parent_matrix) => parent_matrix.split().1.inverted() * world_rotation,
+            None => world_rotation,
+        };
+        scene.set_transform(object_id, transform);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lvl_math::Mat4;
+
+    fn equals_vec3(a: Vec3, b: Vec3) -> bool {
+        (a - b).len() <= 1e-4
+    }
+
+    #[test]
+    fn check_full_billboard_faces_camera() {
+        let billboard = Billboard::new(BillboardMode::Full);
+        let position = Vec3::new(1.0, 2.0, 3.0);
+        let camera_position = Vec3::new(-4.0, 10.0, 8.0);
+
+        let rotation = billboard.world_rotation(position, camera_position).unwrap();
+
+        assert!(equals_vec3(
+            rotation * Vec3::FORWARD,
+            (camera_position - position).normalized()
+        ));
+    }
+
+    #[test]
+    fn check_y_locked_billboard_keeps_up() {
+        let billboard = Billboard::new(BillboardMode::YAxisLocked);
+        let position = Vec3::new(1.0, 2.0, 3.0);
+        let camera_position = Vec3::new(-4.0, 10.0, 8.0);
+
+        let rotation = billboard.world_rotation(position, camera_position).unwrap();
+        let mut expected = camera_position - position;
+        expected.y = 0.0;
+
+        assert!(equals_vec3(rotation * Vec3::FORWARD, expected.normalized()));
+        assert!(equals_vec3(rotation * Vec3::UP, Vec3::UP));
+    }
+
+    #[test]
+    fn check_billboard_under_rotated_parent() {
+        let billboard = Billboard::new(BillboardMode::Full);
+        let parent_rotation = Quat::from_axis_angle(Vec3::UP, 1.0);
+        let parent_matrix = Mat4::srt(Vec3::new(0.0, 1.0, 0.0), parent_rotation, Vec3::ONE);
+        let position = Vec3::new(0.0, 1.0, 0.0);
+        let camera_position = Vec3::new(0.0, 5.0, 5.0);
+
+        let world_rotation = billboard.world_rotation(position, camera_position).unwrap();
+        let local_rotation = parent_matrix.split().1.inverted() * world_rotation;
+
+        assert!(equals_vec3(
+            parent_rotation * (local_rotation * Vec3::FORWARD),
+            (camera_position - position).normalized()
+        ));
+    }
+
+    #[test]
+    fn check_camera_at_same_position() {
+        let billboard = Billboard::new(BillboardMode::YAxisLocked);
+
+        assert_eq!(
+            billboard.world_rotation(Vec3::ZERO, Vec3::new(0.0, 3.0, 0.0)),
+            None
+        );
+        assert!(Billboard::new(BillboardMode::Full)
+            .world_rotation(Vec3::ZERO, Vec3::new(0.0, 3.0, 0.0))
+            .is_some());
+    }
+}
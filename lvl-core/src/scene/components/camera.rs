@@ -1,14 +1,67 @@
-use crate::scene::Component;
-use lvl_math::{Mat4, Vec4};
+use crate::scene::{components::PmxModelRenderer, Component, ObjectId, SceneProxy};
+use lvl_math::{Aabb, Mat4, Quat, Vec3, Vec4};
+use serde::{Deserialize, Serialize};
 use std::any::Any;
 
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Camera {
     pub order: i64,
     pub clear_mode: CameraClearMode,
     pub projection_mode: CameraProjectionMode,
+    /// Scale applied to the HDR color before tonemapping.
+    pub exposure: f32,
+}
+
+impl Camera {
+    /// Returns the position from which the camera, looking along the given rotation,
+    /// sees the whole box. The far plane is pushed back and the near plane is pulled in
+    /// if they would clip the box; orthographic extents are resized to fit the box.
+    pub fn frame_bounds(&mut self, min: Vec3, max: Vec3, rotation: Quat, aspect: f32) -> Vec3 {
+        let aabb = Aabb::new(min, max);
+        // the bounding sphere fits regardless of the orientation of the box
+        let radius = (aabb.size().len() * 0.5).max(f32::EPSILON);
+        let forward = rotation * Vec3::FORWARD;
+
+        let distance = match &mut self.projection_mode {
+            CameraProjectionMode::Perspective { fov, near, far } => {
+                let half_fov_y = *fov * 0.5;
+                let half_fov_x = (half_fov_y.tan() * aspect).atan();
+                let distance = radius / half_fov_y.min(half_fov_x).sin();
+
+                *near = near.min(distance - radius);
+                *far = far.max(distance + radius);
+                distance
+            }
+            CameraProjectionMode::Orthographic {
+                left,
+                right,
+                bottom,
+                top,
+                near,
+                far,
+            } => {
+                *left = -radius;
+                *right = radius;
+                *bottom = -radius;
+                *top = radius;
+                *far = far.max(*near + radius * 2.0);
+                *near + radius
+            }
+        };
+
+        aabb.center() - forward * distance
+    }
 }
 
 impl Component for Camera {
+    fn clone_boxed(&self) -> Option<Box<dyn Component>> {
+        Some(Box::new(self.clone()))
+    }
+
+    fn serialize(&self) -> Option<serde_json::Value> {
+        serde_json::to_value(self).ok()
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -18,14 +71,14 @@ impl Component for Camera {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum CameraClearMode {
     All { color: Vec4 },
     DepthStencilOnly,
     Keep,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum CameraProjectionMode {
     Perspective {
         fov: f32,
@@ -60,3 +113,155 @@ impl CameraProjectionMode {
         transform_matrix * projection_matrix
     }
 }
+
+/// Moves the camera to frame the object and its children, keeping the camera rotation.
+/// Only the objects with a [`PmxModelRenderer`] have bounds. Returns `false` if there is nothing
+/// to frame or the camera is not found.
+pub fn frame_object(scene: &mut SceneProxy, camera_id: ObjectId, object_id: ObjectId) -> bool {
+    let bounds = match world_bounds(scene, object_id) {
+        Some(bounds) => bounds,
+        None => {
+            return false;
+        }
+    };
+    let camera_rotation = match scene.local_to_world_matrix(camera_id) {
+        Some(matrix) => matrix.split().1,
+        None => {
+            return false;
+        }
+    };
+    let screen_size = scene.context().screen_size().size();
+    let aspect = screen_size.width as f32 / screen_size.height.max(1) as f32;
+
+    let camera = match scene
+        .find_object_by_id_mut(camera_id)
+        .and_then(|object| object.find_component_by_type_mut::<Camera>())
+    {
+        Some(camera) => camera,
+        None => {
+            return false;
+        }
+    };
+    let position = camera.frame_bounds(bounds.min, bounds.max, camera_rotation, aspect);
+
+    let mut transform = scene.find_object_by_id(camera_id).unwrap().transform();
+    transform.position = match scene
+        .parent(camera_id)
+        .and_then(|parent_id| scene.local_to_world_matrix(parent_id))
+    {
+        Some(parent_matrix) => {
+            let position = Vec4::from_vec3(position, 1.0) * parent_matrix.inversed();
+            Vec3::new(position.x, position.y, position.z)
+        }
+        None => position,
+    };
+    scene.set_transform(camera_id, transform);
+
+    true
+}
+
+/// Returns the world-space bounds of the models of the object and its children.
+fn world_bounds(scene: &SceneProxy, object_id: ObjectId) -> Option<Aabb> {
+    scene
+        .object_and_children(object_id)?
+        .iter()
+        .filter_map(|&object_id| {
+            let bounds = scene
+                .find_object_by_id(object_id)?
+                .find_component_by_type::<PmxModelRenderer>()?
+                .model()
+                .bounds()?;
+            Some(bounds.transformed(&scene.local_to_world_matrix(object_id)?))
+        })
+        .reduce(|lhs, rhs| lhs.union(&rhs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::Transform;
+
+    fn make_camera(projection_mode: CameraProjectionMode) -> Camera {
+        Camera {
+            order: 0,
+            clear_mode: CameraClearMode::Keep,
+            projection_mode,
+            exposure: 1.0,
+        }
+    }
+
+    fn check_corners_in_viewport(camera: &mut Camera, rotation: Quat, aspect: f32) {
+        let aabb = Aabb::new(Vec3::new(2.0, -1.0, 4.0), Vec3::new(5.0, 3.0, 4.5));
+        let position = camera.frame_bounds(aabb.min, aabb.max, rotation, aspect);
+        let transform = Transform {
+            position,
+            rotation,
+            scale: Vec3::ONE,
+        };
+        let view_projection = camera
+            .projection_mode
+            .to_mat4(aspect, &transform.matrix().inversed());
+
+        for corner in aabb.corners() {
+            let clip = Vec4::from_vec3(corner, 1.0) * &view_projection;
+            let ndc = Vec3::new(clip.x / clip.w, clip.y / clip.w, clip.z / clip.w);
+
+            assert!(0.0 < clip.w, "{:?} is behind the camera", corner);
+            assert!(
+                ndc.x.abs() <= 1.0 && ndc.y.abs() <= 1.0,
+                "{:?} is out",
+                corner
+            );
+            assert!((0.0..=1.0).contains(&ndc.z), "{:?} is clipped", corner);
+        }
+    }
+
+    #[test]
+    fn check_clone_boxed_is_independent() {
+        let mut camera = make_camera(CameraProjectionMode::Perspective {
+            fov: 1.0,
+            near: 0.1,
+            far: 100.0,
+        });
+        let mut copy = camera.clone_boxed().unwrap();
+        let copy = copy.as_any_mut().downcast_mut::<Camera>().unwrap();
+
+        copy.order = 5;
+        camera.exposure = 2.0;
+
+        assert_eq!(camera.order, 0);
+        assert_eq!(copy.exposure, 1.0);
+        assert_eq!(
+            Component::serialize(copy).unwrap()["order"],
+            serde_json::json!(5)
+        );
+    }
+
+    #[test]
+    fn check_frame_bounds_perspective() {
+        let mut camera = make_camera(CameraProjectionMode::Perspective {
+            fov: 60f32.to_radians(),
+            near: 0.1,
+            far: 5.0,
+        });
+        let rotation = Quat::look_rotation(Vec3::new(1.0, -0.5, -1.0), Vec3::UP);
+
+        check_corners_in_viewport(&mut camera, rotation, 16.0 / 9.0);
+        // a narrow viewport is limited by the horizontal fov
+        check_corners_in_viewport(&mut camera, Quat::IDENTITY, 0.25);
+    }
+
+    #[test]
+    fn check_frame_bounds_orthographic() {
+        let mut camera = make_camera(CameraProjectionMode::Orthographic {
+            left: -1.0,
+            right: 1.0,
+            bottom: -1.0,
+            top: 1.0,
+            near: 0.1,
+            far: 1.0,
+        });
+
+        check_corners_in_viewport(&mut camera, Quat::from_axis_angle(Vec3::UP, 0.7), 1.0);
+    }
+}
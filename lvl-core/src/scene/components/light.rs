@@ -1,14 +1,23 @@
 use crate::scene::Component;
 use lvl_math::Vec3;
+use serde::{Deserialize, Serialize};
 use std::any::Any;
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
 pub struct Light {
     pub kind: LightKind,
     pub light_color: Vec3,
 }
 
 impl Component for Light {
+    fn clone_boxed(&self) -> Option<Box<dyn Component>> {
+        Some(Box::new(*self))
+    }
+
+    fn serialize(&self) -> Option<serde_json::Value> {
+        serde_json::to_value(self).ok()
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -18,7 +27,7 @@ impl Component for Light {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
 pub enum LightKind {
     Point,
     Directional { direction: Vec3 },
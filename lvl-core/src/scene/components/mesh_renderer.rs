@@ -0,0 +1,344 @@
+use crate::{
+    gfx::{
+        elements::{Material, Mesh},
+        FaceCulling, GfxContext, GlobalTextureSet,
+    },
+    scene::{Component, ObjectId, SceneProxy, Transform},
+};
+use lvl_resource::{MeshIndexError, MeshSource, ModelElement, ModelSource, ResourceFile};
+use std::{
+    any::Any,
+    cell::RefCell,
+    collections::{hash_map::Entry, HashMap, HashSet},
+    sync::Arc,
+};
+use thiserror::Error;
+use wgpu::{
+    BlendState, CompareFunction, FragmentState, PolygonMode, PrimitiveState, RenderPipeline,
+    RenderPipelineDescriptor, StencilFaceState, StencilState, VertexAttribute, VertexBufferLayout,
+    VertexState, VertexStepMode,
+};
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ModelSpawnError {
+    #[error("the model `{0}` is not found")]
+    ModelNotFound(String),
+    #[error("the mesh `{0}` is not found")]
+    MeshNotFound(String),
+    #[error("the mesh `{name}` is invalid: {error}")]
+    InvalidMesh {
+        name: String,
+        #[source]
+        error: MeshIndexError,
+    },
+    #[error("the material `{0}` is not loaded in the resource registry")]
+    MaterialNotFound(String),
+}
+
+/// Draws a static mesh with a material, e.g. a part of a model imported from glTF.
+/// The mesh and the material can be shared by many renderers.
+#[derive(Debug)]
+pub struct MeshRenderer {
+    mesh: Arc<Mesh>,
+    material: Arc<Material>,
+    render_pipeline: RefCell<Option<Arc<RenderPipeline>>>,
+}
+
+impl MeshRenderer {
+    pub fn new(mesh: Arc<Mesh>, material: Arc<Material>) -> Self {
+        Self {
+            mesh,
+            material,
+            render_pipeline: RefCell::new(None),
+        }
+    }
+
+    /// Creates an object for each element of the model reachable from its root, with the
+    /// transforms and the parents of the elements, and a renderer for each visible part. The
+    /// meshes are uploaded from the file, and the materials are taken from the resource registry,
+    /// which must have loaded the file, e.g. by [`crate::context::Context::load_resources`].
+    /// Returns the object of the root element. Nothing is created if a part cannot be made.
+    pub fn spawn_model(
+        scene: &mut SceneProxy,
+        resource: &ResourceFile,
+        name: &str,
+    ) -> Result<ObjectId, ModelSpawnError> {
+        let model = resource
+            .find::<ModelSource>(name)
+            .ok_or_else(|| ModelSpawnError::ModelNotFound(name.to_owned()))?;
+        let elements = elements_in_hierarchy_order(model);
+
+        let mut meshes = HashMap::<&str, Arc<Mesh>>::new();
+        let mut renderers = Vec::with_capacity(elements.len());
+
+        for element in &elements {
+            let mut element_renderers = Vec::with_capacity(element.visible_parts.len());
+
+            for part in &element.visible_parts {
+                let mesh = match meshes.entry(&part.mesh_name) {
+                    Entry::Occupied(entry) => entry.get().clone(),
+                    Entry::Vacant(entry) => {
+                        let source = resource
+                            .find::<MeshSource>(&part.mesh_name)
+                            .ok_or_else(|| ModelSpawnError::MeshNotFound(part.mesh_name.clone()))?;
+                        let mesh = Mesh::load_from_source(source, &scene.context().gfx_ctx())
+                            .map_err(|error| ModelSpawnError::InvalidMesh {
+                                name: part.mesh_name.clone(),
+                                error,
+                            })?;
+                        entry.insert(Arc::new(mesh)).clone()
+                    }
+                };
+                let material = scene
+                    .context()
+                    .get_material(&part.material_name)
+                    .ok_or_else(|| ModelSpawnError::MaterialNotFound(part.material_name.clone()))?;
+
+                element_renderers.push(Self::new(mesh, material));
+            }
+
+            renderers.push(element_renderers);
+        }
+
+        let mut object_ids = HashMap::with_capacity(elements.len());
+
+        for (element, element_renderers) in elements.iter().zip(renderers) {
+            let object_id = scene.create_object();
+            scene.set_name(object_id, &element.name);
+            scene.set_transform(
+                object_id,
+                Transform {
+                    position: element.transform.position,
+                    rotation: element.transform.rotation,
+                    scale: element.transform.scale,
+                },
+            );
+
+            if let Some(parent_index) = element.parent_index {
+                if let Some(&parent_id) = object_ids.get(&parent_index) {
+                    scene.set_parent(object_id, Some(parent_id));
+                }
+            }
+
+            for renderer in element_renderers {
+                scene.add_component(object_id, renderer);
+            }
+
+            object_ids.insert(element.index, object_id);
+        }
+
+        Ok(object_ids[&model.root_element_index()])
+    }
+
+    pub fn mesh(&self) -> &Arc<Mesh> {
+        &self.mesh
+    }
+
+    pub fn material(&self) -> &Arc<Material> {
+        &self.material
+    }
+
+    pub(crate) fn construct_render_pipeline(
+        &self,
+        global_texture_set: &GlobalTextureSet,
+        instance_data_size: u64,
+        instance_data_attributes: &[VertexAttribute],
+        gfx_ctx: &GfxContext,
+    ) -> Arc<RenderPipeline> {
+        let mut render_pipeline = self.render_pipeline.borrow_mut();
+
+        if let Some(render_pipeline) = render_pipeline.as_ref() {
+            return render_pipeline.clone();
+        }
+
+        let created = Arc::new(self.create_render_pipeline(
+            global_texture_set,
+            instance_data_size,
+            instance_data_attributes,
+            gfx_ctx,
+        ));
+        *render_pipeline = Some(created.clone());
+        created
+    }
+
+    /// Returns the primitive state of the pipeline, which draws the topology of the mesh.
+    pub(crate) fn primitive_state(&self, face_culling: FaceCulling) -> PrimitiveState {
+        PrimitiveState {
+            topology: self.mesh.topology().primitive_topology(),
+            strip_index_format: None,
+            front_face: face_culling.front_face,
+            cull_mode: face_culling.cull_mode(self.material.render_state().no_cull_back_face),
+            unclipped_depth: false,
+            polygon_mode: PolygonMode::Fill,
+            conservative: false,
+        }
+    }
+
+    fn create_render_pipeline(
+        &self,
+        global_texture_set: &GlobalTextureSet,
+        instance_data_size: u64,
+        instance_data_attributes: &[VertexAttribute],
+        gfx_ctx: &GfxContext,
+    ) -> RenderPipeline {
+        // the pipeline has to match the targets of the main pass, which follow the surface
+        let formats = global_texture_set.render_target_formats();
+        let face_culling = global_texture_set.face_culling;
+        let shader = self.material.shader();
+        let shader_locations = &shader.reflection().locations;
+        let attributes = self
+            .mesh
+            .elements()
+            .iter()
+            .filter_map(|element| {
+                Some(VertexAttribute {
+                    format: element.kind.vertex_format(),
+                    offset: element.offset,
+                    shader_location: *shader_locations.get(&element.name)?,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        gfx_ctx
+            .device
+            .create_render_pipeline(&RenderPipelineDescriptor {
+                label: Some("mesh-render-pipeline"),
+                layout: Some(shader.pipeline_layout()),
+                vertex: VertexState {
+                    module: shader.module(),
+                    entry_point: &shader.reflection().vertex_entry_point,
+                    buffers: &[
+                        VertexBufferLayout {
+                            array_stride: instance_data_size,
+                            step_mode: VertexStepMode::Instance,
+                            attributes: instance_data_attributes,
+                        },
+                        VertexBufferLayout {
+                            array_stride: self.mesh.stride(),
+                            step_mode: VertexStepMode::Vertex,
+                            attributes: &attributes,
+                        },
+                    ],
+                },
+                primitive: self.primitive_state(face_culling),
+                depth_stencil: Some(formats.depth_stencil.depth_stencil_state(
+                    true,
+                    CompareFunction::Less,
+                    StencilState {
+                        front: StencilFaceState::IGNORE,
+                        back: StencilFaceState::IGNORE,
+                        read_mask: 0,
+                        write_mask: 0,
+                    },
+                )),
+                multisample: formats.multisample_state(),
+                fragment: Some(FragmentState {
+                    module: shader.module(),
+                    entry_point: &shader.reflection().fragment_entry_point,
+                    targets: &[Some(
+                        formats.color_target_state(Some(BlendState::ALPHA_BLENDING)),
+                    )],
+                }),
+                multiview: None,
+            })
+    }
+}
+
+/// Returns the elements reachable from the root, each after its parent.
+fn elements_in_hierarchy_order(model: &ModelSource) -> Vec<&ModelElement> {
+    let mut elements = Vec::with_capacity(model.elements().len());
+    let mut visited = HashSet::new();
+    let mut stack = vec![model.root_element_index()];
+
+    while let Some(index) = stack.pop() {
+        // an element may claim the root as its child, which must not loop
+        if !visited.insert(index) {
+            continue;
+        }
+
+        let element = match model
+            .elements()
+            .iter()
+            .find(|element| element.index == index)
+        {
+            Some(element) => element,
+            None => continue,
+        };
+
+        elements.push(element);
+        stack.extend(
+            model
+                .elements()
+                .iter()
+                .rev()
+                .filter(|child| child.parent_index == Some(index))
+                .map(|child| child.index),
+        );
+    }
+
+    elements
+}
+
+impl Component for MeshRenderer {
+    // a copy shares the mesh and the material, and builds its own pipeline
+    fn clone_boxed(&self) -> Option<Box<dyn Component>> {
+        Some(Box::new(Self::new(
+            self.mesh.clone(),
+            self.material.clone(),
+        )))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lvl_math::{Quat, Vec3};
+    use lvl_resource::{ModelTransform, ModelVisiblePart};
+
+    fn make_element(index: u32, parent_index: Option<u32>) -> ModelElement {
+        ModelElement {
+            index,
+            name: format!("element-{}", index),
+            parent_index,
+            transform: ModelTransform {
+                position: Vec3::ZERO,
+                rotation: Quat::IDENTITY,
+                scale: Vec3::ONE,
+            },
+            visible_parts: vec![ModelVisiblePart {
+                mesh_name: "mesh".to_owned(),
+                material_name: "material".to_owned(),
+            }],
+        }
+    }
+
+    #[test]
+    fn check_elements_in_hierarchy_order() {
+        let model = ModelSource::new(
+            3,
+            vec![
+                make_element(0, Some(2)),
+                make_element(1, Some(3)),
+                make_element(2, Some(3)),
+                make_element(3, Some(0)),
+                // not reachable from the root
+                make_element(4, None),
+            ],
+        );
+        let indices = elements_in_hierarchy_order(&model)
+            .iter()
+            .map(|element| element.index)
+            .collect::<Vec<_>>();
+
+        // the root claimed by its descendant is visited once
+        assert_eq!(indices, [3, 1, 2, 0]);
+    }
+}
@@ -0,0 +1,323 @@
+use super::PmxModelRenderer;
+use crate::{
+    gfx::elements::{PmxModel, PmxModelAnimation},
+    scene::{Component, ObjectId, SceneProxy, Transform},
+};
+use lvl_math::Mat4;
+use std::{any::Any, collections::HashMap};
+
+/// The pose of a model: the local poses of the bones, relative to their bind poses, and the
+/// weights of the morphs. Bones and morphs that are not listed are left as they are.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ModelPose {
+    pub bones: HashMap<String, Transform>,
+    pub morphs: HashMap<String, f32>,
+}
+
+impl ModelPose {
+    /// Samples the bone poses and the morph weights of the animation at the time in seconds.
+    pub fn sample(animation: &PmxModelAnimation, time: f32) -> Self {
+        Self {
+            bones: animation
+                .sample_bones(time)
+                .into_iter()
+                .map(|(name, (translation, rotation))| {
+                    let transform = Transform {
+                        position: translation,
+                        rotation,
+                        ..Transform::identity()
+                    };
+                    (name, transform)
+                })
+                .collect(),
+            morphs: animation.sample_morphs(time),
+        }
+    }
+
+    /// Poses the skeleton and sets the morph weights of the model.
+    pub fn apply(&self, model: &mut PmxModel) {
+        let local_poses = self
+            .bones
+            .iter()
+            .map(|(name, transform)| (name.clone(), transform.matrix()))
+            .collect::<HashMap<String, Mat4>>();
+        model.skeleton_mut().set_local_poses(&local_poses);
+        model.set_morphs(
+            self.morphs
+                .iter()
+                .map(|(name, weight)| (name.as_str(), *weight)),
+        );
+    }
+}
+
+/// Animates the [`PmxModelRenderer`] of the same object: plays an animation and layers the bone
+/// poses and morph weights set by hand on top of it. Every instance is ticked once per update,
+/// after the controllers, and its pose is applied to the model.
+///
+/// Use [`ModelInstance::spawn`] to create an object with both components.
+#[derive(Debug, Clone)]
+pub struct ModelInstance {
+    animation: Option<PmxModelAnimation>,
+    time: f32,
+    is_playing: bool,
+    pub loop_enabled: bool,
+    manual_pose: ModelPose,
+    pose: ModelPose,
+    is_dirty: bool,
+}
+
+impl ModelInstance {
+    pub fn new(loop_enabled: bool) -> Self {
+        Self {
+            animation: None,
+            time: 0f32,
+            is_playing: false,
+            loop_enabled,
+            manual_pose: ModelPose::default(),
+            pose: ModelPose::default(),
+            is_dirty: false,
+        }
+    }
+
+    /// Creates an object that renders the model and animates it with a new instance.
+    pub fn spawn(scene: &mut SceneProxy, model: PmxModel, loop_enabled: bool) -> ObjectId {
+        let object_id = scene.create_object();
+        scene.add_component(object_id, PmxModelRenderer::new(model));
+        scene.add_component(object_id, Self::new(loop_enabled));
+        object_id
+    }
+
+    pub fn animation(&self) -> Option<&PmxModelAnimation> {
+        self.animation.as_ref()
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.is_playing
+    }
+
+    /// Playback time of the animation in seconds.
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// Plays the animation from its start, replacing the current one.
+    pub fn play_animation(&mut self, animation: PmxModelAnimation) {
+        self.animation = Some(animation);
+        self.time = 0f32;
+        self.is_playing = true;
+        self.is_dirty = true;
+    }
+
+    /// Stops the animation, holding the current pose.
+    pub fn stop_animation(&mut self) {
+        self.is_playing = false;
+    }
+
+    /// Sets the weight of the morph, which wins over the animation until it is cleared.
+    pub fn set_morph(&mut self, name: impl Into<String>, weight: f32) {
+        self.manual_pose.morphs.insert(name.into(), weight);
+        self.is_dirty = true;
+    }
+
+    /// Sets the local pose of the bone, relative to its bind pose, which wins over the animation
+    /// until it is cleared.
+    pub fn set_bone_pose(&mut self, name: impl Into<String>, pose: Transform) {
+        self.manual_pose.bones.insert(name.into(), pose);
+        self.is_dirty = true;
+    }
+
+    /// Clears the bone poses and morph weights set by hand. The morphs keep their last weights
+    /// unless the animation drives them.
+    pub fn clear_manual_pose(&mut self) {
+        self.manual_pose = ModelPose::default();
+        self.is_dirty = true;
+    }
+
+    /// The pose computed by the last tick.
+    pub fn pose(&self) -> &ModelPose {
+        &self.pose
+    }
+
+    pub fn bone_pose(&self, name: &str) -> Option<&Transform> {
+        self.pose.bones.get(name)
+    }
+
+    pub fn morph_weight(&self, name: &str) -> Option<f32> {
+        self.pose.morphs.get(name).copied()
+    }
+
+    /// Advances the animation by the time in seconds and recomputes the pose.
+    /// Returns `false` if the pose has not changed since the previous tick.
+    pub fn tick(&mut self, delta_time: f32) -> bool {
+        let animation = match &self.animation {
+            Some(animation) if self.is_playing => animation,
+            _ => {
+                if !self.is_dirty {
+                    return false;
+                }
+
+                self.is_dirty = false;
+                self.pose = self.compose_pose();
+                return true;
+            }
+        };
+
+        let total_time = animation.total_time();
+        self.time += delta_time;
+
+        if total_time < self.time {
+            if self.loop_enabled && 0f32 < total_time {
+                self.time %= total_time;
+            } else {
+                self.time = total_time;
+                self.is_playing = false;
+            }
+        }
+
+        self.is_dirty = false;
+        self.pose = self.compose_pose();
+        true
+    }
+
+    fn compose_pose(&self) -> ModelPose {
+        let mut pose = match &self.animation {
+            Some(animation) => ModelPose::sample(animation, self.time),
+            None => ModelPose::default(),
+        };
+
+        pose.bones.extend(
+            self.manual_pose
+                .bones
+                .iter()
+                .map(|(name, transform)| (name.clone(), transform.clone())),
+        );
+        pose.morphs.extend(
+            self.manual_pose
+                .morphs
+                .iter()
+                .map(|(name, weight)| (name.clone(), *weight)),
+        );
+        pose
+    }
+}
+
+impl Component for ModelInstance {
+    fn clone_boxed(&self) -> Option<Box<dyn Component>> {
+        Some(Box::new(self.clone()))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Ticks the active model instances and applies their poses to the models rendered next to them.
+pub fn update_model_instances(scene: &mut SceneProxy, delta_time: f32) {
+    let object_ids = match scene.find_object_ids_by_component_type::<ModelInstance>() {
+        Some(object_ids) => object_ids.iter().copied().collect::<Vec<_>>(),
+        None => {
+            return;
+        }
+    };
+
+    for object_id in object_ids {
+        if !scene.is_active(object_id) {
+            continue;
+        }
+
+        let object = scene.find_object_by_id_mut(object_id).unwrap();
+        let instance = object
+            .find_component_by_type_mut::<ModelInstance>()
+            .unwrap();
+
+        if !instance.tick(delta_time) {
+            continue;
+        }
+
+        // both components live in the same object, so the pose is copied out first
+        let pose = instance.pose().clone();
+
+        if let Some(renderer) = object.find_component_by_type_mut::<PmxModelRenderer>() {
+            pose.apply(renderer.model_mut());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lvl_math::{Quat, Vec3};
+    use lvl_resource::{
+        PmxModelAnimationBoneBezier, PmxModelAnimationBoneKeyFrame,
+        PmxModelAnimationBoneKeyFrameElement, PmxModelAnimationMorphKeyFrame,
+        PmxModelAnimationMorphKeyFrameElement, PmxModelAnimationSource,
+    };
+
+    /// Raises the arm by 2 along y and the smile to 1 over 30 frames, at 30 fps.
+    fn make_animation() -> PmxModelAnimation {
+        let linear = [20, 107, 20, 107];
+        let bone_key_frame = |frame_index, y| PmxModelAnimationBoneKeyFrame {
+            frame_index,
+            elements: vec![PmxModelAnimationBoneKeyFrameElement {
+                bone_name: "arm".to_owned(),
+                translation: Vec3::new(0.0, y, 0.0),
+                rotation: Quat::IDENTITY,
+                bezier: PmxModelAnimationBoneBezier {
+                    x_axis: linear,
+                    y_axis: linear,
+                    z_axis: linear,
+                    rotation: linear,
+                },
+            }],
+        };
+        let morph_key_frame = |frame_index, weight| PmxModelAnimationMorphKeyFrame {
+            frame_index,
+            elements: vec![PmxModelAnimationMorphKeyFrameElement {
+                morph_name: "smile".to_owned(),
+                weight,
+            }],
+        };
+        let source = PmxModelAnimationSource::new(
+            vec![bone_key_frame(0, 0.0), bone_key_frame(30, 2.0)],
+            vec![morph_key_frame(0, 0.0), morph_key_frame(30, 1.0)],
+        );
+
+        PmxModelAnimation::load_from_source(&source, 30.0)
+    }
+
+    #[test]
+    fn check_tick_advances_animation() {
+        let mut instance = ModelInstance::new(false);
+        instance.play_animation(make_animation());
+
+        assert!(instance.tick(0.0));
+        assert!(instance.bone_pose("arm").unwrap().position.y.abs() < 1e-3);
+        assert_eq!(instance.morph_weight("smile"), Some(0.0));
+
+        assert!(instance.tick(0.5));
+        assert!((instance.bone_pose("arm").unwrap().position.y - 1.0).abs() < 1e-3);
+        assert!((instance.morph_weight("smile").unwrap() - 0.5).abs() < 1e-3);
+
+        // the pose set by hand wins over the animation
+        instance.set_morph("smile", 0.25);
+        instance.set_bone_pose("head", Transform::identity());
+        assert!(instance.tick(0.25));
+        assert_eq!(instance.morph_weight("smile"), Some(0.25));
+        assert!(instance.bone_pose("head").is_some());
+        assert!((instance.bone_pose("arm").unwrap().position.y - 1.5).abs() < 1e-3);
+    }
+
+    #[test]
+    fn check_tick_without_changes() {
+        let mut instance = ModelInstance::new(false);
+        assert!(!instance.tick(0.1));
+
+        instance.set_morph("smile", 1.0);
+        assert!(instance.tick(0.1));
+        assert!(!instance.tick(0.1));
+    }
+}
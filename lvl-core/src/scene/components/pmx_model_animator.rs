@@ -1,19 +1,26 @@
+mod animation_marker;
 mod bind_pose_bone_set;
 mod bone_hierarchy;
 
+pub use animation_marker::{AnimationMarker, AnimationMarkerEvent};
+
+use self::animation_marker::{crossed_markers, AnimationProgress};
+use super::{ModelPose, PmxModelRenderer};
 use crate::{
     context::Context,
-    gfx::elements::{PmxModel, PmxModelAnimation},
+    gfx::elements::PmxModelAnimation,
     scene::{Component, Object, ObjectId, SceneProxy, Transform},
 };
 use lvl_math::Mat4;
 use std::{any::Any, cell::RefMut};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct PmxModelAnimator {
     animation: Option<PmxModelAnimation>,
     start_time: Option<f32>,
     is_playing: bool,
+    last_elapsed_time: Option<f32>,
+    markers: Vec<AnimationMarker>,
     pub loop_enabled: bool,
     // TODO: because MMD is not following ordinal object hierarchy system, we have to manage bones manually by using bone names.
 }
@@ -24,6 +31,8 @@ impl PmxModelAnimator {
             animation: None,
             start_time: None,
             is_playing: false,
+            last_elapsed_time: None,
+            markers: Vec::new(),
             loop_enabled,
         }
     }
@@ -56,6 +65,18 @@ impl PmxModelAnimator {
         }
     }
 
+    pub fn markers(&self) -> &[AnimationMarker] {
+        &self.markers
+    }
+
+    pub fn add_marker(&mut self, marker: AnimationMarker) {
+        self.markers.push(marker);
+    }
+
+    pub fn clear_markers(&mut self) {
+        self.markers.clear();
+    }
+
     pub fn set_animation(&mut self, animation: PmxModelAnimation) {
         self.animation = Some(animation);
     }
@@ -65,48 +86,108 @@ impl PmxModelAnimator {
     }
 
     pub fn play(&mut self, ctx: &Context) {
-        if self.animation.is_none() {
-            return;
-        }
-
-        self.start_time = Some(ctx.time().time().as_secs_f32());
+        self.start(ctx.time().time().as_secs_f32());
     }
 
-    pub(crate) fn update(&mut self, pmx_model: &mut PmxModel, ctx: &Context) {
+    /// Advances the playback to the time in seconds since the context started. Returns the
+    /// elapsed time of the animation to pose the model at, and the markers crossed since the
+    /// previous update, or `None` if nothing is playing.
+    pub(crate) fn update(&mut self, current_time: f32) -> Option<(f32, Vec<AnimationMarker>)> {
         if !self.is_playing {
-            return;
+            return None;
         }
 
-        let (animation, mut start_time) = match (&self.animation, self.start_time) {
+        let (animation, start_time) = match (&self.animation, self.start_time) {
             (Some(animation), Some(start_time)) => (animation, start_time),
-            _ => return,
+            _ => return None,
         };
 
-        let current_time = ctx.time().time().as_secs_f32();
-        let elapsed_time = current_time - start_time;
+        let total_time = animation.total_time();
+        let mut elapsed_time = current_time - start_time;
+        let mut is_wrapped = false;
 
-        if animation.total_time() < elapsed_time {
-            if self.loop_enabled {
-                start_time = current_time;
+        if total_time < elapsed_time {
+            if self.loop_enabled && 0f32 < total_time {
+                elapsed_time %= total_time;
+                is_wrapped = true;
+                self.start_time = Some(current_time - elapsed_time);
             } else {
+                // fire the remaining markers before stopping
+                elapsed_time = total_time;
                 self.is_playing = false;
-                return;
             }
         }
 
-        let bone_key_frame = animation.get_current_bone_key_frame(elapsed_time);
-        let morph_key_frame = animation.get_current_morph_key_frame(elapsed_time);
+        let progress = AnimationProgress {
+            previous_time: self.last_elapsed_time,
+            current_time: elapsed_time,
+            is_wrapped,
+            total_time,
+        };
+        let markers = crossed_markers(&self.markers, progress)
+            .into_iter()
+            .cloned()
+            .collect();
+
+        self.last_elapsed_time = Some(elapsed_time);
+        Some((elapsed_time, markers))
+    }
+
+    fn start(&mut self, current_time: f32) {
+        if self.animation.is_none() {
+            return;
+        }
+
+        self.start_time = Some(current_time);
+        self.is_playing = true;
+        self.last_elapsed_time = None;
+    }
+}
+
+/// Advances the playing animators, poses the models rendered next to them and emits a scene
+/// event for each crossed marker.
+pub fn update_pmx_model_animators(scene: &mut SceneProxy, ctx: &Context) {
+    let object_ids = match scene.find_object_ids_by_component_type::<PmxModelAnimator>() {
+        Some(object_ids) => object_ids.iter().copied().collect::<Vec<_>>(),
+        None => {
+            return;
+        }
+    };
+    let current_time = ctx.time().time().as_secs_f32();
+
+    for object_id in object_ids {
+        if !scene.is_active(object_id) {
+            continue;
+        }
+
+        let object = scene.find_object_by_id_mut(object_id).unwrap();
+        let animator = object
+            .find_component_by_type_mut::<PmxModelAnimator>()
+            .unwrap();
+        let (elapsed_time, markers) = match animator.update(current_time) {
+            Some(update) => update,
+            None => {
+                continue;
+            }
+        };
+        let pose = animator
+            .animation()
+            .map(|animation| ModelPose::sample(animation, elapsed_time));
 
-        match (bone_key_frame.current, bone_key_frame.next) {
-            (None, None) => {}
-            (Some(current), None) | (None, Some(current)) => for element in &current.elements {},
-            (Some(current), Some(next)) => {}
+        if let Some(pose) = pose {
+            if let Some(renderer) = object.find_component_by_type_mut::<PmxModelRenderer>() {
+                pose.apply(renderer.model_mut());
+            }
         }
 
-        match (morph_key_frame.current, morph_key_frame.next) {
-            (None, None) => {}
-            (Some(current), None) | (None, Some(current)) => {}
-            (Some(current), Some(next)) => {}
+        for marker in markers {
+            scene.emit_event(
+                marker.name,
+                AnimationMarkerEvent {
+                    object_id,
+                    time: marker.time,
+                },
+            );
         }
     }
 }
@@ -131,44 +212,90 @@ fn find_bone<'a>(
     None
 }
 
-fn bezier_interpolation(x1: f32, x2: f32, y1: f32, y2: f32, t: f32) -> f32 {
-    const ITERATIONS: i32 = 15;
-    const EPSILON: f32 = 1e-5;
-
-    let mut c = 0.5;
-    let mut t = c;
-    let mut s = 1.0 - t;
-
-    let mut sst3 = 0f32;
-    let mut stt3 = 0f32;
-    let mut ttt = 0f32;
-
-    for _ in 0..ITERATIONS {
-        sst3 = 3.0 * s * s * t;
-        stt3 = 3.0 * s * t * t;
-        ttt = t * t * t;
+impl Component for PmxModelAnimator {
+    fn clone_boxed(&self) -> Option<Box<dyn Component>> {
+        Some(Box::new(self.clone()))
+    }
 
-        let ft = sst3 * x1 + stt3 * x2 + ttt - t;
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
 
-        if ft.abs() < EPSILON {
-            break;
-        }
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
 
-        c *= 0.5;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lvl_resource::{
+        PmxModelAnimationMorphKeyFrame, PmxModelAnimationMorphKeyFrameElement,
+        PmxModelAnimationSource,
+    };
 
-        t += if ft < 0.0 { c } else { -c };
-        s = 1.0 - t;
+    /// Raises the smile to 1 over 2 seconds.
+    fn make_animator(loop_enabled: bool) -> PmxModelAnimator {
+        let morph_key_frame = |frame_index, weight| PmxModelAnimationMorphKeyFrame {
+            frame_index,
+            elements: vec![PmxModelAnimationMorphKeyFrameElement {
+                morph_name: "smile".to_owned(),
+                weight,
+            }],
+        };
+        let source = PmxModelAnimationSource::new(
+            vec![],
+            vec![morph_key_frame(0, 0.0), morph_key_frame(60, 1.0)],
+        );
+        let mut animator = PmxModelAnimator::new(loop_enabled);
+        animator.set_animation(PmxModelAnimation::load_from_source(&source, 30.0));
+        animator.add_marker(AnimationMarker::new(0.5, "footstep"));
+        animator.add_marker(AnimationMarker::new(1.5, "lip-sync"));
+        animator
     }
 
-    sst3 * y1 + stt3 * y2 + ttt
-}
+    fn marker_names(update: Option<(f32, Vec<AnimationMarker>)>) -> Vec<String> {
+        update
+            .unwrap()
+            .1
+            .into_iter()
+            .map(|marker| marker.name)
+            .collect()
+    }
 
-impl Component for PmxModelAnimator {
-    fn as_any(&self) -> &dyn Any {
-        self
+    #[test]
+    fn check_update_fires_markers() {
+        let mut animator = make_animator(false);
+        assert_eq!(animator.update(10.0), None);
+
+        animator.start(10.0);
+        assert_eq!(animator.update(10.25), Some((0.25, vec![])));
+        assert_eq!(marker_names(animator.update(10.75)), ["footstep"]);
+        assert!(marker_names(animator.update(11.0)).is_empty());
+
+        // the last update poses the end of the animation and fires the remaining markers
+        assert_eq!(
+            animator.update(13.0),
+            Some((2.0, vec![AnimationMarker::new(1.5, "lip-sync")]))
+        );
+        assert!(!animator.is_playing());
+        assert_eq!(animator.update(14.0), None);
     }
 
-    fn as_any_mut(&mut self) -> &mut dyn Any {
-        self
+    #[test]
+    fn check_update_refires_markers_after_loop() {
+        let mut animator = make_animator(true);
+        animator.start(0.0);
+
+        assert_eq!(marker_names(animator.update(1.0)), ["footstep"]);
+        assert_eq!(
+            marker_names(animator.update(2.75)),
+            ["lip-sync", "footstep"]
+        );
+        assert!(animator.is_playing());
+
+        let (elapsed_time, markers) = animator.update(3.0).unwrap();
+        assert!((elapsed_time - 1.0).abs() < 1e-5);
+        assert!(markers.is_empty());
     }
 }
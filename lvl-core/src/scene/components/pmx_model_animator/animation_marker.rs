@@ -0,0 +1,144 @@
+use crate::scene::ObjectId;
+
+/// A named point in time of an animation. The animator emits a scene event named after the
+/// marker whenever the playback crosses its time, once per loop.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimationMarker {
+    /// Time in seconds from the start of the animation.
+    pub time: f32,
+    pub name: String,
+}
+
+impl AnimationMarker {
+    pub fn new(time: f32, name: impl Into<String>) -> Self {
+        Self {
+            time,
+            name: name.into(),
+        }
+    }
+}
+
+/// Parameter of the scene event emitted for a crossed [`AnimationMarker`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnimationMarkerEvent {
+    /// The object that owns the animator.
+    pub object_id: ObjectId,
+    /// Time of the marker.
+    pub time: f32,
+}
+
+/// Playback progress between two updates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct AnimationProgress {
+    /// Elapsed time of the previous update, `None` if the playback has just started.
+    pub previous_time: Option<f32>,
+    /// Elapsed time of the current update.
+    pub current_time: f32,
+    /// `true` if the playback reached the end and started over since the previous update.
+    pub is_wrapped: bool,
+    pub total_time: f32,
+}
+
+/// Returns the markers passed by the given progress, in playback order.
+/// A marker is passed if its time lies in `(previous_time, current_time]`; markers at the very
+/// start are passed by the first update.
+pub(crate) fn crossed_markers<'a>(
+    markers: &'a [AnimationMarker],
+    progress: AnimationProgress,
+) -> Vec<&'a AnimationMarker> {
+    let is_after_previous = |time: f32| match progress.previous_time {
+        Some(previous_time) => previous_time < time,
+        None => 0f32 <= time,
+    };
+    let sorted = |mut markers: Vec<&'a AnimationMarker>| {
+        markers.sort_by(|a, b| a.time.total_cmp(&b.time));
+        markers
+    };
+
+    if progress.is_wrapped {
+        // the rest of the previous loop, then the beginning of the current one
+        let mut crossed = sorted(
+            markers
+                .iter()
+                .filter(|marker| is_after_previous(marker.time))
+                .filter(|marker| marker.time <= progress.total_time)
+                .collect(),
+        );
+        crossed.extend(sorted(
+            markers
+                .iter()
+                .filter(|marker| 0f32 <= marker.time && marker.time <= progress.current_time)
+                .collect(),
+        ));
+        crossed
+    } else {
+        sorted(
+            markers
+                .iter()
+                .filter(|marker| is_after_previous(marker.time))
+                .filter(|marker| marker.time <= progress.current_time)
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn progress(
+        previous_time: Option<f32>,
+        current_time: f32,
+        is_wrapped: bool,
+    ) -> AnimationProgress {
+        AnimationProgress {
+            previous_time,
+            current_time,
+            is_wrapped,
+            total_time: 2.0,
+        }
+    }
+
+    #[test]
+    fn check_marker_emitted_once() {
+        let markers = vec![AnimationMarker::new(0.5, "footstep")];
+
+        assert!(crossed_markers(&markers, progress(None, 0.25, false)).is_empty());
+        assert_eq!(
+            crossed_markers(&markers, progress(Some(0.25), 0.5, false)),
+            [&markers[0]]
+        );
+        assert!(crossed_markers(&markers, progress(Some(0.5), 0.75, false)).is_empty());
+        assert!(crossed_markers(&markers, progress(Some(0.75), 1.5, false)).is_empty());
+    }
+
+    #[test]
+    fn check_marker_refired_after_loop() {
+        let markers = vec![
+            AnimationMarker::new(0.0, "start"),
+            AnimationMarker::new(0.5, "footstep"),
+            AnimationMarker::new(1.75, "lip-sync"),
+        ];
+
+        assert_eq!(
+            crossed_markers(&markers, progress(None, 0.75, false)),
+            [&markers[0], &markers[1]]
+        );
+        assert_eq!(
+            crossed_markers(&markers, progress(Some(0.75), 1.8, false)),
+            [&markers[2]]
+        );
+
+        // wraps around the end: the markers at the start fire again, the passed ones do not
+        assert_eq!(
+            crossed_markers(&markers, progress(Some(1.8), 0.6, true)),
+            [&markers[0], &markers[1]]
+        );
+        // the end of the previous loop comes before the start of the current one
+        assert_eq!(
+            crossed_markers(&markers, progress(Some(1.0), 0.25, true)),
+            [&markers[2], &markers[0]]
+        );
+        assert!(crossed_markers(&markers, progress(Some(0.6), 1.0, false)).is_empty());
+    }
+}
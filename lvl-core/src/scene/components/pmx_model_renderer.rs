@@ -1,7 +1,7 @@
 use crate::{
     gfx::{
-        elements::{PmxModel, PmxModelElement, PmxModelVertexLayout},
-        GfxContext,
+        elements::{MaterialPropertyValue, PmxModel, PmxModelElement, PmxModelVertexLayout},
+        GfxContext, GlobalTextureSet,
     },
     scene::Component,
 };
@@ -9,13 +9,13 @@ use lvl_resource::PmxModelVertexLayoutElementKind;
 use std::{
     any::Any,
     cell::{RefCell, RefMut},
+    collections::BTreeMap,
     sync::Arc,
 };
 use wgpu::{
-    BlendState, ColorTargetState, ColorWrites, CompareFunction, DepthStencilState, Device, Face,
-    FragmentState, FrontFace, MultisampleState, PolygonMode, PrimitiveState, PrimitiveTopology,
-    RenderPipeline, RenderPipelineDescriptor, StencilFaceState, StencilState, TextureFormat,
-    VertexAttribute, VertexBufferLayout, VertexFormat, VertexState, VertexStepMode,
+    BlendState, CompareFunction, Device, FragmentState, PolygonMode, PrimitiveState,
+    PrimitiveTopology, RenderPipeline, RenderPipelineDescriptor, StencilFaceState, StencilState,
+    VertexAttribute, VertexBufferLayout, VertexState, VertexStepMode,
 };
 
 #[derive(Debug)]
@@ -41,9 +41,21 @@ impl PmxModelRenderer {
         &mut self.model
     }
 
+    /// Overrides a material property of this renderer's model only, e.g. to tint one instance.
+    /// See [`PmxModel::override_material_property`].
+    pub fn override_material_property(
+        &mut self,
+        element_index: usize,
+        name: &str,
+        value: MaterialPropertyValue,
+    ) -> bool {
+        self.model
+            .override_material_property(element_index, name, value)
+    }
+
     pub(crate) fn construct_render_pipelines(
         &self,
-        msaa_sample_count: u32,
+        global_texture_set: &GlobalTextureSet,
         instance_data_size: u64,
         instance_data_attributes: &[VertexAttribute],
         gfx_ctx: &GfxContext,
@@ -56,7 +68,7 @@ impl PmxModelRenderer {
 
         for element in self.model.elements() {
             let render_pipeline = self.create_render_pipeline(
-                msaa_sample_count,
+                global_texture_set,
                 instance_data_size,
                 instance_data_attributes,
                 &self.model.vertex_layout(),
@@ -72,13 +84,16 @@ impl PmxModelRenderer {
 
     fn create_render_pipeline(
         &self,
-        msaa_sample_count: u32,
+        global_texture_set: &GlobalTextureSet,
         instance_data_size: u64,
         instance_data_attributes: &[VertexAttribute],
         layout: &PmxModelVertexLayout,
         element: &PmxModelElement,
         device: &Device,
     ) -> RenderPipeline {
+        // the pipeline has to match the targets of the main pass, which follow the surface
+        let formats = global_texture_set.render_target_formats();
+        let face_culling = global_texture_set.face_culling;
         let material = &element.material;
         let shader = material.shader();
         let shader_locations = &shader.reflection().locations;
@@ -87,7 +102,7 @@ impl PmxModelRenderer {
 
         for element in &vertex_layout.elements {
             let name = shader_input_name_from_vertex_layout_kind(element.kind);
-            let format = vertex_format_from_vertex_layout_kind(element.kind);
+            let format = element.kind.vertex_format();
 
             let shader_location = match shader_locations.get(&name) {
                 Some(location) => *location,
@@ -133,43 +148,31 @@ impl PmxModelRenderer {
                     PrimitiveTopology::TriangleList
                 },
                 strip_index_format: None,
-                front_face: FrontFace::Cw,
-                cull_mode: if material.render_state().no_cull_back_face {
-                    None
-                } else {
-                    Some(Face::Back)
-                },
+                front_face: face_culling.front_face,
+                cull_mode: face_culling.cull_mode(material.render_state().no_cull_back_face),
                 unclipped_depth: false,
                 polygon_mode: PolygonMode::Fill,
                 conservative: false,
             },
-            depth_stencil: Some(DepthStencilState {
-                // TODO: let engine decide actual depth stencil state
-                format: TextureFormat::Depth32Float,
-                depth_write_enabled: true,
-                depth_compare: CompareFunction::Less,
-                stencil: StencilState {
+            depth_stencil: Some(formats.depth_stencil.depth_stencil_state(
+                true,
+                CompareFunction::Less,
+                // TODO: let material decide actual stencil state
+                StencilState {
                     front: StencilFaceState::IGNORE,
                     back: StencilFaceState::IGNORE,
                     read_mask: 0,
                     write_mask: 0,
                 },
-                bias: Default::default(),
-            }),
-            multisample: MultisampleState {
-                count: msaa_sample_count,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
+            )),
+            multisample: formats.multisample_state(),
             fragment: Some(FragmentState {
                 module: shader.module(),
                 entry_point: &shader.reflection().fragment_entry_point,
-                targets: &[Some(ColorTargetState {
-                    // TODO: let engine decide actual color target state
-                    format: TextureFormat::Bgra8UnormSrgb,
-                    blend: Some(BlendState::ALPHA_BLENDING),
-                    write_mask: ColorWrites::all(),
-                })],
+                // TODO: let material decide actual blend state
+                targets: &[Some(
+                    formats.color_target_state(Some(BlendState::ALPHA_BLENDING)),
+                )],
             }),
             multiview: None,
         })
@@ -196,31 +199,29 @@ fn shader_input_name_from_vertex_layout_kind(kind: PmxModelVertexLayoutElementKi
         PmxModelVertexLayoutElementKind::UvMorphIndexStart => "uv_morph_index_start".to_owned(),
         PmxModelVertexLayoutElementKind::VertexMorphCount => "vertex_morph_count".to_owned(),
         PmxModelVertexLayoutElementKind::UvMorphCount => "uv_morph_count".to_owned(),
+        PmxModelVertexLayoutElementKind::AdditionalTexCoord(channel) => format!("uv_{}_", channel),
     }
 }
 
-fn vertex_format_from_vertex_layout_kind(kind: PmxModelVertexLayoutElementKind) -> VertexFormat {
-    match kind {
-        PmxModelVertexLayoutElementKind::Position => VertexFormat::Float32x3,
-        PmxModelVertexLayoutElementKind::Normal => VertexFormat::Float32x3,
-        PmxModelVertexLayoutElementKind::TexCoord => VertexFormat::Float32x2,
-        PmxModelVertexLayoutElementKind::Tangent => VertexFormat::Float32x3,
-        PmxModelVertexLayoutElementKind::AdditionalVec4(_) => VertexFormat::Float32x4,
-        PmxModelVertexLayoutElementKind::DeformKind => VertexFormat::Uint32,
-        PmxModelVertexLayoutElementKind::BoneIndex => VertexFormat::Uint32x4,
-        PmxModelVertexLayoutElementKind::BoneWeight => VertexFormat::Float32x4,
-        PmxModelVertexLayoutElementKind::SdefC => VertexFormat::Float32x3,
-        PmxModelVertexLayoutElementKind::SdefR0 => VertexFormat::Float32x3,
-        PmxModelVertexLayoutElementKind::SdefR1 => VertexFormat::Float32x3,
-        PmxModelVertexLayoutElementKind::EdgeSize => VertexFormat::Float32,
-        PmxModelVertexLayoutElementKind::VertexMorphIndexStart => VertexFormat::Uint32,
-        PmxModelVertexLayoutElementKind::UvMorphIndexStart => VertexFormat::Uint32,
-        PmxModelVertexLayoutElementKind::VertexMorphCount => VertexFormat::Uint32,
-        PmxModelVertexLayoutElementKind::UvMorphCount => VertexFormat::Uint32,
+impl Component for PmxModelRenderer {
+    // a copy shares the geometry and owns its materials, morphs and pose; see
+    // `PmxModel::duplicate`
+    fn clone_boxed(&self) -> Option<Box<dyn Component>> {
+        Some(Box::new(Self::new(self.model.duplicate())))
+    }
+
+    // the model is a resource, so only the morph coefficients of this instance are saved
+    fn serialize(&self) -> Option<serde_json::Value> {
+        let morph = self.model.morph();
+        let morphs = morph
+            .names()
+            .filter_map(|name| Some((name, morph.coefficient(name)?)))
+            .filter(|(_, coefficient)| *coefficient != 0.0)
+            .collect::<BTreeMap<_, _>>();
+
+        Some(serde_json::json!({ "morphs": morphs }))
     }
-}
 
-impl Component for PmxModelRenderer {
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -229,3 +230,163 @@ impl Component for PmxModelRenderer {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gfx::{
+        elements::{make_material_render_state, make_material_shader_source},
+        test_gfx_ctx,
+    };
+    use lvl_math::{Mat4, Vec3, Vec4};
+    use lvl_resource::{
+        MaterialSource, PmxModelBone, PmxModelBoneFlags, PmxModelIndexKind, PmxModelMorph,
+        PmxModelMorphKind, PmxModelSource, PmxModelVertexLayoutElement, Resource, ResourceFile,
+        ResourceFileVersion, ResourceKind,
+    };
+
+    /// A triangle with a material, a vertex morph and a bone.
+    fn make_resource_file() -> ResourceFile {
+        let resource = |name: &str, kind| Resource {
+            name: name.to_owned(),
+            kind,
+            metadata: Default::default(),
+        };
+        let model = PmxModelSource::new(
+            vec![0; 3 * 12],
+            vec![PmxModelVertexLayoutElement {
+                kind: PmxModelVertexLayoutElementKind::Position,
+                offset: 0,
+            }],
+            vec![0, 0, 1, 0, 2, 0],
+            PmxModelIndexKind::U16,
+            vec![lvl_resource::PmxModelElement {
+                material_name: "material".to_owned(),
+                index_range: (0, 3),
+            }],
+            vec![PmxModelMorph {
+                name: "smile".to_owned(),
+                kind: PmxModelMorphKind::Vertex {
+                    displacement_range: (0, 0),
+                },
+            }],
+            vec![PmxModelBone {
+                name: "center".to_owned(),
+                position: Vec3::ZERO,
+                parent_index: None,
+                layer: 0,
+                flags: PmxModelBoneFlags {
+                    supports_ik: false,
+                    inherit_rotation: false,
+                    inherit_translation: false,
+                    local_coordinate: false,
+                    physics_after_deform: false,
+                },
+                inheritance: None,
+                ik: None,
+            }],
+            vec![Mat4::identity()],
+            String::new(),
+            String::new(),
+            String::new(),
+            String::new(),
+        );
+
+        ResourceFile::new(
+            ResourceFileVersion::CURRENT,
+            vec![
+                resource(
+                    "shader",
+                    ResourceKind::Shader(make_material_shader_source()),
+                ),
+                resource(
+                    "material",
+                    ResourceKind::Material(MaterialSource::new(
+                        "shader".to_owned(),
+                        make_material_render_state(),
+                        vec![],
+                    )),
+                ),
+                resource("model", ResourceKind::PmxModel(model)),
+            ],
+        )
+    }
+
+    #[test]
+    fn check_clone_boxed_is_independent() {
+        let gfx_ctx = match test_gfx_ctx() {
+            Some(gfx_ctx) => gfx_ctx,
+            None => {
+                return;
+            }
+        };
+        let resource = make_resource_file();
+        let source = resource.find::<PmxModelSource>("model").unwrap();
+        let mut renderer =
+            PmxModelRenderer::new(PmxModel::load_from_source(&resource, source, &gfx_ctx).unwrap());
+        let tint = Vec4::new(1.0, 0.0, 0.0, 1.0);
+        let pose = Mat4::translation(Vec3::new(0.0, 1.0, 0.0));
+
+        renderer.model_mut().set_morph("smile", 0.5);
+        renderer
+            .model_mut()
+            .skeleton_mut()
+            .set_bone_matrix(0, &pose);
+        assert!(renderer.override_material_property(
+            0,
+            "diffuse_color",
+            MaterialPropertyValue::Vec4(tint)
+        ));
+
+        let mut copy = renderer.clone_boxed().unwrap();
+        let copy = copy
+            .as_any_mut()
+            .downcast_mut::<PmxModelRenderer>()
+            .unwrap();
+
+        assert!(copy.model().shares_geometry_with(renderer.model()));
+        assert_eq!(copy.model().morph().coefficient("smile"), Some(0.5));
+        assert_eq!(
+            copy.model().skeleton().skinning_matrices(),
+            renderer.model().skeleton().skinning_matrices()
+        );
+        assert!(matches!(
+            copy.model().elements()[0]
+                .material
+                .get_property("diffuse_color")
+                .and_then(|property| property.value()),
+            Some(MaterialPropertyValue::Vec4(value)) if *value == tint
+        ));
+
+        // the copy owns its morphs and pose
+        copy.model_mut().set_morph("smile", 1.0);
+        copy.model_mut().skeleton_mut().reset_pose();
+        assert_eq!(renderer.model().morph().coefficient("smile"), Some(0.5));
+        assert_eq!(renderer.model().skeleton().skinning_matrices(), [pose]);
+
+        assert_eq!(
+            renderer.serialize(),
+            Some(serde_json::json!({ "morphs": { "smile": 0.5 } }))
+        );
+    }
+
+    #[test]
+    fn check_uv_channel_input_names() {
+        assert_eq!(
+            shader_input_name_from_vertex_layout_kind(PmxModelVertexLayoutElementKind::TexCoord),
+            "uv"
+        );
+        assert_eq!(
+            shader_input_name_from_vertex_layout_kind(
+                PmxModelVertexLayoutElementKind::AdditionalTexCoord(1)
+            ),
+            "uv_1_"
+        );
+        assert_eq!(
+            shader_input_name_from_vertex_layout_kind(
+                PmxModelVertexLayoutElementKind::AdditionalTexCoord(2)
+            ),
+            "uv_2_"
+        );
+    }
+}
@@ -149,6 +149,10 @@ impl UIElement {
 }
 
 impl Component for UIElement {
+    fn clone_boxed(&self) -> Option<Box<dyn Component>> {
+        Some(Box::new(self.clone()))
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
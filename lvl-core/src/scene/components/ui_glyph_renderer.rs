@@ -4,6 +4,7 @@ use crate::{
 };
 use std::{any::Any, sync::Arc};
 
+#[derive(Clone)]
 pub struct UIGlyphRenderer {
     pub font: Arc<Font>,
     pub layout_config: GlyphLayoutConfig,
@@ -21,6 +22,10 @@ impl UIGlyphRenderer {
 }
 
 impl Component for UIGlyphRenderer {
+    fn clone_boxed(&self) -> Option<Box<dyn Component>> {
+        Some(Box::new(self.clone()))
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
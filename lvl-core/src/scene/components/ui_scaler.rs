@@ -83,6 +83,10 @@ impl UIScaler {
 }
 
 impl Component for UIScaler {
+    fn clone_boxed(&self) -> Option<Box<dyn Component>> {
+        Some(Box::new(self.clone()))
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -1,7 +1,7 @@
 use crate::{gfx::elements::Sprite, scene::Component};
 use std::any::Any;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct UISpriteRenderer {
     pub sprite: Sprite,
 }
@@ -13,6 +13,10 @@ impl UISpriteRenderer {
 }
 
 impl Component for UISpriteRenderer {
+    fn clone_boxed(&self) -> Option<Box<dyn Component>> {
+        Some(Box::new(self.clone()))
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
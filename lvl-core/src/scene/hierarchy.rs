@@ -4,10 +4,13 @@ mod component_id;
 mod component_id_allocator;
 mod controller;
 mod object;
+mod object_duplication;
 mod object_id;
 mod object_id_allocator;
+mod object_snapshot;
 mod read_only_scene_proxy;
 mod scene;
+mod scene_document;
 mod scene_proxy;
 mod storage;
 mod transform;
@@ -18,10 +21,13 @@ pub use component_id::*;
 pub use component_id_allocator::*;
 pub use controller::*;
 pub use object::*;
+pub(crate) use object_duplication::*;
 pub use object_id::*;
 pub use object_id_allocator::*;
+pub use object_snapshot::*;
 pub use read_only_scene_proxy::*;
 pub use scene::*;
+pub use scene_document::*;
 pub use scene_proxy::*;
 pub use storage::*;
 pub use transform::*;
@@ -1,4 +1,4 @@
-use super::{Component, ComponentId};
+use super::{Component, ComponentId, ComponentIdAllocator};
 use std::any::TypeId;
 
 pub struct AnyComponent {
@@ -17,6 +17,28 @@ impl AnyComponent {
         }
     }
 
+    pub(crate) fn from_boxed(id: ComponentId, inner: Box<dyn Component>) -> Self {
+        Self { id, inner }
+    }
+
+    /// Copies the component with a new id; see [`Component::clone_boxed`].
+    pub(crate) fn try_clone(
+        &self,
+        component_id_allocator: &mut ComponentIdAllocator,
+    ) -> Option<Self> {
+        let inner = self.inner.clone_boxed()?;
+
+        Some(Self {
+            id: component_id_allocator.allocate(),
+            inner,
+        })
+    }
+
+    /// See [`Component::serialize`].
+    pub fn serialize(&self) -> Option<serde_json::Value> {
+        self.inner.serialize()
+    }
+
     pub fn id(&self) -> ComponentId {
         self.id
     }
@@ -8,6 +8,17 @@ where
         type_name::<Self>()
     }
 
+    /// Returns a copy of the component for [`crate::scene::SceneProxy::duplicate_subtree`], or
+    /// `None` if it cannot be copied, e.g. because it owns GPU resources of its own.
+    fn clone_boxed(&self) -> Option<Box<dyn Component>> {
+        None
+    }
+
+    /// Returns the state of the component as JSON, or `None` if it cannot be serialized.
+    fn serialize(&self) -> Option<serde_json::Value> {
+        None
+    }
+
     fn as_any(&self) -> &dyn Any;
     fn as_any_mut(&mut self) -> &mut dyn Any;
 }
@@ -6,6 +6,9 @@ pub trait Controller: Any {
     fn on_destroy(&mut self, _object_id: ObjectId, _scene: &mut SceneProxy) {}
     fn on_active(&mut self, _object_id: ObjectId, _scene: &mut SceneProxy) {}
     fn on_inactive(&mut self, _object_id: ObjectId, _scene: &mut SceneProxy) {}
+    /// Called once before the first update after the controller is attached,
+    /// on the first update its object is active.
+    fn on_start(&mut self, _object_id: ObjectId, _scene: &mut SceneProxy) {}
     fn on_update(&mut self, _object_id: ObjectId, _scene: &mut SceneProxy) {}
     fn on_late_update(&mut self, _object_id: ObjectId, _scene: &mut SceneProxy) {}
     fn on_event(
@@ -0,0 +1,182 @@
+use super::{
+    ComponentIdAllocator, HierarchyStorage, Object, ObjectId, ObjectIdAllocator, ObjectStorage,
+};
+use log::warn;
+use std::collections::HashMap;
+
+/// Copies the object and its children; see [`crate::scene::SceneProxy::duplicate_subtree`].
+pub(crate) fn duplicate_subtree(
+    object_id_allocator: &mut ObjectIdAllocator,
+    component_id_allocator: &mut ComponentIdAllocator,
+    object_storage: &mut ObjectStorage,
+    hierarchy_storage: &mut HierarchyStorage,
+    root_id: ObjectId,
+) -> Option<ObjectId> {
+    if !object_storage.is_exists(root_id) {
+        return None;
+    }
+
+    // parents come before their children
+    let object_ids = Vec::from(hierarchy_storage.object_and_children(root_id));
+    let mut copy_ids = HashMap::with_capacity(object_ids.len());
+
+    for object_id in object_ids {
+        let object = object_storage.get(object_id)?;
+        let components = object
+            .components()
+            .iter()
+            .filter_map(|component| {
+                let copy = component.try_clone(component_id_allocator);
+
+                if copy.is_none() {
+                    warn!(
+                        "the component `{}` of the object {:?} cannot be cloned; it is not duplicated",
+                        component.name(),
+                        object_id
+                    );
+                }
+
+                copy
+            })
+            .collect();
+
+        let copy_id = object_id_allocator.allocate();
+        let mut copy = Object::with_components(copy_id, components);
+        copy.set_transform(object.transform());
+        object_storage.add(copy);
+
+        let name = hierarchy_storage.name(object_id).to_owned();
+        hierarchy_storage.add(copy_id);
+        hierarchy_storage.set_name(copy_id, &name);
+
+        let parent_id = match hierarchy_storage.parent(object_id) {
+            Some(parent_id) if object_id != root_id => copy_ids.get(&parent_id).copied(),
+            parent_id => parent_id,
+        };
+
+        if parent_id.is_some() {
+            hierarchy_storage.set_parent(copy_id, parent_id);
+        }
+
+        if !hierarchy_storage.is_active_self(object_id) {
+            hierarchy_storage.set_active(copy_id, false);
+        }
+
+        copy_ids.insert(object_id, copy_id);
+    }
+
+    copy_ids.get(&root_id).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::{
+        components::{Light, LightKind},
+        AnyComponent, Transform,
+    };
+    use lvl_math::Vec3;
+
+    fn create_object(
+        object_id_allocator: &mut ObjectIdAllocator,
+        object_storage: &mut ObjectStorage,
+        hierarchy_storage: &mut HierarchyStorage,
+        name: &str,
+        position: Vec3,
+        components: Vec<AnyComponent>,
+        parent_id: Option<ObjectId>,
+    ) -> ObjectId {
+        let object_id = object_id_allocator.allocate();
+        let mut object = Object::with_components(object_id, components);
+        object.set_transform(Transform {
+            position,
+            ..Transform::identity()
+        });
+        object_storage.add(object);
+        hierarchy_storage.add(object_id);
+        hierarchy_storage.set_name(object_id, name);
+        hierarchy_storage.set_parent(object_id, parent_id);
+        object_id
+    }
+
+    #[test]
+    fn check_duplicate_two_levels() {
+        let mut object_ids = ObjectIdAllocator::new();
+        let mut component_ids = ComponentIdAllocator::new();
+        let mut objects = ObjectStorage::new();
+        let mut hierarchy = HierarchyStorage::new();
+        let light = Light {
+            kind: LightKind::Point,
+            light_color: Vec3::new(1.0, 0.5, 0.0),
+        };
+
+        let scene_root = create_object(
+            &mut object_ids,
+            &mut objects,
+            &mut hierarchy,
+            "scene",
+            Vec3::ZERO,
+            vec![],
+            None,
+        );
+        let root = create_object(
+            &mut object_ids,
+            &mut objects,
+            &mut hierarchy,
+            "lamp",
+            Vec3::new(1.0, 2.0, 3.0),
+            vec![],
+            Some(scene_root),
+        );
+        let bulb = create_object(
+            &mut object_ids,
+            &mut objects,
+            &mut hierarchy,
+            "bulb",
+            Vec3::new(0.0, 1.0, 0.0),
+            vec![AnyComponent::new(component_ids.allocate(), light)],
+            Some(root),
+        );
+
+        let copy = duplicate_subtree(
+            &mut object_ids,
+            &mut component_ids,
+            &mut objects,
+            &mut hierarchy,
+            root,
+        )
+        .unwrap();
+
+        assert_ne!(copy, root);
+        assert_eq!(hierarchy.name(copy), "lamp");
+        assert_eq!(hierarchy.parent(copy), Some(scene_root));
+        assert_eq!(
+            objects.get(copy).unwrap().transform(),
+            objects.get(root).unwrap().transform()
+        );
+
+        let copy_children = hierarchy.children(copy).to_vec();
+        assert_eq!(copy_children.len(), 1);
+
+        let copy_bulb = copy_children[0];
+        assert_ne!(copy_bulb, bulb);
+        assert_eq!(hierarchy.name(copy_bulb), "bulb");
+        assert_eq!(hierarchy.parent(copy_bulb), Some(copy));
+        assert_eq!(
+            objects.get(copy_bulb).unwrap().transform(),
+            objects.get(bulb).unwrap().transform()
+        );
+
+        let original_component = &objects.get(bulb).unwrap().components()[0];
+        let copy_component = &objects.get(copy_bulb).unwrap().components()[0];
+        assert_ne!(copy_component.id(), original_component.id());
+        assert_eq!(copy_component.downcast_ref::<Light>(), Some(&light));
+        assert!(objects
+            .object_ids_with_component::<Light>()
+            .unwrap()
+            .contains(&copy_bulb));
+
+        // the original is left as is
+        assert_eq!(hierarchy.children(root), [bulb]);
+    }
+}
@@ -0,0 +1,231 @@
+use super::{HierarchyStorage, Object, ObjectId, ObjectIdAllocator, ObjectStorage, Transform};
+use crate::context::clipboard::ClipboardError;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ObjectSnapshotError {
+    #[error("the object does not exist")]
+    ObjectNotFound,
+    #[error("the snapshot has no objects")]
+    Empty,
+    #[error("the text is not an object snapshot: {0}")]
+    InvalidText(#[from] serde_json::Error),
+    #[error(transparent)]
+    Clipboard(#[from] ClipboardError),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ObjectSnapshotEntry {
+    pub name: String,
+    pub transform: Transform,
+    pub is_active_self: bool,
+    /// Index of the parent entry in the snapshot; `None` for the root.
+    pub parent: Option<usize>,
+}
+
+/// A copy of an object and its children, in hierarchy order. Components and controllers
+/// are type-erased and cannot be serialized, so they are not included.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ObjectSnapshot {
+    entries: Vec<ObjectSnapshotEntry>,
+}
+
+impl ObjectSnapshot {
+    pub(crate) fn capture(
+        object_storage: &ObjectStorage,
+        hierarchy_storage: &HierarchyStorage,
+        object_id: ObjectId,
+    ) -> Option<Self> {
+        let object_ids = hierarchy_storage.object_and_children(object_id);
+        let entries = object_ids
+            .iter()
+            .enumerate()
+            .map(|(index, &object_id)| {
+                // the root is detached from its parent, which is not in the snapshot
+                let parent = match index {
+                    0 => None,
+                    _ => hierarchy_storage.parent(object_id).and_then(|parent_id| {
+                        object_ids[..index].iter().position(|&id| id == parent_id)
+                    }),
+                };
+
+                Some(ObjectSnapshotEntry {
+                    name: hierarchy_storage.name(object_id).to_owned(),
+                    transform: object_storage.get(object_id)?.transform(),
+                    is_active_self: hierarchy_storage.is_active_self(object_id),
+                    parent,
+                })
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(Self { entries })
+    }
+
+    pub fn entries(&self) -> &[ObjectSnapshotEntry] {
+        &self.entries
+    }
+
+    pub fn to_text(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    pub fn from_text(text: &str) -> Result<Self, ObjectSnapshotError> {
+        Ok(serde_json::from_str(text)?)
+    }
+
+    /// Creates the objects of the snapshot and returns the root, which is placed under the given
+    /// parent. Returns `None` if the snapshot is empty.
+    pub(crate) fn spawn(
+        &self,
+        object_id_allocator: &mut ObjectIdAllocator,
+        object_storage: &mut ObjectStorage,
+        hierarchy_storage: &mut HierarchyStorage,
+        parent_id: Option<ObjectId>,
+    ) -> Option<ObjectId> {
+        let mut object_ids = Vec::with_capacity(self.entries.len());
+
+        for entry in &self.entries {
+            let object_id = object_id_allocator.allocate();
+            let mut object = Object::new(object_id);
+            object.set_transform(entry.transform.clone());
+            object_storage.add(object);
+            hierarchy_storage.add(object_id);
+            hierarchy_storage.set_name(object_id, &entry.name);
+
+            let parent_id = match entry.parent {
+                Some(index) => object_ids.get(index).copied().or(parent_id),
+                None => parent_id,
+            };
+
+            if parent_id.is_some() {
+                hierarchy_storage.set_parent(object_id, parent_id);
+            }
+
+            if !entry.is_active_self {
+                hierarchy_storage.set_active(object_id, false);
+            }
+
+            object_ids.push(object_id);
+        }
+
+        object_ids.first().copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::clipboard::{Clipboard, ClipboardBackend};
+    use lvl_math::Vec3;
+
+    struct MockClipboard {
+        text: Option<String>,
+    }
+
+    impl ClipboardBackend for MockClipboard {
+        fn get_text(&mut self) -> Result<String, ClipboardError> {
+            self.text.clone().ok_or(ClipboardError::NoText)
+        }
+
+        fn set_text(&mut self, text: &str) -> Result<(), ClipboardError> {
+            self.text = Some(text.to_owned());
+            Ok(())
+        }
+    }
+
+    fn create_object(
+        object_id_allocator: &mut ObjectIdAllocator,
+        object_storage: &mut ObjectStorage,
+        hierarchy_storage: &mut HierarchyStorage,
+        name: &str,
+        position: Vec3,
+        parent_id: Option<ObjectId>,
+    ) -> ObjectId {
+        let object_id = object_id_allocator.allocate();
+        let mut object = Object::new(object_id);
+        object.set_transform(Transform {
+            position,
+            ..Transform::identity()
+        });
+        object_storage.add(object);
+        hierarchy_storage.add(object_id);
+        hierarchy_storage.set_name(object_id, name);
+        hierarchy_storage.set_parent(object_id, parent_id);
+        object_id
+    }
+
+    #[test]
+    fn check_copy_and_paste_subtree() {
+        let mut allocator = ObjectIdAllocator::new();
+        let mut objects = ObjectStorage::new();
+        let mut hierarchy = HierarchyStorage::new();
+        let mut clipboard = Clipboard::with_backend(MockClipboard { text: None });
+
+        let root = create_object(
+            &mut allocator,
+            &mut objects,
+            &mut hierarchy,
+            "root",
+            Vec3::new(1.0, 2.0, 3.0),
+            None,
+        );
+        let arm = create_object(
+            &mut allocator,
+            &mut objects,
+            &mut hierarchy,
+            "arm",
+            Vec3::new(0.0, 1.0, 0.0),
+            Some(root),
+        );
+        create_object(
+            &mut allocator,
+            &mut objects,
+            &mut hierarchy,
+            "hand",
+            Vec3::new(0.0, 0.5, 0.0),
+            Some(arm),
+        );
+        create_object(
+            &mut allocator,
+            &mut objects,
+            &mut hierarchy,
+            "leg",
+            Vec3::new(0.0, -1.0, 0.0),
+            Some(root),
+        );
+        hierarchy.set_active(arm, false);
+
+        let snapshot = ObjectSnapshot::capture(&objects, &hierarchy, root).unwrap();
+        clipboard.set_text(&snapshot.to_text()).unwrap();
+
+        let pasted = ObjectSnapshot::from_text(&clipboard.get_text().unwrap()).unwrap();
+        let copy = pasted
+            .spawn(&mut allocator, &mut objects, &mut hierarchy, None)
+            .unwrap();
+
+        assert_ne!(copy, root);
+        assert_eq!(hierarchy.name(copy), "root");
+        assert_eq!(hierarchy.parent(copy), None);
+        assert_eq!(
+            hierarchy
+                .children(copy)
+                .iter()
+                .map(|&id| (hierarchy.name(id), hierarchy.is_active_self(id)))
+                .collect::<Vec<_>>(),
+            [("arm", false), ("hand", true), ("leg", true)]
+        );
+        assert_eq!(
+            ObjectSnapshot::capture(&objects, &hierarchy, copy).unwrap(),
+            snapshot
+        );
+    }
+
+    #[test]
+    fn check_invalid_text() {
+        assert!(matches!(
+            ObjectSnapshot::from_text("not a snapshot"),
+            Err(ObjectSnapshotError::InvalidText(_))
+        ));
+    }
+}
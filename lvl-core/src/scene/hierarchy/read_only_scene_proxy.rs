@@ -10,7 +10,7 @@ impl<'scene, 'window> ReadOnlySceneProxy<'scene, 'window> {
         Self { scene_proxy }
     }
 
-    pub fn scene(&self) -> &SceneProxy {
+    pub fn scene(&self) -> &SceneProxy<'scene, 'window> {
         &self.scene_proxy
     }
 }
@@ -1,13 +1,21 @@
+mod environment;
+mod playback;
 mod ui;
 
+pub use self::{environment::Environment, playback::ScenePlayback};
+
 use self::ui::{broadcast_ui_scaler_dirty, mark_root_ui_scaler_dirty, update_ui};
 
 use super::{
     ComponentIdAllocator, ControllerStorage, EventReceiverStorage, HierarchyStorage,
-    ObjectIdAllocator, ObjectStorage, ReadOnlySceneProxy, SceneActionItem, SceneActionResult,
-    SceneProxy,
+    ObjectIdAllocator, ObjectStorage, ReadOnlySceneProxy, SceneActionItem, SceneActionQueue,
+    SceneActionResult, SceneProxy,
+};
+use crate::{
+    context::{screen_size::ScreenSize, Context},
+    scene::components::PmxModelRenderer,
 };
-use crate::context::{screen_size::ScreenSize, Context};
+use std::collections::HashSet;
 use winit::window::Window;
 
 pub struct Scene<'ctx, 'window: 'ctx> {
@@ -19,6 +27,9 @@ pub struct Scene<'ctx, 'window: 'ctx> {
     hierarchy_storage: HierarchyStorage,
     controller_storage: ControllerStorage,
     event_receiver_storage: EventReceiverStorage,
+    playback: ScenePlayback,
+    environment: Environment,
+    is_updating: bool,
 }
 
 impl<'ctx, 'window: 'ctx> Scene<'ctx, 'window> {
@@ -32,10 +43,70 @@ impl<'ctx, 'window: 'ctx> Scene<'ctx, 'window> {
             hierarchy_storage: HierarchyStorage::new(),
             controller_storage: ControllerStorage::new(),
             event_receiver_storage: EventReceiverStorage::new(),
+            playback: ScenePlayback::new(),
+            environment: Environment::default(),
+            is_updating: true,
         }
     }
 
-    pub fn read_only_proxy(&mut self) -> ReadOnlySceneProxy {
+    pub fn playback(&self) -> &ScenePlayback {
+        &self.playback
+    }
+
+    pub fn environment(&self) -> &Environment {
+        &self.environment
+    }
+
+    /// Sets the lighting of the scene, which is written into the materials of the PMX models
+    /// before rendering.
+    pub fn set_environment(&mut self, environment: Environment) {
+        self.environment = environment;
+    }
+
+    pub fn time_scale(&self) -> f32 {
+        self.playback.time_scale()
+    }
+
+    /// Scales the delta time of [`crate::context::time::Time`] from the next frame.
+    pub fn set_time_scale(&mut self, time_scale: f32) {
+        self.playback.set_time_scale(time_scale);
+        self.sync_time_scale();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.playback.is_paused()
+    }
+
+    /// Stops updating the controllers, and stops the scaled time, from the next frame.
+    /// The scene is still rendered, and the drivers are still called.
+    pub fn set_paused(&mut self, is_paused: bool) {
+        self.playback.set_paused(is_paused);
+        self.sync_time_scale();
+    }
+
+    /// Runs exactly one more update while paused, in the next frame.
+    pub fn step(&mut self) {
+        self.playback.step();
+        self.sync_time_scale();
+    }
+
+    fn sync_time_scale(&self) {
+        let time_scale = self.playback.effective_time_scale() as f64;
+        let mut time = self.context.time_mut();
+
+        if time.time_scale() != time_scale {
+            time.set_time_scale(time_scale);
+        }
+    }
+
+    /// Decides whether the controllers are updated in this frame.
+    pub(crate) fn begin_frame(&mut self) {
+        self.is_updating = self.playback.begin_update();
+        // a consumed step pauses the time again from the next frame
+        self.sync_time_scale();
+    }
+
+    pub fn read_only_proxy(&mut self) -> ReadOnlySceneProxy<'_, 'window> {
         ReadOnlySceneProxy::new(SceneProxy::new(
             self.context,
             self.window,
@@ -62,6 +133,10 @@ impl<'ctx, 'window: 'ctx> Scene<'ctx, 'window> {
     }
 
     pub(crate) fn trigger_update(&mut self) {
+        if !self.is_updating {
+            return;
+        }
+
         let mut scene = SceneProxy::new(
             self.context,
             self.window,
@@ -77,6 +152,10 @@ impl<'ctx, 'window: 'ctx> Scene<'ctx, 'window> {
     }
 
     pub(crate) fn trigger_late_update(&mut self) {
+        if !self.is_updating {
+            return;
+        }
+
         let mut scene = SceneProxy::new(
             self.context,
             self.window,
@@ -91,8 +170,12 @@ impl<'ctx, 'window: 'ctx> Scene<'ctx, 'window> {
         self.handle_context_result(result);
     }
 
-    fn handle_context_result(&mut self, mut result: SceneActionResult) {
-        while !result.action_queue.is_empty() {
+    /// Processes the deferred actions; see [`SceneActionQueue`] for the ordering.
+    fn handle_context_result(&mut self, result: SceneActionResult) {
+        let mut queue = SceneActionQueue::new();
+        queue.append(result);
+
+        while let Some(action) = queue.pop() {
             let mut scene = SceneProxy::new(
                 self.context,
                 self.window,
@@ -102,82 +185,86 @@ impl<'ctx, 'window: 'ctx> Scene<'ctx, 'window> {
                 &mut self.hierarchy_storage,
             );
 
-            for action in result.action_queue {
-                match action {
-                    SceneActionItem::RemoveObject { object_id } => {
-                        if !scene.object_storage().is_exists(object_id) {
-                            continue;
-                        }
-
-                        let removed_hierarchy_object_ids =
-                            Vec::from(scene.hierarchy_storage().object_and_children(object_id));
-
-                        for &removed_object_id in removed_hierarchy_object_ids.iter().rev() {
-                            self.controller_storage
-                                .detach_controller(removed_object_id, &mut scene);
-                        }
+            // the object may have been removed by a previous action
+            if let Some(object_id) = action.object_id() {
+                if !scene.object_storage().is_exists(object_id) {
+                    continue;
+                }
+            }
 
-                        for &removed_object_id in removed_hierarchy_object_ids.iter().rev() {
-                            self.event_receiver_storage.unlisten_all(removed_object_id);
-                            scene.object_storage_mut().remove(removed_object_id);
-                            scene.object_id_allocator_mut().deallocate(object_id);
-                        }
+            match action {
+                SceneActionItem::RemoveObject { object_id } => {
+                    let removed_hierarchy_object_ids =
+                        Vec::from(scene.hierarchy_storage().object_and_children(object_id));
 
-                        scene.hierarchy_storage_mut().remove(object_id);
-                    }
-                    SceneActionItem::TriggerOnActive { object_id } => {
+                    for &removed_object_id in removed_hierarchy_object_ids.iter().rev() {
                         self.controller_storage
-                            .invoke_on_active(object_id, &mut scene);
+                            .detach_controller(removed_object_id, &mut scene);
                     }
-                    SceneActionItem::TriggerOnInactive { object_id } => {
-                        self.controller_storage
-                            .invoke_on_inactive(object_id, &mut scene);
-                    }
-                    SceneActionItem::AttachController {
-                        object_id,
-                        controller,
-                    } => {
-                        self.controller_storage
-                            .attach_controller(object_id, controller, &mut scene);
-                    }
-                    SceneActionItem::DetachController { object_id } => {
-                        self.event_receiver_storage.unlisten_all(object_id);
-                        self.controller_storage
-                            .detach_controller(object_id, &mut scene);
-                    }
-                    SceneActionItem::ListenOnUpdate { object_id } => {
-                        self.controller_storage.listen_on_update(object_id);
-                    }
-                    SceneActionItem::UnlistenOnUpdate { object_id } => {
-                        self.controller_storage.unlisten_on_update(object_id);
-                    }
-                    SceneActionItem::ListenOnLateUpdate { object_id } => {
-                        self.controller_storage.listen_on_late_update(object_id);
-                    }
-                    SceneActionItem::UnlistenOnLateUpdate { object_id } => {
-                        self.controller_storage.unlisten_on_late_update(object_id);
-                    }
-                    SceneActionItem::ListenEvent { event, object_id } => {
-                        self.event_receiver_storage.listen(event, object_id);
-                    }
-                    SceneActionItem::UnlistenEvent { event, object_id } => {
-                        self.event_receiver_storage.unlisten(event, object_id);
-                    }
-                    SceneActionItem::UnlistenEventAll { object_id } => {
-                        self.event_receiver_storage.unlisten_all(object_id);
-                    }
-                    SceneActionItem::EmitEvent { event, param } => {
-                        self.event_receiver_storage.emit(
-                            &event,
-                            &param,
-                            &mut scene,
-                            &mut self.controller_storage,
-                        );
+
+                    for &removed_object_id in removed_hierarchy_object_ids.iter().rev() {
+                        self.event_receiver_storage.unlisten_all(removed_object_id);
+                        scene.object_storage_mut().remove(removed_object_id);
+                        scene
+                            .object_id_allocator_mut()
+                            .deallocate(removed_object_id);
                     }
+
+                    scene.hierarchy_storage_mut().remove(object_id);
+                    queue.cancel_object_actions(&HashSet::from_iter(removed_hierarchy_object_ids));
+                }
+                SceneActionItem::TriggerOnActive { object_id } => {
+                    self.controller_storage
+                        .invoke_on_active(object_id, &mut scene);
+                }
+                SceneActionItem::TriggerOnInactive { object_id } => {
+                    self.controller_storage
+                        .invoke_on_inactive(object_id, &mut scene);
+                }
+                SceneActionItem::AttachController {
+                    object_id,
+                    controller,
+                } => {
+                    self.controller_storage
+                        .attach_controller(object_id, controller, &mut scene);
+                }
+                SceneActionItem::DetachController { object_id } => {
+                    self.event_receiver_storage.unlisten_all(object_id);
+                    self.controller_storage
+                        .detach_controller(object_id, &mut scene);
+                }
+                SceneActionItem::ListenOnUpdate { object_id } => {
+                    self.controller_storage.listen_on_update(object_id);
+                }
+                SceneActionItem::UnlistenOnUpdate { object_id } => {
+                    self.controller_storage.unlisten_on_update(object_id);
+                }
+                SceneActionItem::ListenOnLateUpdate { object_id } => {
+                    self.controller_storage.listen_on_late_update(object_id);
+                }
+                SceneActionItem::UnlistenOnLateUpdate { object_id } => {
+                    self.controller_storage.unlisten_on_late_update(object_id);
+                }
+                SceneActionItem::ListenEvent { event, object_id } => {
+                    self.event_receiver_storage.listen(event, object_id);
+                }
+                SceneActionItem::UnlistenEvent { event, object_id } => {
+                    self.event_receiver_storage.unlisten(event, object_id);
+                }
+                SceneActionItem::UnlistenEventAll { object_id } => {
+                    self.event_receiver_storage.unlisten_all(object_id);
+                }
+                SceneActionItem::EmitEvent { event, param } => {
+                    self.event_receiver_storage.emit(
+                        &event,
+                        &param,
+                        &mut scene,
+                        &mut self.controller_storage,
+                    );
                 }
             }
 
-            result = scene.into_result();
+            queue.append(scene.into_result());
         }
     }
 
@@ -203,5 +290,30 @@ impl<'ctx, 'window: 'ctx> Scene<'ctx, 'window> {
             screen_size.width,
             screen_size.height,
         );
+
+        self.apply_environment();
+    }
+
+    fn apply_environment(&mut self) {
+        let object_ids = match self
+            .object_storage
+            .object_ids_with_component::<PmxModelRenderer>()
+        {
+            Some(object_ids) => object_ids.iter().copied().collect::<Vec<_>>(),
+            None => return,
+        };
+
+        for object_id in object_ids {
+            let object = match self.object_storage.get_mut(object_id) {
+                Some(object) => object,
+                None => continue,
+            };
+
+            for renderer in object.find_components_by_type_mut::<PmxModelRenderer>() {
+                for element in renderer.model_mut().elements_mut() {
+                    self.environment.apply(&mut element.material);
+                }
+            }
+        }
     }
 }
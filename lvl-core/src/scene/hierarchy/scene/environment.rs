@@ -0,0 +1,89 @@
+use crate::gfx::elements::{Material, MaterialPropertyValue};
+use lvl_math::Vec3;
+use serde::{Deserialize, Serialize};
+
+/// Lighting of a whole [`crate::scene::Scene`], saved along with it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Environment {
+    /// Added to the ambient color of every material.
+    pub ambient_color: Vec3,
+    /// Direction the main light travels in; it does not have to be normalized.
+    pub light_direction: Vec3,
+    pub light_color: Vec3,
+    /// Name of the skybox texture, if any.
+    pub skybox: Option<String>,
+}
+
+impl Environment {
+    pub fn to_text(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    pub fn from_text(text: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(text)
+    }
+
+    /// Returns the material properties of the standard shaders that this environment sets.
+    pub(crate) fn material_properties(&self) -> [(&'static str, MaterialPropertyValue); 3] {
+        [
+            (
+                "environment_ambient_color",
+                MaterialPropertyValue::Vec3(self.ambient_color),
+            ),
+            (
+                "light_direction",
+                MaterialPropertyValue::Vec3(self.light_direction.normalized()),
+            ),
+            ("light_color", MaterialPropertyValue::Vec3(self.light_color)),
+        ]
+    }
+
+    /// Writes the environment into the material. Properties that already have the values are not
+    /// set again, so that the bind groups of the material are not rebuilt every frame.
+    pub(crate) fn apply(&self, material: &mut Material) {
+        for (name, value) in self.material_properties() {
+            let current = material
+                .get_property(name)
+                .and_then(|property| property.value())
+                .and_then(|value| value.as_bytes());
+
+            if current != value.as_bytes() {
+                material.set_property(name, value);
+            }
+        }
+    }
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Self {
+            ambient_color: Vec3::ZERO,
+            light_direction: Vec3::new(1.0, -1.0, -1.0),
+            light_color: Vec3::ONE,
+            skybox: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_environment_round_trip() {
+        let environment = Environment {
+            ambient_color: Vec3::new(0.1, 0.2, 0.3),
+            skybox: Some("sky".to_owned()),
+            ..Environment::default()
+        };
+
+        let reloaded = Environment::from_text(&environment.to_text()).unwrap();
+        assert_eq!(reloaded, environment);
+
+        let (name, value) = &reloaded.material_properties()[0];
+        assert_eq!(*name, "environment_ambient_color");
+        assert!(
+            matches!(value, MaterialPropertyValue::Vec3(color) if *color == Vec3::new(0.1, 0.2, 0.3))
+        );
+    }
+}
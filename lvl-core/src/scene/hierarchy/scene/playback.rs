@@ -0,0 +1,161 @@
+/// Play, pause and slow-motion state of a [`crate::scene::Scene`].
+/// While paused, the controllers are not updated, but the scene is still rendered.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScenePlayback {
+    time_scale: f32,
+    is_paused: bool,
+    pending_steps: u32,
+}
+
+impl ScenePlayback {
+    pub fn new() -> Self {
+        Self {
+            time_scale: 1.0,
+            is_paused: false,
+            pending_steps: 0,
+        }
+    }
+
+    pub fn time_scale(&self) -> f32 {
+        self.time_scale
+    }
+
+    /// Scales the delta time seen by the controllers. Negative scales are clamped to zero.
+    pub fn set_time_scale(&mut self, time_scale: f32) {
+        self.time_scale = time_scale.max(0.0);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.is_paused
+    }
+
+    pub fn set_paused(&mut self, is_paused: bool) {
+        self.is_paused = is_paused;
+
+        if !is_paused {
+            self.pending_steps = 0;
+        }
+    }
+
+    /// Requests one more update while paused. Does nothing if not paused.
+    pub fn step(&mut self) {
+        if self.is_paused {
+            self.pending_steps += 1;
+        }
+    }
+
+    /// Returns the scale of the next frame's delta time; zero while paused, unless stepping.
+    pub fn effective_time_scale(&self) -> f32 {
+        if self.is_paused && self.pending_steps == 0 {
+            0.0
+        } else {
+            self.time_scale
+        }
+    }
+
+    /// Returns `true` if the controllers should be updated this frame, consuming a step if paused.
+    pub(crate) fn begin_update(&mut self) -> bool {
+        if !self.is_paused {
+            return true;
+        }
+
+        if self.pending_steps == 0 {
+            return false;
+        }
+
+        self.pending_steps -= 1;
+        true
+    }
+}
+
+impl Default for ScenePlayback {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::time::Time;
+    use std::time::{Duration, Instant};
+
+    /// Runs a frame of the looper, returning the delta time seen by the update, if it ran.
+    fn run_frame(
+        playback: &mut ScenePlayback,
+        time: &mut Time,
+        now: Instant,
+        render_count: &mut u32,
+    ) -> Option<Duration> {
+        time.update_at(now);
+
+        let delta_time = if playback.begin_update() {
+            Some(time.delta_time())
+        } else {
+            None
+        };
+        time.set_time_scale(playback.effective_time_scale() as f64);
+
+        *render_count += 1;
+        delta_time
+    }
+
+    #[test]
+    fn check_time_scale_and_pause() {
+        let mut playback = ScenePlayback::new();
+        let mut now = Instant::now();
+        let mut time = Time::new_at(now);
+        let mut render_count = 0;
+        let frame = Duration::from_millis(100);
+
+        playback.set_time_scale(0.5);
+        time.set_time_scale(playback.effective_time_scale() as f64);
+        now += frame;
+        assert_eq!(
+            run_frame(&mut playback, &mut time, now, &mut render_count),
+            Some(Duration::from_millis(50))
+        );
+
+        playback.set_paused(true);
+        time.set_time_scale(playback.effective_time_scale() as f64);
+
+        for _ in 0..3 {
+            now += frame;
+            assert_eq!(
+                run_frame(&mut playback, &mut time, now, &mut render_count),
+                None
+            );
+        }
+
+        assert_eq!(render_count, 4);
+        assert_eq!(time.delta_time(), Duration::ZERO);
+    }
+
+    #[test]
+    fn check_step_while_paused() {
+        let mut playback = ScenePlayback::new();
+        let mut now = Instant::now();
+        let mut time = Time::new_at(now);
+        let mut render_count = 0;
+        let frame = Duration::from_millis(100);
+
+        playback.set_paused(true);
+        playback.step();
+        time.set_time_scale(playback.effective_time_scale() as f64);
+
+        now += frame;
+        assert_eq!(
+            run_frame(&mut playback, &mut time, now, &mut render_count),
+            Some(frame)
+        );
+        now += frame;
+        assert_eq!(
+            run_frame(&mut playback, &mut time, now, &mut render_count),
+            None
+        );
+
+        playback.set_paused(false);
+        playback.step();
+        assert_eq!(playback.effective_time_scale(), 1.0);
+    }
+}
@@ -0,0 +1,563 @@
+use super::{
+    AnyComponent, Component, ComponentIdAllocator, HierarchyStorage, Object, ObjectId,
+    ObjectIdAllocator, ObjectStorage, Transform,
+};
+use crate::scene::components::{Camera, Light};
+use log::warn;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    any::TypeId,
+    collections::{HashMap, HashSet},
+};
+use thiserror::Error;
+
+/// The version of the [`SceneDocument`] format written by this build.
+pub const SCENE_DOCUMENT_VERSION: u32 = 1;
+
+#[derive(Error, Debug)]
+pub enum SceneDocumentError {
+    #[error("the text is not a scene document: {0}")]
+    InvalidText(#[from] serde_json::Error),
+    #[error("the scene document version {0} is not supported; expected {SCENE_DOCUMENT_VERSION}")]
+    UnsupportedVersion(u32),
+    #[error("the object {id} appears more than once")]
+    DuplicateObject { id: u32 },
+    #[error("the parent {parent} of the object {id} does not come before it")]
+    InvalidParent { id: u32, parent: u32 },
+    #[error("the component `{name}` of the object {id} is not registered")]
+    UnknownComponent { id: u32, name: String },
+    #[error("failed to deserialize the component `{name}` of the object {id}: {source}")]
+    InvalidComponent {
+        id: u32,
+        name: String,
+        source: serde_json::Error,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SceneDocumentComponent {
+    /// The name the component is registered with in the [`ComponentRegistry`].
+    pub name: String,
+    pub data: serde_json::Value,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SceneDocumentObject {
+    /// Id of the object when the document was exported; loaded objects get new ids.
+    pub id: u32,
+    pub name: String,
+    pub transform: Transform,
+    pub is_active_self: bool,
+    /// Id of the parent object, which comes before this one in the document.
+    pub parent: Option<u32>,
+    pub components: Vec<SceneDocumentComponent>,
+}
+
+/// All the objects of a scene, in hierarchy order, with the registered components that can be
+/// serialized; see [`Component::serialize`]. Controllers are not included.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SceneDocument {
+    /// The format version; see [`SCENE_DOCUMENT_VERSION`].
+    version: u32,
+    objects: Vec<SceneDocumentObject>,
+}
+
+impl Default for SceneDocument {
+    fn default() -> Self {
+        Self {
+            version: SCENE_DOCUMENT_VERSION,
+            objects: Vec::new(),
+        }
+    }
+}
+
+impl SceneDocument {
+    pub(crate) fn capture(
+        registry: &ComponentRegistry,
+        object_storage: &ObjectStorage,
+        hierarchy_storage: &HierarchyStorage,
+    ) -> Self {
+        let objects = hierarchy_storage
+            .objects()
+            .iter()
+            .filter_map(|&object_id| {
+                let object = object_storage.get(object_id)?;
+                let components = object
+                    .components()
+                    .iter()
+                    .filter_map(|component| {
+                        let name = match registry.name_of(component.type_id()) {
+                            Some(name) => name,
+                            None => {
+                                warn!(
+                                    "the component `{}` of the object {:?} is not registered; it is not saved",
+                                    component.name(),
+                                    object_id
+                                );
+                                return None;
+                            }
+                        };
+                        let data = component.serialize();
+
+                        if data.is_none() {
+                            warn!(
+                                "the component `{}` of the object {:?} cannot be serialized; it is not saved",
+                                name, object_id
+                            );
+                        }
+
+                        Some(SceneDocumentComponent {
+                            name: name.to_owned(),
+                            data: data?,
+                        })
+                    })
+                    .collect();
+
+                Some(SceneDocumentObject {
+                    id: object_id.get().get(),
+                    name: hierarchy_storage.name(object_id).to_owned(),
+                    transform: object.transform(),
+                    is_active_self: hierarchy_storage.is_active_self(object_id),
+                    parent: hierarchy_storage
+                        .parent(object_id)
+                        .map(|parent_id| parent_id.get().get()),
+                    components,
+                })
+            })
+            .collect();
+
+        Self {
+            version: SCENE_DOCUMENT_VERSION,
+            objects,
+        }
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    pub fn objects(&self) -> &[SceneDocumentObject] {
+        &self.objects
+    }
+
+    pub fn to_text(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    pub fn from_text(text: &str) -> Result<Self, SceneDocumentError> {
+        let document: Self = serde_json::from_str(text)?;
+        document.check_version()?;
+        Ok(document)
+    }
+
+    fn check_version(&self) -> Result<(), SceneDocumentError> {
+        if self.version != SCENE_DOCUMENT_VERSION {
+            return Err(SceneDocumentError::UnsupportedVersion(self.version));
+        }
+
+        Ok(())
+    }
+
+    /// Creates the objects of the document alongside the existing ones and returns their new
+    /// ids by the ids in the document. Nothing is created if the document is invalid.
+    pub(crate) fn spawn(
+        &self,
+        registry: &ComponentRegistry,
+        object_id_allocator: &mut ObjectIdAllocator,
+        component_id_allocator: &mut ComponentIdAllocator,
+        object_storage: &mut ObjectStorage,
+        hierarchy_storage: &mut HierarchyStorage,
+    ) -> Result<HashMap<u32, ObjectId>, SceneDocumentError> {
+        self.check_version()?;
+
+        let mut ids = HashSet::with_capacity(self.objects.len());
+        let mut object_components = Vec::with_capacity(self.objects.len());
+
+        for object in &self.objects {
+            if let Some(parent) = object.parent {
+                if !ids.contains(&parent) {
+                    return Err(SceneDocumentError::InvalidParent {
+                        id: object.id,
+                        parent,
+                    });
+                }
+            }
+
+            if !ids.insert(object.id) {
+                return Err(SceneDocumentError::DuplicateObject { id: object.id });
+            }
+
+            let components = object
+                .components
+                .iter()
+                .map(|component| registry.deserialize(object.id, component))
+                .collect::<Result<Vec<_>, _>>()?;
+            object_components.push(components);
+        }
+
+        let mut object_ids = HashMap::with_capacity(self.objects.len());
+
+        for (entry, components) in self.objects.iter().zip(object_components) {
+            let object_id = object_id_allocator.allocate();
+            let components = Vec::from_iter(components.into_iter().map(|component| {
+                AnyComponent::from_boxed(component_id_allocator.allocate(), component)
+            }));
+            let mut object = Object::with_components(object_id, components);
+            object.set_transform(entry.transform.clone());
+            object_storage.add(object);
+            hierarchy_storage.add(object_id);
+            hierarchy_storage.set_name(object_id, &entry.name);
+
+            if let Some(parent) = entry.parent {
+                hierarchy_storage.set_parent(object_id, Some(object_ids[&parent]));
+            }
+
+            if !entry.is_active_self {
+                hierarchy_storage.set_active(object_id, false);
+            }
+
+            object_ids.insert(entry.id, object_id);
+        }
+
+        Ok(object_ids)
+    }
+}
+
+type ComponentDeserializer = fn(serde_json::Value) -> Result<Box<dyn Component>, serde_json::Error>;
+
+/// Deserializers of the components by the names they are saved with in a [`SceneDocument`].
+/// The names are part of the document format, so they must not change once documents are saved.
+#[derive(Debug, Clone, Default)]
+pub struct ComponentRegistry {
+    deserializers: HashMap<String, ComponentDeserializer>,
+    names: HashMap<TypeId, String>,
+}
+
+impl ComponentRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes a registry with the built-in components that can be serialized.
+    pub fn with_builtin_components() -> Self {
+        let mut registry = Self::new();
+        registry.register::<Camera>("Camera");
+        registry.register::<Light>("Light");
+        registry
+    }
+
+    /// Registers the component with the name it is saved with. Registering the same type or
+    /// name again replaces the previous registration.
+    pub fn register<T>(&mut self, name: &str)
+    where
+        T: Component + DeserializeOwned,
+    {
+        if let Some(previous) = self.names.insert(TypeId::of::<T>(), name.to_owned()) {
+            self.deserializers.remove(&previous);
+        }
+
+        self.deserializers
+            .insert(name.to_owned(), deserialize_component::<T>);
+    }
+
+    pub fn is_registered(&self, name: &str) -> bool {
+        self.deserializers.contains_key(name)
+    }
+
+    /// Returns the name the component type is registered with.
+    pub fn name_of(&self, type_id: TypeId) -> Option<&str> {
+        self.names.get(&type_id).map(String::as_str)
+    }
+
+    fn deserialize(
+        &self,
+        id: u32,
+        component: &SceneDocumentComponent,
+    ) -> Result<Box<dyn Component>, SceneDocumentError> {
+        let deserializer = self.deserializers.get(&component.name).ok_or_else(|| {
+            SceneDocumentError::UnknownComponent {
+                id,
+                name: component.name.clone(),
+            }
+        })?;
+
+        deserializer(component.data.clone()).map_err(|source| {
+            SceneDocumentError::InvalidComponent {
+                id,
+                name: component.name.clone(),
+                source,
+            }
+        })
+    }
+}
+
+fn deserialize_component<T>(
+    data: serde_json::Value,
+) -> Result<Box<dyn Component>, serde_json::Error>
+where
+    T: Component + DeserializeOwned,
+{
+    Ok(Box::new(serde_json::from_value::<T>(data)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::components::LightKind;
+    use lvl_math::Vec3;
+
+    fn create_object(
+        object_id_allocator: &mut ObjectIdAllocator,
+        object_storage: &mut ObjectStorage,
+        hierarchy_storage: &mut HierarchyStorage,
+        name: &str,
+        components: Vec<AnyComponent>,
+        parent_id: Option<ObjectId>,
+    ) -> ObjectId {
+        let object_id = object_id_allocator.allocate();
+        let mut object = Object::with_components(object_id, components);
+        object.set_transform(Transform {
+            position: Vec3::new(object_id.get().get() as f32, 0.0, 0.0),
+            ..Transform::identity()
+        });
+        object_storage.add(object);
+        hierarchy_storage.add(object_id);
+        hierarchy_storage.set_name(object_id, name);
+        hierarchy_storage.set_parent(object_id, parent_id);
+        object_id
+    }
+
+    /// The name, the parent name, the active flag, the transform and the lights of an object.
+    type ObjectDescription = (String, Option<String>, bool, Transform, Vec<Light>);
+
+    /// Lists the objects in hierarchy order, so that scenes with different ids can be compared.
+    fn describe(
+        object_storage: &ObjectStorage,
+        hierarchy_storage: &HierarchyStorage,
+    ) -> Vec<ObjectDescription> {
+        hierarchy_storage
+            .objects()
+            .iter()
+            .map(|&object_id| {
+                let object = object_storage.get(object_id).unwrap();
+                (
+                    hierarchy_storage.name(object_id).to_owned(),
+                    hierarchy_storage
+                        .parent(object_id)
+                        .map(|parent_id| hierarchy_storage.name(parent_id).to_owned()),
+                    hierarchy_storage.is_active_self(object_id),
+                    object.transform(),
+                    object.find_components_by_type::<Light>().copied().collect(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn check_round_trip() {
+        let mut object_ids = ObjectIdAllocator::new();
+        let mut component_ids = ComponentIdAllocator::new();
+        let mut objects = ObjectStorage::new();
+        let mut hierarchy = HierarchyStorage::new();
+        let light = Light {
+            kind: LightKind::Directional {
+                direction: Vec3::new(0.0, -1.0, 0.0),
+            },
+            light_color: Vec3::new(1.0, 0.5, 0.0),
+        };
+
+        let root = create_object(
+            &mut object_ids,
+            &mut objects,
+            &mut hierarchy,
+            "root",
+            vec![],
+            None,
+        );
+        let lamp = create_object(
+            &mut object_ids,
+            &mut objects,
+            &mut hierarchy,
+            "lamp",
+            vec![AnyComponent::new(component_ids.allocate(), light)],
+            Some(root),
+        );
+        create_object(
+            &mut object_ids,
+            &mut objects,
+            &mut hierarchy,
+            "bulb",
+            vec![],
+            Some(lamp),
+        );
+        create_object(
+            &mut object_ids,
+            &mut objects,
+            &mut hierarchy,
+            "floor",
+            vec![],
+            Some(root),
+        );
+        create_object(
+            &mut object_ids,
+            &mut objects,
+            &mut hierarchy,
+            "sky",
+            vec![],
+            None,
+        );
+        hierarchy.set_active(lamp, false);
+
+        let registry = ComponentRegistry::with_builtin_components();
+        let document = SceneDocument::capture(&registry, &objects, &hierarchy);
+        assert_eq!(document.objects()[1].components[0].name, "Light");
+        let loaded = SceneDocument::from_text(&document.to_text()).unwrap();
+        assert_eq!(loaded, document);
+
+        let mut loaded_object_ids = ObjectIdAllocator::new();
+        let mut loaded_component_ids = ComponentIdAllocator::new();
+        let mut loaded_objects = ObjectStorage::new();
+        let mut loaded_hierarchy = HierarchyStorage::new();
+        let ids = loaded
+            .spawn(
+                &registry,
+                &mut loaded_object_ids,
+                &mut loaded_component_ids,
+                &mut loaded_objects,
+                &mut loaded_hierarchy,
+            )
+            .unwrap();
+
+        assert_eq!(ids.len(), 5);
+        assert_eq!(
+            describe(&loaded_objects, &loaded_hierarchy),
+            describe(&objects, &hierarchy)
+        );
+        assert!(loaded_objects
+            .object_ids_with_component::<Light>()
+            .unwrap()
+            .contains(&ids[&lamp.get().get()]));
+        assert_eq!(
+            SceneDocument::capture(&registry, &loaded_objects, &loaded_hierarchy),
+            document
+        );
+    }
+
+    #[test]
+    fn check_invalid_documents() {
+        let object = |id, parent, components| SceneDocumentObject {
+            id,
+            name: String::new(),
+            transform: Transform::identity(),
+            is_active_self: true,
+            parent,
+            components,
+        };
+        let spawn = |objects| {
+            let mut objects_storage = ObjectStorage::new();
+            let mut hierarchy = HierarchyStorage::new();
+            let result = SceneDocument {
+                version: SCENE_DOCUMENT_VERSION,
+                objects,
+            }
+            .spawn(
+                &ComponentRegistry::with_builtin_components(),
+                &mut ObjectIdAllocator::new(),
+                &mut ComponentIdAllocator::new(),
+                &mut objects_storage,
+                &mut hierarchy,
+            );
+            // nothing is created on failure
+            assert!(result.is_ok() || hierarchy.objects().is_empty());
+            result
+        };
+        let component = |name: &str, data| SceneDocumentComponent {
+            name: name.to_owned(),
+            data,
+        };
+
+        assert!(matches!(
+            spawn(vec![object(1, None, vec![]), object(1, None, vec![])]),
+            Err(SceneDocumentError::DuplicateObject { id: 1 })
+        ));
+        assert!(matches!(
+            spawn(vec![object(1, Some(2), vec![]), object(2, None, vec![])]),
+            Err(SceneDocumentError::InvalidParent { id: 1, parent: 2 })
+        ));
+        assert!(matches!(
+            spawn(vec![object(
+                1,
+                None,
+                vec![component("unknown", serde_json::Value::Null)]
+            )]),
+            Err(SceneDocumentError::UnknownComponent { id: 1, .. })
+        ));
+        assert!(matches!(
+            spawn(vec![object(
+                1,
+                None,
+                vec![component("Light", serde_json::Value::Null)]
+            )]),
+            Err(SceneDocumentError::InvalidComponent { id: 1, .. })
+        ));
+        assert!(matches!(
+            SceneDocument::from_text("not a document"),
+            Err(SceneDocumentError::InvalidText(_))
+        ));
+    }
+
+    #[test]
+    fn check_unsupported_version() {
+        let document = SceneDocument {
+            version: SCENE_DOCUMENT_VERSION + 1,
+            objects: vec![],
+        };
+
+        assert!(matches!(
+            SceneDocument::from_text(&document.to_text()),
+            Err(SceneDocumentError::UnsupportedVersion(version)) if version == SCENE_DOCUMENT_VERSION + 1
+        ));
+        assert!(matches!(
+            document.spawn(
+                &ComponentRegistry::with_builtin_components(),
+                &mut ObjectIdAllocator::new(),
+                &mut ComponentIdAllocator::new(),
+                &mut ObjectStorage::new(),
+                &mut HierarchyStorage::new(),
+            ),
+            Err(SceneDocumentError::UnsupportedVersion(_))
+        ));
+        assert!(matches!(
+            SceneDocument::from_text(r#"{ "objects": [] }"#),
+            Err(SceneDocumentError::InvalidText(_))
+        ));
+    }
+
+    #[test]
+    fn check_unregistered_component_is_not_saved() {
+        let mut object_ids = ObjectIdAllocator::new();
+        let mut component_ids = ComponentIdAllocator::new();
+        let mut objects = ObjectStorage::new();
+        let mut hierarchy = HierarchyStorage::new();
+        let light = Light {
+            kind: LightKind::Point,
+            light_color: Vec3::new(1.0, 1.0, 1.0),
+        };
+        create_object(
+            &mut object_ids,
+            &mut objects,
+            &mut hierarchy,
+            "lamp",
+            vec![AnyComponent::new(component_ids.allocate(), light)],
+            None,
+        );
+
+        let document = SceneDocument::capture(&ComponentRegistry::new(), &objects, &hierarchy);
+        assert!(document.objects()[0].components.is_empty());
+
+        let mut registry = ComponentRegistry::new();
+        registry.register::<Light>("Lamp");
+        let document = SceneDocument::capture(&registry, &objects, &hierarchy);
+        assert_eq!(document.objects()[0].components[0].name, "Lamp");
+    }
+}
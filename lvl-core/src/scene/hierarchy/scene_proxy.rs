@@ -1,12 +1,14 @@
 use super::{
-    AnyComponent, Component, ComponentId, ComponentIdAllocator, Controller, HierarchyStorage,
-    Object, ObjectId, ObjectIdAllocator, ObjectSiblingIter, ObjectStorage, Transform,
+    duplicate_subtree, AnyComponent, Component, ComponentId, ComponentIdAllocator,
+    ComponentRegistry, Controller, HierarchyStorage, Object, ObjectId, ObjectIdAllocator,
+    ObjectSiblingIter, ObjectSnapshot, ObjectSnapshotError, ObjectStorage, SceneDocument,
+    SceneDocumentError, SpatialGridError, Transform,
 };
 use crate::context::Context;
-use lvl_math::Mat4;
+use lvl_math::{Mat4, Vec3};
 use std::{
     any::{Any, TypeId},
-    collections::HashSet,
+    collections::{HashMap, HashSet, VecDeque},
 };
 use winit::window::Window;
 
@@ -56,10 +58,62 @@ pub(crate) enum SceneActionItem {
     },
 }
 
+impl SceneActionItem {
+    /// Returns the object the action applies to, or `None` for the scene-wide actions.
+    pub(crate) fn object_id(&self) -> Option<ObjectId> {
+        match self {
+            Self::RemoveObject { object_id }
+            | Self::TriggerOnActive { object_id }
+            | Self::TriggerOnInactive { object_id }
+            | Self::AttachController { object_id, .. }
+            | Self::DetachController { object_id }
+            | Self::ListenOnUpdate { object_id }
+            | Self::UnlistenOnUpdate { object_id }
+            | Self::ListenOnLateUpdate { object_id }
+            | Self::UnlistenOnLateUpdate { object_id }
+            | Self::ListenEvent { object_id, .. }
+            | Self::UnlistenEvent { object_id, .. }
+            | Self::UnlistenEventAll { object_id } => Some(*object_id),
+            Self::EmitEvent { .. } => None,
+        }
+    }
+}
+
 pub(crate) struct SceneActionResult {
     pub action_queue: Vec<SceneActionItem>,
 }
 
+/// The actions deferred by the [`SceneProxy`], processed in FIFO order. The actions queued while
+/// processing an action, e.g. by an event handler, are appended after the pending ones, and the
+/// processing continues until the queue is empty. Removing an object cancels the pending actions
+/// of the object and its children.
+pub(crate) struct SceneActionQueue {
+    actions: VecDeque<SceneActionItem>,
+}
+
+impl SceneActionQueue {
+    pub fn new() -> Self {
+        Self {
+            actions: VecDeque::new(),
+        }
+    }
+
+    pub fn append(&mut self, result: SceneActionResult) {
+        self.actions.extend(result.action_queue);
+    }
+
+    pub fn pop(&mut self) -> Option<SceneActionItem> {
+        self.actions.pop_front()
+    }
+
+    pub fn cancel_object_actions(&mut self, object_ids: &HashSet<ObjectId>) {
+        self.actions.retain(|action| match action.object_id() {
+            Some(object_id) => !object_ids.contains(&object_id),
+            None => true,
+        });
+    }
+}
+
 pub struct SceneProxy<'scene, 'window> {
     context: &'scene Context<'window>,
     window: &'window Window,
@@ -193,6 +247,32 @@ impl<'scene, 'window> SceneProxy<'scene, 'window> {
         Some(self.hierarchy_storage.matrix(object_id))
     }
 
+    /// Finds the object nearest to the given world position among the objects passing the filter.
+    /// Returns the object with its distance to the point.
+    pub fn nearest_object(
+        &self,
+        point: Vec3,
+        filter: impl Fn(ObjectId) -> bool,
+    ) -> Option<(ObjectId, f32)> {
+        self.hierarchy_storage.nearest_object(point, filter)
+    }
+
+    /// Enables the spatial grid that accelerates the spatial queries such as
+    /// [`SceneProxy::nearest_object`]. Worth enabling for scenes with many objects.
+    /// Fails if the cell size is invalid; see [`SpatialGrid::new`](super::SpatialGrid::new).
+    pub fn enable_spatial_grid(&mut self, cell_size: f32) -> Result<(), SpatialGridError> {
+        self.hierarchy_storage.enable_spatial_grid(cell_size)
+    }
+
+    pub fn disable_spatial_grid(&mut self) {
+        self.hierarchy_storage.disable_spatial_grid();
+    }
+
+    /// Re-indexes all objects in the spatial grid, if it is enabled.
+    pub fn rebuild_spatial_grid(&mut self) {
+        self.hierarchy_storage.rebuild_spatial_grid();
+    }
+
     pub fn parent(&self, object_id: ObjectId) -> Option<ObjectId> {
         if !self.object_storage.is_exists(object_id) {
             return None;
@@ -259,6 +339,8 @@ impl<'scene, 'window> SceneProxy<'scene, 'window> {
         object_id
     }
 
+    /// Removes the object and its children once the pending actions before it are processed.
+    /// The actions for them that are still pending then are dropped.
     pub fn remove_object(&mut self, object_id: ObjectId) {
         self.action_queue
             .push(SceneActionItem::RemoveObject { object_id });
@@ -331,6 +413,92 @@ impl<'scene, 'window> SceneProxy<'scene, 'window> {
         self.hierarchy_storage.set_parent(object_id, parent_id);
     }
 
+    /// Captures the object and its children; see [`ObjectSnapshot`].
+    pub fn snapshot_object(&self, object_id: ObjectId) -> Option<ObjectSnapshot> {
+        if !self.object_storage.is_exists(object_id) {
+            return None;
+        }
+
+        ObjectSnapshot::capture(self.object_storage, self.hierarchy_storage, object_id)
+    }
+
+    /// Creates the objects of the snapshot under the given parent and returns the root.
+    pub fn spawn_snapshot(
+        &mut self,
+        snapshot: &ObjectSnapshot,
+        mut parent_id: Option<ObjectId>,
+    ) -> Option<ObjectId> {
+        if let Some(id) = parent_id {
+            if !self.object_storage.is_exists(id) {
+                parent_id = None;
+            }
+        }
+
+        snapshot.spawn(
+            self.object_id_allocator,
+            self.object_storage,
+            self.hierarchy_storage,
+            parent_id,
+        )
+    }
+
+    /// Copies the object and its children, with their names, transforms and components, and
+    /// places the copy under the object's parent. Returns the copy of the object.
+    /// Controllers and the components that cannot be cloned are not copied; see
+    /// [`Component::clone_boxed`].
+    pub fn duplicate_subtree(&mut self, root_id: ObjectId) -> Option<ObjectId> {
+        duplicate_subtree(
+            self.object_id_allocator,
+            self.component_id_allocator,
+            self.object_storage,
+            self.hierarchy_storage,
+            root_id,
+        )
+    }
+
+    /// Places a snapshot of the object and its children on the clipboard as text.
+    pub fn copy_object(&self, object_id: ObjectId) -> Result<(), ObjectSnapshotError> {
+        let snapshot = self
+            .snapshot_object(object_id)
+            .ok_or(ObjectSnapshotError::ObjectNotFound)?;
+        self.context.clipboard().set_text(&snapshot.to_text())?;
+        Ok(())
+    }
+
+    /// Spawns the objects copied by [`Self::copy_object`] under the given parent.
+    pub fn paste_object(
+        &mut self,
+        parent_id: Option<ObjectId>,
+    ) -> Result<ObjectId, ObjectSnapshotError> {
+        let text = self.context.clipboard().get_text()?;
+        let snapshot = ObjectSnapshot::from_text(&text)?;
+        self.spawn_snapshot(&snapshot, parent_id)
+            .ok_or(ObjectSnapshotError::Empty)
+    }
+
+    /// Captures all the objects of the scene, saving the components registered in the registry;
+    /// see [`SceneDocument`].
+    pub fn export_document(&self, registry: &ComponentRegistry) -> SceneDocument {
+        SceneDocument::capture(registry, self.object_storage, self.hierarchy_storage)
+    }
+
+    /// Creates the objects of the document alongside the existing ones, deserializing their
+    /// components with the registry. Returns the new ids of the objects by their ids in the
+    /// document.
+    pub fn import_document(
+        &mut self,
+        document: &SceneDocument,
+        registry: &ComponentRegistry,
+    ) -> Result<HashMap<u32, ObjectId>, SceneDocumentError> {
+        document.spawn(
+            registry,
+            self.object_id_allocator,
+            self.component_id_allocator,
+            self.object_storage,
+            self.hierarchy_storage,
+        )
+    }
+
     pub fn add_component<T>(&mut self, object_id: ObjectId, component: T) -> Option<ComponentId>
     where
         T: Component,
@@ -414,6 +582,8 @@ impl<'scene, 'window> SceneProxy<'scene, 'window> {
             .push(SceneActionItem::UnlistenEventAll { object_id });
     }
 
+    /// Dispatches the event after the pending actions are processed.
+    /// The actions queued by the handlers are processed after the ones pending at that time.
     pub fn emit_event(&mut self, event: impl Into<String>, param: impl Any) {
         self.action_queue.push(SceneActionItem::EmitEvent {
             event: event.into(),
@@ -421,3 +591,63 @@ impl<'scene, 'window> SceneProxy<'scene, 'window> {
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn object_ids(queue: &mut SceneActionQueue) -> Vec<Option<ObjectId>> {
+        std::iter::from_fn(|| queue.pop())
+            .map(|action| action.object_id())
+            .collect()
+    }
+
+    #[test]
+    fn check_action_queue_order() {
+        let mut allocator = ObjectIdAllocator::new();
+        let a = allocator.allocate();
+        let b = allocator.allocate();
+        let mut queue = SceneActionQueue::new();
+
+        queue.append(SceneActionResult {
+            action_queue: vec![
+                SceneActionItem::ListenOnUpdate { object_id: a },
+                SceneActionItem::EmitEvent {
+                    event: "event".to_owned(),
+                    param: Box::new(()),
+                },
+            ],
+        });
+
+        // an action processed first queues more actions, which go after the pending ones
+        queue.pop();
+        queue.append(SceneActionResult {
+            action_queue: vec![SceneActionItem::ListenOnUpdate { object_id: b }],
+        });
+
+        assert_eq!(object_ids(&mut queue), [None, Some(b)]);
+    }
+
+    #[test]
+    fn check_remove_object_cancels_pending_actions() {
+        let mut allocator = ObjectIdAllocator::new();
+        let removed = allocator.allocate();
+        let other = allocator.allocate();
+        let mut queue = SceneActionQueue::new();
+
+        // a handler removes its own object, after it queued an update for it
+        queue.append(SceneActionResult {
+            action_queue: vec![
+                SceneActionItem::RemoveObject { object_id: removed },
+                SceneActionItem::ListenOnUpdate { object_id: removed },
+                SceneActionItem::ListenOnUpdate { object_id: other },
+            ],
+        });
+
+        let action = queue.pop().unwrap();
+        assert_eq!(action.object_id(), Some(removed));
+        queue.cancel_object_actions(&HashSet::from([removed]));
+
+        assert_eq!(object_ids(&mut queue), [Some(other)]);
+    }
+}
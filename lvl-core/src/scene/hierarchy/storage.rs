@@ -2,8 +2,10 @@ mod controller_storage;
 mod event_receiver_storage;
 mod hierarchy_storage;
 mod object_storage;
+mod spatial_grid;
 
 pub use controller_storage::*;
 pub use event_receiver_storage::*;
 pub use hierarchy_storage::*;
 pub use object_storage::*;
+pub use spatial_grid::*;
@@ -5,6 +5,7 @@ pub struct ControllerStorage {
     controllers: HashMap<ObjectId, Box<dyn Controller>>,
     on_update_hooked_controllers: HashSet<ObjectId>,
     on_late_update_hooked_controllers: HashSet<ObjectId>,
+    unstarted_controllers: HashSet<ObjectId>,
 }
 
 impl ControllerStorage {
@@ -13,6 +14,7 @@ impl ControllerStorage {
             controllers: HashMap::new(),
             on_update_hooked_controllers: HashSet::new(),
             on_late_update_hooked_controllers: HashSet::new(),
+            unstarted_controllers: HashSet::new(),
         }
     }
 
@@ -26,6 +28,8 @@ impl ControllerStorage {
         controller: Box<dyn Controller>,
         scene: &mut SceneProxy,
     ) {
+        self.unstarted_controllers.insert(id);
+
         match self.controllers.entry(id) {
             Entry::Occupied(mut entry) => {
                 entry.get_mut().on_destroy(id, scene);
@@ -41,6 +45,7 @@ impl ControllerStorage {
     pub(crate) fn detach_controller(&mut self, id: ObjectId, scene: &mut SceneProxy) {
         self.on_update_hooked_controllers.remove(&id);
         self.on_late_update_hooked_controllers.remove(&id);
+        self.unstarted_controllers.remove(&id);
 
         if let Some(mut controller) = self.controllers.remove(&id) {
             controller.on_destroy(id, scene);
@@ -80,6 +85,12 @@ impl ControllerStorage {
     }
 
     pub(crate) fn invoke_on_update(&mut self, scene: &mut SceneProxy) {
+        for id in self.take_startable_controllers(|id| scene.is_active(id)) {
+            if let Some(controller) = self.controllers.get_mut(&id) {
+                controller.on_start(id, scene);
+            }
+        }
+
         for id in &self.on_update_hooked_controllers {
             if let Some(controller) = self.controllers.get_mut(id) {
                 controller.on_update(*id, scene);
@@ -94,4 +105,56 @@ impl ControllerStorage {
             }
         }
     }
+
+    /// Returns the controllers that have not started yet and whose objects are active,
+    /// marking them as started.
+    fn take_startable_controllers(
+        &mut self,
+        is_active: impl Fn(ObjectId) -> bool,
+    ) -> Vec<ObjectId> {
+        let ids = self
+            .unstarted_controllers
+            .iter()
+            .copied()
+            .filter(|&id| is_active(id))
+            .collect::<Vec<_>>();
+
+        for id in &ids {
+            self.unstarted_controllers.remove(id);
+        }
+
+        ids
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::ObjectIdAllocator;
+
+    #[test]
+    fn check_start_once_when_active() {
+        let mut allocator = ObjectIdAllocator::new();
+        let mut storage = ControllerStorage::new();
+        let active = allocator.allocate();
+        let inactive = allocator.allocate();
+        let mut is_inactive_active = false;
+        let mut starts = Vec::new();
+
+        storage.unstarted_controllers.insert(active);
+        storage.unstarted_controllers.insert(inactive);
+
+        for frame in 0..10 {
+            if frame == 5 {
+                is_inactive_active = true;
+            }
+
+            for id in storage.take_startable_controllers(|id| id != inactive || is_inactive_active)
+            {
+                starts.push((frame, id));
+            }
+        }
+
+        assert_eq!(starts, [(0, active), (5, inactive)]);
+    }
 }
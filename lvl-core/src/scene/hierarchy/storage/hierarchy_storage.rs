@@ -1,6 +1,7 @@
+use super::{SpatialGrid, SpatialGridError};
 use crate::scene::ObjectId;
 use bitvec::vec::BitVec;
-use lvl_math::Mat4;
+use lvl_math::{Mat4, Vec3};
 use std::{cmp::Ordering, ops::Range};
 use string_interner::StringInterner;
 
@@ -111,6 +112,7 @@ pub struct HierarchyStorage {
     object_matrices: Vec<Mat4>,
     // extra
     string_interner: StringInterner<string_interner::DefaultBackend>,
+    spatial_grid: Option<SpatialGrid>,
 }
 
 impl HierarchyStorage {
@@ -128,6 +130,7 @@ impl HierarchyStorage {
             object_matrices: Vec::with_capacity(1024),
 
             string_interner: StringInterner::default(),
+            spatial_grid: None,
         }
     }
 
@@ -188,6 +191,71 @@ impl HierarchyStorage {
         &self.object_matrices[object_id.get_zero_based_u32() as usize]
     }
 
+    /// Finds the object whose world position is the nearest to the given point among the objects
+    /// passing the filter. Returns the object with its distance to the point.
+    /// The spatial grid is used if it is enabled.
+    pub fn nearest_object(
+        &self,
+        point: Vec3,
+        filter: impl Fn(ObjectId) -> bool,
+    ) -> Option<(ObjectId, f32)> {
+        if let Some(spatial_grid) = &self.spatial_grid {
+            return spatial_grid.nearest(point, filter);
+        }
+
+        let mut nearest: Option<(ObjectId, f32)> = None;
+
+        for &object_id in &self.objects {
+            if !filter(object_id) {
+                continue;
+            }
+
+            let position = self.matrix(object_id).split_translation();
+            let distance_square = Vec3::distance_square(point, position);
+
+            let is_nearer = match nearest {
+                Some((_, nearest_distance_square)) => distance_square < nearest_distance_square,
+                None => true,
+            };
+
+            if is_nearer {
+                nearest = Some((object_id, distance_square));
+            }
+        }
+
+        nearest.map(|(object_id, distance_square)| (object_id, distance_square.sqrt()))
+    }
+
+    pub fn spatial_grid(&self) -> Option<&SpatialGrid> {
+        self.spatial_grid.as_ref()
+    }
+
+    /// Enables the spatial grid with the given cell size and indexes all objects.
+    /// Once enabled, the grid follows the objects as their matrices are updated.
+    /// Fails if the cell size is invalid; see [`SpatialGrid::new`].
+    pub(crate) fn enable_spatial_grid(&mut self, cell_size: f32) -> Result<(), SpatialGridError> {
+        self.spatial_grid = Some(SpatialGrid::new(cell_size)?);
+        self.rebuild_spatial_grid();
+        Ok(())
+    }
+
+    pub(crate) fn disable_spatial_grid(&mut self) {
+        self.spatial_grid = None;
+    }
+
+    /// Re-indexes all objects from their current matrices.
+    pub(crate) fn rebuild_spatial_grid(&mut self) {
+        if let Some(spatial_grid) = &mut self.spatial_grid {
+            spatial_grid.rebuild(self.objects.iter().map(|&object_id| {
+                (
+                    object_id,
+                    self.object_matrices[object_id.get_zero_based_u32() as usize]
+                        .split_translation(),
+                )
+            }));
+        }
+    }
+
     #[cfg(test)]
     pub(crate) fn matrix_mut(&mut self, object_id: ObjectId) -> &mut Mat4 {
         &mut self.object_matrices[object_id.get_zero_based_u32() as usize]
@@ -318,6 +386,12 @@ impl HierarchyStorage {
         let object_usize = object_id.get_zero_based_u32() as usize;
         let span = self.object_spans[object_usize];
 
+        if let Some(spatial_grid) = &mut self.spatial_grid {
+            for &object in &self.objects[span.to_range()] {
+                spatial_grid.remove(object);
+            }
+        }
+
         // Remove the object and its children from its parents.
         for &parent in &self.object_parents[object_usize] {
             let parent_usize = parent.get_zero_based_u32() as usize;
@@ -461,6 +535,10 @@ impl HierarchyStorage {
                 matrix *= self.matrix(parent);
             }
 
+            if let Some(spatial_grid) = &mut self.spatial_grid {
+                spatial_grid.update(object, matrix.split_translation());
+            }
+
             self.object_matrices[object.get_zero_based_u32() as usize] = matrix;
         }
 
@@ -562,7 +640,6 @@ impl HierarchyStorage {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use lvl_math::Vec3;
     use std::{collections::HashMap, num::NonZeroU32};
 
     fn obj_id(id: u32) -> ObjectId {
@@ -812,4 +889,60 @@ mod tests {
             true
         );
     }
+
+    #[test]
+    fn check_hierarchy_nearest_object() {
+        let mut hierarchy = create_hierarchy(3);
+
+        let mut transforms = HashMap::new();
+        transforms.insert(obj_id(0), Mat4::translation(Vec3::new(10.0, 0.0, 0.0)));
+        transforms.insert(obj_id(1), Mat4::translation(Vec3::new(0.0, 3.0, 4.0)));
+        transforms.insert(obj_id(2), Mat4::translation(Vec3::new(-2.0, 0.0, 0.0)));
+
+        hierarchy.update_object_matrices(|entity| transforms.get(&entity).cloned());
+
+        let (nearest, distance) = hierarchy.nearest_object(Vec3::ZERO, |_| true).unwrap();
+        assert_eq!(nearest, obj_id(2));
+        assert!(equals_float(distance, 2.0));
+
+        let (nearest, distance) = hierarchy
+            .nearest_object(Vec3::ZERO, |object_id| object_id != obj_id(2))
+            .unwrap();
+        assert_eq!(nearest, obj_id(1));
+        assert!(equals_float(distance, 5.0));
+
+        assert_eq!(hierarchy.nearest_object(Vec3::ZERO, |_| false), None);
+    }
+
+    #[test]
+    fn check_hierarchy_spatial_grid_follows_matrices() {
+        let mut hierarchy = create_hierarchy(3);
+        hierarchy.set_parent(obj_id(2), Some(obj_id(1)));
+        hierarchy.enable_spatial_grid(1.0).unwrap();
+
+        let mut transforms = HashMap::new();
+        transforms.insert(obj_id(0), Mat4::translation(Vec3::new(10.0, 0.0, 0.0)));
+        transforms.insert(obj_id(1), Mat4::translation(Vec3::new(0.0, 3.0, 4.0)));
+        transforms.insert(obj_id(2), Mat4::translation(Vec3::new(0.0, 0.0, -4.0)));
+
+        hierarchy.update_object_matrices(|entity| transforms.get(&entity).cloned());
+
+        let (nearest, distance) = hierarchy.nearest_object(Vec3::ZERO, |_| true).unwrap();
+        assert_eq!(nearest, obj_id(2));
+        assert!(equals_float(distance, 3.0));
+
+        // only the dirty object is moved in the grid
+        transforms.insert(obj_id(0), Mat4::translation(Vec3::new(1.0, 0.0, 0.0)));
+        hierarchy.set_dirty(obj_id(0));
+        hierarchy.update_object_matrices(|entity| transforms.get(&entity).cloned());
+
+        assert_eq!(
+            hierarchy.nearest_object(Vec3::ZERO, |_| true).unwrap().0,
+            obj_id(0)
+        );
+
+        // removing an object removes its children from the grid as well
+        hierarchy.remove(obj_id(1));
+        assert_eq!(hierarchy.spatial_grid().unwrap().len(), 1);
+    }
 }
@@ -0,0 +1,369 @@
+use crate::scene::ObjectId;
+use lvl_math::Vec3;
+use std::collections::HashMap;
+use thiserror::Error;
+
+type GridCell = [i32; 3];
+
+/// The smallest cell size accepted by [`SpatialGrid::new`]. Smaller cells would put the cell
+/// coordinates of ordinary world positions out of the range of `i32`.
+pub const MIN_SPATIAL_GRID_CELL_SIZE: f32 = 1e-3;
+
+#[derive(Error, Debug, Clone, Copy, PartialEq)]
+pub enum SpatialGridError {
+    #[error("the cell size {0} is not a finite number of at least {MIN_SPATIAL_GRID_CELL_SIZE}")]
+    InvalidCellSize(f32),
+}
+
+/// A uniform grid that indexes objects by their world positions.
+/// It is used to accelerate the spatial queries of the scene; see [`super::HierarchyStorage`].
+#[derive(Debug, Clone)]
+pub struct SpatialGrid {
+    cell_size: f32,
+    cells: HashMap<GridCell, Vec<ObjectId>>,
+    object_cells: HashMap<ObjectId, (GridCell, Vec3)>,
+}
+
+impl SpatialGrid {
+    /// Creates an empty grid. Fails if the `cell_size` is not finite or is smaller than
+    /// [`MIN_SPATIAL_GRID_CELL_SIZE`].
+    pub fn new(cell_size: f32) -> Result<Self, SpatialGridError> {
+        if !cell_size.is_finite() || cell_size < MIN_SPATIAL_GRID_CELL_SIZE {
+            return Err(SpatialGridError::InvalidCellSize(cell_size));
+        }
+
+        Ok(Self {
+            cell_size,
+            cells: HashMap::new(),
+            object_cells: HashMap::new(),
+        })
+    }
+
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    pub fn len(&self) -> usize {
+        self.object_cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.object_cells.is_empty()
+    }
+
+    pub fn position(&self, object_id: ObjectId) -> Option<Vec3> {
+        self.object_cells
+            .get(&object_id)
+            .map(|&(_, position)| position)
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.object_cells.clear();
+    }
+
+    /// Replaces the content of the grid with the given objects.
+    pub fn rebuild(&mut self, objects: impl IntoIterator<Item = (ObjectId, Vec3)>) {
+        self.clear();
+
+        for (object_id, position) in objects {
+            self.update(object_id, position);
+        }
+    }
+
+    /// Inserts the object, or moves it if it is already in the grid.
+    pub fn update(&mut self, object_id: ObjectId, position: Vec3) {
+        let cell = self.cell_of(position);
+
+        match self.object_cells.insert(object_id, (cell, position)) {
+            Some((previous_cell, _)) if previous_cell == cell => {
+                return;
+            }
+            Some((previous_cell, _)) => {
+                self.remove_from_cell(object_id, previous_cell);
+            }
+            None => {}
+        }
+
+        self.cells.entry(cell).or_default().push(object_id);
+    }
+
+    pub fn remove(&mut self, object_id: ObjectId) {
+        if let Some((cell, _)) = self.object_cells.remove(&object_id) {
+            self.remove_from_cell(object_id, cell);
+        }
+    }
+
+    /// Finds the object nearest to the given point among the objects passing the filter.
+    /// Returns the object with its distance to the point.
+    ///
+    /// The cells are searched in growing rings around the point. Once the rings would have
+    /// visited more cells than there are occupied ones, e.g. when the objects are far away from
+    /// the point, the remaining search falls back to a scan of all objects.
+    pub fn nearest(
+        &self,
+        point: Vec3,
+        filter: impl Fn(ObjectId) -> bool,
+    ) -> Option<(ObjectId, f32)> {
+        let center = self.cell_of(point);
+        let mut nearest: Option<(ObjectId, f32)> = None;
+        let mut visited_count = 0;
+        let mut searched_cell_count = 0;
+        let mut ring = 0;
+
+        while visited_count < self.object_cells.len() {
+            searched_cell_count += ring_cell_count(ring);
+
+            if self.cells.len() < searched_cell_count {
+                return self.nearest_linear(point, filter);
+            }
+
+            for cell in ring_cells(center, ring) {
+                let objects = match self.cells.get(&cell) {
+                    Some(objects) => objects,
+                    None => continue,
+                };
+
+                visited_count += objects.len();
+
+                for &object_id in objects {
+                    if !filter(object_id) {
+                        continue;
+                    }
+
+                    let position = self.object_cells[&object_id].1;
+                    let distance_square = Vec3::distance_square(point, position);
+
+                    let is_nearer = match nearest {
+                        Some((_, nearest_distance_square)) => {
+                            distance_square < nearest_distance_square
+                        }
+                        None => true,
+                    };
+
+                    if is_nearer {
+                        nearest = Some((object_id, distance_square));
+                    }
+                }
+            }
+
+            // every object in the further rings is at least `ring * cell_size` away
+            if let Some((_, nearest_distance_square)) = nearest {
+                let ring_distance = ring as f32 * self.cell_size;
+
+                if nearest_distance_square <= ring_distance * ring_distance {
+                    break;
+                }
+            }
+
+            ring += 1;
+        }
+
+        nearest.map(|(object_id, distance_square)| (object_id, distance_square.sqrt()))
+    }
+
+    fn nearest_linear(
+        &self,
+        point: Vec3,
+        filter: impl Fn(ObjectId) -> bool,
+    ) -> Option<(ObjectId, f32)> {
+        self.object_cells
+            .iter()
+            .filter(|(&object_id, _)| filter(object_id))
+            .map(|(&object_id, &(_, position))| (object_id, Vec3::distance(point, position)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+    }
+
+    fn cell_of(&self, position: Vec3) -> GridCell {
+        [
+            (position.x / self.cell_size).floor() as i32,
+            (position.y / self.cell_size).floor() as i32,
+            (position.z / self.cell_size).floor() as i32,
+        ]
+    }
+
+    fn remove_from_cell(&mut self, object_id: ObjectId, cell: GridCell) {
+        if let Some(objects) = self.cells.get_mut(&cell) {
+            objects.retain(|&object| object != object_id);
+
+            if objects.is_empty() {
+                self.cells.remove(&cell);
+            }
+        }
+    }
+}
+
+/// Returns the number of cells that [`ring_cells`] yields for the ring.
+fn ring_cell_count(ring: i32) -> usize {
+    let outer = 2 * ring as usize + 1;
+    let inner = outer.saturating_sub(2);
+    outer.pow(3) - inner.pow(3)
+}
+
+/// Returns the cells on the surface of the cube of the given radius around the center.
+/// The cells out of the range of `i32` are skipped.
+fn ring_cells(center: GridCell, ring: i32) -> impl Iterator<Item = GridCell> {
+    (-ring..=ring).flat_map(move |x| {
+        (-ring..=ring).flat_map(move |y| {
+            let is_on_surface = x.abs() == ring || y.abs() == ring;
+            // the inner cells of this column are already covered by the smaller rings
+            let z_step = if is_on_surface || ring == 0 {
+                1
+            } else {
+                2 * ring as usize
+            };
+
+            (-ring..=ring).step_by(z_step).filter_map(move |z| {
+                Some([
+                    center[0].checked_add(x)?,
+                    center[1].checked_add(y)?,
+                    center[2].checked_add(z)?,
+                ])
+            })
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::num::NonZeroU32;
+
+    fn obj_id(id: u32) -> ObjectId {
+        ObjectId::new(NonZeroU32::new(id + 1).unwrap())
+    }
+
+    fn brute_force_nearest(
+        objects: &[(ObjectId, Vec3)],
+        point: Vec3,
+        filter: impl Fn(ObjectId) -> bool,
+    ) -> Option<(ObjectId, f32)> {
+        objects
+            .iter()
+            .filter(|(object_id, _)| filter(*object_id))
+            .map(|&(object_id, position)| (object_id, Vec3::distance(point, position)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+    }
+
+    /// A small linear congruential generator; good enough for scattering test objects.
+    struct Random(u64);
+
+    impl Random {
+        fn next_f32(&mut self, min: f32, max: f32) -> f32 {
+            self.0 = self
+                .0
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            let unit = (self.0 >> 40) as f32 / (1u64 << 24) as f32;
+            min + (max - min) * unit
+        }
+
+        fn next_vec3(&mut self, min: f32, max: f32) -> Vec3 {
+            Vec3::new(
+                self.next_f32(min, max),
+                self.next_f32(min, max),
+                self.next_f32(min, max),
+            )
+        }
+    }
+
+    #[test]
+    fn check_ring_cells() {
+        assert_eq!(ring_cells([0, 0, 0], 0).count(), 1);
+        assert_eq!(ring_cells([0, 0, 0], 1).count(), 3 * 3 * 3 - 1);
+        assert_eq!(ring_cells([0, 0, 0], 2).count(), 5 * 5 * 5 - 3 * 3 * 3);
+        assert!(ring_cells([0, 0, 0], 2).all(|cell| cell.iter().any(|c| c.abs() == 2)));
+
+        for ring in 0..4 {
+            assert_eq!(ring_cells([0, 0, 0], ring).count(), ring_cell_count(ring));
+        }
+
+        assert_eq!(ring_cells([i32::MAX, 0, 0], 1).count(), 2 * 3 * 3 - 1);
+    }
+
+    #[test]
+    fn check_invalid_cell_size() {
+        for cell_size in [0.0, -1.0, 1e-6, f32::NAN, f32::INFINITY] {
+            assert!(matches!(
+                SpatialGrid::new(cell_size),
+                Err(SpatialGridError::InvalidCellSize(_))
+            ));
+        }
+
+        assert!(SpatialGrid::new(MIN_SPATIAL_GRID_CELL_SIZE).is_ok());
+    }
+
+    #[test]
+    fn check_empty_grid() {
+        let grid = SpatialGrid::new(1.0).unwrap();
+        assert_eq!(grid.nearest(Vec3::ZERO, |_| true), None);
+        assert_eq!(grid.nearest(Vec3::new(1e30, 0.0, 0.0), |_| true), None);
+    }
+
+    #[test]
+    fn check_far_away_object() {
+        let mut grid = SpatialGrid::new(MIN_SPATIAL_GRID_CELL_SIZE).unwrap();
+        grid.update(obj_id(0), Vec3::new(1e6, -1e6, 1e6));
+        grid.update(obj_id(1), Vec3::new(1e30, 0.0, 0.0));
+
+        let (nearest, distance) = grid.nearest(Vec3::ZERO, |_| true).unwrap();
+        assert_eq!(nearest, obj_id(0));
+        assert!((distance - 3f32.sqrt() * 1e6).abs() <= 1.0);
+
+        let (nearest, _) = grid.nearest(Vec3::new(1e30, 0.0, 0.0), |_| true).unwrap();
+        assert_eq!(nearest, obj_id(1));
+
+        assert_eq!(
+            grid.nearest(Vec3::ZERO, |object_id| object_id == obj_id(1))
+                .unwrap()
+                .0,
+            obj_id(1)
+        );
+    }
+
+    #[test]
+    fn check_grid_update_and_remove() {
+        let mut grid = SpatialGrid::new(1.0).unwrap();
+
+        grid.update(obj_id(0), Vec3::new(0.5, 0.5, 0.5));
+        grid.update(obj_id(1), Vec3::new(5.5, 0.5, 0.5));
+        assert_eq!(grid.len(), 2);
+        assert_eq!(grid.nearest(Vec3::ZERO, |_| true).unwrap().0, obj_id(0));
+
+        grid.update(obj_id(0), Vec3::new(10.5, 0.5, 0.5));
+        assert_eq!(grid.len(), 2);
+        assert_eq!(grid.nearest(Vec3::ZERO, |_| true).unwrap().0, obj_id(1));
+
+        grid.remove(obj_id(1));
+        assert_eq!(grid.len(), 1);
+        assert_eq!(grid.nearest(Vec3::ZERO, |_| true).unwrap().0, obj_id(0));
+
+        grid.remove(obj_id(0));
+        assert!(grid.is_empty());
+        assert_eq!(grid.nearest(Vec3::ZERO, |_| true), None);
+    }
+
+    #[test]
+    fn check_grid_nearest_matches_brute_force() {
+        let mut random = Random(0x5eed);
+        let objects = (0..500)
+            .map(|id| (obj_id(id), random.next_vec3(-100.0, 100.0)))
+            .collect::<Vec<_>>();
+
+        let mut grid = SpatialGrid::new(8.0).unwrap();
+        grid.rebuild(objects.iter().cloned());
+
+        for _ in 0..200 {
+            let point = random.next_vec3(-150.0, 150.0);
+            let filter = |object_id: ObjectId| object_id.get_zero_based_u32() % 3 != 1;
+
+            let expected = brute_force_nearest(&objects, point, filter).unwrap();
+            let actual = grid.nearest(point, filter).unwrap();
+
+            assert_eq!(actual.0, expected.0);
+            assert!((actual.1 - expected.1).abs() <= 1e-4);
+        }
+
+        assert_eq!(grid.nearest(Vec3::ZERO, |_| false), None);
+    }
+}
@@ -1,6 +1,7 @@
 use lvl_math::{Mat4, Quat, Vec3};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Transform {
     pub position: Vec3,
     pub rotation: Quat,
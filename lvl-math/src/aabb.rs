@@ -0,0 +1,106 @@
+use super::{Mat4, Vec3, Vec4};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// An axis-aligned bounding box.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Self {
+            min: Vec3::min(min, max),
+            max: Vec3::max(min, max),
+        }
+    }
+
+    /// Returns the smallest box containing all the points, or `None` if there is no point.
+    pub fn from_points(points: impl IntoIterator<Item = Vec3>) -> Option<Self> {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+
+        Some(points.fold(Self::new(first, first), |aabb, point| Self {
+            min: Vec3::min(aabb.min, point),
+            max: Vec3::max(aabb.max, point),
+        }))
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn size(&self) -> Vec3 {
+        self.max - self.min
+    }
+
+    pub fn corners(&self) -> [Vec3; 8] {
+        let (min, max) = (self.min, self.max);
+        [
+            Vec3::new(min.x, min.y, min.z),
+            Vec3::new(max.x, min.y, min.z),
+            Vec3::new(min.x, max.y, min.z),
+            Vec3::new(max.x, max.y, min.z),
+            Vec3::new(min.x, min.y, max.z),
+            Vec3::new(max.x, min.y, max.z),
+            Vec3::new(min.x, max.y, max.z),
+            Vec3::new(max.x, max.y, max.z),
+        ]
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            min: Vec3::min(self.min, other.min),
+            max: Vec3::max(self.max, other.max),
+        }
+    }
+
+    /// Returns the box containing all the corners transformed by the given matrix.
+    pub fn transformed(&self, matrix: &Mat4) -> Self {
+        Self::from_points(self.corners().map(|corner| {
+            let point = Vec4::from_vec3(corner, 1.0) * matrix;
+            Vec3::new(point.x, point.y, point.z)
+        }))
+        .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Quat;
+
+    #[test]
+    fn check_aabb_from_points() {
+        let aabb = Aabb::from_points([
+            Vec3::new(1.0, -2.0, 3.0),
+            Vec3::new(-1.0, 4.0, 0.0),
+            Vec3::new(0.5, 0.0, 5.0),
+        ])
+        .unwrap();
+
+        assert_eq!(aabb.min, Vec3::new(-1.0, -2.0, 0.0));
+        assert_eq!(aabb.max, Vec3::new(1.0, 4.0, 5.0));
+        assert_eq!(aabb.center(), Vec3::new(0.0, 1.0, 2.5));
+        assert_eq!(Aabb::from_points([]), None);
+    }
+
+    #[test]
+    fn check_aabb_transformed() {
+        let aabb = Aabb::new(Vec3::new(-1.0, -1.0, -1.0), Vec3::ONE);
+        let matrix = Mat4::srt(
+            Vec3::new(10.0, 0.0, 0.0),
+            Quat::from_axis_angle(Vec3::UP, std::f32::consts::FRAC_PI_4),
+            Vec3::new(2.0, 1.0, 1.0),
+        );
+        let transformed = aabb.transformed(&matrix);
+        let half_diagonal = 1.5 * std::f32::consts::SQRT_2;
+
+        assert!((transformed.center() - Vec3::new(10.0, 0.0, 0.0)).len() <= 1e-4);
+        assert!((transformed.max.x - 10.0 - half_diagonal).abs() <= 1e-4);
+        assert!((transformed.max.y - 1.0).abs() <= 1e-4);
+    }
+}
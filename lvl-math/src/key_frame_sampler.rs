@@ -0,0 +1,301 @@
+use super::{Quat, Vec3};
+use std::collections::HashMap;
+
+/// A cubic bezier curve from `(0, 0)` to `(1, 1)` through the control points `(x1, y1)` and
+/// `(x2, y2)`, which eases the progress between two key frames.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BezierCurve {
+    pub x1: f32,
+    pub y1: f32,
+    pub x2: f32,
+    pub y2: f32,
+}
+
+impl BezierCurve {
+    pub const LINEAR: Self = Self {
+        x1: 0.25,
+        y1: 0.25,
+        x2: 0.75,
+        y2: 0.75,
+    };
+
+    pub fn new(x1: f32, y1: f32, x2: f32, y2: f32) -> Self {
+        Self { x1, y1, x2, y2 }
+    }
+
+    /// Makes a curve from control points in `[0, 127]`, as they are stored in MMD motions.
+    pub fn from_mmd(x1: u8, y1: u8, x2: u8, y2: u8) -> Self {
+        Self::new(
+            x1 as f32 / 127.0,
+            y1 as f32 / 127.0,
+            x2 as f32 / 127.0,
+            y2 as f32 / 127.0,
+        )
+    }
+
+    /// Maps the linear progress `t` in `[0, 1]` into the eased one.
+    pub fn ease(&self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+
+        // the curve is monotonic in x, so the parameter for `t` is found by bisection
+        let bezier = |p1: f32, p2: f32, s: f32| {
+            let r = 1.0 - s;
+            3.0 * r * r * s * p1 + 3.0 * r * s * s * p2 + s * s * s
+        };
+        let (mut low, mut high) = (0f32, 1f32);
+
+        for _ in 0..24 {
+            let mid = (low + high) * 0.5;
+
+            if bezier(self.x1, self.x2, mid) < t {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+
+        bezier(self.y1, self.y2, (low + high) * 0.5)
+    }
+}
+
+/// The curves of a bone key frame, which ease the progress towards it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoneKeyFrameCurves {
+    pub translation_x: BezierCurve,
+    pub translation_y: BezierCurve,
+    pub translation_z: BezierCurve,
+    pub rotation: BezierCurve,
+}
+
+impl BoneKeyFrameCurves {
+    pub const LINEAR: Self = Self {
+        translation_x: BezierCurve::LINEAR,
+        translation_y: BezierCurve::LINEAR,
+        translation_z: BezierCurve::LINEAR,
+        rotation: BezierCurve::LINEAR,
+    };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoneKeyFrame {
+    pub frame_index: u32,
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub curves: BoneKeyFrameCurves,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MorphKeyFrame {
+    pub frame_index: u32,
+    pub weight: f32,
+}
+
+/// The key frames of a motion grouped by bone and morph name and sorted by frame index, so that
+/// the motion can be sampled at any frame. Before the first and after the last key frame, the
+/// value of that key frame is held.
+#[derive(Debug, Clone, Default)]
+pub struct KeyFrameSampler {
+    bone_tracks: HashMap<String, Vec<BoneKeyFrame>>,
+    morph_tracks: HashMap<String, Vec<MorphKeyFrame>>,
+}
+
+impl KeyFrameSampler {
+    /// Groups the key frames by name. Of the key frames of a name at the same frame, the last
+    /// one wins.
+    pub fn new(
+        bone_key_frames: impl IntoIterator<Item = (String, BoneKeyFrame)>,
+        morph_key_frames: impl IntoIterator<Item = (String, MorphKeyFrame)>,
+    ) -> Self {
+        let mut bone_tracks = HashMap::<String, Vec<BoneKeyFrame>>::new();
+        let mut morph_tracks = HashMap::<String, Vec<MorphKeyFrame>>::new();
+
+        for (name, key_frame) in bone_key_frames {
+            bone_tracks.entry(name).or_default().push(key_frame);
+        }
+
+        for (name, key_frame) in morph_key_frames {
+            morph_tracks.entry(name).or_default().push(key_frame);
+        }
+
+        // stable, so that the order of the key frames at the same frame is kept
+        for key_frames in bone_tracks.values_mut() {
+            key_frames.sort_by_key(|key_frame| key_frame.frame_index);
+        }
+
+        for key_frames in morph_tracks.values_mut() {
+            key_frames.sort_by_key(|key_frame| key_frame.frame_index);
+        }
+
+        Self {
+            bone_tracks,
+            morph_tracks,
+        }
+    }
+
+    pub fn bone_names(&self) -> impl Iterator<Item = &str> {
+        self.bone_tracks.keys().map(|name| name.as_str())
+    }
+
+    pub fn morph_names(&self) -> impl Iterator<Item = &str> {
+        self.morph_tracks.keys().map(|name| name.as_str())
+    }
+
+    /// Returns the frame index of the last key frame of any bone or morph, or `0` if there are
+    /// none.
+    pub fn last_frame_index(&self) -> u32 {
+        let bone_frame_indices = self
+            .bone_tracks
+            .values()
+            .filter_map(|key_frames| key_frames.last())
+            .map(|key_frame| key_frame.frame_index);
+        let morph_frame_indices = self
+            .morph_tracks
+            .values()
+            .filter_map(|key_frames| key_frames.last())
+            .map(|key_frame| key_frame.frame_index);
+
+        bone_frame_indices
+            .chain(morph_frame_indices)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Returns the translation and rotation of the bone at the frame, or `None` if there are no
+    /// key frames of the bone.
+    pub fn sample_bone(&self, bone_name: &str, frame: f32) -> Option<(Vec3, Quat)> {
+        let key_frames = self.bone_tracks.get(bone_name)?;
+
+        Some(
+            match find_segment(key_frames, |key_frame| key_frame.frame_index, frame)? {
+                Segment::Hold(key_frame) => (key_frame.translation, key_frame.rotation),
+                Segment::Between(from, to, t) => {
+                    // the curves of the later key frame drive the interpolation towards it
+                    let curves = &to.curves;
+                    let translation = Vec3::new(
+                        lerp(
+                            from.translation.x,
+                            to.translation.x,
+                            curves.translation_x.ease(t),
+                        ),
+                        lerp(
+                            from.translation.y,
+                            to.translation.y,
+                            curves.translation_y.ease(t),
+                        ),
+                        lerp(
+                            from.translation.z,
+                            to.translation.z,
+                            curves.translation_z.ease(t),
+                        ),
+                    );
+                    let rotation = Quat::slerp(from.rotation, to.rotation, curves.rotation.ease(t));
+
+                    (translation, rotation)
+                }
+            },
+        )
+    }
+
+    /// Returns the weight of the morph at the frame, or `None` if there are no key frames of the
+    /// morph. The weights are interpolated linearly, as morph key frames have no curves.
+    pub fn sample_morph(&self, morph_name: &str, frame: f32) -> Option<f32> {
+        let key_frames = self.morph_tracks.get(morph_name)?;
+
+        Some(
+            match find_segment(key_frames, |key_frame| key_frame.frame_index, frame)? {
+                Segment::Hold(key_frame) => key_frame.weight,
+                Segment::Between(from, to, t) => lerp(from.weight, to.weight, t),
+            },
+        )
+    }
+
+    pub fn sample_bones(&self, frame: f32) -> HashMap<String, (Vec3, Quat)> {
+        self.bone_tracks
+            .keys()
+            .filter_map(|name| Some((name.clone(), self.sample_bone(name, frame)?)))
+            .collect()
+    }
+
+    pub fn sample_morphs(&self, frame: f32) -> HashMap<String, f32> {
+        self.morph_tracks
+            .keys()
+            .filter_map(|name| Some((name.clone(), self.sample_morph(name, frame)?)))
+            .collect()
+    }
+}
+
+enum Segment<'a, T> {
+    Hold(&'a T),
+    /// The key frames around the frame and the linear progress between them.
+    Between(&'a T, &'a T, f32),
+}
+
+fn find_segment<T>(
+    key_frames: &[T],
+    frame_index: impl Fn(&T) -> u32,
+    frame: f32,
+) -> Option<Segment<'_, T>> {
+    let next = key_frames.partition_point(|key_frame| frame_index(key_frame) as f32 <= frame);
+
+    match (
+        next.checked_sub(1).map(|index| &key_frames[index]),
+        key_frames.get(next),
+    ) {
+        (None, None) => None,
+        (Some(key_frame), None) | (None, Some(key_frame)) => Some(Segment::Hold(key_frame)),
+        (Some(from), Some(to)) => {
+            let from_frame = frame_index(from) as f32;
+            let to_frame = frame_index(to) as f32;
+            let t = (frame - from_frame) / (to_frame - from_frame);
+            Some(Segment::Between(from, to, t))
+        }
+    }
+}
+
+fn lerp(from: f32, to: f32, t: f32) -> f32 {
+    from + (to - from) * t
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_bezier_curve_ease() {
+        let ease_in = BezierCurve::from_mmd(64, 0, 127, 127);
+
+        for t in [0.0, 0.25, 0.5, 0.75, 1.0] {
+            assert!((BezierCurve::LINEAR.ease(t) - t).abs() < 1e-5);
+        }
+
+        assert!(ease_in.ease(0.5) < 0.5);
+        assert!((ease_in.ease(0.0)).abs() < 1e-5);
+        assert!((ease_in.ease(1.0) - 1.0).abs() < 1e-5);
+        // out of range progress is clamped
+        assert!((ease_in.ease(2.0) - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn check_last_frame_index() {
+        let bone_key_frame = |frame_index| BoneKeyFrame {
+            frame_index,
+            translation: Vec3::ZERO,
+            rotation: Quat::IDENTITY,
+            curves: BoneKeyFrameCurves::LINEAR,
+        };
+        let morph_key_frame = |frame_index| MorphKeyFrame {
+            frame_index,
+            weight: 0.0,
+        };
+        let sampler = KeyFrameSampler::new(
+            [
+                ("arm".to_owned(), bone_key_frame(40)),
+                ("arm".to_owned(), bone_key_frame(10)),
+            ],
+            [("smile".to_owned(), morph_key_frame(30))],
+        );
+
+        assert_eq!(sampler.last_frame_index(), 40);
+        assert_eq!(KeyFrameSampler::default().last_frame_index(), 0);
+    }
+}
@@ -1,3 +1,5 @@
+mod aabb;
+mod key_frame_sampler;
 mod mat4;
 mod plane;
 mod quat;
@@ -5,9 +7,49 @@ mod vec2;
 mod vec3;
 mod vec4;
 
+pub use aabb::*;
+pub use key_frame_sampler::*;
 pub use mat4::*;
 pub use plane::*;
 pub use quat::*;
 pub use vec2::*;
 pub use vec3::*;
 pub use vec4::*;
+
+#[cfg(all(test, feature = "serde"))]
+mod serde_tests {
+    use super::*;
+    use serde::{de::DeserializeOwned, Serialize};
+    use std::fmt::Debug;
+
+    fn round_trip<T>(value: T) -> String
+    where
+        T: Serialize + DeserializeOwned + PartialEq + Debug,
+    {
+        let json = serde_json::to_string(&value).unwrap();
+        assert_eq!(serde_json::from_str::<T>(&json).unwrap(), value);
+        json
+    }
+
+    #[test]
+    fn check_round_trip() {
+        round_trip(Vec2::new(1.0, -2.0));
+        round_trip(Vec4::new(1.0, -2.0, 3.5, 0.25));
+        round_trip(Quat::from_axis_angle(Vec3::new(0.0, 1.0, 0.0), 0.5));
+        round_trip(Aabb::new(Vec3::ZERO, Vec3::ONE));
+        round_trip(Plane::new(
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, 2.0, 0.0),
+        ));
+        round_trip(PlaneSide::Back);
+
+        assert_eq!(
+            round_trip(Vec3::new(1.0, -2.0, 3.5)),
+            r#"{"x":1.0,"y":-2.0,"z":3.5}"#
+        );
+        assert_eq!(
+            round_trip(Mat4::translation(Vec3::new(1.0, 2.0, 3.0))),
+            "[1.0,0.0,0.0,0.0,0.0,1.0,0.0,0.0,0.0,0.0,1.0,0.0,1.0,2.0,3.0,1.0]"
+        );
+    }
+}
@@ -1,4 +1,5 @@
 use super::{Quat, Vec3, Vec4};
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 use std::{
     fmt::Display,
@@ -7,7 +8,8 @@ use std::{
 use zerocopy::AsBytes;
 
 #[repr(C)]
-#[derive(Serialize, Deserialize, AsBytes, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(transparent))]
+#[derive(AsBytes, Debug, Clone, PartialEq)]
 pub struct Mat4 {
     pub elements: [f32; 16],
 }
@@ -115,6 +117,12 @@ impl Mat4 {
         ])
     }
 
+    /// Makes a transform that scales, then rotates, then translates a point; the inverse of
+    /// [`Mat4::decompose`]. Same as [`Mat4::srt`], which is named after the multiplication order.
+    pub fn from_trs(translation: Vec3, rotation: Quat, scale: Vec3) -> Self {
+        Self::srt(translation, rotation, scale)
+    }
+
     pub fn trs(position: Vec3, rotation: Quat, scale: Vec3) -> Self {
         let translate = Mat4::translation(position);
         let rotation = Mat4::rotation(rotation);
@@ -169,13 +177,33 @@ impl Mat4 {
         )
     }
 
+    /// Same as [`Mat4::decompose`]. A mirroring matrix splits into a negative x scale and a proper
+    /// rotation; the scale used to be positive on every axis, leaving the mirroring in a rotation
+    /// that no quaternion can hold.
     pub fn split(&self) -> (Vec3, Quat, Vec3) {
-        let position = self.row(3).into();
-        let scale = Vec3::new(
-            Vec3::new(self.elements[0], self.elements[1], self.elements[2]).len(),
-            Vec3::new(self.elements[4], self.elements[5], self.elements[6]).len(),
-            Vec3::new(self.elements[8], self.elements[9], self.elements[10]).len(),
+        self.decompose()
+    }
+
+    /// Splits an affine transform made by [`Mat4::from_trs`] into its translation, rotation and
+    /// scale. A mirroring transform is reported as a negative scale on the x axis. The scale must
+    /// not be zero on any axis.
+    pub fn decompose(&self) -> (Vec3, Quat, Vec3) {
+        let translation = self.row(3).into();
+        let mut scale = Vec3::new(
+            Vec3::from(self.row(0)).len(),
+            Vec3::from(self.row(1)).len(),
+            Vec3::from(self.row(2)).len(),
         );
+
+        // the rotation must not contain the mirroring
+        if Vec3::dot(
+            Vec3::cross(self.row(0).into(), self.row(1).into()),
+            self.row(2).into(),
+        ) < 0.0
+        {
+            scale.x = -scale.x;
+        }
+
         let scale_removed = Mat4::compose_rows(
             Vec4::from_vec3(Vec3::from(self.row(0)) / scale.x, 0.0),
             Vec4::from_vec3(Vec3::from(self.row(1)) / scale.y, 0.0),
@@ -184,7 +212,7 @@ impl Mat4 {
         );
         let rotation = Quat::from_mat4(&scale_removed);
 
-        (position, rotation, scale)
+        (translation, rotation, scale)
     }
 
     pub fn split_translation(&self) -> Vec3 {
@@ -235,7 +263,7 @@ impl Mat4 {
 
         let det = self.determinant();
 
-        if det.abs() <= f32::EPSILON {
+        if self.is_singular(det) {
             return self;
         }
 
@@ -283,6 +311,78 @@ impl Mat4 {
         result
     }
 
+    /// Same as [`Mat4::inversed`], but returns `None` if the matrix is singular.
+    pub fn try_inversed(&self) -> Option<Self> {
+        if self.is_singular(self.determinant()) {
+            return None;
+        }
+
+        let inversed = self.inversed();
+        inversed
+            .elements
+            .iter()
+            .all(|element| element.is_finite())
+            .then_some(inversed)
+    }
+
+    /// Returns whether the determinant is too small to invert the matrix. It is compared to the
+    /// product of the lengths of the rows, which bounds it, so that the check does not depend on
+    /// the scale of the matrix.
+    fn is_singular(&self, det: f32) -> bool {
+        let row_lengths = (0..4).map(|index| self.row(index).len()).product::<f32>();
+        det.abs() <= f32::EPSILON * row_lengths
+    }
+
+    /// Inverts an affine matrix, i.e. one whose last column is `(0, 0, 0, 1)`, by inverting its
+    /// 3x3 part and the translation only. Returns `None` if the matrix is not affine or singular.
+    pub fn try_inversed_affine(&self) -> Option<Self> {
+        if self.column(3) != Vec4::new(0.0, 0.0, 0.0, 1.0) {
+            return None;
+        }
+
+        let row_0 = Vec3::new(self.elements[0], self.elements[1], self.elements[2]);
+        let row_1 = Vec3::new(self.elements[4], self.elements[5], self.elements[6]);
+        let row_2 = Vec3::new(self.elements[8], self.elements[9], self.elements[10]);
+        let translation = self.split_translation();
+
+        let det = Vec3::dot(row_0, Vec3::cross(row_1, row_2));
+
+        // scaled as in `is_singular`
+        if det.abs() <= f32::EPSILON * row_0.len() * row_1.len() * row_2.len() {
+            return None;
+        }
+
+        // the columns of the inverse of the 3x3 part
+        let inv_det = det.recip();
+        let column_0 = Vec3::cross(row_1, row_2) * inv_det;
+        let column_1 = Vec3::cross(row_2, row_0) * inv_det;
+        let column_2 = Vec3::cross(row_0, row_1) * inv_det;
+        let inv_translation = -Vec3::new(
+            Vec3::dot(translation, column_0),
+            Vec3::dot(translation, column_1),
+            Vec3::dot(translation, column_2),
+        );
+
+        Some(Self::new([
+            column_0.x,
+            column_1.x,
+            column_2.x,
+            0.0, //
+            column_0.y,
+            column_1.y,
+            column_2.y,
+            0.0, //
+            column_0.z,
+            column_1.z,
+            column_2.z,
+            0.0, //
+            inv_translation.x,
+            inv_translation.y,
+            inv_translation.z,
+            1.0, //
+        ]))
+    }
+
     pub fn transpose(&mut self) -> &mut Self {
         let b = self.elements[0 * 4 + 1];
         let c = self.elements[0 * 4 + 2];
@@ -1243,6 +1343,136 @@ mod test {
         ));
     }
 
+    #[test]
+    fn check_split_mirrored() {
+        let m = Mat4::scale(Vec3::new(1.0, -1.0, 1.0));
+        let (translation, rotation, scale) = m.split();
+
+        // the mirroring on y is reported on x, and the rest is a half turn around z
+        assert_eq!(translation, Vec3::ZERO);
+        assert_eq!(scale, Vec3::new(-1.0, 1.0, 1.0));
+        assert!((rotation.z.abs() - 1.0).abs() < 1e-6);
+        assert!(rotation.x.abs() < 1e-6 && rotation.y.abs() < 1e-6 && rotation.w.abs() < 1e-6);
+
+        let recomposed = Mat4::from_trs(translation, rotation, scale);
+
+        for index in 0..16 {
+            assert!((m.elements[index] - recomposed.elements[index]).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn check_decompose() {
+        let matrices = [
+            Mat4::from_trs(
+                Vec3::new(1.0, -2.0, 3.5),
+                Quat::from_axis_angle(Vec3::new(0.3, 0.8, -0.5).normalized(), 1.2),
+                Vec3::new(2.0, 0.5, 1.5),
+            ),
+            // mirrored on one axis
+            Mat4::from_trs(
+                Vec3::new(-7.0, 0.25, 4.0),
+                Quat::from_axis_angle(Vec3::new(-0.9, 0.1, 0.4).normalized(), -2.7),
+                Vec3::new(0.2, -3.0, 0.7),
+            ),
+            // mirrored on all axes
+            Mat4::from_trs(
+                Vec3::new(0.0, 5.0, 0.0),
+                Quat::from_axis_angle(Vec3::new(0.0, 0.0, 1.0), 0.4),
+                Vec3::new(-1.0, -2.0, -1.0),
+            ),
+            Mat4::look_at(
+                Vec3::new(3.0, 1.0, -2.0),
+                Vec3::ZERO,
+                Vec3::new(0.0, 1.0, 0.0),
+            ),
+        ];
+
+        for m in matrices {
+            let (translation, rotation, scale) = m.decompose();
+            let recomposed = Mat4::from_trs(translation, rotation, scale);
+
+            assert!(0.0 < scale.y && 0.0 < scale.z);
+
+            for index in 0..16 {
+                assert!((m.elements[index] - recomposed.elements[index]).abs() < 1e-5);
+            }
+        }
+
+        let (translation, _, scale) = Mat4::from_trs(
+            Vec3::new(1.0, 2.0, 3.0),
+            Quat::IDENTITY,
+            Vec3::new(-2.0, 1.0, 1.0),
+        )
+        .decompose();
+        assert_eq!(translation, Vec3::new(1.0, 2.0, 3.0));
+        assert!((scale.x + 2.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn check_try_inversed_affine() {
+        let matrices = [
+            Mat4::trs(
+                Vec3::new(1.0, -2.0, 3.5),
+                Quat::from_axis_angle(Vec3::new(0.3, 0.8, -0.5).normalized(), 1.2),
+                Vec3::new(2.0, 0.5, 1.5),
+            ),
+            Mat4::srt(
+                Vec3::new(-7.0, 0.25, 4.0),
+                Quat::from_axis_angle(Vec3::new(-0.9, 0.1, 0.4).normalized(), -2.7),
+                Vec3::new(0.2, 3.0, 0.7),
+            ),
+            // sheared
+            Mat4::new([
+                1.0, 0.4, 0.0, 0.0, //
+                0.0, 2.0, -0.3, 0.0, //
+                0.5, 0.0, 0.8, 0.0, //
+                10.0, -5.0, 2.0, 1.0, //
+            ]),
+        ];
+
+        for m in matrices {
+            let inv = m.try_inversed().unwrap();
+            let inv_affine = m.try_inversed_affine().unwrap();
+
+            for index in 0..16 {
+                assert!((inv.elements[index] - inv_affine.elements[index]).abs() < 1e-4);
+                assert!(
+                    ((m.clone() * inv_affine.clone()).elements[index]
+                        - Mat4::identity().elements[index])
+                        .abs()
+                        < 1e-4
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn check_try_inversed_singular_or_not_affine() {
+        let singular = Mat4::scale(Vec3::new(1.0, 0.0, 1.0));
+        let projection = Mat4::perspective(1.0, 1.5, 0.1, 100.0);
+
+        assert_eq!(singular.try_inversed(), None);
+        assert_eq!(singular.try_inversed_affine(), None);
+        assert!(projection.try_inversed().is_some());
+        assert_eq!(projection.try_inversed_affine(), None);
+
+        // the determinant of 1e-12 is far below the epsilon, but the matrix is well conditioned
+        let small = Mat4::scale(Vec3::new(1e-4, 1e-4, 1e-4));
+        let inv = small.try_inversed().unwrap();
+        assert!((inv.elements[0] - 1e4).abs() < 1e-1);
+        assert!(small.try_inversed_affine().is_some());
+
+        // the last row is the sum of the others, but rounding leaves a determinant above the epsilon
+        let large_singular = Mat4::new([
+            1e3, 2e3, 3e3, 4e3, //
+            -5e3, 6e3, 7e3, 8e3, //
+            9e3, -1e3, 2e3, 3e3, //
+            5e3, 7e3, 12e3, 15e3, //
+        ]);
+        assert_eq!(large_singular.try_inversed(), None);
+    }
+
     #[test]
     fn check_determiant_and_inverse_3() {
         let m = Mat4::new([
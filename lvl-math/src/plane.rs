@@ -1,16 +1,19 @@
 use super::Vec3;
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 use zerocopy::AsBytes;
 
 #[repr(C)]
-#[derive(Serialize, Deserialize, AsBytes, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(AsBytes, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum PlaneSide {
     Front,
     Back,
 }
 
 #[repr(C)]
-#[derive(Serialize, Deserialize, AsBytes, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(AsBytes, Debug, Clone, Copy, PartialEq)]
 pub struct Plane {
     pub normal: Vec3,
     pub distance: f32,
@@ -27,6 +30,30 @@ impl Plane {
         Vec3::dot(self.normal, point) + self.distance
     }
 
+    /// Returns the distance from the plane to the point, positive on the front side.
+    /// Same as [`Plane::distance_to_point`].
+    pub fn signed_distance(&self, point: Vec3) -> f32 {
+        self.distance_to_point(point)
+    }
+
+    /// Returns `t` where the ray `origin + direction * t` meets the plane, from either side.
+    /// Returns `None` if the ray is parallel to the plane or points away from it.
+    pub fn ray_intersection(&self, origin: Vec3, direction: Vec3) -> Option<f32> {
+        let denominator = Vec3::dot(self.normal, direction);
+
+        if denominator.abs() <= f32::EPSILON {
+            return None;
+        }
+
+        let t = -self.distance_to_point(origin) / denominator;
+
+        if t < 0.0 {
+            return None;
+        }
+
+        Some(t)
+    }
+
     pub fn point_side(&self, point: Vec3) -> PlaneSide {
         let distance = self.distance_to_point(point);
 
@@ -37,8 +64,9 @@ impl Plane {
         }
     }
 
+    /// Returns the point where the line through `point` along `direction` meets the plane.
     pub fn point_on(&self, point: Vec3, direction: Vec3) -> Vec3 {
-        point + direction * (self.distance_to_point(point) / Vec3::dot(self.normal, direction))
+        point - direction * (self.distance_to_point(point) / Vec3::dot(self.normal, direction))
     }
 }
 
@@ -65,4 +93,53 @@ mod tests {
         let test_point = Vec3::new(3.5, -5.5, -8.0);
         assert_eq!(plane.point_side(test_point), PlaneSide::Back);
     }
+
+    #[test]
+    fn test_plane_point_on() {
+        let plane_normal = Vec3::new(0.0, 1.0, 0.0);
+        let point_on_plane = Vec3::new(0.0, 2.0, 0.0);
+        let plane = Plane::new(plane_normal, point_on_plane);
+
+        let point = Vec3::new(1.0, 5.0, 0.0);
+        let direction = Vec3::new(1.0, -1.0, 0.0);
+        assert_eq!(plane.point_on(point, direction), Vec3::new(4.0, 2.0, 0.0));
+    }
+
+    #[test]
+    fn test_plane_signed_distance() {
+        let plane = Plane::new(Vec3::new(0.0, 2.0, 0.0), Vec3::new(0.0, 2.0, 0.0));
+
+        assert_eq!(plane.signed_distance(Vec3::new(5.0, 3.0, -1.0)), 1.0);
+        assert_eq!(plane.signed_distance(Vec3::new(5.0, -1.0, -1.0)), -3.0);
+    }
+
+    #[test]
+    fn test_plane_ray_intersection() {
+        let plane = Plane::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 2.0, 0.0));
+
+        // parallel
+        assert_eq!(
+            plane.ray_intersection(Vec3::new(0.0, 5.0, 0.0), Vec3::new(1.0, 0.0, 0.0)),
+            None
+        );
+
+        // from the front
+        assert_eq!(
+            plane.ray_intersection(Vec3::new(1.0, 5.0, 0.0), Vec3::new(0.0, -0.5, 0.0)),
+            Some(6.0)
+        );
+
+        // from behind
+        let origin = Vec3::new(1.0, -2.0, 3.0);
+        let direction = Vec3::new(1.0, 2.0, 0.0);
+        let t = plane.ray_intersection(origin, direction).unwrap();
+        assert_eq!(t, 2.0);
+        assert_eq!(plane.signed_distance(origin + direction * t), 0.0);
+
+        // pointing away
+        assert_eq!(
+            plane.ray_intersection(Vec3::new(0.0, 5.0, 0.0), Vec3::new(0.0, 1.0, 0.0)),
+            None
+        );
+    }
 }
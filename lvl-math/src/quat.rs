@@ -1,4 +1,5 @@
-use super::{Mat4, Vec3};
+use super::{Mat4, Vec3, Vec4};
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 use std::{
     fmt::Display,
@@ -7,7 +8,8 @@ use std::{
 use zerocopy::AsBytes;
 
 #[repr(C)]
-#[derive(Serialize, Deserialize, AsBytes, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(AsBytes, Debug, Clone, Copy, PartialEq)]
 pub struct Quat {
     pub x: f32,
     pub y: f32,
@@ -27,6 +29,8 @@ impl Quat {
         Self { x, y, z, w }
     }
 
+    /// Makes a rotation from the roll, pitch and yaw in radians, around the x, y and z axes.
+    #[deprecated(note = "use `Quat::from_euler`, which applies the angles in the ZXY order of MMD")]
     pub fn from_eular(x: f32, y: f32, z: f32) -> Self {
         let half_x = x * 0.5;
         let half_y = y * 0.5;
@@ -47,6 +51,15 @@ impl Quat {
         }
     }
 
+    /// Makes a rotation from Euler angles in radians, applied in the ZXY order as in MMD:
+    /// around the z axis first, then the x axis, then the y axis.
+    /// The inverse of [`Quat::to_euler`].
+    pub fn from_euler(x: f32, y: f32, z: f32) -> Self {
+        Self::from_axis_angle(Vec3::new(0.0, 1.0, 0.0), y)
+            * Self::from_axis_angle(Vec3::new(1.0, 0.0, 0.0), x)
+            * Self::from_axis_angle(Vec3::new(0.0, 0.0, 1.0), z)
+    }
+
     pub fn from_axis_angle(axis: Vec3, angle: f32) -> Self {
         let half_angle = angle * 0.5;
         let s = half_angle.sin();
@@ -102,6 +115,64 @@ impl Quat {
         quat.normalized()
     }
 
+    /// Returns the rotation that turns [`Vec3::FORWARD`] into the given direction,
+    /// keeping its up axis as close to the given up vector as possible.
+    /// The direction must not be parallel to the up vector.
+    pub fn look_rotation(forward: Vec3, up: Vec3) -> Self {
+        let z = -forward.normalized();
+        let x = Vec3::cross(up, z).normalized();
+        let y = Vec3::cross(z, x);
+
+        Self::from_mat4(&Mat4::compose_rows(
+            Vec4::from_vec3(x, 0.0),
+            Vec4::from_vec3(y, 0.0),
+            Vec4::from_vec3(z, 0.0),
+            Vec4::new(0.0, 0.0, 0.0, 1.0),
+        ))
+    }
+
+    pub fn dot(lhs: Self, rhs: Self) -> f32 {
+        lhs.x * rhs.x + lhs.y * rhs.y + lhs.z * rhs.z + lhs.w * rhs.w
+    }
+
+    pub fn slerp(from: Self, to: Self, t: f32) -> Self {
+        match t {
+            t if t <= 0f32 => from,
+            t if 1f32 <= t => to,
+            t => Self::slerp_unclamped(from, to, t),
+        }
+    }
+
+    /// Interpolates along the shorter arc; `q` and `-q` are the same rotation.
+    pub fn slerp_unclamped(from: Self, to: Self, t: f32) -> Self {
+        let dot = Self::dot(from, to);
+        let (to, dot) = if dot < 0f32 {
+            (Self::new(-to.x, -to.y, -to.z, -to.w), -dot)
+        } else {
+            (to, dot)
+        };
+        let angle = dot.min(1f32).acos();
+        let sin = angle.sin();
+
+        let (from_scale, to_scale) = if sin < f32::EPSILON {
+            (1f32 - t, t)
+        } else {
+            let inv_sin = sin.recip();
+            (
+                ((1f32 - t) * angle).sin() * inv_sin,
+                (t * angle).sin() * inv_sin,
+            )
+        };
+
+        Self::new(
+            from.x * from_scale + to.x * to_scale,
+            from.y * from_scale + to.y * to_scale,
+            from.z * from_scale + to.z * to_scale,
+            from.w * from_scale + to.w * to_scale,
+        )
+        .normalized()
+    }
+
     pub fn normalize(&mut self) -> &mut Self {
         let len = self.x * self.x + self.y * self.y + self.z * self.z + self.w * self.w;
         if len != 1.0 && len != 0.0 {
@@ -144,6 +215,8 @@ impl Quat {
         result
     }
 
+    /// Returns the roll, pitch and yaw in radians; the inverse of [`Quat::from_eular`].
+    #[deprecated(note = "use `Quat::to_euler`, which returns the angles in the ZXY order of MMD")]
     pub fn into_eular(self) -> Vec3 {
         let sinr_cosp = 2.0 * (self.w * self.x + self.y * self.z);
         let cosr_cosp = 1.0 - 2.0 * (self.x * self.x + self.y * self.y);
@@ -163,6 +236,31 @@ impl Quat {
         Vec3::new(roll, pitch, yaw)
     }
 
+    /// Returns the Euler angles in radians in the ZXY order; see [`Quat::from_euler`].
+    /// The x angle is in `[-PI/2, PI/2]` and the others are in `[-PI, PI]`. At the gimbal lock,
+    /// where the x angle is `PI/2` or `-PI/2`, the z angle is zero and the y angle carries the
+    /// rest of the rotation.
+    pub fn to_euler(self) -> Vec3 {
+        let Self { x, y, z, w } = self;
+        // the rotation matrix is `Ry * Rx * Rz`, whose entry at the row 1 and the column 2 is
+        // `-sin(x)`; rounding errors can push it out of the range of `asin`
+        let sin_x = (-2.0 * (y * z - w * x)).clamp(-1.0, 1.0);
+        let angle_x = sin_x.asin();
+
+        if 1.0 - 1e-6 < sin_x.abs() {
+            let m00 = 1.0 - 2.0 * (y * y + z * z);
+            let m01 = 2.0 * (x * y - w * z);
+            let angle_y = (sin_x.signum() * m01).atan2(m00);
+            return Vec3::new(angle_x, angle_y, 0.0);
+        }
+
+        let m02 = 2.0 * (x * z + w * y);
+        let m22 = 1.0 - 2.0 * (x * x + y * y);
+        let m10 = 2.0 * (x * y + w * z);
+        let m11 = 1.0 - 2.0 * (x * x + z * z);
+        Vec3::new(angle_x, m02.atan2(m22), m10.atan2(m11))
+    }
+
     pub fn into_mat4(self) -> Mat4 {
         let x2 = self.x + self.x;
         let y2 = self.y + self.y;
@@ -269,13 +367,99 @@ impl Neg for Quat {
 
 impl Display for Quat {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let eular = self.into_eular();
+        let euler = self.to_euler();
         write!(
             f,
             "Quat(x={}, y={}, z={})",
-            eular.x.to_degrees(),
-            eular.y.to_degrees(),
-            eular.z.to_degrees()
+            euler.x.to_degrees(),
+            euler.y.to_degrees(),
+            euler.z.to_degrees()
         )
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn equals_vec3(a: Vec3, b: Vec3) -> bool {
+        (a - b).len() <= 1e-5
+    }
+
+    fn equals_rotation(a: Quat, b: Quat) -> bool {
+        1.0 - 1e-5 <= Quat::dot(a, b).abs()
+    }
+
+    #[test]
+    fn check_euler_order() {
+        // z first, then x: the x axis turns into the y axis, which then turns into the z axis
+        let rotation = Quat::from_euler(
+            std::f32::consts::FRAC_PI_2,
+            0.0,
+            std::f32::consts::FRAC_PI_2,
+        );
+        assert!(equals_vec3(
+            rotation * Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0)
+        ));
+    }
+
+    #[test]
+    fn check_euler_round_trip() {
+        let angles = [-3.0, -1.2, -0.4, 0.0, 0.3, 1.1, 2.9];
+        let pitches = [-1.5, -0.7, 0.0, 0.5, 1.4];
+
+        for &x in &pitches {
+            for &y in &angles {
+                for &z in &angles {
+                    let euler = Quat::from_euler(x, y, z).to_euler();
+                    assert!(
+                        equals_vec3(euler, Vec3::new(x, y, z)),
+                        "{:?} for {} {} {}",
+                        euler,
+                        x,
+                        y,
+                        z
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn check_euler_at_gimbal_lock() {
+        for x in [std::f32::consts::FRAC_PI_2, -std::f32::consts::FRAC_PI_2] {
+            let rotation = Quat::from_euler(x, 0.7, 0.3);
+            let euler = rotation.to_euler();
+
+            assert!((euler.x - x).abs() <= 1e-3);
+            assert_eq!(euler.z, 0.0);
+            assert!(euler.y.is_finite());
+            assert!(equals_rotation(
+                Quat::from_euler(euler.x, euler.y, euler.z),
+                rotation
+            ));
+        }
+    }
+
+    #[test]
+    fn check_look_rotation() {
+        for forward in [
+            Vec3::FORWARD,
+            Vec3::BACKWARD,
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(1.0, 2.0, -3.0).normalized(),
+        ] {
+            let rotation = Quat::look_rotation(forward, Vec3::UP);
+
+            assert!(equals_vec3(rotation * Vec3::FORWARD, forward));
+            // the right axis stays horizontal
+            assert!((rotation * Vec3::new(1.0, 0.0, 0.0)).y.abs() <= 1e-5);
+        }
+
+        assert!(equals_vec3(
+            Quat::look_rotation(Vec3::FORWARD, Vec3::UP) * Vec3::UP,
+            Vec3::UP
+        ));
+    }
+}
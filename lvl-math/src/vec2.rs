@@ -1,4 +1,5 @@
 use super::{Vec3, Vec4};
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 use std::{
     fmt::Display,
@@ -7,7 +8,8 @@ use std::{
 use zerocopy::AsBytes;
 
 #[repr(C)]
-#[derive(Serialize, Deserialize, AsBytes, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(AsBytes, Debug, Clone, Copy, PartialEq)]
 pub struct Vec2 {
     pub x: f32,
     pub y: f32,
@@ -43,10 +45,13 @@ impl Vec2 {
         self.len_square().sqrt()
     }
 
+    /// Returns the squared length, which avoids the square root when only comparing lengths.
     pub fn len_square(self) -> f32 {
         self.x * self.x + self.y * self.y
     }
 
+    /// Returns the vector scaled to the unit length, or zero if its length is near zero;
+    /// it never produces NaN.
     pub fn normalized(self) -> Self {
         let len = self.len();
         if len < f32::EPSILON {
@@ -67,16 +72,33 @@ impl Vec2 {
         lhs.x * rhs.x + lhs.y * rhs.y
     }
 
+    /// Returns the z component of the cross product of the vectors extended to 3D;
+    /// it is positive if `rhs` is counter-clockwise from `lhs`.
+    pub fn cross(lhs: Self, rhs: Self) -> f32 {
+        lhs.x * rhs.y - lhs.y * rhs.x
+    }
+
     pub fn project(lhs: Self, normal: Self) -> Self {
         normal * Self::projected_len(lhs, normal)
     }
 
+    /// Returns zero if the normal is near zero.
     pub fn projected_len(lhs: Self, normal: Self) -> f32 {
-        Self::dot(lhs, normal) / normal.len()
+        let len = normal.len();
+        if len < f32::EPSILON {
+            return 0f32;
+        }
+        Self::dot(lhs, normal) / len
     }
 
+    /// Returns the angle in radians, or zero if either vector is near zero.
     pub fn angle(from: Self, to: Self) -> f32 {
-        (Self::dot(from, to) / (from.len() * to.len())).acos()
+        let len = from.len() * to.len();
+        if len < f32::EPSILON {
+            return 0f32;
+        }
+        // rounding errors can push the cosine of parallel vectors out of the range of `acos`
+        (Self::dot(from, to) / len).clamp(-1f32, 1f32).acos()
     }
 
     pub fn angle_signed(from: Self, to: Self) -> f32 {
@@ -410,3 +432,24 @@ impl Display for Vec2 {
         write!(f, "Vec2(x={}, y={})", self.x, self.y)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_zero_vector_normalized() {
+        let normalized = Vec2::ZERO.normalized();
+
+        assert_eq!(normalized, Vec2::ZERO);
+        assert!(!normalized.x.is_nan() && !normalized.y.is_nan());
+        assert_eq!(Vec2::new(3.0, 4.0).normalized(), Vec2::new(0.6, 0.8));
+    }
+
+    #[test]
+    fn check_cross() {
+        assert_eq!(Vec2::cross(Vec2::RIGHT, Vec2::UP), 1.0);
+        assert_eq!(Vec2::cross(Vec2::UP, Vec2::RIGHT), -1.0);
+        assert_eq!(Vec2::cross(Vec2::ONE, Vec2::ONE * 2.0), 0.0);
+    }
+}
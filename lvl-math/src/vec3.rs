@@ -1,4 +1,5 @@
 use super::{Vec2, Vec4};
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 use std::{
     fmt::Display,
@@ -7,7 +8,8 @@ use std::{
 use zerocopy::AsBytes;
 
 #[repr(C)]
-#[derive(Serialize, Deserialize, AsBytes, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(AsBytes, Debug, Clone, Copy, PartialEq)]
 pub struct Vec3 {
     pub x: f32,
     pub y: f32,
@@ -80,10 +82,13 @@ impl Vec3 {
         self.len_square().sqrt()
     }
 
+    /// Returns the squared length, which avoids the square root when only comparing lengths.
     pub fn len_square(self) -> f32 {
         self.x * self.x + self.y * self.y + self.z * self.z
     }
 
+    /// Returns the vector scaled to the unit length, or zero if its length is near zero;
+    /// it never produces NaN.
     pub fn normalized(self) -> Self {
         let len = self.len();
         if len < f32::EPSILON {
@@ -116,12 +121,23 @@ impl Vec3 {
         normal * Self::projected_len(lhs, normal)
     }
 
+    /// Returns zero if the normal is near zero.
     pub fn projected_len(lhs: Self, normal: Self) -> f32 {
-        Self::dot(lhs, normal) / normal.len()
+        let len = normal.len();
+        if len < f32::EPSILON {
+            return 0f32;
+        }
+        Self::dot(lhs, normal) / len
     }
 
+    /// Returns the angle in radians, or zero if either vector is near zero.
     pub fn angle(from: Self, to: Self) -> f32 {
-        (Self::dot(from, to) / (from.len() * to.len())).acos()
+        let len = from.len() * to.len();
+        if len < f32::EPSILON {
+            return 0f32;
+        }
+        // rounding errors can push the cosine of parallel vectors out of the range of `acos`
+        (Self::dot(from, to) / len).clamp(-1f32, 1f32).acos()
     }
 
     pub fn angle_signed(from: Self, to: Self, normal: Self) -> f32 {
@@ -473,3 +489,40 @@ impl Display for Vec3 {
         write!(f, "Vec3(x={}, y={}, z={})", self.x, self.y, self.z)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_zero_vector_normalized() {
+        let normalized = Vec3::ZERO.normalized();
+
+        assert_eq!(normalized, Vec3::ZERO);
+        assert!(!normalized.x.is_nan() && !normalized.y.is_nan() && !normalized.z.is_nan());
+        assert_eq!(
+            Vec3::new(0.0, 3.0, 4.0).normalized(),
+            Vec3::new(0.0, 0.6, 0.8)
+        );
+    }
+
+    #[test]
+    fn check_degenerate_angles() {
+        assert_eq!(Vec3::angle(Vec3::ZERO, Vec3::UP), 0.0);
+        assert_eq!(Vec3::projected_len(Vec3::UP, Vec3::ZERO), 0.0);
+
+        let v = Vec3::new(0.1, 0.2, 0.3);
+        assert!(!Vec3::angle(v, v * 3.0).is_nan());
+        assert!(!Vec3::slerp(v, v * 3.0, 0.5).x.is_nan());
+    }
+
+    #[test]
+    fn check_len_square_and_cross() {
+        let v = Vec3::new(1.0, 2.0, 2.0);
+
+        assert_eq!(v.len_square(), 9.0);
+        assert_eq!(v.len(), 3.0);
+        assert_eq!(Vec3::cross(Vec3::RIGHT, Vec3::UP), Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(Vec3::dot(Vec3::cross(v, Vec3::UP), v), 0.0);
+    }
+}
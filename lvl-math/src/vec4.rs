@@ -1,4 +1,5 @@
 use super::{Vec2, Vec3};
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 use std::{
     fmt::Display,
@@ -7,7 +8,8 @@ use std::{
 use zerocopy::AsBytes;
 
 #[repr(C)]
-#[derive(Serialize, Deserialize, AsBytes, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(AsBytes, Debug, Clone, Copy, PartialEq)]
 pub struct Vec4 {
     pub x: f32,
     pub y: f32,
@@ -91,10 +93,13 @@ impl Vec4 {
         self.len_square().sqrt()
     }
 
+    /// Returns the squared length, which avoids the square root when only comparing lengths.
     pub fn len_square(self) -> f32 {
         self.x * self.x + self.y * self.y + self.z * self.z + self.w * self.w
     }
 
+    /// Returns the vector scaled to the unit length, or zero if its length is near zero;
+    /// it never produces NaN.
     pub fn normalized(self) -> Self {
         let len = self.len();
         if len < f32::EPSILON {
@@ -119,8 +124,13 @@ impl Vec4 {
         normal * Self::projected_len(lhs, normal)
     }
 
+    /// Returns zero if the normal is near zero.
     pub fn projected_len(lhs: Self, normal: Self) -> f32 {
-        Self::dot(lhs, normal) / normal.len()
+        let len = normal.len();
+        if len < f32::EPSILON {
+            return 0f32;
+        }
+        Self::dot(lhs, normal) / len
     }
 
     pub fn reflect(lhs: Self, normal: Self) -> Self {
@@ -466,3 +476,20 @@ impl Display for Vec4 {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_zero_vector_normalized() {
+        let normalized = Vec4::ZERO.normalized();
+
+        assert_eq!(normalized, Vec4::ZERO);
+        assert_eq!(Vec4::projected_len(Vec4::ONE, Vec4::ZERO), 0.0);
+        assert_eq!(
+            Vec4::new(0.0, 0.0, 3.0, 4.0).normalized(),
+            Vec4::new(0.0, 0.0, 0.6, 0.8)
+        );
+    }
+}
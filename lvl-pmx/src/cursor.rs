@@ -1,8 +1,11 @@
 use crate::parse::ParseError;
+use std::cell::Cell;
 
 pub struct Cursor<'a> {
     buffer: &'a [u8],
     position: usize,
+    /// `true` if a read ran past the end of the buffer; the item may parse with more bytes.
+    is_truncated: Cell<bool>,
 }
 
 impl<'a> Cursor<'a> {
@@ -10,15 +13,25 @@ impl<'a> Cursor<'a> {
         Self {
             buffer,
             position: 0,
+            is_truncated: Cell::new(false),
         }
     }
 
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn is_truncated(&self) -> bool {
+        self.is_truncated.get()
+    }
+
     pub fn has_bytes(&self, len: usize) -> bool {
         self.position + len <= self.buffer.len()
     }
 
     pub fn ensure_bytes<E: ParseError>(&self, len: usize) -> Result<(), E> {
         if !self.has_bytes(len) {
+            self.is_truncated.set(true);
             return Err(E::error_unexpected_eof());
         }
 
@@ -26,12 +39,16 @@ impl<'a> Cursor<'a> {
     }
 
     pub fn read<E: ParseError, const L: usize>(&mut self) -> Result<&[u8; L], E> {
+        self.ensure_bytes::<E>(L)?;
+
         let result = &self.buffer[self.position..self.position + L];
         self.position += L;
         Ok(unsafe { &*(result as *const [u8] as *const [u8; L]) })
     }
 
     pub fn read_dynamic<E: ParseError>(&mut self, len: usize) -> Result<&[u8], E> {
+        self.ensure_bytes::<E>(len)?;
+
         let result = &self.buffer[self.position..self.position + len];
         self.position += len;
         Ok(result)
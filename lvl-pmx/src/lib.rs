@@ -1,30 +1,42 @@
 mod cursor;
 mod parse;
 mod pmx_bone;
+mod pmx_bone_tree;
 mod pmx_display;
 mod pmx_header;
 mod pmx_indices;
 mod pmx_joint;
 mod pmx_material;
 mod pmx_morph;
+mod pmx_obj;
 mod pmx_primitives;
+mod pmx_reader;
 mod pmx_rigidbody;
 mod pmx_texture;
+mod pmx_validation;
 mod pmx_vertex;
+#[cfg(any(test, feature = "test-util"))]
+mod pmx_writer;
 mod primitives;
 
 use cursor::Cursor;
 use parse::Parse;
 pub use pmx_bone::*;
+pub use pmx_bone_tree::*;
 pub use pmx_display::*;
 pub use pmx_header::*;
 pub use pmx_indices::*;
 pub use pmx_joint::*;
 pub use pmx_material::*;
 pub use pmx_morph::*;
+pub use pmx_obj::*;
+pub use pmx_reader::*;
 pub use pmx_rigidbody::*;
 pub use pmx_texture::*;
+pub use pmx_validation::*;
 pub use pmx_vertex::*;
+#[cfg(any(test, feature = "test-util"))]
+pub use pmx_writer::*;
 use std::fmt::Display;
 use thiserror::Error;
 
@@ -50,6 +62,16 @@ pub enum PmxParseError {
     PmxRigidbodyParseError(#[from] pmx_rigidbody::PmxRigidbodyParseError),
     #[error("failed to parse PMX joint: {0}")]
     PmxJointParseError(#[from] pmx_joint::PmxJointParseError),
+    #[error("failed to read PMX data: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("PMX has {} invalid indices{}", .0.len(), describe_first_error(.0))]
+    PmxValidationError(Vec<PmxValidationError>),
+}
+
+fn describe_first_error(errors: &[PmxValidationError]) -> String {
+    errors
+        .first()
+        .map_or_else(String::new, |error| format!("; the first one: {}", error))
 }
 
 #[derive(Debug, Clone)]
@@ -94,6 +116,25 @@ impl Pmx {
             joints,
         })
     }
+
+    /// Parses the PMX and rejects it if [`Pmx::validate`] fails.
+    pub fn parse_validated(buf: impl AsRef<[u8]>) -> Result<Self, PmxParseError> {
+        let pmx = Self::parse(buf)?;
+        pmx.validate().map_err(PmxParseError::PmxValidationError)?;
+        Ok(pmx)
+    }
+
+    /// Checks that the indices between the items are in bounds and that the materials cover all
+    /// the vertex indices. Parsing does not check them, so that lenient callers can still load
+    /// the model; all the errors found are returned.
+    pub fn validate(&self) -> Result<(), Vec<PmxValidationError>> {
+        pmx_validation::validate(self)
+    }
+
+    /// Resolves the hierarchy of the bones; see [`PmxBoneTree`].
+    pub fn bone_tree(&self) -> PmxBoneTree<'_> {
+        PmxBoneTree::new(&self.bones)
+    }
 }
 
 impl Display for Pmx {
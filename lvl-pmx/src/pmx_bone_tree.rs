@@ -0,0 +1,214 @@
+use crate::{
+    pmx_bone::{PmxBone, PmxBoneIKLink, PmxBoneInheritance},
+    pmx_primitives::{PmxBoneIndex, PmxVec3},
+};
+
+/// The hierarchy of the bones of a [`Pmx`](crate::Pmx), resolved from their parent indices.
+/// Parent, IK and append indices that are out of bounds are treated as absent, so that the tree
+/// can be built from models that do not pass [`Pmx::validate`](crate::Pmx::validate).
+#[derive(Debug, Clone)]
+pub struct PmxBoneTree<'a> {
+    bones: &'a [PmxBone],
+    parents: Vec<Option<usize>>,
+    children: Vec<Vec<usize>>,
+    roots: Vec<usize>,
+}
+
+impl<'a> PmxBoneTree<'a> {
+    pub fn new(bones: &'a [PmxBone]) -> Self {
+        let parents = Vec::from_iter(bones.iter().enumerate().map(|(index, bone)| {
+            resolve_index(bone.parent_index, bones.len()).filter(|&parent| parent != index)
+        }));
+        let mut children = vec![Vec::new(); bones.len()];
+        let mut roots = Vec::new();
+
+        for (index, parent) in parents.iter().enumerate() {
+            match parent {
+                Some(parent) => children[*parent].push(index),
+                None => roots.push(index),
+            }
+        }
+
+        Self {
+            bones,
+            parents,
+            children,
+            roots,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.bones.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bones.is_empty()
+    }
+
+    /// Returns the bones without a parent, in the order they are declared.
+    pub fn roots(&self) -> &[usize] {
+        &self.roots
+    }
+
+    pub fn parent_of(&self, bone_index: usize) -> Option<usize> {
+        self.parents.get(bone_index).copied().flatten()
+    }
+
+    /// Returns the direct children of the bone, in the order they are declared.
+    pub fn children_of(&self, bone_index: usize) -> &[usize] {
+        self.children
+            .get(bone_index)
+            .map(|children| children.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Returns the ancestors of the bone, from its parent up to the root. A cycle in the parent
+    /// indices ends the walk once every bone has been visited.
+    pub fn ancestors_of(&self, bone_index: usize) -> impl Iterator<Item = usize> + '_ {
+        std::iter::successors(self.parent_of(bone_index), |&index| self.parent_of(index))
+            .take(self.bones.len())
+    }
+
+    /// Returns the rest position of the bone in model space. PMX stores the positions of the
+    /// bones in model space already, so this is the position of the bone as it is declared.
+    pub fn world_rest_position(&self, bone_index: usize) -> Option<PmxVec3> {
+        self.bones.get(bone_index).map(|bone| bone.position)
+    }
+
+    /// Returns the rest position of the bone relative to its parent, or to the origin for the
+    /// roots. Summing the offsets of the bone and its ancestors gives its world rest position.
+    pub fn local_rest_offset(&self, bone_index: usize) -> Option<PmxVec3> {
+        let position = self.world_rest_position(bone_index)?;
+
+        Some(match self.parent_of(bone_index) {
+            Some(parent) => {
+                let parent_position = self.bones[parent].position;
+                PmxVec3 {
+                    x: position.x - parent_position.x,
+                    y: position.y - parent_position.y,
+                    z: position.z - parent_position.z,
+                }
+            }
+            None => position,
+        })
+    }
+
+    /// Returns the target bone of the IK bone, or `None` if the bone is not an IK bone.
+    /// IK is not solved here; the chain is exposed for the callers that do.
+    pub fn ik_target_of(&self, bone_index: usize) -> Option<usize> {
+        let ik = self.bones.get(bone_index)?.ik.as_ref()?;
+        resolve_index(ik.index, self.bones.len())
+    }
+
+    /// Returns the links of the IK chain of the bone, from the target towards the IK bone.
+    pub fn ik_links_of(&self, bone_index: usize) -> &'a [PmxBoneIKLink] {
+        let bones = self.bones;
+        bones
+            .get(bone_index)
+            .and_then(|bone| bone.ik.as_ref())
+            .map(|ik| ik.links.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Returns the bone whose rotation or translation the bone appends (grants), along with the
+    /// inheritance that describes how much of it.
+    pub fn append_parent_of(&self, bone_index: usize) -> Option<(usize, &'a PmxBoneInheritance)> {
+        let bones = self.bones;
+        let inheritance = bones.get(bone_index)?.inheritance.as_ref()?;
+        let parent = resolve_index(inheritance.index, bones.len())?;
+        Some((parent, inheritance))
+    }
+}
+
+fn resolve_index(index: PmxBoneIndex, count: usize) -> Option<usize> {
+    usize::try_from(index.get())
+        .ok()
+        .filter(|&index| index < count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pmx_bone::{PmxBoneFlags, PmxBoneTailPosition};
+
+    fn make_bone(name: &str, position: [f32; 3], parent_index: i32) -> PmxBone {
+        PmxBone {
+            name_local: name.to_owned(),
+            name_universal: name.to_owned(),
+            position: PmxVec3 {
+                x: position[0],
+                y: position[1],
+                z: position[2],
+            },
+            parent_index: PmxBoneIndex::new(parent_index),
+            layer: 0,
+            flags: PmxBoneFlags {
+                indexed_tail_position: false,
+                is_rotatable: true,
+                is_translatable: false,
+                is_visible: true,
+                is_enabled: true,
+                supports_ik: false,
+                inherit_rotation: false,
+                inherit_translation: false,
+                fixed_axis: false,
+                local_coordinate: false,
+                physics_after_deform: false,
+                external_parent_deform: false,
+            },
+            tail_position: PmxBoneTailPosition::Vec3 {
+                position: PmxVec3 {
+                    x: 0.0,
+                    y: 0.0,
+                    z: 0.0,
+                },
+            },
+            inheritance: None,
+            fixed_axis: None,
+            local_coordinate: None,
+            external_parent: None,
+            ik: None,
+        }
+    }
+
+    #[test]
+    fn check_three_bone_chain() {
+        // the leaf is declared before its parent, and the last bone is parented to itself
+        let bones = vec![
+            make_bone("root", [0.0, 1.0, 0.0], -1),
+            make_bone("leaf", [1.0, 3.0, 0.5], 2),
+            make_bone("middle", [0.0, 2.0, 0.0], 0),
+            make_bone("loose", [4.0, 0.0, 0.0], 3),
+        ];
+        let tree = PmxBoneTree::new(&bones);
+
+        assert_eq!(tree.roots(), &[0, 3]);
+        assert_eq!(tree.children_of(0), &[2]);
+        assert_eq!(tree.children_of(2), &[1]);
+        assert!(tree.children_of(1).is_empty());
+        assert!(tree.children_of(4).is_empty());
+        assert_eq!(Vec::from_iter(tree.ancestors_of(1)), vec![2, 0]);
+
+        for bone_index in 0..bones.len() {
+            let world_position = tree.world_rest_position(bone_index).unwrap();
+            let accumulated = std::iter::once(bone_index)
+                .chain(tree.ancestors_of(bone_index))
+                .map(|index| tree.local_rest_offset(index).unwrap())
+                .fold([0.0; 3], |sum, offset| {
+                    [sum[0] + offset.x, sum[1] + offset.y, sum[2] + offset.z]
+                });
+
+            assert_eq!(
+                [world_position.x, world_position.y, world_position.z],
+                accumulated
+            );
+        }
+
+        let leaf_offset = tree.local_rest_offset(1).unwrap();
+        assert_eq!(
+            [leaf_offset.x, leaf_offset.y, leaf_offset.z],
+            [1.0, 1.0, 0.5]
+        );
+        assert!(tree.world_rest_position(4).is_none());
+    }
+}
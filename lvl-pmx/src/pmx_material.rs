@@ -142,6 +142,7 @@ impl Parse for Vec<PmxMaterial> {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
 pub struct PmxMaterialFlags {
     /// `true` if back faces should be rendered otherwise `false`.
     pub no_cull_back_face: bool,
@@ -167,6 +168,31 @@ pub struct PmxMaterialFlags {
     ///
     /// `true` if each of the 3 vertices of the triangle should be drawn as a line otherwise `false`.
     pub line_drawing: bool,
+    /// The flag byte as it was read, including the bits that are not modeled by the fields above.
+    pub raw_flags: u8,
+}
+
+impl PmxMaterialFlags {
+    /// Returns the flag byte to be written; the bits that are not modeled are taken from `raw_flags`.
+    pub fn to_bits(&self) -> u8 {
+        let known_flags = [
+            (self.no_cull_back_face, 0b0000_0001),
+            (self.cast_shadow_on_ground, 0b0000_0010),
+            (self.cast_shadow_on_object, 0b0000_0100),
+            (self.receive_shadow, 0b0000_1000),
+            (self.has_edge, 0b0001_0000),
+            (self.vertex_color, 0b0010_0000),
+            (self.point_drawing, 0b0100_0000),
+            (self.line_drawing, 0b1000_0000),
+        ];
+
+        known_flags
+            .into_iter()
+            .fold(self.raw_flags, |bits, (is_set, mask)| match is_set {
+                true => bits | mask,
+                false => bits & !mask,
+            })
+    }
 }
 
 impl Parse for PmxMaterialFlags {
@@ -194,6 +220,7 @@ impl Parse for PmxMaterialFlags {
             vertex_color,
             point_drawing,
             line_drawing,
+            raw_flags: flags,
         })
     }
 }
@@ -266,3 +293,46 @@ impl Parse for PmxMaterialToonMode {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pmx_header::{PmxIndexSize, PmxTextEncoding};
+
+    fn parse_flags(flags: u8) -> PmxMaterialFlags {
+        let config = PmxConfig {
+            text_encoding: PmxTextEncoding::Utf8,
+            additional_vec4_count: 0,
+            vertex_index_size: PmxIndexSize::U8,
+            texture_index_size: PmxIndexSize::U8,
+            material_index_size: PmxIndexSize::U8,
+            bone_index_size: PmxIndexSize::U8,
+            morph_index_size: PmxIndexSize::U8,
+            rigidbody_index_size: PmxIndexSize::U8,
+        };
+        let buffer = [flags];
+
+        PmxMaterialFlags::parse(&config, &mut Cursor::new(&buffer)).unwrap()
+    }
+
+    #[test]
+    fn check_raw_flags_preserved() {
+        let flags = parse_flags(0b1001_0001);
+
+        assert!(flags.no_cull_back_face);
+        assert!(flags.has_edge);
+        assert!(flags.line_drawing);
+        assert!(!flags.point_drawing);
+        assert_eq!(flags.raw_flags, 0b1001_0001);
+        assert_eq!(flags.to_bits(), 0b1001_0001);
+    }
+
+    #[test]
+    fn check_to_bits_applies_edits() {
+        let mut flags = parse_flags(0b1001_0001);
+        flags.has_edge = false;
+        flags.receive_shadow = true;
+
+        assert_eq!(flags.to_bits(), 0b1000_1001);
+    }
+}
@@ -0,0 +1,107 @@
+use crate::Pmx;
+use std::io::{Result as IoResult, Write};
+
+/// Writes the geometry of the PMX as a Wavefront OBJ, for inspecting it in other tools.
+/// Each material becomes a group of the faces it covers, named after it and using an OBJ
+/// material of the same name; no MTL file is written.
+///
+/// PMX is left-handed with clockwise faces and uvs from the top, so the z axis is flipped, the
+/// faces are reversed and the uvs are flipped vertically to match the usual OBJ conventions.
+/// Indices not covered by any material are not written.
+pub fn export_obj(pmx: &Pmx, mut writer: impl Write) -> IoResult<()> {
+    writeln!(writer, "# {}", pmx.header.model_name_local)?;
+    writeln!(writer, "o {}", obj_name(&pmx.header.model_name_local))?;
+
+    for vertex in &pmx.vertices {
+        let position = &vertex.position;
+        writeln!(writer, "v {} {} {}", position.x, position.y, -position.z)?;
+    }
+
+    for vertex in &pmx.vertices {
+        writeln!(writer, "vt {} {}", vertex.uv.x, 1.0 - vertex.uv.y)?;
+    }
+
+    for vertex in &pmx.vertices {
+        let normal = &vertex.normal;
+        writeln!(writer, "vn {} {} {}", normal.x, normal.y, -normal.z)?;
+    }
+
+    let vertex_indices = &pmx.indices.vertex_indices;
+    let mut offset = 0usize;
+
+    for material in &pmx.materials {
+        let end = (offset + material.surface_count as usize).min(vertex_indices.len());
+        let name = obj_name(&material.name_local);
+        writeln!(writer, "g {}", name)?;
+        writeln!(writer, "usemtl {}", name)?;
+
+        for triangle in vertex_indices[offset..end].chunks_exact(3) {
+            write!(writer, "f")?;
+
+            // OBJ indices start from 1
+            for index in triangle.iter().rev() {
+                let index = index.get() as u64 + 1;
+                write!(writer, " {}/{}/{}", index, index, index)?;
+            }
+
+            writeln!(writer)?;
+        }
+
+        offset = end;
+    }
+
+    Ok(())
+}
+
+/// OBJ names end at whitespace, so it is replaced.
+fn obj_name(name: &str) -> String {
+    let name = name
+        .chars()
+        .map(|c| if c.is_whitespace() { '_' } else { c })
+        .collect::<String>();
+
+    match name.is_empty() {
+        true => "unnamed".to_owned(),
+        false => name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bdef1, PmxWriter};
+
+    /// Makes a PMX holding a quad of 4 vertices drawn with two materials of a triangle each.
+    fn make_quad_pmx() -> Vec<u8> {
+        let mut writer = PmxWriter::new("quad").additional_vec4_count(0);
+
+        for (x, y) in [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)] {
+            writer = writer.vertex([x, y, 1.0], [0.0, 0.0, -1.0], [x, y], &bdef1(-1));
+        }
+
+        writer
+            .indices(&[0, 1, 2, 0, 2, 3])
+            .material("front face", -1, 3)
+            .material("back", -1, 3)
+            .build()
+    }
+
+    #[test]
+    fn check_export_obj() {
+        let pmx = Pmx::parse(make_quad_pmx()).unwrap();
+        let mut obj = Vec::new();
+        export_obj(&pmx, &mut obj).unwrap();
+        let obj = String::from_utf8(obj).unwrap();
+        let count = |prefix: &str| obj.lines().filter(|line| line.starts_with(prefix)).count();
+
+        assert_eq!(count("v "), 4);
+        assert_eq!(count("vt "), 4);
+        assert_eq!(count("vn "), 4);
+        assert_eq!(count("f "), 2);
+        assert_eq!(count("g "), 2);
+        assert!(obj.contains("v 1 1 -1\n"));
+        assert!(obj.contains("vn 0 0 1\n"));
+        assert!(obj.contains("g front_face\nusemtl front_face\nf 3/3/3 2/2/2 1/1/1\n"));
+        assert!(obj.contains("g back\nusemtl back\nf 4/4/4 3/3/3 1/1/1\n"));
+    }
+}
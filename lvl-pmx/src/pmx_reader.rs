@@ -0,0 +1,351 @@
+use crate::{
+    cursor::Cursor,
+    parse::Parse,
+    pmx_bone::{PmxBone, PmxBoneParseError},
+    pmx_display::{PmxDisplay, PmxDisplayParseError},
+    pmx_header::PmxHeader,
+    pmx_indices::PmxIndicesParseError,
+    pmx_joint::{PmxJoint, PmxJointParseError},
+    pmx_material::{PmxMaterial, PmxMaterialParseError},
+    pmx_morph::{PmxMorph, PmxMorphParseError},
+    pmx_primitives::PmxVertexIndex,
+    pmx_rigidbody::{PmxRigidbody, PmxRigidbodyParseError},
+    pmx_texture::{PmxTexture, PmxTextureParseError},
+    pmx_vertex::{PmxVertex, PmxVertexParseError},
+    primitives::RustPrimitiveParseError,
+    PmxParseError,
+};
+use std::io::{Read, Seek, SeekFrom};
+
+/// Minimum number of bytes read from the underlying reader at once.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Sections of a PMX file, in the file order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum PmxSection {
+    Vertices,
+    Indices,
+    Textures,
+    Materials,
+    Bones,
+    Morphs,
+    Displays,
+    Rigidbodies,
+    Joints,
+    End,
+}
+
+impl PmxSection {
+    fn next(self) -> Self {
+        match self {
+            Self::Vertices => Self::Indices,
+            Self::Indices => Self::Textures,
+            Self::Textures => Self::Materials,
+            Self::Materials => Self::Bones,
+            Self::Bones => Self::Morphs,
+            Self::Morphs => Self::Displays,
+            Self::Displays => Self::Rigidbodies,
+            Self::Rigidbodies => Self::Joints,
+            Self::Joints | Self::End => Self::End,
+        }
+    }
+}
+
+/// Bytes read ahead from the underlying reader; only the unparsed ones are kept.
+struct ChunkBuffer<R> {
+    reader: R,
+    chunk_size: usize,
+    buffer: Vec<u8>,
+    position: usize,
+    is_eof: bool,
+}
+
+impl<R: Read + Seek> ChunkBuffer<R> {
+    /// Parses an item from the buffered bytes, reading more until the item fits.
+    fn parse<T, E>(
+        &mut self,
+        mut parse: impl FnMut(&mut Cursor) -> Result<T, E>,
+    ) -> Result<T, PmxParseError>
+    where
+        PmxParseError: From<E>,
+    {
+        loop {
+            let mut cursor = Cursor::new(&self.buffer[self.position..]);
+
+            match parse(&mut cursor) {
+                Ok(item) => {
+                    self.position += cursor.position();
+                    return Ok(item);
+                }
+                Err(_) if cursor.is_truncated() && !self.is_eof => {}
+                Err(err) => return Err(err.into()),
+            }
+
+            self.fill()?;
+        }
+    }
+
+    /// Skips the given number of bytes, seeking over the ones not read yet.
+    /// Returns `false` if the reader ends before that.
+    fn skip(&mut self, len: usize) -> Result<bool, PmxParseError> {
+        let buffered = self.buffer.len() - self.position;
+
+        if len <= buffered {
+            self.position += len;
+            return Ok(true);
+        }
+
+        if self.is_eof {
+            return Ok(false);
+        }
+
+        self.reader
+            .seek(SeekFrom::Current((len - buffered) as i64))?;
+        self.buffer.clear();
+        self.position = 0;
+        Ok(true)
+    }
+
+    fn fill(&mut self) -> Result<(), PmxParseError> {
+        self.buffer.drain(..self.position);
+        self.position = 0;
+
+        // grows with the buffer, so that a large item does not take many reads
+        let len = self.chunk_size.max(self.buffer.len());
+        let read = (&mut self.reader)
+            .take(len as u64)
+            .read_to_end(&mut self.buffer)?;
+
+        if read < len {
+            self.is_eof = true;
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses a PMX file incrementally from a reader, without holding the whole file in memory.
+///
+/// The header is parsed eagerly, so the config is available right away. The items are decoded
+/// on demand in the file order; asking for an item of a later section skips the rest of the
+/// current one, and the sections already passed yield `None`.
+pub struct PmxReader<R> {
+    header: PmxHeader,
+    buffer: ChunkBuffer<R>,
+    section: PmxSection,
+    /// Number of items left in the current section; `None` if its count is not read yet.
+    remaining: Option<usize>,
+}
+
+impl<R: Read + Seek> PmxReader<R> {
+    pub fn new(reader: R) -> Result<Self, PmxParseError> {
+        Self::with_chunk_size(reader, CHUNK_SIZE)
+    }
+
+    fn with_chunk_size(reader: R, chunk_size: usize) -> Result<Self, PmxParseError> {
+        let mut buffer = ChunkBuffer {
+            reader,
+            chunk_size,
+            buffer: Vec::new(),
+            position: 0,
+            is_eof: false,
+        };
+        let header = buffer.parse(PmxHeader::parse)?;
+
+        Ok(Self {
+            header,
+            buffer,
+            section: PmxSection::Vertices,
+            remaining: None,
+        })
+    }
+
+    pub fn header(&self) -> &PmxHeader {
+        &self.header
+    }
+
+    pub fn into_inner(self) -> R {
+        self.buffer.reader
+    }
+
+    pub fn next_vertex(&mut self) -> Result<Option<PmxVertex>, PmxParseError> {
+        self.next_item::<_, PmxVertexParseError>(PmxSection::Vertices)
+    }
+
+    /// Returns the next vertex index; every three of them make a face in CW order.
+    pub fn next_index(&mut self) -> Result<Option<PmxVertexIndex>, PmxParseError> {
+        self.next_item::<_, PmxIndicesParseError>(PmxSection::Indices)
+    }
+
+    pub fn next_texture(&mut self) -> Result<Option<PmxTexture>, PmxParseError> {
+        self.next_item::<_, PmxTextureParseError>(PmxSection::Textures)
+    }
+
+    pub fn next_material(&mut self) -> Result<Option<PmxMaterial>, PmxParseError> {
+        self.next_item::<_, PmxMaterialParseError>(PmxSection::Materials)
+    }
+
+    pub fn next_bone(&mut self) -> Result<Option<PmxBone>, PmxParseError> {
+        self.next_item::<_, PmxBoneParseError>(PmxSection::Bones)
+    }
+
+    pub fn next_morph(&mut self) -> Result<Option<PmxMorph>, PmxParseError> {
+        self.next_item::<_, PmxMorphParseError>(PmxSection::Morphs)
+    }
+
+    pub fn next_display(&mut self) -> Result<Option<PmxDisplay>, PmxParseError> {
+        self.next_item::<_, PmxDisplayParseError>(PmxSection::Displays)
+    }
+
+    pub fn next_rigidbody(&mut self) -> Result<Option<PmxRigidbody>, PmxParseError> {
+        self.next_item::<_, PmxRigidbodyParseError>(PmxSection::Rigidbodies)
+    }
+
+    pub fn next_joint(&mut self) -> Result<Option<PmxJoint>, PmxParseError> {
+        self.next_item::<_, PmxJointParseError>(PmxSection::Joints)
+    }
+
+    fn next_item<T, E>(&mut self, section: PmxSection) -> Result<Option<T>, PmxParseError>
+    where
+        T: Parse,
+        E: From<T::Error> + From<RustPrimitiveParseError>,
+        PmxParseError: From<E>,
+    {
+        while self.section < section {
+            self.skip_section()?;
+        }
+
+        if section < self.section {
+            return Ok(None);
+        }
+
+        let remaining = match self.remaining {
+            Some(remaining) => remaining,
+            None => self.read_count::<E>()?,
+        };
+
+        if remaining == 0 {
+            self.remaining = Some(0);
+            return Ok(None);
+        }
+
+        let config = &self.header.config;
+        let item = self
+            .buffer
+            .parse(|cursor| T::parse(config, cursor).map_err(E::from))?;
+        self.remaining = Some(remaining - 1);
+        Ok(Some(item))
+    }
+
+    fn read_count<E>(&mut self) -> Result<usize, PmxParseError>
+    where
+        E: From<RustPrimitiveParseError>,
+        PmxParseError: From<E>,
+    {
+        let config = &self.header.config;
+        let count = self
+            .buffer
+            .parse(|cursor| u32::parse(config, cursor).map_err(E::from))?;
+        Ok(count as usize)
+    }
+
+    fn skip_section(&mut self) -> Result<(), PmxParseError> {
+        match self.section {
+            PmxSection::Vertices => while self.next_vertex()?.is_some() {},
+            // the indices have a fixed size, so they are seeked over instead of being parsed
+            PmxSection::Indices => {
+                let count = match self.remaining {
+                    Some(remaining) => remaining,
+                    None => self.read_count::<PmxIndicesParseError>()?,
+                };
+                let size = count * self.header.config.vertex_index_size.size();
+
+                if !self.buffer.skip(size)? {
+                    return Err(PmxIndicesParseError::UnexpectedEof.into());
+                }
+            }
+            PmxSection::Textures => while self.next_texture()?.is_some() {},
+            PmxSection::Materials => while self.next_material()?.is_some() {},
+            PmxSection::Bones => while self.next_bone()?.is_some() {},
+            PmxSection::Morphs => while self.next_morph()?.is_some() {},
+            PmxSection::Displays => while self.next_display()?.is_some() {},
+            PmxSection::Rigidbodies => while self.next_rigidbody()?.is_some() {},
+            PmxSection::Joints => while self.next_joint()?.is_some() {},
+            PmxSection::End => {}
+        }
+
+        self.section = self.section.next();
+        self.remaining = None;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bdef1, bdef2, Pmx, PmxWriter};
+
+    /// Makes a PMX holding three vertices of different deforms, the given vertex indices and a
+    /// texture.
+    fn make_pmx(vertex_indices: &[u32]) -> Vec<u8> {
+        let deforms = [bdef1(0), bdef2([1, 2], 0.25), bdef1(3)];
+        let mut writer = PmxWriter::new("model");
+
+        for (index, deform) in deforms.iter().enumerate() {
+            let value = |offset: usize| index as f32 + offset as f32 * 0.5;
+            writer = writer.vertex(
+                [value(0), value(1), value(2)],
+                [value(3), value(4), value(5)],
+                [value(6), value(7)],
+                deform,
+            );
+        }
+
+        writer
+            .indices(vertex_indices)
+            .textures(&["textures/skin.png"])
+            .build()
+    }
+
+    #[test]
+    fn check_reader_matches_parse() {
+        let buf = make_pmx(&[0, 1, 2]);
+        let pmx = Pmx::parse(&buf).unwrap();
+
+        let mut reader = PmxReader::with_chunk_size(std::io::Cursor::new(&buf), 7).unwrap();
+        assert_eq!(reader.header().model_name_local, "model");
+
+        let mut vertices = Vec::new();
+
+        while let Some(vertex) = reader.next_vertex().unwrap() {
+            vertices.push(vertex);
+        }
+
+        assert_eq!(format!("{:?}", vertices), format!("{:?}", pmx.vertices));
+
+        let mut vertex_indices = Vec::new();
+
+        while let Some(vertex_index) = reader.next_index().unwrap() {
+            vertex_indices.push(vertex_index);
+        }
+
+        assert_eq!(vertex_indices, pmx.indices.vertex_indices);
+        assert!(reader.next_vertex().unwrap().is_none());
+    }
+
+    #[test]
+    fn check_reader_skips_sections() {
+        let buf = make_pmx(&[0, 1, 2, 2, 1, 0]);
+
+        let mut reader = PmxReader::with_chunk_size(std::io::Cursor::new(&buf), 7).unwrap();
+        reader.next_vertex().unwrap().unwrap();
+
+        assert_eq!(
+            reader.next_texture().unwrap().unwrap().path,
+            "textures/skin.png"
+        );
+        assert!(reader.next_texture().unwrap().is_none());
+        assert!(reader.next_joint().unwrap().is_none());
+        assert!(reader.next_material().unwrap().is_none());
+    }
+}
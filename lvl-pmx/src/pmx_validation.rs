@@ -0,0 +1,346 @@
+use crate::{
+    pmx_bone::PmxBoneTailPosition, pmx_material::PmxMaterialToonMode, pmx_morph::PmxMorphOffset,
+    pmx_vertex::PmxVertexDeformKind, Pmx,
+};
+use std::fmt::Display;
+use thiserror::Error;
+
+/// Kinds of the items of a PMX that refer to each other by index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PmxItemKind {
+    Vertex,
+    Index,
+    Texture,
+    Material,
+    Bone,
+    Morph,
+    Rigidbody,
+}
+
+impl Display for PmxItemKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Vertex => "vertex",
+            Self::Index => "index",
+            Self::Texture => "texture",
+            Self::Material => "material",
+            Self::Bone => "bone",
+            Self::Morph => "morph",
+            Self::Rigidbody => "rigidbody",
+        })
+    }
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum PmxValidationError {
+    #[error("the {section} {item} refers to the {target} `{index}`, but there are {len} of them")]
+    IndexOutOfBounds {
+        section: PmxItemKind,
+        item: usize,
+        target: PmxItemKind,
+        index: i64,
+        len: usize,
+    },
+    #[error("the materials cover {surface_count} indices, but there are {index_count}")]
+    SurfaceCountMismatch {
+        surface_count: u64,
+        index_count: usize,
+    },
+}
+
+/// Collects the errors of a PMX, so that all of them are reported at once.
+struct Validator<'a> {
+    pmx: &'a Pmx,
+    errors: Vec<PmxValidationError>,
+}
+
+impl<'a> Validator<'a> {
+    fn len(&self, kind: PmxItemKind) -> usize {
+        match kind {
+            PmxItemKind::Vertex => self.pmx.vertices.len(),
+            PmxItemKind::Index => self.pmx.indices.vertex_indices.len(),
+            PmxItemKind::Texture => self.pmx.textures.len(),
+            PmxItemKind::Material => self.pmx.materials.len(),
+            PmxItemKind::Bone => self.pmx.bones.len(),
+            PmxItemKind::Morph => self.pmx.morphs.len(),
+            PmxItemKind::Rigidbody => self.pmx.rigidbodies.len(),
+        }
+    }
+
+    fn check(&mut self, section: PmxItemKind, item: usize, target: PmxItemKind, index: i64) {
+        let len = self.len(target);
+
+        if index < 0 || len as i64 <= index {
+            self.errors.push(PmxValidationError::IndexOutOfBounds {
+                section,
+                item,
+                target,
+                index,
+                len,
+            });
+        }
+    }
+
+    /// Same as [`Validator::check`], but allows `-1`, which PMX uses for "none".
+    fn check_optional(
+        &mut self,
+        section: PmxItemKind,
+        item: usize,
+        target: PmxItemKind,
+        index: i64,
+    ) {
+        if index != -1 {
+            self.check(section, item, target, index);
+        }
+    }
+
+    fn validate_vertices(&mut self) {
+        for (item, vertex) in self.pmx.vertices.iter().enumerate() {
+            let bone_indices = match &vertex.deform_kind {
+                PmxVertexDeformKind::Bdef1 { bone_index } => vec![*bone_index],
+                PmxVertexDeformKind::Bdef2 {
+                    bone_index_1,
+                    bone_index_2,
+                    ..
+                }
+                | PmxVertexDeformKind::Sdef {
+                    bone_index_1,
+                    bone_index_2,
+                    ..
+                } => vec![*bone_index_1, *bone_index_2],
+                PmxVertexDeformKind::Bdef4 {
+                    bone_index_1,
+                    bone_index_2,
+                    bone_index_3,
+                    bone_index_4,
+                    ..
+                }
+                | PmxVertexDeformKind::Qdef {
+                    bone_index_1,
+                    bone_index_2,
+                    bone_index_3,
+                    bone_index_4,
+                    ..
+                } => vec![*bone_index_1, *bone_index_2, *bone_index_3, *bone_index_4],
+            };
+
+            // the unused bones of a deform are `-1`
+            for bone_index in bone_indices {
+                self.check_optional(
+                    PmxItemKind::Vertex,
+                    item,
+                    PmxItemKind::Bone,
+                    bone_index.get() as i64,
+                );
+            }
+        }
+    }
+
+    fn validate_indices(&mut self) {
+        for (item, vertex_index) in self.pmx.indices.vertex_indices.iter().enumerate() {
+            self.check(
+                PmxItemKind::Index,
+                item,
+                PmxItemKind::Vertex,
+                vertex_index.get() as i64,
+            );
+        }
+    }
+
+    fn validate_materials(&mut self) {
+        let mut surface_count = 0u64;
+
+        for (item, material) in self.pmx.materials.iter().enumerate() {
+            let section = PmxItemKind::Material;
+            self.check_optional(
+                section,
+                item,
+                PmxItemKind::Texture,
+                material.texture_index.get() as i64,
+            );
+            self.check_optional(
+                section,
+                item,
+                PmxItemKind::Texture,
+                material.environment_texture_index.get() as i64,
+            );
+
+            if let PmxMaterialToonMode::Texture { index } = material.toon_mode {
+                self.check_optional(section, item, PmxItemKind::Texture, index.get() as i64);
+            }
+
+            surface_count += material.surface_count as u64;
+        }
+
+        let index_count = self.pmx.indices.vertex_indices.len();
+
+        if surface_count != index_count as u64 {
+            self.errors.push(PmxValidationError::SurfaceCountMismatch {
+                surface_count,
+                index_count,
+            });
+        }
+    }
+
+    fn validate_bones(&mut self) {
+        for (item, bone) in self.pmx.bones.iter().enumerate() {
+            let section = PmxItemKind::Bone;
+            let target = PmxItemKind::Bone;
+            self.check_optional(section, item, target, bone.parent_index.get() as i64);
+
+            if let PmxBoneTailPosition::BoneIndex { index } = bone.tail_position {
+                self.check_optional(section, item, target, index.get() as i64);
+            }
+
+            if let Some(inheritance) = &bone.inheritance {
+                self.check(section, item, target, inheritance.index.get() as i64);
+            }
+
+            if let Some(ik) = &bone.ik {
+                self.check(section, item, target, ik.index.get() as i64);
+
+                for link in &ik.links {
+                    self.check(section, item, target, link.index.get() as i64);
+                }
+            }
+        }
+    }
+
+    fn validate_morphs(&mut self) {
+        for (item, morph) in self.pmx.morphs.iter().enumerate() {
+            let section = PmxItemKind::Morph;
+            let targets = match &morph.offset {
+                PmxMorphOffset::Group(offsets) => offsets
+                    .iter()
+                    .map(|offset| (PmxItemKind::Morph, offset.index.get() as i64))
+                    .collect::<Vec<_>>(),
+                PmxMorphOffset::Vertex(offsets) => offsets
+                    .iter()
+                    .map(|offset| (PmxItemKind::Vertex, offset.index.get() as i64))
+                    .collect(),
+                PmxMorphOffset::Bone(offsets) => offsets
+                    .iter()
+                    .map(|offset| (PmxItemKind::Bone, offset.index.get() as i64))
+                    .collect(),
+                PmxMorphOffset::Uv { offsets, .. } => offsets
+                    .iter()
+                    .map(|offset| (PmxItemKind::Vertex, offset.index.get() as i64))
+                    .collect(),
+                PmxMorphOffset::Material(offsets) => {
+                    // `-1` applies the offset to all the materials
+                    for offset in offsets {
+                        self.check_optional(
+                            section,
+                            item,
+                            PmxItemKind::Material,
+                            offset.index.get() as i64,
+                        );
+                    }
+
+                    continue;
+                }
+                PmxMorphOffset::Flip(offsets) => offsets
+                    .iter()
+                    .map(|offset| (PmxItemKind::Morph, offset.index.get() as i64))
+                    .collect(),
+                PmxMorphOffset::Impulse(offsets) => offsets
+                    .iter()
+                    .map(|offset| (PmxItemKind::Rigidbody, offset.index.get() as i64))
+                    .collect(),
+            };
+
+            for (target, index) in targets {
+                self.check(section, item, target, index);
+            }
+        }
+    }
+}
+
+/// Checks the indices between the items of the PMX; see [`Pmx::validate`].
+pub(crate) fn validate(pmx: &Pmx) -> Result<(), Vec<PmxValidationError>> {
+    let mut validator = Validator {
+        pmx,
+        errors: Vec::new(),
+    };
+    validator.validate_vertices();
+    validator.validate_indices();
+    validator.validate_materials();
+    validator.validate_bones();
+    validator.validate_morphs();
+
+    match validator.errors.is_empty() {
+        true => Ok(()),
+        false => Err(validator.errors),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bdef1, PmxWriter};
+
+    /// Makes a PMX whose indices are all out of bounds:
+    /// - a vertex deformed by the bone 5
+    /// - the vertex indices 0, 1 and 7 of 2 vertices
+    /// - a material with the texture 0 of no textures, covering 6 of 3 indices
+    /// - a bone whose parent is the bone 3
+    /// - a group morph of the morph 9
+    fn make_corrupted_pmx() -> Vec<u8> {
+        PmxWriter::new("corrupted")
+            .vertex_deforms(&[&bdef1(5), &bdef1(-1)])
+            .indices(&[0, 1, 7])
+            .material("material", 0, 6)
+            .bone("bone", [0.0; 3], 3)
+            .group_morph("morph", &[(9, 1.0)])
+            .build()
+    }
+
+    #[test]
+    fn check_corrupted_pmx() {
+        let buf = make_corrupted_pmx();
+        let pmx = Pmx::parse(&buf).unwrap();
+        let out_of_bounds =
+            |section, item, target, index, len| PmxValidationError::IndexOutOfBounds {
+                section,
+                item,
+                target,
+                index,
+                len,
+            };
+
+        assert_eq!(
+            pmx.validate(),
+            Err(vec![
+                out_of_bounds(PmxItemKind::Vertex, 0, PmxItemKind::Bone, 5, 1),
+                out_of_bounds(PmxItemKind::Index, 2, PmxItemKind::Vertex, 7, 2),
+                out_of_bounds(PmxItemKind::Material, 0, PmxItemKind::Texture, 0, 0),
+                PmxValidationError::SurfaceCountMismatch {
+                    surface_count: 6,
+                    index_count: 3,
+                },
+                out_of_bounds(PmxItemKind::Bone, 0, PmxItemKind::Bone, 3, 1),
+                out_of_bounds(PmxItemKind::Morph, 0, PmxItemKind::Morph, 9, 1),
+            ])
+        );
+        assert!(matches!(
+            Pmx::parse_validated(&buf),
+            Err(crate::PmxParseError::PmxValidationError(errors)) if errors.len() == 6
+        ));
+    }
+
+    #[test]
+    fn check_validation_error_message() {
+        let error = crate::PmxParseError::PmxValidationError(vec![
+            PmxValidationError::SurfaceCountMismatch {
+                surface_count: 6,
+                index_count: 3,
+            },
+        ]);
+        assert!(error
+            .to_string()
+            .starts_with("PMX has 1 invalid indices; the first one: "));
+
+        let error = crate::PmxParseError::PmxValidationError(Vec::new());
+        assert_eq!(error.to_string(), "PMX has 0 invalid indices");
+    }
+}
@@ -32,6 +32,7 @@ pub struct PmxVertex {
     /// up to 4 additional vec4s
     pub additional_vec4s: [PmxVec4; 4],
     pub deform_kind: PmxVertexDeformKind,
+    /// The edge scale of the vertex; multiplies the edge size of the material it is drawn with.
     pub edge_size: f32,
 }
 
@@ -0,0 +1,299 @@
+/// An IK link of [`PmxWriter::ik_bone`]: the bone index and the optional angle limit as the
+/// minimum and the maximum.
+pub type PmxWriterIkLink = (i32, Option<([f32; 3], [f32; 3])>);
+
+/// Writes PMX 2.0 files for tests. The text is UTF-8 and every index is 4 bytes wide; the
+/// sections that nothing is added to are written empty. The vertices have four additional vec4s
+/// unless told otherwise, as the parser expects the bytes of all four to be in the file.
+pub struct PmxWriter {
+    model_name: String,
+    additional_vec4_count: u8,
+    vertices: Vec<Vec<u8>>,
+    vertex_indices: Vec<u32>,
+    texture_paths: Vec<String>,
+    materials: Vec<Vec<u8>>,
+    bones: Vec<Vec<u8>>,
+    morphs: Vec<Vec<u8>>,
+}
+
+impl PmxWriter {
+    pub fn new(model_name: &str) -> Self {
+        Self {
+            model_name: model_name.to_owned(),
+            additional_vec4_count: 4,
+            vertices: Vec::new(),
+            vertex_indices: Vec::new(),
+            texture_paths: Vec::new(),
+            materials: Vec::new(),
+            bones: Vec::new(),
+            morphs: Vec::new(),
+        }
+    }
+
+    /// Sets the number of additional vec4s of the vertices; the vertices added before are not
+    /// affected.
+    pub fn additional_vec4_count(mut self, count: u8) -> Self {
+        self.additional_vec4_count = count;
+        self
+    }
+
+    /// Adds the given number of zeroed BDEF1 vertices of the bone 0.
+    pub fn vertices(self, count: usize) -> Self {
+        self.vertex_deforms(&vec![&bdef1(0)[..]; count])
+    }
+
+    /// Adds a zeroed vertex per deform, which is the deform kind followed by its bone indices
+    /// and weights.
+    pub fn vertex_deforms(mut self, deforms: &[&[u8]]) -> Self {
+        for deform in deforms {
+            self = self.vertex([0.0; 3], [0.0; 3], [0.0; 2], deform);
+        }
+
+        self
+    }
+
+    /// Adds a vertex with zeroed additional vec4s and an edge size of 1.
+    pub fn vertex(
+        mut self,
+        position: [f32; 3],
+        normal: [f32; 3],
+        uv: [f32; 2],
+        deform: &[u8],
+    ) -> Self {
+        let mut vertex = Vec::new();
+
+        for component in position.into_iter().chain(normal).chain(uv) {
+            vertex.extend(component.to_le_bytes());
+        }
+
+        vertex.extend(vec![0; 16 * self.additional_vec4_count as usize]);
+        vertex.extend(deform);
+        vertex.extend(1f32.to_le_bytes());
+
+        self.vertices.push(vertex);
+        self
+    }
+
+    pub fn indices(mut self, vertex_indices: &[u32]) -> Self {
+        self.vertex_indices.extend(vertex_indices);
+        self
+    }
+
+    pub fn textures(mut self, texture_paths: &[impl AsRef<str>]) -> Self {
+        self.texture_paths.extend(
+            texture_paths
+                .iter()
+                .map(|texture_path| texture_path.as_ref().to_owned()),
+        );
+        self
+    }
+
+    /// Adds a material per texture index, which has no surfaces, environment texture or toon
+    /// texture. The materials are named after their indices: `material0`, `material1`, ...
+    pub fn materials(mut self, texture_indices: &[i32]) -> Self {
+        for &texture_index in texture_indices {
+            let name = format!("material{}", self.materials.len());
+            self = self.material(&name, texture_index, 0);
+        }
+
+        self
+    }
+
+    /// Adds a material with no environment texture or toon texture, covering the given number
+    /// of indices.
+    pub fn material(mut self, name: &str, texture_index: i32, surface_count: u32) -> Self {
+        let mut material = Vec::new();
+        push_text(&mut material, name);
+        push_text(&mut material, "");
+        // colors, specular strength, flags, edge color and size
+        material.extend([0; 65]);
+        material.extend(texture_index.to_le_bytes());
+        // environment texture, blend mode
+        material.extend((-1i32).to_le_bytes());
+        material.push(0);
+        // toon texture
+        material.push(0);
+        material.extend((-1i32).to_le_bytes());
+        // metadata
+        push_text(&mut material, "");
+        material.extend(surface_count.to_le_bytes());
+
+        self.materials.push(material);
+        self
+    }
+
+    /// Adds a rotatable bone with a parent index of -1 for none.
+    pub fn bone(self, name: &str, position: [f32; 3], parent_index: i32) -> Self {
+        self.push_bone(name, position, parent_index, None)
+    }
+
+    /// Adds an IK bone that pulls the target bone with the links.
+    pub fn ik_bone(
+        self,
+        name: &str,
+        position: [f32; 3],
+        target_index: i32,
+        links: &[PmxWriterIkLink],
+    ) -> Self {
+        self.push_bone(name, position, -1, Some((target_index, links)))
+    }
+
+    fn push_bone(
+        mut self,
+        name: &str,
+        position: [f32; 3],
+        parent_index: i32,
+        ik: Option<(i32, &[PmxWriterIkLink])>,
+    ) -> Self {
+        let push_vec3 =
+            |bone: &mut Vec<u8>, v: [f32; 3]| bone.extend(v.map(f32::to_le_bytes).concat());
+
+        let mut bone = Vec::new();
+        push_text(&mut bone, name);
+        push_text(&mut bone, "");
+        push_vec3(&mut bone, position);
+        bone.extend(parent_index.to_le_bytes());
+        // layer
+        bone.extend(0i32.to_le_bytes());
+        // rotatable, visible and enabled, with IK if any; the tail is a position
+        bone.extend([
+            if ik.is_some() {
+                0b0011_1010
+            } else {
+                0b0001_1010
+            },
+            0,
+        ]);
+        push_vec3(&mut bone, [0.0; 3]);
+
+        if let Some((target_index, links)) = ik {
+            bone.extend(target_index.to_le_bytes());
+            // loop count, limit angle
+            bone.extend(40i32.to_le_bytes());
+            bone.extend(1f32.to_le_bytes());
+            bone.extend((links.len() as u32).to_le_bytes());
+
+            for &(index, angle_limit) in links {
+                bone.extend(index.to_le_bytes());
+
+                match angle_limit {
+                    Some((min, max)) => {
+                        bone.push(1);
+                        push_vec3(&mut bone, min);
+                        push_vec3(&mut bone, max);
+                    }
+                    None => bone.push(0),
+                }
+            }
+        }
+
+        self.bones.push(bone);
+        self
+    }
+
+    /// Adds a vertex morph that moves each vertex by its offset.
+    pub fn vertex_morph(mut self, name: &str, offsets: &[(u32, [f32; 3])]) -> Self {
+        let mut morph = Vec::new();
+        push_text(&mut morph, name);
+        push_text(&mut morph, "");
+        // panel kind, morph kind
+        morph.extend([4, 1]);
+        morph.extend((offsets.len() as u32).to_le_bytes());
+
+        for (index, offset) in offsets {
+            morph.extend(index.to_le_bytes());
+            morph.extend(offset.map(f32::to_le_bytes).concat());
+        }
+
+        self.morphs.push(morph);
+        self
+    }
+
+    /// Adds a group morph that applies each morph with its coefficient.
+    pub fn group_morph(mut self, name: &str, elements: &[(i32, f32)]) -> Self {
+        let mut morph = Vec::new();
+        push_text(&mut morph, name);
+        push_text(&mut morph, "");
+        // panel kind, morph kind
+        morph.extend([4, 0]);
+        morph.extend((elements.len() as u32).to_le_bytes());
+
+        for (index, coefficient) in elements {
+            morph.extend(index.to_le_bytes());
+            morph.extend(coefficient.to_le_bytes());
+        }
+
+        self.morphs.push(morph);
+        self
+    }
+
+    pub fn build(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(b"PMX ");
+        buf.extend(2.0f32.to_le_bytes());
+        buf.push(8);
+        // utf-8, additional vec4s, 4-byte indices
+        buf.extend([1, self.additional_vec4_count, 4, 4, 4, 4, 4, 4]);
+
+        push_text(&mut buf, &self.model_name);
+
+        // universal name, comments
+        for _ in 0..3 {
+            push_text(&mut buf, "");
+        }
+
+        push_items(&mut buf, &self.vertices);
+
+        buf.extend((self.vertex_indices.len() as u32).to_le_bytes());
+
+        for vertex_index in &self.vertex_indices {
+            buf.extend(vertex_index.to_le_bytes());
+        }
+
+        buf.extend((self.texture_paths.len() as u32).to_le_bytes());
+
+        for texture_path in &self.texture_paths {
+            push_text(&mut buf, texture_path);
+        }
+
+        push_items(&mut buf, &self.materials);
+        push_items(&mut buf, &self.bones);
+        push_items(&mut buf, &self.morphs);
+
+        // displays, rigidbodies, joints
+        for _ in 0..3 {
+            buf.extend(0u32.to_le_bytes());
+        }
+
+        buf
+    }
+}
+
+/// Makes a BDEF1 deform of the bone.
+pub fn bdef1(bone_index: i32) -> Vec<u8> {
+    let mut deform = vec![0];
+    deform.extend(bone_index.to_le_bytes());
+    deform
+}
+
+/// Makes a BDEF2 deform of the two bones, with the weight of the first one.
+pub fn bdef2(bone_indices: [i32; 2], weight: f32) -> Vec<u8> {
+    let mut deform = vec![1];
+    deform.extend(bone_indices[0].to_le_bytes());
+    deform.extend(bone_indices[1].to_le_bytes());
+    deform.extend(weight.to_le_bytes());
+    deform
+}
+
+fn push_items(buf: &mut Vec<u8>, items: &[Vec<u8>]) {
+    buf.extend((items.len() as u32).to_le_bytes());
+
+    for item in items {
+        buf.extend(item);
+    }
+}
+
+fn push_text(buf: &mut Vec<u8>, text: &str) {
+    buf.extend((text.len() as u32).to_le_bytes());
+    buf.extend(text.as_bytes());
+}
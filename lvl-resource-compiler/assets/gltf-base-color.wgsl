@@ -0,0 +1,50 @@
+struct Uniform {
+  base_color: vec4<f32>,
+};
+
+@group(0) @binding(0) var<uniform> uniforms: Uniform;
+@group(1) @binding(0) var base_color_texture: texture_2d<f32>;
+@group(1) @binding(1) var base_color_sampler: sampler;
+
+struct VertexInput {
+  @location(0) position: vec3<f32>,
+  @location(1) normal: vec3<f32>,
+  @location(2) uv: vec2<f32>,
+};
+
+struct VertexOutput {
+  @builtin(position) position: vec4<f32>,
+  @location(0) normal: vec3<f32>,
+  @location(1) uv: vec2<f32>,
+};
+
+struct FragmentOutput {
+  @location(0) color: vec4<f32>,
+};
+
+@vertex
+fn vs_main(instance: InstanceInput, vertex: VertexInput) -> VertexOutput {
+  let world_pos = builtin_transform_vertex_to_world_space(instance, vec4<f32>(vertex.position, 1.0));
+  let clip_pos = builtin_transform_vertex_to_clip_space(world_pos);
+  let normal = builtin_transform_normal_to_world_space(instance, vertex.normal);
+
+  var out: VertexOutput;
+  out.position = clip_pos;
+  out.normal = normal;
+  out.uv = vertex.uv;
+  return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+  // half lambert against a light from above, so that the shapes read without a light setup
+  let light_dir = normalize(vec3<f32>(0.3, 1.0, 0.5));
+  let ln = clamp(dot(normalize(in.normal), light_dir) * 0.5 + 0.5, 0.0, 1.0);
+
+  let tex_color = textureSample(base_color_texture, base_color_sampler, in.uv);
+  let color = uniforms.base_color * tex_color;
+
+  var out: FragmentOutput;
+  out.color = vec4<f32>(color.rgb * ln, color.a);
+  return out;
+}
@@ -11,10 +11,12 @@ struct Uniform {
   env_tint_color_add: vec4<f32>,
   light_color: vec3<f32>,
   light_direction: vec3<f32>,
+  environment_ambient_color: vec3<f32>,
 };
 
 @group(0) @binding(0) var<uniform> uniforms: Uniform;
 @group(0) @binding(1) var<storage, read> morph_coefficients: array<f32, 128>;
+@group(0) @binding(2) var<storage, read> bone_matrices: array<mat4x4<f32>, 1024>;
 @group(1) @binding(0) var texture: texture_2d<f32>;
 @group(1) @binding(1) var texture_sampler: sampler;
 @group(1) @binding(2) var vertex_morph_index_texture: texture_2d<u32>;
@@ -34,6 +36,12 @@ struct VertexInput {
   @location(5) uv_morph_index_start: u32,
   @location(6) uv_morph_count: u32,
   @location(7) additional_0: vec4<f32>,
+  @location(8) deform_kind: u32,
+  @location(9) bone_index: vec4<u32>,
+  @location(10) bone_weight: vec4<f32>,
+  @location(11) sdef_c: vec3<f32>,
+  @location(12) sdef_r0: vec3<f32>,
+  @location(13) sdef_r1: vec3<f32>,
 };
 
 struct VertexOutput {
@@ -106,9 +114,12 @@ fn vs_main(instance: InstanceInput, vertex: VertexInput) -> VertexOutput {
     }
   }
 
-  let world_pos = builtin_transform_vertex_to_world_space(instance, vec4<f32>(position, 1.0));
+  // the morphs are applied at the bind pose
+  let skinned = skin_vertex(vertex, position);
+
+  let world_pos = builtin_transform_vertex_to_world_space(instance, vec4<f32>(skinned.position, 1.0));
   let clip_pos = builtin_transform_vertex_to_clip_space(world_pos);
-  let normal = builtin_transform_normal_to_world_space(instance, vertex.normal);
+  let normal = builtin_transform_normal_to_world_space(instance, skinned.normal);
   let view_normal = builtin_transform_normal_to_view_space(normal);
 
   var out: VertexOutput;
@@ -133,7 +144,7 @@ fn fs_main(in: VertexOutput) -> FragmentOutput {
   ln = clamp(ln + 0.5, 0.0, 1.0);
 
   // ambient term
-  var color = uniforms.ambient_color;
+  var color = uniforms.ambient_color + uniforms.environment_ambient_color;
   var alpha = uniforms.diffuse_color.a;
 
   // diffuse term
@@ -0,0 +1,104 @@
+// The skinning shared by the standard shaders, which are expanded with this prepended.
+// It expects the shader to declare `bone_matrices` and a `VertexInput` with `normal`,
+// `deform_kind`, `bone_index`, `bone_weight`, `sdef_c`, `sdef_r0` and `sdef_r1`.
+//
+// With the bone inputs and the 8 instance inputs, a standard shader takes up to 22 vertex
+// attributes, more than the 16 that a device allows by default; the device is requested with
+// the `max_vertex_attributes` of the adapter to draw them.
+
+fn bone_matrix(bone_index: u32) -> mat4x4<f32> {
+  // the unused bones of a vertex are `-1`
+  if (bone_index >= 1024u) {
+    return mat4x4<f32>(
+      vec4<f32>(1.0, 0.0, 0.0, 0.0),
+      vec4<f32>(0.0, 1.0, 0.0, 0.0),
+      vec4<f32>(0.0, 0.0, 1.0, 0.0),
+      vec4<f32>(0.0, 0.0, 0.0, 1.0),
+    );
+  }
+
+  return bone_matrices[bone_index];
+}
+
+fn matrix_to_quat(m: mat4x4<f32>) -> vec4<f32> {
+  let trace = m[0][0] + m[1][1] + m[2][2];
+  var q: vec4<f32>;
+
+  if (0.0 < trace) {
+    let s = sqrt(trace + 1.0) * 2.0;
+    q = vec4<f32>(m[1][2] - m[2][1], m[2][0] - m[0][2], m[0][1] - m[1][0], 0.25 * s * s) / s;
+  } else if (m[0][0] > m[1][1] && m[0][0] > m[2][2]) {
+    let s = sqrt(1.0 + m[0][0] - m[1][1] - m[2][2]) * 2.0;
+    q = vec4<f32>(0.25 * s * s, m[1][0] + m[0][1], m[0][2] + m[2][0], m[1][2] - m[2][1]) / s;
+  } else if (m[1][1] > m[2][2]) {
+    let s = sqrt(1.0 + m[1][1] - m[0][0] - m[2][2]) * 2.0;
+    q = vec4<f32>(m[1][0] + m[0][1], 0.25 * s * s, m[2][1] + m[1][2], m[2][0] - m[0][2]) / s;
+  } else {
+    let s = sqrt(1.0 + m[2][2] - m[0][0] - m[1][1]) * 2.0;
+    q = vec4<f32>(m[0][2] + m[2][0], m[2][1] + m[1][2], 0.25 * s * s, m[0][1] - m[1][0]) / s;
+  }
+
+  return normalize(q);
+}
+
+fn rotate_by_quat(q: vec4<f32>, v: vec3<f32>) -> vec3<f32> {
+  let uv = cross(q.xyz, v);
+  let uuv = cross(q.xyz, uv);
+  return v + (q.w * uv + uuv) * 2.0;
+}
+
+struct SkinnedVertex {
+  position: vec3<f32>,
+  normal: vec3<f32>,
+};
+
+fn skin_vertex(vertex: VertexInput, position: vec3<f32>) -> SkinnedVertex {
+  var out: SkinnedVertex;
+
+  switch (vertex.deform_kind) {
+    // bdef1 has no weights
+    case 0u: {
+      let m = bone_matrix(vertex.bone_index.x);
+      out.position = (m * vec4<f32>(position, 1.0)).xyz;
+      out.normal = (m * vec4<f32>(vertex.normal, 0.0)).xyz;
+    }
+    case 3u: {
+      let m0 = bone_matrix(vertex.bone_index.x);
+      let m1 = bone_matrix(vertex.bone_index.y);
+      let w0 = vertex.bone_weight.x;
+      let w1 = vertex.bone_weight.y;
+
+      // the centers of rotation of the bones, moved so that their blend is the center
+      let r = vertex.sdef_r0 * w0 + vertex.sdef_r1 * w1;
+      let cr0 = (vertex.sdef_c * 2.0 + vertex.sdef_r0 - r) * 0.5;
+      let cr1 = (vertex.sdef_c * 2.0 + vertex.sdef_r1 - r) * 0.5;
+
+      let q0 = matrix_to_quat(m0);
+      var q1 = matrix_to_quat(m1);
+
+      if (dot(q0, q1) < 0.0) {
+        q1 = -q1;
+      }
+
+      let q = normalize(q0 * w0 + q1 * w1);
+      out.position = rotate_by_quat(q, position - vertex.sdef_c)
+        + (m0 * vec4<f32>(cr0, 1.0)).xyz * w0
+        + (m1 * vec4<f32>(cr1, 1.0)).xyz * w1;
+      out.normal = rotate_by_quat(q, vertex.normal);
+    }
+    default: {
+      out.position = vec3<f32>(0.0);
+      out.normal = vec3<f32>(0.0);
+
+      for (var i = 0; i < 4; i += 1) {
+        let m = bone_matrix(vertex.bone_index[i]);
+        let w = vertex.bone_weight[i];
+        out.position += (m * vec4<f32>(position, 1.0)).xyz * w;
+        out.normal += (m * vec4<f32>(vertex.normal, 0.0)).xyz * w;
+      }
+    }
+  }
+
+  out.normal = normalize(out.normal);
+  return out;
+}
@@ -1,8 +1,9 @@
 mod compile;
+mod compile_cache;
 
 pub use compile::*;
 
-use clap::{builder::ValueParser, Arg, Command};
+use clap::{builder::ValueParser, Arg, ArgAction, Command};
 
 pub fn cli() -> Command {
     Command::new(env!("CARGO_PKG_NAME"))
@@ -19,4 +20,28 @@ pub fn cli() -> Command {
                 .value_parser(ValueParser::path_buf())
                 .required(false),
         )
+        .arg(
+            Arg::new("no-precompile-shaders")
+                .long("no-precompile-shaders")
+                .help("Stores shaders as WGSL only, to be parsed at runtime")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("strip-shader-source")
+                .long("strip-shader-source")
+                .help("Stores shaders as naga IR without their WGSL source")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("compression-level")
+                .long("compression-level")
+                .help("Compresses the resources at the level, from 0 to 9")
+                .value_parser(clap::value_parser!(u32).range(0..=9)),
+        )
+        .arg(
+            Arg::new("force")
+                .long("force")
+                .help("Processes every file, ignoring the compile cache of the previous run")
+                .action(ArgAction::SetTrue),
+        )
 }
@@ -1,19 +1,60 @@
+use super::compile_cache::{CompileCache, CompileCacheOptions};
 use crate::processors::{
-    process_single_file, PmxModelAnimationProcessor, PmxModelProcessor, Processor, ShaderProcessor,
-    TextureProcessor,
+    process_single_file, GltfModelProcessor, PmxModelAnimationProcessor, PmxModelProcessor,
+    Processor, ShaderProcessor, TextureProcessor,
 };
 use anyhow::{anyhow, Context, Error as AnyError};
 use log::{debug, error, info, warn};
-use lvl_resource::{Resource, ResourceFile, ResourceFileVersion};
+use lvl_resource::{Resource, ResourceFile, ResourceFileCodec, ResourceFileVersion, ResourceKind};
 use std::{
     collections::HashSet,
     path::{Path, PathBuf},
 };
 
+#[derive(Debug, Clone)]
+pub struct CompileOptions {
+    /// `true` if shaders should be precompiled to naga IR, so that they are not parsed at runtime.
+    pub precompile_shaders: bool,
+    /// `true` if shaders should be stored as naga IR without their WGSL source.
+    /// The source is needed for debugging and hot reloading, so it is kept by default.
+    pub strip_shader_source: bool,
+    /// The deflate level, from `0` to `9`, to compress the resources at, or `None` to leave them
+    /// uncompressed. Compressed files are written as [`ResourceFileVersion::V3`].
+    pub compression_level: Option<u32>,
+    /// `true` if every file should be processed, ignoring the compile cache of the previous run.
+    pub force: bool,
+}
+
+impl Default for CompileOptions {
+    fn default() -> Self {
+        Self {
+            precompile_shaders: true,
+            strip_shader_source: false,
+            compression_level: None,
+            force: false,
+        }
+    }
+}
+
+/// Numbers of the input files that were processed and that were taken from the compile cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompileStats {
+    pub processed_files: usize,
+    pub reused_files: usize,
+}
+
+/// The resources made from a file.
+enum CompiledFile {
+    Processed(Vec<Resource>),
+    /// Taken from the previous output, as the file has not changed.
+    Reused(Vec<Resource>),
+}
+
 pub fn compile(
     input: Option<impl AsRef<Path>>,
     output: Option<impl AsRef<Path>>,
-) -> Result<(), AnyError> {
+    options: &CompileOptions,
+) -> Result<CompileStats, AnyError> {
     info!("compiling resources.");
 
     let input = match input {
@@ -82,8 +123,19 @@ pub fn compile(
         None => None,
     };
 
+    let cache_options = CompileCacheOptions {
+        precompile_shaders: options.precompile_shaders,
+        strip_shader_source: options.strip_shader_source,
+    };
+    let mut cache = if options.force {
+        CompileCache::empty(&input, cache_options)
+    } else {
+        CompileCache::load(&input, &output, cache_options)
+    };
+
     let mut dirs = vec![input];
     let mut resources = Vec::new();
+    let mut reused_resources = Vec::new();
 
     loop {
         if dirs.is_empty() {
@@ -151,8 +203,8 @@ pub fn compile(
 
                 debug!("entry `{}` is a file. processing.", entry_path.display());
 
-                let processed = match compile_single_file(&entry_path) {
-                    Ok(processed) => processed,
+                let compiled = match compile_single_file(&entry_path, &mut cache) {
+                    Ok(compiled) => compiled,
                     Err(err) => {
                         let mut errors = Vec::new();
 
@@ -169,16 +221,32 @@ pub fn compile(
                     }
                 };
 
-                resources.extend(processed);
+                match compiled {
+                    CompiledFile::Processed(processed) => resources.extend(processed),
+                    CompiledFile::Reused(reused) => reused_resources.extend(reused),
+                }
             }
         }
 
         dirs = added_dirs;
     }
 
-    let resource_file = ResourceFile::new(ResourceFileVersion::V1, resources);
-    let resource_file_data = bincode::serialize(&resource_file)
-        .with_context(|| format!("failed to serialize the resource file"))?;
+    // the reused shaders have been compiled by the previous run
+    compile_shaders(&mut resources, options)?;
+    resources.extend(reused_resources);
+
+    let resource_file = ResourceFile::new(ResourceFileVersion::CURRENT, resources);
+    let resource_file_data = match options.compression_level {
+        Some(level) => {
+            let mut data = Vec::new();
+            resource_file
+                .write_compressed(&mut data, ResourceFileCodec::Deflate, level)
+                .context("failed to compress the resource file")?;
+            data
+        }
+        None => bincode::serialize(&resource_file)
+            .with_context(|| format!("failed to serialize the resource file"))?,
+    };
 
     let output_dir = match output.parent() {
         Some(output_dir) => output_dir,
@@ -197,64 +265,187 @@ pub fn compile(
         )
     })?;
 
-    info!("compilation finished.");
+    cache.save(&output)?;
+
+    let stats = CompileStats {
+        processed_files: cache.processed_files(),
+        reused_files: cache.reused_files(),
+    };
+    info!(
+        "compilation finished. {} files processed, {} unchanged.",
+        stats.processed_files, stats.reused_files
+    );
+
+    Ok(stats)
+}
+
+fn compile_shaders(resources: &mut [Resource], options: &CompileOptions) -> Result<(), AnyError> {
+    for resource in resources {
+        let source = match &mut resource.kind {
+            ResourceKind::Shader(source) => source,
+            _ => {
+                continue;
+            }
+        };
+
+        if options.strip_shader_source {
+            ShaderProcessor::strip_shader_source(&resource.name, source).with_context(|| {
+                format!(
+                    "failed to strip the source of the shader `{}`",
+                    resource.name
+                )
+            })?;
+        } else if options.precompile_shaders {
+            ShaderProcessor::precompile_shader(&resource.name, source)
+                .with_context(|| format!("failed to precompile the shader `{}`", resource.name))?;
+        }
+    }
 
     Ok(())
 }
 
-fn compile_single_file(file: &Path) -> Result<Vec<Resource>, AnyError> {
+fn compile_single_file(file: &Path, cache: &mut CompileCache) -> Result<CompiledFile, AnyError> {
     let extension = match file.extension() {
         Some(extension) => extension,
         None => {
             debug!("the file `{}` has no extension. ignoring.", file.display());
-            return Ok(vec![]);
+            return Ok(CompiledFile::Processed(vec![]));
         }
     };
 
     match extension.to_string_lossy().to_string().as_str() {
         extension if PmxModelProcessor::extension().contains(&extension) => {
-            let processed = process_single_file::<PmxModelProcessor>(file).with_context(|| {
+            let compiled = compile_file::<PmxModelProcessor>(file, cache).with_context(|| {
                 format!(
                     "failed to process the file `{}` as a PMX model",
                     file.display()
                 )
             })?;
-            Ok(processed)
+            Ok(compiled)
         }
         extension if PmxModelAnimationProcessor::extension().contains(&extension) => {
-            let processed =
-                process_single_file::<PmxModelAnimationProcessor>(file).with_context(|| {
+            let compiled =
+                compile_file::<PmxModelAnimationProcessor>(file, cache).with_context(|| {
                     format!(
                         "failed to process the file `{}` as a PMX model animation",
                         file.display()
                     )
                 })?;
-            Ok(processed)
+            Ok(compiled)
+        }
+        extension if GltfModelProcessor::extension().contains(&extension) => {
+            let compiled = compile_file::<GltfModelProcessor>(file, cache).with_context(|| {
+                format!(
+                    "failed to process the file `{}` as a glTF model",
+                    file.display()
+                )
+            })?;
+            Ok(compiled)
         }
         extension if ShaderProcessor::extension().contains(&extension) => {
-            let processed = process_single_file::<ShaderProcessor>(file).with_context(|| {
+            let compiled = compile_file::<ShaderProcessor>(file, cache).with_context(|| {
                 format!(
                     "failed to process the file `{}` as a shader",
                     file.display()
                 )
             })?;
-            Ok(processed)
+            Ok(compiled)
         }
         extension if TextureProcessor::extension().contains(&extension) => {
-            let processed = process_single_file::<TextureProcessor>(file).with_context(|| {
+            let compiled = compile_file::<TextureProcessor>(file, cache).with_context(|| {
                 format!(
                     "failed to process the file `{}` as a texture",
                     file.display()
                 )
             })?;
-            Ok(processed)
+            Ok(compiled)
         }
         _ => {
             debug!(
                 "the file `{}` has an unsupported extension. ignoring.",
                 file.display()
             );
-            return Ok(vec![]);
+            return Ok(CompiledFile::Processed(vec![]));
         }
     }
 }
+
+fn compile_file<P: Processor>(
+    file: &Path,
+    cache: &mut CompileCache,
+) -> Result<CompiledFile, AnyError> {
+    if let Some(resources) = cache.reuse(file) {
+        debug!(
+            "the file `{}` is unchanged. reusing its resources.",
+            file.display()
+        );
+        return Ok(CompiledFile::Reused(resources));
+    }
+
+    let resources = process_single_file::<P>(file)?;
+    let dependencies = P::dependencies(file)?;
+    cache.insert(file, &dependencies, &resources);
+    Ok(CompiledFile::Processed(resources))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHADER: &str = r#"
+@vertex
+fn vs_main() -> @builtin(position) vec4<f32> {
+    return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return vec4<f32>(1.0, 1.0, 1.0, 1.0);
+}
+"#;
+
+    #[test]
+    fn check_unchanged_input_is_skipped() {
+        let dir = std::env::temp_dir().join(format!(
+            "lvl-resource-compiler-cache-{}",
+            std::process::id()
+        ));
+        let input = dir.join("input");
+        let output = dir.join("output").join("resource.res");
+        std::fs::create_dir_all(&input).unwrap();
+        std::fs::write(input.join("a.wgsl"), SHADER).unwrap();
+        std::fs::write(input.join("b.wgsl"), SHADER).unwrap();
+
+        let options = CompileOptions::default();
+        let compile_all = || {
+            let first = compile(Some(&input), Some(&output), &options)?;
+
+            // touch one of the files
+            std::fs::write(input.join("b.wgsl"), format!("{}\n", SHADER))?;
+            let second = compile(Some(&input), Some(&output), &options)?;
+            let forced = compile(
+                Some(&input),
+                Some(&output),
+                &CompileOptions {
+                    force: true,
+                    ..options.clone()
+                },
+            )?;
+            let resource_file = ResourceFile::read(&std::fs::read(&output)?)?;
+
+            Ok::<_, AnyError>((first, second, forced, resource_file))
+        };
+        let result = compile_all();
+        std::fs::remove_dir_all(&dir).unwrap();
+        let (first, second, forced, resource_file) = result.unwrap();
+
+        let stats = |processed_files, reused_files| CompileStats {
+            processed_files,
+            reused_files,
+        };
+        assert_eq!(first, stats(2, 0));
+        assert_eq!(second, stats(1, 1));
+        assert_eq!(forced, stats(2, 0));
+        assert_eq!(resource_file.resources().len(), 2);
+    }
+}
@@ -0,0 +1,216 @@
+use crate::processors::metadata_path;
+use anyhow::{Context, Error as AnyError};
+use log::{debug, warn};
+use lvl_resource::{Resource, ResourceFile};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+/// Version of the cache file layout. Cache files of other versions are ignored.
+const COMPILE_CACHE_VERSION: u32 = 1;
+
+/// The compile options that change the resources; the cache is not used if they change.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct CompileCacheOptions {
+    pub precompile_shaders: bool,
+    pub strip_shader_source: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct CompileCacheManifest {
+    version: u32,
+    options: CompileCacheOptions,
+    /// Entries by the path of the input file, relative to the input directory.
+    entries: BTreeMap<String, CompileCacheEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+struct CompileCacheEntry {
+    /// Content hashes of the input file, its metadata file and its dependencies by path, or
+    /// `None` for the files that did not exist.
+    inputs: BTreeMap<String, Option<String>>,
+    /// Names of the resources made from the input file.
+    resources: Vec<String>,
+}
+
+/// A manifest written next to the output, which maps each input file to the content hashes of
+/// the files it was processed from and the names of the resources it produced. Files whose
+/// inputs are unchanged take their resources from the previous output instead of being
+/// processed again.
+///
+/// Changes to the compiler itself are not tracked; compile with `--force` after updating it.
+pub(crate) struct CompileCache {
+    input_dir: PathBuf,
+    options: CompileCacheOptions,
+    previous_entries: BTreeMap<String, CompileCacheEntry>,
+    previous_resources: BTreeMap<String, Resource>,
+    entries: BTreeMap<String, CompileCacheEntry>,
+    processed_files: usize,
+    reused_files: usize,
+}
+
+impl CompileCache {
+    /// Returns the path of the cache file of the output, e.g. `resource.res.cache`.
+    pub fn path(output: &Path) -> PathBuf {
+        let mut file_name = output.file_name().unwrap_or_default().to_owned();
+        file_name.push(".cache");
+        output.with_file_name(file_name)
+    }
+
+    /// Makes a cache that reuses nothing, but records the files for the next run.
+    pub fn empty(input_dir: &Path, options: CompileCacheOptions) -> Self {
+        Self {
+            input_dir: input_dir.to_owned(),
+            options,
+            previous_entries: BTreeMap::new(),
+            previous_resources: BTreeMap::new(),
+            entries: BTreeMap::new(),
+            processed_files: 0,
+            reused_files: 0,
+        }
+    }
+
+    /// Loads the cache of the previous run along with its output. A cache that is missing,
+    /// unreadable or made with other options is ignored.
+    pub fn load(input_dir: &Path, output: &Path, options: CompileCacheOptions) -> Self {
+        let mut cache = Self::empty(input_dir, options);
+        let cache_path = Self::path(output);
+
+        if !cache_path.is_file() || !output.is_file() {
+            debug!("no compile cache found at `{}`.", cache_path.display());
+            return cache;
+        }
+
+        let manifest = match std::fs::read_to_string(&cache_path)
+            .map_err(AnyError::from)
+            .and_then(|content| Ok(serde_json::from_str::<CompileCacheManifest>(&content)?))
+        {
+            Ok(manifest) => manifest,
+            Err(err) => {
+                warn!(
+                    "failed to read the compile cache `{}`; it will be ignored: {}",
+                    cache_path.display(),
+                    err
+                );
+                return cache;
+            }
+        };
+
+        if manifest.version != COMPILE_CACHE_VERSION || manifest.options != options {
+            debug!(
+                "the compile cache `{}` is outdated. ignoring.",
+                cache_path.display()
+            );
+            return cache;
+        }
+
+        let previous = match std::fs::read(output)
+            .map_err(AnyError::from)
+            .and_then(|bytes| Ok(ResourceFile::read(&bytes)?))
+        {
+            Ok(previous) => previous,
+            Err(err) => {
+                warn!(
+                    "failed to read the previous output `{}`; the compile cache will be ignored: {}",
+                    output.display(),
+                    err
+                );
+                return cache;
+            }
+        };
+
+        cache.previous_entries = manifest.entries;
+        cache.previous_resources = previous.resources().clone();
+        cache
+    }
+
+    pub fn processed_files(&self) -> usize {
+        self.processed_files
+    }
+
+    pub fn reused_files(&self) -> usize {
+        self.reused_files
+    }
+
+    /// Returns the resources made from the file by the previous run, if neither the file nor
+    /// anything it was made from has changed since.
+    pub fn reuse(&mut self, file: &Path) -> Option<Vec<Resource>> {
+        let key = self.key(file);
+        let entry = self.previous_entries.get(&key)?;
+
+        for (path, hash) in &entry.inputs {
+            if &hash_file(&self.input_dir.join(path)) != hash {
+                debug!("`{}` has changed since the previous run.", path);
+                return None;
+            }
+        }
+
+        let resources = entry
+            .resources
+            .iter()
+            .map(|name| self.previous_resources.get(name).cloned())
+            .collect::<Option<Vec<_>>>()?;
+
+        self.entries.insert(key, entry.clone());
+        self.reused_files += 1;
+        Some(resources)
+    }
+
+    /// Records the resources made from the file, and the hashes of the files they were made
+    /// from.
+    pub fn insert(&mut self, file: &Path, dependencies: &[PathBuf], resources: &[Resource]) {
+        let inputs = [file.to_owned(), metadata_path(file)]
+            .iter()
+            .chain(dependencies)
+            .map(|path| (self.key(path), hash_file(path)))
+            .collect();
+        let entry = CompileCacheEntry {
+            inputs,
+            resources: Vec::from_iter(resources.iter().map(|resource| resource.name.clone())),
+        };
+
+        self.entries.insert(self.key(file), entry);
+        self.processed_files += 1;
+    }
+
+    /// Writes the files recorded by this run as the cache of the output.
+    pub fn save(&self, output: &Path) -> Result<(), AnyError> {
+        let cache_path = Self::path(output);
+        let manifest = CompileCacheManifest {
+            version: COMPILE_CACHE_VERSION,
+            options: self.options,
+            entries: self.entries.clone(),
+        };
+        let content = serde_json::to_string_pretty(&manifest)
+            .context("failed to serialize the compile cache")?;
+
+        std::fs::write(&cache_path, content).with_context(|| {
+            format!(
+                "failed to write the compile cache to `{}`",
+                cache_path.display()
+            )
+        })
+    }
+
+    /// Makes the path relative to the input directory, so that the cache survives moving it.
+    /// Paths outside of it are kept as they are.
+    fn key(&self, path: &Path) -> String {
+        path.strip_prefix(&self.input_dir)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+/// Hashes the content of the file with 64-bit FNV-1a, which is stable across runs and
+/// platforms. Returns `None` if the file cannot be read.
+fn hash_file(path: &Path) -> Option<String> {
+    let content = std::fs::read(path).ok()?;
+    let hash = content.iter().fold(0xcbf29ce484222325u64, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+
+    Some(format!("{:016x}", hash))
+}
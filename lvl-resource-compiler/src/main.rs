@@ -1,7 +1,7 @@
 mod cli;
 mod processors;
 
-use cli::{cli, compile};
+use cli::{cli, compile, CompileOptions};
 use log::{error, LevelFilter};
 use std::path::PathBuf;
 
@@ -18,8 +18,14 @@ fn main() {
         None => {
             let input = matches.get_one::<PathBuf>("input");
             let output = matches.get_one::<PathBuf>("output");
+            let options = CompileOptions {
+                precompile_shaders: !matches.get_flag("no-precompile-shaders"),
+                strip_shader_source: matches.get_flag("strip-shader-source"),
+                compression_level: matches.get_one::<u32>("compression-level").copied(),
+                force: matches.get_flag("force"),
+            };
 
-            if let Err(err) = compile(input, output) {
+            if let Err(err) = compile(input, output, &options) {
                 let mut errors = Vec::new();
 
                 for cause in err.chain() {
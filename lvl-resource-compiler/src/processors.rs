@@ -1,8 +1,10 @@
+mod gltf_model_processor;
 mod pmx_model_animation_processor;
 mod pmx_model_processor;
 mod shader_processor;
 mod texture_processor;
 
+pub use gltf_model_processor::*;
 pub use pmx_model_animation_processor::*;
 pub use pmx_model_processor::*;
 pub use shader_processor::*;
@@ -12,13 +14,39 @@ use anyhow::{Context, Error as AnyError};
 use log::{debug, warn};
 use lvl_resource::Resource;
 use serde::Deserialize;
-use std::path::Path;
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Key of the metadata file entry that holds the author-defined resource metadata.
+/// See [`Resource::metadata`].
+const RESOURCE_METADATA_KEY: &str = "metadata";
+
+/// Key of the resource metadata entry that holds the names a resource was made from, one per
+/// line, if any of them differs from the resource name, e.g. when [`sanitize_resource_name`]
+/// changed it or when several paths to the same file share a resource. Lets tools map a
+/// resource back to e.g. the texture paths written in a PMX file.
+pub const ORIGINAL_NAME_METADATA_KEY: &str = "original_name";
+
+/// Keys of the resource metadata entries that the compiler makes, which metadata files cannot set.
+const RESERVED_METADATA_KEYS: &[&str] = &[ORIGINAL_NAME_METADATA_KEY];
 
 pub trait Processor {
     type Metadata: for<'de> Deserialize<'de>;
 
     fn extension() -> &'static [&'static str];
     fn process(file: &Path, metadata: Option<&Self::Metadata>) -> Result<Vec<Resource>, AnyError>;
+
+    /// Returns the files other than `file` and its metadata file that the resources are made
+    /// from, so that the file is processed again when any of them changes. The files may not
+    /// exist.
+    fn dependencies(_file: &Path) -> Result<Vec<PathBuf>, AnyError> {
+        Ok(vec![])
+    }
 }
 
 pub fn process_single_file<P: Processor>(file: &Path) -> Result<Vec<Resource>, AnyError> {
@@ -38,19 +66,162 @@ pub fn process_single_file<P: Processor>(file: &Path) -> Result<Vec<Resource>, A
         return Ok(vec![]);
     }
 
-    let metadata = load_metadata::<P::Metadata>(file)?;
-    P::process(file, metadata.as_ref())
+    let (metadata, mut resource_metadata) = match load_metadata(file)? {
+        Some(content) => split_metadata::<P::Metadata>(content)
+            .with_context(|| format!("parsing the metadata of `{}`", file.display()))?,
+        None => (None, BTreeMap::new()),
+    };
+    let mut resources = P::process(file, metadata.as_ref())?;
+
+    for key in RESERVED_METADATA_KEYS {
+        if resource_metadata.remove(*key).is_some() {
+            warn!(
+                "the metadata of `{}` sets the reserved key `{}`; it will be ignored",
+                file.display(),
+                key
+            );
+        }
+    }
+
+    for resource in &mut resources {
+        // the metadata made by the processor wins over the metadata file
+        for (key, value) in &resource_metadata {
+            resource
+                .metadata
+                .entry(key.clone())
+                .or_insert_with(|| value.clone());
+        }
+    }
+
+    Ok(resources)
+}
+
+/// Normalizes a name taken from a source file so that the same thing is always named the same.
+/// Backslashes become slashes, and empty and `.` segments are dropped along with the whitespace
+/// around each segment, so `tex\\a.png` and `./tex//a.png` both become `tex/a.png`.
+/// Anything else, including spaces inside a segment and non-ASCII characters, is kept as is;
+/// PMX files are mostly authored in Japanese and the names must stay readable.
+pub fn sanitize_resource_name(name: &str) -> String {
+    name.split(['/', '\\'])
+        .map(str::trim)
+        .filter(|segment| !segment.is_empty() && *segment != ".")
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Makes the resource metadata that records the original names of a resource named `name`, if
+/// any of them differs from it. Repeated original names are recorded once.
+pub(crate) fn original_name_metadata<'a>(
+    name: &str,
+    original_names: impl IntoIterator<Item = &'a str>,
+) -> BTreeMap<String, String> {
+    let mut seen = BTreeSet::new();
+    let original_names = original_names
+        .into_iter()
+        .filter(|original_name| seen.insert(*original_name))
+        .collect::<Vec<_>>();
+    let mut metadata = BTreeMap::new();
+
+    if original_names
+        .iter()
+        .any(|original_name| *original_name != name)
+    {
+        metadata.insert(
+            ORIGINAL_NAME_METADATA_KEY.to_owned(),
+            original_names.join("\n"),
+        );
+    }
+
+    metadata
+}
+
+/// Number of threads to run the work within a file on: the available parallelism.
+pub(crate) fn default_thread_count() -> usize {
+    std::thread::available_parallelism().map_or(1, NonZeroUsize::get)
 }
 
-fn load_metadata<T>(file_path: &Path) -> Result<Option<T>, AnyError>
+/// Maps the items on up to `thread_count` threads, returning the results in the order of the
+/// items. The threads take the next item when they finish one, so uneven items are balanced.
+pub(crate) fn parallel_map<T, R>(
+    items: &[T],
+    thread_count: usize,
+    f: impl Fn(&T) -> R + Sync,
+) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+{
+    let thread_count = thread_count.clamp(1, items.len().max(1));
+
+    if thread_count == 1 {
+        return items.iter().map(f).collect();
+    }
+
+    let next_index = AtomicUsize::new(0);
+    let mut results = std::thread::scope(|scope| {
+        let workers = (0..thread_count)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = Vec::new();
+
+                    loop {
+                        let index = next_index.fetch_add(1, Ordering::Relaxed);
+                        let item = match items.get(index) {
+                            Some(item) => item,
+                            None => break,
+                        };
+
+                        results.push((index, f(item)));
+                    }
+
+                    results
+                })
+            })
+            .collect::<Vec<_>>();
+
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap())
+            .collect::<Vec<_>>()
+    });
+
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// Splits the content of a metadata file into the processor metadata and the resource metadata.
+/// A metadata file that has nothing but the resource metadata yields no processor metadata.
+fn split_metadata<T>(mut content: Value) -> Result<(Option<T>, BTreeMap<String, String>), AnyError>
 where
     T: for<'de> Deserialize<'de>,
 {
+    let resource_metadata = match content
+        .as_object_mut()
+        .and_then(|object| object.remove(RESOURCE_METADATA_KEY))
+    {
+        Some(resource_metadata) => serde_json::from_value(resource_metadata)
+            .context("the resource metadata must be a map of strings")?,
+        None => BTreeMap::new(),
+    };
+    let metadata = match &content {
+        Value::Object(object) if object.is_empty() => None,
+        _ => Some(serde_json::from_value(content)?),
+    };
+
+    Ok((metadata, resource_metadata))
+}
+
+/// Returns the path of the metadata file of the file, e.g. `a.png.meta` for `a.png`.
+pub(crate) fn metadata_path(file_path: &Path) -> PathBuf {
     let metadata_extension = match file_path.extension() {
         Some(extension) => format!("{}.meta", extension.to_string_lossy().to_string()),
         None => "meta".to_owned(),
     };
-    let metadata_path = file_path.with_extension(metadata_extension);
+    file_path.with_extension(metadata_extension)
+}
+
+fn load_metadata(file_path: &Path) -> Result<Option<Value>, AnyError> {
+    let metadata_path = metadata_path(file_path);
 
     if !metadata_path.is_file() {
         debug!(
@@ -84,3 +255,95 @@ where
 
     Ok(Some(metadata))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_sanitize_resource_name() {
+        assert_eq!(sanitize_resource_name("tex\\a.png"), "tex/a.png");
+        assert_eq!(sanitize_resource_name("./tex//a.png"), "tex/a.png");
+        assert_eq!(sanitize_resource_name(" tex / a b.png "), "tex/a b.png");
+        assert_eq!(sanitize_resource_name("../肌.png"), "../肌.png");
+        assert_eq!(sanitize_resource_name("初音ミク"), "初音ミク");
+
+        assert!(original_name_metadata("tex/a.png", ["tex/a.png", "tex/a.png"]).is_empty());
+        assert_eq!(
+            original_name_metadata("tex/a.png", ["tex\\a.png"]).get(ORIGINAL_NAME_METADATA_KEY),
+            Some(&"tex\\a.png".to_owned())
+        );
+        assert_eq!(
+            original_name_metadata("tex/a.png", ["tex/a.png", "tex/../tex/a.png", "tex/a.png"])
+                .get(ORIGINAL_NAME_METADATA_KEY),
+            Some(&"tex/a.png\ntex/../tex/a.png".to_owned())
+        );
+    }
+
+    #[test]
+    fn check_resource_metadata_from_metadata_file() {
+        let dir = std::env::temp_dir().join(format!(
+            "lvl-resource-compiler-metadata-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let shader_path = dir.join("plain.wgsl");
+        std::fs::write(
+            &shader_path,
+            r#"
+@vertex
+fn vs_main() -> @builtin(position) vec4<f32> {
+    return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return vec4<f32>(1.0, 1.0, 1.0, 1.0);
+}
+"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("plain.wgsl.meta"),
+            r#"{ "metadata": { "author": "someone", "license": "CC0", "original_name": "forged" } }"#,
+        )
+        .unwrap();
+
+        let resources = process_single_file::<ShaderProcessor>(&shader_path);
+        std::fs::remove_dir_all(&dir).unwrap();
+        let resources = resources.unwrap();
+
+        assert_eq!(resources.len(), 1);
+        assert_eq!(
+            resources[0].metadata.get("author").map(String::as_str),
+            Some("someone")
+        );
+        assert_eq!(
+            resources[0].metadata.get("license").map(String::as_str),
+            Some("CC0")
+        );
+        assert_eq!(resources[0].metadata.get(ORIGINAL_NAME_METADATA_KEY), None);
+    }
+
+    #[test]
+    fn check_split_metadata() {
+        let content = serde_json::json!({
+            "texture_format": "RGBA8Unorm",
+            "metadata": { "notes": "hand painted" },
+        });
+        let (metadata, resource_metadata) = split_metadata::<TextureMetadata>(content).unwrap();
+
+        assert!(metadata.is_some());
+        assert_eq!(
+            resource_metadata.get("notes").map(String::as_str),
+            Some("hand painted")
+        );
+
+        let (metadata, resource_metadata) =
+            split_metadata::<TextureMetadata>(serde_json::json!({})).unwrap();
+
+        assert!(metadata.is_none());
+        assert!(resource_metadata.is_empty());
+    }
+}
@@ -0,0 +1,1208 @@
+use super::{sanitize_resource_name, Processor, ShaderProcessor};
+use anyhow::{anyhow, Context, Error as AnyError};
+use log::error;
+use lvl_math::{Mat4, Quat, Vec3, Vec4};
+use lvl_resource::{
+    MaterialProperty, MaterialPropertyUniformValue, MaterialPropertyValue, MaterialRenderState,
+    MaterialRenderType, MaterialSource, MeshElement, MeshElementKind, MeshIndexKind, MeshSource,
+    MeshTopology, ModelElement, ModelSource, ModelTransform, ModelVisiblePart, Resource,
+    ResourceKind, TextureElement, TextureElementSamplingMode, TextureElementSize,
+    TextureElementTextureFormat, TextureElementWrappingMode, TextureKind, TextureSource,
+};
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+};
+use wgpu_types::{AddressMode, FilterMode};
+
+/// Imports static glTF 2.0 scenes, either `.gltf` with its buffers and images in data URIs or
+/// next to it, or `.glb`. Each mesh primitive becomes a mesh, the node hierarchy of the scene
+/// becomes a model, and the materials are drawn with their base color only.
+/// Skins, morph targets, animations, cameras and extensions are ignored.
+pub struct GltfModelProcessor;
+
+impl Processor for GltfModelProcessor {
+    type Metadata = ();
+
+    fn extension() -> &'static [&'static str] {
+        &["gltf", "glb"]
+    }
+
+    fn process(file: &Path, _metadata: Option<&Self::Metadata>) -> Result<Vec<Resource>, AnyError> {
+        let content = std::fs::read(file)?;
+        let (gltf, binary_chunk) = parse_container(&content)?;
+        let parent_path = file.parent().unwrap_or(Path::new(""));
+        let buffers = gltf
+            .buffers
+            .iter()
+            .enumerate()
+            .map(|(index, buffer)| load_buffer(parent_path, index, buffer, binary_chunk))
+            .collect::<Result<Vec<_>, _>>()?;
+        let model_name = sanitize_resource_name(&file.file_stem().unwrap().to_string_lossy());
+
+        GltfImporter {
+            model_name,
+            parent_path,
+            gltf: &gltf,
+            buffers: &buffers,
+        }
+        .import()
+    }
+
+    /// The buffers and images referred to by relative URIs.
+    fn dependencies(file: &Path) -> Result<Vec<PathBuf>, AnyError> {
+        let content = std::fs::read(file)?;
+        let (gltf, _) = parse_container(&content)?;
+        let parent_path = file.parent().unwrap_or(Path::new(""));
+
+        Ok(gltf
+            .buffers
+            .iter()
+            .filter_map(|buffer| buffer.uri.as_deref())
+            .chain(gltf.images.iter().filter_map(|image| image.uri.as_deref()))
+            .filter(|uri| !uri.starts_with("data:"))
+            .map(|uri| parent_path.join(decode_uri(uri)))
+            .collect())
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct Gltf {
+    scene: Option<usize>,
+    scenes: Vec<GltfScene>,
+    nodes: Vec<GltfNode>,
+    meshes: Vec<GltfMesh>,
+    materials: Vec<GltfMaterial>,
+    textures: Vec<GltfTexture>,
+    images: Vec<GltfImage>,
+    samplers: Vec<GltfSampler>,
+    accessors: Vec<GltfAccessor>,
+    buffer_views: Vec<GltfBufferView>,
+    buffers: Vec<GltfBuffer>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+struct GltfScene {
+    name: Option<String>,
+    nodes: Vec<usize>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+struct GltfNode {
+    name: Option<String>,
+    children: Vec<usize>,
+    mesh: Option<usize>,
+    /// Column-major, which is the memory layout of [`Mat4`] as well.
+    matrix: Option<[f32; 16]>,
+    translation: Option<[f32; 3]>,
+    /// `[x, y, z, w]`
+    rotation: Option<[f32; 4]>,
+    scale: Option<[f32; 3]>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+struct GltfMesh {
+    name: Option<String>,
+    primitives: Vec<GltfPrimitive>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+struct GltfPrimitive {
+    attributes: BTreeMap<String, usize>,
+    indices: Option<usize>,
+    material: Option<usize>,
+    mode: Option<u32>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct GltfMaterial {
+    name: Option<String>,
+    pbr_metallic_roughness: Option<GltfPbrMetallicRoughness>,
+    alpha_mode: Option<String>,
+    double_sided: bool,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct GltfPbrMetallicRoughness {
+    base_color_factor: Option<[f32; 4]>,
+    base_color_texture: Option<GltfTextureInfo>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct GltfTextureInfo {
+    index: usize,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+struct GltfTexture {
+    sampler: Option<usize>,
+    source: Option<usize>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct GltfImage {
+    name: Option<String>,
+    uri: Option<String>,
+    buffer_view: Option<usize>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct GltfSampler {
+    mag_filter: Option<u32>,
+    wrap_s: Option<u32>,
+    wrap_t: Option<u32>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct GltfAccessor {
+    buffer_view: Option<usize>,
+    byte_offset: usize,
+    component_type: u32,
+    normalized: bool,
+    count: usize,
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct GltfBufferView {
+    buffer: usize,
+    byte_offset: usize,
+    byte_length: usize,
+    byte_stride: Option<usize>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct GltfBuffer {
+    uri: Option<String>,
+    byte_length: usize,
+}
+
+const GLB_MAGIC: &[u8; 4] = b"glTF";
+const GLB_CHUNK_JSON: u32 = 0x4E4F534A;
+const GLB_CHUNK_BIN: u32 = 0x004E4942;
+
+const COMPONENT_TYPE_UNSIGNED_BYTE: u32 = 5121;
+const COMPONENT_TYPE_UNSIGNED_SHORT: u32 = 5123;
+const COMPONENT_TYPE_UNSIGNED_INT: u32 = 5125;
+const COMPONENT_TYPE_FLOAT: u32 = 5126;
+
+const MODE_POINTS: u32 = 0;
+const MODE_LINES: u32 = 1;
+const MODE_TRIANGLES: u32 = 4;
+
+const FILTER_NEAREST: u32 = 9728;
+const WRAP_CLAMP_TO_EDGE: u32 = 33071;
+const WRAP_MIRRORED_REPEAT: u32 = 33648;
+
+/// Position, normal and uv.
+const VERTEX_STRIDE: usize = 4 * (3 + 3 + 2);
+
+/// Splits a `.glb` into its JSON and binary chunks; anything else is read as `.gltf` JSON.
+fn parse_container(content: &[u8]) -> Result<(Gltf, Option<&[u8]>), AnyError> {
+    if !content.starts_with(GLB_MAGIC) {
+        let gltf = serde_json::from_slice(content).context("failed to parse the glTF JSON")?;
+        return Ok((gltf, None));
+    }
+
+    let read_u32 = |offset: usize| -> Result<u32, AnyError> {
+        let bytes = content
+            .get(offset..offset + 4)
+            .ok_or_else(|| anyhow!("the GLB is truncated"))?;
+        Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+    };
+
+    let version = read_u32(4)?;
+
+    if version != 2 {
+        return Err(anyhow!("the GLB version {} is not supported", version));
+    }
+
+    let length = (read_u32(8)? as usize).min(content.len());
+    let mut offset = 12;
+    let mut json = None;
+    let mut binary_chunk = None;
+
+    while offset + 8 <= length {
+        let chunk_length = read_u32(offset)? as usize;
+        let chunk_type = read_u32(offset + 4)?;
+        let chunk = content
+            .get(offset + 8..offset + 8 + chunk_length)
+            .ok_or_else(|| anyhow!("the GLB chunk at {} is truncated", offset))?;
+
+        match chunk_type {
+            GLB_CHUNK_JSON if json.is_none() => json = Some(chunk),
+            GLB_CHUNK_BIN if binary_chunk.is_none() => binary_chunk = Some(chunk),
+            _ => {}
+        }
+
+        // chunks are padded to 4 bytes
+        offset += 8 + chunk_length.next_multiple_of(4);
+    }
+
+    let json = json.ok_or_else(|| anyhow!("the GLB has no JSON chunk"))?;
+    let gltf = serde_json::from_slice(json).context("failed to parse the GLB JSON chunk")?;
+    Ok((gltf, binary_chunk))
+}
+
+fn load_buffer(
+    parent_path: &Path,
+    index: usize,
+    buffer: &GltfBuffer,
+    binary_chunk: Option<&[u8]>,
+) -> Result<Vec<u8>, AnyError> {
+    let data = match &buffer.uri {
+        // only the first buffer of a GLB may refer to the binary chunk
+        None if index == 0 => binary_chunk
+            .ok_or_else(|| anyhow!("the buffer {} has no URI and no GLB binary chunk", index))?
+            .to_vec(),
+        None => return Err(anyhow!("the buffer {} has no URI", index)),
+        Some(uri) => load_uri(parent_path, uri)
+            .with_context(|| format!("failed to load the buffer {}", index))?,
+    };
+
+    if data.len() < buffer.byte_length {
+        return Err(anyhow!(
+            "the buffer {} has {} bytes, but {} are declared",
+            index,
+            data.len(),
+            buffer.byte_length
+        ));
+    }
+
+    Ok(data)
+}
+
+/// Loads a data URI, or a file relative to the glTF.
+fn load_uri(parent_path: &Path, uri: &str) -> Result<Vec<u8>, AnyError> {
+    match uri.strip_prefix("data:") {
+        Some(data) => {
+            let (_, encoded) = data
+                .split_once(";base64,")
+                .ok_or_else(|| anyhow!("only base64 data URIs are supported"))?;
+            decode_base64(encoded)
+        }
+        None => {
+            let path = parent_path.join(decode_uri(uri));
+            std::fs::read(&path)
+                .with_context(|| format!("failed to read the file `{}`", path.display()))
+        }
+    }
+}
+
+/// Decodes the percent-encoded bytes of a relative URI, e.g. `%20` for a space.
+fn decode_uri(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+
+    while index < bytes.len() {
+        let hex = bytes
+            .get(index + 1..index + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match (bytes[index], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                index += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn decode_base64(encoded: &str) -> Result<Vec<u8>, AnyError> {
+    let mut decoded = Vec::with_capacity(encoded.len() / 4 * 3);
+    let mut bits = 0u32;
+    let mut bit_count = 0;
+
+    for byte in encoded.bytes() {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            b'=' => break,
+            _ => return Err(anyhow!("the data URI is not valid base64")),
+        };
+
+        bits = (bits << 6) | value as u32;
+        bit_count += 6;
+
+        if 8 <= bit_count {
+            bit_count -= 8;
+            decoded.push((bits >> bit_count) as u8);
+        }
+    }
+
+    Ok(decoded)
+}
+
+struct GltfImporter<'a> {
+    model_name: String,
+    parent_path: &'a Path,
+    gltf: &'a Gltf,
+    buffers: &'a [Vec<u8>],
+}
+
+impl GltfImporter<'_> {
+    fn import(&self) -> Result<Vec<Resource>, AnyError> {
+        let shader_name = format!("{}/shader:{}", self.model_name, "gltf-base-color");
+        let white_texture_name = format!("{}/texture:{}", self.model_name, "white");
+        let mut resources = Vec::new();
+        let mut texture_names = BTreeMap::new();
+        let mut is_white_texture_used = false;
+
+        for (image_index, image) in self.gltf.images.iter().enumerate() {
+            let name = format!(
+                "{}/texture:{}",
+                self.model_name,
+                self.image_name(image_index)
+            );
+
+            match self.make_texture_source(image_index, image) {
+                Ok(source) => {
+                    texture_names.insert(image_index, name.clone());
+                    resources.push(Resource {
+                        name,
+                        kind: ResourceKind::Texture(source),
+                        metadata: BTreeMap::new(),
+                    });
+                }
+                Err(err) => {
+                    error!(
+                        "failed to import the image `{}`; it will be ignored: {}",
+                        name, err
+                    );
+                }
+            }
+        }
+
+        let mut material_names = Vec::with_capacity(self.gltf.materials.len());
+
+        for (material_index, material) in self.gltf.materials.iter().enumerate() {
+            let name = self.material_name(material_index);
+            let pbr = material.pbr_metallic_roughness.clone().unwrap_or_default();
+            let texture = pbr.base_color_texture.as_ref().and_then(|info| {
+                let texture = self.gltf.textures.get(info.index)?;
+                let texture_name = texture_names.get(&texture.source?)?;
+                Some((texture, texture_name))
+            });
+            let texture_name = match texture {
+                Some((_, texture_name)) => texture_name.clone(),
+                None => {
+                    is_white_texture_used = true;
+                    white_texture_name.clone()
+                }
+            };
+            let sampler = texture
+                .and_then(|(texture, _)| self.gltf.samplers.get(texture.sampler?))
+                .cloned()
+                .unwrap_or_default();
+
+            resources.push(Resource {
+                name: name.clone(),
+                kind: ResourceKind::Material(make_material_source(
+                    &shader_name,
+                    material,
+                    pbr.base_color_factor.unwrap_or([1.0; 4]),
+                    &texture_name,
+                    &sampler,
+                )),
+                metadata: BTreeMap::new(),
+            });
+            material_names.push(name);
+        }
+
+        let default_material_name = format!("{}/material:{}", self.model_name, "default");
+        let mut is_default_material_used = false;
+        let mut mesh_parts = Vec::with_capacity(self.gltf.meshes.len());
+
+        for (mesh_index, mesh) in self.gltf.meshes.iter().enumerate() {
+            let mesh_name = mesh
+                .name
+                .as_deref()
+                .map(sanitize_resource_name)
+                .unwrap_or_else(|| format!("mesh{}", mesh_index));
+            let mut parts = Vec::with_capacity(mesh.primitives.len());
+
+            for (primitive_index, primitive) in mesh.primitives.iter().enumerate() {
+                let name = match mesh.primitives.len() {
+                    1 => format!("{}/mesh:{}", self.model_name, mesh_name),
+                    _ => format!("{}/mesh:{}.{}", self.model_name, mesh_name, primitive_index),
+                };
+                let source = match self.make_mesh_source(primitive) {
+                    Ok(source) => source,
+                    Err(err) => {
+                        error!(
+                            "failed to import the mesh `{}`; it will be ignored: {}",
+                            name, err
+                        );
+                        continue;
+                    }
+                };
+                let material_name = match primitive
+                    .material
+                    .and_then(|index| material_names.get(index))
+                {
+                    Some(material_name) => material_name.clone(),
+                    None => {
+                        is_default_material_used = true;
+                        default_material_name.clone()
+                    }
+                };
+
+                resources.push(Resource {
+                    name: name.clone(),
+                    kind: ResourceKind::Mesh(source),
+                    metadata: BTreeMap::new(),
+                });
+                parts.push(ModelVisiblePart {
+                    mesh_name: name,
+                    material_name,
+                });
+            }
+
+            mesh_parts.push(parts);
+        }
+
+        if is_default_material_used {
+            is_white_texture_used = true;
+            resources.push(Resource {
+                name: default_material_name,
+                kind: ResourceKind::Material(make_material_source(
+                    &shader_name,
+                    &GltfMaterial::default(),
+                    [1.0; 4],
+                    &white_texture_name,
+                    &GltfSampler::default(),
+                )),
+                metadata: BTreeMap::new(),
+            });
+        }
+
+        if is_white_texture_used {
+            resources.push(Resource {
+                name: white_texture_name,
+                kind: ResourceKind::Texture(TextureSource::new(TextureKind::Single(
+                    TextureElement {
+                        data: vec![255; 4],
+                        size: TextureElementSize {
+                            width: 1,
+                            height: 1,
+                        },
+                        texture_format: TextureElementTextureFormat::RGBA8UnormSrgb,
+                        sampling_mode: TextureElementSamplingMode::Point,
+                        wrapping_mode_u: TextureElementWrappingMode::Clamp,
+                        wrapping_mode_v: TextureElementWrappingMode::Clamp,
+                        mip_data: vec![],
+                    },
+                ))),
+                metadata: BTreeMap::new(),
+            });
+        }
+
+        let shader_source = ShaderProcessor::generate_shader_resource_from_wsgl_content(
+            &shader_name,
+            include_str!("../../assets/gltf-base-color.wgsl").to_owned(),
+            &BTreeSet::new(),
+        )?;
+        resources.push(Resource {
+            name: shader_name,
+            kind: ResourceKind::Shader(shader_source),
+            metadata: BTreeMap::new(),
+        });
+
+        resources.push(Resource {
+            name: self.model_name.clone(),
+            kind: ResourceKind::Model(self.make_model_source(&mesh_parts)?),
+            metadata: BTreeMap::new(),
+        });
+
+        Ok(resources)
+    }
+
+    fn image_name(&self, image_index: usize) -> String {
+        match &self.gltf.images[image_index].name {
+            Some(name) => sanitize_resource_name(name),
+            None => format!("image{}", image_index),
+        }
+    }
+
+    fn material_name(&self, material_index: usize) -> String {
+        let name = match &self.gltf.materials[material_index].name {
+            Some(name) => sanitize_resource_name(name),
+            None => format!("material{}", material_index),
+        };
+        format!("{}/material:{}", self.model_name, name)
+    }
+
+    fn make_texture_source(
+        &self,
+        image_index: usize,
+        image: &GltfImage,
+    ) -> Result<TextureSource, AnyError> {
+        let data = match (&image.uri, image.buffer_view) {
+            (Some(uri), _) => load_uri(self.parent_path, uri)?,
+            (None, Some(buffer_view)) => self.buffer_view(buffer_view)?.to_vec(),
+            (None, None) => {
+                return Err(anyhow!("the image {} has no data", image_index));
+            }
+        };
+        let decoded = image::load_from_memory(&data)?;
+        let width = decoded.width();
+        let height = decoded.height();
+
+        if (u16::MAX as u32) < width || (u16::MAX as u32) < height {
+            return Err(anyhow!("image too large"));
+        }
+
+        Ok(TextureSource::new(TextureKind::Single(TextureElement {
+            data: decoded.into_rgba8().to_vec(),
+            size: TextureElementSize {
+                width: width as u16,
+                height: height as u16,
+            },
+            // base colors are sRGB in glTF
+            texture_format: TextureElementTextureFormat::RGBA8UnormSrgb,
+            sampling_mode: TextureElementSamplingMode::Bilinear,
+            wrapping_mode_u: TextureElementWrappingMode::Repeat,
+            wrapping_mode_v: TextureElementWrappingMode::Repeat,
+            mip_data: vec![],
+        })))
+    }
+
+    fn buffer_view(&self, index: usize) -> Result<&[u8], AnyError> {
+        let view = self
+            .gltf
+            .buffer_views
+            .get(index)
+            .ok_or_else(|| anyhow!("the buffer view {} does not exist", index))?;
+        let end = view
+            .byte_offset
+            .checked_add(view.byte_length)
+            .ok_or_else(|| anyhow!("the buffer view {} is out of its buffer", index))?;
+        self.buffers
+            .get(view.buffer)
+            .and_then(|buffer| buffer.get(view.byte_offset..end))
+            .ok_or_else(|| anyhow!("the buffer view {} is out of its buffer", index))
+    }
+
+    /// Reads the accessor as floats, `component_count` per element. Integer components are
+    /// converted as is, or into `[0, 1]` if they are normalized.
+    fn read_floats(&self, index: usize, component_count: usize) -> Result<Vec<f32>, AnyError> {
+        let accessor = self.accessor(index, component_count)?;
+        let read = |bytes: &[u8]| match accessor.component_type {
+            COMPONENT_TYPE_FLOAT => Ok(f32::from_le_bytes(bytes.try_into().unwrap())),
+            COMPONENT_TYPE_UNSIGNED_BYTE if accessor.normalized => Ok(bytes[0] as f32 / 255.0),
+            COMPONENT_TYPE_UNSIGNED_SHORT if accessor.normalized => {
+                Ok(u16::from_le_bytes(bytes.try_into().unwrap()) as f32 / 65535.0)
+            }
+            component_type => Err(anyhow!(
+                "the component type {} is not supported for the accessor {}",
+                component_type,
+                index
+            )),
+        };
+
+        self.read_components(index, accessor, component_count, read)
+    }
+
+    fn read_indices(&self, index: usize) -> Result<Vec<u32>, AnyError> {
+        let accessor = self.accessor(index, 1)?;
+        let read = |bytes: &[u8]| match accessor.component_type {
+            COMPONENT_TYPE_UNSIGNED_BYTE => Ok(bytes[0] as u32),
+            COMPONENT_TYPE_UNSIGNED_SHORT => {
+                Ok(u16::from_le_bytes(bytes.try_into().unwrap()) as u32)
+            }
+            COMPONENT_TYPE_UNSIGNED_INT => Ok(u32::from_le_bytes(bytes.try_into().unwrap())),
+            component_type => Err(anyhow!(
+                "the component type {} is not supported for the indices {}",
+                component_type,
+                index
+            )),
+        };
+
+        self.read_components(index, accessor, 1, read)
+    }
+
+    fn accessor(&self, index: usize, component_count: usize) -> Result<&GltfAccessor, AnyError> {
+        let accessor = self
+            .gltf
+            .accessors
+            .get(index)
+            .ok_or_else(|| anyhow!("the accessor {} does not exist", index))?;
+        let expected_kind = match component_count {
+            1 => "SCALAR",
+            2 => "VEC2",
+            3 => "VEC3",
+            _ => "VEC4",
+        };
+
+        if accessor.kind != expected_kind {
+            return Err(anyhow!(
+                "the accessor {} is `{}`, but `{}` is expected",
+                index,
+                accessor.kind,
+                expected_kind
+            ));
+        }
+
+        Ok(accessor)
+    }
+
+    fn read_components<T>(
+        &self,
+        index: usize,
+        accessor: &GltfAccessor,
+        component_count: usize,
+        read: impl Fn(&[u8]) -> Result<T, AnyError>,
+    ) -> Result<Vec<T>, AnyError> {
+        let component_size = match accessor.component_type {
+            COMPONENT_TYPE_UNSIGNED_BYTE => 1,
+            COMPONENT_TYPE_UNSIGNED_SHORT => 2,
+            _ => 4,
+        };
+        let element_size = component_size * component_count;
+        let buffer_view_index = accessor
+            .buffer_view
+            .ok_or_else(|| anyhow!("the accessor {} has no buffer view", index))?;
+        let data = self.buffer_view(buffer_view_index)?;
+        let stride = self.gltf.buffer_views[buffer_view_index]
+            .byte_stride
+            .unwrap_or(element_size);
+        let out_of_buffer_view = || anyhow!("the accessor {} is out of its buffer view", index);
+
+        if stride < element_size {
+            return Err(anyhow!(
+                "the stride of the accessor {} is smaller than its elements",
+                index
+            ));
+        }
+
+        // checked before allocating, so that a huge count in a small file fails early
+        if accessor.count != 0 {
+            let end = (accessor.count - 1)
+                .checked_mul(stride)
+                .and_then(|offset| offset.checked_add(accessor.byte_offset))
+                .and_then(|offset| offset.checked_add(element_size))
+                .ok_or_else(out_of_buffer_view)?;
+
+            if data.len() < end {
+                return Err(out_of_buffer_view());
+            }
+        }
+
+        let mut values = Vec::with_capacity(accessor.count * component_count);
+
+        for element_index in 0..accessor.count {
+            let offset = accessor.byte_offset + element_index * stride;
+            let element = data
+                .get(offset..offset + element_size)
+                .ok_or_else(out_of_buffer_view)?;
+
+            for component in element.chunks_exact(component_size) {
+                values.push(read(component)?);
+            }
+        }
+
+        Ok(values)
+    }
+
+    fn make_mesh_source(&self, primitive: &GltfPrimitive) -> Result<MeshSource, AnyError> {
+        let topology = match primitive.mode.unwrap_or(MODE_TRIANGLES) {
+            MODE_TRIANGLES => MeshTopology::TriangleList,
+            MODE_LINES => MeshTopology::LineList,
+            MODE_POINTS => MeshTopology::PointList,
+            mode => {
+                return Err(anyhow!("the primitive mode {} is not supported", mode));
+            }
+        };
+        let position_index = *primitive
+            .attributes
+            .get("POSITION")
+            .ok_or_else(|| anyhow!("the primitive has no positions"))?;
+        let positions = self.read_floats(position_index, 3)?;
+        let vertex_count = positions.len() / 3;
+        let indices = match primitive.indices {
+            Some(index) => self.read_indices(index)?,
+            None => (0..vertex_count as u32).collect(),
+        };
+        let normals = match primitive.attributes.get("NORMAL") {
+            Some(&index) => self.read_floats(index, 3)?,
+            None if topology == MeshTopology::TriangleList => compute_normals(&positions, &indices),
+            None => vec![0.0; vertex_count * 3],
+        };
+        let uvs = match primitive.attributes.get("TEXCOORD_0") {
+            Some(&index) => self.read_floats(index, 2)?,
+            None => vec![0.0; vertex_count * 2],
+        };
+
+        if normals.len() != positions.len() || uvs.len() / 2 != vertex_count {
+            return Err(anyhow!(
+                "the attributes of the primitive have different vertex counts"
+            ));
+        }
+
+        let mut vertex_data = Vec::with_capacity(vertex_count * VERTEX_STRIDE);
+
+        for vertex_index in 0..vertex_count {
+            let components = positions[vertex_index * 3..vertex_index * 3 + 3]
+                .iter()
+                .chain(&normals[vertex_index * 3..vertex_index * 3 + 3])
+                .chain(&uvs[vertex_index * 2..vertex_index * 2 + 2]);
+
+            for component in components {
+                vertex_data.extend(component.to_le_bytes());
+            }
+        }
+
+        let index_data = Vec::from_iter(indices.iter().flat_map(|index| index.to_le_bytes()));
+        let source = MeshSource::new(
+            vertex_count as u32,
+            vertex_data,
+            index_data,
+            MeshIndexKind::U32,
+            topology,
+            vec![
+                MeshElement {
+                    name: "position".to_owned(),
+                    kind: MeshElementKind::Position,
+                    offset: 0,
+                },
+                MeshElement {
+                    name: "normal".to_owned(),
+                    kind: MeshElementKind::Normal,
+                    offset: 12,
+                },
+                MeshElement {
+                    name: "uv".to_owned(),
+                    kind: MeshElementKind::TexCoord(0),
+                    offset: 24,
+                },
+            ],
+        );
+        source.validate_indices()?;
+
+        Ok(source)
+    }
+
+    /// Makes an element for each node of the scene, and a root element holding its root nodes,
+    /// since a scene may have many of them. The elements are indexed by their nodes, and the
+    /// root by the node count.
+    fn make_model_source(
+        &self,
+        mesh_parts: &[Vec<ModelVisiblePart>],
+    ) -> Result<ModelSource, AnyError> {
+        let scene = self
+            .gltf
+            .scene
+            .or((!self.gltf.scenes.is_empty()).then_some(0))
+            .and_then(|index| self.gltf.scenes.get(index));
+        let root_index = self.gltf.nodes.len() as u32;
+        let root_nodes = match scene {
+            Some(scene) => scene.nodes.clone(),
+            // without scenes, every node without a parent is a root
+            None => {
+                let children = BTreeSet::from_iter(
+                    self.gltf
+                        .nodes
+                        .iter()
+                        .flat_map(|node| node.children.iter().copied()),
+                );
+                Vec::from_iter((0..self.gltf.nodes.len()).filter(|index| !children.contains(index)))
+            }
+        };
+
+        let mut elements = vec![ModelElement {
+            index: root_index,
+            name: scene
+                .and_then(|scene| scene.name.clone())
+                .unwrap_or_else(|| self.model_name.clone()),
+            parent_index: None,
+            transform: ModelTransform {
+                position: Vec3::ZERO,
+                rotation: Quat::IDENTITY,
+                scale: Vec3::ONE,
+            },
+            visible_parts: vec![],
+        }];
+        let mut stack = Vec::from_iter(root_nodes.into_iter().map(|index| (index, root_index)));
+        let mut visited = BTreeSet::new();
+
+        while let Some((node_index, parent_index)) = stack.pop() {
+            let node = self
+                .gltf
+                .nodes
+                .get(node_index)
+                .ok_or_else(|| anyhow!("the node {} does not exist", node_index))?;
+
+            if !visited.insert(node_index) {
+                return Err(anyhow!("the node {} has many parents", node_index));
+            }
+
+            elements.push(ModelElement {
+                index: node_index as u32,
+                name: node
+                    .name
+                    .clone()
+                    .unwrap_or_else(|| format!("node{}", node_index)),
+                parent_index: Some(parent_index),
+                transform: node_transform(node),
+                visible_parts: node
+                    .mesh
+                    .and_then(|mesh_index| mesh_parts.get(mesh_index))
+                    .cloned()
+                    .unwrap_or_default(),
+            });
+            stack.extend(
+                node.children
+                    .iter()
+                    .rev()
+                    .map(|&child_index| (child_index, node_index as u32)),
+            );
+        }
+
+        Ok(ModelSource::new(root_index, elements))
+    }
+}
+
+fn node_transform(node: &GltfNode) -> ModelTransform {
+    if let Some(matrix) = node.matrix {
+        let (position, rotation, scale) = Mat4::new(matrix).decompose();
+        return ModelTransform {
+            position,
+            rotation,
+            scale,
+        };
+    }
+
+    let [x, y, z] = node.translation.unwrap_or([0.0; 3]);
+    let [rx, ry, rz, rw] = node.rotation.unwrap_or([0.0, 0.0, 0.0, 1.0]);
+    let [sx, sy, sz] = node.scale.unwrap_or([1.0; 3]);
+
+    ModelTransform {
+        position: Vec3::new(x, y, z),
+        rotation: Quat::new(rx, ry, rz, rw),
+        scale: Vec3::new(sx, sy, sz),
+    }
+}
+
+/// Computes smooth normals by summing the normals of the faces around each vertex, weighted by
+/// their areas.
+fn compute_normals(positions: &[f32], indices: &[u32]) -> Vec<f32> {
+    let position = |index: u32| {
+        let index = index as usize * 3;
+        Vec3::new(positions[index], positions[index + 1], positions[index + 2])
+    };
+    let mut normals = vec![Vec3::ZERO; positions.len() / 3];
+
+    for triangle in indices.chunks_exact(3) {
+        if triangle
+            .iter()
+            .any(|&index| normals.len() <= index as usize)
+        {
+            continue;
+        }
+
+        let a = position(triangle[0]);
+        let normal = Vec3::cross(position(triangle[1]) - a, position(triangle[2]) - a);
+
+        for &index in triangle {
+            normals[index as usize] += normal;
+        }
+    }
+
+    Vec::from_iter(normals.into_iter().flat_map(|normal| {
+        let normal = match normal.len() {
+            len if f32::EPSILON < len => normal / len,
+            _ => Vec3::UP,
+        };
+        [normal.x, normal.y, normal.z]
+    }))
+}
+
+fn make_material_source(
+    shader_name: &str,
+    material: &GltfMaterial,
+    base_color_factor: [f32; 4],
+    texture_name: &str,
+    sampler: &GltfSampler,
+) -> MaterialSource {
+    let address_mode = |wrap: Option<u32>| match wrap {
+        Some(WRAP_CLAMP_TO_EDGE) => AddressMode::ClampToEdge,
+        Some(WRAP_MIRRORED_REPEAT) => AddressMode::MirrorRepeat,
+        _ => AddressMode::Repeat,
+    };
+    let filter = match sampler.mag_filter {
+        Some(FILTER_NEAREST) => FilterMode::Nearest,
+        _ => FilterMode::Linear,
+    };
+    let [r, g, b, a] = base_color_factor;
+
+    MaterialSource::new(
+        shader_name.to_owned(),
+        MaterialRenderState {
+            render_type: match material.alpha_mode.as_deref() {
+                Some("BLEND") => MaterialRenderType::Transparent,
+                _ => MaterialRenderType::Opaque,
+            },
+            no_cull_back_face: material.double_sided,
+            cast_shadow_on_ground: false,
+            cast_shadow_on_object: false,
+            receive_shadow: false,
+            has_edge: false,
+            vertex_color: false,
+            point_drawing: false,
+            line_drawing: false,
+        },
+        vec![
+            MaterialProperty {
+                name: "base_color".to_owned(),
+                value: MaterialPropertyValue::Uniform(MaterialPropertyUniformValue::Vec4(
+                    Vec4::new(r, g, b, a),
+                )),
+            },
+            MaterialProperty {
+                name: "base_color_texture".to_owned(),
+                value: MaterialPropertyValue::Texture {
+                    texture_name: texture_name.to_owned(),
+                },
+            },
+            MaterialProperty {
+                name: "base_color_sampler".to_owned(),
+                value: MaterialPropertyValue::Sampler {
+                    address_mode_u: address_mode(sampler.wrap_s),
+                    address_mode_v: address_mode(sampler.wrap_t),
+                    address_mode_w: AddressMode::ClampToEdge,
+                    mag_filter: filter,
+                    min_filter: filter,
+                    mipmap_filter: FilterMode::Nearest,
+                    lod_min_clamp: 0.0,
+                    lod_max_clamp: 32.0,
+                    compare: None,
+                    anisotropy_clamp: 1,
+                    border_color: None,
+                },
+            },
+        ],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lvl_resource::{ResourceFile, ResourceFileVersion};
+
+    /// Makes a GLB with a triangle mesh of two primitives, one with a textured material and one
+    /// with none, under a node with a child:
+    /// - `root`, moved up by 1
+    ///   - `leaf`, scaled by 2, with the mesh
+    fn make_glb() -> Vec<u8> {
+        let mut png = Vec::new();
+        image::RgbaImage::from_pixel(2, 2, image::Rgba([255, 0, 0, 255]))
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+
+        let mut binary = Vec::new();
+
+        for component in [0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0] {
+            binary.extend(component.to_le_bytes());
+        }
+
+        for index in [0u16, 1, 2, 0] {
+            binary.extend(index.to_le_bytes());
+        }
+
+        binary.extend(&png);
+        binary.resize(binary.len().next_multiple_of(4), 0);
+
+        let json = serde_json::json!({
+            "asset": { "version": "2.0" },
+            "scene": 0,
+            "scenes": [{ "nodes": [0] }],
+            "nodes": [
+                { "name": "root", "children": [1], "translation": [0.0, 1.0, 0.0] },
+                { "name": "leaf", "mesh": 0, "scale": [2.0, 2.0, 2.0] },
+            ],
+            "meshes": [{
+                "name": "triangle",
+                "primitives": [
+                    { "attributes": { "POSITION": 0 }, "indices": 1, "material": 0 },
+                    { "attributes": { "POSITION": 0 } },
+                ],
+            }],
+            "materials": [{
+                "name": "red",
+                "pbrMetallicRoughness": {
+                    "baseColorFactor": [1.0, 0.5, 0.5, 1.0],
+                    "baseColorTexture": { "index": 0 },
+                },
+            }],
+            "textures": [{ "source": 0 }],
+            "images": [{ "name": "skin", "bufferView": 2, "mimeType": "image/png" }],
+            "accessors": [
+                { "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3" },
+                { "bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR" },
+            ],
+            "bufferViews": [
+                { "buffer": 0, "byteOffset": 0, "byteLength": 36 },
+                { "buffer": 0, "byteOffset": 36, "byteLength": 6 },
+                { "buffer": 0, "byteOffset": 44, "byteLength": png.len() },
+            ],
+            "buffers": [{ "byteLength": binary.len() }],
+        });
+        let mut json = serde_json::to_vec(&json).unwrap();
+        json.resize(json.len().next_multiple_of(4), b' ');
+
+        let mut glb = Vec::new();
+        glb.extend(GLB_MAGIC);
+        glb.extend(2u32.to_le_bytes());
+        glb.extend(((12 + 8 + json.len() + 8 + binary.len()) as u32).to_le_bytes());
+        glb.extend((json.len() as u32).to_le_bytes());
+        glb.extend(GLB_CHUNK_JSON.to_le_bytes());
+        glb.extend(json);
+        glb.extend((binary.len() as u32).to_le_bytes());
+        glb.extend(GLB_CHUNK_BIN.to_le_bytes());
+        glb.extend(binary);
+        glb
+    }
+
+    fn names<T>(file: &ResourceFile) -> Vec<&str>
+    where
+        T: lvl_resource::FromResourceKind + 'static,
+    {
+        Vec::from_iter(file.find_all::<T>().map(|(name, _)| name))
+    }
+
+    #[test]
+    fn check_glb_resources() {
+        let dir =
+            std::env::temp_dir().join(format!("lvl-resource-compiler-gltf-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let glb_path = dir.join("model.glb");
+        std::fs::write(&glb_path, make_glb()).unwrap();
+
+        let resources = GltfModelProcessor::process(&glb_path, None);
+        std::fs::remove_dir_all(&dir).unwrap();
+        let file = ResourceFile::new(ResourceFileVersion::CURRENT, resources.unwrap());
+
+        assert_eq!(
+            names::<MeshSource>(&file),
+            ["model/mesh:triangle.0", "model/mesh:triangle.1"]
+        );
+        assert_eq!(
+            names::<MaterialSource>(&file),
+            ["model/material:default", "model/material:red"]
+        );
+        assert_eq!(
+            names::<TextureSource>(&file),
+            ["model/texture:skin", "model/texture:white"]
+        );
+        assert_eq!(
+            names::<lvl_resource::ShaderSource>(&file),
+            ["model/shader:gltf-base-color"]
+        );
+        assert_eq!(file.load_order().unwrap().last(), Some(&"model"));
+
+        let red = file.find::<MaterialSource>("model/material:red").unwrap();
+        assert_eq!(
+            red.properties()["base_color_texture"].value,
+            MaterialPropertyValue::Texture {
+                texture_name: "model/texture:skin".to_owned()
+            }
+        );
+
+        // the missing normals are computed from the faces
+        let mesh = file.find::<MeshSource>("model/mesh:triangle.0").unwrap();
+        let normal_z = f32::from_le_bytes(mesh.vertex_data()[20..24].try_into().unwrap());
+        assert_eq!(mesh.vertex_count(), 3);
+        assert_eq!(mesh.vertex_data().len(), 3 * VERTEX_STRIDE);
+        assert_eq!(normal_z, 1.0);
+
+        let model = file.find::<ModelSource>("model").unwrap();
+        let element = |name: &str| {
+            model
+                .elements()
+                .iter()
+                .find(|element| element.name == name)
+                .unwrap()
+        };
+        assert_eq!(model.root_element_index(), 2);
+        assert_eq!(element("root").parent_index, Some(2));
+        assert_eq!(element("root").transform.position, Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(element("leaf").parent_index, Some(0));
+        assert_eq!(element("leaf").transform.scale, Vec3::new(2.0, 2.0, 2.0));
+        assert_eq!(
+            Vec::from_iter(
+                element("leaf")
+                    .visible_parts
+                    .iter()
+                    .map(|part| (part.mesh_name.as_str(), part.material_name.as_str()))
+            ),
+            [
+                ("model/mesh:triangle.0", "model/material:red"),
+                ("model/mesh:triangle.1", "model/material:default"),
+            ]
+        );
+    }
+
+    #[test]
+    fn check_accessor_out_of_buffer_view() {
+        let gltf = serde_json::from_value::<Gltf>(serde_json::json!({
+            "accessors": [
+                // far more elements than the buffer view holds
+                { "bufferView": 0, "componentType": 5126, "count": usize::MAX / 4, "type": "VEC3" },
+                { "bufferView": 1, "componentType": 5126, "count": 1000, "type": "VEC3" },
+                { "bufferView": 0, "componentType": 5126, "count": 1, "type": "VEC3" },
+            ],
+            "bufferViews": [
+                { "buffer": 0, "byteOffset": 0, "byteLength": 12 },
+                { "buffer": 0, "byteOffset": 0, "byteLength": 12, "byteStride": 0 },
+                { "buffer": 0, "byteOffset": usize::MAX, "byteLength": 12 },
+            ],
+        }))
+        .unwrap();
+        let buffers = [vec![0; 12]];
+        let importer = GltfImporter {
+            model_name: "model".to_owned(),
+            parent_path: Path::new(""),
+            gltf: &gltf,
+            buffers: &buffers,
+        };
+
+        assert!(importer.read_floats(0, 3).is_err());
+        assert!(importer.read_floats(1, 3).is_err());
+        assert_eq!(importer.read_floats(2, 3).unwrap(), [0.0; 3]);
+        assert!(importer.buffer_view(2).is_err());
+    }
+
+    #[test]
+    fn check_decode_uri() {
+        assert_eq!(decode_base64("aGVsbG8=").unwrap(), b"hello");
+        assert_eq!(decode_base64("aGk").unwrap(), b"hi");
+        assert_eq!(decode_uri("my%20model.bin"), "my model.bin");
+        assert_eq!(decode_uri("100%"), "100%");
+    }
+}
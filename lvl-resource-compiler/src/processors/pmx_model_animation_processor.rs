@@ -7,7 +7,10 @@ use lvl_resource::{
     PmxModelAnimationMorphKeyFrameElement, PmxModelAnimationSource, Resource, ResourceKind,
 };
 use lvl_vmd::Vmd;
-use std::{collections::HashMap, path::Path};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+};
 
 pub struct PmxModelAnimationProcessor;
 
@@ -109,6 +112,7 @@ impl Processor for PmxModelAnimationProcessor {
                 bone_key_frames,
                 morph_key_frames,
             )),
+            metadata: BTreeMap::new(),
         }])
     }
 }
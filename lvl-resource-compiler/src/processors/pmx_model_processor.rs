@@ -1,28 +1,33 @@
-use super::{Processor, ShaderProcessor, TextureMetadata, TextureProcessor};
+use super::{
+    default_thread_count, original_name_metadata, parallel_map, sanitize_resource_name, Processor,
+    ShaderProcessor, TextureMetadata, TextureProcessor,
+};
 use anyhow::{anyhow, Error as AnyError};
 use log::{error, warn};
-use lvl_math::{Vec3, Vec4};
+use lvl_math::{Mat4, Vec3, Vec4};
 use lvl_pmx::{
-    Pmx, PmxBone, PmxBoneInheritanceMode, PmxIndices, PmxMaterial, PmxMaterialEnvironmentBlendMode,
+    Pmx, PmxBone, PmxBoneInheritanceMode, PmxMaterial, PmxMaterialEnvironmentBlendMode,
     PmxMaterialToonMode, PmxMorph, PmxMorphOffset, PmxMorphOffsetMaterialOffsetMode, PmxTexture,
     PmxVertex, PmxVertexDeformKind,
 };
 use lvl_resource::{
     MaterialProperty, MaterialPropertyUniformValue, MaterialPropertyValue, MaterialRenderState,
-    MaterialRenderType, MaterialSource, PmxModelBone, PmxModelBoneFlags, PmxModelBoneIK,
-    PmxModelBoneIKAngleLimit, PmxModelBoneIKLink, PmxModelBoneInheritance,
-    PmxModelBoneInheritanceMode, PmxModelElement, PmxModelIndexKind, PmxModelMorph,
-    PmxModelMorphGroupElement, PmxModelMorphKind, PmxModelMorphMaterialElement,
-    PmxModelMorphMaterialOffsetMode, PmxModelSource, PmxModelVertexLayoutElement,
-    PmxModelVertexLayoutElementKind, Resource, ResourceKind, TextureElement,
-    TextureElementSamplingMode, TextureElementSize, TextureElementTextureFormat,
+    MaterialRenderType, MaterialSource, MeshElement, MeshElementKind, MeshIndexKind, MeshSource,
+    MeshTopology, PmxModelBone, PmxModelBoneFlags, PmxModelBoneIK, PmxModelBoneIKAngleLimit,
+    PmxModelBoneIKLink, PmxModelBoneInheritance, PmxModelBoneInheritanceMode, PmxModelElement,
+    PmxModelIndexKind, PmxModelMorph, PmxModelMorphGroupElement, PmxModelMorphKind,
+    PmxModelMorphMaterialElement, PmxModelMorphMaterialOffsetMode, PmxModelSource,
+    PmxModelVertexLayoutElement, PmxModelVertexLayoutElementKind, Resource, ResourceKind,
+    TextureElement, TextureElementSamplingMode, TextureElementSize, TextureElementTextureFormat,
     TextureElementWrappingMode, TextureKind, TextureSource,
 };
 use serde::Deserialize;
 use std::{
-    collections::{BTreeMap, BTreeSet, HashMap},
-    mem::size_of,
-    path::Path,
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    mem::{size_of, size_of_val},
+    ops::Range,
+    path::{Path, PathBuf},
 };
 use wgpu_types::{AddressMode, FilterMode};
 use zerocopy::{ByteOrder, LittleEndian};
@@ -30,11 +35,52 @@ use zerocopy::{ByteOrder, LittleEndian};
 #[derive(Deserialize, Debug, Clone)]
 pub struct PmxModelMetadata {
     pub material_descriptions: BTreeMap<String, PmxModelMaterialDescription>,
+    /// The color space the material color uniforms are emitted in. Defaults to `Srgb`.
+    pub color_space: Option<PmxModelColorSpace>,
+    /// The `max_texture_dimension_2d` of the target devices, which the morph textures are packed
+    /// to fit. Defaults to [`DEFAULT_MAX_TEXTURE_SIZE`].
+    pub max_texture_size: Option<u32>,
+    /// Indices of the additional vec4s bound as extra UV channels; the xy of the `n`th listed
+    /// vec4 becomes the channel `n + 1`, read by the shaders as `uv_1_`, `uv_2_` and so on.
+    /// UV morphs do not affect them. Defaults to none.
+    pub uv_channels: Option<Vec<u8>>,
+    /// Also emits a static mesh of each material, named `<model>/mesh:<material>`, with the
+    /// positions, normals and UVs of the vertices it draws, e.g. for props that are neither
+    /// skinned nor morphed. Defaults to `false`.
+    pub static_meshes: Option<bool>,
+}
+
+/// The maximum texture size guaranteed by the downlevel limits of wgpu.
+pub const DEFAULT_MAX_TEXTURE_SIZE: u32 = 2048;
+
+/// PMX material colors are authored in sRGB.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PmxModelColorSpace {
+    /// Keeps the colors as authored.
+    Srgb,
+    /// Converts the colors into linear space, for shaders that do lighting in linear space.
+    Linear,
 }
 
+/// The size of a vertex of the static meshes: a position, a normal and a UV.
+const STATIC_MESH_VERTEX_STRIDE: usize = 32;
+
+const STANDARD_SKINNING: &str = include_str!("../../assets/standard-skinning.wgsl");
+
 #[derive(Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct PmxModelMaterialDescription {
     pub render_type: MaterialRenderType,
+    /// How the surfaces of the material are shaded. Defaults to `Smooth`.
+    pub shading: Option<PmxModelMaterialShading>,
+}
+
+/// PMX models are authored smooth-shaded, with vertices shared between the faces.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PmxModelMaterialShading {
+    /// Keeps the vertices and normals as authored.
+    Smooth,
+    /// Gives each face its own vertices with the face normal, for hard edges.
+    Flat,
 }
 
 pub struct PmxModelProcessor;
@@ -52,293 +98,450 @@ impl Processor for PmxModelProcessor {
             Pmx::parse(&content)?
         };
 
-        let full_shader_name = format!("{}/shader:{}", pmx.header.model_name_local, "standard");
-        let no_toon_shader_name = format!(
-            "{}/shader:{}",
-            pmx.header.model_name_local, "standard-no-toon"
-        );
-        let no_env_shader_name = format!(
-            "{}/shader:{}",
-            pmx.header.model_name_local, "standard-no-env"
-        );
-        let no_toon_no_env_shader_name = format!(
-            "{}/shader:{}",
-            pmx.header.model_name_local, "standard-no-toon-no-env"
-        );
+        // the compiler tolerates these, e.g. by clamping the material ranges, but they are
+        // likely to show up as broken meshes or deforms
+        if let Err(errors) = pmx.validate() {
+            for error in errors {
+                warn!("`{}` is malformed: {}", file.display(), error);
+            }
+        }
 
-        let pmx_material_namer = |pmx_material: &PmxMaterial| -> String {
-            format!(
-                "{}/material:{}",
-                pmx.header.model_name_local, pmx_material.name_local
-            )
-        };
-        let pmx_shader_namer =
-            |_pmx_material: &PmxMaterial, toon_enabled: bool, env_enabled: bool| -> String {
-                match (toon_enabled, env_enabled) {
-                    (true, true) => full_shader_name.clone(),
-                    (true, false) => no_env_shader_name.clone(),
-                    (false, true) => no_toon_shader_name.clone(),
-                    (false, false) => no_toon_no_env_shader_name.clone(),
-                }
-            };
-        let pmx_texture_namer = |pmx_texture: &PmxTexture| -> String {
-            format!(
-                "{}/texture:{}",
-                pmx.header.model_name_local, pmx_texture.path
-            )
+        process_pmx(file, &pmx, metadata, default_thread_count())
+    }
+
+    /// The textures of the model and the internal toon textures, which are looked up next to it.
+    fn dependencies(file: &Path) -> Result<Vec<PathBuf>, AnyError> {
+        let pmx = {
+            let content = std::fs::read(file)?;
+            Pmx::parse(&content)?
         };
-        let pmx_internal_toon_texture_namer = |index: u8| -> String {
-            format!(
-                "{}/toon_texture:toon{:0>2}.bmp",
-                pmx.header.model_name_local, index
-            )
+        let parent_path = match file.parent() {
+            Some(parent_path) => parent_path,
+            None => {
+                return Ok(vec![]);
+            }
         };
 
-        let morph_data = make_morph_data(
-            &pmx.header.model_name_local,
-            pmx.vertices.len() as u32,
-            &pmx.morphs,
-        );
-        let vertex_morph_index_texture_name = format!(
-            "{}/morph-texture:{}",
-            pmx.header.model_name_local, "vertex-morph-index"
-        );
-        let uv_morph_index_texture_name = format!(
-            "{}/morph-texture:{}",
-            pmx.header.model_name_local, "uv-morph-index"
-        );
-        let vertex_displacement_texture_name = format!(
-            "{}/morph-texture:{}",
-            pmx.header.model_name_local, "vertex-displacement"
-        );
-        let uv_displacement_texture_name = format!(
-            "{}/morph-texture:{}",
-            pmx.header.model_name_local, "uv-displacement"
+        let mut dependencies = Vec::from_iter(
+            pmx.textures
+                .iter()
+                .map(|pmx_texture| texture_path(parent_path, pmx_texture)),
         );
+        dependencies.extend((1..10).map(|index| internal_toon_texture_path(parent_path, index)));
+        Ok(dependencies)
+    }
+}
 
-        let (vertex_data, vertex_layout) =
-            make_vertex_data(&pmx.vertices, morph_data.vertex_attributes);
-        let (index_data, index_kind, elements) =
-            make_index_data(pmx_material_namer, &pmx.materials, &pmx.indices);
-
-        let pmx_bones = make_bone_data(&pmx.bones);
-
-        let pmx_model = PmxModelSource::new(
-            vertex_data,
-            vertex_layout,
-            index_data,
-            index_kind,
-            elements,
-            morph_data.morphs,
-            pmx_bones,
-            vertex_morph_index_texture_name.clone(),
-            uv_morph_index_texture_name.clone(),
-            vertex_displacement_texture_name.clone(),
-            uv_displacement_texture_name.clone(),
-        );
-        let pmx_model_resource = Resource {
-            name: pmx.header.model_name_local.clone(),
-            kind: ResourceKind::PmxModel(pmx_model),
+/// Compiles the parsed PMX into resources. Empty sections (no vertices, materials, morphs, ...)
+/// compile into empty but valid resources. The textures are decoded on up to `thread_count`
+/// threads; the output does not depend on it. Fails if the morphs do not fit in textures of the
+/// maximum texture size.
+fn process_pmx(
+    file: &Path,
+    pmx: &Pmx,
+    metadata: Option<&PmxModelMetadata>,
+    thread_count: usize,
+) -> Result<Vec<Resource>, AnyError> {
+    let model_name = sanitize_resource_name(&pmx.header.model_name_local);
+    let full_shader_name = format!("{}/shader:{}", model_name, "standard");
+    let no_toon_shader_name = format!("{}/shader:{}", model_name, "standard-no-toon");
+    let no_env_shader_name = format!("{}/shader:{}", model_name, "standard-no-env");
+    let no_toon_no_env_shader_name = format!("{}/shader:{}", model_name, "standard-no-toon-no-env");
+
+    let material_names = make_material_names(&pmx.materials);
+    let pmx_shader_namer =
+        |_pmx_material: &PmxMaterial, toon_enabled: bool, env_enabled: bool| -> String {
+            match (toon_enabled, env_enabled) {
+                (true, true) => full_shader_name.clone(),
+                (true, false) => no_env_shader_name.clone(),
+                (false, true) => no_toon_shader_name.clone(),
+                (false, false) => no_toon_no_env_shader_name.clone(),
+            }
         };
-
-        let mut materials = Vec::with_capacity(pmx.materials.len());
-
-        for pmx_material in &pmx.materials {
-            let render_type = metadata
+    // textures that resolve to the same file share the name of the first of them, so that the
+    // file is decoded once and the materials reference a single resource
+    let mut shared_texture_names = HashMap::<PathBuf, String>::new();
+    let texture_names = pmx
+        .textures
+        .iter()
+        .map(|pmx_texture| {
+            let name = shared_texture_names
+                .entry(canonical_texture_path(file, pmx_texture))
+                .or_insert_with(|| {
+                    format!(
+                        "{}/texture:{}",
+                        model_name,
+                        sanitize_resource_name(&pmx_texture.path)
+                    )
+                });
+            (pmx_texture.path.as_str(), name.clone())
+        })
+        .collect::<HashMap<_, _>>();
+    let pmx_texture_namer =
+        |pmx_texture: &PmxTexture| -> String { texture_names[pmx_texture.path.as_str()].clone() };
+    let pmx_internal_toon_texture_namer =
+        |index: u8| -> String { format!("{}/toon_texture:toon{:0>2}.bmp", model_name, index) };
+
+    let morph_data = make_morph_data(
+        &model_name,
+        pmx.vertices.len() as u32,
+        pmx.materials.len() as u32,
+        &pmx.morphs,
+        &MorphTextureConfigs::default(),
+        metadata
+            .and_then(|metadata| metadata.max_texture_size)
+            .unwrap_or(DEFAULT_MAX_TEXTURE_SIZE),
+    )?;
+    let vertex_morph_index_texture_name =
+        format!("{}/morph-texture:{}", model_name, "vertex-morph-index");
+    let uv_morph_index_texture_name = format!("{}/morph-texture:{}", model_name, "uv-morph-index");
+    let vertex_displacement_texture_name =
+        format!("{}/morph-texture:{}", model_name, "vertex-displacement");
+    let uv_displacement_texture_name =
+        format!("{}/morph-texture:{}", model_name, "uv-displacement");
+
+    let mut vertices = Cow::Borrowed(pmx.vertices.as_slice());
+    let mut vertex_attributes = morph_data.vertex_attributes;
+    let mut vertex_indices = pmx
+        .indices
+        .vertex_indices
+        .iter()
+        .map(|index| index.get())
+        .collect::<Vec<_>>();
+    let flat_shaded_index_ranges = pmx
+        .materials
+        .iter()
+        .zip(material_index_ranges(
+            &pmx.materials,
+            vertex_indices.len() as u32,
+        ))
+        .filter(|(pmx_material, _)| {
+            metadata
                 .and_then(|metadata| metadata.material_descriptions.get(&pmx_material.name_local))
-                .map(|description| description.render_type)
-                .unwrap_or(MaterialRenderType::Opaque);
-
-            let source = make_material_source(
-                pmx_shader_namer,
-                pmx_texture_namer,
-                pmx_internal_toon_texture_namer,
-                render_type,
-                pmx_material,
-                &pmx.textures,
-                &vertex_morph_index_texture_name,
-                &uv_morph_index_texture_name,
-                &vertex_displacement_texture_name,
-                &uv_displacement_texture_name,
-            );
-            let resource = Resource {
-                name: pmx_material_namer(pmx_material),
-                kind: ResourceKind::Material(source),
-            };
-
-            materials.push(resource);
-        }
-
-        let mut textures = Vec::with_capacity(pmx.textures.len() + 10);
-
-        for pmx_texture in &pmx.textures {
-            let source = match make_texture_source(file, pmx_texture) {
-                Ok(source) => source,
-                Err(err) => {
-                    error!(
-                        "failed to process texture `{}`; it will be ignored: {}",
-                        pmx_texture.path, err
-                    );
-                    continue;
-                }
-            };
-            let resource = Resource {
-                name: pmx_texture_namer(pmx_texture),
-                kind: ResourceKind::Texture(source),
-            };
-
-            textures.push(resource);
-        }
+                .and_then(|description| description.shading)
+                == Some(PmxModelMaterialShading::Flat)
+        })
+        .map(|(_, (start, end))| start as usize..end as usize)
+        .collect::<Vec<_>>();
+
+    if !flat_shaded_index_ranges.is_empty() {
+        unshare_flat_shaded_vertices(
+            vertices.to_mut(),
+            &mut vertex_attributes,
+            &mut vertex_indices,
+            flat_shaded_index_ranges,
+        );
+    }
 
-        for index in 1..10 {
-            let source = match make_internal_toon_texture_source(file, index) {
-                Ok(source) => source,
-                Err(err) => {
-                    error!(
-                        "failed to process internal toon texture index `{}`; it will be ignored: {}",
-                        index, err
-                    );
-                    continue;
-                }
-            };
-            let resource = Resource {
-                name: pmx_internal_toon_texture_namer(index),
-                kind: ResourceKind::Texture(source),
-            };
+    let uv_channels = metadata
+        .and_then(|metadata| metadata.uv_channels.as_deref())
+        .unwrap_or_default();
 
-            textures.push(resource);
+    for &additional_vec4_index in uv_channels {
+        if pmx.header.config.additional_vec4_count <= additional_vec4_index as usize {
+            return Err(anyhow!(
+                "for the PMX model `{}`, the additional vec4 {} cannot be a UV channel; the model has {} additional vec4s",
+                model_name,
+                additional_vec4_index,
+                pmx.header.config.additional_vec4_count
+            ));
         }
+    }
 
-        let full_shader_content = include_str!("../../assets/standard-full.wgsl");
-        let full_shader_source = ShaderProcessor::generate_shader_resource_from_wsgl_content(
-            &full_shader_name,
-            full_shader_content.to_owned(),
-            &BTreeSet::from_iter(vec![
-                "vertex_displacement_texture".to_owned(),
-                "uv_displacement_texture".to_owned(),
-            ]),
-        );
+    let tangents = make_vertex_tangents(&vertices, &vertex_indices);
+    let (vertex_data, vertex_layout) = make_vertex_data(
+        &vertices,
+        &tangents,
+        pmx.header.config.additional_vec4_count,
+        uv_channels,
+        vertex_attributes,
+    );
+    let (index_data, index_kind, elements) = make_index_data(
+        &model_name,
+        &material_names,
+        &pmx.materials,
+        &vertex_indices,
+    );
+    let meshes = if metadata
+        .and_then(|metadata| metadata.static_meshes)
+        .unwrap_or(false)
+    {
+        let index_ranges = Vec::from_iter(elements.iter().map(|element| element.index_range));
+
+        material_names
+            .iter()
+            .zip(split_pmx(&index_ranges, &vertices, &vertex_indices))
+            .map(|(material_name, source)| Resource {
+                name: format!("{}/mesh:{}", model_name, material_name),
+                kind: ResourceKind::Mesh(source),
+                metadata: BTreeMap::new(),
+            })
+            .collect()
+    } else {
+        vec![]
+    };
 
-        let no_toon_shader_content = include_str!("../../assets/standard-no-toon.wgsl");
-        let no_toon_shader_source = ShaderProcessor::generate_shader_resource_from_wsgl_content(
-            &no_toon_shader_name,
-            no_toon_shader_content.to_owned(),
-            &BTreeSet::from_iter(vec![
-                "vertex_displacement_texture".to_owned(),
-                "uv_displacement_texture".to_owned(),
-            ]),
-        );
+    let pmx_bones = make_bone_data(&pmx.bones);
+    let inverse_bind_matrices = make_inverse_bind_matrices(&pmx_bones);
+
+    let pmx_model = PmxModelSource::new(
+        vertex_data,
+        vertex_layout,
+        index_data,
+        index_kind,
+        elements,
+        morph_data.morphs,
+        pmx_bones,
+        inverse_bind_matrices,
+        vertex_morph_index_texture_name.clone(),
+        uv_morph_index_texture_name.clone(),
+        vertex_displacement_texture_name.clone(),
+        uv_displacement_texture_name.clone(),
+    );
+    let pmx_model_resource = Resource {
+        name: model_name.clone(),
+        kind: ResourceKind::PmxModel(pmx_model),
+        metadata: BTreeMap::new(),
+    };
 
-        let no_env_shader_content = include_str!("../../assets/standard-no-env.wgsl");
-        let no_env_shader_source = ShaderProcessor::generate_shader_resource_from_wsgl_content(
-            &no_env_shader_name,
-            no_env_shader_content.to_owned(),
-            &BTreeSet::from_iter(vec![
-                "vertex_displacement_texture".to_owned(),
-                "uv_displacement_texture".to_owned(),
-            ]),
+    let color_space = metadata
+        .and_then(|metadata| metadata.color_space)
+        .unwrap_or(PmxModelColorSpace::Srgb);
+    let mut materials = Vec::with_capacity(pmx.materials.len());
+
+    for (pmx_material, material_name) in pmx.materials.iter().zip(&material_names) {
+        let render_type = metadata
+            .and_then(|metadata| metadata.material_descriptions.get(&pmx_material.name_local))
+            .map(|description| description.render_type)
+            .unwrap_or(MaterialRenderType::Opaque);
+
+        let source = make_material_source(
+            pmx_shader_namer,
+            pmx_texture_namer,
+            pmx_internal_toon_texture_namer,
+            render_type,
+            color_space,
+            pmx_material,
+            &pmx.textures,
+            &vertex_morph_index_texture_name,
+            &uv_morph_index_texture_name,
+            &vertex_displacement_texture_name,
+            &uv_displacement_texture_name,
         );
+        let resource = Resource {
+            name: format!("{}/material:{}", model_name, material_name),
+            kind: ResourceKind::Material(source),
+            metadata: original_name_metadata(material_name, [pmx_material.name_local.as_str()]),
+        };
 
-        let no_toon_no_env_shader_content =
-            include_str!("../../assets/standard-no-toon-no-env.wgsl");
-        let no_toon_no_env_shader_source =
-            ShaderProcessor::generate_shader_resource_from_wsgl_content(
-                &no_toon_no_env_shader_name,
-                no_toon_no_env_shader_content.to_owned(),
-                &BTreeSet::from_iter(vec![
-                    "vertex_displacement_texture".to_owned(),
-                    "uv_displacement_texture".to_owned(),
-                ]),
-            );
+        materials.push(resource);
+    }
 
-        let mut resources = Vec::with_capacity(2 + pmx.materials.len() + pmx.textures.len());
+    let mut textures = Vec::with_capacity(pmx.textures.len() + 10);
+    let mut emitted_texture_names = BTreeSet::new();
+    let unique_textures = pmx
+        .textures
+        .iter()
+        .filter(|pmx_texture| emitted_texture_names.insert(pmx_texture_namer(pmx_texture)))
+        .collect::<Vec<_>>();
+    let texture_sources = parallel_map(&unique_textures, thread_count, |pmx_texture| {
+        make_texture_source(file, pmx_texture)
+    });
 
-        match full_shader_source {
-            Ok(source) => {
-                let resource = Resource {
-                    name: full_shader_name,
-                    kind: ResourceKind::Shader(source),
-                };
-                resources.push(resource);
-            }
+    for (pmx_texture, source) in unique_textures.into_iter().zip(texture_sources) {
+        let source = match source {
+            Ok(source) => source,
             Err(err) => {
                 error!(
-                    "failed to process shader `{}`; it will be ignored: {}",
-                    full_shader_name, err
+                    "failed to process texture `{}`; it will be ignored: {}",
+                    pmx_texture.path, err
                 );
+                continue;
             }
-        }
+        };
+        let name = pmx_texture_namer(pmx_texture);
+        // all the paths that share the resource, so that each of them can be looked up again
+        let metadata = original_name_metadata(
+            &sanitize_resource_name(&pmx_texture.path),
+            pmx.textures
+                .iter()
+                .filter(|other| pmx_texture_namer(other) == name)
+                .map(|other| other.path.as_str()),
+        );
+        let resource = Resource {
+            name,
+            kind: ResourceKind::Texture(source),
+            metadata,
+        };
 
-        match no_toon_shader_source {
-            Ok(source) => {
-                let resource = Resource {
-                    name: no_toon_shader_name,
-                    kind: ResourceKind::Shader(source),
-                };
-                resources.push(resource);
-            }
-            Err(err) => {
-                error!(
-                    "failed to process shader `{}`; it will be ignored: {}",
-                    no_toon_shader_name, err
-                );
-            }
-        }
+        textures.push(resource);
+    }
 
-        match no_env_shader_source {
-            Ok(source) => {
-                let resource = Resource {
-                    name: no_env_shader_name,
-                    kind: ResourceKind::Shader(source),
-                };
-                resources.push(resource);
-            }
+    let internal_toon_indices = (1..10).collect::<Vec<u8>>();
+    let internal_toon_texture_sources =
+        parallel_map(&internal_toon_indices, thread_count, |&index| {
+            make_internal_toon_texture_source(file, index)
+        });
+
+    for (index, source) in internal_toon_indices
+        .into_iter()
+        .zip(internal_toon_texture_sources)
+    {
+        let source = match source {
+            Ok(source) => source,
             Err(err) => {
                 error!(
-                    "failed to process shader `{}`; it will be ignored: {}",
-                    no_env_shader_name, err
+                    "failed to process internal toon texture index `{}`; it will be ignored: {}",
+                    index, err
                 );
+                continue;
             }
+        };
+        let resource = Resource {
+            name: pmx_internal_toon_texture_namer(index),
+            kind: ResourceKind::Texture(source),
+            metadata: BTreeMap::new(),
+        };
+
+        textures.push(resource);
+    }
+
+    let full_shader_content = include_str!("../../assets/standard-full.wgsl");
+    let full_shader_source = ShaderProcessor::generate_shader_resource_from_wsgl_content(
+        &full_shader_name,
+        expand_standard_shader_content(full_shader_content),
+        &BTreeSet::from_iter(vec![
+            "vertex_displacement_texture".to_owned(),
+            "uv_displacement_texture".to_owned(),
+        ]),
+    );
+
+    let no_toon_shader_content = include_str!("../../assets/standard-no-toon.wgsl");
+    let no_toon_shader_source = ShaderProcessor::generate_shader_resource_from_wsgl_content(
+        &no_toon_shader_name,
+        expand_standard_shader_content(no_toon_shader_content),
+        &BTreeSet::from_iter(vec![
+            "vertex_displacement_texture".to_owned(),
+            "uv_displacement_texture".to_owned(),
+        ]),
+    );
+
+    let no_env_shader_content = include_str!("../../assets/standard-no-env.wgsl");
+    let no_env_shader_source = ShaderProcessor::generate_shader_resource_from_wsgl_content(
+        &no_env_shader_name,
+        expand_standard_shader_content(no_env_shader_content),
+        &BTreeSet::from_iter(vec![
+            "vertex_displacement_texture".to_owned(),
+            "uv_displacement_texture".to_owned(),
+        ]),
+    );
+
+    let no_toon_no_env_shader_content = include_str!("../../assets/standard-no-toon-no-env.wgsl");
+    let no_toon_no_env_shader_source = ShaderProcessor::generate_shader_resource_from_wsgl_content(
+        &no_toon_no_env_shader_name,
+        expand_standard_shader_content(no_toon_no_env_shader_content),
+        &BTreeSet::from_iter(vec![
+            "vertex_displacement_texture".to_owned(),
+            "uv_displacement_texture".to_owned(),
+        ]),
+    );
+
+    let mut resources = Vec::with_capacity(2 + pmx.materials.len() + pmx.textures.len());
+
+    match full_shader_source {
+        Ok(source) => {
+            let resource = Resource {
+                name: full_shader_name,
+                kind: ResourceKind::Shader(source),
+                metadata: BTreeMap::new(),
+            };
+            resources.push(resource);
         }
+        Err(err) => {
+            error!(
+                "failed to process shader `{}`; it will be ignored: {}",
+                full_shader_name, err
+            );
+        }
+    }
 
-        match no_toon_no_env_shader_source {
-            Ok(source) => {
-                let resource = Resource {
-                    name: no_toon_no_env_shader_name,
-                    kind: ResourceKind::Shader(source),
-                };
-                resources.push(resource);
-            }
-            Err(err) => {
-                error!(
-                    "failed to process shader `{}`; it will be ignored: {}",
-                    no_toon_no_env_shader_name, err
-                );
-            }
+    match no_toon_shader_source {
+        Ok(source) => {
+            let resource = Resource {
+                name: no_toon_shader_name,
+                kind: ResourceKind::Shader(source),
+                metadata: BTreeMap::new(),
+            };
+            resources.push(resource);
+        }
+        Err(err) => {
+            error!(
+                "failed to process shader `{}`; it will be ignored: {}",
+                no_toon_shader_name, err
+            );
         }
+    }
 
-        resources.push(pmx_model_resource);
-        resources.push(Resource {
-            name: vertex_morph_index_texture_name,
-            kind: ResourceKind::Texture(morph_data.vertex_morph_index_texture_source),
-        });
-        resources.push(Resource {
-            name: uv_morph_index_texture_name,
-            kind: ResourceKind::Texture(morph_data.uv_morph_index_texture_source),
-        });
-        resources.push(Resource {
-            name: vertex_displacement_texture_name,
-            kind: ResourceKind::Texture(morph_data.vertex_displacement_texture_source),
-        });
-        resources.push(Resource {
-            name: uv_displacement_texture_name,
-            kind: ResourceKind::Texture(morph_data.uv_displacement_texture_source),
-        });
-        resources.extend(materials);
-        resources.extend(textures);
+    match no_env_shader_source {
+        Ok(source) => {
+            let resource = Resource {
+                name: no_env_shader_name,
+                kind: ResourceKind::Shader(source),
+                metadata: BTreeMap::new(),
+            };
+            resources.push(resource);
+        }
+        Err(err) => {
+            error!(
+                "failed to process shader `{}`; it will be ignored: {}",
+                no_env_shader_name, err
+            );
+        }
+    }
 
-        Ok(resources)
+    match no_toon_no_env_shader_source {
+        Ok(source) => {
+            let resource = Resource {
+                name: no_toon_no_env_shader_name,
+                kind: ResourceKind::Shader(source),
+                metadata: BTreeMap::new(),
+            };
+            resources.push(resource);
+        }
+        Err(err) => {
+            error!(
+                "failed to process shader `{}`; it will be ignored: {}",
+                no_toon_no_env_shader_name, err
+            );
+        }
     }
+
+    resources.push(pmx_model_resource);
+    resources.push(Resource {
+        name: vertex_morph_index_texture_name,
+        kind: ResourceKind::Texture(morph_data.vertex_morph_index_texture_source),
+        metadata: BTreeMap::new(),
+    });
+    resources.push(Resource {
+        name: uv_morph_index_texture_name,
+        kind: ResourceKind::Texture(morph_data.uv_morph_index_texture_source),
+        metadata: BTreeMap::new(),
+    });
+    resources.push(Resource {
+        name: vertex_displacement_texture_name,
+        kind: ResourceKind::Texture(morph_data.vertex_displacement_texture_source),
+        metadata: BTreeMap::new(),
+    });
+    resources.push(Resource {
+        name: uv_displacement_texture_name,
+        kind: ResourceKind::Texture(morph_data.uv_displacement_texture_source),
+        metadata: BTreeMap::new(),
+    });
+    resources.extend(meshes);
+    resources.extend(materials);
+    resources.extend(textures);
+
+    Ok(resources)
 }
 
 struct MorphData {
@@ -358,7 +561,176 @@ struct MorphVertexAttribute {
     pub uv_morph_count: u32,
 }
 
-fn make_morph_data(pmx_name: &str, vertex_count: u32, pmx_morphs: &[PmxMorph]) -> MorphData {
+/// Prepends the skinning shared by the standard shaders to the given one of them.
+pub(crate) fn expand_standard_shader_content(content: &str) -> String {
+    format!("{}\n{}", STANDARD_SKINNING, content)
+}
+
+const VERTEX_MORPH_INDEX_TEXTURE_FORMAT: TextureElementTextureFormat =
+    TextureElementTextureFormat::RG32Uint;
+const UV_MORPH_INDEX_TEXTURE_FORMAT: TextureElementTextureFormat =
+    TextureElementTextureFormat::RGBA32Uint;
+const DISPLACEMENT_TEXTURE_FORMAT: TextureElementTextureFormat =
+    TextureElementTextureFormat::RGBA32Float;
+/// The morph textures are looked up by texel index, so they are never filtered by default.
+const MORPH_TEXTURE_SAMPLING_MODE: TextureElementSamplingMode = TextureElementSamplingMode::Point;
+const MORPH_TEXTURE_WRAPPING_MODE: TextureElementWrappingMode = TextureElementWrappingMode::Clamp;
+
+/// How a morph texture is stored and sampled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct MorphTextureConfig {
+    texture_format: TextureElementTextureFormat,
+    sampling_mode: TextureElementSamplingMode,
+    wrapping_mode: TextureElementWrappingMode,
+}
+
+impl MorphTextureConfig {
+    /// Fails if an integer format is not point sampled, as integer textures cannot be filtered.
+    fn new(
+        texture_format: TextureElementTextureFormat,
+        sampling_mode: TextureElementSamplingMode,
+        wrapping_mode: TextureElementWrappingMode,
+    ) -> Result<Self, AnyError> {
+        if texture_format.is_integer() && sampling_mode != TextureElementSamplingMode::Point {
+            return Err(anyhow!(
+                "the integer texture format `{:?}` cannot be sampled with `{:?}`; it must be `Point`",
+                texture_format,
+                sampling_mode
+            ));
+        }
+
+        Ok(Self {
+            texture_format,
+            sampling_mode,
+            wrapping_mode,
+        })
+    }
+
+    /// Returns the size in bytes of the texels of a texture of the size.
+    fn data_size(&self, (width, height): (u32, u32)) -> usize {
+        let bytes_per_texel = self
+            .texture_format
+            .bytes_per_texel()
+            .expect("the morph texture formats are not block-compressed");
+        width as usize * height as usize * bytes_per_texel
+    }
+
+    /// Makes the texture, padding the texels with zeros up to the size.
+    fn make_texture_source(&self, mut data: Vec<u8>, (width, height): (u32, u32)) -> TextureSource {
+        let data_size = self.data_size((width, height));
+        debug_assert!(data.len() <= data_size);
+        data.resize(data_size, 0);
+
+        TextureSource::new(TextureKind::Single(TextureElement {
+            data,
+            size: TextureElementSize {
+                width: width as u16,
+                height: height as u16,
+            },
+            texture_format: self.texture_format,
+            sampling_mode: self.sampling_mode,
+            wrapping_mode_u: self.wrapping_mode,
+            wrapping_mode_v: self.wrapping_mode,
+            mip_data: vec![],
+        }))
+    }
+}
+
+/// Configs of the four morph textures made by [`make_morph_data`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct MorphTextureConfigs {
+    vertex_morph_index: MorphTextureConfig,
+    uv_morph_index: MorphTextureConfig,
+    vertex_displacement: MorphTextureConfig,
+    uv_displacement: MorphTextureConfig,
+}
+
+impl MorphTextureConfigs {
+    /// Fails if a format does not fit the texels packed for that texture.
+    fn new(
+        vertex_morph_index: MorphTextureConfig,
+        uv_morph_index: MorphTextureConfig,
+        vertex_displacement: MorphTextureConfig,
+        uv_displacement: MorphTextureConfig,
+    ) -> Result<Self, AnyError> {
+        let textures = [
+            (
+                "vertex morph index",
+                vertex_morph_index,
+                size_of::<[u32; 2]>(),
+                true,
+            ),
+            (
+                "uv morph index",
+                uv_morph_index,
+                size_of::<[u32; 4]>(),
+                true,
+            ),
+            (
+                "vertex displacement",
+                vertex_displacement,
+                size_of::<[f32; 4]>(),
+                false,
+            ),
+            (
+                "uv displacement",
+                uv_displacement,
+                size_of::<[f32; 4]>(),
+                false,
+            ),
+        ];
+
+        for (name, config, texel_size, is_integer) in textures {
+            if config.texture_format.bytes_per_texel() != Some(texel_size)
+                || config.texture_format.is_integer() != is_integer
+            {
+                return Err(anyhow!(
+                    "the texture format `{:?}` does not fit the texels of the {} texture",
+                    config.texture_format,
+                    name
+                ));
+            }
+        }
+
+        Ok(Self {
+            vertex_morph_index,
+            uv_morph_index,
+            vertex_displacement,
+            uv_displacement,
+        })
+    }
+}
+
+impl Default for MorphTextureConfigs {
+    fn default() -> Self {
+        let config = |texture_format| {
+            MorphTextureConfig::new(
+                texture_format,
+                MORPH_TEXTURE_SAMPLING_MODE,
+                MORPH_TEXTURE_WRAPPING_MODE,
+            )
+            .unwrap()
+        };
+
+        // the defaults always fit the texels, so this never fails
+        Self::new(
+            config(VERTEX_MORPH_INDEX_TEXTURE_FORMAT),
+            config(UV_MORPH_INDEX_TEXTURE_FORMAT),
+            config(DISPLACEMENT_TEXTURE_FORMAT),
+            config(DISPLACEMENT_TEXTURE_FORMAT),
+        )
+        .unwrap()
+    }
+}
+
+fn make_morph_data(
+    pmx_name: &str,
+    vertex_count: u32,
+    material_count: u32,
+    pmx_morphs: &[PmxMorph],
+    texture_configs: &MorphTextureConfigs,
+    max_texture_size: u32,
+) -> Result<MorphData, AnyError> {
     let mut morphs = Vec::with_capacity(pmx_morphs.len());
 
     /// Encoded as texture format `RG32U`
@@ -407,6 +779,8 @@ fn make_morph_data(pmx_name: &str, vertex_count: u32, pmx_morphs: &[PmxMorph]) -
                 PmxModelMorphKind::Group(group_elements)
             }
             PmxMorphOffset::Vertex(vertices) => {
+                let displacement_start = vertex_displacements.len() as u32;
+
                 for vertex in vertices {
                     let vertex_index = vertex.index.get();
 
@@ -425,10 +799,14 @@ fn make_morph_data(pmx_name: &str, vertex_count: u32, pmx_morphs: &[PmxMorph]) -
                     ));
                 }
 
-                PmxModelMorphKind::Vertex
+                PmxModelMorphKind::Vertex {
+                    displacement_range: (displacement_start, vertex_displacements.len() as u32),
+                }
             }
             PmxMorphOffset::Bone(_) => PmxModelMorphKind::Bone,
             PmxMorphOffset::Uv { offsets, uv_index } => {
+                let displacement_start = uv_displacements.len() as u32;
+
                 for uv in offsets {
                     let vertex_index = uv.index.get();
 
@@ -444,19 +822,23 @@ fn make_morph_data(pmx_name: &str, vertex_count: u32, pmx_morphs: &[PmxMorph]) -
                     uv_displacements.push(Vec4::new(uv.vec4.x, uv.vec4.y, uv.vec4.z, uv.vec4.w));
                 }
 
-                PmxModelMorphKind::Uv
+                PmxModelMorphKind::Uv {
+                    displacement_range: (displacement_start, uv_displacements.len() as u32),
+                }
             }
             PmxMorphOffset::Material(elements) => {
                 let mut material_elements = Vec::with_capacity(elements.len());
 
                 for element in elements {
+                    // a negative index targets all materials
                     let material_index = element.index.get();
-                    let material_index =
-                        if material_index < 0 || pmx_morphs.len() <= material_index as usize {
-                            None
-                        } else {
-                            Some(material_index as u32)
-                        };
+                    let material_index = if material_index < 0 {
+                        None
+                    } else if material_count <= material_index as u32 {
+                        continue;
+                    } else {
+                        Some(material_index as u32)
+                    };
 
                     material_elements.push(PmxModelMorphMaterialElement {
                         material_index,
@@ -546,8 +928,14 @@ fn make_morph_data(pmx_name: &str, vertex_count: u32, pmx_morphs: &[PmxMorph]) -
     let mut vertex_morph_indices = Vec::new();
     let mut uv_morph_indices = Vec::new();
 
+    // morph offsets of vertices that do not exist are dropped
     for (vertex_index, morph_indices) in vertex_morph_index_map {
-        let attribute = &mut vertex_attributes[vertex_index as usize];
+        let attribute = match vertex_attributes.get_mut(vertex_index as usize) {
+            Some(attribute) => attribute,
+            None => {
+                continue;
+            }
+        };
 
         attribute.vertex_morph_index_start = vertex_morph_indices.len() as u32;
         attribute.vertex_morph_count = morph_indices.len() as u32;
@@ -556,7 +944,12 @@ fn make_morph_data(pmx_name: &str, vertex_count: u32, pmx_morphs: &[PmxMorph]) -
     }
 
     for (vertex_index, morph_indices) in uv_morph_index_map {
-        let attribute = &mut vertex_attributes[vertex_index as usize];
+        let attribute = match vertex_attributes.get_mut(vertex_index as usize) {
+            Some(attribute) => attribute,
+            None => {
+                continue;
+            }
+        };
 
         attribute.uv_morph_index_start = uv_morph_indices.len() as u32;
         attribute.uv_morph_count = morph_indices.len() as u32;
@@ -565,60 +958,49 @@ fn make_morph_data(pmx_name: &str, vertex_count: u32, pmx_morphs: &[PmxMorph]) -
     }
 
     let vertex_morph_index_texture_size =
-        ((vertex_morph_indices.len() as f32).sqrt().ceil() as u32).max(1);
-    let uv_morph_index_texture_size = ((uv_morph_indices.len() as f32).sqrt().ceil() as u32).max(1);
+        morph_texture_size(vertex_morph_indices.len(), max_texture_size);
+    let uv_morph_index_texture_size = morph_texture_size(uv_morph_indices.len(), max_texture_size);
     let vertex_displacement_texture_size =
-        ((vertex_displacements.len() as f32).sqrt().ceil() as u32).max(1);
-    let uv_displacement_texture_size =
-        ((uv_displacements.len() as f32).sqrt().ceil() as u32).max(1);
-
-    if 2048 < vertex_morph_index_texture_size {
-        warn!(
-            "for the PMX model `{}`, vertex morph index texture size `{}` exceeds the maximum texture size of 2048; it may not be able to be used as a texture",
-            pmx_name,
-            vertex_morph_index_texture_size
-        );
-    }
-
-    if 2048 < uv_morph_index_texture_size {
-        warn!(
-            "for the PMX model `{}`, uv morph index texture size `{}` exceeds the maximum texture size of 2048; it may not be able to be used as a texture",
-            pmx_name,
-            uv_morph_index_texture_size
-        );
-    }
-
-    if 2048 < vertex_displacement_texture_size {
-        warn!(
-            "for the PMX model `{}`, vertex displacement texture size `{}` exceeds the maximum texture size of 2048; it may not be able to be used as a texture",
-            pmx_name,
-            vertex_displacement_texture_size
-        );
-    }
-
-    if 2048 < uv_displacement_texture_size {
-        warn!(
-            "for the PMX model `{}`, uv displacement texture size `{}` exceeds the maximum texture size of 2048; it may not be able to be used as a texture",
-            pmx_name,
-            uv_displacement_texture_size
-        );
+        morph_texture_size(vertex_displacements.len(), max_texture_size);
+    let uv_displacement_texture_size = morph_texture_size(uv_displacements.len(), max_texture_size);
+
+    for (texture_name, texture_size) in [
+        ("vertex morph index", vertex_morph_index_texture_size),
+        ("uv morph index", uv_morph_index_texture_size),
+        ("vertex displacement", vertex_displacement_texture_size),
+        ("uv displacement", uv_displacement_texture_size),
+    ] {
+        if !is_morph_texture_size_supported(texture_size, max_texture_size) {
+            return Err(anyhow!(
+                "for the PMX model `{}`, the {} texture size `{}x{}` exceeds the maximum texture size of {}",
+                pmx_name,
+                texture_name,
+                texture_size.0,
+                texture_size.1,
+                max_texture_size
+            ));
+        }
     }
 
     let mut vertex_morph_index_texels = Vec::with_capacity(
-        (vertex_morph_index_texture_size * vertex_morph_index_texture_size) as usize
-            * size_of::<[u32; 2]>(),
+        texture_configs
+            .vertex_morph_index
+            .data_size(vertex_morph_index_texture_size),
     );
     let mut uv_morph_index_texels = Vec::with_capacity(
-        (uv_morph_index_texture_size * uv_morph_index_texture_size) as usize
-            * size_of::<[u32; 4]>(),
+        texture_configs
+            .uv_morph_index
+            .data_size(uv_morph_index_texture_size),
     );
     let mut vertex_displacement_texels = Vec::with_capacity(
-        (vertex_displacement_texture_size * vertex_displacement_texture_size) as usize
-            * size_of::<[f32; 4]>(),
+        texture_configs
+            .vertex_displacement
+            .data_size(vertex_displacement_texture_size),
     );
     let mut uv_displacement_texels = Vec::with_capacity(
-        (uv_displacement_texture_size * uv_displacement_texture_size) as usize
-            * size_of::<[f32; 4]>(),
+        texture_configs
+            .uv_displacement
+            .data_size(uv_displacement_texture_size),
     );
 
     for index in &vertex_morph_indices {
@@ -683,169 +1065,145 @@ fn make_morph_data(pmx_name: &str, vertex_count: u32, pmx_morphs: &[PmxMorph]) -
         uv_displacement_texels.extend(w);
     }
 
-    vertex_morph_index_texels.extend(std::iter::repeat(0u8).take(
-        ((vertex_morph_index_texture_size * vertex_morph_index_texture_size) as usize)
-            * size_of::<[u32; 2]>()
-            - vertex_morph_index_texels.len(),
-    ));
-
-    uv_morph_index_texels.extend(std::iter::repeat(0u8).take(
-        ((uv_morph_index_texture_size * uv_morph_index_texture_size) as usize)
-            * size_of::<[u32; 4]>()
-            - uv_morph_index_texels.len(),
-    ));
-
-    vertex_displacement_texels.extend(std::iter::repeat(0u8).take(
-        ((vertex_displacement_texture_size * vertex_displacement_texture_size) as usize)
-            * size_of::<[u32; 4]>()
-            - vertex_displacement_texels.len(),
-    ));
-
-    uv_displacement_texels.extend(std::iter::repeat(0u8).take(
-        ((uv_displacement_texture_size * uv_displacement_texture_size) as usize)
-            * size_of::<[u32; 4]>()
-            - uv_displacement_texels.len(),
-    ));
-
-    let vertex_morph_index_texture = TextureSource::new(TextureKind::Single(TextureElement {
-        data: vertex_morph_index_texels,
-        size: TextureElementSize {
-            width: vertex_morph_index_texture_size as u16,
-            height: vertex_morph_index_texture_size as u16,
-        },
-        texture_format: TextureElementTextureFormat::RG32Uint,
-        sampling_mode: TextureElementSamplingMode::Point,
-        wrapping_mode_u: TextureElementWrappingMode::Clamp,
-        wrapping_mode_v: TextureElementWrappingMode::Clamp,
-    }));
-
-    let uv_morph_index_texture = TextureSource::new(TextureKind::Single(TextureElement {
-        data: uv_morph_index_texels,
-        size: TextureElementSize {
-            width: uv_morph_index_texture_size as u16,
-            height: uv_morph_index_texture_size as u16,
-        },
-        texture_format: TextureElementTextureFormat::RGBA32Uint,
-        sampling_mode: TextureElementSamplingMode::Point,
-        wrapping_mode_u: TextureElementWrappingMode::Clamp,
-        wrapping_mode_v: TextureElementWrappingMode::Clamp,
-    }));
-
-    let vertex_displacement_texture = TextureSource::new(TextureKind::Single(TextureElement {
-        data: vertex_displacement_texels,
-        size: TextureElementSize {
-            width: vertex_displacement_texture_size as u16,
-            height: vertex_displacement_texture_size as u16,
-        },
-        texture_format: TextureElementTextureFormat::RGBA32Float,
-        sampling_mode: TextureElementSamplingMode::Point,
-        wrapping_mode_u: TextureElementWrappingMode::Clamp,
-        wrapping_mode_v: TextureElementWrappingMode::Clamp,
-    }));
-
-    let uv_displacement_texture = TextureSource::new(TextureKind::Single(TextureElement {
-        data: uv_displacement_texels,
-        size: TextureElementSize {
-            width: uv_displacement_texture_size as u16,
-            height: uv_displacement_texture_size as u16,
-        },
-        texture_format: TextureElementTextureFormat::RGBA32Float,
-        sampling_mode: TextureElementSamplingMode::Point,
-        wrapping_mode_u: TextureElementWrappingMode::Clamp,
-        wrapping_mode_v: TextureElementWrappingMode::Clamp,
-    }));
-
-    MorphData {
+    let vertex_morph_index_texture = texture_configs
+        .vertex_morph_index
+        .make_texture_source(vertex_morph_index_texels, vertex_morph_index_texture_size);
+    let uv_morph_index_texture = texture_configs
+        .uv_morph_index
+        .make_texture_source(uv_morph_index_texels, uv_morph_index_texture_size);
+    let vertex_displacement_texture = texture_configs
+        .vertex_displacement
+        .make_texture_source(vertex_displacement_texels, vertex_displacement_texture_size);
+    let uv_displacement_texture = texture_configs
+        .uv_displacement
+        .make_texture_source(uv_displacement_texels, uv_displacement_texture_size);
+
+    Ok(MorphData {
         morphs,
         vertex_morph_index_texture_source: vertex_morph_index_texture,
         uv_morph_index_texture_source: uv_morph_index_texture,
         vertex_displacement_texture_source: vertex_displacement_texture,
         uv_displacement_texture_source: uv_displacement_texture,
         vertex_attributes,
+    })
+}
+
+/// Returns the width and height of a morph texture holding the given number of texels.
+/// The texels are packed into rows as wide as the maximum texture size, so that only the
+/// height grows with the count; the shaders find a texel by its index and the texture width.
+fn morph_texture_size(texel_count: usize, max_texture_size: u32) -> (u32, u32) {
+    let texel_count = texel_count as u32;
+    let width = texel_count.clamp(1, max_texture_size);
+    let height = texel_count.div_ceil(width).max(1);
+    (width, height)
+}
+
+fn is_morph_texture_size_supported((width, height): (u32, u32), max_texture_size: u32) -> bool {
+    width <= max_texture_size && height <= max_texture_size
+}
+
+/// Number of additional vec4s written per vertex. The standard shaders always read the first
+/// one, so a model declaring none still gets a zeroed slot.
+fn vertex_additional_vec4_count(pmx_additional_vec4_count: usize) -> usize {
+    pmx_additional_vec4_count.clamp(1, 4)
+}
+
+/// Returns the layout of the vertices written by [`make_vertex_data`], in the written order.
+/// The elements are tightly packed four-byte slots; wgpu requires the attribute offsets to be
+/// aligned to `min(4, format size)` and the stride to a multiple of 4, so no padding is needed.
+fn make_vertex_layout(
+    additional_vec4_count: usize,
+    uv_channel_count: usize,
+) -> Vec<PmxModelVertexLayoutElement> {
+    let kinds = [
+        PmxModelVertexLayoutElementKind::Position,
+        PmxModelVertexLayoutElementKind::Normal,
+        PmxModelVertexLayoutElementKind::TexCoord,
+        PmxModelVertexLayoutElementKind::Tangent,
+    ]
+    .into_iter()
+    .chain((0..additional_vec4_count as u8).map(PmxModelVertexLayoutElementKind::AdditionalVec4))
+    .chain((1..=uv_channel_count as u8).map(PmxModelVertexLayoutElementKind::AdditionalTexCoord))
+    .chain([
+        PmxModelVertexLayoutElementKind::DeformKind,
+        PmxModelVertexLayoutElementKind::BoneIndex,
+        PmxModelVertexLayoutElementKind::BoneWeight,
+        PmxModelVertexLayoutElementKind::SdefC,
+        PmxModelVertexLayoutElementKind::SdefR0,
+        PmxModelVertexLayoutElementKind::SdefR1,
+        PmxModelVertexLayoutElementKind::EdgeSize,
+        PmxModelVertexLayoutElementKind::VertexMorphIndexStart,
+        PmxModelVertexLayoutElementKind::VertexMorphCount,
+        PmxModelVertexLayoutElementKind::UvMorphIndexStart,
+        PmxModelVertexLayoutElementKind::UvMorphCount,
+    ]);
+    let mut offset = 0;
+
+    kinds
+        .map(|kind| {
+            let element = PmxModelVertexLayoutElement { kind, offset };
+            offset += kind.vertex_format().size();
+            element
+        })
+        .collect()
+}
+
+/// Returns the size of a vertex of the given layout.
+fn vertex_stride(layout_elements: &[PmxModelVertexLayoutElement]) -> u64 {
+    layout_elements
+        .last()
+        .map(PmxModelVertexLayoutElement::end)
+        .unwrap_or(0)
+}
+
+/// Clamps a deform weight into `[0, 1]`, so that the weight of the other bone is not negative.
+fn clamp_bone_weight(vertex_index: usize, bone_weight: f32) -> f32 {
+    if (0.0..=1.0).contains(&bone_weight) {
+        return bone_weight;
+    }
+
+    let clamped = if bone_weight.is_nan() {
+        0.0
+    } else {
+        bone_weight.clamp(0.0, 1.0)
+    };
+    warn!(
+        "the bone weight {} of the vertex {} is out of [0, 1]; it will be clamped to {}.",
+        bone_weight, vertex_index, clamped
+    );
+    clamped
+}
+
+/// Clamps the weights and scales them to sum to 1. If none of them is positive, the first bone
+/// takes the whole weight, as the vertex would otherwise collapse to the origin.
+fn normalize_bone_weights(vertex_index: usize, bone_weights: [f32; 4]) -> [f32; 4] {
+    let bone_weights = bone_weights.map(|bone_weight| clamp_bone_weight(vertex_index, bone_weight));
+    let total = bone_weights.iter().sum::<f32>();
+
+    if total <= f32::EPSILON {
+        warn!(
+            "the bone weights of the vertex {} are all zero; the first bone will take the whole weight.",
+            vertex_index
+        );
+        return [1.0, 0.0, 0.0, 0.0];
     }
+
+    bone_weights.map(|bone_weight| bone_weight / total)
 }
 
 fn make_vertex_data(
     pmx_vertices: &[PmxVertex],
+    tangents: &[Vec3],
+    pmx_additional_vec4_count: usize,
+    uv_channels: &[u8],
     morph_vertex_attributes: Vec<MorphVertexAttribute>,
 ) -> (Vec<u8>, Vec<PmxModelVertexLayoutElement>) {
-    let layout_elements = vec![
-        PmxModelVertexLayoutElement {
-            kind: PmxModelVertexLayoutElementKind::Position,
-            offset: size_of::<[[u8; 4]; 0]>() as u64,
-        },
-        PmxModelVertexLayoutElement {
-            kind: PmxModelVertexLayoutElementKind::Normal,
-            offset: size_of::<[[u8; 4]; 3]>() as u64,
-        },
-        PmxModelVertexLayoutElement {
-            kind: PmxModelVertexLayoutElementKind::TexCoord,
-            offset: size_of::<[[u8; 4]; 6]>() as u64,
-        },
-        PmxModelVertexLayoutElement {
-            kind: PmxModelVertexLayoutElementKind::AdditionalVec4(0),
-            offset: size_of::<[[u8; 4]; 8]>() as u64,
-        },
-        PmxModelVertexLayoutElement {
-            kind: PmxModelVertexLayoutElementKind::AdditionalVec4(1),
-            offset: size_of::<[[u8; 4]; 12]>() as u64,
-        },
-        PmxModelVertexLayoutElement {
-            kind: PmxModelVertexLayoutElementKind::AdditionalVec4(2),
-            offset: size_of::<[[u8; 4]; 16]>() as u64,
-        },
-        PmxModelVertexLayoutElement {
-            kind: PmxModelVertexLayoutElementKind::AdditionalVec4(3),
-            offset: size_of::<[[u8; 4]; 20]>() as u64,
-        },
-        PmxModelVertexLayoutElement {
-            kind: PmxModelVertexLayoutElementKind::DeformKind,
-            offset: size_of::<[[u8; 4]; 24]>() as u64,
-        },
-        PmxModelVertexLayoutElement {
-            kind: PmxModelVertexLayoutElementKind::BoneIndex,
-            offset: size_of::<[[u8; 4]; 25]>() as u64,
-        },
-        PmxModelVertexLayoutElement {
-            kind: PmxModelVertexLayoutElementKind::BoneWeight,
-            offset: size_of::<[[u8; 4]; 29]>() as u64,
-        },
-        PmxModelVertexLayoutElement {
-            kind: PmxModelVertexLayoutElementKind::SdefC,
-            offset: size_of::<[[u8; 4]; 33]>() as u64,
-        },
-        PmxModelVertexLayoutElement {
-            kind: PmxModelVertexLayoutElementKind::SdefR0,
-            offset: size_of::<[[u8; 4]; 36]>() as u64,
-        },
-        PmxModelVertexLayoutElement {
-            kind: PmxModelVertexLayoutElementKind::SdefR1,
-            offset: size_of::<[[u8; 4]; 39]>() as u64,
-        },
-        PmxModelVertexLayoutElement {
-            kind: PmxModelVertexLayoutElementKind::EdgeSize,
-            offset: size_of::<[[u8; 4]; 42]>() as u64,
-        },
-        PmxModelVertexLayoutElement {
-            kind: PmxModelVertexLayoutElementKind::VertexMorphIndexStart,
-            offset: size_of::<[[u8; 4]; 43]>() as u64,
-        },
-        PmxModelVertexLayoutElement {
-            kind: PmxModelVertexLayoutElementKind::VertexMorphCount,
-            offset: size_of::<[[u8; 4]; 44]>() as u64,
-        },
-        PmxModelVertexLayoutElement {
-            kind: PmxModelVertexLayoutElementKind::UvMorphIndexStart,
-            offset: size_of::<[[u8; 4]; 45]>() as u64,
-        },
-        PmxModelVertexLayoutElement {
-            kind: PmxModelVertexLayoutElementKind::UvMorphCount,
-            offset: size_of::<[[u8; 4]; 46]>() as u64,
-        },
-    ];
+    let additional_vec4_count = vertex_additional_vec4_count(pmx_additional_vec4_count);
+    let layout_elements = make_vertex_layout(additional_vec4_count, uv_channels.len());
+    let stride = vertex_stride(&layout_elements);
 
     let mut position = 0;
-    let mut vertex_data = vec![0; size_of::<[[u8; 4]; 47]>() * pmx_vertices.len()];
+    let mut vertex_data = vec![0; stride as usize * pmx_vertices.len()];
 
     let mut write = |data: &[u8]| {
         vertex_data[position..position + data.len()].copy_from_slice(data);
@@ -897,29 +1255,25 @@ fn make_vertex_data(
         write!(write, pmx_vertex.uv.x);
         write!(write, pmx_vertex.uv.y);
 
-        // additional vec4 0
-        write!(write, pmx_vertex.additional_vec4s[0].x);
-        write!(write, pmx_vertex.additional_vec4s[0].y);
-        write!(write, pmx_vertex.additional_vec4s[0].z);
-        write!(write, pmx_vertex.additional_vec4s[0].w);
-
-        // additional vec4 1
-        write!(write, pmx_vertex.additional_vec4s[1].x);
-        write!(write, pmx_vertex.additional_vec4s[1].y);
-        write!(write, pmx_vertex.additional_vec4s[1].z);
-        write!(write, pmx_vertex.additional_vec4s[1].w);
-
-        // additional vec4 2
-        write!(write, pmx_vertex.additional_vec4s[2].x);
-        write!(write, pmx_vertex.additional_vec4s[2].y);
-        write!(write, pmx_vertex.additional_vec4s[2].z);
-        write!(write, pmx_vertex.additional_vec4s[2].w);
-
-        // additional vec4 3
-        write!(write, pmx_vertex.additional_vec4s[3].x);
-        write!(write, pmx_vertex.additional_vec4s[3].y);
-        write!(write, pmx_vertex.additional_vec4s[3].z);
-        write!(write, pmx_vertex.additional_vec4s[3].w);
+        // tangent
+        write!(write, tangents[index].x);
+        write!(write, tangents[index].y);
+        write!(write, -tangents[index].z);
+
+        // additional vec4s; the ones not declared by the model are zeroed by the parser
+        for additional_vec4 in &pmx_vertex.additional_vec4s[..additional_vec4_count] {
+            write!(write, additional_vec4.x);
+            write!(write, additional_vec4.y);
+            write!(write, additional_vec4.z);
+            write!(write, additional_vec4.w);
+        }
+
+        // extra texcoords
+        for &additional_vec4_index in uv_channels {
+            let additional_vec4 = &pmx_vertex.additional_vec4s[additional_vec4_index as usize];
+            write!(write, additional_vec4.x);
+            write!(write, additional_vec4.y);
+        }
 
         // deform info
         match &pmx_vertex.deform_kind {
@@ -969,7 +1323,8 @@ fn make_vertex_data(
                 write!(write, -1i32);
 
                 // bone weight
-                write!(write, *bone_weight);
+                let bone_weight = clamp_bone_weight(index, *bone_weight);
+                write!(write, bone_weight);
                 write!(write, 1f32 - bone_weight);
                 write!(write, 0f32);
                 write!(write, 0f32);
@@ -1009,18 +1364,18 @@ fn make_vertex_data(
                 write!(write, bone_index_4.get());
 
                 // bone weight
-                let total = bone_weight_1 + bone_weight_2 + bone_weight_3 + bone_weight_4;
+                let bone_weights = normalize_bone_weights(
+                    index,
+                    [
+                        *bone_weight_1,
+                        *bone_weight_2,
+                        *bone_weight_3,
+                        *bone_weight_4,
+                    ],
+                );
 
-                if total <= f32::EPSILON {
-                    write!(write, 0f32);
-                    write!(write, 0f32);
-                    write!(write, 0f32);
-                    write!(write, 0f32);
-                } else {
-                    write!(write, bone_weight_1 / total);
-                    write!(write, bone_weight_2 / total);
-                    write!(write, bone_weight_3 / total);
-                    write!(write, bone_weight_4 / total);
+                for bone_weight in bone_weights {
+                    write!(write, bone_weight);
                 }
 
                 // sdef c
@@ -1056,25 +1411,26 @@ fn make_vertex_data(
                 write!(write, -1i32);
 
                 // bone weight
-                write!(write, *bone_weight);
+                let bone_weight = clamp_bone_weight(index, *bone_weight);
+                write!(write, bone_weight);
                 write!(write, 1f32 - bone_weight);
                 write!(write, 0f32);
                 write!(write, 0f32);
 
-                // sdef c
+                // sdef c; the points are flipped like the positions
                 write!(write, c.x);
                 write!(write, c.y);
-                write!(write, c.z);
+                write!(write, -c.z);
 
                 // sdef r0
                 write!(write, r0.x);
                 write!(write, r0.y);
-                write!(write, r0.z);
+                write!(write, -r0.z);
 
                 // sdef r1
                 write!(write, r1.x);
                 write!(write, r1.y);
-                write!(write, r1.z);
+                write!(write, -r1.z);
             }
             PmxVertexDeformKind::Qdef {
                 bone_index_1,
@@ -1096,18 +1452,18 @@ fn make_vertex_data(
                 write!(write, bone_index_4.get());
 
                 // bone weight
-                let total = bone_weight_1 + bone_weight_2 + bone_weight_3 + bone_weight_4;
+                let bone_weights = normalize_bone_weights(
+                    index,
+                    [
+                        *bone_weight_1,
+                        *bone_weight_2,
+                        *bone_weight_3,
+                        *bone_weight_4,
+                    ],
+                );
 
-                if total <= f32::EPSILON {
-                    write!(write, 0f32);
-                    write!(write, 0f32);
-                    write!(write, 0f32);
-                    write!(write, 0f32);
-                } else {
-                    write!(write, bone_weight_1 / total);
-                    write!(write, bone_weight_2 / total);
-                    write!(write, bone_weight_3 / total);
-                    write!(write, bone_weight_4 / total);
+                for bone_weight in bone_weights {
+                    write!(write, bone_weight);
                 }
 
                 // sdef c
@@ -1127,7 +1483,7 @@ fn make_vertex_data(
             }
         }
 
-        // edge size
+        // edge size; a factor of the material edge size, written as is
         write!(write, pmx_vertex.edge_size);
 
         let vertex_morph_attribute = &morph_vertex_attributes[index];
@@ -1148,35 +1504,367 @@ fn make_vertex_data(
     (vertex_data, layout_elements)
 }
 
+/// Computes the tangent of each vertex, the direction in which `u` increases, as the average of
+/// those of the triangles around it orthogonalized against the normal. Vertices without a
+/// usable tangent, e.g. where the UVs of all triangles around it are degenerate, get an
+/// arbitrary unit vector orthogonal to the normal instead.
+fn make_vertex_tangents(pmx_vertices: &[PmxVertex], vertex_indices: &[u32]) -> Vec<Vec3> {
+    let mut tangent_sums = vec![Vec3::ZERO; pmx_vertices.len()];
+
+    for triangle in vertex_indices.chunks_exact(3) {
+        let vertices = match triangle
+            .iter()
+            .map(|&index| pmx_vertices.get(index as usize))
+            .collect::<Option<Vec<_>>>()
+        {
+            Some(vertices) => vertices,
+            None => {
+                continue;
+            }
+        };
+
+        let [a, b, c] = [vertices[0], vertices[1], vertices[2]]
+            .map(|vertex| Vec3::new(vertex.position.x, vertex.position.y, vertex.position.z));
+        let (du1, dv1) = (
+            vertices[1].uv.x - vertices[0].uv.x,
+            vertices[1].uv.y - vertices[0].uv.y,
+        );
+        let (du2, dv2) = (
+            vertices[2].uv.x - vertices[0].uv.x,
+            vertices[2].uv.y - vertices[0].uv.y,
+        );
+        let determinant = du1 * dv2 - du2 * dv1;
+
+        if determinant.abs() < f32::EPSILON {
+            continue;
+        }
+
+        // each triangle counts the same, regardless of its size in space or in the texture
+        let tangent = ((b - a) * dv2 - (c - a) * dv1) / determinant;
+        let tangent = tangent.normalized();
+
+        for &index in triangle {
+            tangent_sums[index as usize] += tangent;
+        }
+    }
+
+    pmx_vertices
+        .iter()
+        .zip(tangent_sums)
+        .map(|(pmx_vertex, tangent)| {
+            let normal = Vec3::new(
+                pmx_vertex.normal.x,
+                pmx_vertex.normal.y,
+                pmx_vertex.normal.z,
+            )
+            .normalized();
+            let tangent = (tangent - normal * Vec3::dot(normal, tangent)).normalized();
+
+            if tangent != Vec3::ZERO {
+                return tangent;
+            }
+
+            // any axis that is not parallel to the normal gives an orthogonal one
+            let axis = match normal.x.abs() < 0.9 {
+                true => Vec3::RIGHT,
+                false => Vec3::UP,
+            };
+            let tangent = Vec3::cross(axis, normal).normalized();
+
+            match tangent == Vec3::ZERO {
+                true => Vec3::RIGHT,
+                false => tangent,
+            }
+        })
+        .collect()
+}
+
 fn make_index_data(
-    mut pmx_material_namer: impl FnMut(&PmxMaterial) -> String,
+    model_name: &str,
+    material_names: &[String],
     pmx_materials: &[PmxMaterial],
-    pmx_indices: &PmxIndices,
+    vertex_indices: &[u32],
 ) -> (Vec<u8>, PmxModelIndexKind, Vec<PmxModelElement>) {
     let mut position = 0;
-    let mut index_data = vec![0; size_of::<u32>() * pmx_indices.vertex_indices.len()];
+    let mut index_data = vec![0; size_of_val(vertex_indices)];
 
-    for index in &pmx_indices.vertex_indices {
-        index_data[position..position + 4].copy_from_slice(&index.get().to_le_bytes());
+    for index in vertex_indices {
+        index_data[position..position + 4].copy_from_slice(&index.to_le_bytes());
         position += size_of::<u32>();
     }
 
-    let mut previous_index_count = 0;
-    let mut elements = Vec::with_capacity(pmx_materials.len());
+    let elements = material_names
+        .iter()
+        .zip(material_index_ranges(
+            pmx_materials,
+            vertex_indices.len() as u32,
+        ))
+        .map(|(material_name, index_range)| PmxModelElement {
+            material_name: format!("{}/material:{}", model_name, material_name),
+            index_range,
+        })
+        .collect();
+
+    (index_data, PmxModelIndexKind::U32, elements)
+}
+
+/// Names each material after its sanitized name. The first material of a name keeps it, and each
+/// later one takes the first free suffix `-1`, `-2`, ..., in the order of the materials.
+fn make_material_names(pmx_materials: &[PmxMaterial]) -> Vec<String> {
+    let mut names = Vec::with_capacity(pmx_materials.len());
+    let mut used_names = HashSet::new();
 
     for pmx_material in pmx_materials {
-        elements.push(PmxModelElement {
-            material_name: pmx_material_namer(pmx_material),
-            index_range: (
-                previous_index_count,
-                previous_index_count + pmx_material.surface_count,
-            ),
-        });
+        let name = sanitize_resource_name(&pmx_material.name_local);
+        let name = if used_names.contains(&name) {
+            (1..)
+                .map(|suffix| format!("{}-{}", name, suffix))
+                .find(|name| !used_names.contains(name))
+                .unwrap()
+        } else {
+            name
+        };
 
-        previous_index_count += pmx_material.surface_count;
+        used_names.insert(name.clone());
+        names.push(name);
     }
 
-    (index_data, PmxModelIndexKind::U32, elements)
+    names
+}
+
+/// Returns the range of the indices of each material, in order.
+fn material_index_ranges(pmx_materials: &[PmxMaterial], index_count: u32) -> Vec<(u32, u32)> {
+    let mut previous_index_count = 0u32;
+    let mut index_ranges = Vec::with_capacity(pmx_materials.len());
+
+    for pmx_material in pmx_materials {
+        // the ranges are clamped, so that materials claiming more surfaces than the file has
+        // do not reach out of the index buffer
+        let index_end = previous_index_count
+            .saturating_add(pmx_material.surface_count)
+            .min(index_count);
+
+        index_ranges.push((previous_index_count, index_end));
+        previous_index_count = index_end;
+    }
+
+    index_ranges
+}
+
+/// Gives each triangle in the index ranges its own vertices with the face normal, for flat
+/// shading. The copies keep the morphs of their source vertices, and the vertices no longer
+/// referenced afterwards are removed.
+fn unshare_flat_shaded_vertices(
+    pmx_vertices: &mut Vec<PmxVertex>,
+    morph_vertex_attributes: &mut Vec<MorphVertexAttribute>,
+    vertex_indices: &mut [u32],
+    index_ranges: impl IntoIterator<Item = Range<usize>>,
+) {
+    for index_range in index_ranges {
+        for triangle in vertex_indices[index_range].chunks_exact_mut(3) {
+            let sources = match triangle
+                .iter()
+                .map(|&index| pmx_vertices.get(index as usize).cloned())
+                .collect::<Option<Vec<_>>>()
+            {
+                Some(sources) => sources,
+                None => {
+                    continue;
+                }
+            };
+
+            let [a, b, c] = [&sources[0], &sources[1], &sources[2]]
+                .map(|vertex| Vec3::new(vertex.position.x, vertex.position.y, vertex.position.z));
+            // the PMX triangles are clockwise, so this points to the front
+            let normal = Vec3::cross(b - a, c - a);
+
+            for (index, mut vertex) in triangle.iter_mut().zip(sources) {
+                // degenerated triangles keep the authored normals
+                if f32::EPSILON < normal.len() {
+                    let normal = normal.normalized();
+                    vertex.normal.x = normal.x;
+                    vertex.normal.y = normal.y;
+                    vertex.normal.z = normal.z;
+                }
+
+                let attribute = morph_vertex_attributes
+                    .get(*index as usize)
+                    .cloned()
+                    .unwrap_or_default();
+                morph_vertex_attributes.push(attribute);
+
+                *index = pmx_vertices.len() as u32;
+                pmx_vertices.push(vertex);
+            }
+        }
+    }
+
+    remove_unreferenced_vertices(pmx_vertices, morph_vertex_attributes, vertex_indices);
+}
+
+fn remove_unreferenced_vertices(
+    pmx_vertices: &mut Vec<PmxVertex>,
+    morph_vertex_attributes: &mut Vec<MorphVertexAttribute>,
+    vertex_indices: &mut [u32],
+) {
+    let mut is_referenced = vec![false; pmx_vertices.len()];
+
+    for &index in vertex_indices.iter() {
+        if let Some(is_referenced) = is_referenced.get_mut(index as usize) {
+            *is_referenced = true;
+        }
+    }
+
+    let mut new_indices = Vec::with_capacity(is_referenced.len());
+    let mut next_index = 0u32;
+
+    for &is_referenced in &is_referenced {
+        new_indices.push(next_index);
+
+        if is_referenced {
+            next_index += 1;
+        }
+    }
+
+    for index in vertex_indices.iter_mut() {
+        if let Some(&new_index) = new_indices.get(*index as usize) {
+            *index = new_index;
+        }
+    }
+
+    let mut is_vertex_referenced = is_referenced.iter();
+    pmx_vertices.retain(|_| *is_vertex_referenced.next().unwrap());
+
+    let mut is_attribute_referenced = is_referenced.iter();
+    morph_vertex_attributes.retain(|_| *is_attribute_referenced.next().unwrap());
+}
+
+/// Makes a static mesh of each index range, in order, with the vertices it references in the
+/// order of their first references. Triangles that reference missing vertices are dropped.
+fn split_pmx(
+    index_ranges: &[(u32, u32)],
+    pmx_vertices: &[PmxVertex],
+    vertex_indices: &[u32],
+) -> Vec<MeshSource> {
+    // a single range drawing every index references nearly every vertex, so the vertices are
+    // remapped through a table instead of a map
+    if let [(0, end)] = index_ranges {
+        if *end as usize == vertex_indices.len() {
+            let mut remap = vec![u32::MAX; pmx_vertices.len()];
+
+            return vec![remap_static_mesh(
+                pmx_vertices,
+                vertex_indices,
+                |index, next| {
+                    let remapped = &mut remap[index as usize];
+
+                    if *remapped == u32::MAX {
+                        *remapped = next;
+                    }
+
+                    *remapped
+                },
+            )];
+        }
+    }
+
+    index_ranges
+        .iter()
+        .map(|&(start, end)| {
+            let end = (end as usize).min(vertex_indices.len());
+            let start = (start as usize).min(end);
+            split_pmx_range(pmx_vertices, &vertex_indices[start..end])
+        })
+        .collect()
+}
+
+/// Makes a static mesh of the given indices, remapping the vertices through a map.
+fn split_pmx_range(pmx_vertices: &[PmxVertex], vertex_indices: &[u32]) -> MeshSource {
+    let mut remap = HashMap::new();
+    remap_static_mesh(pmx_vertices, vertex_indices, |index, next| {
+        *remap.entry(index).or_insert(next)
+    })
+}
+
+/// Makes a static mesh of the given indices. `remap` returns the index of a vertex in the mesh,
+/// or takes the given next index for a vertex that is not in the mesh yet.
+fn remap_static_mesh(
+    pmx_vertices: &[PmxVertex],
+    vertex_indices: &[u32],
+    mut remap: impl FnMut(u32, u32) -> u32,
+) -> MeshSource {
+    let mut vertices = Vec::new();
+    let mut indices = Vec::with_capacity(vertex_indices.len());
+
+    for triangle in vertex_indices.chunks_exact(3) {
+        if triangle
+            .iter()
+            .any(|&index| pmx_vertices.len() <= index as usize)
+        {
+            continue;
+        }
+
+        for &index in triangle {
+            let next = vertices.len() as u32;
+            let remapped = remap(index, next);
+
+            if remapped == next {
+                vertices.push(&pmx_vertices[index as usize]);
+            }
+
+            indices.push(remapped);
+        }
+    }
+
+    make_static_mesh_source(&vertices, &indices)
+}
+
+/// Makes a mesh of the positions, normals and UVs of the vertices, with the z axis flipped as
+/// in the vertex data of the model.
+fn make_static_mesh_source(pmx_vertices: &[&PmxVertex], indices: &[u32]) -> MeshSource {
+    let mut vertex_data = Vec::with_capacity(pmx_vertices.len() * STATIC_MESH_VERTEX_STRIDE);
+
+    for pmx_vertex in pmx_vertices {
+        let components = [
+            pmx_vertex.position.x,
+            pmx_vertex.position.y,
+            -pmx_vertex.position.z,
+            pmx_vertex.normal.x,
+            pmx_vertex.normal.y,
+            -pmx_vertex.normal.z,
+            pmx_vertex.uv.x,
+            pmx_vertex.uv.y,
+        ];
+
+        for component in components {
+            vertex_data.extend(component.to_le_bytes());
+        }
+    }
+
+    MeshSource::new(
+        pmx_vertices.len() as u32,
+        vertex_data,
+        Vec::from_iter(indices.iter().flat_map(|index| index.to_le_bytes())),
+        MeshIndexKind::U32,
+        MeshTopology::TriangleList,
+        vec![
+            MeshElement {
+                name: "position".to_owned(),
+                kind: MeshElementKind::Position,
+                offset: 0,
+            },
+            MeshElement {
+                name: "normal".to_owned(),
+                kind: MeshElementKind::Normal,
+                offset: 12,
+            },
+            MeshElement {
+                name: "uv".to_owned(),
+                kind: MeshElementKind::TexCoord(0),
+                offset: 24,
+            },
+        ],
+    )
 }
 
 fn make_material_source(
@@ -1184,6 +1872,7 @@ fn make_material_source(
     mut pmx_texture_namer: impl FnMut(&PmxTexture) -> String,
     mut pmx_internal_toon_texture_namer: impl FnMut(u8) -> String,
     render_type: MaterialRenderType,
+    color_space: PmxModelColorSpace,
     pmx_material: &PmxMaterial,
     pmx_textures: &[PmxTexture],
     vertex_morph_index_texture_name: &str,
@@ -1329,21 +2018,32 @@ fn make_material_source(
         });
     }
 
+    let convert_color = |color: Vec3| -> Vec3 {
+        match color_space {
+            PmxModelColorSpace::Srgb => color,
+            PmxModelColorSpace::Linear => srgb_to_linear_vec3(color),
+        }
+    };
+
     properties.push(MaterialProperty {
         name: "diffuse_color".to_owned(),
-        value: MaterialPropertyValue::Uniform(MaterialPropertyUniformValue::Vec4(Vec4::new(
-            pmx_material.diffuse_color.x,
-            pmx_material.diffuse_color.y,
-            pmx_material.diffuse_color.z,
+        value: MaterialPropertyValue::Uniform(MaterialPropertyUniformValue::Vec4(Vec4::from_vec3(
+            convert_color(Vec3::new(
+                pmx_material.diffuse_color.x,
+                pmx_material.diffuse_color.y,
+                pmx_material.diffuse_color.z,
+            )),
             pmx_material.diffuse_color.w,
         ))),
     });
     properties.push(MaterialProperty {
         name: "specular_color".to_owned(),
-        value: MaterialPropertyValue::Uniform(MaterialPropertyUniformValue::Vec3(Vec3::new(
-            pmx_material.specular_color.x,
-            pmx_material.specular_color.y,
-            pmx_material.specular_color.z,
+        value: MaterialPropertyValue::Uniform(MaterialPropertyUniformValue::Vec3(convert_color(
+            Vec3::new(
+                pmx_material.specular_color.x,
+                pmx_material.specular_color.y,
+                pmx_material.specular_color.z,
+            ),
         ))),
     });
     properties.push(MaterialProperty {
@@ -1354,18 +2054,22 @@ fn make_material_source(
     });
     properties.push(MaterialProperty {
         name: "ambient_color".to_owned(),
-        value: MaterialPropertyValue::Uniform(MaterialPropertyUniformValue::Vec3(Vec3::new(
-            pmx_material.ambient_color.x,
-            pmx_material.ambient_color.y,
-            pmx_material.ambient_color.z,
+        value: MaterialPropertyValue::Uniform(MaterialPropertyUniformValue::Vec3(convert_color(
+            Vec3::new(
+                pmx_material.ambient_color.x,
+                pmx_material.ambient_color.y,
+                pmx_material.ambient_color.z,
+            ),
         ))),
     });
     properties.push(MaterialProperty {
         name: "edge_color".to_owned(),
-        value: MaterialPropertyValue::Uniform(MaterialPropertyUniformValue::Vec4(Vec4::new(
-            pmx_material.edge_color.x,
-            pmx_material.edge_color.y,
-            pmx_material.edge_color.z,
+        value: MaterialPropertyValue::Uniform(MaterialPropertyUniformValue::Vec4(Vec4::from_vec3(
+            convert_color(Vec3::new(
+                pmx_material.edge_color.x,
+                pmx_material.edge_color.y,
+                pmx_material.edge_color.z,
+            )),
             pmx_material.edge_color.w,
         ))),
     });
@@ -1428,6 +2132,23 @@ fn make_material_source(
     )
 }
 
+/// Converts an RGB color from sRGB into linear space.
+fn srgb_to_linear_vec3(color: Vec3) -> Vec3 {
+    Vec3::new(
+        srgb_to_linear(color.x),
+        srgb_to_linear(color.y),
+        srgb_to_linear(color.z),
+    )
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
 fn make_texture_source(
     pmx_path: &Path,
     pmx_texture: &PmxTexture,
@@ -1443,13 +2164,15 @@ fn make_texture_source(
     };
 
     TextureProcessor::generate_texture_source(
-        &parent_path.join(&pmx_texture.path),
+        &texture_path(parent_path, pmx_texture),
         &TextureMetadata {
             texture_format: TextureElementTextureFormat::RGBA8UnormSrgb,
             sampling_mode: Some(TextureElementSamplingMode::Bilinear),
             wrapping_mode_u: Some(TextureElementWrappingMode::Clamp),
             wrapping_mode_v: Some(TextureElementWrappingMode::Clamp),
             sprites: None,
+            generate_mipmaps: false,
+            compression: None,
         },
     )
 }
@@ -1469,27 +2192,49 @@ fn make_internal_toon_texture_source(
     };
 
     TextureProcessor::generate_texture_source(
-        &parent_path.join(&format!("toon{:0>2}.bmp", index)),
+        &internal_toon_texture_path(parent_path, index),
         &TextureMetadata {
             texture_format: TextureElementTextureFormat::RGBA8UnormSrgb,
             sampling_mode: Some(TextureElementSamplingMode::Bilinear),
             wrapping_mode_u: Some(TextureElementWrappingMode::Clamp),
             wrapping_mode_v: Some(TextureElementWrappingMode::Clamp),
             sprites: None,
+            generate_mipmaps: false,
+            compression: None,
         },
     )
 }
 
+/// Joins the texture path written in the PMX file to the directory of the file. Unlike the
+/// resource name, the path is kept as written, apart from the backslashes of Windows-authored
+/// files, so that absolute paths and `..` still point at the file on disk.
+fn texture_path(parent_path: &Path, pmx_texture: &PmxTexture) -> PathBuf {
+    parent_path.join(pmx_texture.path.replace('\\', "/"))
+}
+
+/// Resolves the texture to an absolute path without `.`, `..` or links, so that the paths that
+/// spell the same file differently compare equal. Paths that cannot be resolved, e.g. of missing
+/// files, are kept as they are joined.
+fn canonical_texture_path(pmx_path: &Path, pmx_texture: &PmxTexture) -> PathBuf {
+    let path = texture_path(pmx_path.parent().unwrap_or(Path::new("")), pmx_texture);
+    std::fs::canonicalize(&path).unwrap_or(path)
+}
+
+fn internal_toon_texture_path(parent_path: &Path, index: u8) -> PathBuf {
+    parent_path.join(format!("toon{:0>2}.bmp", index))
+}
+
 fn make_bone_data(pmx_bones: &[PmxBone]) -> Vec<PmxModelBone> {
     let mut bones = Vec::with_capacity(pmx_bones.len());
 
     for pmx_bone in pmx_bones {
         bones.push(PmxModelBone {
             name: pmx_bone.name_local.clone(),
+            // flipped like the vertices, so that the skinning matrices apply to them
             position: Vec3::new(
                 pmx_bone.position.x,
                 pmx_bone.position.y,
-                pmx_bone.position.z,
+                -pmx_bone.position.z,
             ),
             parent_index: {
                 let index = pmx_bone.parent_index.get();
@@ -1545,21 +2290,23 @@ fn make_bone_data(pmx_bones: &[PmxBone]) -> Vec<PmxModelBone> {
                         if link.index.get() < 0 || pmx_bones.len() <= link.index.get() as usize {
                             return None;
                         } else {
-                            ik.index.get() as u32
+                            link.index.get() as u32
                         };
 
                     links.push(PmxModelBoneIKLink {
                         index,
+                        // mirroring the z axis negates the rotations around the x and y axes,
+                        // which swaps their bounds
                         angle_limit: link.angle_limit.as_ref().map(|angle_limit| {
                             PmxModelBoneIKAngleLimit {
                                 min: Vec3::new(
-                                    angle_limit.min.x,
-                                    angle_limit.min.y,
+                                    -angle_limit.max.x,
+                                    -angle_limit.max.y,
                                     angle_limit.min.z,
                                 ),
                                 max: Vec3::new(
-                                    angle_limit.max.x,
-                                    angle_limit.max.y,
+                                    -angle_limit.min.x,
+                                    -angle_limit.min.y,
                                     angle_limit.max.z,
                                 ),
                             }
@@ -1579,3 +2326,888 @@ fn make_bone_data(pmx_bones: &[PmxBone]) -> Vec<PmxModelBone> {
 
     bones
 }
+
+/// Computes the inverse of the bind pose matrix of each bone.
+/// The bind pose has no rotation, so each bone is placed by accumulating the offsets from its
+/// parent along the hierarchy. Bones in a parent cycle are treated as roots.
+fn make_inverse_bind_matrices(bones: &[PmxModelBone]) -> Vec<Mat4> {
+    let mut bind_matrices: Vec<Option<Mat4>> = vec![None; bones.len()];
+    let mut chain = Vec::new();
+
+    for index in 0..bones.len() {
+        // collect the ancestors that are not computed yet, from the bone up to the root
+        let mut current = Some(index);
+
+        while let Some(bone_index) = current {
+            if bind_matrices[bone_index].is_some() || chain.contains(&bone_index) {
+                break;
+            }
+
+            chain.push(bone_index);
+            current = bones[bone_index].parent_index.map(|index| index as usize);
+        }
+
+        while let Some(bone_index) = chain.pop() {
+            let bone = &bones[bone_index];
+            let parent = bone
+                .parent_index
+                .map(|index| index as usize)
+                .and_then(|index| Some((index, bind_matrices[index].clone()?)));
+
+            let bind_matrix = match parent {
+                Some((parent_index, parent_matrix)) => {
+                    let offset = bone.position - bones[parent_index].position;
+                    Mat4::translation(offset) * parent_matrix
+                }
+                None => Mat4::translation(bone.position),
+            };
+
+            bind_matrices[bone_index] = Some(bind_matrix);
+        }
+    }
+
+    bind_matrices
+        .into_iter()
+        .map(|matrix| matrix.unwrap().inversed())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processors::ORIGINAL_NAME_METADATA_KEY;
+    use lvl_pmx::{bdef2, PmxWriter};
+
+    /// Finds the compiled model of the given name among the resources.
+    fn find_model<'a>(resources: &'a [Resource], name: &str) -> &'a PmxModelSource {
+        resources
+            .iter()
+            .find_map(|resource| match &resource.kind {
+                ResourceKind::PmxModel(source) if resource.name == name => Some(source),
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn check_flat_shaded_quad() {
+        let mut vertices = Pmx::parse(PmxWriter::new("quad").vertices(4).build())
+            .unwrap()
+            .vertices;
+        let positions = [(0.0, 0.0), (0.0, 1.0), (1.0, 0.0), (1.0, 1.0)];
+
+        for (vertex, (x, y)) in vertices.iter_mut().zip(positions) {
+            vertex.position.x = x;
+            vertex.position.y = y;
+            vertex.normal.y = 1.0;
+        }
+
+        let mut morph_vertex_attributes = vec![MorphVertexAttribute::default(); 4];
+        morph_vertex_attributes[1].vertex_morph_count = 2;
+
+        // two clockwise triangles facing -z, sharing the vertices 1 and 2
+        let mut vertex_indices = vec![0, 1, 2, 2, 1, 3];
+        unshare_flat_shaded_vertices(
+            &mut vertices,
+            &mut morph_vertex_attributes,
+            &mut vertex_indices,
+            std::iter::once(0..6),
+        );
+
+        assert_eq!(vertices.len(), 6);
+        assert_eq!(morph_vertex_attributes.len(), 6);
+        assert_eq!(vertex_indices, [0, 1, 2, 3, 4, 5]);
+
+        for vertex in &vertices {
+            assert_eq!(
+                (vertex.normal.x, vertex.normal.y, vertex.normal.z),
+                (0.0, 0.0, -1.0)
+            );
+        }
+
+        // the copies of the vertex 1 keep its morphs
+        let morph_counts = morph_vertex_attributes
+            .iter()
+            .map(|attribute| attribute.vertex_morph_count)
+            .collect::<Vec<_>>();
+        assert_eq!(morph_counts, [0, 2, 0, 0, 2, 0]);
+    }
+
+    #[test]
+    fn check_quad_tangents() {
+        let mut vertices = Pmx::parse(PmxWriter::new("quad").vertices(4).build())
+            .unwrap()
+            .vertices;
+        let positions = [(0.0, 0.0), (0.0, 1.0), (1.0, 0.0), (1.0, 1.0)];
+
+        for (vertex, (x, y)) in vertices.iter_mut().zip(positions) {
+            vertex.position.x = x;
+            vertex.position.y = y;
+            vertex.uv.x = x;
+            vertex.uv.y = 1.0 - y;
+            // slightly tilted, so that the tangents have to be orthogonalized
+            vertex.normal.x = 0.1;
+            vertex.normal.z = -1.0;
+        }
+
+        let vertex_indices = [0, 1, 2, 2, 1, 3];
+        let check = |tangents: &[Vec3], vertices: &[PmxVertex]| {
+            assert_eq!(tangents.len(), 4);
+
+            for (tangent, vertex) in tangents.iter().zip(vertices) {
+                let normal = Vec3::new(vertex.normal.x, vertex.normal.y, vertex.normal.z);
+                assert!((tangent.len() - 1.0).abs() < 1e-4, "{:?}", tangent);
+                assert!(Vec3::dot(*tangent, normal.normalized()).abs() < 1e-4);
+            }
+        };
+
+        let tangents = make_vertex_tangents(&vertices, &vertex_indices);
+        check(&tangents, &vertices);
+        // u increases along x
+        assert!(0.99 < tangents[0].x);
+
+        // degenerate UVs fall back to an orthogonal basis rather than NaN
+        for vertex in &mut vertices {
+            vertex.uv.x = 0.5;
+            vertex.uv.y = 0.5;
+        }
+
+        check(&make_vertex_tangents(&vertices, &vertex_indices), &vertices);
+    }
+
+    #[test]
+    fn check_parallel_texture_decoding() {
+        let dir = std::env::temp_dir().join(format!(
+            "lvl-resource-compiler-textures-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let texture_paths = (0..10)
+            .map(|index| format!("texture{}.png", index))
+            .collect::<Vec<_>>();
+
+        for (index, texture_path) in texture_paths.iter().enumerate() {
+            let size = 4 + index as u32;
+            let image = image::RgbaImage::from_fn(size, size, |x, y| {
+                image::Rgba([x as u8 * 16, y as u8 * 16, index as u8 * 16, 255])
+            });
+            image.save(dir.join(texture_path)).unwrap();
+        }
+
+        let pmx = Pmx::parse(PmxWriter::new("textured").textures(&texture_paths).build()).unwrap();
+        let file = dir.join("textured.pmx");
+        let single_threaded = process_pmx(&file, &pmx, None, 1).unwrap();
+        let multi_threaded = process_pmx(&file, &pmx, None, 4).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let texture_names = single_threaded
+            .iter()
+            .map(|resource| resource.name.as_str())
+            .filter(|name| name.starts_with("textured/texture:"))
+            .collect::<Vec<_>>();
+        assert_eq!(texture_names.len(), 10);
+        assert_eq!(texture_names[0], "textured/texture:texture0.png");
+        assert_eq!(
+            bincode::serialize(&single_threaded).unwrap(),
+            bincode::serialize(&multi_threaded).unwrap()
+        );
+    }
+
+    #[test]
+    fn check_texture_paths_with_different_separators() {
+        let dir = std::env::temp_dir().join(format!(
+            "lvl-resource-compiler-separators-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(dir.join("tex")).unwrap();
+        image::RgbaImage::from_pixel(4, 4, image::Rgba([255, 0, 0, 255]))
+            .save(dir.join("tex").join("a.png"))
+            .unwrap();
+
+        let pmx = Pmx::parse(
+            PmxWriter::new("separated")
+                .textures(&["tex\\a.png", "./tex//a.png"])
+                .build(),
+        )
+        .unwrap();
+        let resources = process_pmx(&dir.join("separated.pmx"), &pmx, None, 1).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let textures = resources
+            .iter()
+            .filter(|resource| resource.name.starts_with("separated/texture:"))
+            .collect::<Vec<_>>();
+        assert_eq!(textures.len(), 1);
+        assert_eq!(textures[0].name, "separated/texture:tex/a.png");
+        assert!(matches!(textures[0].kind, ResourceKind::Texture(_)));
+        assert_eq!(
+            textures[0].metadata.get(ORIGINAL_NAME_METADATA_KEY),
+            Some(&"tex\\a.png\n./tex//a.png".to_owned())
+        );
+    }
+
+    #[test]
+    fn check_absolute_texture_path() {
+        let dir = std::env::temp_dir().join(format!(
+            "lvl-resource-compiler-absolute-texture-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(dir.join("tex")).unwrap();
+        std::fs::create_dir_all(dir.join("model")).unwrap();
+        image::RgbaImage::from_pixel(4, 4, image::Rgba([0, 0, 255, 255]))
+            .save(dir.join("tex").join("a.png"))
+            .unwrap();
+
+        // the name drops the leading slash, but the file is still read from the absolute path
+        let texture_path = dir.join("tex").join("a.png").to_string_lossy().into_owned();
+        let pmx = Pmx::parse(
+            PmxWriter::new("absolute")
+                .textures(&[texture_path.as_str()])
+                .build(),
+        )
+        .unwrap();
+        let resources =
+            process_pmx(&dir.join("model").join("absolute.pmx"), &pmx, None, 1).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let textures = resources
+            .iter()
+            .filter(|resource| resource.name.starts_with("absolute/texture:"))
+            .collect::<Vec<_>>();
+        assert_eq!(textures.len(), 1);
+        assert_eq!(
+            textures[0].name,
+            format!("absolute/texture:{}", sanitize_resource_name(&texture_path))
+        );
+        assert!(matches!(textures[0].kind, ResourceKind::Texture(_)));
+        assert_eq!(
+            textures[0].metadata.get(ORIGINAL_NAME_METADATA_KEY),
+            Some(&texture_path)
+        );
+    }
+
+    #[test]
+    fn check_shared_texture_is_emitted_once() {
+        let dir = std::env::temp_dir().join(format!(
+            "lvl-resource-compiler-shared-texture-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(dir.join("tex")).unwrap();
+        image::RgbaImage::from_pixel(4, 4, image::Rgba([0, 255, 0, 255]))
+            .save(dir.join("tex").join("skin.png"))
+            .unwrap();
+
+        let pmx = Pmx::parse(
+            PmxWriter::new("shared")
+                .textures(&["tex/skin.png", "tex/../tex/skin.png"])
+                .materials(&[0, 1])
+                .build(),
+        )
+        .unwrap();
+        let resources = process_pmx(&dir.join("shared.pmx"), &pmx, None, 1).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let textures = resources
+            .iter()
+            .filter(|resource| resource.name.starts_with("shared/texture:"))
+            .collect::<Vec<_>>();
+        assert_eq!(textures.len(), 1);
+        assert_eq!(textures[0].name, "shared/texture:tex/skin.png");
+        assert_eq!(
+            textures[0].metadata.get(ORIGINAL_NAME_METADATA_KEY),
+            Some(&"tex/skin.png\ntex/../tex/skin.png".to_owned())
+        );
+
+        for material_name in ["shared/material:material0", "shared/material:material1"] {
+            let material = resources
+                .iter()
+                .find(|resource| resource.name == material_name)
+                .unwrap();
+            let source = match &material.kind {
+                ResourceKind::Material(source) => source,
+                _ => unreachable!(),
+            };
+            assert_eq!(
+                source.properties()["texture"].value,
+                MaterialPropertyValue::Texture {
+                    texture_name: "shared/texture:tex/skin.png".to_owned()
+                }
+            );
+        }
+    }
+
+    /// Returns the x of the position of each vertex of the mesh.
+    fn positions(mesh: &MeshSource) -> Vec<f32> {
+        mesh.vertex_data()
+            .chunks_exact(STATIC_MESH_VERTEX_STRIDE)
+            .map(|vertex| LittleEndian::read_f32(&vertex[..4]))
+            .collect()
+    }
+
+    fn indices(mesh: &MeshSource) -> Vec<u32> {
+        mesh.index_data()
+            .chunks_exact(4)
+            .map(LittleEndian::read_u32)
+            .collect()
+    }
+
+    #[test]
+    fn check_split_pmx() {
+        let mut vertices = Pmx::parse(PmxWriter::new("split").vertices(5).build())
+            .unwrap()
+            .vertices;
+
+        for (index, vertex) in vertices.iter_mut().enumerate() {
+            vertex.position.x = index as f32;
+            vertex.position.z = 1.0;
+        }
+
+        // the vertex 4 is not referenced, and the others are referenced out of order
+        let vertex_indices = [3, 1, 2, 2, 1, 0];
+
+        // a single range takes the table, which matches the map
+        let meshes = split_pmx(&[(0, 6)], &vertices, &vertex_indices);
+        let remapped = split_pmx_range(&vertices, &vertex_indices);
+        assert_eq!(meshes.len(), 1);
+        assert_eq!(meshes[0].vertex_count(), remapped.vertex_count());
+        assert_eq!(meshes[0].vertex_data(), remapped.vertex_data());
+        assert_eq!(meshes[0].index_data(), remapped.index_data());
+        assert_eq!(positions(&meshes[0]), [3.0, 1.0, 2.0, 0.0]);
+        assert_eq!(indices(&meshes[0]), [0, 1, 2, 2, 1, 3]);
+        assert_eq!(
+            meshes[0].vertex_data()[..12],
+            [3f32, 0.0, -1.0].map(f32::to_le_bytes).concat()
+        );
+
+        let meshes = split_pmx(&[(0, 3), (3, 6)], &vertices, &vertex_indices);
+        assert_eq!(positions(&meshes[0]), [3.0, 1.0, 2.0]);
+        assert_eq!(positions(&meshes[1]), [2.0, 1.0, 0.0]);
+        assert_eq!(indices(&meshes[1]), [0, 1, 2]);
+
+        // the triangle that reaches out of the vertices is dropped
+        let meshes = split_pmx(&[(0, 6)], &vertices, &[0, 1, 5, 2, 1, 3]);
+        assert_eq!(positions(&meshes[0]), [2.0, 1.0, 3.0]);
+        assert_eq!(indices(&meshes[0]), [0, 1, 2]);
+    }
+
+    #[test]
+    fn check_shared_material_names() {
+        let pmx = Pmx::parse(
+            PmxWriter::new("twins")
+                .material("skin ", -1, 0)
+                .material("skin-1", -1, 0)
+                .material("skin", -1, 0)
+                .material("skin", -1, 0)
+                .build(),
+        )
+        .unwrap();
+        let resources = process_pmx(Path::new("twins.pmx"), &pmx, None, 1).unwrap();
+        let materials = resources
+            .iter()
+            .filter(|resource| matches!(resource.kind, ResourceKind::Material(_)))
+            .collect::<Vec<_>>();
+        let names = Vec::from_iter(materials.iter().map(|resource| resource.name.as_str()));
+
+        assert_eq!(
+            names,
+            [
+                "twins/material:skin",
+                "twins/material:skin-1",
+                "twins/material:skin-2",
+                "twins/material:skin-3"
+            ]
+        );
+        assert_eq!(
+            Vec::from_iter(
+                find_model(&resources, "twins")
+                    .elements()
+                    .iter()
+                    .map(|element| element.material_name.as_str())
+            ),
+            names
+        );
+        assert_eq!(
+            materials[0].metadata.get(ORIGINAL_NAME_METADATA_KEY),
+            Some(&"skin ".to_owned())
+        );
+        assert!(materials[1].metadata.is_empty());
+    }
+
+    #[test]
+    fn check_static_mesh_names() {
+        let pmx = Pmx::parse(
+            PmxWriter::new(" props\\quad ")
+                .material("front\\left ", -1, 0)
+                .material("back", -1, 0)
+                .build(),
+        )
+        .unwrap();
+        let metadata = PmxModelMetadata {
+            material_descriptions: BTreeMap::new(),
+            color_space: None,
+            max_texture_size: None,
+            uv_channels: None,
+            static_meshes: Some(true),
+        };
+        let mesh_names = |metadata| {
+            process_pmx(Path::new("quad.pmx"), &pmx, metadata, 1)
+                .unwrap()
+                .into_iter()
+                .filter(|resource| matches!(resource.kind, ResourceKind::Mesh(_)))
+                .map(|resource| resource.name)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            mesh_names(Some(&metadata)),
+            ["props/quad/mesh:front/left", "props/quad/mesh:back"]
+        );
+        assert!(mesh_names(None).is_empty());
+    }
+
+    #[test]
+    fn check_zero_material_pmx() {
+        let pmx = Pmx::parse(PmxWriter::new("empty").additional_vec4_count(0).build()).unwrap();
+        let resources = process_pmx(Path::new("empty.pmx"), &pmx, None, 1).unwrap();
+
+        let model = find_model(&resources, "empty");
+
+        assert!(model.elements().is_empty());
+        assert!(model.morphs().is_empty());
+        assert!(model.vertex_data().is_empty());
+        assert!(model.index_data().is_empty());
+        assert!(!resources
+            .iter()
+            .any(|resource| matches!(resource.kind, ResourceKind::Material(_))));
+    }
+
+    fn equals_float(a: f32, b: f32) -> bool {
+        (a - b).abs() <= 1e-6
+    }
+
+    #[test]
+    fn check_srgb_to_linear_mid_gray() {
+        let linear = srgb_to_linear_vec3(Vec3::new(0.5, 0.5, 0.5));
+
+        assert!(equals_float(linear.x, 0.21404114));
+        assert!(equals_float(linear.y, 0.21404114));
+        assert!(equals_float(linear.z, 0.21404114));
+    }
+
+    #[test]
+    fn check_srgb_to_linear_end_points() {
+        assert!(equals_float(srgb_to_linear(0.0), 0.0));
+        assert!(equals_float(srgb_to_linear(1.0), 1.0));
+        assert!(equals_float(srgb_to_linear(0.04045), 0.04045 / 12.92));
+    }
+
+    fn make_bone(position: Vec3, parent_index: Option<u32>) -> PmxModelBone {
+        PmxModelBone {
+            name: String::new(),
+            position,
+            parent_index,
+            layer: 0,
+            flags: PmxModelBoneFlags {
+                supports_ik: false,
+                inherit_rotation: false,
+                inherit_translation: false,
+                local_coordinate: false,
+                physics_after_deform: false,
+            },
+            inheritance: None,
+            ik: None,
+        }
+    }
+
+    #[test]
+    fn check_inverse_bind_matrices_two_bone_chain() {
+        let bones = [
+            make_bone(Vec3::new(0.0, 1.0, 0.0), None),
+            make_bone(Vec3::new(0.0, 3.0, 1.0), Some(0)),
+        ];
+        let inverse_bind_matrices = make_inverse_bind_matrices(&bones);
+        assert_eq!(inverse_bind_matrices.len(), 2);
+
+        let root_bind_matrix = Mat4::translation(bones[0].position);
+        let child_bind_matrix =
+            Mat4::translation(bones[1].position - bones[0].position) * &root_bind_matrix;
+
+        for (bind_matrix, inverse_bind_matrix) in [root_bind_matrix, child_bind_matrix]
+            .iter()
+            .zip(&inverse_bind_matrices)
+        {
+            let identity = inverse_bind_matrix * bind_matrix.clone();
+
+            for (element, expected) in identity.elements.iter().zip(Mat4::identity().elements) {
+                assert!(equals_float(*element, expected));
+            }
+        }
+
+        // the bone itself ends up at the origin of its own space
+        let child_position = Vec4::new(0.0, 3.0, 1.0, 1.0) * &inverse_bind_matrices[1];
+        assert!(equals_float(child_position.x, 0.0));
+        assert!(equals_float(child_position.y, 0.0));
+        assert!(equals_float(child_position.z, 0.0));
+    }
+
+    #[test]
+    fn check_inverse_bind_matrices_child_before_parent() {
+        let bones = [
+            make_bone(Vec3::new(2.0, 0.0, 0.0), Some(1)),
+            make_bone(Vec3::new(1.0, 0.0, 0.0), None),
+        ];
+        let inverse_bind_matrices = make_inverse_bind_matrices(&bones);
+
+        assert_eq!(
+            inverse_bind_matrices[0].split_translation(),
+            Vec3::new(-2.0, 0.0, 0.0)
+        );
+        assert_eq!(
+            inverse_bind_matrices[1].split_translation(),
+            Vec3::new(-1.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn check_ik_bone_data() {
+        let pmx = Pmx::parse(
+            PmxWriter::new("legged")
+                .bone("hip", [0.0, 2.0, 0.0], -1)
+                .bone("knee", [0.0, 1.0, 0.5], 0)
+                .bone("ankle", [0.0, 0.0, 0.0], 1)
+                .ik_bone(
+                    "leg-ik",
+                    [0.0, 0.0, 0.0],
+                    2,
+                    &[
+                        (1, Some(([-3.0, -0.5, -0.25], [-0.5, 1.0, 0.75]))),
+                        (0, None),
+                    ],
+                )
+                .build(),
+        )
+        .unwrap();
+        let bones = make_bone_data(&pmx.bones);
+
+        assert_eq!(bones[1].position, Vec3::new(0.0, 1.0, -0.5));
+
+        let ik = bones[3].ik.as_ref().unwrap();
+        assert_eq!(ik.index, 2);
+        assert_eq!(
+            Vec::from_iter(ik.links.iter().map(|link| link.index)),
+            [1, 0]
+        );
+        assert!(ik.links[1].angle_limit.is_none());
+
+        // mirroring the z axis negates the bounds around the x and y axes and swaps them
+        let angle_limit = ik.links[0].angle_limit.as_ref().unwrap();
+        assert_eq!(angle_limit.min, Vec3::new(0.5, -1.0, -0.25));
+        assert_eq!(angle_limit.max, Vec3::new(3.0, 0.5, 0.75));
+    }
+
+    #[test]
+    fn check_morph_texture_config() {
+        assert!(MorphTextureConfig::new(
+            TextureElementTextureFormat::RG32Uint,
+            TextureElementSamplingMode::Bilinear,
+            TextureElementWrappingMode::Clamp,
+        )
+        .is_err());
+        assert!(MorphTextureConfig::new(
+            TextureElementTextureFormat::RGBA32Float,
+            TextureElementSamplingMode::Bilinear,
+            TextureElementWrappingMode::Repeat,
+        )
+        .is_ok());
+
+        let configs = MorphTextureConfigs::default();
+        assert_eq!(
+            configs.vertex_morph_index.sampling_mode,
+            TextureElementSamplingMode::Point
+        );
+
+        // the uv morph indices do not fit in a RG32Uint texel
+        assert!(MorphTextureConfigs::new(
+            configs.vertex_morph_index,
+            configs.vertex_morph_index,
+            configs.vertex_displacement,
+            configs.uv_displacement,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn check_morph_texture_size() {
+        assert_eq!(morph_texture_size(0, DEFAULT_MAX_TEXTURE_SIZE), (1, 1));
+        assert_eq!(morph_texture_size(100, DEFAULT_MAX_TEXTURE_SIZE), (100, 1));
+        assert_eq!(
+            morph_texture_size(4097, DEFAULT_MAX_TEXTURE_SIZE),
+            (2048, 3)
+        );
+
+        // more texels than a 2048x2048 texture holds
+        let texel_count = 2048 * 2048 + 1;
+        let size = morph_texture_size(texel_count, DEFAULT_MAX_TEXTURE_SIZE);
+        assert!(!is_morph_texture_size_supported(
+            size,
+            DEFAULT_MAX_TEXTURE_SIZE
+        ));
+
+        let size = morph_texture_size(texel_count, 4096);
+        assert_eq!(size, (4096, 1025));
+        assert!(is_morph_texture_size_supported(size, 4096));
+    }
+
+    /// Makes a PMX with 5 vertices and a vertex morph that raises each of them.
+    fn make_raised_pmx() -> Vec<u8> {
+        let offsets = Vec::from_iter((0..5).map(|index| (index, [0.0, 1.0, 0.0])));
+        PmxWriter::new("raised")
+            .vertices(5)
+            .vertex_morph("raise", &offsets)
+            .build()
+    }
+
+    #[test]
+    fn check_oversized_morph_textures() {
+        let pmx = Pmx::parse(make_raised_pmx()).unwrap();
+        let metadata = |max_texture_size| PmxModelMetadata {
+            material_descriptions: BTreeMap::new(),
+            color_space: None,
+            max_texture_size: Some(max_texture_size),
+            uv_channels: None,
+            static_meshes: None,
+        };
+        let file = Path::new("raised.pmx");
+
+        // the 5 displacements fit in 4x4, but not in 2x2
+        assert!(process_pmx(file, &pmx, Some(&metadata(4)), 1).is_ok());
+
+        let err = process_pmx(file, &pmx, Some(&metadata(2)), 1)
+            .err()
+            .unwrap();
+        assert!(err
+            .to_string()
+            .contains("exceeds the maximum texture size of 2"));
+    }
+
+    #[test]
+    fn check_morph_texel_buffer_sizes() {
+        let pmx = Pmx::parse(make_raised_pmx()).unwrap();
+        let morph_data = make_morph_data(
+            "raised",
+            5,
+            0,
+            &pmx.morphs,
+            &MorphTextureConfigs::default(),
+            4,
+        )
+        .unwrap();
+
+        for source in [
+            &morph_data.vertex_morph_index_texture_source,
+            &morph_data.uv_morph_index_texture_source,
+            &morph_data.vertex_displacement_texture_source,
+            &morph_data.uv_displacement_texture_source,
+        ] {
+            let element = match source.kind() {
+                TextureKind::Single(element) => element,
+                TextureKind::Cubemap { .. } => unreachable!(),
+            };
+            let bytes_per_texel = element.texture_format.bytes_per_texel().unwrap();
+
+            assert_eq!(
+                element.data.len(),
+                element.size.width as usize * element.size.height as usize * bytes_per_texel,
+                "{:?}",
+                element.texture_format
+            );
+        }
+    }
+
+    #[test]
+    fn check_vertex_layout_alignment() {
+        let limits = wgpu_types::Limits::default();
+
+        for (additional_vec4_count, uv_channel_count) in
+            (0..=4).flat_map(|count| (0..=count).map(move |channels| (count, channels)))
+        {
+            let layout = make_vertex_layout(additional_vec4_count, uv_channel_count);
+            let stride = vertex_stride(&layout);
+            let mut end = 0;
+
+            assert_eq!(stride % wgpu_types::VERTEX_STRIDE_ALIGNMENT, 0);
+            assert!(stride <= limits.max_vertex_buffer_array_stride as u64);
+
+            for element in &layout {
+                let size = element.kind.vertex_format().size();
+
+                assert_eq!(
+                    element.offset % size.min(4),
+                    0,
+                    "{:?} is misaligned",
+                    element.kind
+                );
+                assert!(end <= element.offset, "{:?} overlaps", element.kind);
+                end = element.offset + size;
+            }
+
+            // the slots are tightly packed up to the stride
+            assert_eq!(end, stride);
+        }
+    }
+
+    #[test]
+    fn check_uv_channels() {
+        let mut vertices = Pmx::parse(PmxWriter::new("two-uv").vertices(1).build())
+            .unwrap()
+            .vertices;
+        vertices[0].additional_vec4s[1].x = 0.25;
+        vertices[0].additional_vec4s[1].y = 0.75;
+
+        // the second additional vec4 holds the UV set of the detail maps
+        let (vertex_data, layout) = make_vertex_data(
+            &vertices,
+            &[Vec3::new(1.0, 0.0, 0.0)],
+            2,
+            &[1],
+            vec![MorphVertexAttribute::default()],
+        );
+        let offset_of = |kind| {
+            layout
+                .iter()
+                .find(|element| element.kind == kind)
+                .map(|element| element.offset)
+        };
+
+        assert_eq!(
+            offset_of(PmxModelVertexLayoutElementKind::TexCoord),
+            Some(24)
+        );
+        // after the position, normal, texcoord, tangent and two additional vec4s
+        let offset = offset_of(PmxModelVertexLayoutElementKind::AdditionalTexCoord(1)).unwrap();
+        assert_eq!(offset, 12 + 12 + 8 + 12 + 2 * 16);
+        assert_eq!(
+            offset_of(PmxModelVertexLayoutElementKind::AdditionalTexCoord(2)),
+            None
+        );
+        assert_eq!(vertex_data.len() as u64, vertex_stride(&layout));
+
+        let offset = offset as usize;
+        assert_eq!(LittleEndian::read_f32(&vertex_data[offset..]), 0.25);
+        assert_eq!(LittleEndian::read_f32(&vertex_data[offset + 4..]), 0.75);
+
+        // the model has to declare the additional vec4
+        let pmx = Pmx::parse(PmxWriter::new("two-uv").additional_vec4_count(0).build()).unwrap();
+        let metadata = PmxModelMetadata {
+            material_descriptions: BTreeMap::new(),
+            color_space: None,
+            max_texture_size: None,
+            uv_channels: Some(vec![1]),
+            static_meshes: None,
+        };
+        assert!(process_pmx(Path::new("two-uv.pmx"), &pmx, Some(&metadata), 1).is_err());
+    }
+
+    #[test]
+    fn check_additional_vec4_count() {
+        let pmx = Pmx::parse(
+            PmxWriter::new("one-additional-vec4")
+                .additional_vec4_count(1)
+                .build(),
+        )
+        .unwrap();
+        assert_eq!(pmx.header.config.additional_vec4_count, 1);
+
+        let resources = process_pmx(Path::new("one-additional-vec4.pmx"), &pmx, None, 1).unwrap();
+        let model = find_model(&resources, "one-additional-vec4");
+        let additional_vec4s = model
+            .vertex_layout()
+            .iter()
+            .filter(|element| {
+                matches!(
+                    element.kind,
+                    PmxModelVertexLayoutElementKind::AdditionalVec4(_)
+                )
+            })
+            .count();
+
+        assert_eq!(additional_vec4s, 1);
+        assert_eq!(model.vertex_stride(), 38 * 4);
+        assert_eq!(vertex_additional_vec4_count(0), 1);
+    }
+
+    #[test]
+    fn check_vertex_edge_scale() {
+        let mut pmx = Pmx::parse(PmxWriter::new("edged").vertices(2).build()).unwrap();
+        pmx.vertices[1].edge_size = 2.0;
+
+        let resources = process_pmx(Path::new("edged.pmx"), &pmx, None, 1).unwrap();
+        let model = find_model(&resources, "edged");
+        let edge_size_offset = model
+            .vertex_layout()
+            .iter()
+            .find(|element| element.kind == PmxModelVertexLayoutElementKind::EdgeSize)
+            .unwrap()
+            .offset as usize;
+        let edge_scales = model
+            .vertex_data()
+            .chunks_exact(model.vertex_stride() as usize)
+            .map(|vertex| {
+                f32::from_le_bytes(
+                    vertex[edge_size_offset..edge_size_offset + 4]
+                        .try_into()
+                        .unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        // the outline of the second vertex is twice as thick for any material edge size
+        assert_eq!(edge_scales, [1.0, 2.0]);
+    }
+
+    #[test]
+    fn check_bdef2_bone_weights() {
+        let pmx = Pmx::parse(
+            PmxWriter::new("weighted")
+                .vertex_deforms(&[&bdef2([3, 5], 0.25)])
+                .build(),
+        )
+        .unwrap();
+        let resources = process_pmx(Path::new("weighted.pmx"), &pmx, None, 1).unwrap();
+        let model = find_model(&resources, "weighted");
+        let bone_weights = model.bone_weights(0).unwrap();
+
+        assert_eq!(bone_weights, [(3, 0.25), (5, 0.75), (-1, 0.0), (-1, 0.0)]);
+        assert_eq!(
+            bone_weights.iter().map(|(_, weight)| weight).sum::<f32>(),
+            1.0
+        );
+        assert_eq!(model.bone_weights(1), None);
+    }
+
+    #[test]
+    fn check_out_of_range_bone_weights() {
+        let pmx = Pmx::parse(
+            PmxWriter::new("overweighted")
+                .vertex_deforms(&[&bdef2([3, 5], 1.5)])
+                .build(),
+        )
+        .unwrap();
+        let resources = process_pmx(Path::new("overweighted.pmx"), &pmx, None, 1).unwrap();
+        let model = find_model(&resources, "overweighted");
+
+        assert_eq!(
+            model.bone_weights(0).unwrap(),
+            [(3, 1.0), (5, 0.0), (-1, 0.0), (-1, 0.0)]
+        );
+        assert_eq!(
+            normalize_bone_weights(0, [2.0, -1.0, 1.0, f32::NAN]),
+            [0.5, 0.0, 0.5, 0.0]
+        );
+        assert_eq!(
+            normalize_bone_weights(0, [0.0, -1.0, 0.0, 0.0]),
+            [1.0, 0.0, 0.0, 0.0]
+        );
+    }
+}
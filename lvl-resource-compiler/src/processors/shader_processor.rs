@@ -7,9 +7,15 @@ use self::{
 };
 use super::Processor;
 use anyhow::{anyhow, Context, Error as AnyError};
-use lvl_resource::{Resource, ResourceKind, ShaderSource};
-use naga::{Module, ShaderStage};
-use std::{collections::BTreeSet, path::Path};
+use lvl_resource::{Resource, ResourceKind, ShaderCode, ShaderSource, ShaderSourceDescriptor};
+use naga::{
+    valid::{Capabilities, ValidationFlags, Validator},
+    Function, Module, ShaderStage,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+};
 
 pub struct ShaderProcessor;
 
@@ -37,6 +43,42 @@ impl ShaderProcessor {
         )
     }
 
+    /// Stores the validated naga IR of the shader next to its WGSL source,
+    /// so that the source does not have to be parsed at runtime.
+    /// Shaders whose source has been stripped are left as they are.
+    pub fn precompile_shader(
+        display_name: &str,
+        source: &mut ShaderSource,
+    ) -> Result<(), AnyError> {
+        let module = match source.source() {
+            Some(content) => compile_module(display_name, content, false)?,
+            None => {
+                return Ok(());
+            }
+        };
+
+        source.set_module(module);
+        Ok(())
+    }
+
+    /// Replaces the WGSL source of the shader with its validated naga IR,
+    /// so that the source is not shipped. Shaders already stripped are left as they are.
+    pub fn strip_shader_source(
+        display_name: &str,
+        source: &mut ShaderSource,
+    ) -> Result<(), AnyError> {
+        let module = match source.source() {
+            Some(content) => compile_module(display_name, content, true)?,
+            None => {
+                return Ok(());
+            }
+        };
+
+        source.set_module(module);
+        source.strip_source();
+        Ok(())
+    }
+
     fn generate_shader_resource_from_module(
         display_name: &str,
         content: String,
@@ -45,16 +87,16 @@ impl ShaderProcessor {
         builtin_uniform_bind_group: Option<u32>,
         instance_input_typename: Option<&str>,
     ) -> Result<ShaderSource, AnyError> {
-        let mut vertex_entry_point = None;
-        let mut fragment_entry_point = None;
+        let mut vertex_entry_points = Vec::new();
+        let mut fragment_entry_points = Vec::new();
 
         for entry_point in &module.entry_points {
             match entry_point.stage {
                 ShaderStage::Vertex => {
-                    vertex_entry_point = Some(entry_point.name.clone());
+                    vertex_entry_points.push(entry_point.name.clone());
                 }
                 ShaderStage::Fragment => {
-                    fragment_entry_point = Some(entry_point.name.clone());
+                    fragment_entry_points.push(entry_point.name.clone());
                 }
                 ShaderStage::Compute => {
                     continue;
@@ -62,7 +104,7 @@ impl ShaderProcessor {
             }
         }
 
-        let vertex_entry_point = match vertex_entry_point {
+        let vertex_entry_point = match select_default_entry_point(&vertex_entry_points, "vs_main") {
             Some(entry_point) => entry_point,
             None => {
                 return Err(anyhow!(
@@ -71,15 +113,16 @@ impl ShaderProcessor {
                 ));
             }
         };
-        let fragment_entry_point = match fragment_entry_point {
-            Some(entry_point) => entry_point,
-            None => {
-                return Err(anyhow!(
-                    "the shader `{}` does not contain a fragment entry point",
-                    display_name
-                ));
-            }
-        };
+        let fragment_entry_point =
+            match select_default_entry_point(&fragment_entry_points, "fs_main") {
+                Some(entry_point) => entry_point,
+                None => {
+                    return Err(anyhow!(
+                        "the shader `{}` does not contain a fragment entry point",
+                        display_name
+                    ));
+                }
+            };
 
         let bindings = inspect_bindings(
             module,
@@ -89,18 +132,88 @@ impl ShaderProcessor {
         let uniform_bindings = inspect_uniform_members(module, builtin_uniform_bind_group);
         let locations = inspect_locations(display_name, module, instance_input_typename);
 
-        Ok(ShaderSource::new(
-            content,
-            vertex_entry_point,
-            fragment_entry_point,
+        Ok(ShaderSource::new(ShaderSourceDescriptor {
+            code: ShaderCode::from_wgsl(content),
+            vs_main: vertex_entry_point,
+            fs_main: fragment_entry_point,
+            vertex_entry_points,
+            fragment_entry_points,
             builtin_uniform_bind_group,
             bindings,
-            uniform_bindings,
-            locations,
-        ))
+            uniform_members: uniform_bindings,
+            vertex_inputs: locations,
+        }))
     }
 }
 
+/// Parses and validates the WGSL source into naga IR.
+fn compile_module(
+    display_name: &str,
+    content: &str,
+    is_debug_names_stripped: bool,
+) -> Result<Module, AnyError> {
+    let mut module = naga::front::wgsl::parse_str(content).with_context(|| {
+        format!(
+            "failed to parse the file `{}` as a wgsl shader",
+            display_name
+        )
+    })?;
+
+    if is_debug_names_stripped {
+        strip_debug_names(&mut module);
+    }
+
+    Validator::new(ValidationFlags::all(), Capabilities::all())
+        .validate(&module)
+        .with_context(|| format!("failed to validate the shader `{}`", display_name))?;
+
+    Ok(module)
+}
+
+/// Removes the names of constants, variables and functions, which are only used for debugging.
+/// The bindings and entry points are found through the reflected metadata and the entry point
+/// names, which are kept. Type names are kept too, since types are deduplicated by their contents.
+fn strip_debug_names(module: &mut Module) {
+    fn strip_function(function: &mut Function) {
+        function.name = None;
+        function.named_expressions.clear();
+
+        for argument in &mut function.arguments {
+            argument.name = None;
+        }
+
+        for (_, local_variable) in function.local_variables.iter_mut() {
+            local_variable.name = None;
+        }
+    }
+
+    for (_, constant) in module.constants.iter_mut() {
+        constant.name = None;
+    }
+
+    for (_, global_variable) in module.global_variables.iter_mut() {
+        global_variable.name = None;
+    }
+
+    for (_, function) in module.functions.iter_mut() {
+        strip_function(function);
+    }
+
+    for entry_point in &mut module.entry_points {
+        strip_function(&mut entry_point.function);
+    }
+}
+
+/// Selects the entry point used when a pass does not ask for a specific one.
+/// It is the one with the conventional name if present, otherwise the first one declared.
+fn select_default_entry_point(entry_points: &[String], conventional_name: &str) -> Option<String> {
+    entry_points
+        .iter()
+        .find(|entry_point| entry_point.as_str() == conventional_name)
+        .or_else(|| entry_points.first())
+        .cloned()
+}
+
 impl Processor for ShaderProcessor {
     type Metadata = ();
 
@@ -120,6 +233,226 @@ impl Processor for ShaderProcessor {
         Ok(vec![Resource {
             name,
             kind: ResourceKind::Shader(source),
+            metadata: BTreeMap::new(),
         }])
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processors::expand_standard_shader_content;
+    use naga::back::wgsl::WriterFlags;
+
+    const MULTI_PASS_SHADER: &str = r#"
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    return vec4<f32>(f32(index), 0.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_depth() -> @location(0) vec4<f32> {
+    return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return vec4<f32>(1.0, 1.0, 1.0, 1.0);
+}
+"#;
+
+    #[test]
+    fn check_multiple_fragment_entry_points() {
+        let source = ShaderProcessor::generate_shader_resource_from_wsgl_content(
+            "multi-pass",
+            MULTI_PASS_SHADER.to_owned(),
+            &BTreeSet::new(),
+        )
+        .unwrap();
+
+        assert_eq!(source.vertex_entry_points(), ["vs_main"]);
+        assert_eq!(source.fragment_entry_points(), ["fs_depth", "fs_main"]);
+        assert_eq!(source.vs_main(), "vs_main");
+        assert_eq!(source.fs_main(), "fs_main");
+
+        assert_eq!(
+            source.find_fragment_entry_point("fs_depth"),
+            Some("fs_depth")
+        );
+        assert_eq!(source.find_fragment_entry_point("fs_main"), Some("fs_main"));
+        assert_eq!(source.find_fragment_entry_point("fs_shadow"), None);
+    }
+
+    #[test]
+    fn check_stripped_shader_source() {
+        let source = ShaderProcessor::generate_shader_resource_from_wsgl_content(
+            "multi-pass",
+            MULTI_PASS_SHADER.to_owned(),
+            &BTreeSet::new(),
+        )
+        .unwrap();
+        let mut stripped = source.clone();
+        ShaderProcessor::strip_shader_source("multi-pass", &mut stripped).unwrap();
+
+        assert_eq!(stripped.source(), None);
+        assert_eq!(stripped.fragment_entry_points(), ["fs_depth", "fs_main"]);
+
+        // the multi-pass shader is too small to compare, so the standard shaders are used
+        for (name, content) in [
+            (
+                "standard-full",
+                include_str!("../../assets/standard-full.wgsl"),
+            ),
+            (
+                "standard-no-env",
+                include_str!("../../assets/standard-no-env.wgsl"),
+            ),
+            (
+                "standard-no-toon",
+                include_str!("../../assets/standard-no-toon.wgsl"),
+            ),
+            (
+                "standard-no-toon-no-env",
+                include_str!("../../assets/standard-no-toon-no-env.wgsl"),
+            ),
+        ] {
+            let source = ShaderProcessor::generate_shader_resource_from_wsgl_content(
+                name,
+                expand_standard_shader_content(content),
+                &BTreeSet::new(),
+            )
+            .unwrap();
+            let mut stripped = source.clone();
+            ShaderProcessor::strip_shader_source(name, &mut stripped).unwrap();
+
+            let source_data = bincode::serialize(&source).unwrap();
+            let stripped_data = bincode::serialize(&stripped).unwrap();
+            assert!(stripped_data.len() < source_data.len(), "{}", name);
+
+            // the module read back from the bundle must still be a valid shader
+            let stripped = bincode::deserialize::<ShaderSource>(&stripped_data).unwrap();
+            Validator::new(ValidationFlags::all(), Capabilities::all())
+                .validate(stripped.module().unwrap())
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn check_precompiled_module_matches_wgsl() {
+        let mut source = ShaderProcessor::generate_shader_resource_from_wsgl_content(
+            "standard",
+            expand_standard_shader_content(include_str!("../../assets/standard-full.wgsl")),
+            &BTreeSet::new(),
+        )
+        .unwrap();
+        ShaderProcessor::precompile_shader("standard", &mut source).unwrap();
+
+        // the WGSL source is kept as a fallback
+        let data = bincode::serialize(&source).unwrap();
+        let source = bincode::deserialize::<ShaderSource>(&data).unwrap();
+        let wgsl_module = naga::front::wgsl::parse_str(source.source().unwrap()).unwrap();
+        let precompiled_module = source.module().unwrap();
+
+        // both produce the same shader once handed to a backend
+        let write = |module: &Module| {
+            let info = Validator::new(ValidationFlags::all(), Capabilities::all())
+                .validate(module)
+                .unwrap();
+            naga::back::wgsl::write_string(module, &info, WriterFlags::empty()).unwrap()
+        };
+
+        assert_eq!(write(precompiled_module), write(&wgsl_module));
+        assert_eq!(
+            precompiled_module
+                .entry_points
+                .iter()
+                .map(|entry_point| (entry_point.name.as_str(), entry_point.stage))
+                .collect::<Vec<_>>(),
+            wgsl_module
+                .entry_points
+                .iter()
+                .map(|entry_point| (entry_point.name.as_str(), entry_point.stage))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn check_shader_introspection() {
+        let source = ShaderProcessor::generate_shader_resource_from_wsgl_content(
+            "standard",
+            expand_standard_shader_content(include_str!("../../assets/standard-full.wgsl")),
+            &BTreeSet::new(),
+        )
+        .unwrap();
+
+        // the custom inputs are placed after the instance inputs
+        assert_eq!(source.vertex_input("position"), Some(8));
+        assert_eq!(source.vertex_input("uv"), Some(10));
+        assert_eq!(source.vertex_input("tangent"), None);
+        assert_eq!(source.vertex_inputs().len(), 14);
+
+        let texture = source.find_binding("texture").unwrap();
+        // the custom groups are placed after the builtin uniform group
+        assert_eq!((texture.group, texture.binding), (2, 0));
+        assert!(source.find_uniform_member("diffuse_color").is_some());
+        assert!(source.find_uniform_member("shininess").is_none());
+    }
+
+    #[test]
+    fn check_standard_shaders_declare_skinning() {
+        for (name, content) in [
+            (
+                "standard-full",
+                include_str!("../../assets/standard-full.wgsl"),
+            ),
+            (
+                "standard-no-env",
+                include_str!("../../assets/standard-no-env.wgsl"),
+            ),
+            (
+                "standard-no-toon",
+                include_str!("../../assets/standard-no-toon.wgsl"),
+            ),
+            (
+                "standard-no-toon-no-env",
+                include_str!("../../assets/standard-no-toon-no-env.wgsl"),
+            ),
+        ] {
+            let source = ShaderProcessor::generate_shader_resource_from_wsgl_content(
+                name,
+                expand_standard_shader_content(content),
+                &BTreeSet::new(),
+            )
+            .unwrap();
+
+            for input in [
+                "deform_kind",
+                "bone_index",
+                "bone_weight",
+                "sdef_c",
+                "sdef_r0_",
+                "sdef_r1_",
+            ] {
+                assert!(
+                    source.vertex_input(input).is_some(),
+                    "{} in {}",
+                    input,
+                    name
+                );
+            }
+
+            assert!(source.find_binding("bone_matrices").is_some(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn check_default_entry_point_falls_back_to_first() {
+        let entry_points = vec!["fs_depth".to_owned(), "fs_color".to_owned()];
+
+        assert_eq!(
+            select_default_entry_point(&entry_points, "fs_main"),
+            Some("fs_depth".to_owned())
+        );
+        assert_eq!(select_default_entry_point(&[], "fs_main"), None);
+    }
+}
@@ -1,4 +1,7 @@
-use super::Processor;
+mod block_compression;
+
+use self::block_compression::{compress_bc3, compress_bc7};
+use super::{sanitize_resource_name, Processor};
 use anyhow::{anyhow, Error as AnyError};
 use image::io::Reader as ImageReader;
 use lvl_resource::{
@@ -16,6 +19,37 @@ pub struct TextureMetadata {
     pub wrapping_mode_u: Option<TextureElementWrappingMode>,
     pub wrapping_mode_v: Option<TextureElementWrappingMode>,
     pub sprites: Option<BTreeMap<String, TextureSpriteElement>>,
+    /// `true` if the mip levels down to 1x1 should be generated with a box filter.
+    #[serde(default)]
+    pub generate_mipmaps: bool,
+    /// The block compression to store the texels with; the width and height must be multiples
+    /// of 4. The `texture_format` selects between the linear and sRGB variants.
+    pub compression: Option<TextureCompression>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TextureCompression {
+    Bc3,
+    Bc7,
+}
+
+impl TextureCompression {
+    fn texture_format(self, is_srgb: bool) -> TextureElementTextureFormat {
+        match (self, is_srgb) {
+            (Self::Bc3, false) => TextureElementTextureFormat::Bc3RgbaUnorm,
+            (Self::Bc3, true) => TextureElementTextureFormat::Bc3RgbaUnormSrgb,
+            (Self::Bc7, false) => TextureElementTextureFormat::Bc7RgbaUnorm,
+            (Self::Bc7, true) => TextureElementTextureFormat::Bc7RgbaUnormSrgb,
+        }
+    }
+
+    /// Compresses the RGBA8 texels of an image of the size.
+    fn compress(self, data: &[u8], width: u32, height: u32) -> Vec<u8> {
+        match self {
+            Self::Bc3 => compress_bc3(data, width, height),
+            Self::Bc7 => compress_bc7(data, width, height),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Clone, PartialEq, Eq, Hash)]
@@ -39,6 +73,8 @@ impl TextureProcessor {
             metadata.sampling_mode,
             metadata.wrapping_mode_u,
             metadata.wrapping_mode_v,
+            metadata.generate_mipmaps,
+            metadata.compression,
         )?;
 
         Ok(TextureSource::new(TextureKind::Single(element)))
@@ -70,6 +106,7 @@ impl Processor for TextureProcessor {
         resources.push(Resource {
             name: name.clone(),
             kind: ResourceKind::Texture(source),
+            metadata: BTreeMap::new(),
         });
 
         if let Some(sprites) = &metadata.sprites {
@@ -82,8 +119,9 @@ impl Processor for TextureProcessor {
                     },
                 );
                 resources.push(Resource {
-                    name: format!("{}/{}", name, sprite_name),
+                    name: format!("{}/{}", name, sanitize_resource_name(sprite_name)),
                     kind: ResourceKind::Sprite(source),
+                    metadata: BTreeMap::new(),
                 });
             }
         }
@@ -98,6 +136,8 @@ fn make_texture_element(
     sampling_mode: Option<TextureElementSamplingMode>,
     wrapping_mode_u: Option<TextureElementWrappingMode>,
     wrapping_mode_v: Option<TextureElementWrappingMode>,
+    generate_mipmaps: bool,
+    compression: Option<TextureCompression>,
 ) -> Result<TextureElement, AnyError> {
     let image = ImageReader::open(file)?.with_guessed_format()?;
     let decoded = image.decode()?;
@@ -129,6 +169,47 @@ fn make_texture_element(
         }
         TextureElementTextureFormat::RGBA8Unorm => decoded.into_rgba8().to_vec(),
         TextureElementTextureFormat::RGBA8UnormSrgb => decoded.into_rgba8().to_vec(),
+        TextureElementTextureFormat::Bc3RgbaUnorm
+        | TextureElementTextureFormat::Bc3RgbaUnormSrgb
+        | TextureElementTextureFormat::Bc7RgbaUnorm
+        | TextureElementTextureFormat::Bc7RgbaUnormSrgb => {
+            return Err(anyhow!(
+                "{:?} format is not supported; set `compression` instead",
+                texture_format
+            ));
+        }
+    };
+    let is_srgb = texture_format == TextureElementTextureFormat::RGBA8UnormSrgb;
+    let mip_data = match generate_mipmaps {
+        true => make_mip_data(&data, (width, height), is_srgb),
+        false => vec![],
+    };
+    let (texture_format, data, mip_data) = match compression {
+        Some(compression) => {
+            if width % 4 != 0 || height % 4 != 0 {
+                return Err(anyhow!(
+                    "the size of a block-compressed texture must be a multiple of 4, but it is {}x{}",
+                    width,
+                    height
+                ));
+            }
+
+            let mip_data = mip_data
+                .iter()
+                .enumerate()
+                .map(|(index, level)| {
+                    let shift = index as u32 + 1;
+                    compression.compress(level, (width >> shift).max(1), (height >> shift).max(1))
+                })
+                .collect();
+
+            (
+                compression.texture_format(is_srgb),
+                compression.compress(&data, width, height),
+                mip_data,
+            )
+        }
+        None => (texture_format, data, mip_data),
     };
 
     Ok(TextureElement {
@@ -138,5 +219,239 @@ fn make_texture_element(
         sampling_mode,
         wrapping_mode_u,
         wrapping_mode_v,
+        mip_data,
     })
 }
+
+/// Makes the mip levels below the RGBA8 texels, halving the size down to 1x1. Each texel is the
+/// average of the 2x2 texels above it; the last row or column of an odd size is folded into the
+/// last texels. sRGB colors are averaged in linear space, so that the levels do not get
+/// darker.
+fn make_mip_data(data: &[u8], (width, height): (u32, u32), is_srgb: bool) -> Vec<Vec<u8>> {
+    let decode = |value: u8, channel: usize| {
+        let value = value as f32 / 255.0;
+        match is_srgb && channel < 3 {
+            true => srgb_to_linear(value),
+            false => value,
+        }
+    };
+    let encode = |value: f32, channel: usize| {
+        let value = match is_srgb && channel < 3 {
+            true => linear_to_srgb(value),
+            false => value,
+        };
+        (value * 255.0).round().clamp(0.0, 255.0) as u8
+    };
+
+    let mut levels = Vec::<Vec<u8>>::new();
+    let (mut width, mut height) = (width, height);
+
+    while 1 < width || 1 < height {
+        let level = levels.last().map_or(data, |level| level.as_slice());
+        let next_width = (width / 2).max(1);
+        let next_height = (height / 2).max(1);
+        let mut next = vec![0; (next_width * next_height * 4) as usize];
+
+        for y in 0..next_height {
+            let ys = (y * 2).min(height - 1)..match y + 1 == next_height {
+                true => height,
+                false => y * 2 + 2,
+            };
+
+            for x in 0..next_width {
+                let xs = (x * 2).min(width - 1)..match x + 1 == next_width {
+                    true => width,
+                    false => x * 2 + 2,
+                };
+                let count = (xs.len() * ys.len()) as f32;
+
+                for channel in 0..4 {
+                    let mut sum = 0f32;
+
+                    for sy in ys.clone() {
+                        for sx in xs.clone() {
+                            let index = ((sy * width + sx) * 4) as usize + channel;
+                            sum += decode(level[index], channel);
+                        }
+                    }
+
+                    next[((y * next_width + x) * 4) as usize + channel] =
+                        encode(sum / count, channel);
+                }
+            }
+        }
+
+        levels.push(next);
+        width = next_width;
+        height = next_height;
+    }
+
+    levels
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    match value <= 0.04045 {
+        true => value / 12.92,
+        false => ((value + 0.055) / 1.055).powf(2.4),
+    }
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+    match value <= 0.0031308 {
+        true => value * 12.92,
+        false => 1.055 * value.powf(1.0 / 2.4) - 0.055,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_texture_content_equality() {
+        let dir = std::env::temp_dir().join(format!(
+            "lvl-resource-compiler-texture-equality-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("checker.png");
+        image::RgbaImage::from_fn(2, 2, |x, y| match (x + y) % 2 {
+            0 => image::Rgba([255, 0, 255, 255]),
+            _ => image::Rgba([0, 0, 0, 255]),
+        })
+        .save(&file)
+        .unwrap();
+
+        let metadata = TextureMetadata {
+            texture_format: TextureElementTextureFormat::RGBA8UnormSrgb,
+            sampling_mode: None,
+            wrapping_mode_u: None,
+            wrapping_mode_v: None,
+            sprites: None,
+            generate_mipmaps: false,
+            compression: None,
+        };
+        let first = TextureProcessor::generate_texture_source(&file, &metadata).unwrap();
+        let second = TextureProcessor::generate_texture_source(&file, &metadata).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(first, second);
+
+        let mut element = match second.kind() {
+            TextureKind::Single(element) => element.clone(),
+            TextureKind::Cubemap { .. } => unreachable!(),
+        };
+        element.data[5] ^= 1;
+
+        assert_ne!(first, TextureSource::new(TextureKind::Single(element)));
+    }
+
+    #[test]
+    fn check_mip_chain() {
+        let dir = std::env::temp_dir().join(format!(
+            "lvl-resource-compiler-texture-mip-chain-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("checker.png");
+        image::RgbaImage::from_fn(256, 256, |x, y| match (x + y) % 2 {
+            0 => image::Rgba([255, 0, 255, 255]),
+            _ => image::Rgba([0, 0, 0, 255]),
+        })
+        .save(&file)
+        .unwrap();
+
+        let metadata = TextureMetadata {
+            texture_format: TextureElementTextureFormat::RGBA8Unorm,
+            sampling_mode: Some(TextureElementSamplingMode::Trilinear),
+            wrapping_mode_u: None,
+            wrapping_mode_v: None,
+            sprites: None,
+            generate_mipmaps: true,
+            compression: None,
+        };
+        let source = TextureProcessor::generate_texture_source(&file, &metadata);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let element = match source.unwrap().kind() {
+            TextureKind::Single(element) => element.clone(),
+            TextureKind::Cubemap { .. } => unreachable!(),
+        };
+
+        assert_eq!(element.mip_level_count(), 9);
+
+        for level in 0..9 {
+            let side = 256 >> level;
+            assert_eq!(
+                element.mip_level_size(level),
+                TextureElementSize {
+                    width: side,
+                    height: side
+                }
+            );
+            assert_eq!(
+                element.mip_level_data(level).unwrap().len(),
+                side as usize * side as usize * 4
+            );
+        }
+
+        // the checker averages out from the first level on
+        assert_eq!(element.mip_level_data(8), Some(&[128, 0, 128, 255][..]));
+    }
+
+    #[test]
+    fn check_compressed_size() {
+        let dir = std::env::temp_dir().join(format!(
+            "lvl-resource-compiler-texture-compressed-size-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("gradient.png");
+        image::RgbaImage::from_fn(64, 32, |x, y| {
+            image::Rgba([x as u8 * 4, y as u8 * 8, 0, 255])
+        })
+        .save(&file)
+        .unwrap();
+        let odd_file = dir.join("odd.png");
+        image::RgbaImage::new(6, 4).save(&odd_file).unwrap();
+
+        let metadata = TextureMetadata {
+            texture_format: TextureElementTextureFormat::RGBA8UnormSrgb,
+            sampling_mode: Some(TextureElementSamplingMode::Trilinear),
+            wrapping_mode_u: None,
+            wrapping_mode_v: None,
+            sprites: None,
+            generate_mipmaps: true,
+            compression: Some(TextureCompression::Bc7),
+        };
+        let source = TextureProcessor::generate_texture_source(&file, &metadata);
+        let odd_source = TextureProcessor::generate_texture_source(&odd_file, &metadata);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let element = match source.unwrap().kind() {
+            TextureKind::Single(element) => element.clone(),
+            TextureKind::Cubemap { .. } => unreachable!(),
+        };
+
+        assert_eq!(
+            element.texture_format,
+            TextureElementTextureFormat::Bc7RgbaUnormSrgb
+        );
+        assert_eq!(element.data.len(), (64 / 4) * (32 / 4) * 16);
+        assert_eq!(element.mip_level_count(), 7);
+
+        for level in 0..element.mip_level_count() {
+            let size = element.mip_level_size(level);
+            let (columns, rows) = element.texture_format.block_count(size);
+
+            assert_eq!(
+                element.mip_level_data(level).unwrap().len(),
+                columns as usize * rows as usize * 16
+            );
+        }
+
+        // the 2x1 and 1x1 levels still take a whole block
+        assert_eq!(element.mip_level_data(6).unwrap().len(), 16);
+        assert!(odd_source.is_err());
+    }
+}
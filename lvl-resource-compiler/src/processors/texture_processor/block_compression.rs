@@ -0,0 +1,429 @@
+//! Encoders of the BC3 and BC7 block-compressed formats. Both split the image into 4x4 blocks of
+//! 16 bytes each, in row-major order; blocks that overhang the right or bottom edge repeat the
+//! texels of the edge.
+
+/// The RGBA8 texels of a block, in row-major order.
+type Block = [[u8; 4]; 16];
+
+/// Compresses the RGBA8 texels into BC3 blocks.
+pub fn compress_bc3(data: &[u8], width: u32, height: u32) -> Vec<u8> {
+    compress_blocks(data, width, height, encode_bc3_block)
+}
+
+/// Compresses the RGBA8 texels into BC7 blocks.
+pub fn compress_bc7(data: &[u8], width: u32, height: u32) -> Vec<u8> {
+    compress_blocks(data, width, height, encode_bc7_block)
+}
+
+fn compress_blocks(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    encode: impl Fn(&Block) -> [u8; 16],
+) -> Vec<u8> {
+    let columns = width.div_ceil(4);
+    let rows = height.div_ceil(4);
+    let mut compressed = Vec::with_capacity((columns * rows * 16) as usize);
+
+    for row in 0..rows {
+        for column in 0..columns {
+            let mut block = [[0u8; 4]; 16];
+
+            for (index, texel) in block.iter_mut().enumerate() {
+                let x = (column * 4 + index as u32 % 4).min(width - 1);
+                let y = (row * 4 + index as u32 / 4).min(height - 1);
+                let offset = ((y * width + x) * 4) as usize;
+                texel.copy_from_slice(&data[offset..offset + 4]);
+            }
+
+            compressed.extend_from_slice(&encode(&block));
+        }
+    }
+
+    compressed
+}
+
+/// Encodes the block as BC3: the alpha as 8 levels between two endpoints, followed by the color
+/// as a BC1 block of 4 levels between two RGB565 endpoints.
+fn encode_bc3_block(block: &Block) -> [u8; 16] {
+    let mut encoded = [0u8; 16];
+
+    let alpha_max = block.iter().map(|texel| texel[3]).max().unwrap();
+    let alpha_min = block.iter().map(|texel| texel[3]).min().unwrap();
+    // with the first endpoint greater, the 6 levels between the endpoints are interpolated
+    let alpha_levels: [i32; 8] = std::array::from_fn(|code| {
+        let (a0, a1) = (alpha_max as i32, alpha_min as i32);
+        match code {
+            0 => a0,
+            1 => a1,
+            _ => ((8 - code as i32) * a0 + (code as i32 - 1) * a1) / 7,
+        }
+    });
+    let mut alpha_indices = 0u64;
+
+    if alpha_min != alpha_max {
+        for (index, texel) in block.iter().enumerate() {
+            let code = nearest(&alpha_levels, |level| (level - texel[3] as i32).pow(2));
+            alpha_indices |= (code as u64) << (index * 3);
+        }
+    }
+
+    encoded[0] = alpha_max;
+    encoded[1] = alpha_min;
+    encoded[2..8].copy_from_slice(&alpha_indices.to_le_bytes()[..6]);
+
+    let (low, high) = principal_endpoints(block, 3);
+    let mut color0 = to_rgb565(high);
+    let mut color1 = to_rgb565(low);
+
+    // the 4 level mode needs the first endpoint to be greater
+    if color0 < color1 {
+        std::mem::swap(&mut color0, &mut color1);
+    }
+
+    let mut color_indices = 0u32;
+
+    if color0 != color1 {
+        let c0 = from_rgb565(color0);
+        let c1 = from_rgb565(color1);
+        let color_levels: [[i32; 3]; 4] = [
+            c0,
+            c1,
+            std::array::from_fn(|channel| (2 * c0[channel] + c1[channel]) / 3),
+            std::array::from_fn(|channel| (c0[channel] + 2 * c1[channel]) / 3),
+        ];
+
+        for (index, texel) in block.iter().enumerate() {
+            let code = nearest(&color_levels, |level| distance(level, texel));
+            color_indices |= (code as u32) << (index * 2);
+        }
+    }
+
+    encoded[8..10].copy_from_slice(&color0.to_le_bytes());
+    encoded[10..12].copy_from_slice(&color1.to_le_bytes());
+    encoded[12..16].copy_from_slice(&color_indices.to_le_bytes());
+    encoded
+}
+
+/// Encodes the block as BC7 in mode 6: a single subset of 16 levels between two RGBA
+/// endpoints of 7 bits plus a shared lowest bit per endpoint.
+fn encode_bc7_block(block: &Block) -> [u8; 16] {
+    const WEIGHTS: [i32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+    let (low, high) = principal_endpoints(block, 4);
+    let mut endpoints = [quantize_bc7_endpoint(low), quantize_bc7_endpoint(high)];
+    let values = endpoints.map(|(quantized, p_bit)| quantized.map(|value| value << 1 | p_bit));
+    let levels: [[i32; 4]; 16] = std::array::from_fn(|code| {
+        std::array::from_fn(|channel| {
+            let (e0, e1) = (values[0][channel] as i32, values[1][channel] as i32);
+            ((64 - WEIGHTS[code]) * e0 + WEIGHTS[code] * e1 + 32) >> 6
+        })
+    });
+    let mut indices = block.map(|texel| nearest(&levels, |level| distance(level, &texel)));
+
+    // the highest bit of the first index is implied to be zero
+    if 8 <= indices[0] {
+        endpoints.swap(0, 1);
+        indices = indices.map(|index| 15 - index);
+    }
+
+    let mut bits = BitWriter::default();
+    bits.write(1 << 6, 7);
+
+    for channel in 0..4 {
+        for (quantized, _) in &endpoints {
+            bits.write(quantized[channel] as u128, 7);
+        }
+    }
+
+    for (_, p_bit) in &endpoints {
+        bits.write(*p_bit as u128, 1);
+    }
+
+    for (index, code) in indices.iter().enumerate() {
+        bits.write(*code as u128, if index == 0 { 3 } else { 4 });
+    }
+
+    bits.value.to_le_bytes()
+}
+
+/// Quantizes the endpoint to 7 bits per channel and picks the shared lowest bit that is closer.
+fn quantize_bc7_endpoint(endpoint: [f32; 4]) -> ([u8; 4], u8) {
+    (0..2u8)
+        .map(|p_bit| {
+            let quantized = endpoint
+                .map(|value| ((value - p_bit as f32) / 2.0).round().clamp(0.0, 127.0) as u8);
+            let error = (0..4)
+                .map(|channel| {
+                    let value = (quantized[channel] << 1 | p_bit) as f32;
+                    (value - endpoint[channel]).powi(2)
+                })
+                .sum::<f32>();
+            ((quantized, p_bit), error)
+        })
+        .min_by(|(_, lhs), (_, rhs)| lhs.total_cmp(rhs))
+        .unwrap()
+        .0
+}
+
+/// Returns the extremes of the texels along the axis they vary the most on, found by power
+/// iteration on their covariance. Only the first `channels` channels are considered; the
+/// others are left zero.
+fn principal_endpoints(block: &Block, channels: usize) -> ([f32; 4], [f32; 4]) {
+    let texels = block.map(|texel| texel.map(|value| value as f32));
+    let mut mean = [0f32; 4];
+
+    for texel in &texels {
+        for channel in 0..channels {
+            mean[channel] += texel[channel] / 16.0;
+        }
+    }
+
+    let mut covariance = [[0f32; 4]; 4];
+
+    for texel in &texels {
+        for row in 0..channels {
+            for column in 0..channels {
+                covariance[row][column] +=
+                    (texel[row] - mean[row]) * (texel[column] - mean[column]);
+            }
+        }
+    }
+
+    // starting from the extent of the bounding box keeps the axis away from being orthogonal
+    let mut axis: [f32; 4] = std::array::from_fn(|channel| {
+        let max = texels
+            .iter()
+            .map(|texel| texel[channel])
+            .fold(0f32, f32::max);
+        let min = texels
+            .iter()
+            .map(|texel| texel[channel])
+            .fold(255f32, f32::min);
+        match channel < channels {
+            true => max - min,
+            false => 0.0,
+        }
+    });
+
+    for _ in 0..8 {
+        let next: [f32; 4] = std::array::from_fn(|row| {
+            (0..4)
+                .map(|column| covariance[row][column] * axis[column])
+                .sum()
+        });
+        let length = next.iter().map(|value| value * value).sum::<f32>().sqrt();
+
+        if length < f32::EPSILON {
+            break;
+        }
+
+        axis = next.map(|value| value / length);
+    }
+
+    let length = axis.iter().map(|value| value * value).sum::<f32>().sqrt();
+
+    if length < f32::EPSILON {
+        return (mean, mean);
+    }
+
+    axis = axis.map(|value| value / length);
+
+    let projections = texels.map(|texel| {
+        (0..channels)
+            .map(|channel| (texel[channel] - mean[channel]) * axis[channel])
+            .sum::<f32>()
+    });
+    let min = projections.iter().copied().fold(f32::MAX, f32::min);
+    let max = projections.iter().copied().fold(f32::MIN, f32::max);
+    let along =
+        |t: f32| -> [f32; 4] { std::array::from_fn(|channel| mean[channel] + axis[channel] * t) };
+
+    (along(min), along(max))
+}
+
+fn to_rgb565(color: [f32; 4]) -> u16 {
+    let quantize = |value: f32, max: f32| (value / 255.0 * max).round().clamp(0.0, max) as u16;
+    quantize(color[0], 31.0) << 11 | quantize(color[1], 63.0) << 5 | quantize(color[2], 31.0)
+}
+
+fn from_rgb565(color: u16) -> [i32; 3] {
+    let r = (color >> 11 & 0x1f) as i32;
+    let g = (color >> 5 & 0x3f) as i32;
+    let b = (color & 0x1f) as i32;
+    [r << 3 | r >> 2, g << 2 | g >> 4, b << 3 | b >> 2]
+}
+
+/// Returns the squared distance between the level and the texel over the channels of the level.
+fn distance<const N: usize>(level: &[i32; N], texel: &[u8; 4]) -> i32 {
+    (0..N)
+        .map(|channel| (level[channel] - texel[channel] as i32).pow(2))
+        .sum()
+}
+
+/// Returns the index of the level with the least error.
+fn nearest<T>(levels: &[T], error: impl Fn(&T) -> i32) -> u8 {
+    (0..levels.len())
+        .min_by_key(|&index| error(&levels[index]))
+        .unwrap() as u8
+}
+
+/// Packs values into a 128-bit block from the lowest bit up.
+#[derive(Default)]
+struct BitWriter {
+    value: u128,
+    offset: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u128, bits: u32) {
+        self.value |= value << self.offset;
+        self.offset += bits;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_bc3_block(encoded: &[u8]) -> Block {
+        let (a0, a1) = (encoded[0] as i32, encoded[1] as i32);
+        let alpha_indices = u64::from_le_bytes(std::array::from_fn(|index| match index < 6 {
+            true => encoded[2 + index],
+            false => 0,
+        }));
+        let color0 = u16::from_le_bytes([encoded[8], encoded[9]]);
+        let color1 = u16::from_le_bytes([encoded[10], encoded[11]]);
+        let color_indices =
+            u32::from_le_bytes([encoded[12], encoded[13], encoded[14], encoded[15]]);
+        let (c0, c1) = (from_rgb565(color0), from_rgb565(color1));
+
+        std::array::from_fn(|index| {
+            let alpha_code = (alpha_indices >> (index * 3) & 0x7) as i32;
+            let alpha = match (alpha_code, a0 > a1) {
+                (0, _) => a0,
+                (1, _) => a1,
+                (code, true) => ((8 - code) * a0 + (code - 1) * a1) / 7,
+                (code, false) => match code {
+                    6 => 0,
+                    7 => 255,
+                    _ => ((6 - code) * a0 + (code - 1) * a1) / 5,
+                },
+            };
+            let color_code = color_indices >> (index * 2) & 0x3;
+            let color: [i32; 3] = std::array::from_fn(|channel| match color_code {
+                0 => c0[channel],
+                1 => c1[channel],
+                2 => (2 * c0[channel] + c1[channel]) / 3,
+                _ => (c0[channel] + 2 * c1[channel]) / 3,
+            });
+
+            [color[0] as u8, color[1] as u8, color[2] as u8, alpha as u8]
+        })
+    }
+
+    fn decode_bc7_mode6_block(encoded: &[u8]) -> Block {
+        const WEIGHTS: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+        let value = u128::from_le_bytes(encoded.try_into().unwrap());
+        let mut offset = 0;
+        let mut read = |bits: u32| {
+            let field = (value >> offset) as u32 & ((1 << bits) - 1);
+            offset += bits;
+            field
+        };
+
+        assert_eq!(read(7), 1 << 6);
+
+        let mut endpoints = [[0u32; 4]; 2];
+
+        for channel in 0..4 {
+            for endpoint in &mut endpoints {
+                endpoint[channel] = read(7);
+            }
+        }
+
+        for endpoint in &mut endpoints {
+            let p_bit = read(1);
+            *endpoint = endpoint.map(|value| value << 1 | p_bit);
+        }
+
+        std::array::from_fn(|index| {
+            let weight = WEIGHTS[read(if index == 0 { 3 } else { 4 }) as usize];
+            std::array::from_fn(|channel| {
+                (((64 - weight) * endpoints[0][channel] + weight * endpoints[1][channel] + 32) >> 6)
+                    as u8
+            })
+        })
+    }
+
+    /// A 8x8 image of horizontal gradients, which lie on a line within each block.
+    fn make_gradient() -> Vec<u8> {
+        (0..64u32)
+            .flat_map(|index| {
+                let x = index % 8;
+                [
+                    (x * 32) as u8,
+                    255 - (x * 32) as u8,
+                    (x * 16) as u8,
+                    255 - (x * 24) as u8,
+                ]
+            })
+            .collect()
+    }
+
+    fn max_error(data: &[u8], compressed: &[u8], decode: impl Fn(&[u8]) -> Block) -> i32 {
+        let mut max_error = 0;
+
+        for (block_index, encoded) in compressed.chunks(16).enumerate() {
+            let (column, row) = (block_index as u32 % 2, block_index as u32 / 2);
+
+            for (index, texel) in decode(encoded).iter().enumerate() {
+                let x = column * 4 + index as u32 % 4;
+                let y = row * 4 + index as u32 / 4;
+                let offset = ((y * 8 + x) * 4) as usize;
+
+                for channel in 0..4 {
+                    let error = (texel[channel] as i32 - data[offset + channel] as i32).abs();
+                    max_error = max_error.max(error);
+                }
+            }
+        }
+
+        max_error
+    }
+
+    #[test]
+    fn check_bc3_round_trip() {
+        let data = make_gradient();
+        let compressed = compress_bc3(&data, 8, 8);
+
+        assert_eq!(compressed.len(), 4 * 16);
+        assert!(max_error(&data, &compressed, decode_bc3_block) <= 16);
+
+        let solid = [200, 100, 50, 128].repeat(16);
+        let block = decode_bc3_block(&compress_bc3(&solid, 4, 4));
+        assert!(block.iter().all(|texel| texel == &block[0]));
+        assert_eq!(block[0][3], 128);
+    }
+
+    #[test]
+    fn check_bc7_round_trip() {
+        let data = make_gradient();
+        let compressed = compress_bc7(&data, 8, 8);
+
+        assert_eq!(compressed.len(), 4 * 16);
+        assert!(max_error(&data, &compressed, decode_bc7_mode6_block) <= 6);
+
+        let solid = [200, 100, 50, 128].repeat(16);
+        let block = decode_bc7_mode6_block(&compress_bc7(&solid, 4, 4));
+        assert!(block.iter().all(|texel| texel == &[200, 100, 50, 128]));
+    }
+
+    #[test]
+    fn check_partial_blocks() {
+        // a 1x1 mip level still takes a whole block
+        assert_eq!(compress_bc3(&[1, 2, 3, 4], 1, 1).len(), 16);
+        assert_eq!(compress_bc7(&[1, 2, 3, 4].repeat(6), 3, 2).len(), 16);
+    }
+}
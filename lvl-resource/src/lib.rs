@@ -1,17 +1,23 @@
+mod load_order;
 mod material_source;
 mod mesh_source;
 mod model_source;
 mod pmx_model_animation_source;
 mod pmx_model_source;
+mod resource_diff;
+mod resource_file_codec;
 mod shader_source;
 mod sprite_source;
 mod texture_source;
 
+pub use load_order::*;
 pub use material_source::*;
 pub use mesh_source::*;
 pub use model_source::*;
 pub use pmx_model_animation_source::*;
 pub use pmx_model_source::*;
+pub use resource_diff::*;
+pub use resource_file_codec::*;
 pub use shader_source::*;
 pub use sprite_source::*;
 pub use texture_source::*;
@@ -20,6 +26,7 @@ use serde::{Deserialize, Serialize};
 use std::{
     collections::BTreeMap,
     fmt::{Display, Formatter, Result as FmtResult},
+    ops::Bound,
 };
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -60,17 +67,66 @@ impl ResourceFile {
     pub fn find_by_name(&self, name: &str) -> Option<&Resource> {
         self.resources.get(name)
     }
+
+    /// Returns all the resources of the kind, with their names, in name order.
+    pub fn find_all<'a, T>(&'a self) -> impl Iterator<Item = (&'a str, &'a T)>
+    where
+        T: 'a + FromResourceKind,
+    {
+        self.resources
+            .iter()
+            .filter_map(|(name, resource)| Some((name.as_str(), T::from(&resource.kind)?)))
+    }
+
+    /// Returns the resources whose names start with the prefix, in name order. Names follow the
+    /// `model/kind:subname` convention, so e.g. `"model/"` finds everything of a model.
+    pub fn find_by_prefix<'a>(&'a self, prefix: &'a str) -> impl Iterator<Item = &'a Resource> {
+        self.resources
+            .range::<str, _>((Bound::Included(prefix), Bound::Unbounded))
+            .take_while(move |(name, _)| name.starts_with(prefix))
+            .map(|(_, resource)| resource)
+    }
+
+    /// Returns the names of the resources the given one refers to; see
+    /// [`ResourceKind::dependencies`]. Returns `None` if there is no such resource.
+    pub fn dependencies(&self, name: &str) -> Option<Vec<&str>> {
+        Some(self.resources.get(name)?.kind.dependencies())
+    }
+
+    /// Returns the names of all the resources, ordered so that every resource comes after the
+    /// ones it depends on. Loading in this order never meets an unresolved reference.
+    pub fn load_order(&self) -> Result<Vec<&str>, ResourceLoadOrderError> {
+        load_order::load_order(self)
+    }
+
+    /// Compares this file with a newer one; see [`ResourceDiff`].
+    pub fn diff(&self, other: &ResourceFile) -> ResourceDiff {
+        ResourceDiff::new(self, other)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum ResourceFileVersion {
+    /// The layout before resource metadata, texture mips, mesh topologies and precompiled
+    /// shaders. It cannot be read anymore; the resources must be recompiled.
     V1,
+    V2,
+    /// Same as [`ResourceFileVersion::V2`], but the resources are compressed; see
+    /// [`ResourceFile::write_compressed`].
+    V3,
+}
+
+impl ResourceFileVersion {
+    /// The version of the uncompressed files written by this build.
+    pub const CURRENT: Self = Self::V2;
 }
 
 impl Display for ResourceFileVersion {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
         match self {
             Self::V1 => write!(f, "v1"),
+            Self::V2 => write!(f, "v2"),
+            Self::V3 => write!(f, "v3"),
         }
     }
 }
@@ -79,6 +135,9 @@ impl Display for ResourceFileVersion {
 pub struct Resource {
     pub name: String,
     pub kind: ResourceKind,
+    /// Arbitrary key/value pairs attached by the author, e.g. author or license.
+    /// The engine does not use them; they are kept for tools.
+    pub metadata: BTreeMap<String, String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -154,3 +213,88 @@ impl ResourceKind {
 pub trait FromResourceKind {
     fn from(kind: &ResourceKind) -> Option<&Self>;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sprite(name: &str) -> Resource {
+        Resource {
+            name: name.to_owned(),
+            kind: ResourceKind::Sprite(SpriteSource::new(
+                "texture".to_owned(),
+                SpriteMapping {
+                    min: (0, 0),
+                    max: (1, 1),
+                },
+            )),
+            metadata: Default::default(),
+        }
+    }
+
+    fn shader(name: &str) -> Resource {
+        Resource {
+            name: name.to_owned(),
+            kind: ResourceKind::Shader(ShaderSource::new(make_shader_source_descriptor(""))),
+            metadata: Default::default(),
+        }
+    }
+
+    fn make_file() -> ResourceFile {
+        ResourceFile::new(
+            ResourceFileVersion::CURRENT,
+            vec![
+                sprite("model/sprite:b"),
+                shader("model/shader:a"),
+                sprite("model/sprite:a"),
+                sprite("model2/sprite:a"),
+                shader("other"),
+            ],
+        )
+    }
+
+    #[test]
+    fn check_find_all() {
+        let file = make_file();
+
+        assert_eq!(
+            Vec::from_iter(file.find_all::<SpriteSource>().map(|(name, _)| name)),
+            vec!["model/sprite:a", "model/sprite:b", "model2/sprite:a"]
+        );
+        assert_eq!(
+            Vec::from_iter(file.find_all::<ShaderSource>().map(|(name, _)| name)),
+            vec!["model/shader:a", "other"]
+        );
+        assert_eq!(file.find_all::<TextureSource>().count(), 0);
+    }
+
+    #[test]
+    fn check_find_by_prefix() {
+        let file = make_file();
+        let names = |prefix| {
+            Vec::from_iter(
+                file.find_by_prefix(prefix)
+                    .map(|resource| resource.name.as_str()),
+            )
+        };
+
+        assert_eq!(
+            names("model/"),
+            vec!["model/shader:a", "model/sprite:a", "model/sprite:b"]
+        );
+        assert_eq!(
+            names("model/sprite:"),
+            vec!["model/sprite:a", "model/sprite:b"]
+        );
+        assert_eq!(
+            names("model"),
+            vec![
+                "model/shader:a",
+                "model/sprite:a",
+                "model/sprite:b",
+                "model2/sprite:a"
+            ]
+        );
+        assert!(names("missing").is_empty());
+    }
+}
@@ -0,0 +1,171 @@
+use crate::{MaterialPropertyValue, ResourceFile, ResourceKind};
+use std::collections::{BTreeMap, BTreeSet};
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ResourceLoadOrderError {
+    #[error("the resources depend on each other in a cycle: {}", .0.join(" -> "))]
+    Cycle(Vec<String>),
+}
+
+impl ResourceKind {
+    /// Returns the names of the resources this one refers to, in name order.
+    pub fn dependencies(&self) -> Vec<&str> {
+        let mut names = BTreeSet::new();
+
+        match self {
+            Self::Material(source) => {
+                names.insert(source.shader_name());
+
+                for property in source.properties().values() {
+                    if let MaterialPropertyValue::Texture { texture_name } = &property.value {
+                        names.insert(texture_name);
+                    }
+                }
+            }
+            Self::Model(source) => {
+                for part in source
+                    .elements()
+                    .iter()
+                    .flat_map(|element| &element.visible_parts)
+                {
+                    names.insert(&part.mesh_name);
+                    names.insert(&part.material_name);
+                }
+            }
+            Self::PmxModel(source) => {
+                for element in source.elements() {
+                    names.insert(&element.material_name);
+                }
+
+                names.insert(source.vertex_morph_index_texture_name());
+                names.insert(source.uv_morph_index_texture_name());
+                names.insert(source.vertex_displacement_texture_name());
+                names.insert(source.uv_displacement_texture_name());
+            }
+            Self::Sprite(source) => {
+                names.insert(source.texture_name());
+            }
+            Self::Mesh(_) | Self::PmxModelAnimation(_) | Self::Shader(_) | Self::Texture(_) => {}
+        }
+
+        names.into_iter().collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum VisitState {
+    Visiting,
+    Visited,
+}
+
+/// Sorts the resources so that every resource comes after the ones it depends on. Resources
+/// that do not depend on each other are kept in name order. Dependencies missing from the file
+/// are ignored.
+pub(crate) fn load_order(file: &ResourceFile) -> Result<Vec<&str>, ResourceLoadOrderError> {
+    let mut states = BTreeMap::<&str, VisitState>::new();
+    let mut order = Vec::with_capacity(file.resources().len());
+
+    for name in file.resources().keys() {
+        visit(file, name, &mut states, &mut Vec::new(), &mut order)?;
+    }
+
+    Ok(order)
+}
+
+fn visit<'a>(
+    file: &'a ResourceFile,
+    name: &'a str,
+    states: &mut BTreeMap<&'a str, VisitState>,
+    path: &mut Vec<&'a str>,
+    order: &mut Vec<&'a str>,
+) -> Result<(), ResourceLoadOrderError> {
+    let resource = match file.find_by_name(name) {
+        Some(resource) => resource,
+        None => {
+            return Ok(());
+        }
+    };
+
+    match states.get(name) {
+        Some(VisitState::Visited) => {
+            return Ok(());
+        }
+        Some(VisitState::Visiting) => {
+            let start = path.iter().position(|&visiting| visiting == name).unwrap();
+            let mut cycle = Vec::from_iter(path[start..].iter().map(|&name| name.to_owned()));
+            cycle.push(name.to_owned());
+            return Err(ResourceLoadOrderError::Cycle(cycle));
+        }
+        None => {}
+    }
+
+    states.insert(&resource.name, VisitState::Visiting);
+    path.push(&resource.name);
+
+    for dependency in resource.kind.dependencies() {
+        visit(file, dependency, states, path, order)?;
+    }
+
+    path.pop();
+    states.insert(&resource.name, VisitState::Visited);
+    order.push(&resource.name);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        make_material_render_state, make_shader_source_descriptor, MaterialProperty,
+        MaterialSource, Resource, ResourceFileVersion, ShaderSource,
+    };
+
+    fn material(name: &str, shader_name: &str) -> Resource {
+        Resource {
+            name: name.to_owned(),
+            kind: ResourceKind::Material(MaterialSource::new(
+                shader_name.to_owned(),
+                make_material_render_state(),
+                Vec::<MaterialProperty>::new(),
+            )),
+            metadata: Default::default(),
+        }
+    }
+
+    #[test]
+    fn check_shader_before_material() {
+        let file = ResourceFile::new(
+            ResourceFileVersion::CURRENT,
+            vec![
+                material("a/material", "z/shader"),
+                Resource {
+                    name: "z/shader".to_owned(),
+                    kind: ResourceKind::Shader(ShaderSource::new(make_shader_source_descriptor(
+                        "",
+                    ))),
+                    metadata: Default::default(),
+                },
+            ],
+        );
+
+        assert_eq!(file.load_order(), Ok(vec!["z/shader", "a/material"]));
+    }
+
+    #[test]
+    fn check_cycle() {
+        let file = ResourceFile::new(
+            ResourceFileVersion::CURRENT,
+            vec![material("a", "b"), material("b", "a")],
+        );
+
+        assert_eq!(
+            file.load_order(),
+            Err(ResourceLoadOrderError::Cycle(vec![
+                "a".to_owned(),
+                "b".to_owned(),
+                "a".to_owned()
+            ]))
+        );
+    }
+}
@@ -4,7 +4,7 @@ use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use wgpu_types::{AddressMode, CompareFunction, FilterMode, SamplerBorderColor};
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct MaterialSource {
     shader_name: String,
     render_state: MaterialRenderState,
@@ -59,19 +59,35 @@ pub struct MaterialRenderState {
     pub line_drawing: bool,
 }
 
+/// Makes the render state of an opaque material with every option turned off, for tests.
+#[cfg(test)]
+pub(crate) fn make_material_render_state() -> MaterialRenderState {
+    MaterialRenderState {
+        render_type: MaterialRenderType::Opaque,
+        no_cull_back_face: false,
+        cast_shadow_on_ground: false,
+        cast_shadow_on_object: false,
+        receive_shadow: false,
+        has_edge: false,
+        vertex_color: false,
+        point_drawing: false,
+        line_drawing: false,
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum MaterialRenderType {
     Opaque,
     Transparent,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct MaterialProperty {
     pub name: String,
     pub value: MaterialPropertyValue,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum MaterialPropertyValue {
     Uniform(MaterialPropertyUniformValue),
     Texture {
@@ -92,7 +108,7 @@ pub enum MaterialPropertyValue {
     },
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum MaterialPropertyUniformValue {
     Float(f32),
     Vec2(Vec2),
@@ -1,12 +1,28 @@
 use crate::{FromResourceKind, ResourceKind};
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use wgpu_types::{PrimitiveTopology, VertexFormat};
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum MeshIndexError {
+    #[error("the index data of {0} bytes is not a whole number of indices")]
+    PartialIndex(usize),
+    #[error("{index_count} indices do not make whole {topology:?} primitives")]
+    PartialPrimitive {
+        index_count: usize,
+        topology: MeshTopology,
+    },
+    #[error("the index {index} is out of the {vertex_count} vertices")]
+    IndexOutOfRange { index: u32, vertex_count: u32 },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct MeshSource {
     vertex_count: u32,
     vertex_data: Vec<u8>,
     index_data: Vec<u8>,
     index_kind: MeshIndexKind,
+    topology: MeshTopology,
     elements: Vec<MeshElement>,
 }
 
@@ -16,6 +32,7 @@ impl MeshSource {
         vertex_data: Vec<u8>,
         index_data: Vec<u8>,
         index_kind: MeshIndexKind,
+        topology: MeshTopology,
         elements: Vec<MeshElement>,
     ) -> Self {
         Self {
@@ -23,6 +40,7 @@ impl MeshSource {
             vertex_data,
             index_data,
             index_kind,
+            topology,
             elements,
         }
     }
@@ -43,9 +61,51 @@ impl MeshSource {
         self.index_kind
     }
 
+    pub fn topology(&self) -> MeshTopology {
+        self.topology
+    }
+
     pub fn elements(&self) -> &[MeshElement] {
         &self.elements
     }
+
+    /// Checks that the index data is made of whole primitives of the topology, and that every
+    /// index refers to a vertex.
+    pub fn validate_indices(&self) -> Result<(), MeshIndexError> {
+        let index_size = self.index_kind.size();
+
+        if !self.index_data.len().is_multiple_of(index_size) {
+            return Err(MeshIndexError::PartialIndex(self.index_data.len()));
+        }
+
+        let index_count = self.index_data.len() / index_size;
+
+        if !index_count.is_multiple_of(self.topology.indices_per_primitive()) {
+            return Err(MeshIndexError::PartialPrimitive {
+                index_count,
+                topology: self.topology,
+            });
+        }
+
+        let indices = self
+            .index_data
+            .chunks_exact(index_size)
+            .map(|bytes| match self.index_kind {
+                MeshIndexKind::U16 => u16::from_le_bytes([bytes[0], bytes[1]]) as u32,
+                MeshIndexKind::U32 => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            });
+
+        for index in indices {
+            if self.vertex_count <= index {
+                return Err(MeshIndexError::IndexOutOfRange {
+                    index,
+                    vertex_count: self.vertex_count,
+                });
+            }
+        }
+
+        Ok(())
+    }
 }
 
 impl FromResourceKind for MeshSource {
@@ -65,6 +125,44 @@ pub enum MeshIndexKind {
     U32,
 }
 
+impl MeshIndexKind {
+    /// Returns the size of an index in bytes.
+    pub fn size(self) -> usize {
+        match self {
+            Self::U16 => 2,
+            Self::U32 => 4,
+        }
+    }
+}
+
+/// How the indices of a mesh are assembled into primitives.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MeshTopology {
+    TriangleList,
+    /// Every two indices make a segment, e.g. for debug geometry or splines.
+    LineList,
+    PointList,
+}
+
+impl MeshTopology {
+    pub fn indices_per_primitive(self) -> usize {
+        match self {
+            Self::TriangleList => 3,
+            Self::LineList => 2,
+            Self::PointList => 1,
+        }
+    }
+
+    /// Returns the topology the render pipeline of the mesh is built with.
+    pub fn primitive_topology(self) -> PrimitiveTopology {
+        match self {
+            Self::TriangleList => PrimitiveTopology::TriangleList,
+            Self::LineList => PrimitiveTopology::LineList,
+            Self::PointList => PrimitiveTopology::PointList,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct MeshElement {
     pub name: String,
@@ -85,3 +183,68 @@ pub enum MeshElementKind {
     /// vec4
     Additional(u8),
 }
+
+impl MeshElementKind {
+    /// Returns the format the element is bound with.
+    pub fn vertex_format(self) -> VertexFormat {
+        match self {
+            Self::Position => VertexFormat::Float32x3,
+            Self::Normal => VertexFormat::Float32x3,
+            Self::TexCoord(_) => VertexFormat::Float32x2,
+            Self::Tangent => VertexFormat::Float32x3,
+            Self::Additional(_) => VertexFormat::Float32x4,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_mesh(vertex_count: u32, indices: &[u16], topology: MeshTopology) -> MeshSource {
+        MeshSource::new(
+            vertex_count,
+            vec![0; vertex_count as usize * 12],
+            indices
+                .iter()
+                .flat_map(|index| index.to_le_bytes())
+                .collect(),
+            MeshIndexKind::U16,
+            topology,
+            vec![MeshElement {
+                name: "position".to_owned(),
+                kind: MeshElementKind::Position,
+                offset: 0,
+            }],
+        )
+    }
+
+    #[test]
+    fn check_line_mesh() {
+        let spline = make_mesh(3, &[0, 1, 1, 2], MeshTopology::LineList);
+
+        assert_eq!(
+            spline.topology().primitive_topology(),
+            PrimitiveTopology::LineList
+        );
+        assert_eq!(spline.validate_indices(), Ok(()));
+        assert_eq!(
+            make_mesh(3, &[0, 1, 2], MeshTopology::LineList).validate_indices(),
+            Err(MeshIndexError::PartialPrimitive {
+                index_count: 3,
+                topology: MeshTopology::LineList,
+            })
+        );
+        assert_eq!(
+            make_mesh(3, &[0, 3], MeshTopology::LineList).validate_indices(),
+            Err(MeshIndexError::IndexOutOfRange {
+                index: 3,
+                vertex_count: 3,
+            })
+        );
+        assert_eq!(
+            make_mesh(3, &[0, 1, 2], MeshTopology::PointList).validate_indices(),
+            Ok(())
+        );
+    }
+}
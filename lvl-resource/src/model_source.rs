@@ -1,5 +1,5 @@
 use crate::{FromResourceKind, ResourceKind};
-use lvl_math::{Quat, Vec3};
+use lvl_math::{Aabb, Mat4, Quat, Vec3};
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -23,6 +23,45 @@ impl ModelSource {
     pub fn elements(&self) -> &[ModelElement] {
         &self.elements
     }
+
+    /// Returns the bounds of the visible parts of all elements reachable from the root, in the
+    /// space the model is spawned in. The bounds of each mesh are given by `mesh_bounds`;
+    /// returns `None` if no visible part has bounds.
+    pub fn local_bounds(&self, mesh_bounds: impl Fn(&str) -> Option<Aabb>) -> Option<Aabb> {
+        let mut bounds: Option<Aabb> = None;
+        let mut stack = vec![(self.root_element_index, Mat4::identity())];
+
+        while let Some((element_index, parent_matrix)) = stack.pop() {
+            let element = match self
+                .elements
+                .iter()
+                .find(|element| element.index == element_index)
+            {
+                Some(element) => element,
+                None => continue,
+            };
+            let matrix = element.transform.matrix() * parent_matrix;
+
+            for visible_part in &element.visible_parts {
+                if let Some(mesh_bounds) = mesh_bounds(&visible_part.mesh_name) {
+                    let part_bounds = mesh_bounds.transformed(&matrix);
+                    bounds = Some(match bounds {
+                        Some(bounds) => bounds.union(&part_bounds),
+                        None => part_bounds,
+                    });
+                }
+            }
+
+            stack.extend(
+                self.elements
+                    .iter()
+                    .filter(|child| child.parent_index == Some(element_index))
+                    .map(|child| (child.index, matrix.clone())),
+            );
+        }
+
+        bounds
+    }
 }
 
 impl FromResourceKind for ModelSource {
@@ -50,8 +89,65 @@ pub struct ModelTransform {
     pub scale: Vec3,
 }
 
+impl ModelTransform {
+    /// Returns the matrix from the element space to its parent space.
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::srt(self.position, self.rotation, self.scale)
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ModelVisiblePart {
     pub mesh_name: String,
     pub material_name: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_element(
+        index: u32,
+        parent_index: Option<u32>,
+        position: Vec3,
+        mesh_name: &str,
+    ) -> ModelElement {
+        ModelElement {
+            index,
+            name: format!("element-{}", index),
+            parent_index,
+            transform: ModelTransform {
+                position,
+                rotation: Quat::IDENTITY,
+                scale: Vec3::ONE,
+            },
+            visible_parts: vec![ModelVisiblePart {
+                mesh_name: mesh_name.to_owned(),
+                material_name: "material".to_owned(),
+            }],
+        }
+    }
+
+    #[test]
+    fn check_local_bounds_with_translated_child() {
+        let model = ModelSource::new(
+            0,
+            vec![
+                make_element(0, None, Vec3::new(0.0, 1.0, 0.0), "cube"),
+                make_element(1, Some(0), Vec3::new(5.0, 0.0, 0.0), "cube"),
+                make_element(2, Some(1), Vec3::ZERO, "missing"),
+            ],
+        );
+        let mesh_bounds = |mesh_name: &str| match mesh_name {
+            "cube" => Some(Aabb::new(Vec3::new(-1.0, -1.0, -1.0), Vec3::ONE)),
+            _ => None,
+        };
+
+        let bounds = model.local_bounds(mesh_bounds).unwrap();
+        assert_eq!(bounds.min, Vec3::new(-1.0, 0.0, -1.0));
+        assert_eq!(bounds.max, Vec3::new(6.0, 2.0, 1.0));
+
+        let empty = ModelSource::new(0, vec![make_element(0, None, Vec3::ZERO, "missing")]);
+        assert_eq!(empty.local_bounds(mesh_bounds), None);
+    }
+}
@@ -1,5 +1,5 @@
 use crate::{FromResourceKind, ResourceKind};
-use lvl_math::{Quat, Vec3};
+use lvl_math::{BezierCurve, BoneKeyFrameCurves, Quat, Vec3};
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -66,6 +66,20 @@ pub struct PmxModelAnimationBoneBezier {
     pub rotation: [u8; 4],
 }
 
+impl PmxModelAnimationBoneBezier {
+    /// Returns the curves, whose control points are stored as `[x1, x2, y1, y2]`.
+    pub fn curves(&self) -> BoneKeyFrameCurves {
+        let curve = |[x1, x2, y1, y2]: [u8; 4]| BezierCurve::from_mmd(x1, y1, x2, y2);
+
+        BoneKeyFrameCurves {
+            translation_x: curve(self.x_axis),
+            translation_y: curve(self.y_axis),
+            translation_z: curve(self.z_axis),
+            rotation: curve(self.rotation),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct PmxModelAnimationMorphKeyFrame {
     pub frame_index: u32,
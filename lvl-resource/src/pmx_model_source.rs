@@ -1,8 +1,9 @@
 use crate::{FromResourceKind, ResourceKind};
-use lvl_math::{Vec3, Vec4};
+use lvl_math::{Mat4, Vec3, Vec4};
 use serde::{Deserialize, Serialize};
+use wgpu_types::VertexFormat;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct PmxModelSource {
     vertex_data: Vec<u8>,
     vertex_layout: Vec<PmxModelVertexLayoutElement>,
@@ -11,6 +12,7 @@ pub struct PmxModelSource {
     elements: Vec<PmxModelElement>,
     morphs: Vec<PmxModelMorph>,
     bones: Vec<PmxModelBone>,
+    inverse_bind_matrices: Vec<Mat4>,
     vertex_morph_index_texture_name: String,
     uv_morph_index_texture_name: String,
     vertex_displacement_texture_name: String,
@@ -26,6 +28,7 @@ impl PmxModelSource {
         elements: Vec<PmxModelElement>,
         morphs: Vec<PmxModelMorph>,
         bones: Vec<PmxModelBone>,
+        inverse_bind_matrices: Vec<Mat4>,
         vertex_morph_index_texture_name: String,
         uv_morph_index_texture_name: String,
         vertex_displacement_texture_name: String,
@@ -39,6 +42,7 @@ impl PmxModelSource {
             elements,
             morphs,
             bones,
+            inverse_bind_matrices,
             vertex_morph_index_texture_name,
             uv_morph_index_texture_name,
             vertex_displacement_texture_name,
@@ -54,6 +58,48 @@ impl PmxModelSource {
         &self.vertex_layout
     }
 
+    /// Size of a vertex in bytes: the end of the last element of the layout.
+    pub fn vertex_stride(&self) -> u64 {
+        self.vertex_layout
+            .iter()
+            .map(PmxModelVertexLayoutElement::end)
+            .max()
+            .unwrap_or_default()
+    }
+
+    pub fn vertex_count(&self) -> u32 {
+        match self.vertex_stride() {
+            0 => 0,
+            stride => (self.vertex_data.len() as u64 / stride) as u32,
+        }
+    }
+
+    /// Decodes the bones and their weights that deform the vertex, e.g. to paint the weights.
+    /// Unused slots have the bone index `-1` and the weight `0.0`. Returns `None` if the vertex
+    /// does not exist or the layout has no bone elements.
+    pub fn bone_weights(&self, vertex_index: u32) -> Option<[(i32, f32); 4]> {
+        let stride = self.vertex_stride() as usize;
+        let vertex_start = vertex_index as usize * stride;
+        let vertex = self.vertex_data.get(vertex_start..vertex_start + stride)?;
+        let element_data = |kind: PmxModelVertexLayoutElementKind| {
+            let element = self
+                .vertex_layout
+                .iter()
+                .find(|element| element.kind == kind)?;
+            vertex.get(element.offset as usize..element.end() as usize)
+        };
+        let bone_indices = element_data(PmxModelVertexLayoutElementKind::BoneIndex)?;
+        let bone_weights = element_data(PmxModelVertexLayoutElementKind::BoneWeight)?;
+
+        Some(std::array::from_fn(|slot| {
+            let range = slot * 4..slot * 4 + 4;
+            (
+                i32::from_le_bytes(bone_indices[range.clone()].try_into().unwrap()),
+                f32::from_le_bytes(bone_weights[range].try_into().unwrap()),
+            )
+        }))
+    }
+
     pub fn index_data(&self) -> &[u8] {
         &self.index_data
     }
@@ -62,6 +108,10 @@ impl PmxModelSource {
         self.index_kind
     }
 
+    pub fn index_count(&self) -> u32 {
+        (self.index_data.len() as u64 / self.index_kind.size()) as u32
+    }
+
     pub fn elements(&self) -> &[PmxModelElement] {
         &self.elements
     }
@@ -74,6 +124,13 @@ impl PmxModelSource {
         &self.bones
     }
 
+    /// Inverse bind matrices, indexed by bone.
+    /// Each one moves a model-space vertex into the space of the bone at the bind pose,
+    /// and the slice can be uploaded as-is for the skinning shader.
+    pub fn inverse_bind_matrices(&self) -> &[Mat4] {
+        &self.inverse_bind_matrices
+    }
+
     pub fn vertex_morph_index_texture_name(&self) -> &str {
         &self.vertex_morph_index_texture_name
     }
@@ -106,6 +163,13 @@ pub struct PmxModelVertexLayoutElement {
     pub offset: u64,
 }
 
+impl PmxModelVertexLayoutElement {
+    /// Returns the offset right after the element.
+    pub fn end(&self) -> u64 {
+        self.offset + self.kind.vertex_format().size()
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum PmxModelVertexLayoutElementKind {
     /// `vec3f`
@@ -130,7 +194,8 @@ pub enum PmxModelVertexLayoutElementKind {
     SdefR0,
     /// `vec3f`
     SdefR1,
-    /// `float`
+    /// `float`; the PMX per-vertex edge scale. The outline of a vertex is as thick as this
+    /// times the `edge_size` property of the material, so `1.0` keeps the material edge size.
     EdgeSize,
     /// `u32`
     VertexMorphIndexStart,
@@ -140,6 +205,34 @@ pub enum PmxModelVertexLayoutElementKind {
     UvMorphIndexStart,
     /// `u32`
     UvMorphCount,
+    /// `vec2f`, the UV channel of the given number from 1; the channel 0 is [`Self::TexCoord`].
+    /// Taken from the xy of an additional vec4, e.g. for the second UV set of detail maps.
+    AdditionalTexCoord(u8),
+}
+
+impl PmxModelVertexLayoutElementKind {
+    /// Returns the format the element is bound with.
+    pub fn vertex_format(self) -> VertexFormat {
+        match self {
+            Self::Position => VertexFormat::Float32x3,
+            Self::Normal => VertexFormat::Float32x3,
+            Self::TexCoord => VertexFormat::Float32x2,
+            Self::Tangent => VertexFormat::Float32x3,
+            Self::AdditionalVec4(_) => VertexFormat::Float32x4,
+            Self::DeformKind => VertexFormat::Uint32,
+            Self::BoneIndex => VertexFormat::Uint32x4,
+            Self::BoneWeight => VertexFormat::Float32x4,
+            Self::SdefC => VertexFormat::Float32x3,
+            Self::SdefR0 => VertexFormat::Float32x3,
+            Self::SdefR1 => VertexFormat::Float32x3,
+            Self::EdgeSize => VertexFormat::Float32,
+            Self::VertexMorphIndexStart => VertexFormat::Uint32,
+            Self::UvMorphIndexStart => VertexFormat::Uint32,
+            Self::VertexMorphCount => VertexFormat::Uint32,
+            Self::UvMorphCount => VertexFormat::Uint32,
+            Self::AdditionalTexCoord(_) => VertexFormat::Float32x2,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -148,23 +241,39 @@ pub enum PmxModelIndexKind {
     U32,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+impl PmxModelIndexKind {
+    /// Size of an index in bytes.
+    pub fn size(self) -> u64 {
+        match self {
+            Self::U16 => 2,
+            Self::U32 => 4,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct PmxModelElement {
     pub material_name: String,
     pub index_range: (u32, u32),
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct PmxModelMorph {
     pub name: String,
     pub kind: PmxModelMorphKind,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum PmxModelMorphKind {
     Group(Vec<PmxModelMorphGroupElement>),
-    Vertex,
-    Uv,
+    /// Range of the entries in the vertex displacement texture owned by this morph.
+    Vertex {
+        displacement_range: (u32, u32),
+    },
+    /// Range of the entries in the uv displacement texture owned by this morph.
+    Uv {
+        displacement_range: (u32, u32),
+    },
     Material(Vec<PmxModelMorphMaterialElement>),
     // TODO: implement this
     Bone,
@@ -178,7 +287,7 @@ pub struct PmxModelMorphGroupElement {
     pub coefficient: f32,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct PmxModelMorphMaterialElement {
     /// `None` for all materials
     pub material_index: Option<u32>,
@@ -200,7 +309,7 @@ pub enum PmxModelMorphMaterialOffsetMode {
     Additive,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct PmxModelBone {
     pub name: String,
     pub position: Vec3,
@@ -220,7 +329,7 @@ pub struct PmxModelBoneFlags {
     pub physics_after_deform: bool,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct PmxModelBoneInheritance {
     pub index: u32,
     pub coefficient: f32,
@@ -234,7 +343,7 @@ pub enum PmxModelBoneInheritanceMode {
     TranslationOnly,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct PmxModelBoneIK {
     pub index: u32,
     pub loop_count: i32,
@@ -242,15 +351,56 @@ pub struct PmxModelBoneIK {
     pub links: Vec<PmxModelBoneIKLink>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct PmxModelBoneIKLink {
     pub index: u32,
     pub angle_limit: Option<PmxModelBoneIKAngleLimit>,
 }
 
 /// In radians.
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct PmxModelBoneIKAngleLimit {
     pub min: Vec3,
     pub max: Vec3,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_vertex_and_index_counts() {
+        let vertex_layout = vec![
+            PmxModelVertexLayoutElement {
+                kind: PmxModelVertexLayoutElementKind::Position,
+                offset: 0,
+            },
+            PmxModelVertexLayoutElement {
+                kind: PmxModelVertexLayoutElementKind::TexCoord,
+                offset: 12,
+            },
+        ];
+        let source = PmxModelSource::new(
+            vec![0; 20 * 3],
+            vertex_layout,
+            vec![0; 2 * 6],
+            PmxModelIndexKind::U16,
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            String::new(),
+            String::new(),
+            String::new(),
+            String::new(),
+        );
+
+        assert_eq!(source.vertex_stride(), 20);
+        assert_eq!(source.vertex_count(), 3);
+        assert_eq!(
+            source.vertex_count() as u64 * source.vertex_stride(),
+            source.vertex_data().len() as u64
+        );
+        assert_eq!(source.index_count(), 6);
+    }
+}
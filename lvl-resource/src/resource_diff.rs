@@ -0,0 +1,103 @@
+use crate::ResourceFile;
+use serde::Serialize;
+
+/// Names of the resources that differ between two resource files, in name order.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ResourceDiff {
+    /// Resources only in the newer file.
+    pub added: Vec<String>,
+    /// Resources only in the older file.
+    pub removed: Vec<String>,
+    /// Resources in both files whose kinds serialize differently.
+    pub changed: Vec<String>,
+}
+
+impl ResourceDiff {
+    pub fn new(old: &ResourceFile, new: &ResourceFile) -> Self {
+        let mut diff = Self::default();
+
+        for (name, old_resource) in old.resources() {
+            match new.find_by_name(name) {
+                Some(new_resource) => {
+                    if is_source_changed(&old_resource.kind, &new_resource.kind) {
+                        diff.changed.push(name.clone());
+                    }
+                }
+                None => {
+                    diff.removed.push(name.clone());
+                }
+            }
+        }
+
+        for name in new.resources().keys() {
+            if old.find_by_name(name).is_none() {
+                diff.added.push(name.clone());
+            }
+        }
+
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Returns `true` if the sources serialize differently. Unlike `PartialEq`, this also compares
+/// sources holding precompiled shaders.
+pub fn is_source_changed<T: Serialize>(old: &T, new: &T) -> bool {
+    match (bincode::serialize(old), bincode::serialize(new)) {
+        (Ok(old), Ok(new)) => old != new,
+        // sources that cannot be compared are reported as changed
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Resource, ResourceFileVersion, ResourceKind, SpriteMapping, SpriteSource};
+    use std::collections::BTreeMap;
+
+    fn sprite(name: &str, texture_name: &str) -> Resource {
+        Resource {
+            name: name.to_owned(),
+            kind: ResourceKind::Sprite(SpriteSource::new(
+                texture_name.to_owned(),
+                SpriteMapping {
+                    min: (0, 0),
+                    max: (16, 16),
+                },
+            )),
+            metadata: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn check_diff_buckets() {
+        let old = ResourceFile::new(
+            ResourceFileVersion::CURRENT,
+            vec![
+                sprite("kept", "atlas"),
+                sprite("modified", "atlas"),
+                sprite("removed", "atlas"),
+            ],
+        );
+        let new = ResourceFile::new(
+            ResourceFileVersion::CURRENT,
+            vec![
+                sprite("added", "atlas"),
+                sprite("kept", "atlas"),
+                sprite("modified", "atlas-2"),
+            ],
+        );
+
+        let diff = old.diff(&new);
+
+        assert_eq!(diff.added, ["added"]);
+        assert_eq!(diff.removed, ["removed"]);
+        assert_eq!(diff.changed, ["modified"]);
+        assert!(!diff.is_empty());
+        assert!(old.diff(&old).is_empty());
+    }
+}
@@ -0,0 +1,174 @@
+use crate::{Resource, ResourceFile, ResourceFileVersion};
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    io::{Read, Write},
+};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ResourceFileCodecError {
+    #[error("failed to encode or decode: {0}")]
+    BincodeError(#[from] bincode::Error),
+    #[error("failed to compress or decompress: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("the file version {0} is not supported; recompile the resources")]
+    UnsupportedVersion(ResourceFileVersion),
+}
+
+/// Codecs that compress the resources of a [`ResourceFileVersion::V3`] file.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResourceFileCodec {
+    /// Raw deflate; the level is from `0`, no compression, to `9`, the smallest output.
+    Deflate,
+}
+
+impl ResourceFileCodec {
+    fn compress(self, data: &[u8], level: u32) -> Result<Vec<u8>, std::io::Error> {
+        match self {
+            Self::Deflate => {
+                let mut encoder = DeflateEncoder::new(Vec::new(), Compression::new(level.min(9)));
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+        }
+    }
+
+    fn decompress(self, data: &[u8]) -> Result<Vec<u8>, std::io::Error> {
+        match self {
+            Self::Deflate => {
+                let mut decompressed = Vec::new();
+                DeflateDecoder::new(data).read_to_end(&mut decompressed)?;
+                Ok(decompressed)
+            }
+        }
+    }
+}
+
+/// The layout of a [`ResourceFileVersion::V3`] file. The version comes first as in the other
+/// versions, so that it can be read before the rest.
+#[derive(Serialize, Deserialize)]
+struct CompressedResourceFile {
+    version: ResourceFileVersion,
+    codec: ResourceFileCodec,
+    /// The resources, serialized and then compressed by the codec.
+    payload: Vec<u8>,
+}
+
+impl ResourceFile {
+    /// Reads a file of a supported version, decompressing the resources of a
+    /// [`ResourceFileVersion::V3`] file. [`ResourceFileVersion::V1`] files are rejected with
+    /// [`ResourceFileCodecError::UnsupportedVersion`], as their layout is not compatible.
+    pub fn read(bytes: &[u8]) -> Result<Self, ResourceFileCodecError> {
+        match bincode::deserialize::<ResourceFileVersion>(bytes)? {
+            version @ ResourceFileVersion::V1 => {
+                Err(ResourceFileCodecError::UnsupportedVersion(version))
+            }
+            ResourceFileVersion::V2 => Ok(bincode::deserialize::<ResourceFile>(bytes)?),
+            ResourceFileVersion::V3 => {
+                let file = bincode::deserialize::<CompressedResourceFile>(bytes)?;
+                let payload = file.codec.decompress(&file.payload)?;
+                let resources = bincode::deserialize::<BTreeMap<String, Resource>>(&payload)?;
+
+                Ok(Self {
+                    version: file.version,
+                    resources,
+                })
+            }
+        }
+    }
+
+    /// Writes the file as a [`ResourceFileVersion::V3`] file, whose resources are compressed by
+    /// the codec at the level. Texture and mesh data usually shrink the most.
+    pub fn write_compressed(
+        &self,
+        writer: impl Write,
+        codec: ResourceFileCodec,
+        level: u32,
+    ) -> Result<(), ResourceFileCodecError> {
+        let payload = bincode::serialize(&self.resources)?;
+        let file = CompressedResourceFile {
+            version: ResourceFileVersion::V3,
+            codec,
+            payload: codec.compress(&payload, level)?,
+        };
+
+        Ok(bincode::serialize_into(writer, &file)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ResourceKind, TextureElement, TextureElementSamplingMode, TextureElementSize,
+        TextureElementTextureFormat, TextureElementWrappingMode, TextureKind, TextureSource,
+    };
+
+    /// A file with a single 64x64 black texture, which compresses well.
+    fn make_file(version: ResourceFileVersion) -> ResourceFile {
+        ResourceFile::new(
+            version,
+            vec![Resource {
+                name: "texture".to_owned(),
+                kind: ResourceKind::Texture(TextureSource::new(TextureKind::Single(
+                    TextureElement {
+                        data: vec![0; 64 * 64 * 4],
+                        size: TextureElementSize {
+                            width: 64,
+                            height: 64,
+                        },
+                        texture_format: TextureElementTextureFormat::RGBA8Unorm,
+                        sampling_mode: TextureElementSamplingMode::Point,
+                        wrapping_mode_u: TextureElementWrappingMode::Clamp,
+                        wrapping_mode_v: TextureElementWrappingMode::Clamp,
+                        mip_data: vec![],
+                    },
+                ))),
+                metadata: Default::default(),
+            }],
+        )
+    }
+
+    fn texture(file: &ResourceFile) -> &TextureSource {
+        file.find::<TextureSource>("texture").unwrap()
+    }
+
+    #[test]
+    fn check_read_v1() {
+        // written by the compiler before the layout changed, from a 2x2 texture
+        let bytes = include_bytes!("../tests/fixtures/v1_texture.res");
+
+        assert!(matches!(
+            ResourceFile::read(bytes),
+            Err(ResourceFileCodecError::UnsupportedVersion(
+                ResourceFileVersion::V1
+            ))
+        ));
+    }
+
+    #[test]
+    fn check_read_v2() {
+        let file = make_file(ResourceFileVersion::V2);
+        let bytes = bincode::serialize(&file).unwrap();
+        let read = ResourceFile::read(&bytes).unwrap();
+
+        assert_eq!(read.version(), ResourceFileVersion::V2);
+        assert_eq!(texture(&read), texture(&file));
+    }
+
+    #[test]
+    fn check_compressed_round_trip() {
+        let file = make_file(ResourceFileVersion::V2);
+        let uncompressed = bincode::serialize(&file).unwrap();
+        let mut compressed = Vec::new();
+        file.write_compressed(&mut compressed, ResourceFileCodec::Deflate, 9)
+            .unwrap();
+        let read = ResourceFile::read(&compressed).unwrap();
+
+        assert!(compressed.len() < uncompressed.len() / 10);
+        assert_eq!(read.version(), ResourceFileVersion::V3);
+        assert_eq!(texture(&read), texture(&file));
+    }
+}
@@ -5,62 +5,235 @@ use wgpu_types::{SamplerBindingType, TextureSampleType, TextureViewDimension};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ShaderSource {
-    source: String,
+    code: ShaderCode,
     vs_main: String,
     fs_main: String,
+    vertex_entry_points: Vec<String>,
+    fragment_entry_points: Vec<String>,
     builtin_uniform_bind_group: Option<u32>,
     bindings: Vec<ShaderBinding>,
     uniform_members: Vec<ShaderUniformMember>,
-    locations: BTreeMap<String, u32>,
+    vertex_inputs: BTreeMap<String, u32>,
+}
+
+/// The parts of a [`ShaderSource`]; see the accessors of the same names for their meaning.
+#[derive(Debug, Clone)]
+pub struct ShaderSourceDescriptor {
+    pub code: ShaderCode,
+    pub vs_main: String,
+    pub fs_main: String,
+    pub vertex_entry_points: Vec<String>,
+    pub fragment_entry_points: Vec<String>,
+    pub builtin_uniform_bind_group: Option<u32>,
+    pub bindings: Vec<ShaderBinding>,
+    pub uniform_members: Vec<ShaderUniformMember>,
+    pub vertex_inputs: BTreeMap<String, u32>,
 }
 
 impl ShaderSource {
-    pub fn new(
-        source: String,
-        vs_main: String,
-        fs_main: String,
-        builtin_uniform_bind_group: Option<u32>,
-        bindings: Vec<ShaderBinding>,
-        uniform_members: Vec<ShaderUniformMember>,
-        locations: BTreeMap<String, u32>,
-    ) -> Self {
+    pub fn new(descriptor: ShaderSourceDescriptor) -> Self {
+        let ShaderSourceDescriptor {
+            code,
+            vs_main,
+            fs_main,
+            vertex_entry_points,
+            fragment_entry_points,
+            builtin_uniform_bind_group,
+            bindings,
+            uniform_members,
+            vertex_inputs,
+        } = descriptor;
+
         Self {
-            source,
+            code,
             vs_main,
             fs_main,
+            vertex_entry_points,
+            fragment_entry_points,
             builtin_uniform_bind_group,
             bindings,
             uniform_members,
-            locations,
+            vertex_inputs,
         }
     }
 
-    pub fn source(&self) -> &str {
-        &self.source
+    pub fn code(&self) -> &ShaderCode {
+        &self.code
+    }
+
+    /// Returns the WGSL source, or `None` if it has been stripped.
+    pub fn source(&self) -> Option<&str> {
+        self.code.wgsl()
+    }
+
+    /// Returns the precompiled module, or `None` if the shader has not been precompiled.
+    pub fn module(&self) -> Option<&naga::Module> {
+        self.code.naga()
+    }
+
+    /// Stores the module precompiled from the WGSL source. The reflected metadata is kept as is.
+    pub fn set_module(&mut self, module: naga::Module) {
+        self.code.naga = Some(Box::new(module));
     }
 
+    /// Drops the WGSL source, keeping only the precompiled module.
+    /// Returns `false` without dropping it if the shader has not been precompiled.
+    pub fn strip_source(&mut self) -> bool {
+        if self.code.naga.is_none() {
+            return false;
+        }
+
+        self.code.wgsl = None;
+        true
+    }
+
+    /// The default vertex entry point.
     pub fn vs_main(&self) -> &str {
         &self.vs_main
     }
 
+    /// The default fragment entry point.
     pub fn fs_main(&self) -> &str {
         &self.fs_main
     }
 
+    /// All vertex entry points of the shader, in declaration order.
+    pub fn vertex_entry_points(&self) -> &[String] {
+        &self.vertex_entry_points
+    }
+
+    /// All fragment entry points of the shader, in declaration order.
+    pub fn fragment_entry_points(&self) -> &[String] {
+        &self.fragment_entry_points
+    }
+
+    pub fn find_vertex_entry_point(&self, name: &str) -> Option<&str> {
+        self.vertex_entry_points
+            .iter()
+            .find(|entry_point| entry_point.as_str() == name)
+            .map(|entry_point| entry_point.as_str())
+    }
+
+    pub fn find_fragment_entry_point(&self, name: &str) -> Option<&str> {
+        self.fragment_entry_points
+            .iter()
+            .find(|entry_point| entry_point.as_str() == name)
+            .map(|entry_point| entry_point.as_str())
+    }
+
     pub fn builtin_uniform_bind_group(&self) -> Option<u32> {
         self.builtin_uniform_bind_group
     }
 
+    /// All resource bindings of the shader, except the builtin uniforms. The groups are the ones
+    /// of the expanded shader, placed after the builtin uniform group.
     pub fn bindings(&self) -> &[ShaderBinding] {
         &self.bindings
     }
 
+    pub fn find_binding(&self, name: &str) -> Option<&ShaderBinding> {
+        self.bindings.iter().find(|binding| binding.name == name)
+    }
+
+    /// All members of the uniform buffers of the shader, except the builtin uniforms.
     pub fn uniform_members(&self) -> &[ShaderUniformMember] {
         &self.uniform_members
     }
 
-    pub fn locations(&self) -> &BTreeMap<String, u32> {
-        &self.locations
+    pub fn find_uniform_member(&self, name: &str) -> Option<&ShaderUniformMember> {
+        self.uniform_members
+            .iter()
+            .find(|uniform_member| uniform_member.name == name)
+    }
+
+    /// The vertex inputs of the vertex entry point, by name, and their locations.
+    /// The instance inputs provided by the engine are not included; the locations are the ones
+    /// of the expanded shader, placed after the instance inputs.
+    pub fn vertex_inputs(&self) -> &BTreeMap<String, u32> {
+        &self.vertex_inputs
+    }
+
+    pub fn vertex_input(&self, name: &str) -> Option<u32> {
+        self.vertex_inputs.get(name).copied()
+    }
+}
+
+/// Makes a descriptor of the given WGSL code with the `vs_main` and `fs_main` entry points and
+/// nothing else, for tests.
+#[cfg(test)]
+pub(crate) fn make_shader_source_descriptor(code: &str) -> ShaderSourceDescriptor {
+    ShaderSourceDescriptor {
+        code: ShaderCode::from_wgsl(code.to_owned()),
+        vs_main: "vs_main".to_owned(),
+        fs_main: "fs_main".to_owned(),
+        vertex_entry_points: vec![],
+        fragment_entry_points: vec![],
+        builtin_uniform_bind_group: None,
+        bindings: vec![],
+        uniform_members: vec![],
+        vertex_inputs: Default::default(),
+    }
+}
+
+/// The code of a shader: the WGSL source, the naga IR precompiled from it, or both.
+/// The WGSL source is kept for debugging and hot reloading, and is used
+/// when the shader has not been precompiled.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ShaderCode {
+    wgsl: Option<String>,
+    #[serde(with = "varint_module")]
+    naga: Option<Box<naga::Module>>,
+}
+
+/// Encodes the module with variable-length integers. The IR is mostly handles, enum tags and
+/// lengths, which take a byte or two instead of four or eight, so that a precompiled shader is
+/// smaller than its WGSL source.
+mod varint_module {
+    use bincode::Options;
+    use serde::{
+        de::Error as _, ser::Error as _, Deserialize, Deserializer, Serialize, Serializer,
+    };
+
+    pub fn serialize<S>(
+        module: &Option<Box<naga::Module>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let bytes = module
+            .as_ref()
+            .map(|module| bincode::DefaultOptions::new().serialize(module))
+            .transpose()
+            .map_err(S::Error::custom)?;
+        bytes.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Box<naga::Module>>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Option::<Vec<u8>>::deserialize(deserializer)?
+            .map(|bytes| bincode::DefaultOptions::new().deserialize(&bytes))
+            .transpose()
+            .map_err(D::Error::custom)
+    }
+}
+
+impl ShaderCode {
+    pub fn from_wgsl(source: String) -> Self {
+        Self {
+            wgsl: Some(source),
+            naga: None,
+        }
+    }
+
+    pub fn wgsl(&self) -> Option<&str> {
+        self.wgsl.as_deref()
+    }
+
+    pub fn naga(&self) -> Option<&naga::Module> {
+        self.naga.as_deref()
     }
 }
 
@@ -1,7 +1,7 @@
 use crate::{FromResourceKind, ResourceKind};
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct TextureSource {
     kind: TextureKind,
 }
@@ -25,7 +25,9 @@ impl FromResourceKind for TextureSource {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+// the sources are loaded once and dropped, so the size of a cubemap is not worth boxing
+#[allow(clippy::large_enum_variant)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum TextureKind {
     Single(TextureElement),
     Cubemap {
@@ -38,7 +40,7 @@ pub enum TextureKind {
     },
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct TextureElement {
     pub data: Vec<u8>,
     pub size: TextureElementSize,
@@ -46,6 +48,35 @@ pub struct TextureElement {
     pub sampling_mode: TextureElementSamplingMode,
     pub wrapping_mode_u: TextureElementWrappingMode,
     pub wrapping_mode_v: TextureElementWrappingMode,
+    /// The mip levels below `data`, each half the size of the one above down to 1x1; see
+    /// [`TextureElement::mip_level_size`]. Empty if the texture has no mip chain.
+    pub mip_data: Vec<Vec<u8>>,
+}
+
+impl TextureElement {
+    /// Returns the number of mip levels, including the base level.
+    pub fn mip_level_count(&self) -> u32 {
+        1 + self.mip_data.len() as u32
+    }
+
+    /// Returns the data of the mip level; the level `0` is `data`.
+    pub fn mip_level_data(&self, level: u32) -> Option<&[u8]> {
+        match level {
+            0 => Some(&self.data),
+            _ => self
+                .mip_data
+                .get(level as usize - 1)
+                .map(|data| data.as_slice()),
+        }
+    }
+
+    /// Returns the size of the mip level, which halves per level and never goes below 1.
+    pub fn mip_level_size(&self, level: u32) -> TextureElementSize {
+        TextureElementSize {
+            width: (self.size.width >> level).max(1),
+            height: (self.size.height >> level).max(1),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -61,6 +92,88 @@ pub enum TextureElementTextureFormat {
     RGBA32Float,
     RGBA8Unorm,
     RGBA8UnormSrgb,
+    /// BC3 (DXT5): 4x4 blocks of 16 bytes, with interpolated RGB and separately stored alpha.
+    Bc3RgbaUnorm,
+    Bc3RgbaUnormSrgb,
+    /// BC7: 4x4 blocks of 16 bytes, with higher quality RGBA than BC3.
+    Bc7RgbaUnorm,
+    Bc7RgbaUnormSrgb,
+}
+
+impl TextureElementTextureFormat {
+    /// Returns `true` if the texels are unfiltered integers; they can only be point sampled.
+    pub fn is_integer(self) -> bool {
+        match self {
+            Self::RG32Uint | Self::RGBA32Uint => true,
+            Self::RGBA32Float
+            | Self::RGBA8Unorm
+            | Self::RGBA8UnormSrgb
+            | Self::Bc3RgbaUnorm
+            | Self::Bc3RgbaUnormSrgb
+            | Self::Bc7RgbaUnorm
+            | Self::Bc7RgbaUnormSrgb => false,
+        }
+    }
+
+    /// Returns `true` if the texels are stored in compressed blocks; see
+    /// [`TextureElementTextureFormat::block_dimension`].
+    pub fn is_block_compressed(self) -> bool {
+        self.block_dimension() != 1
+    }
+
+    /// Returns the width and height of a block in texels; `1` for uncompressed formats.
+    pub fn block_dimension(self) -> u32 {
+        match self {
+            Self::RG32Uint
+            | Self::RGBA32Uint
+            | Self::RGBA32Float
+            | Self::RGBA8Unorm
+            | Self::RGBA8UnormSrgb => 1,
+            Self::Bc3RgbaUnorm
+            | Self::Bc3RgbaUnormSrgb
+            | Self::Bc7RgbaUnorm
+            | Self::Bc7RgbaUnormSrgb => 4,
+        }
+    }
+
+    /// Returns the size of a block in bytes, which is the size of a texel for uncompressed
+    /// formats.
+    pub fn block_size(self) -> usize {
+        match self {
+            Self::RG32Uint => 8,
+            Self::RGBA32Uint | Self::RGBA32Float => 16,
+            Self::RGBA8Unorm | Self::RGBA8UnormSrgb => 4,
+            Self::Bc3RgbaUnorm
+            | Self::Bc3RgbaUnormSrgb
+            | Self::Bc7RgbaUnorm
+            | Self::Bc7RgbaUnormSrgb => 16,
+        }
+    }
+
+    /// Returns the size of a texel in bytes, or `None` for block-compressed formats, whose
+    /// texels do not take whole bytes.
+    pub fn bytes_per_texel(self) -> Option<usize> {
+        match self.is_block_compressed() {
+            true => None,
+            false => Some(self.block_size()),
+        }
+    }
+
+    /// Returns the number of blocks per row and the number of rows of blocks of an image of the
+    /// size; partial blocks at the right and bottom edges count as whole blocks.
+    pub fn block_count(self, size: TextureElementSize) -> (u32, u32) {
+        let dimension = self.block_dimension();
+        (
+            (size.width as u32).div_ceil(dimension),
+            (size.height as u32).div_ceil(dimension),
+        )
+    }
+
+    /// Returns the size in bytes of the data of an image of the size.
+    pub fn data_size(self, size: TextureElementSize) -> usize {
+        let (columns, rows) = self.block_count(size);
+        columns as usize * rows as usize * self.block_size()
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -0,0 +1,170 @@
+use crate::{vmd_primitives::VmdVec3, Vmd};
+use lvl_math::Vec3;
+
+/// An engine-native camera key frame, resolved from the MMD camera representation.
+/// Positions are in the engine's right-handed space, i.e. the z axis of MMD is flipped,
+/// the same as the PMX vertex positions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CameraKeyframe {
+    pub frame_index: u32,
+    /// Position of the eye.
+    pub position: Vec3,
+    /// Position the camera looks at.
+    pub target: Vec3,
+    /// Up direction of the camera, including the roll.
+    pub up: Vec3,
+    /// Vertical field of view, in radians.
+    pub fov: f32,
+    /// `true` if the camera is in perspective mode, orthographic mode otherwise.
+    pub is_perspective: bool,
+}
+
+impl Vmd {
+    /// Converts the camera key frames into eye/target key frames, sorted by frame index.
+    ///
+    /// MMD places the eye at `distance` along the z axis from the target (a negative distance
+    /// puts the eye in front of the target), then rotates it around the target by the camera
+    /// rotation: roll (z) first, then pitch (x), then yaw (y).
+    pub fn to_camera_keyframes(&self) -> Vec<CameraKeyframe> {
+        let mut keyframes = self
+            .camera_key_frames
+            .iter()
+            .map(|key_frame| {
+                let rotation = key_frame.camera_rotation;
+                let target = Vec3::new(
+                    key_frame.target_position.x,
+                    key_frame.target_position.y,
+                    key_frame.target_position.z,
+                );
+                let offset = rotate_yxz(Vec3::new(0.0, 0.0, key_frame.distance), rotation);
+                let up = rotate_yxz(Vec3::new(0.0, 1.0, 0.0), rotation);
+
+                CameraKeyframe {
+                    frame_index: key_frame.frame_index,
+                    position: flip_handedness(target + offset),
+                    target: flip_handedness(target),
+                    up: flip_handedness(up),
+                    fov: key_frame.fov.to_radians(),
+                    is_perspective: key_frame.is_perspective,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        keyframes.sort_by_key(|keyframe| keyframe.frame_index);
+        keyframes
+    }
+}
+
+fn rotate_yxz(vector: Vec3, rotation: VmdVec3) -> Vec3 {
+    let (sin_x, cos_x) = rotation.x.sin_cos();
+    let (sin_y, cos_y) = rotation.y.sin_cos();
+    let (sin_z, cos_z) = rotation.z.sin_cos();
+
+    // roll
+    let vector = Vec3::new(
+        vector.x * cos_z - vector.y * sin_z,
+        vector.x * sin_z + vector.y * cos_z,
+        vector.z,
+    );
+    // pitch
+    let vector = Vec3::new(
+        vector.x,
+        vector.y * cos_x - vector.z * sin_x,
+        vector.y * sin_x + vector.z * cos_x,
+    );
+    // yaw
+    Vec3::new(
+        vector.x * cos_y + vector.z * sin_y,
+        vector.y,
+        -vector.x * sin_y + vector.z * cos_y,
+    )
+}
+
+fn flip_handedness(vector: Vec3) -> Vec3 {
+    Vec3::new(vector.x, vector.y, -vector.z)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{VmdCameraKeyFrame, VmdCameraKeyFrameBezier, VmdHeader, VmdVersion};
+
+    fn equals_float(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-4
+    }
+
+    fn make_vmd(camera_key_frames: Vec<VmdCameraKeyFrame>) -> Vmd {
+        Vmd {
+            header: VmdHeader::new(VmdVersion::V2, String::new()),
+            bone_key_frames: vec![],
+            morph_key_frames: vec![],
+            camera_key_frames,
+            light_key_frames: vec![],
+            trailing_bytes: vec![],
+        }
+    }
+
+    fn make_key_frame(distance: f32, target: VmdVec3, rotation: VmdVec3) -> VmdCameraKeyFrame {
+        VmdCameraKeyFrame {
+            frame_index: 0,
+            distance,
+            target_position: target,
+            camera_rotation: rotation,
+            fov: 30.0,
+            bezier: VmdCameraKeyFrameBezier { data: [0; 24] },
+            is_perspective: true,
+        }
+    }
+
+    #[test]
+    fn check_camera_keyframe_distance_behind_target() {
+        let target = VmdVec3 {
+            x: 0.0,
+            y: 10.0,
+            z: 0.0,
+        };
+        let rotation = VmdVec3 {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        };
+        let vmd = make_vmd(vec![make_key_frame(-45.0, target, rotation)]);
+        let keyframes = vmd.to_camera_keyframes();
+
+        assert_eq!(keyframes.len(), 1);
+
+        let keyframe = &keyframes[0];
+        assert!(equals_float(
+            Vec3::distance(keyframe.position, keyframe.target),
+            45.0
+        ));
+        // the engine camera looks along -z, so the eye sits on the +z side of the target
+        assert!(equals_float(keyframe.position.x, 0.0));
+        assert!(equals_float(keyframe.position.y, 10.0));
+        assert!(equals_float(keyframe.position.z, 45.0));
+        assert!(equals_float(keyframe.up.y, 1.0));
+        assert!(equals_float(keyframe.fov, 30f32.to_radians()));
+    }
+
+    #[test]
+    fn check_camera_keyframe_rotation_keeps_distance() {
+        let target = VmdVec3 {
+            x: 1.0,
+            y: 2.0,
+            z: 3.0,
+        };
+        let rotation = VmdVec3 {
+            x: 0.3,
+            y: 1.2,
+            z: 0.1,
+        };
+        let vmd = make_vmd(vec![make_key_frame(-20.0, target, rotation)]);
+        let keyframe = &vmd.to_camera_keyframes()[0];
+
+        assert!(equals_float(
+            Vec3::distance(keyframe.position, keyframe.target),
+            20.0
+        ));
+        assert!(equals_float(keyframe.up.len(), 1.0));
+    }
+}
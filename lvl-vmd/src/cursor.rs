@@ -31,6 +31,13 @@ impl<'a> Cursor<'a> {
         Ok(unsafe { &*(result as *const [u8] as *const [u8; L]) })
     }
 
+    /// Reads all the bytes left.
+    pub fn read_remaining(&mut self) -> &[u8] {
+        let result = &self.buffer[self.position..];
+        self.position = self.buffer.len();
+        result
+    }
+
     pub fn read_dynamic<E: ParseError>(&mut self, len: usize) -> Result<&[u8], E> {
         let result = &self.buffer[self.position..self.position + len];
         self.position += len;
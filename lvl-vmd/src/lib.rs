@@ -1,6 +1,8 @@
+mod camera_keyframe;
 mod cursor;
 mod parse;
 mod primitives;
+mod sampler;
 mod vmd_bone_key_frame;
 mod vmd_camera_key_frame;
 mod vmd_header;
@@ -8,8 +10,10 @@ mod vmd_light_key_frame;
 mod vmd_morph_key_frame;
 mod vmd_primitives;
 
+pub use camera_keyframe::*;
 use cursor::Cursor;
 use parse::Parse;
+pub use sampler::*;
 use std::fmt::Display;
 use thiserror::Error;
 pub use vmd_bone_key_frame::*;
@@ -39,6 +43,9 @@ pub struct Vmd {
     pub morph_key_frames: Vec<VmdMorphKeyFrame>,
     pub camera_key_frames: Vec<VmdCameraKeyFrame>,
     pub light_key_frames: Vec<VmdLightKeyFrame>,
+    /// Bytes after the light key frames, e.g. the self shadow section or exporter-specific data.
+    /// They are not parsed, but kept as is.
+    pub trailing_bytes: Vec<u8>,
 }
 
 impl Vmd {
@@ -47,9 +54,23 @@ impl Vmd {
 
         let header = VmdHeader::parse(&mut cursor)?;
         let bone_key_frames = Vec::parse(&mut cursor)?;
-        let morph_key_frames = Vec::parse(&mut cursor)?;
-        let camera_key_frames = Vec::parse(&mut cursor)?;
-        let light_key_frames = Vec::parse(&mut cursor)?;
+        // the sections after the bone key frames are omitted by some exporters if they are empty
+        let morph_key_frames = if cursor.has_bytes(4) {
+            Vec::parse(&mut cursor)?
+        } else {
+            Vec::new()
+        };
+        let camera_key_frames = if cursor.has_bytes(4) {
+            Vec::parse(&mut cursor)?
+        } else {
+            Vec::new()
+        };
+        let light_key_frames = if cursor.has_bytes(4) {
+            Vec::parse(&mut cursor)?
+        } else {
+            Vec::new()
+        };
+        let trailing_bytes = cursor.read_remaining().to_vec();
 
         Ok(Self {
             header,
@@ -57,6 +78,7 @@ impl Vmd {
             morph_key_frames,
             camera_key_frames,
             light_key_frames,
+            trailing_bytes,
         })
     }
 }
@@ -72,3 +94,106 @@ impl Display for Vmd {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_header() -> Vec<u8> {
+        make_header_with_model_name([0u8; 20])
+    }
+
+    fn make_header_with_model_name(model_name: [u8; 20]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        let mut signature = [0u8; 30];
+        signature[..25].copy_from_slice(b"Vocaloid Motion Data 0002");
+        buf.extend(signature);
+        buf.extend(model_name);
+        buf
+    }
+
+    #[test]
+    fn check_model_name_junk_after_null() {
+        // "ミク", then bytes left over that are not valid Shift JIS
+        let mut model_name = [0u8; 20];
+        model_name[..7].copy_from_slice(&[0x83, 0x7e, 0x83, 0x4e, 0x00, 0xff, 0x81]);
+        let mut buf = make_header_with_model_name(model_name);
+        buf.extend(0u32.to_le_bytes());
+
+        let vmd = Vmd::parse(&buf).unwrap();
+
+        assert_eq!(vmd.header.model_name, "ミク");
+        assert_eq!(vmd.header.model_name_raw(), model_name);
+    }
+
+    #[test]
+    fn check_new_header_encodes_model_name() {
+        let header = VmdHeader::new(VmdVersion::V2, "ミク".to_owned());
+
+        assert_eq!(header.model_name, "ミク");
+        assert_eq!(header.model_name_raw(), [0x83, 0x7e, 0x83, 0x4e]);
+    }
+
+    #[test]
+    fn check_invalid_model_name() {
+        let mut model_name = [0u8; 20];
+        model_name[..2].copy_from_slice(&[0x83, 0xff]);
+        let mut buf = make_header_with_model_name(model_name);
+        buf.extend(0u32.to_le_bytes());
+
+        assert!(matches!(
+            Vmd::parse(&buf),
+            Err(VmdParseError::VmdHeaderParseError(
+                VmdHeaderParseError::InvalidShiftJis { raw }
+            )) if raw == model_name
+        ));
+    }
+
+    #[test]
+    fn check_empty_sections() {
+        let mut buf = make_header();
+        buf.extend(0u32.to_le_bytes());
+        buf.extend(0u32.to_le_bytes());
+        buf.extend(0u32.to_le_bytes());
+        buf.extend(0u32.to_le_bytes());
+
+        let vmd = Vmd::parse(&buf).unwrap();
+
+        assert!(vmd.bone_key_frames.is_empty());
+        assert!(vmd.morph_key_frames.is_empty());
+        assert!(vmd.camera_key_frames.is_empty());
+        assert!(vmd.light_key_frames.is_empty());
+    }
+
+    #[test]
+    fn check_omitted_trailing_sections() {
+        let mut buf = make_header();
+        buf.extend(0u32.to_le_bytes());
+
+        let vmd = Vmd::parse(&buf).unwrap();
+
+        assert!(vmd.bone_key_frames.is_empty());
+        assert!(vmd.morph_key_frames.is_empty());
+        assert!(vmd.camera_key_frames.is_empty());
+        assert!(vmd.light_key_frames.is_empty());
+    }
+
+    #[test]
+    fn check_trailing_bytes() {
+        let mut buf = make_header();
+        buf.extend(0u32.to_le_bytes());
+        buf.extend(0u32.to_le_bytes());
+        buf.extend(0u32.to_le_bytes());
+        // a light key frame
+        buf.extend(1u32.to_le_bytes());
+        buf.extend(30u32.to_le_bytes());
+        buf.extend([0u8; 24]);
+        buf.extend(b"garbage");
+
+        let vmd = Vmd::parse(&buf).unwrap();
+
+        assert_eq!(vmd.light_key_frames.len(), 1);
+        assert_eq!(vmd.light_key_frames[0].frame_index, 30);
+        assert_eq!(vmd.trailing_bytes, b"garbage");
+    }
+}
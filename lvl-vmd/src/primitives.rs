@@ -56,15 +56,23 @@ impl ShiftJISString {
         cursor.ensure_bytes(byte_len)?;
 
         let bytes = cursor.read_dynamic::<RustPrimitiveParseError>(byte_len)?;
-        let string = match encoding_rs::SHIFT_JIS.decode_without_bom_handling(bytes) {
-            (_, true) => {
-                return Err(RustPrimitiveParseError::InvalidShiftJISString);
-            }
-            (string, false) => match string.find('\0') {
-                Some(index) => string[..index].to_owned(),
-                None => string.to_string(),
-            },
-        };
-        Ok(Self(string))
+        match Self::decode(bytes) {
+            Some(string) => Ok(Self(string)),
+            None => Err(RustPrimitiveParseError::InvalidShiftJISString),
+        }
+    }
+
+    /// Decodes a fixed-width field up to its first null byte. The bytes after it are often left
+    /// over from a longer name, so they are ignored rather than decoded.
+    pub fn decode(bytes: &[u8]) -> Option<String> {
+        let len = bytes
+            .iter()
+            .position(|&byte| byte == 0)
+            .unwrap_or(bytes.len());
+
+        match encoding_rs::SHIFT_JIS.decode_without_bom_handling(&bytes[..len]) {
+            (_, true) => None,
+            (string, false) => Some(string.into_owned()),
+        }
     }
 }
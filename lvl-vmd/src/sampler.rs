@@ -0,0 +1,171 @@
+use crate::Vmd;
+use lvl_math::{BoneKeyFrame, KeyFrameSampler, MorphKeyFrame, Quat, Vec3};
+use std::collections::HashMap;
+
+/// The key frames of a motion grouped by bone and morph name and sorted by frame index, so that
+/// the motion can be sampled at any frame; see [`KeyFrameSampler`].
+///
+/// The values are in the VMD space, as they are stored in the file; the translations are
+/// relative to the bind pose of the bones.
+#[derive(Debug, Clone)]
+pub struct VmdSampler {
+    sampler: KeyFrameSampler,
+}
+
+impl VmdSampler {
+    pub fn new(vmd: &Vmd) -> Self {
+        let bone_key_frames = vmd.bone_key_frames.iter().map(|key_frame| {
+            let translation = key_frame.translation;
+            let rotation = key_frame.rotation;
+
+            (
+                key_frame.bone_name.clone(),
+                BoneKeyFrame {
+                    frame_index: key_frame.frame_index,
+                    translation: Vec3::new(translation.x, translation.y, translation.z),
+                    rotation: Quat::new(rotation.x, rotation.y, rotation.z, rotation.w),
+                    curves: key_frame.bezier.curves(),
+                },
+            )
+        });
+        let morph_key_frames = vmd.morph_key_frames.iter().map(|key_frame| {
+            (
+                key_frame.morph_name.clone(),
+                MorphKeyFrame {
+                    frame_index: key_frame.frame_index,
+                    weight: key_frame.weight,
+                },
+            )
+        });
+
+        Self {
+            sampler: KeyFrameSampler::new(bone_key_frames, morph_key_frames),
+        }
+    }
+
+    pub fn key_frame_sampler(&self) -> &KeyFrameSampler {
+        &self.sampler
+    }
+
+    pub fn bone_names(&self) -> impl Iterator<Item = &str> {
+        self.sampler.bone_names()
+    }
+
+    pub fn morph_names(&self) -> impl Iterator<Item = &str> {
+        self.sampler.morph_names()
+    }
+
+    /// Returns the translation and rotation of the bone at the frame, or `None` if the motion
+    /// does not animate the bone.
+    pub fn sample_bone(&self, bone_name: &str, frame: f32) -> Option<(Vec3, Quat)> {
+        self.sampler.sample_bone(bone_name, frame)
+    }
+
+    /// Returns the weight of the morph at the frame, or `None` if the motion does not animate
+    /// the morph. The weights are interpolated linearly, as morph key frames have no curves.
+    pub fn sample_morph(&self, morph_name: &str, frame: f32) -> Option<f32> {
+        self.sampler.sample_morph(morph_name, frame)
+    }
+
+    pub fn sample_bones(&self, frame: f32) -> HashMap<String, (Vec3, Quat)> {
+        self.sampler.sample_bones(frame)
+    }
+
+    pub fn sample_morphs(&self, frame: f32) -> HashMap<String, f32> {
+        self.sampler.sample_morphs(frame)
+    }
+}
+
+impl Vmd {
+    /// Groups the key frames for sampling; keep the sampler to sample many frames.
+    pub fn sampler(&self) -> VmdSampler {
+        VmdSampler::new(self)
+    }
+
+    /// Samples all the animated bones at the frame; see [`VmdSampler::sample_bones`].
+    pub fn sample_bones(&self, frame: f32) -> HashMap<String, (Vec3, Quat)> {
+        self.sampler().sample_bones(frame)
+    }
+
+    /// Samples all the animated morphs at the frame; see [`VmdSampler::sample_morphs`].
+    pub fn sample_morphs(&self, frame: f32) -> HashMap<String, f32> {
+        self.sampler().sample_morphs(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        vmd_primitives::{VmdQuat, VmdVec3},
+        VmdBoneKeyFrame, VmdBoneKeyFrameBezier, VmdHeader, VmdMorphKeyFrame, VmdVersion,
+    };
+
+    /// A bezier whose curves are all the straight line from `(20, 20)` to `(107, 107)`.
+    fn linear_bezier() -> VmdBoneKeyFrameBezier {
+        let mut data = [0; 64];
+
+        for axis in 0..4 {
+            for (offset, value) in [(0, 20), (4, 20), (8, 107), (12, 107)] {
+                data[axis * 16 + offset] = value;
+            }
+        }
+
+        VmdBoneKeyFrameBezier { data }
+    }
+
+    fn bone_key_frame(frame_index: u32, x: f32, rotation: Quat) -> VmdBoneKeyFrame {
+        VmdBoneKeyFrame {
+            bone_name: "arm".to_owned(),
+            frame_index,
+            translation: VmdVec3 { x, y: 0.0, z: 0.0 },
+            rotation: VmdQuat {
+                x: rotation.x,
+                y: rotation.y,
+                z: rotation.z,
+                w: rotation.w,
+            },
+            bezier: linear_bezier(),
+        }
+    }
+
+    fn morph_key_frame(frame_index: u32, weight: f32) -> VmdMorphKeyFrame {
+        VmdMorphKeyFrame {
+            morph_name: "smile".to_owned(),
+            frame_index,
+            weight,
+        }
+    }
+
+    #[test]
+    fn check_sample_between_and_outside_key_frames() {
+        let turned = Quat::from_axis_angle(Vec3::UP, std::f32::consts::FRAC_PI_2);
+        let vmd = Vmd {
+            header: VmdHeader::new(VmdVersion::V2, String::new()),
+            // out of order, as in some files
+            bone_key_frames: vec![
+                bone_key_frame(20, 4.0, turned),
+                bone_key_frame(10, 2.0, Quat::IDENTITY),
+            ],
+            morph_key_frames: vec![morph_key_frame(0, 0.0), morph_key_frame(10, 1.0)],
+            camera_key_frames: vec![],
+            light_key_frames: vec![],
+            trailing_bytes: vec![],
+        };
+        let sampler = vmd.sampler();
+
+        let (translation, rotation) = sampler.sample_bone("arm", 15.0).unwrap();
+        let halfway = Quat::from_axis_angle(Vec3::UP, std::f32::consts::FRAC_PI_4);
+        assert!((translation.x - 3.0).abs() < 1e-3);
+        assert!(Quat::dot(rotation, halfway).abs() > 1.0 - 1e-4);
+
+        assert_eq!(sampler.sample_bone("arm", 0.0).unwrap().0.x, 2.0);
+        assert_eq!(sampler.sample_bone("arm", 30.0).unwrap().0.x, 4.0);
+        assert_eq!(sampler.sample_bone("leg", 15.0), None);
+
+        let morphs = vmd.sample_morphs(2.5);
+        assert_eq!(morphs.len(), 1);
+        assert!((morphs["smile"] - 0.25).abs() < 1e-6);
+        assert_eq!(vmd.sample_bones(20.0)["arm"].0.x, 4.0);
+    }
+}
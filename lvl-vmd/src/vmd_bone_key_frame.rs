@@ -4,6 +4,7 @@ use crate::{
     primitives::ShiftJISString,
     vmd_primitives::{VmdQuat, VmdVec3},
 };
+use lvl_math::{BezierCurve, BoneKeyFrameCurves};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -127,6 +128,43 @@ pub struct VmdBoneKeyFrameBezier {
     pub data: [u8; 64],
 }
 
+/// Values of a bone key frame that have their own interpolation curve.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VmdBoneKeyFrameChannel {
+    TranslationX,
+    TranslationY,
+    TranslationZ,
+    Rotation,
+}
+
+impl VmdBoneKeyFrameBezier {
+    /// Returns the curve of the channel.
+    pub fn curve(&self, channel: VmdBoneKeyFrameChannel) -> BezierCurve {
+        let base = match channel {
+            VmdBoneKeyFrameChannel::TranslationX => 0,
+            VmdBoneKeyFrameChannel::TranslationY => 16,
+            VmdBoneKeyFrameChannel::TranslationZ => 32,
+            VmdBoneKeyFrameChannel::Rotation => 48,
+        };
+
+        BezierCurve::from_mmd(
+            self.data[base],
+            self.data[base + 4],
+            self.data[base + 8],
+            self.data[base + 12],
+        )
+    }
+
+    pub fn curves(&self) -> BoneKeyFrameCurves {
+        BoneKeyFrameCurves {
+            translation_x: self.curve(VmdBoneKeyFrameChannel::TranslationX),
+            translation_y: self.curve(VmdBoneKeyFrameChannel::TranslationY),
+            translation_z: self.curve(VmdBoneKeyFrameChannel::TranslationZ),
+            rotation: self.curve(VmdBoneKeyFrameChannel::Rotation),
+        }
+    }
+}
+
 impl Parse for VmdBoneKeyFrameBezier {
     type Error = VmdBoneKeyFrameParseError;
 
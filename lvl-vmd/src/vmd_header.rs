@@ -14,6 +14,8 @@ pub enum VmdHeaderParseError {
     RustPrimitiveParseError(#[from] crate::primitives::RustPrimitiveParseError),
     #[error("`{signature:?}` is not a valid VMD signature")]
     InvalidSignature { signature: [u8; 30] },
+    #[error("the model name `{raw:?}` is not a valid Shift JIS string")]
+    InvalidShiftJis { raw: Vec<u8> },
 }
 
 impl ParseError for VmdHeaderParseError {
@@ -26,6 +28,26 @@ impl ParseError for VmdHeaderParseError {
 pub struct VmdHeader {
     pub version: VmdVersion,
     pub model_name: String,
+    /// The whole model name field, including the bytes after the null terminator.
+    pub(crate) model_name_raw: Vec<u8>,
+}
+
+impl VmdHeader {
+    /// Makes a header whose raw model name is the Shift JIS encoding of the model name, with no
+    /// bytes after it.
+    pub fn new(version: VmdVersion, model_name: String) -> Self {
+        let model_name_raw = encoding_rs::SHIFT_JIS.encode(&model_name).0.into_owned();
+
+        Self {
+            version,
+            model_name,
+            model_name_raw,
+        }
+    }
+
+    pub fn model_name_raw(&self) -> &[u8] {
+        &self.model_name_raw
+    }
 }
 
 impl Parse for VmdHeader {
@@ -46,14 +68,28 @@ impl Parse for VmdHeader {
             return Err(VmdHeaderParseError::InvalidSignature { signature });
         };
 
-        let model_name = match version {
-            VmdVersion::V1 => ShiftJISString::parse(cursor, 10)?,
-            VmdVersion::V2 => ShiftJISString::parse(cursor, 20)?,
+        let model_name_len = match version {
+            VmdVersion::V1 => 10,
+            VmdVersion::V2 => 20,
+        };
+        cursor.ensure_bytes::<VmdHeaderParseError>(model_name_len)?;
+
+        let model_name_raw = cursor
+            .read_dynamic::<VmdHeaderParseError>(model_name_len)?
+            .to_vec();
+        let model_name = match ShiftJISString::decode(&model_name_raw) {
+            Some(model_name) => model_name,
+            None => {
+                return Err(VmdHeaderParseError::InvalidShiftJis {
+                    raw: model_name_raw,
+                });
+            }
         };
 
         Ok(Self {
             version,
-            model_name: model_name.0,
+            model_name,
+            model_name_raw,
         })
     }
 }
@@ -1,4 +1,5 @@
 use crate::object::{make_camera_object, make_light_object, make_pmx_model_renderer};
+use log::{error, info};
 use lvl_core::{
     context::{driver::Driver, Context},
     resource::load_resource_file,
@@ -8,14 +9,81 @@ use lvl_core::{
     },
 };
 use lvl_math::{Quat, Vec3, Vec4};
+use lvl_resource::{PmxModelSource, ResourceFile};
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 use winit::{
     keyboard::{KeyCode, PhysicalKey},
     window::Window,
 };
 
+const RESOURCE_PATH: &str = "./assets/resources.res";
+const PMX_MODEL_NAME: &str = "YYB Hatsune Miku_NT_1.0ver";
+
+/// Watches a file by polling its modification time, so that the resources are reloaded when
+/// the resource compiler writes them again.
+struct FileWatch {
+    path: PathBuf,
+    modified_time: Option<SystemTime>,
+    last_poll_time: Instant,
+}
+
+impl FileWatch {
+    const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+    fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+
+        Self {
+            modified_time: modified_time(&path),
+            path,
+            last_poll_time: Instant::now(),
+        }
+    }
+
+    /// Returns the modification time of the file if it differs from the last accepted one.
+    /// The change is reported again on the next poll until it is accepted by [`Self::accept`],
+    /// so that a file read while it is still being written is read again.
+    fn poll(&mut self) -> Option<SystemTime> {
+        if self.last_poll_time.elapsed() < Self::POLL_INTERVAL {
+            return None;
+        }
+
+        self.last_poll_time = Instant::now();
+
+        let modified_time = modified_time(&self.path)?;
+
+        if Some(modified_time) == self.modified_time {
+            return None;
+        }
+
+        Some(modified_time)
+    }
+
+    fn accept(&mut self, modified_time: SystemTime) {
+        self.modified_time = Some(modified_time);
+    }
+}
+
+fn read_resource_file() -> Result<ResourceFile, Box<dyn std::error::Error>> {
+    let bytes = std::fs::read(RESOURCE_PATH)?;
+    Ok(load_resource_file(&bytes)?)
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
 pub struct DriverImpl {
     camera_id: Option<ObjectId>,
     pmx_model_id: Option<ObjectId>,
+    resource: Option<ResourceFile>,
+    resource_watch: FileWatch,
+    is_pmx_model_reload_pending: bool,
 }
 
 impl DriverImpl {
@@ -23,7 +91,61 @@ impl DriverImpl {
         Self {
             camera_id: None,
             pmx_model_id: None,
+            resource: None,
+            resource_watch: FileWatch::new(RESOURCE_PATH),
+            is_pmx_model_reload_pending: false,
+        }
+    }
+
+    /// Returns `false` if the file cannot be read, e.g. because it is still being written.
+    fn reload_resources(&mut self, context: &Context) -> bool {
+        let resource = match read_resource_file() {
+            Ok(resource) => resource,
+            Err(err) => {
+                error!("failed to read the changed resources: {}", err);
+                return false;
+            }
+        };
+
+        if let Err(err) = context.reload_resources(&resource) {
+            error!("failed to reload the resources: {}", err);
+            return true;
         }
+
+        self.resource = Some(resource);
+        true
+    }
+
+    fn reload_pmx_model_materials(&mut self, context: &Context, scene: &mut Scene) {
+        let (resource, pmx_model_id) = match (&self.resource, self.pmx_model_id) {
+            (Some(resource), Some(pmx_model_id)) => (resource, pmx_model_id),
+            _ => {
+                return;
+            }
+        };
+        let pmx_model_source = match resource.find::<PmxModelSource>(PMX_MODEL_NAME) {
+            Some(source) => source,
+            None => {
+                error!("the model `{}` is not found", PMX_MODEL_NAME);
+                return;
+            }
+        };
+
+        scene.with_proxy(|scene| {
+            let pmx_model_renderer = scene
+                .find_object_by_id_mut(pmx_model_id)
+                .unwrap()
+                .find_component_by_type_mut::<PmxModelRenderer>()
+                .unwrap();
+
+            if let Err(err) = pmx_model_renderer.model_mut().reload_materials(
+                resource,
+                pmx_model_source,
+                &context.gfx_ctx(),
+            ) {
+                error!("failed to reload the materials of the model: {}", err);
+            }
+        });
     }
 }
 
@@ -59,10 +181,9 @@ impl Driver for DriverImpl {
             .input_mut()
             .register_key("Space", PhysicalKey::Code(KeyCode::Space));
 
-        let resource = {
-            let bytes = std::fs::read("./assets/resources.res").unwrap();
-            load_resource_file(&bytes).unwrap()
-        };
+        context
+            .input_mut()
+            .register_key("F12", PhysicalKey::Code(KeyCode::F12));
 
         scene.with_proxy(|scene| {
             let camera_id = make_camera_object(
@@ -87,30 +208,6 @@ impl Driver for DriverImpl {
                 ),
             );
 
-            let pmx_model_id =
-                make_pmx_model_renderer(&resource, "YYB Hatsune Miku_NT_1.0ver", scene).unwrap();
-            self.pmx_model_id = Some(pmx_model_id);
-
-            let pmx_model_object = scene
-                .find_object_by_id_mut(self.pmx_model_id.unwrap())
-                .unwrap();
-            // let pmx_model_renderer = pmx_model_object
-            //     .find_component_by_type_mut::<PmxModelRenderer>()
-            //     .unwrap();
-
-            // let pmx_model = pmx_model_renderer.model_mut();
-
-            // pmx_model.set_morph("+EarPierce", 1f32);
-            // pmx_model.set_morph("+TKB", 1f32);
-            // pmx_model.set_morph("+Shoes", 1f32);
-            // pmx_model.set_morph("+Tebukuro1", 1f32);
-            // pmx_model.set_morph("+Tebukuro2", 1f32);
-            // pmx_model.set_morph("+Hat", 1f32);
-            // pmx_model.set_morph("+ShoulderVeil", 1f32);
-            // pmx_model.set_morph("+NeckBand", 1f32);
-            // pmx_model.set_morph("+Tights", 1f32);
-            // pmx_model.set_morph("+LegAccessory", 1f32);
-
             make_light_object(
                 Vec3::new(10.0, 20.0, 10.0),
                 LightKind::Directional {
@@ -126,11 +223,136 @@ impl Driver for DriverImpl {
                 scene,
             );
         });
+
+        let resource = match read_resource_file() {
+            Ok(resource) => resource,
+            Err(err) => {
+                error!("failed to read the resources: {}", err);
+                return;
+            }
+        };
+
+        // registered so that reloading the file can tell which resources have changed, and
+        // shared with the model
+        if let Err(err) = context.load_resources(&resource) {
+            error!("failed to load the resources: {}", err);
+            return;
+        }
+
+        self.pmx_model_id = scene.with_proxy(|scene| {
+            match make_pmx_model_renderer(&resource, PMX_MODEL_NAME, scene) {
+                Ok(pmx_model_id) => Some(pmx_model_id),
+                Err(err) => {
+                    error!("failed to make the model: {}", err);
+                    None
+                }
+            }
+        });
+
+        // let pmx_model_renderer = pmx_model_object
+        //     .find_component_by_type_mut::<PmxModelRenderer>()
+        //     .unwrap();
+
+        // let pmx_model = pmx_model_renderer.model_mut();
+
+        // pmx_model.set_morph("+EarPierce", 1f32);
+        // pmx_model.set_morph("+TKB", 1f32);
+        // pmx_model.set_morph("+Shoes", 1f32);
+        // pmx_model.set_morph("+Tebukuro1", 1f32);
+        // pmx_model.set_morph("+Tebukuro2", 1f32);
+        // pmx_model.set_morph("+Hat", 1f32);
+        // pmx_model.set_morph("+ShoulderVeil", 1f32);
+        // pmx_model.set_morph("+NeckBand", 1f32);
+        // pmx_model.set_morph("+Tights", 1f32);
+        // pmx_model.set_morph("+LegAccessory", 1f32);
+
+        self.resource = Some(resource);
+    }
+
+    fn on_resource_reloaded(
+        &mut self,
+        _context: &Context,
+        _window: &Window,
+        _scene: &mut Scene,
+        name: &str,
+    ) {
+        info!("reloaded `{}`", name);
+        // the materials are reloaded once, however many of their resources have changed
+        self.is_pmx_model_reload_pending = true;
+    }
+
+    fn on_device_recreated(&mut self, _context: &Context, _window: &Window, scene: &mut Scene) {
+        let (resource, pmx_model_id) = match (&self.resource, self.pmx_model_id) {
+            (Some(resource), Some(pmx_model_id)) => (resource, pmx_model_id),
+            _ => {
+                return;
+            }
+        };
+
+        // the model keeps its buffers and materials, so it is made again on the new device
+        self.pmx_model_id = scene.with_proxy(|scene| {
+            scene.remove_object(pmx_model_id);
+
+            match make_pmx_model_renderer(resource, PMX_MODEL_NAME, scene) {
+                Ok(pmx_model_id) => Some(pmx_model_id),
+                Err(err) => {
+                    error!("failed to make the model again: {}", err);
+                    None
+                }
+            }
+        });
+    }
+
+    fn on_before_update(&mut self, context: &Context, _window: &Window, scene: &mut Scene) {
+        if self.is_pmx_model_reload_pending {
+            self.is_pmx_model_reload_pending = false;
+            self.reload_pmx_model_materials(context, scene);
+        }
+
+        if let Some(modified_time) = self.resource_watch.poll() {
+            if self.reload_resources(context) {
+                self.resource_watch.accept(modified_time);
+            }
+        }
+    }
+
+    fn on_after_render(&mut self, context: &Context, _window: &Window, _scene: &mut Scene) {
+        let frame_capture = match context.take_frame_capture() {
+            Some(Ok(frame_capture)) => frame_capture,
+            Some(Err(err)) => {
+                error!("failed to capture the frame: {}", err);
+                return;
+            }
+            None => {
+                return;
+            }
+        };
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let path = format!("screenshot-{}.png", timestamp);
+
+        match image::save_buffer(
+            &path,
+            &frame_capture.pixels,
+            frame_capture.width,
+            frame_capture.height,
+            image::ColorType::Rgba8,
+        ) {
+            Ok(()) => info!("screenshot saved to {}", path),
+            Err(err) => error!("failed to save the screenshot: {}", err),
+        }
     }
 
     fn on_after_update(&mut self, context: &Context, _window: &Window, scene: &mut Scene) {
         let delta = context.time().delta_time().as_secs_f32();
 
+        if context.input().key("F12").unwrap().is_pressed_frame {
+            context.request_frame_capture();
+        }
+
         scene.with_proxy(|scene| {
             let angle_speed = f32::to_radians(60.0);
             let movement_speed = 10.0;
@@ -197,10 +419,15 @@ impl Driver for DriverImpl {
             scene.set_transform(self.camera_id.unwrap(), camera_transform);
         });
 
+        let pmx_model_id = match self.pmx_model_id {
+            Some(pmx_model_id) => pmx_model_id,
+            None => {
+                return;
+            }
+        };
+
         scene.with_proxy(|scene| {
-            let pmx_model_object = scene
-                .find_object_by_id_mut(self.pmx_model_id.unwrap())
-                .unwrap();
+            let pmx_model_object = scene.find_object_by_id_mut(pmx_model_id).unwrap();
             let pmx_model_renderer = pmx_model_object
                 .find_component_by_type_mut::<PmxModelRenderer>()
                 .unwrap();
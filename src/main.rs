@@ -2,12 +2,21 @@ mod driver_impl;
 mod object;
 
 use driver_impl::DriverImpl;
+use log::LevelFilter;
 use lvl_core::{
+    gfx::{DepthStencilFormat, FaceCulling, RenderConfig},
     launch_core,
     looper::{loop_window::LoopWindowConfig, LooperMode, TargetFps},
 };
+use wgpu::PresentMode;
 
 fn main() {
+    env_logger::Builder::from_env("LOG")
+        .filter_level(LevelFilter::Info)
+        .format_module_path(false)
+        .format_target(false)
+        .init();
+
     let window_config = LoopWindowConfig {
         title: "Level Editor".to_owned(),
         width: 800,
@@ -19,10 +28,16 @@ fn main() {
 
     launch_core(
         window_config,
-        false,
-        4,
+        PresentMode::AutoNoVsync,
+        RenderConfig {
+            msaa_sample_count: 4,
+            depth_stencil_format: DepthStencilFormat::Depth32Float,
+            hdr: false,
+            face_culling: FaceCulling::default(),
+        },
         looper_mode,
         target_fps,
+        false,
         Some(Box::new(DriverImpl::new())),
     );
 }
@@ -1,5 +1,5 @@
 use lvl_core::{
-    gfx::elements::{MaterialPropertyValue, PmxModel},
+    gfx::elements::{MaterialPropertyValue, PmxModel, PmxModelError},
     scene::{
         components::{
             Camera, CameraClearMode, CameraProjectionMode, Light, LightKind, PmxModelRenderer,
@@ -9,6 +9,15 @@ use lvl_core::{
 };
 use lvl_math::{Vec3, Vec4};
 use lvl_resource::{PmxModelSource, ResourceFile};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum SpawnError {
+    #[error("the model `{0}` is not found")]
+    ModelNotFound(String),
+    #[error("failed to load the model: {0}")]
+    PmxModel(#[from] PmxModelError),
+}
 
 pub fn make_camera_object(
     order: i64,
@@ -28,6 +37,7 @@ pub fn make_camera_object(
                 near: 0.1,
                 far: 100.0,
             },
+            exposure: 1.0,
         },
     );
 
@@ -38,28 +48,27 @@ pub fn make_pmx_model_renderer(
     resource: &ResourceFile,
     name: &str,
     scene: &mut SceneProxy,
-) -> Option<ObjectId> {
-    let pmx_model_source = resource.find::<PmxModelSource>(name)?;
-    let mut pmx_model =
-        PmxModel::load_from_source(resource, pmx_model_source, scene.context().gfx_ctx());
+) -> Result<ObjectId, SpawnError> {
+    let pmx_model_source = resource
+        .find::<PmxModelSource>(name)
+        .ok_or_else(|| SpawnError::ModelNotFound(name.to_owned()))?;
+    let context = scene.context();
+    let mut pmx_model = PmxModel::load_with_registry(
+        resource,
+        pmx_model_source,
+        &mut context.resource_registry_mut(),
+        &context.gfx_ctx(),
+    )?;
 
     for element in pmx_model.elements_mut() {
         element
             .material
             .set_property("light_smooth", MaterialPropertyValue::Float(0.1));
-        element.material.set_property(
-            "light_color",
-            MaterialPropertyValue::Vec3(Vec3::new(1.0, 1.0, 1.0)),
-        );
-        element.material.set_property(
-            "light_direction",
-            MaterialPropertyValue::Vec3(Vec3::new(1.0, -1.0, -1.0).normalized()),
-        );
     }
 
     let id = scene.create_object();
     scene.add_component(id, PmxModelRenderer::new(pmx_model));
-    Some(id)
+    Ok(id)
 }
 
 pub fn make_light_object(